@@ -2,15 +2,58 @@ extern crate proc_macro;
 use proc_macro::TokenStream;
 use proc_macro2::TokenStream as TokenStream2;
 use quote::{format_ident, quote, ToTokens, TokenStreamExt};
-use syn::{parse_macro_input, punctuated::Punctuated, ItemStruct, Lit, MetaNameValue, Token};
+use syn::{
+    parse_macro_input, punctuated::Punctuated, Data, DeriveInput, Fields, GenericArgument,
+    ItemStruct, Lit, Meta, MetaList, MetaNameValue, NestedMeta, PathArguments, Token, Type,
+};
 
 /// Create necessary handler, validator and meta functions for foreign data wrapper
 ///
-/// This macro will create three functions which can be used in Postgres.
+/// This macro will create six functions which can be used in Postgres.
 ///
 /// 1. `<snake_case_fdw_name>_fdw_handler()` - foreign data wrapper handler function
 /// 2. `<snake_case_fdw_name>_fdw_validator()` - foreign data wrapper validator function
 /// 3. `<snake_case_fdw_name>_fdw_meta()` - function to return a table contains fdw metadata
+/// 4. `<snake_case_fdw_name>_fdw_health_check(server_oid)` - function to check a foreign server
+///    health by `ForeignDataWrapper::health_check()`
+/// 5. `<snake_case_fdw_name>_fdw_capabilities()` - function to return a table contains fdw
+///    capabilities by `ForeignDataWrapper::capabilities()`
+/// 6. `<snake_case_fdw_name>_fdw_options()` - function to return a table contains the options
+///    declared in the options schema, it is empty if the schema isn't declared
+///
+/// The optional `options` attribute declares the options schema, the options
+/// of each level (`server`, `user_mapping`, `table` or `column`) and their
+/// flags:
+///
+/// - `required`: the option must be specified
+/// - `secret`: the option can also be the secret referenced by `<name>_id` or
+///   `<name>_name`, and it can be in the user mapping instead of the server
+/// - `example = "..."`: an example value, shown in the `_options()` function
+///
+/// The generated validator checks the options against the schema before
+/// calling `ForeignDataWrapper::validator()`, a warning is reported for each
+/// unknown option.
+///
+/// The handler checks if the FDW is built for an interface version supported
+/// by `supabase_wrappers`, so a version mismatch between them fails with a
+/// clear error.
+///
+/// The optional `interface` attribute is the version of the `ForeignDataWrapper`
+/// trait implemented by the FDW, `v1` (default) or `v2`. A v2 FDW implements
+/// `supabase_wrappers::interface::v2::ForeignDataWrapper` and is run by
+/// `supabase_wrappers::interface::v2::V1Adapter`.
+///
+/// The optional `smoke_sql` attribute generates a `#[pg_test]` function
+/// `<snake_case_fdw_name>_smoke_test()` in a `__<snake_case_fdw_name>_smoke_tests`
+/// module, which creates the foreign data wrapper `<snake_case_fdw_name>_wrapper`
+/// with the handler and validator, checks the meta and capabilities functions,
+/// and then runs the SQL statements, like creating a server and a foreign table
+/// against a mock server and scanning it. It can be used along with the FDW's
+/// own `tests` module.
+///
+/// The optional `import_schema` attribute makes `IMPORT FOREIGN SCHEMA` import
+/// the tables listed by `ListTables::list_tables()`, which must be implemented
+/// by the FDW, instead of `ForeignDataWrapper::import_foreign_schema()`.
 ///
 /// # Example
 ///
@@ -20,7 +63,11 @@ use syn::{parse_macro_input, punctuated::Punctuated, ItemStruct, Lit, MetaNameVa
 /// #[wrappers_fdw(
 ///     version = "0.1.0",
 ///     author = "Supabase",
-///     website = "https://github.com/supabase/wrappers/tree/main/wrappers/src/fdw/helloworld_fdw"
+///     website = "https://github.com/supabase/wrappers/tree/main/wrappers/src/fdw/helloworld_fdw",
+///     options(
+///         server(api_url, api_key(secret)),
+///         table(object(required)),
+///     )
 /// )]
 /// pub struct HelloWorldFdw;
 /// ```
@@ -35,23 +82,51 @@ use syn::{parse_macro_input, punctuated::Punctuated, ItemStruct, Lit, MetaNameVa
 ///   validator hello_world_fdw_validator;
 ///
 /// select * from hello_world_fdw_meta();
+/// select * from hello_world_fdw_health_check('my_server'::regserver);
+/// select * from hello_world_fdw_capabilities();
 /// ```
 #[proc_macro_attribute]
 pub fn wrappers_fdw(attr: TokenStream, item: TokenStream) -> TokenStream {
     let mut metas = TokenStream2::new();
-    let meta_attrs: Punctuated<MetaNameValue, Token![,]> =
+    let meta_attrs: Punctuated<NestedMeta, Token![,]> =
         parse_macro_input!(attr with Punctuated::parse_terminated);
     let mut error_type: Option<String> = None;
+    let mut options_schema: Option<TokenStream2> = None;
+    let mut import_schema = false;
+    let mut smoke_sql: Option<String> = None;
+    let mut interface: Option<String> = None;
     for attr in meta_attrs {
-        let name = format!("{}", attr.path.segments.first().unwrap().ident);
-        if let Lit::Str(val) = attr.lit {
-            let value = val.value();
-            if name == "version" || name == "author" || name == "website" {
-                metas.append_all(quote! {
-                    meta.insert(#name.to_owned(), #value.to_owned());
-                });
-            } else if name == "error_type" {
-                error_type = Some(value);
+        match attr {
+            NestedMeta::Meta(Meta::NameValue(attr)) => {
+                let name = format!("{}", attr.path.segments.first().unwrap().ident);
+                if let Lit::Str(val) = attr.lit {
+                    let value = val.value();
+                    if name == "version" || name == "author" || name == "website" {
+                        metas.append_all(quote! {
+                            meta.insert(#name.to_owned(), #value.to_owned());
+                        });
+                    } else if name == "error_type" {
+                        error_type = Some(value);
+                    } else if name == "smoke_sql" {
+                        smoke_sql = Some(value);
+                    } else if name == "interface" {
+                        interface = Some(value);
+                    }
+                }
+            }
+            NestedMeta::Meta(Meta::Path(path)) if path.is_ident("import_schema") => {
+                import_schema = true;
+            }
+            NestedMeta::Meta(Meta::List(list)) if list.path.is_ident("options") => {
+                match parse_options_schema(list) {
+                    Ok(schema) => options_schema = Some(schema),
+                    Err(err) => return err.to_compile_error().into(),
+                }
+            }
+            other => {
+                return syn::Error::new_spanned(other, "unknown `wrappers_fdw` attribute")
+                    .to_compile_error()
+                    .into()
             }
         }
     }
@@ -76,6 +151,83 @@ pub fn wrappers_fdw(attr: TokenStream, item: TokenStream) -> TokenStream {
     let fn_validator_ident = format_ident!("{}_validator", ident_snake);
     let fn_meta_ident = format_ident!("{}_meta", ident_snake);
     let fn_get_meta_ident = format_ident!("{}_get_meta", ident_snake);
+    let fn_health_check_ident = format_ident!("{}_health_check", ident_snake);
+    let fn_capabilities_ident = format_ident!("{}_capabilities", ident_snake);
+    let fn_options_ident = format_ident!("{}_options", ident_snake);
+
+    // the type implementing the v1 trait, a v2 FDW is run by the adapter
+    let fdw_type = match interface.as_deref() {
+        None | Some("v1") => quote! { #ident },
+        Some("v2") => quote! { supabase_wrappers::interface::v2::V1Adapter<#ident> },
+        Some(_) => {
+            let quoted = quote! {
+                compile_error!("`interface` in the `wrappers_fdw` attribute must be v1 or v2");
+            };
+            return quoted.into();
+        }
+    };
+
+    let (fdw_routine, import_capability) = if import_schema {
+        (
+            quote! {
+                <#fdw_type as ListTables<super::#error_type_ident>>::fdw_routine_with_import()
+            },
+            quote! { caps.import_schema = true; },
+        )
+    } else {
+        (
+            quote! { <#fdw_type as ForeignDataWrapper<super::#error_type_ident>>::fdw_routine() },
+            TokenStream2::new(),
+        )
+    };
+
+    // the smoke test creates the foreign data wrapper and runs the SQL, it is
+    // in the `tests` schema as required by pg_test, which is nested in a module
+    // named after the FDW to not collide with the FDW's own `tests` module
+    let smoke_test = match smoke_sql {
+        Some(sql) => {
+            let mod_smoke_tests_ident = format_ident!("__{}_smoke_tests", ident_snake);
+            let fn_smoke_test_ident = format_ident!("{}_smoke_test", ident_snake);
+            let create_fdw = format!(
+                "create foreign data wrapper {}_wrapper handler {} validator {}",
+                ident_snake, fn_ident, fn_validator_ident
+            );
+            let check_funcs = format!(
+                "select count(*) from {}() m, {}() c",
+                fn_meta_ident, fn_capabilities_ident
+            );
+            quote! {
+                #[cfg(any(test, feature = "pg_test"))]
+                mod #mod_smoke_tests_ident {
+                    #[pgrx::pg_schema]
+                    mod tests {
+                        use pgrx::prelude::*;
+
+                        #[pg_test]
+                        fn #fn_smoke_test_ident() {
+                            Spi::connect(|mut c| {
+                                c.update(#create_fdw, None, None).unwrap();
+                                let cnt = c
+                                    .select(#check_funcs, None, None)
+                                    .unwrap()
+                                    .first()
+                                    .get_one::<i64>()
+                                    .unwrap();
+                                assert_eq!(cnt, Some(1));
+                                c.update(#sql, None, None).unwrap();
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        None => TokenStream2::new(),
+    };
+
+    let options_schema = match options_schema {
+        Some(schema) => quote! { Some(&[#schema]) },
+        None => quote! { None },
+    };
 
     let quoted = quote! {
         #item_tokens
@@ -87,14 +239,24 @@ pub fn wrappers_fdw(attr: TokenStream, item: TokenStream) -> TokenStream {
             use pgrx::prelude::*;
             use supabase_wrappers::prelude::*;
 
+            const OPTIONS_SCHEMA: Option<&[OptionSpec]> = #options_schema;
+
+            // the interface version of supabase-wrappers the FDW is compiled
+            // against, it is embedded at compile time
+            const FDW_INTERFACE_VERSION: u32 = supabase_wrappers::interface::INTERFACE_VERSION;
+
             #[pg_extern(create_or_replace)]
             fn #fn_ident() -> supabase_wrappers::FdwRoutine {
-                #ident::fdw_routine()
+                check_interface_version(#ident_str, FDW_INTERFACE_VERSION);
+                #fdw_routine
             }
 
             #[pg_extern(create_or_replace)]
             fn #fn_validator_ident(options: Vec<Option<String>>, catalog: Option<pg_sys::Oid>) {
-                #ident::validator(options, catalog)
+                if let Some(schema) = OPTIONS_SCHEMA {
+                    validate_options_schema(&options, catalog, schema).unwrap_or_report();
+                }
+                <#fdw_type as ForeignDataWrapper<super::#error_type_ident>>::validator(options, catalog)
                     .map_err(|e| <super::#error_type_ident as Into<ErrorReport>>::into(e))
                     .unwrap_or_report();
             }
@@ -121,13 +283,375 @@ pub fn wrappers_fdw(attr: TokenStream, item: TokenStream) -> TokenStream {
                     meta.get("website").map(|s| s.to_owned()),
                 )].into_iter())
             }
+
+            #[pg_extern(create_or_replace)]
+            fn #fn_options_ident() -> TableIterator<'static, (
+                name!(name, String),
+                name!(level, String),
+                name!(required, bool),
+                name!(secret, bool),
+                name!(example, Option<String>)
+            )> {
+                let specs = OPTIONS_SCHEMA.unwrap_or_default();
+
+                TableIterator::new(specs.iter().map(|spec| (
+                    spec.name.to_owned(),
+                    spec.level.name().to_owned(),
+                    spec.required,
+                    spec.secret,
+                    spec.example.map(|s| s.to_owned()),
+                )))
+            }
+
+            #[pg_extern(create_or_replace)]
+            fn #fn_health_check_ident(server_oid: pg_sys::Oid) -> TableIterator<'static, (
+                name!(status, String),
+                name!(latency_ms, f64),
+                name!(version, Option<String>),
+                name!(message, Option<String>)
+            )> {
+                let check = check_server_health::<super::#error_type_ident, #fdw_type>(server_oid);
+
+                TableIterator::once((
+                    check.status,
+                    check.latency.as_secs_f64() * 1000.0,
+                    check.version,
+                    check.message,
+                ))
+            }
+
+            #[pg_extern(create_or_replace)]
+            fn #fn_capabilities_ident() -> TableIterator<'static, (
+                name!(read, bool),
+                name!(write, bool),
+                name!(import_schema, bool),
+                name!(qual_pushdown, bool),
+                name!(sort_pushdown, bool),
+                name!(limit_pushdown, bool),
+                name!(aggregate_pushdown, bool),
+                name!(join_pushdown, bool)
+            )> {
+                #[allow(unused_mut)]
+                let mut caps = <#fdw_type as ForeignDataWrapper<super::#error_type_ident>>::capabilities();
+                #import_capability
+
+                TableIterator::once((
+                    caps.read,
+                    caps.write,
+                    caps.import_schema,
+                    caps.qual_pushdown,
+                    caps.sort_pushdown,
+                    caps.limit_pushdown,
+                    caps.aggregate_pushdown,
+                    caps.join_pushdown,
+                ))
+            }
+        }
+
+        #smoke_test
+    };
+
+    quoted.into()
+}
+
+// parse the options schema in `wrappers_fdw` attribute, for example,
+//
+// options(
+//     server(api_url(example = "https://api.example.com"), api_key(secret)),
+//     table(object(required)),
+// )
+fn parse_options_schema(list: MetaList) -> syn::Result<TokenStream2> {
+    let mut specs = TokenStream2::new();
+    for level in list.nested {
+        let NestedMeta::Meta(Meta::List(level)) = level else {
+            return Err(syn::Error::new_spanned(
+                level,
+                "expected `<level>(<options>)`",
+            ));
+        };
+        let level_ident = match level.path.get_ident().map(|i| i.to_string()).as_deref() {
+            Some("server") => format_ident!("Server"),
+            Some("user_mapping") => format_ident!("UserMapping"),
+            Some("table") => format_ident!("Table"),
+            Some("column") => format_ident!("Column"),
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    level.path,
+                    "option level must be server, user_mapping, table or column",
+                ))
+            }
+        };
+        for opt in level.nested {
+            let (path, flags) = match opt {
+                NestedMeta::Meta(Meta::Path(path)) => (path, Punctuated::new()),
+                NestedMeta::Meta(Meta::List(opt)) => (opt.path, opt.nested),
+                other => {
+                    return Err(syn::Error::new_spanned(
+                        other,
+                        "expected `<name>` or `<name>(required, secret, example = \"...\")`",
+                    ))
+                }
+            };
+            let Some(name) = path.get_ident().map(|i| i.to_string()) else {
+                return Err(syn::Error::new_spanned(path, "expected option name"));
+            };
+            let (mut required, mut secret) = (false, false);
+            let mut example = quote! { None };
+            for flag in flags {
+                match flag {
+                    NestedMeta::Meta(Meta::Path(p)) if p.is_ident("required") => required = true,
+                    NestedMeta::Meta(Meta::Path(p)) if p.is_ident("secret") => secret = true,
+                    NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                        path,
+                        lit: Lit::Str(val),
+                        ..
+                    })) if path.is_ident("example") => {
+                        let val = val.value();
+                        example = quote! { Some(#val) };
+                    }
+                    other => {
+                        return Err(syn::Error::new_spanned(
+                            other,
+                            "expected `required`, `secret` or `example = \"...\"`",
+                        ))
+                    }
+                }
+            }
+            specs.append_all(quote! {
+                OptionSpec {
+                    name: #name,
+                    level: OptionLevel::#level_ident,
+                    required: #required,
+                    secret: #secret,
+                    example: #example,
+                },
+            });
         }
+    }
+    Ok(specs)
+}
 
+/// Derive `FdwOptions` to parse the options into a struct
+///
+/// Each field is parsed from the option of the same name by its `FromStr`
+/// implementation. The option is required unless the field is an `Option` or
+/// has a default value. The `option` attribute customizes a field:
+///
+/// - `name = "..."`: the option name, default is the field name
+/// - `default = "..."`: the default value if the option is not specified
+/// - `secret`: the value can also be the secret referenced by option
+///   `<name>_id` or `<name>_name`, read from the provider set by option
+///   `secret_provider`
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use supabase_wrappers::prelude::*;
+///
+/// #[derive(FdwOptions)]
+/// struct ServerOptions {
+///     #[option(default = "https://api.example.com/v1/")]
+///     api_url: url::Url,
+///     #[option(secret)]
+///     api_key: String,
+///     #[option(name = "max_rows")]
+///     limit: Option<u64>,
+/// }
+/// ```
+///
+/// then it can be used in the foreign data wrapper,
+///
+/// ```rust,ignore
+/// fn new(server: ForeignServer) -> Result<Self, MyFdwError> {
+///     let opts = ServerOptions::from_server(&server)?;
+///     ...
+/// }
+///
+/// fn validator(options: Vec<Option<String>>, catalog: Option<pg_sys::Oid>) -> Result<(), MyFdwError> {
+///     if catalog == Some(FOREIGN_SERVER_RELATION_ID) {
+///         ServerOptions::validate(&options)?;
+///     }
+///     Ok(())
+/// }
+/// ```
+#[proc_macro_derive(FdwOptions, attributes(option))]
+pub fn derive_fdw_options(item: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(item as DeriveInput);
+    let ident = input.ident;
+    let fields = match input.data {
+        Data::Struct(data) => match data.fields {
+            Fields::Named(fields) => fields.named,
+            _ => {
+                return syn::Error::new_spanned(ident, "FdwOptions requires named fields")
+                    .to_compile_error()
+                    .into()
+            }
+        },
+        _ => {
+            return syn::Error::new_spanned(ident, "FdwOptions can only be derived for structs")
+                .to_compile_error()
+                .into()
+        }
+    };
+
+    let mut parses = TokenStream2::new();
+    let mut checks = TokenStream2::new();
+    let mut field_idents = Vec::new();
+    for field in fields {
+        let field_ident = field.ident.clone().unwrap();
+        let attrs = match FieldOptionAttrs::parse(&field) {
+            Ok(attrs) => attrs,
+            Err(err) => return err.to_compile_error().into(),
+        };
+        let name = attrs.name.unwrap_or_else(|| field_ident.to_string());
+        let (ty, optional) = match option_inner_type(&field.ty) {
+            Some(inner) => (inner, true),
+            None => (&field.ty, false),
+        };
+
+        let fetch = if attrs.secret {
+            quote! { secret_option(#name, options)? }
+        } else {
+            quote! { options.get(#name).cloned() }
+        };
+        let value = match &attrs.default {
+            Some(default) => quote! { #fetch.or_else(|| Some(#default.to_owned())) },
+            None => fetch,
+        };
+        let required = !optional && attrs.default.is_none();
+        let parse = if required {
+            let missing = if attrs.secret {
+                format!("{name} or {name}_id")
+            } else {
+                name.clone()
+            };
+            quote! {
+                let value = value.ok_or_else(|| OptionsError::OptionNameNotFound(#missing.to_owned()))?;
+                parse_option_value::<#ty>(#name, &value)?
+            }
+        } else if optional {
+            quote! {
+                value.map(|value| parse_option_value::<#ty>(#name, &value)).transpose()?
+            }
+        } else {
+            quote! {
+                parse_option_value::<#ty>(#name, &value.unwrap_or_default())?
+            }
+        };
+        parses.append_all(quote! {
+            let #field_ident = {
+                let value: Option<String> = #value;
+                #parse
+            };
+        });
+
+        // the secret options can be in the user mapping, so they are not
+        // required in the validator
+        checks.append_all(if required && !attrs.secret {
+            quote! {
+                match options.get(#name) {
+                    Some(value) => {
+                        parse_option_value::<#ty>(#name, value)?;
+                    }
+                    None => return Err(OptionsError::OptionNameNotFound(#name.to_owned())),
+                }
+            }
+        } else {
+            quote! {
+                if let Some(value) = options.get(#name) {
+                    parse_option_value::<#ty>(#name, value)?;
+                }
+            }
+        });
+        field_idents.push(field_ident);
+    }
+
+    let quoted = quote! {
+        impl supabase_wrappers::options::FdwOptions for #ident {
+            fn from_options(
+                options: &std::collections::HashMap<String, String>,
+            ) -> Result<Self, supabase_wrappers::options::OptionsError> {
+                use supabase_wrappers::options::*;
+                #parses
+                Ok(Self { #(#field_idents),* })
+            }
+
+            fn validate(
+                opt_list: &[Option<String>],
+            ) -> Result<(), supabase_wrappers::options::OptionsError> {
+                use supabase_wrappers::options::*;
+                let options = options_from_list(opt_list);
+                #checks
+                Ok(())
+            }
+        }
     };
 
     quoted.into()
 }
 
+// the `option` attribute of a field
+#[derive(Default)]
+struct FieldOptionAttrs {
+    name: Option<String>,
+    default: Option<String>,
+    secret: bool,
+}
+
+impl FieldOptionAttrs {
+    fn parse(field: &syn::Field) -> syn::Result<Self> {
+        let mut ret = Self::default();
+        for attr in field.attrs.iter().filter(|a| a.path.is_ident("option")) {
+            let Meta::List(list) = attr.parse_meta()? else {
+                return Err(syn::Error::new_spanned(attr, "expected #[option(...)]"));
+            };
+            for nested in list.nested {
+                match nested {
+                    NestedMeta::Meta(Meta::Path(path)) if path.is_ident("secret") => {
+                        ret.secret = true;
+                    }
+                    NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                        path,
+                        lit: Lit::Str(val),
+                        ..
+                    })) if path.is_ident("name") || path.is_ident("default") => {
+                        if path.is_ident("name") {
+                            ret.name = Some(val.value());
+                        } else {
+                            ret.default = Some(val.value());
+                        }
+                    }
+                    other => {
+                        return Err(syn::Error::new_spanned(
+                            other,
+                            "expected `name = \"...\"`, `default = \"...\"` or `secret`",
+                        ))
+                    }
+                }
+            }
+        }
+        Ok(ret)
+    }
+}
+
+// get `T` if the type is `Option<T>`
+fn option_inner_type(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let seg = path.path.segments.last()?;
+    if seg.ident != "Option" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &seg.arguments else {
+        return None;
+    };
+    match args.args.first()? {
+        GenericArgument::Type(inner) => Some(inner),
+        _ => None,
+    }
+}
+
 fn to_snake_case(s: &str) -> String {
     let mut acc = String::new();
     let mut prev = '_';
@@ -0,0 +1,145 @@
+use crate::interface::{Aggregate, AggregateKind, AggregatePlan, Column};
+use crate::utils::{column_type_oid, get_column_options};
+use pgrx::{is_a, list::PgList, pg_sys};
+use std::ffi::CStr;
+use std::os::raw::c_char;
+
+// extract aggregate from an Aggref node, only simple aggregates on a column
+// of the base relation or count(*) are supported
+unsafe fn extract_aggregate(
+    aggref: *mut pg_sys::Aggref,
+    baserel_id: pg_sys::Oid,
+    baserel_relid: pg_sys::Index,
+) -> Option<Aggregate> {
+    if (*aggref).agglevelsup != 0
+        || (*aggref).aggkind != b'n' as c_char
+        || (*aggref).aggsplit != pg_sys::AggSplit::AGGSPLIT_SIMPLE
+        || !(*aggref).aggdistinct.is_null()
+        || !(*aggref).aggorder.is_null()
+        || !(*aggref).aggfilter.is_null()
+    {
+        return None;
+    }
+
+    // only built-in aggregate functions are supported
+    if pg_sys::get_func_namespace((*aggref).aggfnoid)
+        != pg_sys::Oid::from(pg_sys::PG_CATALOG_NAMESPACE)
+    {
+        return None;
+    }
+    let func_name = pg_sys::get_func_name((*aggref).aggfnoid);
+    if func_name.is_null() {
+        return None;
+    }
+    let kind = match CStr::from_ptr(func_name).to_str().ok()? {
+        "count" => AggregateKind::Count,
+        "min" => AggregateKind::Min,
+        "max" => AggregateKind::Max,
+        "sum" => AggregateKind::Sum,
+        _ => return None,
+    };
+
+    // count(*) has no argument, others must have a column as argument
+    let args = PgList::<pg_sys::TargetEntry>::from_pg((*aggref).args);
+    let field = if (*aggref).aggstar {
+        if kind != AggregateKind::Count {
+            return None;
+        }
+        None
+    } else {
+        if args.len() != 1 {
+            return None;
+        }
+        let var = (*args.head()?).expr as *mut pg_sys::Var;
+        if !is_a(var as _, pg_sys::NodeTag::T_Var)
+            || (*var).varno as pg_sys::Index != baserel_relid
+            || (*var).varlevelsup != 0
+            || (*var).varattno < 1
+        {
+            return None;
+        }
+        let attname = pg_sys::get_attname(baserel_id, (*var).varattno, false);
+        Some(CStr::from_ptr(attname).to_str().ok()?.to_string())
+    };
+
+    Some(Aggregate {
+        kind,
+        field,
+        type_oid: (*aggref).aggtype,
+    })
+}
+
+// extract grouping column from a Var node, it must be a column of the base
+// relation referenced by the GROUP BY clause
+unsafe fn extract_group_column(
+    var: *mut pg_sys::Var,
+    sortgroupref: pg_sys::Index,
+    group_clause: *mut pg_sys::List,
+    baserel_id: pg_sys::Oid,
+    baserel_relid: pg_sys::Index,
+) -> Option<Column> {
+    if sortgroupref == 0
+        || pg_sys::get_sortgroupref_clause_noerr(sortgroupref, group_clause).is_null()
+        || (*var).varno as pg_sys::Index != baserel_relid
+        || (*var).varlevelsup != 0
+        || (*var).varattno < 1
+    {
+        return None;
+    }
+    let attname = pg_sys::get_attname(baserel_id, (*var).varattno, false);
+    Some(Column {
+        name: CStr::from_ptr(attname).to_str().ok()?.to_string(),
+        num: (*var).varattno as usize,
+        type_oid: column_type_oid((*var).vartype),
+        options: get_column_options(baserel_id, (*var).varattno),
+    })
+}
+
+// extract aggregate plan from the grouping target, all target expressions
+// must be grouping columns or supported aggregates, otherwise returns None
+pub(crate) unsafe fn extract_aggregate_plan(
+    root: *mut pg_sys::PlannerInfo,
+    baserel: *mut pg_sys::RelOptInfo,
+    baserel_id: pg_sys::Oid,
+    target: *mut pg_sys::PathTarget,
+) -> Option<AggregatePlan> {
+    let parse = (*root).parse;
+
+    // grouping sets and HAVING are not supported
+    if (!(*parse).hasAggs && (*parse).groupClause.is_null())
+        || !(*parse).groupingSets.is_null()
+        || !(*parse).havingQual.is_null()
+    {
+        return None;
+    }
+
+    let exprs = PgList::<pg_sys::Node>::from_pg((*target).exprs);
+    if exprs.is_empty() {
+        return None;
+    }
+
+    let mut plan = AggregatePlan::default();
+    for (idx, expr) in exprs.iter_ptr().enumerate() {
+        if is_a(expr, pg_sys::NodeTag::T_Aggref) {
+            let aggregate = extract_aggregate(expr as _, baserel_id, (*baserel).relid)?;
+            plan.aggregates.push(aggregate);
+        } else if is_a(expr, pg_sys::NodeTag::T_Var) {
+            if (*target).sortgrouprefs.is_null() {
+                return None;
+            }
+            let sortgroupref = *(*target).sortgrouprefs.add(idx);
+            let column = extract_group_column(
+                expr as _,
+                sortgroupref,
+                (*parse).groupClause,
+                baserel_id,
+                (*baserel).relid,
+            )?;
+            plan.group_by.push(column);
+        } else {
+            return None;
+        }
+    }
+
+    Some(plan)
+}
@@ -0,0 +1,172 @@
+//! Audit log of the remote requests
+//!
+//! If `wrappers.audit_log` is on, each remote request sent by the FDWs on
+//! behalf of the session is written to the Postgres log as a JSON record, so
+//! it can be collected by the log pipeline for compliance review. For example,
+//!
+//! ```text
+//! LOG:  wrappers audit: {"timestamp":"2024-06-20 10:00:00.123+00","user":"postgres","pid":1234,"server":"stripe_server","kind":"request","operation":"GET https://api.stripe.com/v1/customers","rows":null,"duration_ms":212.3,"error":null}
+//! ```
+//!
+//! There are two kinds of records:
+//!
+//! - `request`: a remote request sent by the FDW, the operation is the HTTP
+//!   method and URL, or the SQL or command sent to the remote database
+//! - `scan`: a foreign table scan, recorded by the framework when the scan
+//!   ends, with the number of rows fetched from the remote server
+//!
+//! The secrets in the records are redacted, see [`redact`](crate::redact).
+//! FDWs record their remote requests by [`audit_request`] or
+//! [`audit_remote_request`].
+
+use crate::guc;
+use crate::redact::redact;
+use crate::trace::{flush_traces, trace, trace_enabled};
+use pgrx::pg_sys;
+use serde_json::json;
+use std::ffi::CStr;
+use std::time::Duration;
+
+/// Kind of an audit record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditKind {
+    Request,
+    Scan,
+}
+
+impl AuditKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            AuditKind::Request => "request",
+            AuditKind::Scan => "scan",
+        }
+    }
+}
+
+/// Check if the remote requests should be audited, by `wrappers.audit_log`
+pub fn audit_enabled() -> bool {
+    guc::audit_log()
+}
+
+fn cstr_to_string(s: *const std::ffi::c_char) -> Option<String> {
+    (!s.is_null()).then(|| unsafe { CStr::from_ptr(s) }.to_string_lossy().into_owned())
+}
+
+fn server_name(server_oid: pg_sys::Oid) -> Option<String> {
+    unsafe {
+        let fserver =
+            pg_sys::GetForeignServerExtended(server_oid, pg_sys::FSV_MISSING_OK as pg_sys::bits16);
+        if fserver.is_null() {
+            return None;
+        }
+        cstr_to_string((*fserver).servername)
+    }
+}
+
+/// Write an audit record to the Postgres log if `wrappers.audit_log` is on
+///
+/// The `rows` is the number of rows returned by the remote server, if it is
+/// known, and the `error` is the error message if the request failed.
+pub fn audit_record(
+    server_oid: pg_sys::Oid,
+    kind: AuditKind,
+    operation: &str,
+    rows: Option<i64>,
+    duration: Duration,
+    error: Option<&str>,
+) {
+    if !audit_enabled() {
+        return;
+    }
+    let (timestamp, user, pid) = unsafe {
+        (
+            cstr_to_string(pg_sys::timestamptz_to_str(pg_sys::GetCurrentTimestamp())),
+            cstr_to_string(pg_sys::GetUserNameFromId(pg_sys::GetUserId(), true)),
+            pg_sys::MyProcPid,
+        )
+    };
+    let record = json!({
+        "timestamp": timestamp,
+        "user": user,
+        "pid": pid,
+        "server": server_name(server_oid),
+        "kind": kind.as_str(),
+        "operation": operation,
+        "rows": rows,
+        "duration_ms": duration.as_secs_f64() * 1000.0,
+        "error": error,
+    });
+    pgrx::log!("wrappers audit: {}", redact(&record.to_string()));
+}
+
+/// Write an audit record of a remote request, see [`audit_record`]
+pub fn audit_remote_request(
+    server_oid: pg_sys::Oid,
+    operation: &str,
+    rows: Option<i64>,
+    duration: Duration,
+    error: Option<&str>,
+) {
+    audit_record(
+        server_oid,
+        AuditKind::Request,
+        operation,
+        rows,
+        duration,
+        error,
+    );
+}
+
+/// Send a remote request by `request` and write its audit record, the number
+/// of rows in the result is got by `rows`
+///
+/// The request is also traced if `wrappers.debug_level` is set, see
+/// [`trace`](crate::trace).
+///
+/// For example,
+///
+/// ```rust,no_run
+/// # use supabase_wrappers::prelude::*;
+/// # use pgrx::pg_sys;
+/// # fn query(sql: &str) -> Result<Vec<Row>, FdwError> { Ok(Vec::new()) }
+/// # fn run(server_oid: pg_sys::Oid) -> Result<(), FdwError> {
+/// let sql = "select * from users";
+/// let rows = audit_request(server_oid, sql, || query(sql), |rows| Some(rows.len() as i64))?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn audit_request<T, E: std::fmt::Display>(
+    server_oid: pg_sys::Oid,
+    operation: &str,
+    request: impl FnOnce() -> Result<T, E>,
+    rows: impl FnOnce(&T) -> Option<i64>,
+) -> Result<T, E> {
+    let tracing = trace_enabled(1);
+    if !audit_enabled() && !tracing {
+        return request();
+    }
+    if tracing {
+        trace(format!("> {}", operation));
+        flush_traces();
+    }
+    let started_at = std::time::Instant::now();
+    let result = request();
+    let duration = started_at.elapsed();
+    let (rows, error) = match &result {
+        Ok(value) => (rows(value), None),
+        Err(err) => (None, Some(err.to_string())),
+    };
+    if tracing {
+        match &error {
+            Some(err) => trace(format!("< error in {} ms: {}", duration.as_millis(), err)),
+            None => trace(format!(
+                "< {} rows in {} ms",
+                rows.map_or("unknown".to_string(), |rows| rows.to_string()),
+                duration.as_millis()
+            )),
+        }
+        flush_traces();
+    }
+    audit_remote_request(server_oid, operation, rows, duration, error.as_deref());
+    result
+}
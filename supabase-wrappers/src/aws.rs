@@ -0,0 +1,602 @@
+//! AWS credentials and request signing
+//!
+//! [`AwsCredentialsProvider`] resolves the AWS credentials of a foreign server
+//! from the sources below, the first one found is used:
+//!
+//! 1. static keys in options `aws_access_key_id` and `aws_secret_access_key`
+//! 2. keys in Vault, referenced by the secret ids in options
+//!    `vault_access_key_id` and `vault_secret_access_key`
+//! 3. environment variables `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and
+//!    `AWS_SESSION_TOKEN` of the Postgres server process
+//! 4. web identity token, like IRSA on EKS, by environment variables
+//!    `AWS_WEB_IDENTITY_TOKEN_FILE` and `AWS_ROLE_ARN`
+//! 5. instance profile from the EC2 instance metadata service
+//!
+//! If option `aws_role_arn` is set, the credentials above are then used to
+//! assume that role, with the optional external id in option `aws_external_id`.
+//! The region is set by option `aws_region` or environment variable
+//! `AWS_REGION`, default is `us-east-1`.
+//!
+//! Temporary credentials are cached in the backend and refreshed before they
+//! expire. The HTTP requests to AWS services can be signed by [`SigV4Signer`].
+//! For example,
+//!
+//! ```rust,no_run
+//! # use supabase_wrappers::prelude::*;
+//! # use std::collections::HashMap;
+//! # fn main() -> Result<(), AwsError> {
+//! # let options = HashMap::new();
+//! # let mut req = reqwest::Request::new(reqwest::Method::GET, "https://dynamodb.us-east-1.amazonaws.com/".parse().unwrap());
+//! let provider = AwsCredentialsProvider::from_options(&options)?;
+//! let creds = provider.credentials()?;
+//! let signer = SigV4Signer::new("dynamodb", provider.region());
+//! signer.sign(&mut req, &creds)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! This module requires the `aws` feature.
+
+use crate::error::FdwError;
+use crate::options::{OptionsError, Timeouts};
+use crate::redact::register_secret;
+use crate::secret::get_secret;
+use crate::utils::{create_async_runtime, CreateRuntimeError, Runtime};
+use hmac::{Hmac, Mac};
+use pgrx::pg_sys::panic::ErrorReport;
+use reqwest::header::{HeaderValue, AUTHORIZATION};
+use reqwest::{Method, Request, Url};
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+const DEFAULT_REGION: &str = "us-east-1";
+
+// temporary credentials are refreshed if they expire in this duration
+const REFRESH_MARGIN: Duration = Duration::from_secs(300);
+
+// session name and duration of the assumed roles
+const ROLE_SESSION_NAME: &str = "supabase-wrappers";
+const ROLE_SESSION_DURATION_SECS: u64 = 3600;
+
+const IMDS_ENDPOINT: &str = "http://169.254.169.254";
+const IMDS_TIMEOUT: Duration = Duration::from_secs(1);
+
+thread_local! {
+    // temporary credentials keyed by source and role, so they are shared by
+    // all the FDW instances in the backend
+    static CREDENTIALS: RefCell<HashMap<String, AwsCredentials>> = RefCell::new(HashMap::new());
+}
+
+#[derive(Error, Debug)]
+pub enum AwsError {
+    #[error("{0}")]
+    OptionsError(#[from] OptionsError),
+
+    #[error("secret of option {0} is not found")]
+    SecretNotFound(String),
+
+    #[error("{0}")]
+    CreateRuntimeError(#[from] CreateRuntimeError),
+
+    #[error("no AWS credentials found")]
+    CredentialsNotFound,
+
+    #[error("read web identity token file failed: {0}")]
+    TokenFileError(#[from] std::io::Error),
+
+    #[error("request AWS credentials failed: {0}")]
+    RequestError(#[from] reqwest::Error),
+
+    #[error("request AWS credentials failed with HTTP {0}: {1}")]
+    CredentialsRejected(u16, String),
+
+    #[error("invalid AWS credentials response: {0}")]
+    InvalidResponse(String),
+
+    #[error("invalid request header: {0}")]
+    InvalidHeader(#[from] reqwest::header::InvalidHeaderValue),
+}
+
+impl From<AwsError> for ErrorReport {
+    fn from(value: AwsError) -> Self {
+        let msg = format!("{value}");
+        match value {
+            AwsError::OptionsError(e) => e.into(),
+            AwsError::CreateRuntimeError(e) => e.into(),
+            AwsError::SecretNotFound(_) | AwsError::CredentialsNotFound => {
+                FdwError::InvalidOption(msg).into()
+            }
+            AwsError::RequestError(_) => FdwError::Transient(msg).into(),
+            AwsError::CredentialsRejected(status, _) => {
+                FdwError::from_http_status(status, msg).into()
+            }
+            AwsError::TokenFileError(_)
+            | AwsError::InvalidResponse(_)
+            | AwsError::InvalidHeader(_) => FdwError::Other(msg).into(),
+        }
+    }
+}
+
+/// AWS credentials, they are temporary if `expires_at` is set
+#[derive(Debug, Clone)]
+pub struct AwsCredentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+    pub expires_at: Option<SystemTime>,
+}
+
+impl AwsCredentials {
+    /// Create static credentials
+    pub fn new(access_key_id: &str, secret_access_key: &str) -> Self {
+        Self {
+            access_key_id: access_key_id.to_owned(),
+            secret_access_key: secret_access_key.to_owned(),
+            session_token: None,
+            expires_at: None,
+        }
+    }
+
+    fn is_expiring(&self) -> bool {
+        self.expires_at
+            .is_some_and(|t| t <= SystemTime::now() + REFRESH_MARGIN)
+    }
+}
+
+/// Provider of the AWS credentials of a foreign server
+pub struct AwsCredentialsProvider {
+    static_credentials: Option<AwsCredentials>,
+    role_arn: Option<String>,
+    external_id: Option<String>,
+    region: String,
+    http: reqwest::Client,
+    rt: Runtime,
+}
+
+impl AwsCredentialsProvider {
+    /// Create the provider from the foreign server options
+    pub fn from_options(options: &HashMap<String, String>) -> Result<Self, AwsError> {
+        let static_credentials = match (
+            options.get("aws_access_key_id"),
+            options.get("aws_secret_access_key"),
+            options.get("vault_access_key_id"),
+            options.get("vault_secret_access_key"),
+        ) {
+            (Some(key_id), Some(secret), _, _) => Some(AwsCredentials::new(key_id, secret)),
+            (_, _, Some(key_id), Some(secret)) => {
+                let key_id = get_secret(options, key_id)
+                    .ok_or_else(|| AwsError::SecretNotFound("vault_access_key_id".to_string()))?;
+                let secret = get_secret(options, secret).ok_or_else(|| {
+                    AwsError::SecretNotFound("vault_secret_access_key".to_string())
+                })?;
+                Some(AwsCredentials::new(&key_id, &secret))
+            }
+            _ => None,
+        };
+        let region = options
+            .get("aws_region")
+            .cloned()
+            .or_else(|| env::var("AWS_REGION").ok())
+            .unwrap_or_else(|| DEFAULT_REGION.to_string());
+
+        let timeouts = Timeouts::from_options(options)?;
+        let http = reqwest::Client::builder()
+            .connect_timeout(timeouts.connect)
+            .timeout(timeouts.request)
+            .build()?;
+
+        Ok(Self {
+            static_credentials,
+            role_arn: options.get("aws_role_arn").cloned(),
+            external_id: options.get("aws_external_id").cloned(),
+            region,
+            http,
+            rt: create_async_runtime()?,
+        })
+    }
+
+    /// Use the static credentials got by the FDW itself, instead of the
+    /// static keys in the standard options
+    pub fn with_static_credentials(mut self, credentials: AwsCredentials) -> Self {
+        self.static_credentials = Some(credentials);
+        self
+    }
+
+    /// Set the region, instead of the one in the standard options
+    pub fn with_region(mut self, region: &str) -> Self {
+        self.region = region.to_owned();
+        self
+    }
+
+    /// AWS region of the foreign server
+    pub fn region(&self) -> &str {
+        &self.region
+    }
+
+    /// Get the credentials, temporary credentials are refreshed if they are
+    /// expiring
+    pub fn credentials(&self) -> Result<AwsCredentials, AwsError> {
+        let key = format!(
+            "{:?}/{:?}/{:?}/{}",
+            self.static_credentials
+                .as_ref()
+                .map(|c| c.access_key_id.as_str()),
+            self.role_arn,
+            self.external_id,
+            self.region
+        );
+        let cached = CREDENTIALS.with_borrow(|creds| {
+            creds
+                .get(&key)
+                .filter(|creds| !creds.is_expiring())
+                .cloned()
+        });
+        if let Some(creds) = cached {
+            return Ok(creds);
+        }
+
+        let mut creds = self.base_credentials()?;
+        if let Some(role_arn) = &self.role_arn {
+            creds = self.assume_role(role_arn, &creds)?;
+        }
+        register_secret(&creds.secret_access_key);
+        if let Some(token) = &creds.session_token {
+            register_secret(token);
+        }
+        if creds.expires_at.is_some() {
+            CREDENTIALS.with_borrow_mut(|cache| cache.insert(key, creds.clone()));
+        }
+        Ok(creds)
+    }
+
+    // get credentials from the sources before assuming role
+    fn base_credentials(&self) -> Result<AwsCredentials, AwsError> {
+        if let Some(creds) = &self.static_credentials {
+            return Ok(creds.clone());
+        }
+        if let (Ok(key_id), Ok(secret)) = (
+            env::var("AWS_ACCESS_KEY_ID"),
+            env::var("AWS_SECRET_ACCESS_KEY"),
+        ) {
+            let mut creds = AwsCredentials::new(&key_id, &secret);
+            creds.session_token = env::var("AWS_SESSION_TOKEN").ok();
+            return Ok(creds);
+        }
+        if let (Ok(token_file), Ok(role_arn)) = (
+            env::var("AWS_WEB_IDENTITY_TOKEN_FILE"),
+            env::var("AWS_ROLE_ARN"),
+        ) {
+            let token = std::fs::read_to_string(token_file)?;
+            return self.assume_role_with_web_identity(&role_arn, token.trim());
+        }
+        self.instance_profile()
+    }
+
+    fn sts_url(&self, params: &[(&str, &str)]) -> Url {
+        let mut url = Url::parse(&format!("https://sts.{}.amazonaws.com/", self.region))
+            .expect("STS endpoint should be a valid URL");
+        url.query_pairs_mut()
+            .append_pair("Version", "2011-06-15")
+            .append_pair("RoleSessionName", ROLE_SESSION_NAME)
+            .append_pair("DurationSeconds", &ROLE_SESSION_DURATION_SECS.to_string())
+            .extend_pairs(params);
+        url
+    }
+
+    // send a request and return the response body if it is succeeded
+    fn send(&self, req: reqwest::RequestBuilder) -> Result<String, AwsError> {
+        let (status, body) = self.rt.block_on(async {
+            let resp = req.send().await?;
+            let status = resp.status();
+            Ok::<_, reqwest::Error>((status, resp.text().await?))
+        })?;
+        if !status.is_success() {
+            return Err(AwsError::CredentialsRejected(status.as_u16(), body));
+        }
+        Ok(body)
+    }
+
+    fn assume_role(
+        &self,
+        role_arn: &str,
+        creds: &AwsCredentials,
+    ) -> Result<AwsCredentials, AwsError> {
+        let mut params = vec![("Action", "AssumeRole"), ("RoleArn", role_arn)];
+        if let Some(external_id) = &self.external_id {
+            params.push(("ExternalId", external_id));
+        }
+        let mut req = Request::new(Method::GET, self.sts_url(&params));
+        SigV4Signer::new("sts", &self.region).sign(&mut req, creds)?;
+        let body = self.send(reqwest::RequestBuilder::from_parts(self.http.clone(), req))?;
+        parse_sts_credentials(&body)
+    }
+
+    fn assume_role_with_web_identity(
+        &self,
+        role_arn: &str,
+        token: &str,
+    ) -> Result<AwsCredentials, AwsError> {
+        let url = self.sts_url(&[
+            ("Action", "AssumeRoleWithWebIdentity"),
+            ("RoleArn", role_arn),
+            ("WebIdentityToken", token),
+        ]);
+        let body = self.send(self.http.get(url))?;
+        parse_sts_credentials(&body)
+    }
+
+    // get credentials of the instance profile by IMDSv2
+    fn instance_profile(&self) -> Result<AwsCredentials, AwsError> {
+        let token = self
+            .send(
+                self.http
+                    .put(format!("{}/latest/api/token", IMDS_ENDPOINT))
+                    .header("x-aws-ec2-metadata-token-ttl-seconds", "21600")
+                    .timeout(IMDS_TIMEOUT),
+            )
+            .map_err(|_| AwsError::CredentialsNotFound)?;
+        let url = format!(
+            "{}/latest/meta-data/iam/security-credentials/",
+            IMDS_ENDPOINT
+        );
+        let role = self.send(
+            self.http
+                .get(&url)
+                .header("x-aws-ec2-metadata-token", &token)
+                .timeout(IMDS_TIMEOUT),
+        )?;
+        let role = role.lines().next().ok_or(AwsError::CredentialsNotFound)?;
+        let body = self.send(
+            self.http
+                .get(format!("{}{}", url, role))
+                .header("x-aws-ec2-metadata-token", &token)
+                .timeout(IMDS_TIMEOUT),
+        )?;
+
+        let resp: serde_json::Value = serde_json::from_str(&body)
+            .map_err(|_| AwsError::InvalidResponse("response is not JSON".to_string()))?;
+        let field = |name: &str| {
+            resp.get(name)
+                .and_then(|v| v.as_str())
+                .map(|v| v.to_owned())
+                .ok_or_else(|| AwsError::InvalidResponse(format!("{} is not found", name)))
+        };
+        Ok(AwsCredentials {
+            access_key_id: field("AccessKeyId")?,
+            secret_access_key: field("SecretAccessKey")?,
+            session_token: Some(field("Token")?),
+            expires_at: parse_timestamp(&field("Expiration")?),
+        })
+    }
+}
+
+// get text of a XML element, the STS response is simple enough to not use a
+// XML parser
+fn xml_element<'a>(body: &'a str, name: &str) -> Result<&'a str, AwsError> {
+    let start_tag = format!("<{}>", name);
+    let end_tag = format!("</{}>", name);
+    body.split_once(&start_tag)
+        .and_then(|(_, rest)| rest.split_once(&end_tag))
+        .map(|(text, _)| text.trim())
+        .ok_or_else(|| AwsError::InvalidResponse(format!("{} is not found", name)))
+}
+
+fn parse_sts_credentials(body: &str) -> Result<AwsCredentials, AwsError> {
+    Ok(AwsCredentials {
+        access_key_id: xml_element(body, "AccessKeyId")?.to_owned(),
+        secret_access_key: xml_element(body, "SecretAccessKey")?.to_owned(),
+        session_token: Some(xml_element(body, "SessionToken")?.to_owned()),
+        expires_at: parse_timestamp(xml_element(body, "Expiration")?),
+    })
+}
+
+// parse UTC timestamp like `2024-01-02T03:04:05Z`
+fn parse_timestamp(s: &str) -> Option<SystemTime> {
+    let num = |range: std::ops::Range<usize>| s.get(range)?.parse::<i64>().ok();
+    let days = days_from_civil(num(0..4)?, num(5..7)?, num(8..10)?);
+    let secs = days * 86400 + num(11..13)? * 3600 + num(14..16)? * 60 + num(17..19)?;
+    Some(UNIX_EPOCH + Duration::from_secs(u64::try_from(secs).ok()?))
+}
+
+// days since Unix epoch of a date in the proleptic Gregorian calendar, see
+// http://howardhinnant.github.io/date_algorithms.html
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+// date of the days since Unix epoch, the inverse of days_from_civil()
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Signer of the AWS Signature Version 4
+pub struct SigV4Signer {
+    service: String,
+    region: String,
+}
+
+impl SigV4Signer {
+    /// Create a signer of an AWS service in a region
+    pub fn new(service: &str, region: &str) -> Self {
+        Self {
+            service: service.to_owned(),
+            region: region.to_owned(),
+        }
+    }
+
+    /// Sign the request by adding the authorization headers
+    ///
+    /// The request body is signed if it is not a stream, otherwise the payload
+    /// is sent unsigned.
+    pub fn sign(&self, req: &mut Request, creds: &AwsCredentials) -> Result<(), AwsError> {
+        self.sign_at(req, creds, SystemTime::now())
+    }
+
+    fn sign_at(
+        &self,
+        req: &mut Request,
+        creds: &AwsCredentials,
+        time: SystemTime,
+    ) -> Result<(), AwsError> {
+        let secs = time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64;
+        let (year, month, day) = civil_from_days(secs.div_euclid(86400));
+        let tod = secs.rem_euclid(86400);
+        let date = format!("{:04}{:02}{:02}", year, month, day);
+        let amz_date = format!(
+            "{}T{:02}{:02}{:02}Z",
+            date,
+            tod / 3600,
+            tod % 3600 / 60,
+            tod % 60
+        );
+
+        let payload_hash = match req.body() {
+            Some(body) => match body.as_bytes() {
+                Some(bytes) => hex::encode(Sha256::digest(bytes)),
+                None => "UNSIGNED-PAYLOAD".to_string(),
+            },
+            None => hex::encode(Sha256::digest(b"")),
+        };
+
+        let headers = req.headers_mut();
+        headers.insert("x-amz-date", HeaderValue::from_str(&amz_date)?);
+        headers.insert(
+            "x-amz-content-sha256",
+            HeaderValue::from_str(&payload_hash)?,
+        );
+        if let Some(token) = &creds.session_token {
+            let mut value = HeaderValue::from_str(token)?;
+            value.set_sensitive(true);
+            headers.insert("x-amz-security-token", value);
+        }
+
+        // canonical headers, host is added by the client when sending
+        let url = req.url();
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+            None => url.host_str().unwrap_or_default().to_owned(),
+        };
+        let mut canonical_headers: Vec<(String, String)> = req
+            .headers()
+            .iter()
+            .filter(|(name, _)| {
+                name.as_str().starts_with("x-amz-") || name.as_str() == "content-type"
+            })
+            .map(|(name, value)| {
+                let value = String::from_utf8_lossy(value.as_bytes());
+                let value = value.split_whitespace().collect::<Vec<_>>().join(" ");
+                (name.as_str().to_owned(), value)
+            })
+            .collect();
+        canonical_headers.push(("host".to_string(), host));
+        canonical_headers.sort();
+        let signed_headers = canonical_headers
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>()
+            .join(";");
+
+        // S3 uses the path as it is, other services encode it again
+        let path = if self.service == "s3" {
+            url.path().to_owned()
+        } else {
+            uri_encode(url.path(), false)
+        };
+        let mut query: Vec<(String, String)> = url
+            .query_pairs()
+            .map(|(k, v)| (uri_encode(&k, true), uri_encode(&v, true)))
+            .collect();
+        query.sort();
+        let query = query
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>()
+            .join("&");
+
+        let canonical_request = format!(
+            "{}\n{}\n{}\n{}\n\n{}\n{}",
+            req.method(),
+            path,
+            query,
+            canonical_headers
+                .iter()
+                .map(|(name, value)| format!("{}:{}", name, value))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            signed_headers,
+            payload_hash
+        );
+        let scope = format!("{}/{}/{}/aws4_request", date, self.region, self.service);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex::encode(Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let key = [
+            date.as_str(),
+            self.region.as_str(),
+            self.service.as_str(),
+            "aws4_request",
+        ]
+        .iter()
+        .fold(
+            format!("AWS4{}", creds.secret_access_key).into_bytes(),
+            |key, data| hmac_sha256(&key, data.as_bytes()),
+        );
+        let signature = hex::encode(hmac_sha256(&key, string_to_sign.as_bytes()));
+
+        let mut auth = HeaderValue::from_str(&format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            creds.access_key_id, scope, signed_headers, signature
+        ))?;
+        auth.set_sensitive(true);
+        req.headers_mut().insert(AUTHORIZATION, auth);
+        Ok(())
+    }
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts key of any size");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+// percent-encode all characters except the unreserved ones, and '/' if it is
+// not a query component
+fn uri_encode(s: &str, encode_slash: bool) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(b as char)
+            }
+            b'/' if !encode_slash => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    encoded
+}
@@ -0,0 +1,256 @@
+//! Scan result cache across statements
+//!
+//! Dashboards usually query the same foreign tables every few seconds, and
+//! each query fetches the same data from the remote API again. If the foreign
+//! table option `cache_ttl`, or the setting `wrappers.cache_ttl`, is set, the rows of a completed scan are cached in
+//! the backend for `cache_ttl` seconds, and the following scans with the same
+//! quals, columns, sorts and limit return the cached rows without sending any
+//! requests.
+//!
+//! The cache of a foreign table is invalidated when it is modified through
+//! Wrappers in the same backend, or by [`invalidate_cache`] explicitly.
+//!
+//! The FDWs can also keep their own values across scans in the same cache,
+//! like OAuth access tokens or schema metadata, by [`cache_set`] and
+//! [`cache_get`]. The cache has at most 1024 entries in a backend, the
+//! expired entries and then the entries expiring soonest are evicted when it
+//! is full.
+
+use crate::guc;
+use crate::interface::{Column, Limit, Qual, Row, Sort};
+use pgrx::prelude::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+// scans returning more rows than this are not cached
+const MAX_CACHED_ROWS: usize = 100_000;
+
+// max number of entries in the cache of a backend
+const MAX_CACHE_ENTRIES: usize = 1024;
+
+// values longer than this are not cached by `cache_set`
+const MAX_CACHED_VALUE_LEN: usize = 1024 * 1024;
+
+enum CachedValue {
+    Rows(Vec<Row>),
+    Text(String),
+}
+
+struct CacheEntry {
+    // the foreign table the scan result is from, it is invalid for the
+    // values cached by `cache_set`
+    table_oid: pg_sys::Oid,
+    value: CachedValue,
+    expires_at: Instant,
+}
+
+thread_local! {
+    static CACHE: RefCell<HashMap<String, CacheEntry>> = RefCell::new(HashMap::new());
+}
+
+// insert an entry into the cache, if the cache is full, remove the expired
+// entries first, then the entries expiring soonest
+fn insert_entry(key: String, entry: CacheEntry) {
+    CACHE.with_borrow_mut(|cache| {
+        if cache.len() >= MAX_CACHE_ENTRIES && !cache.contains_key(&key) {
+            let now = Instant::now();
+            cache.retain(|_, entry| entry.expires_at > now);
+            while cache.len() >= MAX_CACHE_ENTRIES {
+                let soonest = cache
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.expires_at)
+                    .map(|(key, _)| key.clone());
+                match soonest {
+                    Some(soonest) => cache.remove(&soonest),
+                    None => break,
+                };
+            }
+        }
+        cache.insert(key, entry);
+    });
+}
+
+// key of the values cached by `cache_set`, it is scoped by the foreign server
+// and the current user, so the values got by other servers or by other
+// users' credentials can't be read
+fn value_key(server_oid: pg_sys::Oid, key: &str) -> String {
+    let user_oid = unsafe { pg_sys::GetUserId() };
+    format!(
+        "value/{}/{}/{}",
+        server_oid.as_u32(),
+        user_oid.as_u32(),
+        key
+    )
+}
+
+/// Get the value cached by [`cache_set`], return `None` if it is not cached
+/// or expired
+pub fn cache_get(server_oid: pg_sys::Oid, key: &str) -> Option<String> {
+    let key = value_key(server_oid, key);
+    CACHE.with_borrow_mut(|cache| match cache.get(&key) {
+        Some(entry) if entry.expires_at <= Instant::now() => {
+            cache.remove(&key);
+            None
+        }
+        Some(CacheEntry {
+            value: CachedValue::Text(value),
+            ..
+        }) => Some(value.clone()),
+        _ => None,
+    })
+}
+
+/// Cache a value of the foreign server for `ttl`, the value is removed if
+/// `ttl` is zero
+///
+/// The value is kept in the backend across scans and isn't shared with other
+/// backends, it isn't cached if it is longer than 1 MiB.
+pub fn cache_set(server_oid: pg_sys::Oid, key: &str, value: String, ttl: Duration) {
+    let key = value_key(server_oid, key);
+    if ttl.is_zero() || value.len() > MAX_CACHED_VALUE_LEN {
+        CACHE.with_borrow_mut(|cache| cache.remove(&key));
+        return;
+    }
+    let entry = CacheEntry {
+        table_oid: pg_sys::InvalidOid,
+        value: CachedValue::Text(value),
+        expires_at: Instant::now() + ttl,
+    };
+    insert_entry(key, entry);
+}
+
+// cached scan of a foreign table, it either replays the cached rows or
+// records the fetched rows to be cached
+pub(crate) struct ScanCache {
+    table_oid: pg_sys::Oid,
+    ttl: Duration,
+    key: String,
+
+    // cached rows being replayed and index of the next row, rows are fetched
+    // from remote if it is not set
+    replay: Option<(Vec<Row>, usize)>,
+
+    // rows fetched so far, recording is stopped if there are too many rows
+    recorded: Option<Vec<Row>>,
+}
+
+impl ScanCache {
+    // create a scan cache by foreign table option `cache_ttl`, or setting
+    // `wrappers.cache_ttl` if the option is not specified, return None if the
+    // cache is not enabled
+    pub(crate) fn from_options(
+        table_oid: pg_sys::Oid,
+        opts: &HashMap<String, String>,
+    ) -> Option<Self> {
+        let ttl = match opts.get("cache_ttl") {
+            Some(v) => match v.parse::<u64>() {
+                Ok(v) if v > 0 => Duration::from_secs(v),
+                _ => {
+                    pgrx::error!("invalid option cache_ttl: {}", v);
+                }
+            },
+            None => guc::cache_ttl()?,
+        };
+        Some(Self {
+            table_oid,
+            ttl,
+            key: String::new(),
+            replay: None,
+            recorded: None,
+        })
+    }
+
+    // look up the cached rows for a scan, return true if the rows are cached
+    // and will be replayed, otherwise the fetched rows will be recorded
+    pub(crate) fn lookup(
+        &mut self,
+        quals: &[Qual],
+        columns: &[Column],
+        sorts: &[Sort],
+        limit: &Option<Limit>,
+    ) -> bool {
+        let server_oid = unsafe { (*pg_sys::GetForeignTable(self.table_oid)).serverid };
+        let cols: Vec<&str> = columns.iter().map(|col| col.name.as_str()).collect();
+        self.key = format!(
+            "{}/{}/{:?}/{:?}/{:?}/{:?}",
+            server_oid.as_u32(),
+            self.table_oid.as_u32(),
+            quals,
+            cols,
+            sorts,
+            limit
+        );
+
+        let now = Instant::now();
+        let rows = CACHE.with_borrow_mut(|cache| {
+            cache.retain(|_, entry| entry.expires_at > now);
+            match cache.get(&self.key) {
+                Some(CacheEntry {
+                    value: CachedValue::Rows(rows),
+                    ..
+                }) => Some(rows.clone()),
+                _ => None,
+            }
+        });
+        self.replay = rows.map(|rows| (rows, 0));
+        self.recorded = if self.replay.is_none() {
+            Some(Vec::new())
+        } else {
+            None
+        };
+        self.replay.is_some()
+    }
+
+    pub(crate) fn is_replaying(&self) -> bool {
+        self.replay.is_some()
+    }
+
+    // get the next cached row being replayed
+    pub(crate) fn next_row(&mut self) -> Option<Row> {
+        let (rows, pos) = self.replay.as_mut()?;
+        let row = rows.get(*pos).cloned();
+        *pos += 1;
+        row
+    }
+
+    // record a fetched row, or cache all the recorded rows when the scan is
+    // completed
+    pub(crate) fn record(&mut self, row: Option<&Row>) {
+        match row {
+            Some(row) => {
+                if let Some(rows) = &mut self.recorded {
+                    if rows.len() < MAX_CACHED_ROWS {
+                        rows.push(row.clone());
+                    } else {
+                        self.recorded = None;
+                    }
+                }
+            }
+            None => {
+                if let Some(rows) = self.recorded.take() {
+                    let entry = CacheEntry {
+                        table_oid: self.table_oid,
+                        value: CachedValue::Rows(rows),
+                        expires_at: Instant::now() + self.ttl,
+                    };
+                    insert_entry(self.key.clone(), entry);
+                }
+            }
+        }
+    }
+
+    // restart the scan, the cached rows are replayed from the beginning, or
+    // the rows are recorded again
+    pub(crate) fn rewind(&mut self) {
+        match &mut self.replay {
+            Some((_, pos)) => *pos = 0,
+            None => self.recorded = Some(Vec::new()),
+        }
+    }
+}
+
+/// Invalidate the cached scan results of a foreign table
+pub fn invalidate_cache(table_oid: pg_sys::Oid) {
+    CACHE.with_borrow_mut(|cache| cache.retain(|_, entry| entry.table_oid != table_oid));
+}
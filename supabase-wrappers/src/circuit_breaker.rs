@@ -0,0 +1,154 @@
+//! Circuit breaker of the remote servers
+//!
+//! When a remote server is down, every query has to wait through the full
+//! retries and timeouts before it fails. The circuit breaker of a foreign
+//! server opens after a number of consecutive failed requests, then the
+//! requests fail fast until the cool-down period ends. After that, one more
+//! failure opens the circuit again, while a successful request closes it.
+//!
+//! It is set by the foreign server options below:
+//!
+//! - `circuit_breaker_threshold`: number of consecutive failures to open the
+//!   circuit, the circuit breaker is disabled if it is not specified
+//! - `circuit_breaker_cooldown`: cool-down period in seconds, default is 60
+//!
+//! FDWs check the circuit before each request and record the request result
+//! after it, for example,
+//!
+//! ```rust,no_run
+//! # use supabase_wrappers::prelude::*;
+//! # fn send_request() -> Result<String, String> { Ok(String::new()) }
+//! # fn scan(server: ForeignServer) -> Result<(), FdwError> {
+//! let circuit_breaker = CircuitBreaker::from_server(&server)?;
+//!
+//! circuit_breaker.check()?;
+//! match send_request() {
+//!     Ok(_) => circuit_breaker.record_success(),
+//!     Err(_) => circuit_breaker.record_failure(),
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Like the rate limiter, the circuit states are shared by all backends if the
+//! extension is loaded by `shared_preload_libraries`, otherwise each backend
+//! has its own circuit states.
+
+use crate::error::FdwError;
+use crate::instance::ForeignServer;
+use crate::options::{parse_option, OptionsError};
+use crate::shmem::{with_server_states, ServerStates};
+use pgrx::prelude::*;
+use pgrx::PgLwLock;
+use std::cell::RefCell;
+use std::time::Duration;
+
+const DEFAULT_COOLDOWN: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Circuit {
+    failures: u32,
+    open_until: pg_sys::TimestampTz,
+}
+
+pub(crate) static CIRCUITS: PgLwLock<ServerStates<Circuit>> = PgLwLock::new();
+
+thread_local! {
+    // circuit states used when shared memory is not available
+    static LOCAL_CIRCUITS: RefCell<ServerStates<Circuit>> = RefCell::new(ServerStates::default());
+}
+
+fn now() -> pg_sys::TimestampTz {
+    unsafe { pg_sys::GetCurrentTimestamp() }
+}
+
+/// Circuit breaker of a foreign server
+///
+/// The default circuit breaker is disabled.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CircuitBreaker {
+    server_oid: pg_sys::Oid,
+    server_name: String,
+    threshold: Option<u32>,
+    cooldown: Duration,
+}
+
+impl CircuitBreaker {
+    /// Create a circuit breaker by the foreign server options
+    pub fn from_server(server: &ForeignServer) -> Result<Self, OptionsError> {
+        let threshold = parse_option("circuit_breaker_threshold", &server.options, |n| *n > 0)?;
+        let cooldown = parse_option("circuit_breaker_cooldown", &server.options, |secs| {
+            *secs > 0
+        })?
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_COOLDOWN);
+        Ok(Self {
+            server_oid: server.server_oid,
+            server_name: server.server_name.clone(),
+            threshold,
+            cooldown,
+        })
+    }
+
+    /// Oid of the foreign server
+    pub fn server_oid(&self) -> pg_sys::Oid {
+        self.server_oid
+    }
+
+    /// Check if a request can be sent to the foreign server, an error is
+    /// returned if the circuit is open
+    pub fn check(&self) -> Result<(), FdwError> {
+        if self.threshold.is_none() {
+            return Ok(());
+        }
+        let open_until = self.with_circuit(|circuit| circuit.open_until);
+        let remaining = open_until - now();
+        if remaining > 0 {
+            return Err(FdwError::Transient(format!(
+                "circuit breaker is open for foreign server \"{}\" due to consecutive failures, retry after {} seconds",
+                self.server_name,
+                (remaining + 999_999) / 1_000_000
+            )));
+        }
+        Ok(())
+    }
+
+    /// Record a successful request, which closes the circuit
+    pub fn record_success(&self) {
+        if self.threshold.is_none() {
+            return;
+        }
+        self.with_circuit(|circuit| *circuit = Circuit::default());
+    }
+
+    /// Record a failed request, the circuit is opened if the number of
+    /// consecutive failures reaches the threshold
+    pub fn record_failure(&self) {
+        let Some(threshold) = self.threshold else {
+            return;
+        };
+        let cooldown = self.cooldown.as_micros() as pg_sys::TimestampTz;
+        self.with_circuit(|circuit| {
+            circuit.failures = circuit.failures.saturating_add(1);
+            if circuit.failures >= threshold {
+                circuit.open_until = now() + cooldown;
+            }
+        });
+    }
+
+    fn with_circuit<R>(&self, f: impl FnOnce(&mut Circuit) -> R) -> R {
+        with_server_states(&CIRCUITS, &LOCAL_CIRCUITS, |circuits| {
+            f(circuits.get_or_insert_with(self.server_oid, Circuit::default))
+        })
+    }
+}
+
+/// Reset the circuit of a foreign server, so requests can be sent to it again
+///
+/// If the extension is not loaded by `shared_preload_libraries`, only the
+/// circuit of the current backend is reset.
+pub fn reset_circuit(server_oid: pg_sys::Oid) {
+    with_server_states(&CIRCUITS, &LOCAL_CIRCUITS, |circuits| {
+        circuits.remove(server_oid)
+    });
+}
@@ -0,0 +1,119 @@
+//! Structured error for remote data source failures
+//!
+//! Errors from the remote data source, such as an HTTP 401 or 429 response,
+//! should be distinguishable by the SQLSTATE code rather than a generic
+//! `ERRCODE_FDW_ERROR`, so that clients can decide how to handle them. FDWs can
+//! convert their errors to [`FdwError`] when reporting, which maps each error
+//! kind to a standard SQLSTATE code and tells whether it is retryable. For
+//! example,
+//!
+//! ```rust,no_run
+//! # use supabase_wrappers::prelude::*;
+//! # use pgrx::pg_sys::panic::ErrorReport;
+//! # enum MyFdwError { RequestFailed(u16, String), Other(String) }
+//! impl From<MyFdwError> for ErrorReport {
+//!     fn from(value: MyFdwError) -> Self {
+//!         match value {
+//!             MyFdwError::RequestFailed(status, msg) => {
+//!                 FdwError::from_http_status(status, msg).into()
+//!             }
+//!             MyFdwError::Other(msg) => FdwError::Other(msg).into(),
+//!         }
+//!     }
+//! }
+//! ```
+
+use crate::json::JsonToRowError;
+use crate::options::OptionsError;
+use pgrx::pg_sys::panic::ErrorReport;
+use pgrx::prelude::PgSqlErrorCode;
+use thiserror::Error;
+
+/// Kinds of error raised by a foreign data wrapper
+///
+/// Each variant carries the error message reported to the client.
+#[derive(Error, Debug)]
+pub enum FdwError {
+    /// Remote authentication or authorization failed, e.g. HTTP 401 or 403
+    #[error("{0}")]
+    Auth(String),
+
+    /// Remote request was rejected by rate limit, e.g. HTTP 429
+    #[error("{0}")]
+    RateLimited(String),
+
+    /// Remote object was not found, e.g. HTTP 404
+    #[error("{0}")]
+    NotFound(String),
+
+    /// Foreign server or table option is missing or invalid
+    #[error("{0}")]
+    InvalidOption(String),
+
+    /// Remote value cannot be converted to the column type
+    #[error("{0}")]
+    TypeMismatch(String),
+
+    /// Temporary remote failure, e.g. connection error, timeout or HTTP 5xx
+    #[error("{0}")]
+    Transient(String),
+
+    /// Any other error
+    #[error("{0}")]
+    Other(String),
+}
+
+impl FdwError {
+    /// Create an error from the HTTP status code of a failed remote request
+    pub fn from_http_status(status: u16, message: impl Into<String>) -> Self {
+        let message = message.into();
+        match status {
+            401 | 403 => Self::Auth(message),
+            404 | 410 => Self::NotFound(message),
+            429 => Self::RateLimited(message),
+            408 | 500..=599 => Self::Transient(message),
+            _ => Self::Other(message),
+        }
+    }
+
+    /// SQLSTATE code reported to the client
+    pub fn sqlstate(&self) -> PgSqlErrorCode {
+        match self {
+            Self::Auth(_) => PgSqlErrorCode::ERRCODE_INVALID_AUTHORIZATION_SPECIFICATION,
+            Self::RateLimited(_) => PgSqlErrorCode::ERRCODE_CONFIGURATION_LIMIT_EXCEEDED,
+            Self::NotFound(_) => PgSqlErrorCode::ERRCODE_FDW_TABLE_NOT_FOUND,
+            Self::InvalidOption(_) => PgSqlErrorCode::ERRCODE_FDW_INVALID_ATTRIBUTE_VALUE,
+            Self::TypeMismatch(_) => PgSqlErrorCode::ERRCODE_FDW_INVALID_DATA_TYPE,
+            Self::Transient(_) => PgSqlErrorCode::ERRCODE_FDW_UNABLE_TO_ESTABLISH_CONNECTION,
+            Self::Other(_) => PgSqlErrorCode::ERRCODE_FDW_ERROR,
+        }
+    }
+
+    /// Whether the failed operation may succeed if retried later
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::RateLimited(_) | Self::Transient(_))
+    }
+}
+
+impl From<OptionsError> for FdwError {
+    fn from(value: OptionsError) -> Self {
+        Self::InvalidOption(format!("{value}"))
+    }
+}
+
+impl From<JsonToRowError> for FdwError {
+    fn from(value: JsonToRowError) -> Self {
+        Self::TypeMismatch(format!("{value}"))
+    }
+}
+
+impl From<FdwError> for ErrorReport {
+    fn from(value: FdwError) -> Self {
+        let report = ErrorReport::new(value.sqlstate(), format!("{value}"), "");
+        if value.is_retryable() {
+            report.set_hint("the error is temporary, retry the query later")
+        } else {
+            report
+        }
+    }
+}
@@ -0,0 +1,270 @@
+//! Extension-level settings of Wrappers
+//!
+//! The settings below give the defaults of all foreign servers and tables, so
+//! they can be set cluster-wide in `postgresql.conf` or per database or role
+//! without changing the options of each foreign server:
+//!
+//! - `wrappers.http_timeout`: default of the `request_timeout` option, in seconds
+//! - `wrappers.max_retries`: default of the `retry_count` option
+//! - `wrappers.log_remote_requests`: if log each remote request to the server log
+//! - `wrappers.cache_ttl`: default of the `cache_ttl` option, in seconds, 0 to
+//!   disable the cache
+//! - `wrappers.secret_cache_ttl`: time to live of the decrypted Vault secrets
+//!   cached in each backend, in seconds, 0 to disable the cache
+//! - `wrappers.secret_dir`: directory of the secret files read by the `file`
+//!   secret provider
+//! - `wrappers.audit_log`: if write the audit records of the remote requests
+//!   to the server log
+//! - `wrappers.dry_run`: if only report the remote requests of the scans
+//!   instead of sending them
+//! - `wrappers.sync_database`: database in which the background worker runs
+//!   the scheduled sync jobs, the worker is not started if it is not set
+//! - `wrappers.sync_naptime`: interval of the background worker checking for
+//!   the sync jobs due, in seconds
+//! - `wrappers.debug_level`: if trace the remote requests at `DEBUG1`, `1`
+//!   traces the requests, `2` also traces the truncated responses
+//! - `wrappers.wasm_package_dirs`: comma-separated directories the Wasm FDW
+//!   packages can be loaded from by `file://` URLs, any local file can be
+//!   loaded if it is not set
+//! - `wrappers.wasm_trusted_keys`: comma-separated minisign public keys, the
+//!   Wasm FDW packages must be signed by one of them if it is set
+//!
+//! The extension should call [`init_gucs`] in its `_PG_init()`. For example,
+//!
+//! ```rust,no_run
+//! use pgrx::prelude::*;
+//! use supabase_wrappers::prelude::init_gucs;
+//!
+//! #[pg_guard]
+//! pub extern "C" fn _PG_init() {
+//!     init_gucs();
+//! }
+//! ```
+
+use pgrx::{GucContext, GucFlags, GucRegistry, GucSetting};
+use std::ffi::CStr;
+use std::time::Duration;
+
+static HTTP_TIMEOUT: GucSetting<i32> = GucSetting::<i32>::new(300);
+static MAX_RETRIES: GucSetting<i32> = GucSetting::<i32>::new(3);
+static LOG_REMOTE_REQUESTS: GucSetting<bool> = GucSetting::<bool>::new(false);
+static CACHE_TTL: GucSetting<i32> = GucSetting::<i32>::new(0);
+static SECRET_CACHE_TTL: GucSetting<i32> = GucSetting::<i32>::new(60);
+static SECRET_DIR: GucSetting<Option<&'static CStr>> =
+    GucSetting::<Option<&'static CStr>>::new(None);
+static AUDIT_LOG: GucSetting<bool> = GucSetting::<bool>::new(false);
+static DRY_RUN: GucSetting<bool> = GucSetting::<bool>::new(false);
+static SYNC_DATABASE: GucSetting<Option<&'static CStr>> =
+    GucSetting::<Option<&'static CStr>>::new(None);
+static SYNC_NAPTIME: GucSetting<i32> = GucSetting::<i32>::new(60);
+static DEBUG_LEVEL: GucSetting<i32> = GucSetting::<i32>::new(0);
+static WASM_PACKAGE_DIRS: GucSetting<Option<&'static CStr>> =
+    GucSetting::<Option<&'static CStr>>::new(None);
+static WASM_TRUSTED_KEYS: GucSetting<Option<&'static CStr>> =
+    GucSetting::<Option<&'static CStr>>::new(None);
+
+/// Register the settings of Wrappers, it must be called in `_PG_init()`
+pub fn init_gucs() {
+    GucRegistry::define_int_guc(
+        "wrappers.http_timeout",
+        "Default timeout of each remote request.",
+        "Used if the foreign server option request_timeout is not specified.",
+        &HTTP_TIMEOUT,
+        1,
+        i32::MAX,
+        GucContext::Userset,
+        GucFlags::UNIT_S,
+    );
+    GucRegistry::define_int_guc(
+        "wrappers.max_retries",
+        "Default max number of retries of a failed remote request.",
+        "Used if the foreign server option retry_count is not specified.",
+        &MAX_RETRIES,
+        0,
+        100,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+    GucRegistry::define_bool_guc(
+        "wrappers.log_remote_requests",
+        "Log each remote request to the server log.",
+        "",
+        &LOG_REMOTE_REQUESTS,
+        GucContext::Suset,
+        GucFlags::default(),
+    );
+    GucRegistry::define_int_guc(
+        "wrappers.cache_ttl",
+        "Default time to live of the cached scan results, 0 disables the cache.",
+        "Used if the foreign table option cache_ttl is not specified.",
+        &CACHE_TTL,
+        0,
+        i32::MAX,
+        GucContext::Userset,
+        GucFlags::UNIT_S,
+    );
+    GucRegistry::define_int_guc(
+        "wrappers.secret_cache_ttl",
+        "Time to live of the cached Vault secrets, 0 disables the cache.",
+        "Call wrappers_flush_secrets() to remove the cached secrets after rotation.",
+        &SECRET_CACHE_TTL,
+        0,
+        i32::MAX,
+        GucContext::Suset,
+        GucFlags::UNIT_S,
+    );
+    GucRegistry::define_string_guc(
+        "wrappers.secret_dir",
+        "Directory of the secret files read by the file secret provider.",
+        "The file secret provider is disabled if it is not set.",
+        &SECRET_DIR,
+        GucContext::Sighup,
+        GucFlags::SUPERUSER_ONLY,
+    );
+    GucRegistry::define_bool_guc(
+        "wrappers.audit_log",
+        "Write the audit records of the remote requests to the server log.",
+        "Each record is a JSON object with the server, operation, rows and duration.",
+        &AUDIT_LOG,
+        GucContext::Suset,
+        GucFlags::default(),
+    );
+    GucRegistry::define_bool_guc(
+        "wrappers.dry_run",
+        "Report the remote requests of the foreign scans without sending them.",
+        "The scans return no rows, and the foreign table modifications are not allowed.",
+        &DRY_RUN,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+    GucRegistry::define_string_guc(
+        "wrappers.sync_database",
+        "Database in which the background worker runs the scheduled sync jobs.",
+        "The background worker is only started if it is set and Wrappers is in shared_preload_libraries.",
+        &SYNC_DATABASE,
+        GucContext::Postmaster,
+        GucFlags::SUPERUSER_ONLY,
+    );
+    GucRegistry::define_int_guc(
+        "wrappers.sync_naptime",
+        "Interval of the background worker checking for the sync jobs due.",
+        "",
+        &SYNC_NAPTIME,
+        1,
+        i32::MAX,
+        GucContext::Sighup,
+        GucFlags::UNIT_S,
+    );
+    GucRegistry::define_int_guc(
+        "wrappers.debug_level",
+        "Trace the remote requests at DEBUG1, 0 disables the tracing.",
+        "1 traces the requests, 2 also traces the truncated responses.",
+        &DEBUG_LEVEL,
+        0,
+        2,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+    GucRegistry::define_string_guc(
+        "wrappers.wasm_package_dirs",
+        "Comma-separated directories the Wasm FDW packages can be loaded from by file:// URLs.",
+        "Any local file can be loaded if it is not set.",
+        &WASM_PACKAGE_DIRS,
+        GucContext::Sighup,
+        GucFlags::SUPERUSER_ONLY,
+    );
+    GucRegistry::define_string_guc(
+        "wrappers.wasm_trusted_keys",
+        "Comma-separated minisign public keys the Wasm FDW packages must be signed by.",
+        "The package signatures are not required if it is not set.",
+        &WASM_TRUSTED_KEYS,
+        GucContext::Sighup,
+        GucFlags::SUPERUSER_ONLY,
+    );
+}
+
+pub(crate) fn http_timeout() -> Duration {
+    Duration::from_secs(HTTP_TIMEOUT.get() as u64)
+}
+
+pub(crate) fn max_retries() -> u32 {
+    MAX_RETRIES.get() as u32
+}
+
+/// Check if the remote requests should be logged, by `wrappers.log_remote_requests`
+pub fn log_remote_requests() -> bool {
+    LOG_REMOTE_REQUESTS.get()
+}
+
+pub(crate) fn cache_ttl() -> Option<Duration> {
+    let ttl = CACHE_TTL.get();
+    (ttl > 0).then(|| Duration::from_secs(ttl as u64))
+}
+
+pub(crate) fn secret_cache_ttl() -> Option<Duration> {
+    let ttl = SECRET_CACHE_TTL.get();
+    (ttl > 0).then(|| Duration::from_secs(ttl as u64))
+}
+
+pub(crate) fn secret_dir() -> Option<String> {
+    SECRET_DIR
+        .get()
+        .and_then(|dir| dir.to_str().ok())
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| dir.to_owned())
+}
+
+pub(crate) fn audit_log() -> bool {
+    AUDIT_LOG.get()
+}
+
+/// Check if the remote requests should only be reported, by `wrappers.dry_run`
+pub fn dry_run() -> bool {
+    DRY_RUN.get()
+}
+
+/// Get the database of the sync background worker, by `wrappers.sync_database`
+pub fn sync_database() -> Option<String> {
+    SYNC_DATABASE
+        .get()
+        .and_then(|db| db.to_str().ok())
+        .filter(|db| !db.is_empty())
+        .map(|db| db.to_owned())
+}
+
+/// Get the interval of the sync background worker, by `wrappers.sync_naptime`
+pub fn sync_naptime() -> Duration {
+    Duration::from_secs(SYNC_NAPTIME.get() as u64)
+}
+
+/// Get the level of tracing the remote requests, by `wrappers.debug_level`
+pub fn debug_level() -> i32 {
+    DEBUG_LEVEL.get()
+}
+
+// split a comma-separated list setting
+fn split_list(setting: &GucSetting<Option<&'static CStr>>) -> Vec<String> {
+    setting
+        .get()
+        .and_then(|list| list.to_str().ok())
+        .map(|list| {
+            list.split(',')
+                .map(|item| item.trim())
+                .filter(|item| !item.is_empty())
+                .map(|item| item.to_owned())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Get the directories of the local Wasm FDW packages, by
+/// `wrappers.wasm_package_dirs`
+pub fn wasm_package_dirs() -> Vec<String> {
+    split_list(&WASM_PACKAGE_DIRS)
+}
+
+/// Get the public keys trusted to sign the Wasm FDW packages, by
+/// `wrappers.wasm_trusted_keys`
+pub fn wasm_trusted_keys() -> Vec<String> {
+    split_list(&WASM_TRUSTED_KEYS)
+}
@@ -0,0 +1,58 @@
+//! Health check of the foreign servers
+//!
+//! [`check_server_health`] creates an FDW instance for a foreign server and
+//! calls its [`health_check`](crate::interface::ForeignDataWrapper::health_check),
+//! so the connectivity and credentials can be verified without running a real
+//! query. It is called by the `<snake_case_fdw_name>_health_check()` function
+//! created by the [`wrappers_fdw`](crate::wrappers_fdw) macro.
+
+use crate::instance;
+use crate::interface::ForeignDataWrapper;
+use crate::redact::redact;
+use pgrx::pg_sys::panic::ErrorReport;
+use pgrx::prelude::*;
+use std::time::{Duration, Instant};
+
+/// Result of a foreign server health check
+#[derive(Debug, Clone, Default)]
+pub struct HealthCheck {
+    /// `ok` if the check succeeded, otherwise `error`
+    pub status: String,
+
+    /// Time spent on creating the FDW instance and the check
+    pub latency: Duration,
+
+    /// Remote server version, if it is known
+    pub version: Option<String>,
+
+    /// Error message if the check failed
+    pub message: Option<String>,
+}
+
+/// Check the health of a foreign server by the FDW
+///
+/// The errors returned by the FDW are reported in the result instead of
+/// being raised, except the ones raised directly in Postgres, like a missing
+/// Vault secret.
+pub fn check_server_health<E: Into<ErrorReport>, W: ForeignDataWrapper<E>>(
+    server_oid: pg_sys::Oid,
+) -> HealthCheck {
+    let server = unsafe { instance::get_foreign_server(server_oid) };
+    let started_at = Instant::now();
+    let result = W::new(server).and_then(|mut instance| instance.health_check());
+    let latency = started_at.elapsed();
+    match result {
+        Ok(version) => HealthCheck {
+            status: "ok".to_string(),
+            latency,
+            version,
+            message: None,
+        },
+        Err(err) => HealthCheck {
+            status: "error".to_string(),
+            latency,
+            version: None,
+            message: Some(redact(err.into().message())),
+        },
+    }
+}
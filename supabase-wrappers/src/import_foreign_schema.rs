@@ -6,6 +6,8 @@ use std::marker::PhantomData;
 use crate::instance;
 use crate::options::options_to_hashmap;
 use crate::prelude::ForeignDataWrapper;
+use crate::utils::ReportableError;
+use crate::FdwRoutine;
 
 // Fdw private state for import_foreign_schema
 struct FdwState<E: Into<ErrorReport>, W: ForeignDataWrapper<E>> {
@@ -41,6 +43,187 @@ pub struct ImportForeignSchemaStmt {
     pub options: std::collections::HashMap<String, String>,
 }
 
+// quote an identifier in SQL statement
+fn quote_ident(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+// quote a string literal in SQL statement
+fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+impl ImportForeignSchemaStmt {
+    /// Check if a remote table is to be imported by the `LIMIT TO` or `EXCEPT`
+    /// clause, so the FDW can skip fetching metadata of the excluded tables
+    pub fn is_table_included(&self, table: &str) -> bool {
+        match self.list_type {
+            ListType::FdwImportSchemaAll => true,
+            ListType::FdwImportSchemaLimitTo => self.table_list.iter().any(|t| t == table),
+            ListType::FdwImportSchemaExcept => !self.table_list.iter().any(|t| t == table),
+        }
+    }
+
+    /// Make a `CREATE FOREIGN TABLE` statement for a remote table
+    ///
+    /// - `table` - the local foreign table name, same as the remote table name
+    /// - `columns` - column name and Postgres type pairs, e.g. `("id", "bigint")`
+    /// - `options` - foreign table option name and value pairs
+    ///
+    /// The identifiers and option values are quoted.
+    pub fn create_foreign_table(
+        &self,
+        table: &str,
+        columns: &[(String, String)],
+        options: &[(String, String)],
+    ) -> String {
+        let cols = columns
+            .iter()
+            .map(|(name, col_type)| format!("{} {}", quote_ident(name), col_type))
+            .collect::<Vec<_>>()
+            .join(",\n  ");
+        let mut sql = format!(
+            "create foreign table if not exists {}.{} (\n  {}\n)\nserver {}",
+            quote_ident(&self.local_schema),
+            quote_ident(table),
+            cols,
+            quote_ident(&self.server_name),
+        );
+        if !options.is_empty() {
+            let opts = options
+                .iter()
+                .map(|(name, value)| format!("{} {}", quote_ident(name), quote_literal(value)))
+                .collect::<Vec<_>>()
+                .join(", ");
+            sql.push_str(&format!("\noptions ({})", opts));
+        }
+        sql
+    }
+}
+
+/// A remote table to be imported as a foreign table, returned by
+/// [`list_tables`](ListTables::list_tables)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RemoteTable {
+    /// the local foreign table name
+    pub name: String,
+    /// column name and Postgres type pairs, e.g. `("id", "bigint")`
+    pub columns: Vec<(String, String)>,
+    /// foreign table option name and value pairs
+    pub options: Vec<(String, String)>,
+}
+
+/// List the remote tables for `IMPORT FOREIGN SCHEMA`
+///
+/// Instead of making the `CREATE FOREIGN TABLE` statements in
+/// [`import_foreign_schema`](ForeignDataWrapper::import_foreign_schema), the
+/// FDW can implement this trait and add `import_schema` to the
+/// [`wrappers_fdw`](crate::wrappers_fdw) macro attribute. Then the tables
+/// excluded by `LIMIT TO` or `EXCEPT` clause are skipped, the statements are
+/// made with quoted identifiers and option values, and the `import_schema`
+/// capability is set. For example,
+///
+/// ```rust,ignore
+/// #[wrappers_fdw(
+///     version = "0.1.0",
+///     author = "Supabase",
+///     website = "https://github.com/supabase/wrappers/tree/main/wrappers/src/fdw/helloworld_fdw",
+///     error_type = "HelloWorldFdwError",
+///     import_schema
+/// )]
+/// pub struct HelloWorldFdw;
+///
+/// impl ListTables<HelloWorldFdwError> for HelloWorldFdw {
+///     fn list_tables(
+///         &mut self,
+///         stmt: &ImportForeignSchemaStmt,
+///     ) -> Result<Vec<RemoteTable>, HelloWorldFdwError> {
+///         Ok(vec![RemoteTable {
+///             name: "hello".to_string(),
+///             columns: vec![("id".to_string(), "bigint".to_string())],
+///             options: vec![("object".to_string(), "hello".to_string())],
+///         }])
+///     }
+/// }
+/// ```
+pub trait ListTables<E: Into<ErrorReport>>: ForeignDataWrapper<E> {
+    /// List the remote tables in `stmt.remote_schema`, the tables can be
+    /// filtered by [`is_table_included`](ImportForeignSchemaStmt::is_table_included)
+    /// early to skip fetching their metadata
+    fn list_tables(&mut self, stmt: &ImportForeignSchemaStmt) -> Result<Vec<RemoteTable>, E>;
+
+    /// Returns a FdwRoutine for the FDW, which imports the tables by
+    /// [`list_tables`](ListTables::list_tables)
+    ///
+    /// Not to be used directly, use [`wrappers_fdw`](crate::wrappers_fdw) macro instead.
+    fn fdw_routine_with_import() -> FdwRoutine
+    where
+        Self: Sized,
+    {
+        let mut fdw_routine = Self::fdw_routine();
+        fdw_routine.ImportForeignSchema = Some(import_remote_tables::<E, Self>);
+        fdw_routine
+    }
+}
+
+// convert the import foreign schema statement from Postgres
+unsafe fn import_stmt(stmt: *mut pg_sys::ImportForeignSchemaStmt) -> ImportForeignSchemaStmt {
+    ImportForeignSchemaStmt {
+        server_name: std::ffi::CStr::from_ptr((*stmt).server_name)
+            .to_str()
+            .unwrap()
+            .to_string(),
+        remote_schema: std::ffi::CStr::from_ptr((*stmt).remote_schema)
+            .to_str()
+            .unwrap()
+            .to_string(),
+        local_schema: std::ffi::CStr::from_ptr((*stmt).local_schema)
+            .to_str()
+            .unwrap()
+            .to_string(),
+
+        list_type: match (*stmt).list_type {
+            pgrx::pg_sys::ImportForeignSchemaType::FDW_IMPORT_SCHEMA_ALL => {
+                ListType::FdwImportSchemaAll
+            }
+            pgrx::pg_sys::ImportForeignSchemaType::FDW_IMPORT_SCHEMA_LIMIT_TO => {
+                ListType::FdwImportSchemaLimitTo
+            }
+            pgrx::pg_sys::ImportForeignSchemaType::FDW_IMPORT_SCHEMA_EXCEPT => {
+                ListType::FdwImportSchemaExcept
+            }
+            // This should not happen, it's okay to default to FdwImportSchemaAll
+            // because PostgreSQL will filter the list anyway.
+            _ => ListType::FdwImportSchemaAll,
+        },
+
+        table_list: {
+            let tables: PgList<pg_sys::RangeVar> = PgList::from_pg((*stmt).table_list);
+            tables
+                .iter_ptr()
+                .map(|item| {
+                    std::ffi::CStr::from_ptr(item.as_mut().unwrap().relname)
+                        .to_str()
+                        .unwrap()
+                        .to_string()
+                })
+                .collect()
+        },
+
+        options: options_to_hashmap((*stmt).options).unwrap(),
+    }
+}
+
+// convert the statements to a list returned to Postgres
+fn to_pg_list(create_stmts: Vec<String>) -> *mut pg_sys::List {
+    let mut ret: PgList<std::ffi::c_char> = PgList::new();
+    for command in create_stmts {
+        ret.push(command.as_pg_cstr());
+    }
+
+    ret.into_pg()
+}
+
 #[pg_guard]
 pub(super) extern "C" fn import_foreign_schema<E: Into<ErrorReport>, W: ForeignDataWrapper<E>>(
     stmt: *mut pg_sys::ImportForeignSchemaStmt,
@@ -48,64 +231,37 @@ pub(super) extern "C" fn import_foreign_schema<E: Into<ErrorReport>, W: ForeignD
 ) -> *mut pg_sys::List {
     debug2!("---> import_foreign_schema");
 
-    let create_stmts: Vec<String>;
-
-    unsafe {
-        let import_foreign_schema_stmt = ImportForeignSchemaStmt {
-            server_name: std::ffi::CStr::from_ptr((*stmt).server_name)
-                .to_str()
-                .unwrap()
-                .to_string(),
-            remote_schema: std::ffi::CStr::from_ptr((*stmt).remote_schema)
-                .to_str()
-                .unwrap()
-                .to_string(),
-            local_schema: std::ffi::CStr::from_ptr((*stmt).local_schema)
-                .to_str()
-                .unwrap()
-                .to_string(),
-
-            list_type: match (*stmt).list_type {
-                pgrx::pg_sys::ImportForeignSchemaType::FDW_IMPORT_SCHEMA_ALL => {
-                    ListType::FdwImportSchemaAll
-                }
-                pgrx::pg_sys::ImportForeignSchemaType::FDW_IMPORT_SCHEMA_LIMIT_TO => {
-                    ListType::FdwImportSchemaLimitTo
-                }
-                pgrx::pg_sys::ImportForeignSchemaType::FDW_IMPORT_SCHEMA_EXCEPT => {
-                    ListType::FdwImportSchemaExcept
-                }
-                // This should not happen, it's okay to default to FdwImportSchemaAll
-                // because PostgreSQL will filter the list anyway.
-                _ => ListType::FdwImportSchemaAll,
-            },
-
-            table_list: {
-                let tables: PgList<pg_sys::RangeVar> = PgList::from_pg((*stmt).table_list);
-                tables
-                    .iter_ptr()
-                    .map(|item| {
-                        std::ffi::CStr::from_ptr(item.as_mut().unwrap().relname)
-                            .to_str()
-                            .unwrap()
-                            .to_string()
-                    })
-                    .collect()
-            },
-
-            options: options_to_hashmap((*stmt).options).unwrap(),
-        };
-
+    let create_stmts = unsafe {
+        let import_foreign_schema_stmt = import_stmt(stmt);
         let mut state = FdwState::<E, W>::new(server_oid);
-        create_stmts = state
+        state
             .instance
-            .import_foreign_schema(import_foreign_schema_stmt);
-    }
+            .import_foreign_schema(import_foreign_schema_stmt)
+            .report_unwrap()
+    };
 
-    let mut ret: PgList<std::ffi::c_char> = PgList::new();
-    for command in create_stmts {
-        ret.push(command.as_pg_cstr());
-    }
+    to_pg_list(create_stmts)
+}
 
-    ret.into_pg()
+#[pg_guard]
+extern "C" fn import_remote_tables<E: Into<ErrorReport>, W: ListTables<E>>(
+    stmt: *mut pg_sys::ImportForeignSchemaStmt,
+    server_oid: pg_sys::Oid,
+) -> *mut pg_sys::List {
+    debug2!("---> import_remote_tables");
+
+    let create_stmts = unsafe {
+        let stmt = import_stmt(stmt);
+        let mut state = FdwState::<E, W>::new(server_oid);
+        state
+            .instance
+            .list_tables(&stmt)
+            .report_unwrap()
+            .iter()
+            .filter(|table| stmt.is_table_included(&table.name))
+            .map(|table| stmt.create_foreign_table(&table.name, &table.columns, &table.options))
+            .collect()
+    };
+
+    to_pg_list(create_stmts)
 }
@@ -6,19 +6,61 @@ use pgrx::pg_sys::panic::ErrorReport;
 use pgrx::prelude::*;
 
 pub struct ForeignServer {
+    pub server_oid: pg_sys::Oid,
     pub server_name: String,
     pub server_type: Option<String>,
     pub server_version: Option<String>,
     pub options: HashMap<String, String>,
+
+    /// options of the user mapping for current user, or for PUBLIC if the
+    /// user has no mapping, it is empty if there is no user mapping
+    pub user_mapping_options: HashMap<String, String>,
 }
 
-// create a fdw instance from its id
-pub(super) unsafe fn create_fdw_instance_from_server_id<
-    E: Into<ErrorReport>,
-    W: ForeignDataWrapper<E>,
->(
-    fserver_id: pg_sys::Oid,
-) -> W {
+impl ForeignServer {
+    /// Get the options holding the credentials, like `api_key` and
+    /// `api_key_id`
+    ///
+    /// The credentials in user mapping take precedence, so each Postgres role
+    /// can use its own credentials. The user mapping options are returned if
+    /// they contain any of the `names`, otherwise the server options are
+    /// returned.
+    pub fn credential_options(&self, names: &[&str]) -> &HashMap<String, String> {
+        if names
+            .iter()
+            .any(|name| self.user_mapping_options.contains_key(*name))
+        {
+            &self.user_mapping_options
+        } else {
+            &self.options
+        }
+    }
+}
+
+// get options of the user mapping for current user on the foreign server
+unsafe fn get_user_mapping_options(fserver_id: pg_sys::Oid) -> HashMap<String, String> {
+    // GetUserMapping() raises an error if there is no user mapping, so check
+    // it exists first
+    let user_id = pg_sys::GetUserId();
+    let exists = [user_id, pg_sys::InvalidOid].iter().any(|uid| {
+        pg_sys::SearchSysCacheExists(
+            pg_sys::SysCacheIdentifier::USERMAPPINGUSERSERVER as _,
+            uid.into_datum().unwrap(),
+            fserver_id.into_datum().unwrap(),
+            pg_sys::Datum::from(0usize),
+            pg_sys::Datum::from(0usize),
+        )
+    });
+    if !exists {
+        return HashMap::new();
+    }
+
+    let user_mapping = pg_sys::GetUserMapping(user_id, fserver_id);
+    options_to_hashmap((*user_mapping).options).report_unwrap()
+}
+
+// get the foreign server and its user mapping options for current user
+pub(super) unsafe fn get_foreign_server(fserver_id: pg_sys::Oid) -> ForeignServer {
     let to_string = |raw: *mut std::ffi::c_char| -> Option<String> {
         if raw.is_null() {
             return None;
@@ -37,12 +79,26 @@ pub(super) unsafe fn create_fdw_instance_from_server_id<
     };
     let fserver = pg_sys::GetForeignServer(fserver_id);
     let server = ForeignServer {
+        server_oid: fserver_id,
         server_name: to_string((*fserver).servername).unwrap(),
         server_type: to_string((*fserver).servertype),
         server_version: to_string((*fserver).serverversion),
         options: options_to_hashmap((*fserver).options).report_unwrap(),
+        user_mapping_options: get_user_mapping_options(fserver_id),
     };
-    let wrapper = W::new(server);
+    register_sensitive_options(&server.options);
+    register_sensitive_options(&server.user_mapping_options);
+    server
+}
+
+// create a fdw instance from its id
+pub(super) unsafe fn create_fdw_instance_from_server_id<
+    E: Into<ErrorReport>,
+    W: ForeignDataWrapper<E>,
+>(
+    fserver_id: pg_sys::Oid,
+) -> W {
+    let wrapper = W::new(get_foreign_server(fserver_id));
     wrapper.report_unwrap()
 }
 
@@ -2,22 +2,33 @@
 //!
 
 use crate::instance::ForeignServer;
+use crate::utils::{escape_like_pattern, format_cell, report_error, SqlDialect};
 use crate::FdwRoutine;
 use pgrx::pg_sys::panic::ErrorReport;
-use pgrx::prelude::{Date, Interval, Time, Timestamp, TimestampWithTimeZone};
+use pgrx::prelude::{Date, Interval, PgHeapTuple, Time, Timestamp, TimestampWithTimeZone};
 use pgrx::{
-    datum::Uuid,
+    datum::{Range, RangeSubType, Uuid},
     fcinfo,
-    pg_sys::{self, bytea, BuiltinOid, Datum, Oid},
-    AllocatedByRust, AnyNumeric, FromDatum, IntoDatum, JsonB, PgBuiltInOids, PgOid,
+    pg_sys::{self, BuiltinOid, Datum, Oid},
+    AllocatedByRust, AnyNumeric, FromDatum, IntoDatum, JsonB, PgBuiltInOids, PgOid, PgSqlErrorCode,
+    PgTupleDesc,
 };
 use std::collections::HashMap;
 use std::ffi::CStr;
 use std::fmt;
 use std::iter::Zip;
 use std::mem;
+use std::num::NonZeroUsize;
 use std::slice::Iter;
 
+pub mod v2;
+
+/// Version 1 of the foreign data wrapper interface, which is the
+/// [`ForeignDataWrapper`] trait
+pub mod v1 {
+    pub use super::ForeignDataWrapper;
+}
+
 // fdw system catalog oids
 // https://doxygen.postgresql.org/pg__foreign__data__wrapper_8h.html
 // https://doxygen.postgresql.org/pg__foreign__server_8h.html
@@ -32,7 +43,17 @@ pub const FOREIGN_SERVER_RELATION_ID: Oid = BuiltinOid::ForeignServerRelationId.
 /// Constant can be used in [validator](ForeignDataWrapper::validator)
 pub const FOREIGN_TABLE_RELATION_ID: Oid = BuiltinOid::ForeignTableRelationId.value();
 
+/// Constant can be used in [validator](ForeignDataWrapper::validator)
+pub const USER_MAPPING_RELATION_ID: Oid = BuiltinOid::UserMappingRelationId.value();
+
+/// Constant can be used in [validator](ForeignDataWrapper::validator), for
+/// the column options
+pub const ATTRIBUTE_RELATION_ID: Oid = BuiltinOid::AttributeRelationId.value();
+
 /// A data cell in a data row
+///
+/// A [`Composite`](Cell::Composite) cell is a value of user-defined composite
+/// type, its cells are matched to the type attributes by column name.
 #[derive(Debug)]
 pub enum Cell {
     Bool(bool),
@@ -50,7 +71,7 @@ pub enum Cell {
     Timestamptz(TimestampWithTimeZone),
     Interval(Interval),
     Json(JsonB),
-    Bytea(*mut bytea),
+    Bytea(Vec<u8>),
     Uuid(Uuid),
     BoolArray(Vec<Option<bool>>),
     I16Array(Vec<Option<i16>>),
@@ -59,6 +80,13 @@ pub enum Cell {
     F32Array(Vec<Option<f32>>),
     F64Array(Vec<Option<f64>>),
     StringArray(Vec<Option<String>>),
+    I32Range(Range<i32>),
+    I64Range(Range<i64>),
+    NumericRange(Range<AnyNumeric>),
+    DateRange(Range<Date>),
+    TimestampRange(Range<Timestamp>),
+    TimestamptzRange(Range<TimestampWithTimeZone>),
+    Composite(Row),
 }
 
 impl Clone for Cell {
@@ -79,7 +107,7 @@ impl Clone for Cell {
             Cell::Timestamptz(v) => Cell::Timestamptz(*v),
             Cell::Interval(v) => Cell::Interval(*v),
             Cell::Json(v) => Cell::Json(JsonB(v.0.clone())),
-            Cell::Bytea(v) => Cell::Bytea(*v),
+            Cell::Bytea(v) => Cell::Bytea(v.clone()),
             Cell::Uuid(v) => Cell::Uuid(*v),
             Cell::BoolArray(v) => Cell::BoolArray(v.clone()),
             Cell::I16Array(v) => Cell::I16Array(v.clone()),
@@ -88,6 +116,13 @@ impl Clone for Cell {
             Cell::F32Array(v) => Cell::F32Array(v.clone()),
             Cell::F64Array(v) => Cell::F64Array(v.clone()),
             Cell::StringArray(v) => Cell::StringArray(v.clone()),
+            Cell::I32Range(v) => Cell::I32Range(v.clone()),
+            Cell::I64Range(v) => Cell::I64Range(v.clone()),
+            Cell::NumericRange(v) => Cell::NumericRange(v.clone()),
+            Cell::DateRange(v) => Cell::DateRange(v.clone()),
+            Cell::TimestampRange(v) => Cell::TimestampRange(v.clone()),
+            Cell::TimestamptzRange(v) => Cell::TimestamptzRange(v.clone()),
+            Cell::Composite(v) => Cell::Composite(v.clone()),
         }
     }
 }
@@ -107,6 +142,26 @@ fn write_array<T: std::fmt::Display>(
     write!(f, "[{}]", res)
 }
 
+fn write_range<T: RangeSubType>(range: &Range<T>, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    unsafe {
+        let datum = range
+            .clone()
+            .into_datum()
+            .expect("cell should be a valid range");
+        let mut typoutput = pg_sys::InvalidOid;
+        let mut typvarlena = false;
+        pg_sys::getTypeOutputInfo(T::range_type_oid(), &mut typoutput, &mut typvarlena);
+        let text = pg_sys::OidOutputFunctionCall(typoutput, datum);
+        write!(
+            f,
+            "'{}'",
+            CStr::from_ptr(text)
+                .to_str()
+                .expect("range should be a valid string")
+        )
+    }
+}
+
 impl fmt::Display for Cell {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -171,11 +226,10 @@ impl fmt::Display for Cell {
                         .expect("timestamptz should be a valid string")
                 )
             },
-            Cell::Interval(v) => write!(f, "{}", v),
+            Cell::Interval(v) => write!(f, "'{}'", v),
             Cell::Json(v) => write!(f, "{:?}", v),
             Cell::Bytea(v) => {
-                let byte_u8 = unsafe { pgrx::varlena::varlena_to_byte_slice(*v) };
-                let hex = byte_u8
+                let hex = v
                     .iter()
                     .map(|b| format!("{:02X}", b))
                     .collect::<Vec<String>>()
@@ -186,14 +240,38 @@ impl fmt::Display for Cell {
                     write!(f, r#"'\x{}'"#, hex)
                 }
             }
-            Cell::Uuid(v) => write!(f, "{}", v),
+            Cell::Uuid(v) => write!(f, "'{}'", v),
             Cell::BoolArray(v) => write_array(v, f),
             Cell::I16Array(v) => write_array(v, f),
             Cell::I32Array(v) => write_array(v, f),
             Cell::I64Array(v) => write_array(v, f),
             Cell::F32Array(v) => write_array(v, f),
             Cell::F64Array(v) => write_array(v, f),
-            Cell::StringArray(v) => write_array(v, f),
+            Cell::StringArray(v) => {
+                let quoted = v
+                    .iter()
+                    .map(|s| s.as_ref().map(|s| format!("'{}'", s)))
+                    .collect::<Vec<_>>();
+                write_array(&quoted, f)
+            }
+            Cell::I32Range(v) => write_range(v, f),
+            Cell::I64Range(v) => write_range(v, f),
+            Cell::NumericRange(v) => write_range(v, f),
+            Cell::DateRange(v) => write_range(v, f),
+            Cell::TimestampRange(v) => write_range(v, f),
+            Cell::TimestamptzRange(v) => write_range(v, f),
+            Cell::Composite(v) => {
+                let fields = v
+                    .cells
+                    .iter()
+                    .map(|c| match c {
+                        Some(cell) => format!("{}", cell),
+                        None => "null".to_owned(),
+                    })
+                    .collect::<Vec<String>>()
+                    .join(",");
+                write!(f, "ROW({})", fields)
+            }
         }
     }
 }
@@ -216,7 +294,7 @@ impl IntoDatum for Cell {
             Cell::Timestamptz(v) => v.into_datum(),
             Cell::Interval(v) => v.into_datum(),
             Cell::Json(v) => v.into_datum(),
-            Cell::Bytea(v) => Some(Datum::from(v)),
+            Cell::Bytea(v) => v.into_datum(),
             Cell::Uuid(v) => v.into_datum(),
             Cell::BoolArray(v) => v.into_datum(),
             Cell::I16Array(v) => v.into_datum(),
@@ -225,6 +303,15 @@ impl IntoDatum for Cell {
             Cell::F32Array(v) => v.into_datum(),
             Cell::F64Array(v) => v.into_datum(),
             Cell::StringArray(v) => v.into_datum(),
+            Cell::I32Range(v) => v.into_datum(),
+            Cell::I64Range(v) => v.into_datum(),
+            Cell::NumericRange(v) => v.into_datum(),
+            Cell::DateRange(v) => v.into_datum(),
+            Cell::TimestampRange(v) => v.into_datum(),
+            Cell::TimestamptzRange(v) => v.into_datum(),
+            // composite cell needs the target type to build its datum, it is
+            // converted when it is stored to the tuple slot
+            Cell::Composite(_) => None,
         }
     }
 
@@ -258,6 +345,12 @@ impl IntoDatum for Cell {
             || other == pg_sys::FLOAT4ARRAYOID
             || other == pg_sys::FLOAT8ARRAYOID
             || other == pg_sys::TEXTARRAYOID
+            || other == pg_sys::INT4RANGEOID
+            || other == pg_sys::INT8RANGEOID
+            || other == pg_sys::NUMRANGEOID
+            || other == pg_sys::DATERANGEOID
+            || other == pg_sys::TSRANGEOID
+            || other == pg_sys::TSTZRANGEOID
     }
 }
 
@@ -312,7 +405,7 @@ impl FromDatum for Cell {
                 JsonB::from_datum(datum, is_null).map(Cell::Json)
             }
             PgOid::BuiltIn(PgBuiltInOids::BYTEAOID) => {
-                Some(Cell::Bytea(datum.cast_mut_ptr::<bytea>()))
+                Vec::<u8>::from_datum(datum, is_null).map(Cell::Bytea)
             }
             PgOid::BuiltIn(PgBuiltInOids::UUIDOID) => {
                 Uuid::from_datum(datum, is_null).map(Cell::Uuid)
@@ -338,9 +431,67 @@ impl FromDatum for Cell {
             PgOid::BuiltIn(PgBuiltInOids::TEXTARRAYOID) => {
                 Vec::<Option<String>>::from_datum(datum, false).map(Cell::StringArray)
             }
-            _ => None,
+            PgOid::BuiltIn(PgBuiltInOids::INT4RANGEOID) => {
+                Range::<i32>::from_datum(datum, is_null).map(Cell::I32Range)
+            }
+            PgOid::BuiltIn(PgBuiltInOids::INT8RANGEOID) => {
+                Range::<i64>::from_datum(datum, is_null).map(Cell::I64Range)
+            }
+            PgOid::BuiltIn(PgBuiltInOids::NUMRANGEOID) => {
+                Range::<AnyNumeric>::from_datum(datum, is_null).map(Cell::NumericRange)
+            }
+            PgOid::BuiltIn(PgBuiltInOids::DATERANGEOID) => {
+                Range::<Date>::from_datum(datum, is_null).map(Cell::DateRange)
+            }
+            PgOid::BuiltIn(PgBuiltInOids::TSRANGEOID) => {
+                Range::<Timestamp>::from_datum(datum, is_null).map(Cell::TimestampRange)
+            }
+            PgOid::BuiltIn(PgBuiltInOids::TSTZRANGEOID) => {
+                Range::<TimestampWithTimeZone>::from_datum(datum, is_null)
+                    .map(Cell::TimestamptzRange)
+            }
+            _ => {
+                // user-defined enum is converted to its text, composite type is
+                // converted field-by-field, and domain is converted as its base type
+                if is_null || typoid.as_u32() < pg_sys::FirstNormalObjectId {
+                    return None;
+                }
+                let base_oid = pg_sys::getBaseType(typoid);
+                if pg_sys::type_is_enum(base_oid) {
+                    let mut typoutput = pg_sys::InvalidOid;
+                    let mut typvarlena = false;
+                    pg_sys::getTypeOutputInfo(base_oid, &mut typoutput, &mut typvarlena);
+                    let text = pg_sys::OidOutputFunctionCall(typoutput, datum);
+                    Some(Cell::String(
+                        CStr::from_ptr(text).to_string_lossy().into_owned(),
+                    ))
+                } else if pg_sys::type_is_rowtype(base_oid) {
+                    composite_from_datum(datum, base_oid)
+                } else if base_oid != typoid {
+                    Self::from_polymorphic_datum(datum, is_null, base_oid)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+// convert a composite datum to composite cell, dropped attributes are skipped
+unsafe fn composite_from_datum(datum: Datum, typoid: Oid) -> Option<Cell> {
+    let tupdesc = PgTupleDesc::for_composite_type_by_oid(typoid)?;
+    let htup = PgHeapTuple::from_composite_datum(datum).into_pg();
+    let mut row = Row::new();
+    for (idx, att) in tupdesc.iter().enumerate() {
+        if att.is_dropped() {
+            continue;
         }
+        let attno = NonZeroUsize::new(idx + 1).unwrap();
+        let cell = pgrx::heap_getattr_raw(htup, attno, tupdesc.as_ptr())
+            .and_then(|v| Cell::from_polymorphic_datum(v, false, att.atttypid));
+        row.push(att.name(), cell);
     }
+    Some(Cell::Composite(row))
 }
 
 pub trait CellFormatter {
@@ -429,6 +580,20 @@ pub struct Column {
 
     /// column type OID, can be used to match pg_sys::BuiltinOid
     pub type_oid: Oid,
+
+    /// column options defined in `CREATE FOREIGN TABLE`, e.g. `name text options (field 'user_name')`
+    pub options: HashMap<String, String>,
+}
+
+impl Column {
+    /// Check if the column has the `coerce 'text'` option
+    ///
+    /// The value of such a column can be returned in any cell type, Wrappers
+    /// stringifies it to the column's `text` or `jsonb` type instead of failing.
+    /// FDWs can check this to fetch unsupported remote types as text.
+    pub fn coerce_to_text(&self) -> bool {
+        self.options.get("coerce").is_some_and(|v| v == "text")
+    }
 }
 
 /// A restiction value used in [`Qual`], either a [`Cell`] or an array of [`Cell`]
@@ -438,14 +603,27 @@ pub enum Value {
     Array(Vec<Cell>),
 }
 
+/// Query parameter kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamKind {
+    /// external parameter, e.g. `$1` in prepared statement
+    Extern,
+
+    /// executor parameter, e.g. outer row value in parameterized nested loop join
+    Exec,
+}
+
 /// Query parameter
 #[derive(Debug, Clone)]
 pub struct Param {
-    /// 1-based parameter id
+    /// parameter id, 1-based for external parameter and 0-based for executor parameter
     pub id: usize,
 
     /// parameter type OID
     pub type_oid: Oid,
+
+    /// parameter kind
+    pub kind: ParamKind,
 }
 
 /// Query restrictions, a.k.a conditions in `WHERE` clause
@@ -454,7 +632,7 @@ pub struct Param {
 /// of the results.
 ///
 /// <div class="example-wrap" style="display:inline-block"><pre class="compile_fail" style="white-space:normal;font:inherit;">
-/// <strong>Warning</strong>: Currently only simple conditions are supported, see below for examples. Other kinds of conditions, like JSON attribute filter e.g. `where json_col->>'key' = 'value'`, are not supported yet.
+/// <strong>Warning</strong>: Currently only simple conditions are supported, see below for examples. Other kinds of conditions, like function call e.g. `where lower(col) = 'foo'`, are not supported yet.
 /// </pre></div>
 ///
 /// ## Examples
@@ -470,11 +648,63 @@ pub struct Param {
 /// ```
 ///
 /// ```sql
+/// where id not in (1, 2);
+/// -- [Qual { field: "id", operator: "<>", value: Array([I64(1), I64(2)]), use_or: false }]
+/// ```
+///
+/// ```sql
+/// where id = any($1);
+/// -- [Qual { field: "id", operator: "=", value: Array([I64(1), I64(2)]), use_or: true, param: Some(Param { id: 1, .. }) }]
+/// ```
+///
+/// ```sql
+/// where id between 1 and 3;
+/// -- [
+/// --   Qual { field: "id", operator: ">=", value: Cell(I32(1)), use_or: false },
+/// --   Qual { field: "id", operator: "<=", value: Cell(I32(3)), use_or: false }
+/// -- ]
+/// ```
+///
+/// `between symmetric` with constant bounds is decomposed in the same way, with
+/// the bounds sorted.
+///
+/// ```sql
+/// where id between symmetric 3 and 1;
+/// -- [
+/// --   Qual { field: "id", operator: ">=", value: Cell(I32(1)), use_or: false },
+/// --   Qual { field: "id", operator: "<=", value: Cell(I32(3)), use_or: false }
+/// -- ]
+/// ```
+///
+/// ```sql
+/// where col like 'foo%';
+/// -- [Qual { field: "col", operator: "~~", value: Cell(String("foo%")), use_or: false }]
+/// ```
+///
+/// ```sql
+/// where col not ilike 'foo%';
+/// -- [Qual { field: "col", operator: "!~~*", value: Cell(String("foo%")), use_or: false }]
+/// ```
+///
+/// ```sql
 /// where col is null
 /// -- [Qual { field: "col", operator: "is", value: Cell(String("null")), use_or: false }]
 /// ```
 ///
 /// ```sql
+/// where col is not null
+/// -- [Qual { field: "col", operator: "is not", value: Cell(String("null")), use_or: false }]
+/// ```
+///
+/// If the FDW accepts conditions on json attributes, see [`ForeignDataWrapper::json_path_quals`],
+/// the attribute path is in `json_path`.
+///
+/// ```sql
+/// where attrs->'address'->>'city' = 'Sydney';
+/// -- [Qual { field: "attrs", operator: "=", value: Cell(String("Sydney")), use_or: false, json_path: ["address", "city"] }]
+/// ```
+///
+/// ```sql
 /// where bool_col
 /// -- [Qual { field: "bool_col", operator: "=", value: Cell(Bool(true)), use_or: false }]
 /// ```
@@ -491,6 +721,38 @@ pub struct Param {
 /// --   Qual { field: "col", operator: "=", value: Cell(String("foo")), use_or: false }
 /// -- ]
 /// ```
+///
+/// If the FDW accepts boolean expression tree, see [`ForeignDataWrapper::bool_expr_tree`],
+/// conditions connected by `OR` are grouped into one qual with `or` operator
+/// and an empty field name, each of its sub-conditions is in `sub_quals`. The
+/// whole group is not pushed down if any of its sub-conditions is unsupported.
+///
+/// ```sql
+/// where id = 1 or col = 'foo';
+/// -- [
+/// --   Qual { field: "", operator: "or", value: Array([]), use_or: false, sub_quals: [
+/// --     Qual { field: "id", operator: "=", value: Cell(I32(1)), use_or: false },
+/// --     Qual { field: "col", operator: "=", value: Cell(String("foo")), use_or: false }
+/// --   ]}
+/// -- ]
+/// ```
+///
+/// Nested `AND` and `NOT` expressions are also grouped in the same way.
+///
+/// ```sql
+/// where (id = 1 and col = 'foo') or not (col ~ 'bar');
+/// -- [
+/// --   Qual { field: "", operator: "or", value: Array([]), use_or: false, sub_quals: [
+/// --     Qual { field: "", operator: "and", value: Array([]), use_or: false, sub_quals: [
+/// --       Qual { field: "id", operator: "=", value: Cell(I32(1)), use_or: false },
+/// --       Qual { field: "col", operator: "=", value: Cell(String("foo")), use_or: false }
+/// --     ]},
+/// --     Qual { field: "", operator: "not", value: Array([]), use_or: false, sub_quals: [
+/// --       Qual { field: "col", operator: "~", value: Cell(String("bar")), use_or: false }
+/// --     ]}
+/// --   ]}
+/// -- ]
+/// ```
 #[derive(Debug, Clone)]
 pub struct Qual {
     pub field: String,
@@ -498,45 +760,228 @@ pub struct Qual {
     pub value: Value,
     pub use_or: bool,
     pub param: Option<Param>,
+
+    /// sub-conditions of a boolean expression qual, empty for simple conditions
+    pub sub_quals: Vec<Qual>,
+
+    /// json path if the condition is on an attribute of json column, e.g.
+    /// `["a", "b"]` for `attrs->'a'->>'b'`, empty for simple conditions
+    pub json_path: Vec<String>,
+
+    /// type of the value operand, e.g. `INT8OID` for `id = 42::bigint`, it is
+    /// the element type for array value and invalid for boolean expression
+    pub type_oid: pg_sys::Oid,
+
+    /// collation name if a non-default collation is used by the operator, e.g.
+    /// `Some("C")` for `col = 'foo' collate "C"`
+    pub collate: Option<String>,
 }
 
 impl Qual {
+    /// Check if this qual is a boolean expression which has sub-conditions
+    pub fn is_bool_expr(&self) -> bool {
+        !self.sub_quals.is_empty()
+    }
+
     pub fn deparse(&self) -> String {
         let mut formatter = DefaultFormatter::new();
         self.deparse_with_fmt(&mut formatter)
     }
 
+    /// Deparse a boolean expression qual, each sub-condition is deparsed by `f`
+    ///
+    /// For example, `(a = 1 and b = 2)` or `not (a = 1)`. This is useful for the
+    /// FDW which needs to customize deparsing of the simple conditions.
+    pub fn deparse_bool_expr<F: FnMut(&Qual) -> String>(&self, mut f: F) -> String {
+        let conds: Vec<String> = self.sub_quals.iter().map(&mut f).collect();
+        if self.operator == "not" {
+            format!("not ({})", conds.join(" and "))
+        } else {
+            format!("({})", conds.join(&format!(" {} ", self.operator)))
+        }
+    }
+
+    // deparse field with its json path, e.g. "(attrs #>> '{a,b}')"
+    fn deparse_field(&self) -> String {
+        if self.json_path.is_empty() {
+            return self.field.clone();
+        }
+
+        let path: Vec<String> = self
+            .json_path
+            .iter()
+            .map(|key| format!("\"{}\"", key.replace('\\', "\\\\").replace('"', "\\\"")))
+            .collect();
+        let opr = if matches!(self.value, Value::Cell(Cell::Json(_))) {
+            "#>"
+        } else {
+            "#>>"
+        };
+        format!(
+            "({} {} '{{{}}}')",
+            self.field,
+            opr,
+            path.join(",").replace('\'', "''")
+        )
+    }
+
     pub fn deparse_with_fmt<T: CellFormatter>(&self, t: &mut T) -> String {
-        if self.use_or {
-            match &self.value {
-                Value::Cell(_) => unreachable!(),
-                Value::Array(cells) => {
-                    let conds: Vec<String> = cells
-                        .iter()
-                        .map(|cell| {
-                            format!("{} {} {}", self.field, self.operator, t.fmt_cell(cell))
-                        })
-                        .collect();
-                    conds.join(" or ")
+        if self.is_bool_expr() {
+            return self.deparse_bool_expr(|qual| qual.deparse_with_fmt(t));
+        }
+
+        let field = self.deparse_field();
+
+        match &self.value {
+            Value::Cell(cell) => match self.operator.as_str() {
+                "is" | "is not" if self.is_null_test() => {
+                    format!("{} {} null", field, self.operator)
+                }
+                "~~" => format!("{} like {}", field, t.fmt_cell(cell)),
+                "!~~" => format!("{} not like {}", field, t.fmt_cell(cell)),
+                "~~*" => format!("{} ilike {}", field, t.fmt_cell(cell)),
+                "!~~*" => format!("{} not ilike {}", field, t.fmt_cell(cell)),
+                _ => format!("{} {} {}", field, self.operator, t.fmt_cell(cell)),
+            },
+            Value::Array(cells) => {
+                // empty list, e.g. "col = any('{}')" is always false and
+                // "col <> all('{}')" is always true
+                if cells.is_empty() {
+                    return if self.use_or { "false" } else { "true" }.to_string();
+                }
+
+                let list: Vec<String> = cells.iter().map(|cell| t.fmt_cell(cell)).collect();
+                match (self.operator.as_str(), self.use_or) {
+                    // "col = any(array)", a.k.a. "col in (list)"
+                    ("=", true) => format!("{} in ({})", field, list.join(", ")),
+                    // "col <> all(array)", a.k.a. "col not in (list)"
+                    ("<>", false) => format!("{} not in ({})", field, list.join(", ")),
+                    _ => {
+                        let conds: Vec<String> = list
+                            .iter()
+                            .map(|v| format!("{} {} {}", field, self.operator, v))
+                            .collect();
+                        let sep = if self.use_or { " or " } else { " and " };
+                        format!("({})", conds.join(sep))
+                    }
                 }
             }
-        } else {
-            match &self.value {
-                Value::Cell(cell) => match self.operator.as_str() {
-                    "is" | "is not" => match cell {
-                        Cell::String(cell) if cell == "null" => {
-                            format!("{} {} null", self.field, self.operator)
-                        }
-                        _ => format!("{} {} {}", self.field, self.operator, t.fmt_cell(cell)),
-                    },
-                    "~~" => format!("{} like {}", self.field, t.fmt_cell(cell)),
-                    "!~~" => format!("{} not like {}", self.field, t.fmt_cell(cell)),
-                    _ => format!("{} {} {}", self.field, self.operator, t.fmt_cell(cell)),
-                },
-                Value::Array(_) => unreachable!(),
+        }
+    }
+
+    /// Format the value of this qual as a SQL literal for the given dialect,
+    /// array value is formatted as a list, e.g. `(1, 2, 3)`
+    ///
+    /// See [`format_cell`] for how each cell is formatted.
+    pub fn fmt_value(&self, dialect: SqlDialect) -> String {
+        match &self.value {
+            Value::Cell(cell) => format_cell(cell, dialect),
+            Value::Array(cells) => {
+                let list: Vec<String> = cells.iter().map(|c| format_cell(c, dialect)).collect();
+                format!("({})", list.join(", "))
             }
         }
     }
+
+    /// Check if this qual is an IN-list predicate, e.g. `col in (1, 2, 3)` or
+    /// `col = any(array[1, 2, 3])`
+    pub fn is_in_list(&self) -> bool {
+        self.operator == "=" && self.use_or && matches!(self.value, Value::Array(_))
+    }
+
+    /// Check if this qual is a range comparison, e.g. `col > 1` or `col <= 3`
+    pub fn is_range(&self) -> bool {
+        matches!(self.operator.as_str(), "<" | "<=" | ">" | ">=")
+            && matches!(self.value, Value::Cell(_))
+    }
+
+    /// Deparse this qual and `upper` as a `between` predicate
+    ///
+    /// For example, `col >= 1` and `col <= 3` are deparsed to `col between 1 and 3`.
+    /// Returns `None` if they are not the inclusive lower and upper bounds on the
+    /// same field.
+    pub fn deparse_between<T: CellFormatter>(&self, upper: &Qual, t: &mut T) -> Option<String> {
+        let (Value::Cell(lower_value), Value::Cell(upper_value)) = (&self.value, &upper.value)
+        else {
+            return None;
+        };
+        if self.operator != ">="
+            || upper.operator != "<="
+            || self.field != upper.field
+            || self.json_path != upper.json_path
+        {
+            return None;
+        }
+        Some(format!(
+            "{} between {} and {}",
+            self.deparse_field(),
+            t.fmt_cell(lower_value),
+            t.fmt_cell(upper_value)
+        ))
+    }
+
+    /// Check if this qual is a null test, e.g. `col is null` or `col is not null`
+    pub fn is_null_test(&self) -> bool {
+        matches!(self.operator.as_str(), "is" | "is not")
+            && matches!(&self.value, Value::Cell(Cell::String(s)) if s == "null")
+    }
+
+    /// Check if this qual is a pattern matching predicate, e.g. `col like 'foo%'`
+    /// or `col not ilike 'foo%'`
+    pub fn is_like(&self) -> bool {
+        matches!(self.operator.as_str(), "~~" | "!~~" | "~~*" | "!~~*")
+            && matches!(self.value, Value::Cell(Cell::String(_)))
+    }
+
+    /// Deparse a pattern matching qual for the given SQL dialect
+    ///
+    /// The pattern is escaped by [`escape_like_pattern`]. For dialects which
+    /// don't support `ilike`, both sides are converted to lower case, for
+    /// example, `col ilike 'Foo%'` is deparsed to `lower(col) like 'foo%'`.
+    /// Returns `None` if this qual is not a pattern matching predicate.
+    pub fn deparse_like(&self, dialect: SqlDialect) -> Option<String> {
+        let Value::Cell(Cell::String(pattern)) = &self.value else {
+            return None;
+        };
+        if !self.is_like() {
+            return None;
+        }
+
+        let not = if self.is_not_like() { " not" } else { "" };
+        let sql = match (self.is_ilike(), dialect) {
+            (true, SqlDialect::Postgres | SqlDialect::ClickHouse) => format!(
+                "{}{} ilike {}",
+                self.field,
+                not,
+                escape_like_pattern(pattern, dialect)
+            ),
+            (true, SqlDialect::BigQuery | SqlDialect::SqlServer) => format!(
+                "lower({}){} like {}",
+                self.field,
+                not,
+                escape_like_pattern(&pattern.to_lowercase(), dialect)
+            ),
+            (false, _) => format!(
+                "{}{} like {}",
+                self.field,
+                not,
+                escape_like_pattern(pattern, dialect)
+            ),
+        };
+        Some(sql)
+    }
+
+    /// Check if this qual is a negated pattern matching predicate, e.g.
+    /// `col not like 'foo%'`
+    pub fn is_not_like(&self) -> bool {
+        self.is_like() && self.operator.starts_with('!')
+    }
+
+    /// Check if this qual is a case-insensitive pattern matching predicate,
+    /// e.g. `col ilike 'foo%'`
+    pub fn is_ilike(&self) -> bool {
+        self.is_like() && self.operator.ends_with('*')
+    }
 }
 
 /// Query sort, a.k.a `ORDER BY` clause
@@ -617,6 +1062,12 @@ impl Sort {
 /// limit 42 offset 7;
 /// -- Limit { count: 42, offset: 7 }
 /// ```
+///
+/// The `OFFSET` is only applied remotely if the limit is accepted by
+/// [`plan_limit`](ForeignDataWrapper::plan_limit), otherwise Postgres skips
+/// the offset rows locally, and the limit passed to
+/// [`begin_scan`](ForeignDataWrapper::begin_scan) has the offset added to
+/// `count` and a zero `offset`, for example, `Limit { count: 49, offset: 0 }`.
 #[derive(Debug, Clone, Default)]
 pub struct Limit {
     pub count: i64,
@@ -629,6 +1080,303 @@ impl Limit {
     }
 }
 
+/// Aggregate function kind in [`Aggregate`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggregateKind {
+    Count,
+    Min,
+    Max,
+    Sum,
+}
+
+/// Aggregate function call in query, a.k.a `count(*)`, `sum(col)` and etc.
+///
+/// Only simple aggregates on a column or `count(*)` are supported, aggregates
+/// with `distinct`, `filter` or `order by` are not pushed down.
+///
+/// ## Examples
+///
+/// ```sql
+/// select count(*) from tbl;
+/// -- [Aggregate { kind: Count, field: None, type_oid: Oid(20) }]
+/// ```
+///
+/// ```sql
+/// select min(id), sum(amount) from tbl;
+/// -- [
+/// --   Aggregate { kind: Min, field: Some("id"), type_oid: Oid(20) },
+/// --   Aggregate { kind: Sum, field: Some("amount"), type_oid: Oid(1700) }
+/// -- ]
+/// ```
+#[derive(Debug, Clone)]
+pub struct Aggregate {
+    pub kind: AggregateKind,
+
+    /// column name the aggregate applies to, `None` for `count(*)`
+    pub field: Option<String>,
+
+    /// aggregate result type OID, e.g. `INT8OID` for `count(*)`
+    pub type_oid: Oid,
+}
+
+impl Aggregate {
+    pub fn deparse(&self) -> String {
+        let func = match self.kind {
+            AggregateKind::Count => "count",
+            AggregateKind::Min => "min",
+            AggregateKind::Max => "max",
+            AggregateKind::Sum => "sum",
+        };
+        format!("{}({})", func, self.field.as_deref().unwrap_or("*"))
+    }
+}
+
+/// Aggregate query plan pushed down to the foreign data source
+///
+/// Each result row of the aggregate scan has the `group_by` columns first,
+/// followed by the `aggregates`, both in the same order as they are listed.
+///
+/// ## Examples
+///
+/// ```sql
+/// select count(*) from tbl;
+/// -- AggregatePlan {
+/// --   group_by: [],
+/// --   aggregates: [Aggregate { kind: Count, field: None, type_oid: Oid(20) }]
+/// -- }
+/// ```
+///
+/// ```sql
+/// select customer_id, sum(amount) from tbl group by customer_id;
+/// -- AggregatePlan {
+/// --   group_by: [Column { name: "customer_id", num: 2, type_oid: Oid(20) }],
+/// --   aggregates: [Aggregate { kind: Sum, field: Some("amount"), type_oid: Oid(1700) }]
+/// -- }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct AggregatePlan {
+    /// grouping columns in `group by` clause, empty if there is no `group by`
+    pub group_by: Vec<Column>,
+
+    /// aggregate function calls in target list
+    pub aggregates: Vec<Aggregate>,
+}
+
+impl AggregatePlan {
+    /// Deparse the grouping columns and aggregates to a target list,
+    /// e.g. `customer_id, sum(amount)`
+    pub fn deparse_targets(&self) -> String {
+        self.group_by
+            .iter()
+            .map(|col| col.name.clone())
+            .chain(self.aggregates.iter().map(|agg| agg.deparse()))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+
+    /// Deparse the grouping columns to a `group by` clause, e.g.
+    /// `group by customer_id`, returns `None` if there is no grouping column
+    pub fn deparse_group_by(&self) -> Option<String> {
+        if self.group_by.is_empty() {
+            return None;
+        }
+        let cols = self
+            .group_by
+            .iter()
+            .map(|col| col.name.clone())
+            .collect::<Vec<String>>()
+            .join(", ");
+        Some(format!("group by {}", cols))
+    }
+}
+
+/// Join type in [`Join`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinKind {
+    Inner,
+    Left,
+    Right,
+    Full,
+}
+
+impl JoinKind {
+    /// Deparse the join type, e.g. `left join`
+    pub fn deparse(&self) -> &'static str {
+        match self {
+            JoinKind::Inner => "inner join",
+            JoinKind::Left => "left join",
+            JoinKind::Right => "right join",
+            JoinKind::Full => "full join",
+        }
+    }
+}
+
+/// Equi-join condition in [`Join`], e.g. `a.id = b.user_id`
+#[derive(Debug, Clone)]
+pub struct JoinCond {
+    /// column name in the outer table
+    pub outer_field: String,
+
+    /// column name in the inner table
+    pub inner_field: String,
+
+    /// type OID of both columns
+    pub type_oid: Oid,
+}
+
+/// Foreign table in [`Join`]
+#[derive(Debug, Clone, Default)]
+pub struct JoinTable {
+    /// target columns to be queried from this table
+    pub columns: Vec<Column>,
+
+    /// `WHERE` clause pushed down for this table, it must be applied to the
+    /// table before joining
+    pub quals: Vec<Qual>,
+
+    /// the options defined when `CREATE FOREIGN TABLE`
+    pub options: HashMap<String, String>,
+}
+
+/// Join of two foreign tables on the same foreign server
+///
+/// Only joins with equi-join conditions on columns are pushed down, and all
+/// the `WHERE` conditions on both tables must be fully handled by the FDW.
+///
+/// ## Examples
+///
+/// ```sql
+/// select u.name, o.amount
+/// from users u left join orders o on o.user_id = u.id
+/// where u.id > 42;
+/// -- Join {
+/// --   kind: Left,
+/// --   outer: JoinTable {
+/// --     columns: [Column { name: "name", .. }],
+/// --     quals: [Qual { field: "id", operator: ">", value: Cell(I64(42)), .. }],
+/// --     options: { "table": "users" }
+/// --   },
+/// --   inner: JoinTable {
+/// --     columns: [Column { name: "amount", .. }],
+/// --     quals: [],
+/// --     options: { "table": "orders" }
+/// --   },
+/// --   conds: [JoinCond { outer_field: "id", inner_field: "user_id", .. }]
+/// -- }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Join {
+    pub kind: JoinKind,
+
+    /// outer (left) table
+    pub outer: JoinTable,
+
+    /// inner (right) table
+    pub inner: JoinTable,
+
+    /// equi-join conditions connected by `and`
+    pub conds: Vec<JoinCond>,
+}
+
+/// Action of [`upsert`](ForeignDataWrapper::upsert) when the inserted row
+/// conflicts with an existing row on the remote side
+///
+/// Postgres doesn't allow `ON CONFLICT DO UPDATE` or `MERGE` on foreign
+/// tables, so `DoUpdate` is enabled by the `upsert 'true'` foreign table option
+/// for all the inserts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnConflict {
+    /// skip the inserted row, from `INSERT ... ON CONFLICT DO NOTHING`
+    DoNothing,
+
+    /// update the existing row with the inserted row
+    DoUpdate,
+}
+
+/// Version of the interface between the FDWs and the framework
+///
+/// It is bumped when [`ForeignDataWrapper`] or the functions generated by
+/// [`wrappers_fdw`](crate::wrappers_fdw) macro change incompatibly. The code
+/// generated by the macro embeds this constant as the version the FDW is
+/// compiled against, which is checked by [`check_interface_version`] when the
+/// FDW handler is called. This is the only definition of the version.
+pub const INTERFACE_VERSION: u32 = 1;
+
+/// The oldest interface version still supported by the framework
+pub const MIN_INTERFACE_VERSION: u32 = 1;
+
+/// Check if the FDW is built for a supported interface version, report error
+/// and stop current transaction if not
+///
+/// Not to be used directly, it is called by the handler generated by
+/// [`wrappers_fdw`](crate::wrappers_fdw) macro.
+pub fn check_interface_version(fdw_name: &str, version: u32) {
+    if (MIN_INTERFACE_VERSION..=INTERFACE_VERSION).contains(&version) {
+        return;
+    }
+    let hint = if version < MIN_INTERFACE_VERSION {
+        "upgrade supabase-wrappers-macros and rebuild the foreign data wrapper"
+    } else {
+        "upgrade supabase-wrappers to a version supporting the foreign data wrapper"
+    };
+    report_error(
+        PgSqlErrorCode::ERRCODE_FDW_ERROR,
+        &format!(
+            "foreign data wrapper {} is built for interface version {}, but supabase-wrappers {} supports interface version {} to {}, {}",
+            fdw_name,
+            version,
+            env!("CARGO_PKG_VERSION"),
+            MIN_INTERFACE_VERSION,
+            INTERFACE_VERSION,
+            hint
+        ),
+    );
+}
+
+/// Capabilities of a foreign data wrapper, see
+/// [`capabilities`](ForeignDataWrapper::capabilities)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FdwCapabilities {
+    /// supports `SELECT`
+    pub read: bool,
+
+    /// supports `INSERT`, `UPDATE` or `DELETE`
+    pub write: bool,
+
+    /// supports `IMPORT FOREIGN SCHEMA`
+    pub import_schema: bool,
+
+    /// pushes down the `WHERE` conditions
+    pub qual_pushdown: bool,
+
+    /// pushes down `ORDER BY`
+    pub sort_pushdown: bool,
+
+    /// pushes down `LIMIT` and `OFFSET`
+    pub limit_pushdown: bool,
+
+    /// pushes down aggregates and `GROUP BY`
+    pub aggregate_pushdown: bool,
+
+    /// pushes down joins of the foreign tables
+    pub join_pushdown: bool,
+}
+
+impl Default for FdwCapabilities {
+    fn default() -> Self {
+        Self {
+            read: true,
+            write: false,
+            import_schema: false,
+            qual_pushdown: false,
+            sort_pushdown: false,
+            limit_pushdown: false,
+            aggregate_pushdown: false,
+            join_pushdown: false,
+        }
+    }
+}
+
 /// The Foreign Data Wrapper trait
 ///
 /// This is the main interface for your foreign data wrapper. Required functions
@@ -666,7 +1414,8 @@ pub trait ForeignDataWrapper<E: Into<ErrorReport>> {
     /// Obtain relation size estimates for a foreign table
     ///
     /// Return the expected number of rows and row size (in bytes) by the
-    /// foreign table scan.
+    /// foreign table scan. If it returns non-zero rows, they take precedence
+    /// over the rows and width returned by [`estimate_size`](Self::estimate_size).
     ///
     /// [See more details](https://www.postgresql.org/docs/current/fdw-callbacks.html#FDW-CALLBACKS-SCAN).
     fn get_rel_size(
@@ -680,6 +1429,250 @@ pub trait ForeignDataWrapper<E: Into<ErrorReport>> {
         Ok((0, 0))
     }
 
+    /// Estimate the size and costs of a foreign table scan
+    ///
+    /// - `quals` - `WHERE` clause pushed down
+    /// - `columns` - target columns to be queried
+    /// - `options` - the options defined when `CREATE FOREIGN TABLE`
+    ///
+    /// Returns `(rows, width, startup_cost, total_cost)`, where `rows` is the
+    /// expected number of rows after applying `quals`, `width` is the average
+    /// row size in bytes, and the costs are in the same unit as Postgres
+    /// planner's cost, e.g. `seq_page_cost`. Zero `width` means using the
+    /// Postgres estimate based on column types. The `startup_cost` table option,
+    /// if any, is added to both costs.
+    ///
+    /// These estimates are used by Postgres planner to choose the join order
+    /// and join method. By default, 1000 rows is assumed and each row costs 1.
+    fn estimate_size(
+        &mut self,
+        _quals: &[Qual],
+        _columns: &[Column],
+        _options: &HashMap<String, String>,
+    ) -> Result<(f64, i32, f64, f64), E> {
+        let rows = 1000.0;
+        Ok((rows, 0, 0.0, rows))
+    }
+
+    /// Returns if the FDW accepts boolean expression tree in quals
+    ///
+    /// By default, the quals passed to [`begin_scan`](Self::begin_scan) only
+    /// contain simple conditions, and the `OR`, `AND` and `NOT` expressions are
+    /// filtered by Postgres locally. Return `true` to also receive them, each
+    /// of them is a [`Qual`] with `or`, `and` or `not` operator and its
+    /// sub-conditions in `sub_quals`.
+    fn bool_expr_tree() -> bool
+    where
+        Self: Sized,
+    {
+        false
+    }
+
+    /// Returns if the FDW accepts conditions on json attributes in quals
+    ///
+    /// Return `true` to receive conditions like `attrs->>'key' = 'value'` or
+    /// `attrs #>> '{a,b}' = 'value'`, the json column is in `field` and the
+    /// attribute path is in `json_path` of the [`Qual`].
+    fn json_path_quals() -> bool
+    where
+        Self: Sized,
+    {
+        false
+    }
+
+    /// Returns which quals are fully handled by the foreign data source
+    ///
+    /// Called in planning phase after [`get_rel_size`](Self::get_rel_size),
+    /// returns a flag for each qual in `quals`. A `true` flag means the
+    /// condition is guaranteed to be applied remotely with the same semantics as
+    /// Postgres, so it will not be re-checked locally. Missing flags are treated
+    /// as `false`.
+    ///
+    /// By default, all quals are re-checked locally.
+    fn handled_quals(
+        &mut self,
+        _quals: &[Qual],
+        _options: &HashMap<String, String>,
+    ) -> Result<Vec<bool>, E> {
+        Ok(Vec::new())
+    }
+
+    /// Returns if the FDW supports parameterized scan
+    ///
+    /// Return `true` to let Postgres consider parameterized scan for the foreign
+    /// table when it is the inner side of a nested loop join. For example,
+    ///
+    /// ```sql
+    /// select * from local_tbl l join foreign_tbl f on f.id = l.id;
+    /// ```
+    ///
+    /// The join condition `f.id = l.id` is passed to [`begin_scan`](Self::begin_scan)
+    /// as a qual with [`ParamKind::Exec`] parameter, and its value is the `l.id`
+    /// of current outer row. When the outer row changes, the scan is restarted
+    /// by calling [`end_scan`](Self::end_scan) and then `begin_scan` again with
+    /// the new parameter value, so the FDW can do a point lookup for each outer row.
+    fn parameterized_scan() -> bool
+    where
+        Self: Sized,
+    {
+        false
+    }
+
+    /// Returns if the aggregate plan can be pushed down to the foreign data source
+    ///
+    /// Called in planning phase for query like `select count(*), sum(amount) from tbl`
+    /// or `select customer_id, count(*) from tbl group by customer_id`, where
+    /// all the target expressions are grouping columns or supported [`Aggregate`]s.
+    /// It is only called when all the `quals` are reported as fully handled by
+    /// [`handled_quals`](Self::handled_quals), because they cannot be re-checked
+    /// locally after aggregation.
+    ///
+    /// Return `true` to have [`scan_aggregate`](Self::scan_aggregate) called
+    /// instead of the normal scan. By default, aggregates are not pushed down.
+    fn plan_aggregate(
+        &mut self,
+        _plan: &AggregatePlan,
+        _quals: &[Qual],
+        _options: &HashMap<String, String>,
+    ) -> Result<bool, E> {
+        Ok(false)
+    }
+
+    /// Called when executing a pushed down aggregate scan
+    ///
+    /// - `plan` - aggregate plan accepted by [`plan_aggregate`](Self::plan_aggregate)
+    /// - `quals` - `WHERE` clause pushed down
+    /// - `options` - the options defined when `CREATE FOREIGN TABLE`
+    ///
+    /// Returns the result rows, one row for each group or a single row if there
+    /// is no `group by`. Each row has one cell for each grouping column and then
+    /// one cell for each aggregate, in the same order as in `plan`. The aggregate
+    /// cells are converted to the aggregate result types if they are numeric, for
+    /// example, `Cell::I64` can be returned for `sum(col)` even if its result
+    /// type is `numeric`.
+    fn scan_aggregate(
+        &mut self,
+        _plan: &AggregatePlan,
+        _quals: &[Qual],
+        _options: &HashMap<String, String>,
+    ) -> Result<Vec<Row>, E> {
+        Ok(Vec::new())
+    }
+
+    /// Returns if the join can be pushed down to the foreign data source
+    ///
+    /// Called in planning phase when two foreign tables on the same foreign
+    /// server are joined, for example, `select * from a join b on a.id = b.id`.
+    /// It is only called when all the `WHERE` conditions on both tables are
+    /// reported as fully handled by [`handled_quals`](Self::handled_quals).
+    ///
+    /// Return `true` to have [`begin_join_scan`](Self::begin_join_scan) called
+    /// instead of scanning the two tables separately. By default, joins are not
+    /// pushed down.
+    fn plan_join(&mut self, _join: &Join) -> Result<bool, E> {
+        Ok(false)
+    }
+
+    /// Called when begin executing a pushed down join scan
+    ///
+    /// - `join` - join accepted by [`plan_join`](Self::plan_join)
+    ///
+    /// After this, [`iter_scan`](Self::iter_scan) is called to fetch the joined
+    /// rows, each row has one cell for each outer table column and then one
+    /// cell for each inner table column, in the same order as in `join`. The
+    /// scan is finished by [`end_scan`](Self::end_scan).
+    fn begin_join_scan(&mut self, _join: &Join) -> Result<(), E> {
+        Ok(())
+    }
+
+    /// Returns if the limit can be applied on the foreign data source
+    ///
+    /// - `quals` - `WHERE` clause pushed down
+    /// - `columns` - target columns to be queried
+    /// - `sorts` - `ORDER BY` clause pushed down
+    /// - `limit` - `LIMIT` and `OFFSET` clause to be pushed down
+    /// - `options` - the options defined when `CREATE FOREIGN TABLE`
+    ///
+    /// Called in planning phase for query like `select * from tbl order by id limit 10 offset 20`
+    /// on a single foreign table. It is only called when all the `quals` are
+    /// reported as fully handled by [`handled_quals`](Self::handled_quals)
+    /// and all the `ORDER BY` columns are in `sorts`.
+    ///
+    /// Return `true` if the FDW applies the sorts, `LIMIT` and `OFFSET` exactly
+    /// as given, then Postgres doesn't apply them again locally and the same
+    /// `limit` is passed to [`begin_scan`](Self::begin_scan). By default, the
+    /// limit is not accepted and only used as a hint of the number of rows to
+    /// fetch, see [`Limit`] for details.
+    fn plan_limit(
+        &mut self,
+        _quals: &[Qual],
+        _columns: &[Column],
+        _sorts: &[Sort],
+        _limit: &Limit,
+        _options: &HashMap<String, String>,
+    ) -> Result<bool, E> {
+        Ok(false)
+    }
+
+    /// Returns if the foreign scan can run in parallel workers
+    ///
+    /// Return `true` to let Postgres consider a parallel scan for the foreign
+    /// table, where the scan is split into partitions by
+    /// [`scan_partitions`](Self::scan_partitions), and each partition is
+    /// scanned by [`begin_partition_scan`](Self::begin_partition_scan) in one
+    /// of the parallel processes. Each process has its own FDW instance, so the
+    /// FDW must not rely on state shared between instances.
+    ///
+    /// `ORDER BY` and `LIMIT` are not pushed down to a parallel scan.
+    fn parallel_safe() -> bool
+    where
+        Self: Sized,
+    {
+        false
+    }
+
+    /// Returns the number of partitions a parallel scan is split into
+    ///
+    /// - `quals` - `WHERE` clause pushed down
+    /// - `columns` - target columns to be queried
+    /// - `options` - the options defined when `CREATE FOREIGN TABLE`
+    ///
+    /// Called once in the leader process before the parallel workers start, for
+    /// example, an FDW can return the number of objects to read or the number
+    /// of read streams on the remote side. By default, the scan has only one
+    /// partition.
+    fn scan_partitions(
+        &mut self,
+        _quals: &[Qual],
+        _columns: &[Column],
+        _options: &HashMap<String, String>,
+    ) -> Result<usize, E> {
+        Ok(1)
+    }
+
+    /// Called when begin scanning a partition in a parallel scan
+    ///
+    /// - `partition` - partition index, from `0` to the number returned by
+    ///   [`scan_partitions`](Self::scan_partitions) minus one
+    /// - `quals` - `WHERE` clause pushed down
+    /// - `columns` - target columns to be queried
+    /// - `options` - the options defined when `CREATE FOREIGN TABLE`
+    ///
+    /// After this, [`iter_scan`](Self::iter_scan) is called to fetch the rows
+    /// of the partition, and the partition is finished by
+    /// [`end_scan`](Self::end_scan). One process may scan multiple partitions
+    /// one after another. By default, it calls [`begin_scan`](Self::begin_scan)
+    /// for the whole table.
+    fn begin_partition_scan(
+        &mut self,
+        _partition: usize,
+        quals: &[Qual],
+        columns: &[Column],
+        options: &HashMap<String, String>,
+    ) -> Result<(), E> {
+        self.begin_scan(quals, columns, &[], &None, options)
+    }
+
     /// Called when begin executing a foreign scan
     ///
     /// - `quals` - `WHERE` clause pushed down
@@ -698,6 +1691,26 @@ pub trait ForeignDataWrapper<E: Into<ErrorReport>> {
         options: &HashMap<String, String>,
     ) -> Result<(), E>;
 
+    /// Returns the remote request details shown in `EXPLAIN VERBOSE`
+    ///
+    /// Called with the same parameters as [`begin_scan`](Self::begin_scan),
+    /// but the scan is not begun for `EXPLAIN` without `ANALYZE`, so the
+    /// details should be built from the parameters rather than the scan state.
+    ///
+    /// Returns a list of `(label, value)` pairs, for example, the remote SQL
+    /// query, REST API URL or API parameters to be issued, which is useful to
+    /// check if the conditions are pushed down. By default, nothing is shown.
+    fn explain(
+        &self,
+        _quals: &[Qual],
+        _columns: &[Column],
+        _sorts: &[Sort],
+        _limit: &Option<Limit>,
+        _options: &HashMap<String, String>,
+    ) -> Result<Vec<(String, String)>, E> {
+        Ok(Vec::new())
+    }
+
     /// Called when fetch one row from the foreign source
     ///
     /// FDW must save fetched foreign data into the [`Row`], or return `None` if no more rows to read.
@@ -738,6 +1751,9 @@ pub trait ForeignDataWrapper<E: Into<ErrorReport>> {
     ///   );
     /// ```
     ///
+    /// It is also called before rows are inserted by `COPY FROM` or routed to
+    /// a foreign table partition, in which case `rowid_column` is not required.
+    ///
     /// [See more details](https://www.postgresql.org/docs/current/fdw-callbacks.html#FDW-CALLBACKS-UPDATE).
     fn begin_modify(&mut self, _options: &HashMap<String, String>) -> Result<(), E> {
         Ok(())
@@ -752,6 +1768,64 @@ pub trait ForeignDataWrapper<E: Into<ErrorReport>> {
         Ok(())
     }
 
+    /// Called when insert a batch of rows into the foreign table
+    ///
+    /// - rows - the new rows to be inserted
+    ///
+    /// Rows are inserted in batches when the `batch_size` option is set on the
+    /// foreign table, for example, `options (batch_size '1000')`, so the FDW
+    /// can insert them to the remote side in one request. The batch is not used
+    /// if the table has row triggers or `WITH CHECK OPTION` constraints, or
+    /// on Postgres 13. `COPY FROM` uses the batch on Postgres 16 and later. By default, it calls [`insert`](Self::insert) for each row.
+    ///
+    /// [See more details](https://www.postgresql.org/docs/current/fdw-callbacks.html#FDW-CALLBACKS-UPDATE).
+    fn insert_rows(&mut self, rows: &[Row]) -> Result<(), E> {
+        for row in rows {
+            self.insert(row)?;
+        }
+        Ok(())
+    }
+
+    /// Called when insert one row into the foreign table with `RETURNING` clause
+    ///
+    /// - row - the new row to be inserted
+    /// - columns - all the columns of the foreign table
+    ///
+    /// Returns the row created on the remote side, for example, the object
+    /// returned by the remote API with its generated id, which is then
+    /// projected by the `RETURNING` clause. The returned columns are matched
+    /// by name, and the columns not in the returned row keep the inserted
+    /// values. By default, it calls [`insert`](Self::insert) and returns
+    /// `None`, so the inserted values are returned as is.
+    ///
+    /// [See more details](https://www.postgresql.org/docs/current/fdw-callbacks.html#FDW-CALLBACKS-UPDATE).
+    fn insert_returning(&mut self, row: &Row, _columns: &[Column]) -> Result<Option<Row>, E> {
+        self.insert(row)?;
+        Ok(None)
+    }
+
+    /// Called when insert one row which may conflict with an existing row
+    ///
+    /// - row - the new row to be inserted
+    /// - on_conflict - the action to take when the row conflicts with an
+    ///   existing row, see [`OnConflict`]
+    ///
+    /// It is called instead of [`insert`](Self::insert) for the
+    /// `INSERT ... ON CONFLICT DO NOTHING` statement, or for all the inserts
+    /// if the `upsert 'true'` option is set on the foreign table. Returns
+    /// `false` if the row is skipped, so it is not counted as inserted. The
+    /// FDW decides how the conflict is detected, for example, by the
+    /// `rowid_column`. By default, it reports an error as upsert is not supported.
+    ///
+    /// [See more details](https://www.postgresql.org/docs/current/fdw-callbacks.html#FDW-CALLBACKS-UPDATE).
+    fn upsert(&mut self, _row: &Row, _on_conflict: OnConflict) -> Result<bool, E> {
+        crate::utils::report_error(
+            pgrx::PgSqlErrorCode::ERRCODE_FEATURE_NOT_SUPPORTED,
+            "ON CONFLICT is not supported",
+        );
+        Ok(false)
+    }
+
     /// Called when update one row into the foreign table
     ///
     /// - rowid - the `rowid_column` cell
@@ -762,6 +1836,28 @@ pub trait ForeignDataWrapper<E: Into<ErrorReport>> {
         Ok(())
     }
 
+    /// Called when update one row into the foreign table with `RETURNING` clause
+    ///
+    /// - rowid - the `rowid_column` cell
+    /// - new_row - the new row with updated cells
+    /// - columns - all the columns of the foreign table
+    ///
+    /// Returns the row updated on the remote side, which is then projected by
+    /// the `RETURNING` clause. The returned columns are matched by name, and
+    /// the columns not in the returned row keep their values in the new row.
+    /// By default, it calls [`update`](Self::update) and returns `None`.
+    ///
+    /// [See more details](https://www.postgresql.org/docs/current/fdw-callbacks.html#FDW-CALLBACKS-UPDATE).
+    fn update_returning(
+        &mut self,
+        rowid: &Cell,
+        new_row: &Row,
+        _columns: &[Column],
+    ) -> Result<Option<Row>, E> {
+        self.update(rowid, new_row)?;
+        Ok(None)
+    }
+
     /// Called when delete one row into the foreign table
     ///
     /// - rowid - the `rowid_column` cell
@@ -778,17 +1874,142 @@ pub trait ForeignDataWrapper<E: Into<ErrorReport>> {
         Ok(())
     }
 
+    /// Called when planning a direct modification on the foreign table
+    ///
+    /// - `quals` - `WHERE` clause of the `UPDATE` or `DELETE` statement
+    /// - `new_row` - the new column values for `UPDATE`, or `None` for `DELETE`
+    /// - `options` - the options defined when `CREATE FOREIGN TABLE`
+    ///
+    /// Return `true` if the FDW can execute the whole statement on the remote
+    /// side, then the rows are not fetched and modified one by one, instead
+    /// [`exec_direct_modify`](Self::exec_direct_modify) is called once. For
+    /// example,
+    ///
+    /// ```sql
+    /// delete from my_foreign_table where id < 42;
+    /// ```
+    ///
+    /// It is only called when all the conditions are extracted to `quals`, the
+    /// new values are constants and there is no `RETURNING` clause, and the
+    /// FDW must apply all the `quals` remotely if it returns `true`. Parameter
+    /// values in `quals` are not available yet. By default, it returns `false`.
+    /// This is not available on Postgres 13.
+    ///
+    /// [See more details](https://www.postgresql.org/docs/current/fdw-callbacks.html#FDW-CALLBACKS-UPDATE).
+    fn plan_direct_modify(
+        &mut self,
+        _quals: &[Qual],
+        _new_row: Option<&Row>,
+        _options: &HashMap<String, String>,
+    ) -> Result<bool, E> {
+        Ok(false)
+    }
+
+    /// Called when execute the direct modification accepted by
+    /// [`plan_direct_modify`](Self::plan_direct_modify)
+    ///
+    /// The parameters are the same as `plan_direct_modify`, with the parameter
+    /// values in `quals` assigned. Returns the number of affected rows, which
+    /// is reported as the command status.
+    ///
+    /// [See more details](https://www.postgresql.org/docs/current/fdw-callbacks.html#FDW-CALLBACKS-UPDATE).
+    fn exec_direct_modify(
+        &mut self,
+        _quals: &[Qual],
+        _new_row: Option<&Row>,
+        _options: &HashMap<String, String>,
+    ) -> Result<u64, E> {
+        Ok(0)
+    }
+
+    /// Called when truncate the foreign table
+    ///
+    /// - `options` - the options defined when `CREATE FOREIGN TABLE`
+    ///
+    /// It is called once for each foreign table in the `TRUNCATE` command. The
+    /// `CASCADE` and `RESTART IDENTITY` options only apply to local tables.
+    /// By default, it reports an error as truncate is not supported. This is
+    /// not available on Postgres 13.
+    ///
+    /// [See more details](https://www.postgresql.org/docs/current/fdw-callbacks.html#FDW-CALLBACKS-TRUNCATE).
+    fn truncate(&mut self, _options: &HashMap<String, String>) -> Result<(), E> {
+        crate::utils::report_error(
+            pgrx::PgSqlErrorCode::ERRCODE_FEATURE_NOT_SUPPORTED,
+            "truncate is not supported",
+        );
+        Ok(())
+    }
+
+    /// Called before the local transaction which modified the foreign table
+    /// commits
+    ///
+    /// It is called once for each finished `INSERT`, `UPDATE`, `DELETE`,
+    /// `COPY FROM` or `TRUNCATE` statement in the transaction, on the same
+    /// instance which executed the statement. The FDW can buffer the writes
+    /// and flush them here. Returning an error aborts the local transaction,
+    /// and then [`rollback`](Self::rollback) is called.
+    ///
+    /// [See more details](https://www.postgresql.org/docs/current/xact-callbacks.html).
+    fn commit(&mut self) -> Result<(), E> {
+        Ok(())
+    }
+
+    /// Called when the local transaction which modified the foreign table is
+    /// aborted
+    ///
+    /// It is called on the same instances as [`commit`](Self::commit), so the
+    /// FDW can discard the buffered writes or run compensating cleanup on the
+    /// remote side. Errors returned here are reported as warnings. Rolling
+    /// back to a savepoint does not call it.
+    ///
+    /// [See more details](https://www.postgresql.org/docs/current/xact-callbacks.html).
+    fn rollback(&mut self) -> Result<(), E> {
+        Ok(())
+    }
+
     /// Obtain a list of foreign table creation commands
     ///
     /// Return a list of string, each of which must contain a CREATE FOREIGN TABLE
     /// which will be executed by the core server.
     ///
+    /// The tables excluded by `LIMIT TO` or `EXCEPT` clause are filtered out by
+    /// Postgres, use [`is_table_included`](crate::import_foreign_schema::ImportForeignSchemaStmt::is_table_included)
+    /// to skip them early and [`create_foreign_table`](crate::import_foreign_schema::ImportForeignSchemaStmt::create_foreign_table)
+    /// to make the statements.
+    ///
     /// [See more details](https://www.postgresql.org/docs/current/fdw-callbacks.html#FDW-CALLBACKS-IMPORT).
     fn import_foreign_schema(
         &mut self,
         _stmt: crate::import_foreign_schema::ImportForeignSchemaStmt,
-    ) -> Vec<String> {
-        Vec::new()
+    ) -> Result<Vec<String>, E> {
+        Ok(Vec::new())
+    }
+
+    /// Check if the foreign server is reachable and the credentials are valid
+    ///
+    /// It should send a lightweight authenticated request to the remote
+    /// server, like `select version()` to a database, and return the remote
+    /// server version if it is known. It is called by the
+    /// `<snake_case_fdw_name>_health_check()` function created by the
+    /// [`wrappers_fdw`](crate::wrappers_fdw) macro.
+    ///
+    /// The default implementation returns `None`, so only the creation of the
+    /// FDW instance is checked.
+    fn health_check(&mut self) -> Result<Option<String>, E> {
+        Ok(None)
+    }
+
+    /// Returns the capabilities of the FDW
+    ///
+    /// They are listed by the `<snake_case_fdw_name>_capabilities()` function
+    /// created by the [`wrappers_fdw`](crate::wrappers_fdw) macro, so tools can
+    /// find what the FDW supports. The default is read only without any
+    /// pushdown.
+    fn capabilities() -> FdwCapabilities
+    where
+        Self: Sized,
+    {
+        FdwCapabilities::default()
     }
 
     /// Returns a FdwRoutine for the FDW
@@ -799,6 +2020,8 @@ pub trait ForeignDataWrapper<E: Into<ErrorReport>> {
         Self: Sized,
     {
         unsafe {
+            #[cfg(not(feature = "pg13"))]
+            use crate::truncate;
             use crate::{import_foreign_schema, modify, scan};
             let mut fdw_routine =
                 FdwRoutine::<AllocatedByRust>::alloc_node(pg_sys::NodeTag::T_FdwRoutine);
@@ -812,6 +2035,8 @@ pub trait ForeignDataWrapper<E: Into<ErrorReport>> {
             fdw_routine.GetForeignPaths = Some(scan::get_foreign_paths::<E, Self>);
             fdw_routine.GetForeignPlan = Some(scan::get_foreign_plan::<E, Self>);
             fdw_routine.ExplainForeignScan = Some(scan::explain_foreign_scan::<E, Self>);
+            fdw_routine.GetForeignUpperPaths = Some(scan::get_foreign_upper_paths::<E, Self>);
+            fdw_routine.GetForeignJoinPaths = Some(scan::get_foreign_join_paths::<E, Self>);
 
             // scan phase
             fdw_routine.BeginForeignScan = Some(scan::begin_foreign_scan::<E, Self>);
@@ -819,14 +2044,48 @@ pub trait ForeignDataWrapper<E: Into<ErrorReport>> {
             fdw_routine.ReScanForeignScan = Some(scan::re_scan_foreign_scan::<E, Self>);
             fdw_routine.EndForeignScan = Some(scan::end_foreign_scan::<E, Self>);
 
+            // parallel scan
+            fdw_routine.IsForeignScanParallelSafe =
+                Some(scan::is_foreign_scan_parallel_safe::<E, Self>);
+            fdw_routine.EstimateDSMForeignScan = Some(scan::estimate_dsm_foreign_scan::<E, Self>);
+            fdw_routine.InitializeDSMForeignScan =
+                Some(scan::initialize_dsm_foreign_scan::<E, Self>);
+            fdw_routine.ReInitializeDSMForeignScan =
+                Some(scan::re_initialize_dsm_foreign_scan::<E, Self>);
+            fdw_routine.InitializeWorkerForeignScan =
+                Some(scan::initialize_worker_foreign_scan::<E, Self>);
+
             // modify phase
             fdw_routine.AddForeignUpdateTargets = Some(modify::add_foreign_update_targets);
             fdw_routine.PlanForeignModify = Some(modify::plan_foreign_modify::<E, Self>);
             fdw_routine.BeginForeignModify = Some(modify::begin_foreign_modify::<E, Self>);
             fdw_routine.ExecForeignInsert = Some(modify::exec_foreign_insert::<E, Self>);
+            #[cfg(not(feature = "pg13"))]
+            {
+                fdw_routine.GetForeignModifyBatchSize =
+                    Some(modify::get_foreign_modify_batch_size::<E, Self>);
+                fdw_routine.ExecForeignBatchInsert =
+                    Some(modify::exec_foreign_batch_insert::<E, Self>);
+            }
             fdw_routine.ExecForeignDelete = Some(modify::exec_foreign_delete::<E, Self>);
             fdw_routine.ExecForeignUpdate = Some(modify::exec_foreign_update::<E, Self>);
             fdw_routine.EndForeignModify = Some(modify::end_foreign_modify::<E, Self>);
+            #[cfg(not(feature = "pg13"))]
+            {
+                fdw_routine.PlanDirectModify = Some(scan::plan_direct_modify::<E, Self>);
+                fdw_routine.BeginDirectModify = Some(scan::begin_direct_modify::<E, Self>);
+                fdw_routine.IterateDirectModify = Some(scan::iterate_direct_modify::<E, Self>);
+                fdw_routine.EndDirectModify = Some(scan::end_direct_modify::<E, Self>);
+                fdw_routine.ExplainDirectModify = Some(scan::explain_foreign_scan::<E, Self>);
+            }
+            fdw_routine.BeginForeignInsert = Some(modify::begin_foreign_insert::<E, Self>);
+            fdw_routine.EndForeignInsert = Some(modify::end_foreign_insert::<E, Self>);
+
+            // truncate
+            #[cfg(not(feature = "pg13"))]
+            {
+                fdw_routine.ExecForeignTruncate = Some(truncate::exec_foreign_truncate::<E, Self>);
+            }
 
             Self::fdw_routine_hook(&mut fdw_routine);
             fdw_routine.into_pg_boxed()
@@ -0,0 +1,624 @@
+//! Version 2 of the foreign data wrapper interface
+//!
+//! The [v1](super::v1) trait passes the scan parameters as separate arguments
+//! and fetches one row per call, so adding a parameter or a batch API breaks
+//! all the FDWs implementing it. The v2 [`ForeignDataWrapper`] trait instead
+//! takes the parameters in [`ScanRequest`], which can grow new fields, and
+//! fetches and inserts the rows in batches.
+//!
+//! A v2 FDW is run through [`V1Adapter`], which implements the v1 trait, so
+//! both versions work with the same framework and the FDWs can migrate
+//! gradually. Add `interface = "v2"` to the [`wrappers_fdw`](crate::wrappers_fdw)
+//! macro attribute to use the adapter. For example,
+//!
+//! ```rust,ignore
+//! use supabase_wrappers::interface::v2;
+//! use supabase_wrappers::prelude::*;
+//!
+//! #[wrappers_fdw(
+//!     version = "0.1.0",
+//!     author = "Supabase",
+//!     website = "https://github.com/supabase/wrappers/tree/main/wrappers/src/fdw/helloworld_fdw",
+//!     error_type = "HelloWorldFdwError",
+//!     interface = "v2"
+//! )]
+//! pub struct HelloWorldFdw {
+//!     done: bool,
+//! }
+//!
+//! impl v2::ForeignDataWrapper<HelloWorldFdwError> for HelloWorldFdw {
+//!     fn new(_server: ForeignServer) -> Result<Self, HelloWorldFdwError> {
+//!         Ok(Self { done: false })
+//!     }
+//!
+//!     fn begin_scan(&mut self, _req: &v2::ScanRequest) -> Result<(), HelloWorldFdwError> {
+//!         self.done = false;
+//!         Ok(())
+//!     }
+//!
+//!     fn fetch_rows(&mut self, _max_rows: usize) -> Result<Vec<Row>, HelloWorldFdwError> {
+//!         if std::mem::replace(&mut self.done, true) {
+//!             return Ok(Vec::new());
+//!         }
+//!         let mut row = Row::new();
+//!         row.push("id", Some(Cell::I64(1)));
+//!         Ok(vec![row])
+//!     }
+//!
+//!     fn end_scan(&mut self) -> Result<(), HelloWorldFdwError> {
+//!         Ok(())
+//!     }
+//! }
+//! ```
+
+use super::{Cell, Column, FdwCapabilities, Limit, OnConflict, Qual, Row, Sort};
+use crate::import_foreign_schema::{ImportForeignSchemaStmt, RemoteTable};
+use crate::instance::ForeignServer;
+use crate::FdwRoutine;
+use pgrx::pg_sys::panic::ErrorReport;
+use pgrx::pg_sys::Oid;
+use pgrx::AllocatedByRust;
+use std::collections::{HashMap, VecDeque};
+
+/// Default max number of rows fetched in one [`fetch_rows`](ForeignDataWrapper::fetch_rows) call
+pub const DEFAULT_FETCH_SIZE: usize = 1000;
+
+/// Parameters of a foreign table scan
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub struct ScanRequest<'a> {
+    /// the `WHERE` conditions
+    pub quals: &'a [Qual],
+    /// the target columns
+    pub columns: &'a [Column],
+    /// the `ORDER BY` clauses
+    pub sorts: &'a [Sort],
+    /// the `LIMIT` and `OFFSET` clauses
+    pub limit: &'a Option<Limit>,
+    /// the foreign table options
+    pub options: &'a HashMap<String, String>,
+}
+
+/// The Foreign Data Wrapper trait, version 2
+///
+/// Required functions are listed below, all the others are optional.
+///
+/// 1. new
+/// 2. begin_scan
+/// 3. fetch_rows
+/// 4. end_scan
+///
+/// The aggregate and join pushdown of the [v1](super::v1) trait are not in
+/// v2 yet, so the FDWs using them stay on v1. The other v1 functions are
+/// forwarded by [`V1Adapter`].
+pub trait ForeignDataWrapper<E: Into<ErrorReport>> {
+    /// Create a FDW instance, see [v1](super::v1::ForeignDataWrapper::new)
+    fn new(server: ForeignServer) -> Result<Self, E>
+    where
+        Self: Sized;
+
+    /// Obtain relation size estimates for a foreign table, see
+    /// [v1](super::v1::ForeignDataWrapper::get_rel_size)
+    fn get_rel_size(&mut self, _req: &ScanRequest) -> Result<(i64, i32), E> {
+        Ok((0, 0))
+    }
+
+    /// Estimate the size and costs of a foreign table scan, see
+    /// [v1](super::v1::ForeignDataWrapper::estimate_size), the sorts and
+    /// limit in `req` are always empty
+    fn estimate_size(&mut self, _req: &ScanRequest) -> Result<(f64, i32, f64, f64), E> {
+        let rows = 1000.0;
+        Ok((rows, 0, 0.0, rows))
+    }
+
+    /// Returns if the FDW accepts boolean expression tree in quals, see
+    /// [v1](super::v1::ForeignDataWrapper::bool_expr_tree)
+    fn bool_expr_tree() -> bool
+    where
+        Self: Sized,
+    {
+        false
+    }
+
+    /// Returns if the FDW accepts conditions on json attributes in quals, see
+    /// [v1](super::v1::ForeignDataWrapper::json_path_quals)
+    fn json_path_quals() -> bool
+    where
+        Self: Sized,
+    {
+        false
+    }
+
+    /// Returns which quals are fully handled by the foreign data source, see
+    /// [v1](super::v1::ForeignDataWrapper::handled_quals)
+    fn handled_quals(
+        &mut self,
+        _quals: &[Qual],
+        _options: &HashMap<String, String>,
+    ) -> Result<Vec<bool>, E> {
+        Ok(Vec::new())
+    }
+
+    /// Returns if the FDW supports parameterized scan, see
+    /// [v1](super::v1::ForeignDataWrapper::parameterized_scan)
+    fn parameterized_scan() -> bool
+    where
+        Self: Sized,
+    {
+        false
+    }
+
+    /// Returns if the limit in `req` can be applied on the foreign data
+    /// source, see [v1](super::v1::ForeignDataWrapper::plan_limit)
+    fn plan_limit(&mut self, _req: &ScanRequest) -> Result<bool, E> {
+        Ok(false)
+    }
+
+    /// Returns if the foreign scan can run in parallel workers, see
+    /// [v1](super::v1::ForeignDataWrapper::parallel_safe)
+    fn parallel_safe() -> bool
+    where
+        Self: Sized,
+    {
+        false
+    }
+
+    /// Returns the number of partitions a parallel scan is split into, see
+    /// [v1](super::v1::ForeignDataWrapper::scan_partitions), the sorts and
+    /// limit in `req` are always empty
+    fn scan_partitions(&mut self, _req: &ScanRequest) -> Result<usize, E> {
+        Ok(1)
+    }
+
+    /// Called when begin scanning a partition in a parallel scan, see
+    /// [v1](super::v1::ForeignDataWrapper::begin_partition_scan). By default,
+    /// it calls [`begin_scan`](Self::begin_scan) for the whole table.
+    fn begin_partition_scan(&mut self, _partition: usize, req: &ScanRequest) -> Result<(), E> {
+        self.begin_scan(req)
+    }
+
+    /// Called when begin executing a foreign scan
+    fn begin_scan(&mut self, req: &ScanRequest) -> Result<(), E>;
+
+    /// Returns the remote request details shown in `EXPLAIN VERBOSE`, see
+    /// [v1](super::v1::ForeignDataWrapper::explain)
+    fn explain(&self, _req: &ScanRequest) -> Result<Vec<(String, String)>, E> {
+        Ok(Vec::new())
+    }
+
+    /// Fetch the next batch of rows, up to `max_rows` rows, return an empty
+    /// batch if no more rows to read
+    fn fetch_rows(&mut self, max_rows: usize) -> Result<Vec<Row>, E>;
+
+    /// Called when restart the scan from the beginning
+    fn re_scan(&mut self) -> Result<(), E> {
+        Ok(())
+    }
+
+    /// Called when end the scan
+    fn end_scan(&mut self) -> Result<(), E>;
+
+    /// Called when begin executing a foreign table modification operation,
+    /// see [v1](super::v1::ForeignDataWrapper::begin_modify)
+    fn begin_modify(&mut self, _options: &HashMap<String, String>) -> Result<(), E> {
+        Ok(())
+    }
+
+    /// Called when insert a batch of rows into the foreign table, a single
+    /// row is inserted as a batch of one row
+    fn insert_rows(&mut self, _rows: &[Row]) -> Result<(), E> {
+        Ok(())
+    }
+
+    /// Called when insert one row with `RETURNING` clause, see
+    /// [v1](super::v1::ForeignDataWrapper::insert_returning). By default, it
+    /// inserts the row as a batch of one row and returns `None`.
+    fn insert_returning(&mut self, row: &Row, _columns: &[Column]) -> Result<Option<Row>, E> {
+        self.insert_rows(std::slice::from_ref(row))?;
+        Ok(None)
+    }
+
+    /// Called when insert one row which may conflict with an existing row,
+    /// see [v1](super::v1::ForeignDataWrapper::upsert)
+    fn upsert(&mut self, _row: &Row, _on_conflict: OnConflict) -> Result<bool, E> {
+        crate::utils::report_error(
+            pgrx::PgSqlErrorCode::ERRCODE_FEATURE_NOT_SUPPORTED,
+            "ON CONFLICT is not supported",
+        );
+        Ok(false)
+    }
+
+    /// Called when update one row in the foreign table
+    fn update(&mut self, _rowid: &Cell, _new_row: &Row) -> Result<(), E> {
+        Ok(())
+    }
+
+    /// Called when update one row with `RETURNING` clause, see
+    /// [v1](super::v1::ForeignDataWrapper::update_returning)
+    fn update_returning(
+        &mut self,
+        rowid: &Cell,
+        new_row: &Row,
+        _columns: &[Column],
+    ) -> Result<Option<Row>, E> {
+        self.update(rowid, new_row)?;
+        Ok(None)
+    }
+
+    /// Called when delete one row from the foreign table
+    fn delete(&mut self, _rowid: &Cell) -> Result<(), E> {
+        Ok(())
+    }
+
+    /// Called when end the table update
+    fn end_modify(&mut self) -> Result<(), E> {
+        Ok(())
+    }
+
+    /// Called when planning a direct modification on the foreign table, see
+    /// [v1](super::v1::ForeignDataWrapper::plan_direct_modify)
+    fn plan_direct_modify(
+        &mut self,
+        _quals: &[Qual],
+        _new_row: Option<&Row>,
+        _options: &HashMap<String, String>,
+    ) -> Result<bool, E> {
+        Ok(false)
+    }
+
+    /// Called when execute the direct modification, see
+    /// [v1](super::v1::ForeignDataWrapper::exec_direct_modify)
+    fn exec_direct_modify(
+        &mut self,
+        _quals: &[Qual],
+        _new_row: Option<&Row>,
+        _options: &HashMap<String, String>,
+    ) -> Result<u64, E> {
+        Ok(0)
+    }
+
+    /// Called when truncate the foreign table, see
+    /// [v1](super::v1::ForeignDataWrapper::truncate)
+    fn truncate(&mut self, _options: &HashMap<String, String>) -> Result<(), E> {
+        crate::utils::report_error(
+            pgrx::PgSqlErrorCode::ERRCODE_FEATURE_NOT_SUPPORTED,
+            "truncate is not supported",
+        );
+        Ok(())
+    }
+
+    /// Called before the local transaction which modified the foreign table
+    /// commits, see [v1](super::v1::ForeignDataWrapper::commit)
+    fn commit(&mut self) -> Result<(), E> {
+        Ok(())
+    }
+
+    /// Called when the local transaction which modified the foreign table is
+    /// aborted, see [v1](super::v1::ForeignDataWrapper::rollback)
+    fn rollback(&mut self) -> Result<(), E> {
+        Ok(())
+    }
+
+    /// List the remote tables for `IMPORT FOREIGN SCHEMA`, see
+    /// [`ListTables`](crate::import_foreign_schema::ListTables)
+    fn list_tables(&mut self, _stmt: &ImportForeignSchemaStmt) -> Result<Vec<RemoteTable>, E> {
+        Ok(Vec::new())
+    }
+
+    /// Check if the foreign server is reachable and the credentials are
+    /// valid, see [v1](super::v1::ForeignDataWrapper::health_check)
+    fn health_check(&mut self) -> Result<Option<String>, E> {
+        Ok(None)
+    }
+
+    /// Returns the capabilities of the FDW
+    fn capabilities() -> FdwCapabilities
+    where
+        Self: Sized,
+    {
+        FdwCapabilities::default()
+    }
+
+    /// Additional FdwRoutine setup, see
+    /// [v1](super::v1::ForeignDataWrapper::fdw_routine_hook)
+    fn fdw_routine_hook(_routine: &mut FdwRoutine<AllocatedByRust>) {}
+
+    /// Validator function for the options, see
+    /// [v1](super::v1::ForeignDataWrapper::validator)
+    fn validator(_options: Vec<Option<String>>, _catalog: Option<Oid>) -> Result<(), E> {
+        Ok(())
+    }
+}
+
+/// Run a v2 FDW as a v1 FDW
+///
+/// It buffers the rows fetched in batches and returns them one by one to the
+/// v1 scan.
+pub struct V1Adapter<T> {
+    inner: T,
+    rows: VecDeque<Row>,
+    eof: bool,
+}
+
+impl<T> V1Adapter<T> {
+    /// Get the v2 FDW instance
+    pub fn inner(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    fn reset(&mut self) {
+        self.rows.clear();
+        self.eof = false;
+    }
+}
+
+impl<E: Into<ErrorReport>, T: ForeignDataWrapper<E>> super::v1::ForeignDataWrapper<E>
+    for V1Adapter<T>
+{
+    fn new(server: ForeignServer) -> Result<Self, E> {
+        Ok(Self {
+            inner: T::new(server)?,
+            rows: VecDeque::new(),
+            eof: false,
+        })
+    }
+
+    fn get_rel_size(
+        &mut self,
+        quals: &[Qual],
+        columns: &[Column],
+        sorts: &[Sort],
+        limit: &Option<Limit>,
+        options: &HashMap<String, String>,
+    ) -> Result<(i64, i32), E> {
+        self.inner.get_rel_size(&ScanRequest {
+            quals,
+            columns,
+            sorts,
+            limit,
+            options,
+        })
+    }
+
+    fn estimate_size(
+        &mut self,
+        quals: &[Qual],
+        columns: &[Column],
+        options: &HashMap<String, String>,
+    ) -> Result<(f64, i32, f64, f64), E> {
+        self.inner.estimate_size(&ScanRequest {
+            quals,
+            columns,
+            sorts: &[],
+            limit: &None,
+            options,
+        })
+    }
+
+    fn bool_expr_tree() -> bool {
+        T::bool_expr_tree()
+    }
+
+    fn json_path_quals() -> bool {
+        T::json_path_quals()
+    }
+
+    fn handled_quals(
+        &mut self,
+        quals: &[Qual],
+        options: &HashMap<String, String>,
+    ) -> Result<Vec<bool>, E> {
+        self.inner.handled_quals(quals, options)
+    }
+
+    fn parameterized_scan() -> bool {
+        T::parameterized_scan()
+    }
+
+    fn plan_limit(
+        &mut self,
+        quals: &[Qual],
+        columns: &[Column],
+        sorts: &[Sort],
+        limit: &Limit,
+        options: &HashMap<String, String>,
+    ) -> Result<bool, E> {
+        self.inner.plan_limit(&ScanRequest {
+            quals,
+            columns,
+            sorts,
+            limit: &Some(limit.clone()),
+            options,
+        })
+    }
+
+    fn parallel_safe() -> bool {
+        T::parallel_safe()
+    }
+
+    fn scan_partitions(
+        &mut self,
+        quals: &[Qual],
+        columns: &[Column],
+        options: &HashMap<String, String>,
+    ) -> Result<usize, E> {
+        self.inner.scan_partitions(&ScanRequest {
+            quals,
+            columns,
+            sorts: &[],
+            limit: &None,
+            options,
+        })
+    }
+
+    fn begin_partition_scan(
+        &mut self,
+        partition: usize,
+        quals: &[Qual],
+        columns: &[Column],
+        options: &HashMap<String, String>,
+    ) -> Result<(), E> {
+        self.reset();
+        self.inner.begin_partition_scan(
+            partition,
+            &ScanRequest {
+                quals,
+                columns,
+                sorts: &[],
+                limit: &None,
+                options,
+            },
+        )
+    }
+
+    fn begin_scan(
+        &mut self,
+        quals: &[Qual],
+        columns: &[Column],
+        sorts: &[Sort],
+        limit: &Option<Limit>,
+        options: &HashMap<String, String>,
+    ) -> Result<(), E> {
+        self.reset();
+        self.inner.begin_scan(&ScanRequest {
+            quals,
+            columns,
+            sorts,
+            limit,
+            options,
+        })
+    }
+
+    fn explain(
+        &self,
+        quals: &[Qual],
+        columns: &[Column],
+        sorts: &[Sort],
+        limit: &Option<Limit>,
+        options: &HashMap<String, String>,
+    ) -> Result<Vec<(String, String)>, E> {
+        self.inner.explain(&ScanRequest {
+            quals,
+            columns,
+            sorts,
+            limit,
+            options,
+        })
+    }
+
+    fn iter_scan(&mut self, row: &mut Row) -> Result<Option<()>, E> {
+        while self.rows.is_empty() && !self.eof {
+            let rows = self.inner.fetch_rows(DEFAULT_FETCH_SIZE)?;
+            self.eof = rows.is_empty();
+            self.rows.extend(rows);
+        }
+        Ok(self.rows.pop_front().map(|src| row.replace_with(src)))
+    }
+
+    fn re_scan(&mut self) -> Result<(), E> {
+        self.reset();
+        self.inner.re_scan()
+    }
+
+    fn end_scan(&mut self) -> Result<(), E> {
+        self.reset();
+        self.inner.end_scan()
+    }
+
+    fn begin_modify(&mut self, options: &HashMap<String, String>) -> Result<(), E> {
+        self.inner.begin_modify(options)
+    }
+
+    fn insert(&mut self, row: &Row) -> Result<(), E> {
+        self.inner.insert_rows(std::slice::from_ref(row))
+    }
+
+    fn insert_rows(&mut self, rows: &[Row]) -> Result<(), E> {
+        self.inner.insert_rows(rows)
+    }
+
+    fn insert_returning(&mut self, row: &Row, columns: &[Column]) -> Result<Option<Row>, E> {
+        self.inner.insert_returning(row, columns)
+    }
+
+    fn upsert(&mut self, row: &Row, on_conflict: OnConflict) -> Result<bool, E> {
+        self.inner.upsert(row, on_conflict)
+    }
+
+    fn update(&mut self, rowid: &Cell, new_row: &Row) -> Result<(), E> {
+        self.inner.update(rowid, new_row)
+    }
+
+    fn update_returning(
+        &mut self,
+        rowid: &Cell,
+        new_row: &Row,
+        columns: &[Column],
+    ) -> Result<Option<Row>, E> {
+        self.inner.update_returning(rowid, new_row, columns)
+    }
+
+    fn delete(&mut self, rowid: &Cell) -> Result<(), E> {
+        self.inner.delete(rowid)
+    }
+
+    fn end_modify(&mut self) -> Result<(), E> {
+        self.inner.end_modify()
+    }
+
+    fn plan_direct_modify(
+        &mut self,
+        quals: &[Qual],
+        new_row: Option<&Row>,
+        options: &HashMap<String, String>,
+    ) -> Result<bool, E> {
+        self.inner.plan_direct_modify(quals, new_row, options)
+    }
+
+    fn exec_direct_modify(
+        &mut self,
+        quals: &[Qual],
+        new_row: Option<&Row>,
+        options: &HashMap<String, String>,
+    ) -> Result<u64, E> {
+        self.inner.exec_direct_modify(quals, new_row, options)
+    }
+
+    fn truncate(&mut self, options: &HashMap<String, String>) -> Result<(), E> {
+        self.inner.truncate(options)
+    }
+
+    fn commit(&mut self) -> Result<(), E> {
+        self.inner.commit()
+    }
+
+    fn rollback(&mut self) -> Result<(), E> {
+        self.inner.rollback()
+    }
+
+    fn import_foreign_schema(&mut self, stmt: ImportForeignSchemaStmt) -> Result<Vec<String>, E> {
+        Ok(self
+            .inner
+            .list_tables(&stmt)?
+            .iter()
+            .filter(|table| stmt.is_table_included(&table.name))
+            .map(|table| stmt.create_foreign_table(&table.name, &table.columns, &table.options))
+            .collect())
+    }
+
+    fn health_check(&mut self) -> Result<Option<String>, E> {
+        self.inner.health_check()
+    }
+
+    fn capabilities() -> FdwCapabilities {
+        T::capabilities()
+    }
+
+    fn fdw_routine_hook(routine: &mut FdwRoutine<AllocatedByRust>) {
+        T::fdw_routine_hook(routine)
+    }
+
+    fn validator(options: Vec<Option<String>>, catalog: Option<Oid>) -> Result<(), E> {
+        T::validator(options, catalog)
+    }
+}
@@ -0,0 +1,138 @@
+use crate::interface::{Column, Join, JoinCond, JoinKind, JoinTable};
+use crate::qual::{get_operator, unnest_clause};
+use crate::utils::{column_type_oid, get_column_options};
+use pgrx::{is_a, list::PgList, pg_sys};
+use std::ffi::CStr;
+
+// get column from a Var node if it is a column of the relation
+unsafe fn extract_column(
+    node: *mut pg_sys::Node,
+    relid: pg_sys::Index,
+    rel_oid: pg_sys::Oid,
+) -> Option<Column> {
+    if !is_a(node, pg_sys::NodeTag::T_Var) {
+        return None;
+    }
+    let var = node as *mut pg_sys::Var;
+    if (*var).varno as pg_sys::Index != relid || (*var).varlevelsup != 0 || (*var).varattno < 1 {
+        return None;
+    }
+    let attname = pg_sys::get_attname(rel_oid, (*var).varattno, false);
+    Some(Column {
+        name: CStr::from_ptr(attname).to_str().ok()?.to_string(),
+        num: (*var).varattno as usize,
+        type_oid: column_type_oid((*var).vartype),
+        options: get_column_options(rel_oid, (*var).varattno),
+    })
+}
+
+// extract equi-join condition from a join clause, e.g. "a.id = b.user_id",
+// both sides must be a column of the same type
+unsafe fn extract_join_cond(
+    rinfo: *mut pg_sys::RestrictInfo,
+    outer: (pg_sys::Index, pg_sys::Oid),
+    inner: (pg_sys::Index, pg_sys::Oid),
+) -> Option<JoinCond> {
+    let clause = (*rinfo).clause as *mut pg_sys::Node;
+    if (*rinfo).pseudoconstant || !is_a(clause, pg_sys::NodeTag::T_OpExpr) {
+        return None;
+    }
+
+    let expr = clause as *mut pg_sys::OpExpr;
+    let opr = get_operator((*expr).opno);
+    if pgrx::name_data_to_str(&(*opr).oprname) != "=" {
+        return None;
+    }
+
+    let args: PgList<pg_sys::Node> = PgList::from_pg((*expr).args);
+    if args.len() != 2 {
+        return None;
+    }
+    let left = unnest_clause(args.head()?);
+    let right = unnest_clause(args.tail()?);
+
+    let (outer_col, inner_col) = match (
+        extract_column(left, outer.0, outer.1),
+        extract_column(right, inner.0, inner.1),
+    ) {
+        (Some(outer_col), Some(inner_col)) => (outer_col, inner_col),
+        _ => (
+            extract_column(right, outer.0, outer.1)?,
+            extract_column(left, inner.0, inner.1)?,
+        ),
+    };
+    if outer_col.type_oid != inner_col.type_oid {
+        return None;
+    }
+
+    Some(JoinCond {
+        outer_field: outer_col.name,
+        inner_field: inner_col.name,
+        type_oid: outer_col.type_oid,
+    })
+}
+
+// extract join of two foreign tables, all the join clauses must be equi-join
+// conditions and all the target expressions must be columns, otherwise
+// returns None
+pub(crate) unsafe fn extract_join(
+    root: *mut pg_sys::PlannerInfo,
+    joinrel: *mut pg_sys::RelOptInfo,
+    outerrel: *mut pg_sys::RelOptInfo,
+    innerrel: *mut pg_sys::RelOptInfo,
+    jointype: pg_sys::JoinType::Type,
+    restrictlist: *mut pg_sys::List,
+) -> Option<Join> {
+    let kind = match jointype {
+        pg_sys::JoinType::JOIN_INNER => JoinKind::Inner,
+        pg_sys::JoinType::JOIN_LEFT => JoinKind::Left,
+        pg_sys::JoinType::JOIN_RIGHT => JoinKind::Right,
+        pg_sys::JoinType::JOIN_FULL => JoinKind::Full,
+        _ => return None,
+    };
+
+    let rel_oid = |rel: *mut pg_sys::RelOptInfo| {
+        let rte = *(*root).simple_rte_array.add((*rel).relid as usize);
+        (*rte).relid
+    };
+    let outer = ((*outerrel).relid, rel_oid(outerrel));
+    let inner = ((*innerrel).relid, rel_oid(innerrel));
+
+    // all join clauses must be applied remotely, and filter clauses of an
+    // outer join cannot be applied as join conditions
+    let mut conds = Vec::new();
+    for rinfo in PgList::<pg_sys::RestrictInfo>::from_pg(restrictlist).iter_ptr() {
+        let is_pushed_down = (*rinfo).is_pushed_down
+            || !pg_sys::bms_is_subset((*rinfo).required_relids, (*joinrel).relids);
+        if kind != JoinKind::Inner && is_pushed_down {
+            return None;
+        }
+        conds.push(extract_join_cond(rinfo, outer, inner)?);
+    }
+
+    // cross join is not pushed down
+    if conds.is_empty() {
+        return None;
+    }
+
+    // split target columns to each table
+    let mut outer_table = JoinTable::default();
+    let mut inner_table = JoinTable::default();
+    let exprs = PgList::<pg_sys::Node>::from_pg((*(*joinrel).reltarget).exprs);
+    for expr in exprs.iter_ptr() {
+        if let Some(col) = extract_column(expr, outer.0, outer.1) {
+            outer_table.columns.push(col);
+        } else {
+            inner_table
+                .columns
+                .push(extract_column(expr, inner.0, inner.1)?);
+        }
+    }
+
+    Some(Join {
+        kind,
+        outer: outer_table,
+        inner: inner_table,
+        conds,
+    })
+}
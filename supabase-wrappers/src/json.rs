@@ -0,0 +1,219 @@
+//! Helper functions to map JSON records to rows
+//!
+//! Most of the RESTful API FDWs get JSON records from the remote source, these
+//! functions convert a record to a [`Row`] by the target column types, so FDWs
+//! don't need to match each column type themselves. For example,
+//!
+//! ```rust,no_run
+//! # use supabase_wrappers::prelude::*;
+//! # fn scan(records: &[serde_json::Value], tgt_cols: &[Column]) -> Result<Vec<Row>, JsonToRowError> {
+//! let rows = records
+//!     .iter()
+//!     .map(|record| json_to_row(record, tgt_cols))
+//!     .collect::<Result<Vec<Row>, _>>()?;
+//! # Ok(rows)
+//! # }
+//! ```
+
+use crate::interface::{Cell, Column, Row};
+use crate::utils::{
+    composite_type_columns, interval_from_iso8601, interval_from_secs, timestamptz_from_rfc3339,
+};
+use pgrx::pg_sys::panic::ErrorReport;
+use pgrx::prelude::{to_timestamp, Date, PgSqlErrorCode, Timestamp, TimestampWithTimeZone};
+use pgrx::{pg_sys, AnyNumeric, JsonB};
+use serde_json::Value as JsonValue;
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum JsonToRowError {
+    #[error("column '{0}' data type is not supported")]
+    UnsupportedColumnType(String),
+
+    #[error("column '{0}' data type not match")]
+    ColumnTypeNotMatch(String),
+}
+
+impl From<JsonToRowError> for ErrorReport {
+    fn from(value: JsonToRowError) -> Self {
+        ErrorReport::new(
+            PgSqlErrorCode::ERRCODE_FDW_INVALID_DATA_TYPE,
+            format!("{value}"),
+            "",
+        )
+    }
+}
+
+/// Get the field value of a JSON record for a column
+///
+/// The field is located by the column option `path` if it is specified, which
+/// is a dot-separated path to a nested property, e.g. `address.city`, otherwise
+/// by the column name.
+pub fn json_field<'a>(record: &'a JsonValue, col: &Column) -> Option<&'a JsonValue> {
+    match col.options.get("path") {
+        Some(path) => path.split('.').try_fold(record, |v, key| v.get(key)),
+        None => record.get(&col.name),
+    }
+}
+
+/// Convert a JSON record to a row of the target columns
+///
+/// Each column value is got by [`json_field`] and converted by [`json_to_cell`],
+/// a missing field is converted to `null`.
+pub fn json_to_row(record: &JsonValue, tgt_cols: &[Column]) -> Result<Row, JsonToRowError> {
+    let mut row = Row::new();
+    for tgt_col in tgt_cols {
+        let cell = match json_field(record, tgt_col) {
+            Some(value) => json_to_cell(value, tgt_col)?,
+            None => None,
+        };
+        row.push(&tgt_col.name, cell);
+    }
+    Ok(row)
+}
+
+/// Convert a JSON value to a cell of the column type
+///
+/// JSON `null` is converted to `None` for all types. Other values are converted
+/// as below, or returns [`JsonToRowError::ColumnTypeNotMatch`] if the value
+/// cannot be converted:
+///
+/// - numbers and numeric strings for numeric types, `numeric` is converted from
+///   the number text to keep its precision
+/// - strings for `text`, other values are converted to their JSON text
+/// - date and time strings for `date`, `timestamp` and `timestamptz`, or the
+///   number of seconds since Unix epoch for `timestamp` and `timestamptz`
+/// - ISO 8601 duration strings or the number of seconds for `interval`
+/// - UUID strings for `uuid`
+/// - any value for `jsonb`
+/// - arrays for `boolean[]`, `smallint[]`, `integer[]`, `bigint[]`, `real[]`,
+///   `double precision[]` and `text[]`
+/// - objects for user-defined composite types, which are converted field-by-field
+pub fn json_to_cell(value: &JsonValue, col: &Column) -> Result<Option<Cell>, JsonToRowError> {
+    if value.is_null() {
+        return Ok(None);
+    }
+
+    let cell = match col.type_oid {
+        pg_sys::BOOLOID => value.as_bool().map(Cell::Bool),
+        pg_sys::CHAROID => json_to_i64(value)
+            .and_then(|v| i8::try_from(v).ok())
+            .map(Cell::I8),
+        pg_sys::INT2OID => json_to_i64(value)
+            .and_then(|v| i16::try_from(v).ok())
+            .map(Cell::I16),
+        pg_sys::INT4OID => json_to_i64(value)
+            .and_then(|v| i32::try_from(v).ok())
+            .map(Cell::I32),
+        pg_sys::INT8OID => json_to_i64(value).map(Cell::I64),
+        pg_sys::FLOAT4OID => json_to_f64(value).map(|v| Cell::F32(v as f32)),
+        pg_sys::FLOAT8OID => json_to_f64(value).map(Cell::F64),
+        pg_sys::NUMERICOID => match value {
+            JsonValue::Number(v) => AnyNumeric::try_from(v.to_string().as_str()).ok(),
+            JsonValue::String(v) => AnyNumeric::try_from(v.as_str()).ok(),
+            _ => None,
+        }
+        .map(Cell::Numeric),
+        pg_sys::TEXTOID => match value {
+            JsonValue::String(v) => Some(Cell::String(v.to_owned())),
+            v => Some(Cell::String(v.to_string())),
+        },
+        pg_sys::DATEOID => value
+            .as_str()
+            .and_then(|v| Date::from_str(v).ok())
+            .map(Cell::Date),
+        pg_sys::TIMESTAMPOID => match value {
+            JsonValue::String(v) => Timestamp::from_str(v).ok(),
+            v => v.as_f64().map(|secs| to_timestamp(secs).to_utc()),
+        }
+        .map(Cell::Timestamp),
+        pg_sys::TIMESTAMPTZOID => match value {
+            JsonValue::String(v) => {
+                timestamptz_from_rfc3339(v).or_else(|| TimestampWithTimeZone::from_str(v).ok())
+            }
+            v => v.as_f64().map(to_timestamp),
+        }
+        .map(Cell::Timestamptz),
+        pg_sys::INTERVALOID => match value {
+            JsonValue::String(v) => interval_from_iso8601(v),
+            v => v.as_f64().and_then(interval_from_secs),
+        }
+        .map(Cell::Interval),
+        pg_sys::UUIDOID => value
+            .as_str()
+            .and_then(|v| uuid::Uuid::parse_str(v).ok())
+            .map(|v| Cell::Uuid(pgrx::Uuid::from_bytes(*v.as_bytes()))),
+        pg_sys::JSONBOID => Some(Cell::Json(JsonB(value.clone()))),
+        pg_sys::BOOLARRAYOID => json_to_array(value, col, |v| v.as_bool())?.map(Cell::BoolArray),
+        pg_sys::INT2ARRAYOID => json_to_array(value, col, |v| {
+            json_to_i64(v).and_then(|v| i16::try_from(v).ok())
+        })?
+        .map(Cell::I16Array),
+        pg_sys::INT4ARRAYOID => json_to_array(value, col, |v| {
+            json_to_i64(v).and_then(|v| i32::try_from(v).ok())
+        })?
+        .map(Cell::I32Array),
+        pg_sys::INT8ARRAYOID => json_to_array(value, col, json_to_i64)?.map(Cell::I64Array),
+        pg_sys::FLOAT4ARRAYOID => {
+            json_to_array(value, col, |v| json_to_f64(v).map(|v| v as f32))?.map(Cell::F32Array)
+        }
+        pg_sys::FLOAT8ARRAYOID => json_to_array(value, col, json_to_f64)?.map(Cell::F64Array),
+        pg_sys::TEXTARRAYOID => json_to_array(value, col, |v| match v {
+            JsonValue::String(v) => Some(v.to_owned()),
+            v => Some(v.to_string()),
+        })?
+        .map(Cell::StringArray),
+        type_oid => match composite_type_columns(type_oid) {
+            Some(fields) if value.is_object() => {
+                Some(Cell::Composite(json_to_row(value, &fields)?))
+            }
+            Some(_) => None,
+            None => return Err(JsonToRowError::UnsupportedColumnType(col.name.clone())),
+        },
+    };
+
+    cell.map(Some)
+        .ok_or_else(|| JsonToRowError::ColumnTypeNotMatch(col.name.clone()))
+}
+
+// get integer from a JSON number or numeric string
+fn json_to_i64(value: &JsonValue) -> Option<i64> {
+    match value {
+        JsonValue::String(v) => v.parse().ok(),
+        v => v.as_i64(),
+    }
+}
+
+// get float from a JSON number or numeric string
+fn json_to_f64(value: &JsonValue) -> Option<f64> {
+    match value {
+        JsonValue::String(v) => v.parse().ok(),
+        v => v.as_f64(),
+    }
+}
+
+// convert a JSON array by the element conversion, null element is kept as None
+fn json_to_array<T, F>(
+    value: &JsonValue,
+    col: &Column,
+    f: F,
+) -> Result<Option<Vec<Option<T>>>, JsonToRowError>
+where
+    F: Fn(&JsonValue) -> Option<T>,
+{
+    let Some(arr) = value.as_array() else {
+        return Ok(None);
+    };
+    arr.iter()
+        .map(|v| {
+            if v.is_null() {
+                Ok(None)
+            } else {
+                f(v).map(Some)
+                    .ok_or_else(|| JsonToRowError::ColumnTypeNotMatch(col.name.clone()))
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(Some)
+}
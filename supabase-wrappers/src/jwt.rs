@@ -0,0 +1,193 @@
+//! JWT creation and verification
+//!
+//! Some APIs, like Google service accounts and self-hosted services, require a
+//! JWT signed by the client as the assertion or bearer token. [`JwtKey`] signs
+//! and verifies JWTs with the algorithms below:
+//!
+//! - `HS256`: HMAC with SHA-256, the key is a shared secret
+//! - `RS256`: RSA signature with SHA-256, the key is a PEM encoded private key
+//!   for signing, or a PEM encoded public key for verification only
+//!
+//! The key can be specified in a foreign server option, or stored in Vault or
+//! other [secret providers](crate::secret) and referenced by the secret id or
+//! name in option `<option>_id` or `<option>_name`. For example,
+//!
+//! ```rust,no_run
+//! # use supabase_wrappers::prelude::*;
+//! # use std::collections::HashMap;
+//! # use std::time::Duration;
+//! # fn main() -> Result<(), JwtError> {
+//! # let options = HashMap::new();
+//! // the key is in option `private_key`, `private_key_id` or `private_key_name`
+//! let key = JwtKey::from_options(&options, "private_key", "RS256")?;
+//! let claims = JwtClaims {
+//!     issuer: Some("sa@example.iam.gserviceaccount.com".to_string()),
+//!     audience: Some("https://oauth2.googleapis.com/token".to_string()),
+//!     ..Default::default()
+//! };
+//! let token = key.sign(&claims, Duration::from_secs(3600))?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! This module requires the `jwt` feature.
+
+use crate::error::FdwError;
+use crate::options::OptionsError;
+use crate::secret::{get_secret, get_secret_by_name};
+use jwt_simple::prelude::{
+    Claims, Duration as JwtDuration, HS256Key, JWTClaims, MACLike, RS256KeyPair, RS256PublicKey,
+    RSAKeyPairLike, RSAPublicKeyLike,
+};
+use pgrx::pg_sys::panic::ErrorReport;
+use serde_json::{Map, Value as JsonValue};
+use std::collections::HashMap;
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum JwtError {
+    #[error("{0}")]
+    OptionsError(#[from] OptionsError),
+
+    #[error("secret of option {0} is not found")]
+    SecretNotFound(String),
+
+    #[error("JWT algorithm {0} is not supported")]
+    UnsupportedAlgorithm(String),
+
+    #[error("invalid JWT key: {0}")]
+    InvalidKey(String),
+
+    #[error("sign JWT failed: {0}")]
+    SignError(String),
+
+    #[error("verify JWT failed: {0}")]
+    VerifyError(String),
+}
+
+impl From<JwtError> for ErrorReport {
+    fn from(value: JwtError) -> Self {
+        let msg = format!("{value}");
+        match value {
+            JwtError::OptionsError(e) => e.into(),
+            JwtError::SecretNotFound(_)
+            | JwtError::UnsupportedAlgorithm(_)
+            | JwtError::InvalidKey(_) => FdwError::InvalidOption(msg).into(),
+            JwtError::SignError(_) => FdwError::Other(msg).into(),
+            JwtError::VerifyError(_) => FdwError::Auth(msg).into(),
+        }
+    }
+}
+
+/// Claims of a JWT
+///
+/// The issued at, not before and expiration time claims are set when signing.
+#[derive(Debug, Clone, Default)]
+pub struct JwtClaims {
+    pub issuer: Option<String>,
+    pub subject: Option<String>,
+    pub audience: Option<String>,
+    pub custom: Map<String, JsonValue>,
+}
+
+impl From<JWTClaims<Map<String, JsonValue>>> for JwtClaims {
+    fn from(claims: JWTClaims<Map<String, JsonValue>>) -> Self {
+        Self {
+            issuer: claims.issuer,
+            subject: claims.subject,
+            audience: claims
+                .audiences
+                .and_then(|aud| aud.into_set().into_iter().next()),
+            custom: claims.custom,
+        }
+    }
+}
+
+/// Key to sign and verify JWTs
+pub enum JwtKey {
+    HS256(HS256Key),
+    RS256(RS256KeyPair),
+    RS256Public(RS256PublicKey),
+}
+
+impl JwtKey {
+    /// Create a key of an algorithm, `HS256` or `RS256`
+    pub fn new(algorithm: &str, key: &str) -> Result<Self, JwtError> {
+        let invalid_key = |e: jwt_simple::Error| JwtError::InvalidKey(e.to_string());
+        match algorithm {
+            "HS256" => Ok(Self::HS256(HS256Key::from_bytes(key.as_bytes()))),
+            "RS256" if key.contains("PUBLIC KEY") => Ok(Self::RS256Public(
+                RS256PublicKey::from_pem(key).map_err(invalid_key)?,
+            )),
+            "RS256" => Ok(Self::RS256(
+                RS256KeyPair::from_pem(key).map_err(invalid_key)?,
+            )),
+            _ => Err(JwtError::UnsupportedAlgorithm(algorithm.to_owned())),
+        }
+    }
+
+    /// Create a key of an algorithm from the option, or from the secret provider
+    /// by the secret id or name in option `<name>_id` or `<name>_name`
+    pub fn from_options(
+        options: &HashMap<String, String>,
+        name: &str,
+        algorithm: &str,
+    ) -> Result<Self, JwtError> {
+        if let Some(key) = options.get(name) {
+            return Self::new(algorithm, key);
+        }
+        for (suffix, get_secret) in [
+            (
+                "_id",
+                get_secret as fn(&HashMap<String, String>, &str) -> Option<String>,
+            ),
+            ("_name", get_secret_by_name),
+        ] {
+            let opt_name = format!("{}{}", name, suffix);
+            if let Some(secret) = options.get(&opt_name) {
+                let key = get_secret(options, secret).ok_or(JwtError::SecretNotFound(opt_name))?;
+                return Self::new(algorithm, &key);
+            }
+        }
+        Err(OptionsError::OptionNameNotFound(name.to_owned()).into())
+    }
+
+    /// Sign a JWT which is valid for a duration from now
+    pub fn sign(&self, claims: &JwtClaims, valid_for: Duration) -> Result<String, JwtError> {
+        let mut jwt_claims = Claims::with_custom_claims(
+            claims.custom.clone(),
+            JwtDuration::from_secs(valid_for.as_secs()),
+        );
+        if let Some(issuer) = &claims.issuer {
+            jwt_claims = jwt_claims.with_issuer(issuer);
+        }
+        if let Some(subject) = &claims.subject {
+            jwt_claims = jwt_claims.with_subject(subject);
+        }
+        if let Some(audience) = &claims.audience {
+            jwt_claims = jwt_claims.with_audience(audience);
+        }
+        match self {
+            Self::HS256(key) => key.authenticate(jwt_claims),
+            Self::RS256(key) => key.sign(jwt_claims),
+            Self::RS256Public(_) => {
+                return Err(JwtError::SignError(
+                    "public key cannot be used to sign".to_string(),
+                ))
+            }
+        }
+        .map_err(|e| JwtError::SignError(e.to_string()))
+    }
+
+    /// Verify a JWT and return its claims, the expiration time is checked
+    pub fn verify(&self, token: &str) -> Result<JwtClaims, JwtError> {
+        match self {
+            Self::HS256(key) => key.verify_token(token, None),
+            Self::RS256(key) => key.public_key().verify_token(token, None),
+            Self::RS256Public(key) => key.verify_token(token, None),
+        }
+        .map(JwtClaims::from)
+        .map_err(|e| JwtError::VerifyError(e.to_string()))
+    }
+}
@@ -43,17 +43,29 @@
 //!
 //! - Query planning phase
 //!   - [get_rel_size()](`interface::ForeignDataWrapper#method.get_rel_size`)
+//!   - [estimate_size()](`interface::ForeignDataWrapper#method.estimate_size`)
 //! - Scan phase
 //!   - [begin_scan()](`interface::ForeignDataWrapper#tymethod.begin_scan`) *required*
 //!   - [iter_scan()](`interface::ForeignDataWrapper#tymethod.iter_scan`) *required*
 //!   - [re_scan()](`interface::ForeignDataWrapper#method.re_scan`)
 //!   - [end_scan()](`interface::ForeignDataWrapper#tymethod.end_scan`) *required*
+//! - Parallel scan
+//!   - [parallel_safe()](`interface::ForeignDataWrapper#method.parallel_safe`)
+//!   - [scan_partitions()](`interface::ForeignDataWrapper#method.scan_partitions`)
+//!   - [begin_partition_scan()](`interface::ForeignDataWrapper#method.begin_partition_scan`)
 //! - Modify phase
 //!   - [begin_modify()](`interface::ForeignDataWrapper#method.begin_modify`)
 //!   - [insert()](`interface::ForeignDataWrapper#method.insert`)
+//!   - [upsert()](`interface::ForeignDataWrapper#method.upsert`)
 //!   - [update()](`interface::ForeignDataWrapper#method.update`)
 //!   - [delete()](`interface::ForeignDataWrapper#method.delete`)
 //!   - [end_modify()](`interface::ForeignDataWrapper#method.end_modify`)
+//!   - [plan_direct_modify()](`interface::ForeignDataWrapper#method.plan_direct_modify`)
+//!   - [exec_direct_modify()](`interface::ForeignDataWrapper#method.exec_direct_modify`)
+//!   - [truncate()](`interface::ForeignDataWrapper#method.truncate`)
+//! - Transaction end
+//!   - [commit()](`interface::ForeignDataWrapper#method.commit`)
+//!   - [rollback()](`interface::ForeignDataWrapper#method.rollback`)
 //!
 //! To give different functionalities to your FDW, you can choose different callback functions to implement. The required ones are `begin_scan`, `iter_scan` and `end_scan`, all the others are optional. See [Postgres FDW document](https://www.postgresql.org/docs/current/fdw-callbacks.html) for more details about FDW development.
 //!
@@ -270,8 +282,8 @@
 //!          Sort Key: col
 //!          ->  Foreign Scan on hello  (cost=0.00..1.00 rows=1 width=0)
 //!                Filter: (id = 1)
-//!                Wrappers: quals = [Qual { field: "id", operator: "=", value: Cell(I32(1)), use_or: false, param: None }]
-//!                Wrappers: tgts = [Column { name: "id", num: 1, type_oid: 20 }, Column { name: "col", num: 2, type_oid: 25 }]
+//!                Wrappers: quals = [Qual { field: "id", operator: "=", value: Cell(I32(1)), use_or: false, param: None, sub_quals: [], json_path: [], type_oid: Oid(23), collate: None }]
+//!                Wrappers: tgts = [Column { name: "id", num: 1, type_oid: 20, options: {} }, Column { name: "col", num: 2, type_oid: 25, options: {} }]
 //!                Wrappers: sorts = [Sort { field: "col", field_no: 2, reversed: false, nulls_first: false, collate: None }]
 //!                Wrappers: limit = Some(Limit { count: 1, offset: 0 })
 //! (9 rows)
@@ -292,26 +304,75 @@
 //! - [SQL Server](https://github.com/supabase/wrappers/tree/main/wrappers/src/fdw/mssql_fdw): A FDW for [Microsoft SQL Server](https://www.microsoft.com/en-au/sql-server/) which supports data read only.
 //! - [Redis](https://github.com/supabase/wrappers/tree/main/wrappers/src/fdw/redis_fdw): A FDW for [Redis](https://redis.io/) which supports data read only.
 
+pub mod audit;
+#[cfg(feature = "aws")]
+pub mod aws;
+pub mod cache;
+pub mod circuit_breaker;
+pub mod error;
+pub mod guc;
+pub mod health;
 pub mod interface;
+pub mod json;
+#[cfg(feature = "jwt")]
+pub mod jwt;
+pub mod mask;
+pub mod metrics;
+#[cfg(feature = "oauth2")]
+pub mod oauth2;
 pub mod options;
+pub mod pagination;
+pub mod pool;
+pub mod prefetch;
+pub mod rate_limit;
+pub mod redact;
+pub mod scan_buffer;
+pub mod secret;
+pub mod shmem;
+pub mod trace;
 pub mod utils;
 
 /// The prelude includes all necessary imports to make Wrappers work
 pub mod prelude {
+    pub use crate::audit::*;
+    #[cfg(feature = "aws")]
+    pub use crate::aws::*;
+    pub use crate::cache::*;
+    pub use crate::circuit_breaker::*;
+    pub use crate::error::*;
+    pub use crate::guc::*;
+    pub use crate::health::*;
     pub use crate::import_foreign_schema::*;
     pub use crate::instance::ForeignServer;
     pub use crate::interface::*;
+    pub use crate::json::*;
+    #[cfg(feature = "jwt")]
+    pub use crate::jwt::*;
+    pub use crate::mask::*;
+    pub use crate::metrics::*;
+    #[cfg(feature = "oauth2")]
+    pub use crate::oauth2::*;
     pub use crate::options::*;
+    pub use crate::pool::*;
+    pub use crate::prefetch::*;
+    pub use crate::rate_limit::*;
+    pub use crate::redact::*;
+    pub use crate::scan_buffer::*;
+    pub use crate::secret::*;
+    pub use crate::shmem::*;
+    pub use crate::trace::*;
     pub use crate::utils::*;
     pub use crate::wrappers_fdw;
-    pub use tokio::runtime::Runtime;
+    pub use crate::FdwOptions;
 }
 
 use pgrx::prelude::*;
 use pgrx::AllocatedByPostgres;
 
+mod aggregate;
 mod import_foreign_schema;
 mod instance;
+mod join;
 mod limit;
 mod memctx;
 mod modify;
@@ -319,8 +380,12 @@ mod polyfill;
 mod qual;
 mod scan;
 mod sort;
+#[cfg(not(feature = "pg13"))]
+mod truncate;
+mod write_buffer;
+mod xact;
 
 /// PgBox'ed `FdwRoutine`, used in [`fdw_routine`](interface::ForeignDataWrapper::fdw_routine)
 pub type FdwRoutine<A = AllocatedByPostgres> = PgBox<pg_sys::FdwRoutine, A>;
 
-pub use supabase_wrappers_macros::wrappers_fdw;
+pub use supabase_wrappers_macros::{wrappers_fdw, FdwOptions};
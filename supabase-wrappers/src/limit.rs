@@ -14,6 +14,11 @@ pub(crate) unsafe fn extract_limit(
         return None;
     }
 
+    // WITH TIES may return more rows than the limit count
+    if (*parse).limitOption == pg_sys::LimitOption::LIMIT_OPTION_WITH_TIES {
+        return None;
+    }
+
     // only push down constant LIMITs that are not NULL
     let limit_count = (*parse).limitCount as *mut pg_sys::Const;
     if limit_count.is_null() || !is_a(limit_count as *mut pg_sys::Node, pg_sys::NodeTag::T_Const) {
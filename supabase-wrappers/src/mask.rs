@@ -0,0 +1,126 @@
+//! Column masking
+//!
+//! Foreign tables often expose sensitive fields, like emails and phone numbers,
+//! which not every role should see. They can be masked by the column option
+//! `mask`, the masking is done by the framework when returning the rows, so
+//! the FDWs don't need to implement it:
+//!
+//! - `partial`: keep the first character and domain of an email, or the last
+//!   4 characters of other values, the rest are replaced by `*`
+//! - `hash`: replace the value by its SHA-256 hex digest, so it can still be
+//!   used to join or group the rows
+//! - `null`: replace the value by null
+//!
+//! The `partial` and `hash` masks only apply to text values, other values are
+//! replaced by null. The roles in column option `unmask_roles`, a comma
+//! separated list, and their members see the original values. For example,
+//!
+//! ```sql
+//! create foreign table customers (
+//!   id text,
+//!   email text options (mask 'partial', unmask_roles 'support,billing')
+//! )
+//! server my_server;
+//! ```
+
+use crate::interface::{Cell, Column, Qual};
+use crate::options::OptionsError;
+use crate::utils::get_column_options;
+use pgrx::pg_sys;
+use sha2::{Digest, Sha256};
+use std::ffi::CString;
+
+/// How a masked column value is returned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaskKind {
+    Partial,
+    Hash,
+    Null,
+}
+
+impl MaskKind {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "partial" => Some(Self::Partial),
+            "hash" => Some(Self::Hash),
+            "null" => Some(Self::Null),
+            _ => None,
+        }
+    }
+}
+
+// check if current user has the privileges of the role, superusers have the
+// privileges of all roles
+fn has_privs_of(role: &str) -> bool {
+    let Ok(role) = CString::new(role) else {
+        return false;
+    };
+    unsafe {
+        let role_oid = pg_sys::get_role_oid(role.as_ptr(), true);
+        role_oid != pg_sys::InvalidOid && pg_sys::has_privs_of_role(pg_sys::GetUserId(), role_oid)
+    }
+}
+
+/// Get the mask of a column for current user, or `None` if the column is not
+/// masked or the user is in its `unmask_roles`
+pub fn column_mask(col: &Column) -> Result<Option<MaskKind>, OptionsError> {
+    let Some(mask) = col.options.get("mask") else {
+        return Ok(None);
+    };
+    let kind = MaskKind::parse(mask)
+        .ok_or_else(|| OptionsError::OptionValueIsInvalid("mask".to_string(), mask.to_owned()))?;
+    let unmasked = col.options.get("unmask_roles").is_some_and(|roles| {
+        roles
+            .split(',')
+            .map(|role| role.trim())
+            .filter(|role| !role.is_empty())
+            .any(has_privs_of)
+    });
+    Ok((!unmasked).then_some(kind))
+}
+
+// keep the first character of an email's local part and its domain, or the
+// last 4 characters of other values, the values with no more than 4
+// characters are fully masked
+fn mask_partial(s: &str) -> String {
+    if let Some(at) = s.find('@') {
+        let (local, domain) = s.split_at(at);
+        let mut chars = local.chars();
+        let first = chars.next().map(String::from).unwrap_or_default();
+        return format!("{}{}{}", first, "*".repeat(chars.count()), domain);
+    }
+    let len = s.chars().count();
+    let keep = if len > 4 { 4 } else { 0 };
+    s.chars()
+        .enumerate()
+        .map(|(i, c)| if i < len - keep { '*' } else { c })
+        .collect()
+}
+
+/// Mask a cell value, return `None` if it is masked to null
+pub fn mask_cell(cell: Cell, kind: MaskKind) -> Option<Cell> {
+    match (kind, cell) {
+        (MaskKind::Partial, Cell::String(s)) => Some(Cell::String(mask_partial(&s))),
+        (MaskKind::Hash, Cell::String(s)) => {
+            Some(Cell::String(hex::encode(Sha256::digest(s.as_bytes()))))
+        }
+        _ => None,
+    }
+}
+
+// check if the qual or any of its sub quals is on a masked column of the
+// foreign table
+pub(crate) unsafe fn is_masked_qual(ftable_id: pg_sys::Oid, qual: &Qual) -> bool {
+    if qual.is_bool_expr() {
+        return qual
+            .sub_quals
+            .iter()
+            .any(|sub_qual| is_masked_qual(ftable_id, sub_qual));
+    }
+    let Ok(field) = CString::new(qual.field.as_str()) else {
+        return false;
+    };
+    let attnum = pg_sys::get_attnum(ftable_id, field.as_ptr());
+    attnum != pg_sys::InvalidAttrNumber as pg_sys::AttrNumber
+        && get_column_options(ftable_id, attnum).contains_key("mask")
+}
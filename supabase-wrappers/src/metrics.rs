@@ -0,0 +1,100 @@
+//! Runtime metrics of the foreign servers
+//!
+//! The counters below are kept for each foreign server, so operators can see
+//! what the FDWs are doing, like the `pg_stat_*` views:
+//!
+//! - scans: number of foreign table scans started
+//! - rows fetched: number of rows returned by the scans
+//! - bytes received: size of the remote responses
+//! - remote requests: number of requests sent to the remote server, including
+//!   retries
+//! - errors: number of errors raised by the FDW
+//! - retries: number of retried remote requests
+//! - wait time: total time waiting for the remote responses, in microseconds
+//!
+//! The scan metrics are recorded by the framework, and the FDWs record the
+//! remote request metrics by [`add_server_metric`]. The counters are in shared
+//! memory so they cover all backends if the extension is loaded by
+//! `shared_preload_libraries`, see
+//! [`init_shared_memory`](crate::shmem::init_shared_memory), otherwise each
+//! backend has its own counters.
+
+use crate::shmem::{with_server_states, ServerStates};
+use pgrx::prelude::*;
+use pgrx::PgLwLock;
+use std::cell::RefCell;
+
+/// Metrics of a foreign server
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ServerMetrics {
+    pub scans: i64,
+    pub rows_fetched: i64,
+    pub bytes_received: i64,
+    pub remote_requests: i64,
+    pub errors: i64,
+    pub retries: i64,
+    pub wait_time_us: i64,
+}
+
+/// Metric of a foreign server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServerMetric {
+    Scans,
+    RowsFetched,
+    BytesReceived,
+    RemoteRequests,
+    Errors,
+    Retries,
+    WaitTimeUs,
+}
+
+impl ServerMetrics {
+    fn add(&mut self, metric: ServerMetric, value: i64) {
+        let counter = match metric {
+            ServerMetric::Scans => &mut self.scans,
+            ServerMetric::RowsFetched => &mut self.rows_fetched,
+            ServerMetric::BytesReceived => &mut self.bytes_received,
+            ServerMetric::RemoteRequests => &mut self.remote_requests,
+            ServerMetric::Errors => &mut self.errors,
+            ServerMetric::Retries => &mut self.retries,
+            ServerMetric::WaitTimeUs => &mut self.wait_time_us,
+        };
+        *counter = counter.saturating_add(value);
+    }
+}
+
+pub(crate) static SERVER_METRICS: PgLwLock<ServerStates<ServerMetrics>> = PgLwLock::new();
+
+thread_local! {
+    // metrics used when shared memory is not available
+    static LOCAL_METRICS: RefCell<ServerStates<ServerMetrics>> =
+        RefCell::new(ServerStates::default());
+}
+
+/// Add value to a metric of the foreign server
+pub fn add_server_metric(server_oid: pg_sys::Oid, metric: ServerMetric, value: i64) {
+    if value == 0 || server_oid == pg_sys::InvalidOid {
+        return;
+    }
+    with_server_states(&SERVER_METRICS, &LOCAL_METRICS, |states| {
+        states
+            .get_or_insert_with(server_oid, ServerMetrics::default)
+            .add(metric, value)
+    });
+}
+
+/// Get the metrics of the foreign servers in current database
+pub fn server_metrics() -> Vec<(pg_sys::Oid, ServerMetrics)> {
+    with_server_states(&SERVER_METRICS, &LOCAL_METRICS, |states| {
+        states.current_database()
+    })
+}
+
+/// Reset the metrics of all foreign servers in current database
+pub fn reset_server_metrics() {
+    with_server_states(&SERVER_METRICS, &LOCAL_METRICS, |states| {
+        for (server_oid, _) in states.current_database() {
+            states.remove(server_oid);
+        }
+    });
+}
@@ -14,11 +14,17 @@ use super::instance;
 use super::memctx;
 use super::polyfill;
 use super::utils;
+use super::write_buffer;
+use super::xact;
 
 // Fdw private state for modify
 struct FdwModifyState<E: Into<ErrorReport>, W: ForeignDataWrapper<E>> {
-    // foreign data wrapper instance
-    instance: W,
+    // foreign table oid
+    ftable_id: Oid,
+
+    // foreign data wrapper instance, it is handed over to current transaction
+    // when the modification ends and re-created if the plan is executed again
+    instance: Option<W>,
 
     // row id attribute number and type id
     rowid_name: String,
@@ -28,6 +34,22 @@ struct FdwModifyState<E: Into<ErrorReport>, W: ForeignDataWrapper<E>> {
     // foreign table options
     opts: HashMap<String, String>,
 
+    // max number of rows inserted in one batch, set by foreign table option
+    // `batch_size`
+    batch_size: usize,
+
+    // all the foreign table columns if there is RETURNING clause, otherwise
+    // it is empty
+    returning_cols: Vec<Column>,
+
+    // conflict action of insert, set by ON CONFLICT DO NOTHING or foreign
+    // table option `upsert`
+    on_conflict: Option<OnConflict>,
+
+    // if the modified rows are recorded for later scans in current
+    // transaction, set by foreign table option `read_your_writes`
+    read_your_writes: bool,
+
     // temporary memory context per foreign table, created under Wrappers root
     // memory context
     tmp_ctx: PgMemoryContexts,
@@ -40,11 +62,16 @@ struct FdwModifyState<E: Into<ErrorReport>, W: ForeignDataWrapper<E>> {
 impl<E: Into<ErrorReport>, W: ForeignDataWrapper<E>> FdwModifyState<E, W> {
     unsafe fn new(foreigntableid: Oid, tmp_ctx: PgMemoryContexts) -> Self {
         Self {
-            instance: instance::create_fdw_instance_from_table_id(foreigntableid),
+            ftable_id: foreigntableid,
+            instance: Some(instance::create_fdw_instance_from_table_id(foreigntableid)),
             rowid_name: String::default(),
             rowid_attno: 0,
             rowid_typid: Oid::INVALID,
             opts: HashMap::new(),
+            batch_size: 1,
+            returning_cols: Vec::new(),
+            on_conflict: None,
+            read_your_writes: false,
             tmp_ctx,
             _phantom: PhantomData,
             #[cfg(feature = "pg13")]
@@ -52,29 +79,155 @@ impl<E: Into<ErrorReport>, W: ForeignDataWrapper<E>> FdwModifyState<E, W> {
         }
     }
 
+    fn instance(&mut self) -> &mut W {
+        self.instance
+            .as_mut()
+            .expect("foreign data wrapper instance is not initialized")
+    }
+
     fn begin_modify(&mut self) -> Result<(), E> {
-        self.instance.begin_modify(&self.opts)
+        let instance = self.instance.as_mut().unwrap();
+        instance.begin_modify(&self.opts)
     }
 
     fn insert(&mut self, row: &Row) -> Result<(), E> {
-        self.instance.insert(row)
+        self.instance().insert(row)
+    }
+
+    #[cfg(not(feature = "pg13"))]
+    fn insert_rows(&mut self, rows: &[Row]) -> Result<(), E> {
+        self.instance().insert_rows(rows)
+    }
+
+    fn insert_returning(&mut self, row: &Row) -> Result<Option<Row>, E> {
+        let instance = self.instance.as_mut().unwrap();
+        instance.insert_returning(row, &self.returning_cols)
+    }
+
+    fn upsert(&mut self, row: &Row, on_conflict: OnConflict) -> Result<bool, E> {
+        self.instance().upsert(row, on_conflict)
     }
 
     fn update(&mut self, rowid: &Cell, new_row: &Row) -> Result<(), E> {
-        self.instance.update(rowid, new_row)
+        self.instance().update(rowid, new_row)
+    }
+
+    fn update_returning(&mut self, rowid: &Cell, new_row: &Row) -> Result<Option<Row>, E> {
+        let instance = self.instance.as_mut().unwrap();
+        instance.update_returning(rowid, new_row, &self.returning_cols)
     }
 
     fn delete(&mut self, rowid: &Cell) -> Result<(), E> {
-        self.instance.delete(rowid)
+        self.instance().delete(rowid)
     }
 
+    // end the modification and hand over the instance to current transaction
     fn end_modify(&mut self) -> Result<(), E> {
-        self.instance.end_modify()
+        if let Some(mut instance) = self.instance.take() {
+            instance.end_modify()?;
+            xact::register_instance(instance);
+        }
+        Ok(())
     }
 }
 
 impl<E: Into<ErrorReport>, W: ForeignDataWrapper<E>> utils::SerdeList for FdwModifyState<E, W> {}
 
+// the remote modification cannot be reported without executing it, so it is
+// not allowed in dry run
+fn check_dry_run() {
+    if dry_run() {
+        report_error(
+            PgSqlErrorCode::ERRCODE_FDW_ERROR,
+            "foreign table cannot be modified when wrappers.dry_run is on",
+        );
+    }
+}
+
+// get max number of rows inserted in one batch from foreign table options
+fn get_batch_size(opts: &HashMap<String, String>) -> usize {
+    opts.get("batch_size")
+        .map(|v| match v.parse::<usize>() {
+            Ok(v) if v > 0 => v,
+            _ => {
+                pgrx::error!("invalid option batch_size: {}", v);
+            }
+        })
+        .unwrap_or(1)
+}
+
+// get conflict action of insert, ON CONFLICT DO NOTHING takes precedence over
+// the foreign table option `upsert`
+unsafe fn get_on_conflict(
+    plan: *mut pg_sys::ModifyTable,
+    opts: &HashMap<String, String>,
+) -> Option<OnConflict> {
+    if !plan.is_null() {
+        if (*plan).operation != pg_sys::CmdType::CMD_INSERT {
+            return None;
+        }
+        if (*plan).onConflictAction == pg_sys::OnConflictAction::ONCONFLICT_NOTHING {
+            return Some(OnConflict::DoNothing);
+        }
+    }
+    match opts.get("upsert").map(|v| v.as_str()) {
+        Some("true") => Some(OnConflict::DoUpdate),
+        Some("false") | None => None,
+        Some(v) => {
+            pgrx::error!("invalid option upsert: {}", v);
+        }
+    }
+}
+
+// get all the columns in relation description
+unsafe fn get_table_columns(relid: Oid, tup_desc: &PgTupleDesc) -> Vec<Column> {
+    tup_desc
+        .iter()
+        .filter(|a| !a.attisdropped)
+        .map(|a| Column {
+            name: pgrx::name_data_to_str(&a.attname).to_string(),
+            num: a.attnum as usize,
+            type_oid: utils::column_type_oid(a.atttypid),
+            options: utils::get_column_options(relid, a.attnum),
+        })
+        .collect()
+}
+
+// store the row returned by the FDW to the slot for RETURNING clause, the
+// columns not in the returned row keep their values in the slot
+unsafe fn store_returning_row(slot: *mut pg_sys::TupleTableSlot, mut row: Row) {
+    let tup_desc = PgTupleDesc::from_pg_copy((*slot).tts_tupleDescriptor);
+    let natts = tup_desc.len();
+    if natts > (*slot).tts_nvalid as usize {
+        pg_sys::slot_getsomeattrs_int(slot, natts as _);
+    }
+    let mut values = std::slice::from_raw_parts((*slot).tts_values, natts).to_vec();
+    let mut nulls = std::slice::from_raw_parts((*slot).tts_isnull, natts).to_vec();
+
+    for (col, cell) in row.cols.drain(..).zip(row.cells.drain(..)) {
+        let att_idx = tup_desc
+            .iter()
+            .position(|a| !a.attisdropped && pgrx::name_data_to_str(&a.attname) == col);
+        if let Some(att_idx) = att_idx {
+            match cell {
+                Some(cell) => {
+                    let type_oid = tup_desc.get(att_idx).unwrap().atttypid;
+                    values[att_idx] = utils::cell_to_datum(cell, type_oid).unwrap();
+                    nulls[att_idx] = false;
+                }
+                None => nulls[att_idx] = true,
+            }
+        }
+    }
+
+    let tuple = pg_sys::heap_form_tuple(
+        (*slot).tts_tupleDescriptor,
+        values.as_mut_ptr(),
+        nulls.as_mut_ptr(),
+    );
+    pg_sys::ExecForceStoreHeapTuple(tuple, slot, true);
+}
+
 // find rowid column in relation description
 unsafe fn find_rowid_column(
     target_relation: pg_sys::Relation,
@@ -170,10 +323,11 @@ pub(super) extern "C" fn plan_foreign_modify<E: Into<ErrorReport>, W: ForeignDat
 ) -> *mut pg_sys::List {
     debug2!("---> plan_foreign_modify");
     unsafe {
-        if !(*plan).returningLists.is_null() {
+        let returning = !(*plan).returningLists.is_null();
+        if returning && (*plan).operation == pg_sys::CmdType::CMD_DELETE {
             report_error(
                 PgSqlErrorCode::ERRCODE_FDW_ERROR,
-                "RETURNING is not supported",
+                "RETURNING is not supported for DELETE",
             )
         }
 
@@ -212,6 +366,12 @@ pub(super) extern "C" fn plan_foreign_modify<E: Into<ErrorReport>, W: ForeignDat
 
                 state.rowid_name = rowid_name.to_string();
                 state.rowid_typid = attr.atttypid;
+                state.batch_size = get_batch_size(&opts);
+                if returning {
+                    state.returning_cols = get_table_columns(ftable_id, &tup_desc);
+                }
+                state.on_conflict = get_on_conflict(plan, &opts);
+                state.read_your_writes = write_buffer::is_enabled(&opts);
                 state.opts = opts;
 
                 #[cfg(feature = "pg13")]
@@ -259,6 +419,7 @@ pub(super) extern "C" fn begin_foreign_modify<E: Into<ErrorReport>, W: ForeignDa
     if eflags & pg_sys::EXEC_FLAG_EXPLAIN_ONLY as c_int > 0 {
         return;
     }
+    check_dry_run();
 
     unsafe {
         let mut state = FdwModifyState::<E, W>::deserialize_from_list(fdw_private as _);
@@ -273,12 +434,66 @@ pub(super) extern "C" fn begin_foreign_modify<E: Into<ErrorReport>, W: ForeignDa
         state.rowid_attno =
             pg_sys::ExecFindJunkAttributeInTlist((*subplan).targetlist, rowid_name_c);
 
+        // the instance was handed over to previous transaction if the plan is
+        // executed again
+        if state.instance.is_none() {
+            let ftable_id = (*(*rinfo).ri_RelationDesc).rd_id;
+            state.instance = Some(instance::create_fdw_instance_from_table_id(ftable_id));
+        }
+
         state.begin_modify().report_unwrap();
 
         (*rinfo).ri_FdwState = state.into_pg() as _;
     }
 }
 
+// called when rows are inserted by COPY FROM or routed to a foreign table
+// partition, there is no planning phase so the modify state is created here
+#[pg_guard]
+pub(super) extern "C" fn begin_foreign_insert<E: Into<ErrorReport>, W: ForeignDataWrapper<E>>(
+    mtstate: *mut pg_sys::ModifyTableState,
+    rinfo: *mut pg_sys::ResultRelInfo,
+) {
+    debug2!("---> begin_foreign_insert");
+    check_dry_run();
+    unsafe {
+        let ftable_id = (*(*rinfo).ri_RelationDesc).rd_id;
+        let ftable = pg_sys::GetForeignTable(ftable_id);
+        let opts = options_to_hashmap((*ftable).options).report_unwrap();
+
+        // refresh leftover memory context
+        let ctx_name = format!("Wrappers_modify_{}", ftable_id.as_u32());
+        let ctx = memctx::refresh_wrappers_memctx(&ctx_name);
+
+        // create modify state, rowid column is not needed for insert
+        let mut state = FdwModifyState::<E, W>::new(ftable_id, ctx);
+        if let Some(rowid_name) = opts.get("rowid_column") {
+            state.rowid_name = rowid_name.to_string();
+        }
+        state.batch_size = get_batch_size(&opts);
+        if !(*rinfo).ri_returningList.is_null() {
+            let tup_desc = PgTupleDesc::from_pg_copy((*(*rinfo).ri_RelationDesc).rd_att);
+            state.returning_cols = get_table_columns(ftable_id, &tup_desc);
+        }
+
+        // there is no plan for COPY FROM
+        let plan = if mtstate.is_null() {
+            ptr::null_mut()
+        } else {
+            (*mtstate).ps.plan as *mut pg_sys::ModifyTable
+        };
+        state.on_conflict = get_on_conflict(plan, &opts);
+        state.read_your_writes = write_buffer::is_enabled(&opts);
+        state.opts = opts;
+
+        state.begin_modify().report_unwrap();
+
+        // install callback to drop the state when memory context is reset
+        let mut ctx = PgMemoryContexts::For(state.tmp_ctx.value());
+        (*rinfo).ri_FdwState = ctx.leak_and_drop_on_delete(state) as _;
+    }
+}
+
 #[pg_guard]
 pub(super) extern "C" fn exec_foreign_insert<E: Into<ErrorReport>, W: ForeignDataWrapper<E>>(
     _estate: *mut pg_sys::EState,
@@ -293,12 +508,101 @@ pub(super) extern "C" fn exec_foreign_insert<E: Into<ErrorReport>, W: ForeignDat
         );
 
         let row = utils::tuple_table_slot_to_row(slot);
-        state.insert(&row).report_unwrap();
+        if let Some(on_conflict) = state.on_conflict {
+            // skipped row is not counted as inserted
+            if !state.upsert(&row, on_conflict).report_unwrap() {
+                return ptr::null_mut();
+            }
+        } else if state.returning_cols.is_empty() {
+            state.insert(&row).report_unwrap();
+        } else if let Some(row) = state.insert_returning(&row).report_unwrap() {
+            store_returning_row(slot, row);
+        }
+
+        if state.read_your_writes {
+            // the slot has the values returned by the FDW for RETURNING clause
+            let row = if state.returning_cols.is_empty() {
+                row
+            } else {
+                utils::tuple_table_slot_to_row(slot)
+            };
+            write_buffer::record_insert(state.ftable_id, &state.rowid_name, &row);
+        }
     }
 
     slot
 }
 
+#[cfg(not(feature = "pg13"))]
+#[pg_guard]
+pub(super) extern "C" fn get_foreign_modify_batch_size<
+    E: Into<ErrorReport>,
+    W: ForeignDataWrapper<E>,
+>(
+    rinfo: *mut pg_sys::ResultRelInfo,
+) -> c_int {
+    debug2!("---> get_foreign_modify_batch_size");
+    unsafe {
+        let fdw_state = (*rinfo).ri_FdwState as *mut FdwModifyState<E, W>;
+        if fdw_state.is_null() {
+            return 1;
+        }
+
+        // row triggers, check options and RETURNING need to process rows
+        // one by one
+        let trig_desc = (*rinfo).ri_TrigDesc;
+        if !(*rinfo).ri_WithCheckOptions.is_null()
+            || !(*rinfo).ri_projectReturning.is_null()
+            || (!trig_desc.is_null()
+                && ((*trig_desc).trig_insert_before_row || (*trig_desc).trig_insert_after_row))
+        {
+            return 1;
+        }
+
+        // upsert is done row by row
+        let state = PgBox::<FdwModifyState<E, W>>::from_pg(fdw_state);
+        if state.on_conflict.is_some() {
+            return 1;
+        }
+        state.batch_size.min(c_int::MAX as usize) as c_int
+    }
+}
+
+#[cfg(not(feature = "pg13"))]
+#[pg_guard]
+pub(super) extern "C" fn exec_foreign_batch_insert<
+    E: Into<ErrorReport>,
+    W: ForeignDataWrapper<E>,
+>(
+    _estate: *mut pg_sys::EState,
+    rinfo: *mut pg_sys::ResultRelInfo,
+    slots: *mut *mut pg_sys::TupleTableSlot,
+    _plan_slots: *mut *mut pg_sys::TupleTableSlot,
+    num_slots: *mut c_int,
+) -> *mut *mut pg_sys::TupleTableSlot {
+    debug2!("---> exec_foreign_batch_insert");
+    unsafe {
+        let mut state = PgBox::<FdwModifyState<E, W>>::from_pg(
+            (*rinfo).ri_FdwState as *mut FdwModifyState<E, W>,
+        );
+
+        let slots_cnt = *num_slots as usize;
+        let rows: Vec<Row> = std::slice::from_raw_parts(slots, slots_cnt)
+            .iter()
+            .map(|slot| utils::tuple_table_slot_to_row(*slot))
+            .collect();
+        state.insert_rows(&rows).report_unwrap();
+
+        if state.read_your_writes {
+            for row in rows.iter() {
+                write_buffer::record_insert(state.ftable_id, &state.rowid_name, row);
+            }
+        }
+    }
+
+    slots
+}
+
 unsafe fn get_rowid_cell<E: Into<ErrorReport>, W: ForeignDataWrapper<E>>(
     state: &FdwModifyState<E, W>,
     plan_slot: *mut pg_sys::TupleTableSlot,
@@ -324,6 +628,10 @@ pub(super) extern "C" fn exec_foreign_delete<E: Into<ErrorReport>, W: ForeignDat
         let cell = get_rowid_cell(&state, plan_slot);
         if let Some(rowid) = cell {
             state.delete(&rowid).report_unwrap();
+
+            if state.read_your_writes {
+                write_buffer::record_delete(state.ftable_id, &state.rowid_name, &rowid);
+            }
         }
     }
 
@@ -367,7 +675,15 @@ pub(super) extern "C" fn exec_foreign_update<E: Into<ErrorReport>, W: ForeignDat
                 }
             });
 
-            state.update(&rowid, &new_row).report_unwrap();
+            if state.returning_cols.is_empty() {
+                state.update(&rowid, &new_row).report_unwrap();
+            } else if let Some(row) = state.update_returning(&rowid, &new_row).report_unwrap() {
+                store_returning_row(slot, row);
+            }
+
+            if state.read_your_writes {
+                write_buffer::record_update(state.ftable_id, &state.rowid_name, &rowid, &new_row);
+            }
         }
     }
 
@@ -386,5 +702,26 @@ pub(super) extern "C" fn end_foreign_modify<E: Into<ErrorReport>, W: ForeignData
             let mut state = PgBox::<FdwModifyState<E, W>>::from_pg(fdw_state);
             state.end_modify().report_unwrap();
         }
+
+        // cached scan results are stale after the modification
+        invalidate_cache((*(*rinfo).ri_RelationDesc).rd_id);
+    }
+}
+
+#[pg_guard]
+pub(super) extern "C" fn end_foreign_insert<E: Into<ErrorReport>, W: ForeignDataWrapper<E>>(
+    _estate: *mut pg_sys::EState,
+    rinfo: *mut pg_sys::ResultRelInfo,
+) {
+    debug2!("---> end_foreign_insert");
+    unsafe {
+        let fdw_state = (*rinfo).ri_FdwState as *mut FdwModifyState<E, W>;
+        if !fdw_state.is_null() {
+            let mut state = PgBox::<FdwModifyState<E, W>>::from_pg(fdw_state);
+            state.end_modify().report_unwrap();
+        }
+
+        // cached scan results are stale after the modification
+        invalidate_cache((*(*rinfo).ri_RelationDesc).rd_id);
     }
 }
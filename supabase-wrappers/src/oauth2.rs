@@ -0,0 +1,228 @@
+//! OAuth2 client credentials flow
+//!
+//! Many APIs, like Auth0 Management API and Google services, only accept
+//! bearer tokens which expire in an hour or so. [`OAuth2Client`] exchanges the
+//! client id and secret for an access token from the token endpoint, caches it
+//! in the backend, and requests a new one before it expires, so a long-lived
+//! session can keep querying the foreign tables.
+//!
+//! It is set up by the foreign server options below:
+//!
+//! - `token_url`: URL of the token endpoint
+//! - `client_id`: client id
+//! - `client_secret`: client secret, or `client_secret_id` and
+//!   `client_secret_name` for the secret id and name in Vault or other
+//!   [secret providers](crate::secret)
+//! - `scope`: space-separated scopes of the access token, optional
+//! - `audience`: audience of the access token, required by some providers like
+//!   Auth0, optional
+//!
+//! For example,
+//!
+//! ```rust,no_run
+//! # use supabase_wrappers::prelude::*;
+//! # use std::collections::HashMap;
+//! # fn main() -> Result<(), OAuth2Error> {
+//! # let options = HashMap::new();
+//! if let Some(oauth2) = OAuth2Client::from_options(&options)? {
+//!     // get the cached token, or request a new one if it is expiring
+//!     let token = oauth2.access_token()?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! This module requires the `oauth2` feature.
+
+use crate::error::FdwError;
+use crate::options::{OptionsError, Timeouts};
+use crate::redact::register_secret;
+use crate::secret::{get_secret, get_secret_by_name};
+use crate::utils::{create_async_runtime, CreateRuntimeError, Runtime};
+use pgrx::pg_sys::panic::ErrorReport;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+// a token is refreshed if it expires in this duration, so it won't expire
+// while the request is in flight
+const REFRESH_MARGIN: Duration = Duration::from_secs(60);
+
+// token lifetime if the token endpoint doesn't return `expires_in`
+const DEFAULT_EXPIRES_IN: u64 = 3600;
+
+struct CachedToken {
+    access_token: String,
+    expires_at: Instant,
+}
+
+thread_local! {
+    // access tokens keyed by token url, client id, scope and audience, so
+    // they are shared by all the FDW instances in the backend
+    static TOKENS: RefCell<HashMap<String, CachedToken>> = RefCell::new(HashMap::new());
+}
+
+#[derive(Error, Debug)]
+pub enum OAuth2Error {
+    #[error("{0}")]
+    OptionsError(#[from] OptionsError),
+
+    #[error("secret of option {0} is not found")]
+    SecretNotFound(String),
+
+    #[error("{0}")]
+    CreateRuntimeError(#[from] CreateRuntimeError),
+
+    #[error("request access token failed: {0}")]
+    RequestError(#[from] reqwest::Error),
+
+    #[error("request access token failed with HTTP {0}: {1}")]
+    TokenRejected(u16, String),
+
+    #[error("invalid access token response: {0}")]
+    InvalidResponse(String),
+}
+
+impl From<OAuth2Error> for ErrorReport {
+    fn from(value: OAuth2Error) -> Self {
+        let msg = format!("{value}");
+        match value {
+            OAuth2Error::OptionsError(e) => e.into(),
+            OAuth2Error::SecretNotFound(_) => FdwError::InvalidOption(msg).into(),
+            OAuth2Error::CreateRuntimeError(e) => e.into(),
+            OAuth2Error::RequestError(_) => FdwError::Transient(msg).into(),
+            OAuth2Error::TokenRejected(status, _) => FdwError::from_http_status(status, msg).into(),
+            OAuth2Error::InvalidResponse(_) => FdwError::Other(msg).into(),
+        }
+    }
+}
+
+/// OAuth2 client credentials flow of a foreign server
+pub struct OAuth2Client {
+    token_url: String,
+    client_id: String,
+    client_secret: String,
+    scope: Option<String>,
+    audience: Option<String>,
+    http: reqwest::Client,
+    rt: Runtime,
+}
+
+impl OAuth2Client {
+    /// Create the client from the foreign server options, return `None` if
+    /// option `client_id` is not specified
+    pub fn from_options(options: &HashMap<String, String>) -> Result<Option<Self>, OAuth2Error> {
+        let Some(client_id) = options.get("client_id") else {
+            return Ok(None);
+        };
+        let token_url = options
+            .get("token_url")
+            .ok_or_else(|| OptionsError::OptionNameNotFound("token_url".to_string()))?;
+        let client_secret = if let Some(secret) = options.get("client_secret") {
+            secret.to_owned()
+        } else if let Some(id) = options.get("client_secret_id") {
+            get_secret(options, id)
+                .ok_or_else(|| OAuth2Error::SecretNotFound("client_secret_id".to_string()))?
+        } else if let Some(name) = options.get("client_secret_name") {
+            get_secret_by_name(options, name)
+                .ok_or_else(|| OAuth2Error::SecretNotFound("client_secret_name".to_string()))?
+        } else {
+            return Err(OptionsError::OptionNameNotFound("client_secret".to_string()).into());
+        };
+
+        let timeouts = Timeouts::from_options(options)?;
+        let http = reqwest::Client::builder()
+            .connect_timeout(timeouts.connect)
+            .timeout(timeouts.request)
+            .build()?;
+
+        Ok(Some(Self {
+            token_url: token_url.to_owned(),
+            client_id: client_id.to_owned(),
+            client_secret,
+            scope: options.get("scope").cloned(),
+            audience: options.get("audience").cloned(),
+            http,
+            rt: create_async_runtime()?,
+        }))
+    }
+
+    fn cache_key(&self) -> String {
+        format!(
+            "{}/{}/{:?}/{:?}",
+            self.token_url, self.client_id, self.scope, self.audience
+        )
+    }
+
+    /// Get the access token, a new token is requested if the cached one is
+    /// expiring or there is no cached one
+    pub fn access_token(&self) -> Result<String, OAuth2Error> {
+        let key = self.cache_key();
+        let now = Instant::now();
+        let cached = TOKENS.with_borrow(|tokens| {
+            tokens
+                .get(&key)
+                .filter(|token| token.expires_at > now + REFRESH_MARGIN)
+                .map(|token| token.access_token.clone())
+        });
+        if let Some(access_token) = cached {
+            return Ok(access_token);
+        }
+
+        let token = self.request_token()?;
+        let access_token = token.access_token.clone();
+        TOKENS.with_borrow_mut(|tokens| tokens.insert(key, token));
+        Ok(access_token)
+    }
+
+    /// Remove the cached access token, so a new one is requested next time
+    ///
+    /// It can be called when a request is rejected with HTTP 401, in case the
+    /// token is revoked before it expires.
+    pub fn invalidate(&self) {
+        let key = self.cache_key();
+        TOKENS.with_borrow_mut(|tokens| tokens.remove(&key));
+    }
+
+    fn request_token(&self) -> Result<CachedToken, OAuth2Error> {
+        let mut form = vec![
+            ("grant_type", "client_credentials"),
+            ("client_id", &self.client_id),
+            ("client_secret", &self.client_secret),
+        ];
+        if let Some(scope) = &self.scope {
+            form.push(("scope", scope));
+        }
+        if let Some(audience) = &self.audience {
+            form.push(("audience", audience));
+        }
+
+        let requested_at = Instant::now();
+        let (status, body) = self.rt.block_on(async {
+            let resp = self.http.post(&self.token_url).form(&form).send().await?;
+            let status = resp.status();
+            Ok::<_, reqwest::Error>((status, resp.text().await?))
+        })?;
+        if !status.is_success() {
+            return Err(OAuth2Error::TokenRejected(status.as_u16(), body));
+        }
+
+        let resp: serde_json::Value = serde_json::from_str(&body)
+            .map_err(|_| OAuth2Error::InvalidResponse("response is not JSON".to_string()))?;
+        let access_token = resp
+            .get("access_token")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| OAuth2Error::InvalidResponse("access_token is not found".to_string()))?;
+        let expires_in = resp
+            .get("expires_in")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(DEFAULT_EXPIRES_IN);
+        register_secret(access_token);
+
+        Ok(CachedToken {
+            access_token: access_token.to_owned(),
+            expires_at: requested_at + Duration::from_secs(expires_in),
+        })
+    }
+}
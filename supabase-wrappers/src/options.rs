@@ -1,7 +1,17 @@
+use crate::guc;
+use crate::instance::ForeignServer;
+use crate::interface::{
+    ATTRIBUTE_RELATION_ID, FOREIGN_SERVER_RELATION_ID, FOREIGN_TABLE_RELATION_ID,
+    USER_MAPPING_RELATION_ID,
+};
+use crate::secret::{get_secret, get_secret_by_name};
+use crate::utils::report_warning;
 use pgrx::pg_sys::panic::ErrorReport;
 use pgrx::{pg_sys, PgList, PgSqlErrorCode};
 use std::collections::HashMap;
 use std::ffi::CStr;
+use std::str::FromStr;
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -12,6 +22,8 @@ pub enum OptionsError {
     OptionNameIsInvalidUtf8(String),
     #[error("option value `{0}` is not a valid UTF-8 string")]
     OptionValueIsInvalidUtf8(String),
+    #[error("option `{0}` has invalid value `{1}`")]
+    OptionValueIsInvalid(String, String),
 }
 
 impl From<OptionsError> for ErrorReport {
@@ -29,6 +41,11 @@ impl From<OptionsError> for ErrorReport {
                 error_message,
                 "",
             ),
+            OptionsError::OptionValueIsInvalid(_, _) => ErrorReport::new(
+                PgSqlErrorCode::ERRCODE_FDW_INVALID_ATTRIBUTE_VALUE,
+                error_message,
+                "",
+            ),
         }
     }
 }
@@ -94,6 +111,425 @@ pub fn check_options_contain(opt_list: &[Option<String>], tgt: &str) -> Result<(
     }
 }
 
+/// Typed options of a foreign server or table
+///
+/// It is usually derived by [`FdwOptions`](crate::FdwOptions) macro, which
+/// parses each field from the option of the same name, for example,
+///
+/// ```rust,no_run
+/// # use supabase_wrappers::prelude::*;
+/// # use supabase_wrappers::options::OptionsError;
+/// # fn main() -> Result<(), OptionsError> {
+/// #[derive(FdwOptions)]
+/// struct ServerOptions {
+///     // required option
+///     api_url: String,
+///     // option `api_key`, or the secret referenced by option `api_key_id`
+///     // or `api_key_name`
+///     #[option(secret)]
+///     api_key: String,
+///     // option with default value
+///     #[option(default = "100")]
+///     page_size: u64,
+///     // optional option with a different name
+///     #[option(name = "api_version")]
+///     version: Option<String>,
+/// }
+///
+/// # let options = &std::collections::HashMap::new();
+/// let opts = ServerOptions::from_options(options)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// The field can be any type implementing [`FromStr`], like `String`, `u64`,
+/// `bool` and `Url`.
+pub trait FdwOptions: Sized {
+    /// Parse the options
+    fn from_options(options: &HashMap<String, String>) -> Result<Self, OptionsError>;
+
+    /// Check the option list, used in [validator](crate::interface::ForeignDataWrapper::validator)
+    ///
+    /// The secret options are not required here, as they can be in the user
+    /// mapping options.
+    fn validate(opt_list: &[Option<String>]) -> Result<(), OptionsError>;
+
+    /// Parse the foreign server options, the options in current user's user
+    /// mapping take precedence
+    fn from_server(server: &ForeignServer) -> Result<Self, OptionsError> {
+        let mut options = server.options.clone();
+        options.extend(server.user_mapping_options.clone());
+        Self::from_options(&options)
+    }
+}
+
+/// Convert the option list passed to validator to the `options` map
+pub fn options_from_list(opt_list: &[Option<String>]) -> HashMap<String, String> {
+    opt_list
+        .iter()
+        .flatten()
+        .filter_map(|opt| opt.split_once('='))
+        .map(|(name, value)| (name.to_owned(), value.to_owned()))
+        .collect()
+}
+
+/// Parse an option value to the type
+pub fn parse_option_value<T: FromStr>(opt_name: &str, value: &str) -> Result<T, OptionsError> {
+    value
+        .trim()
+        .parse::<T>()
+        .map_err(|_| OptionsError::OptionValueIsInvalid(opt_name.to_string(), value.to_owned()))
+}
+
+/// Get a secret option value from the `options` map
+///
+/// The value is from the option itself, or the secret referenced by option
+/// `<opt_name>_id` or `<opt_name>_name`, which is read from the provider set
+/// by the option `secret_provider`.
+pub fn secret_option(
+    opt_name: &str,
+    options: &HashMap<String, String>,
+) -> Result<Option<String>, OptionsError> {
+    if let Some(value) = options.get(opt_name) {
+        return Ok(Some(value.to_owned()));
+    }
+    for (suffix, get) in [
+        (
+            "_id",
+            get_secret as fn(&HashMap<String, String>, &str) -> Option<String>,
+        ),
+        ("_name", get_secret_by_name),
+    ] {
+        let ref_name = format!("{}{}", opt_name, suffix);
+        if let Some(secret_ref) = options.get(&ref_name) {
+            return get(options, secret_ref).map(Some).ok_or_else(|| {
+                OptionsError::OptionValueIsInvalid(ref_name, secret_ref.to_owned())
+            });
+        }
+    }
+    Ok(None)
+}
+
+/// Level of an option in the options schema
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptionLevel {
+    Server,
+    UserMapping,
+    Table,
+    Column,
+}
+
+impl OptionLevel {
+    // get the level of the options in validator by its catalog
+    fn from_catalog(catalog: pg_sys::Oid) -> Option<Self> {
+        match catalog {
+            FOREIGN_SERVER_RELATION_ID => Some(Self::Server),
+            USER_MAPPING_RELATION_ID => Some(Self::UserMapping),
+            FOREIGN_TABLE_RELATION_ID => Some(Self::Table),
+            ATTRIBUTE_RELATION_ID => Some(Self::Column),
+            _ => None,
+        }
+    }
+
+    /// Name of the level in the options schema
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Server => "server",
+            Self::UserMapping => "user_mapping",
+            Self::Table => "table",
+            Self::Column => "column",
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Server => "foreign server",
+            Self::UserMapping => "user mapping",
+            Self::Table => "foreign table",
+            Self::Column => "column",
+        }
+    }
+
+    // the options handled by the framework and the HTTP clients, which are
+    // accepted by all foreign data wrappers
+    fn standard_options(&self) -> &'static [&'static str] {
+        match self {
+            Self::Server => &[
+                "secret_provider",
+                "connect_timeout",
+                "request_timeout",
+                "retry_count",
+                "retry_backoff_ms",
+                "retry_on_429",
+                "circuit_breaker_threshold",
+                "circuit_breaker_cooldown",
+                "pool_max_size",
+                "pool_idle_timeout",
+                "requests_per_second",
+                "http_proxy",
+                "https_proxy",
+                "no_proxy",
+                "ssl_ca",
+                "ssl_ca_id",
+                "ssl_cert",
+                "ssl_cert_id",
+                "ssl_key",
+                "ssl_key_id",
+            ],
+            Self::UserMapping => &[],
+            Self::Table => &[
+                "cache_ttl",
+                "max_rows",
+                "max_result_bytes",
+                "on_limit_exceeded",
+                "startup_cost",
+                "rescan_buffer",
+                "spill_threshold_kb",
+                "batch_size",
+                "upsert",
+                "read_your_writes",
+                "rowid_column",
+            ],
+            Self::Column => &["path", "coerce", "mask", "unmask_roles"],
+        }
+    }
+}
+
+/// An option in the options schema, declared by the `options` attribute of
+/// [`wrappers_fdw`](crate::wrappers_fdw) macro
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OptionSpec {
+    pub name: &'static str,
+    pub level: OptionLevel,
+    pub required: bool,
+    /// The option can also be the secret referenced by `<name>_id` or
+    /// `<name>_name`, and set in the user mapping
+    pub secret: bool,
+    /// An example value, for documentation
+    pub example: Option<&'static str>,
+}
+
+impl OptionSpec {
+    fn accepts(&self, name: &str) -> bool {
+        name == self.name
+            || (self.secret
+                && name
+                    .strip_prefix(self.name)
+                    .is_some_and(|suffix| suffix == "_id" || suffix == "_name"))
+    }
+}
+
+// edit distance between two option names, used to suggest the option for a
+// misspelled one
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut curr = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != *cb);
+            curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        prev = curr;
+    }
+    prev[b.len()]
+}
+
+/// Check the option list by the options schema, used in the validator
+/// generated by [`wrappers_fdw`](crate::wrappers_fdw) macro
+///
+/// The required options must be specified, except the secret options which
+/// can be in either the foreign server or the user mapping. A warning is
+/// reported for each unknown option, with the most similar option if any.
+#[allow(clippy::result_large_err)]
+pub fn validate_options_schema(
+    opt_list: &[Option<String>],
+    catalog: Option<pg_sys::Oid>,
+    schema: &[OptionSpec],
+) -> Result<(), ErrorReport> {
+    let Some(level) = catalog.and_then(OptionLevel::from_catalog) else {
+        return Ok(());
+    };
+    // the secret options can be set in the user mapping
+    let specs: Vec<&OptionSpec> = schema
+        .iter()
+        .filter(|spec| {
+            spec.level == level
+                || (level == OptionLevel::UserMapping
+                    && spec.level == OptionLevel::Server
+                    && spec.secret)
+        })
+        .collect();
+    let mut known: Vec<String> = Vec::new();
+    for spec in &specs {
+        known.push(spec.name.to_string());
+        if spec.secret {
+            known.push(format!("{}_id", spec.name));
+            known.push(format!("{}_name", spec.name));
+        }
+    }
+    known.extend(level.standard_options().iter().map(|name| name.to_string()));
+
+    let options = options_from_list(opt_list);
+    for spec in specs.iter().filter(|spec| spec.required && !spec.secret) {
+        if !options.contains_key(spec.name) {
+            return Err(ErrorReport::new(
+                PgSqlErrorCode::ERRCODE_FDW_OPTION_NAME_NOT_FOUND,
+                format!(
+                    "required option `{}` is not specified for {}",
+                    spec.name,
+                    level.as_str()
+                ),
+                "",
+            )
+            .set_hint(format!(
+                "valid options for {} are: {}",
+                level.as_str(),
+                known.join(", ")
+            )));
+        }
+    }
+
+    let mut names: Vec<&String> = options.keys().collect();
+    names.sort();
+    for name in names {
+        if specs.iter().any(|spec| spec.accepts(name))
+            || level.standard_options().contains(&name.as_str())
+        {
+            continue;
+        }
+        let mut msg = format!("unknown option `{}` for {}", name, level.as_str());
+        if let Some(similar) = known
+            .iter()
+            .map(|k| (edit_distance(name, k), k))
+            .filter(|(dist, _)| *dist <= 2)
+            .min_by_key(|(dist, _)| *dist)
+            .map(|(_, k)| k)
+        {
+            msg.push_str(&format!(", did you mean `{}`?", similar));
+        }
+        report_warning(&msg);
+    }
+
+    Ok(())
+}
+
+/// Timeouts of the remote calls
+///
+/// They are set by the standard foreign server options below, in seconds:
+///
+/// - `connect_timeout`: timeout to connect to the remote server, default is 30 seconds
+/// - `request_timeout`: timeout of each request to the remote server, default is
+///   `wrappers.http_timeout` which is 300 seconds by default
+///
+/// For example,
+///
+/// ```rust,no_run
+/// # use supabase_wrappers::prelude::Timeouts;
+/// # use std::collections::HashMap;
+/// # use supabase_wrappers::options::OptionsError;
+/// # fn main() -> Result<(), OptionsError> {
+/// # let options = &HashMap::new();
+/// let timeouts = Timeouts::from_options(options)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeouts {
+    pub connect: Duration,
+    pub request: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            connect: Duration::from_secs(30),
+            request: guc::http_timeout(),
+        }
+    }
+}
+
+impl Timeouts {
+    /// Get timeouts from the foreign server options, use default if not specified
+    pub fn from_options(options: &HashMap<String, String>) -> Result<Self, OptionsError> {
+        let default = Self::default();
+        Ok(Self {
+            connect: parse_timeout("connect_timeout", options)?.unwrap_or(default.connect),
+            request: parse_timeout("request_timeout", options)?.unwrap_or(default.request),
+        })
+    }
+}
+
+/// Retry policy of the remote requests
+///
+/// It is set by the standard foreign server options below:
+///
+/// - `retry_count`: max number of retries of a failed request, default is
+///   `wrappers.max_retries` which is 3 by default
+/// - `retry_backoff_ms`: initial backoff in milliseconds before retrying, which
+///   grows exponentially on each retry, default is 1000
+/// - `retry_on_429`: if retry the request rejected by rate limit (HTTP 429),
+///   default is `true`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryOptions {
+    pub count: u32,
+    pub backoff: Duration,
+    pub on_429: bool,
+}
+
+impl Default for RetryOptions {
+    fn default() -> Self {
+        Self {
+            count: guc::max_retries(),
+            backoff: Duration::from_millis(1000),
+            on_429: true,
+        }
+    }
+}
+
+impl RetryOptions {
+    /// Get retry policy from the foreign server options, use default if not specified
+    pub fn from_options(options: &HashMap<String, String>) -> Result<Self, OptionsError> {
+        let default = Self::default();
+        Ok(Self {
+            count: parse_option("retry_count", options, |_| true)?.unwrap_or(default.count),
+            backoff: parse_option("retry_backoff_ms", options, |ms| *ms > 0)?
+                .map(Duration::from_millis)
+                .unwrap_or(default.backoff),
+            on_429: parse_option("retry_on_429", options, |_| true)?.unwrap_or(default.on_429),
+        })
+    }
+}
+
+// parse a timeout option in seconds, it must be a positive integer
+fn parse_timeout(
+    opt_name: &str,
+    options: &HashMap<String, String>,
+) -> Result<Option<Duration>, OptionsError> {
+    Ok(parse_option(opt_name, options, |secs| *secs > 0)?.map(Duration::from_secs))
+}
+
+// parse an option value and check if it is valid
+pub(crate) fn parse_option<T: FromStr>(
+    opt_name: &str,
+    options: &HashMap<String, String>,
+    is_valid: impl Fn(&T) -> bool,
+) -> Result<Option<T>, OptionsError> {
+    options
+        .get(opt_name)
+        .map(|value| {
+            value
+                .trim()
+                .parse::<T>()
+                .ok()
+                .filter(&is_valid)
+                .ok_or_else(|| {
+                    OptionsError::OptionValueIsInvalid(opt_name.to_string(), value.to_owned())
+                })
+        })
+        .transpose()
+}
+
 // convert options definition to hashmap
 pub(super) unsafe fn options_to_hashmap(
     options: *mut pg_sys::List,
@@ -0,0 +1,339 @@
+//! Pagination of REST API results
+//!
+//! REST APIs return a large result in pages, and the URL of the next page is
+//! decided by the previous page in different ways. A [`Paginator`] builds the
+//! URL of each page, and [`Pages`] drives the page requests and feeds the rows
+//! into a [`ScanResultBuffer`]. The paginators below are provided:
+//!
+//! - [`CursorPaginator`]: the next page starts after a field, like `id`, of
+//!   the last row
+//! - [`PageTokenPaginator`]: the next page token is returned in the response body
+//! - [`LinkHeaderPaginator`]: the next page URL is in the `Link` response header
+//! - [`OffsetPaginator`]: the pages are requested by offset and limit
+//!
+//! For example, to fetch the pages lazily in `iter_scan()`,
+//!
+//! ```rust,no_run
+//! # use supabase_wrappers::prelude::*;
+//! # use supabase_wrappers::pagination::*;
+//! # fn fetch(url: url::Url) -> Result<Page, FdwError> { unimplemented!() }
+//! # fn iter_scan(pages: &mut Pages<PageTokenPaginator>, buffer: &mut ScanResultBuffer, row: &mut Row) -> Result<Option<()>, FdwError> {
+//! loop {
+//!     if let Some(src_row) = buffer.next_row() {
+//!         row.replace_with(src_row);
+//!         return Ok(Some(()));
+//!     }
+//!     // request the next page if all rows in the buffer are returned
+//!     if !pages.fill_next(buffer, fetch, |record| {
+//!         // convert the record to row
+//!         Ok(Row::new())
+//!     })? {
+//!         return Ok(None);
+//!     }
+//! }
+//! # }
+//! ```
+
+use crate::interface::Row;
+use crate::scan_buffer::ScanResultBuffer;
+use serde_json::Value as JsonValue;
+use url::Url;
+
+/// A fetched page
+#[derive(Debug, Clone, Default)]
+pub struct Page {
+    /// Response body
+    pub body: JsonValue,
+
+    /// Value of the `Link` response header, if any
+    pub link: Option<String>,
+}
+
+/// Builder of the page URLs
+pub trait Paginator {
+    /// URL of the next page, or `None` if all pages are fetched
+    fn next_url(&self, base_url: &Url) -> Option<Url>;
+
+    /// Update the pagination state by the fetched page and its rows
+    fn update(&mut self, page: &Page, rows: &[JsonValue]);
+
+    /// Restart from the first page
+    fn reset(&mut self);
+}
+
+// get a string or number field by JSON pointer
+fn field_str(value: &JsonValue, pointer: &str) -> Option<String> {
+    match value.pointer(pointer)? {
+        JsonValue::String(s) if !s.is_empty() => Some(s.to_owned()),
+        JsonValue::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+fn with_param(base_url: &Url, name: &str, value: &str) -> Url {
+    let mut url = base_url.clone();
+    url.query_pairs_mut().append_pair(name, value);
+    url
+}
+
+/// Pagination by a field of the last row, like Stripe's `starting_after`
+///
+/// All pages are fetched if the page has no rows, or the `has_more` field in
+/// the response body is `false` if it is set.
+pub struct CursorPaginator {
+    param: String,
+    field: String,
+    has_more: Option<String>,
+    cursor: Option<String>,
+    done: bool,
+}
+
+impl CursorPaginator {
+    /// Create a paginator which sets query parameter `param` to the `field`
+    /// of the last row
+    pub fn new(param: &str, field: &str) -> Self {
+        Self {
+            param: param.to_owned(),
+            field: field.to_owned(),
+            has_more: None,
+            cursor: None,
+            done: false,
+        }
+    }
+
+    /// Check if there are more pages by a boolean field in the response body,
+    /// the field is a JSON pointer like `/has_more`
+    pub fn with_has_more(mut self, has_more: &str) -> Self {
+        self.has_more = Some(has_more.to_owned());
+        self
+    }
+}
+
+impl Paginator for CursorPaginator {
+    fn next_url(&self, base_url: &Url) -> Option<Url> {
+        if self.done {
+            return None;
+        }
+        Some(match &self.cursor {
+            Some(cursor) => with_param(base_url, &self.param, cursor),
+            None => base_url.clone(),
+        })
+    }
+
+    fn update(&mut self, page: &Page, rows: &[JsonValue]) {
+        let has_more = self
+            .has_more
+            .as_ref()
+            .and_then(|ptr| page.body.pointer(ptr))
+            .and_then(|v| v.as_bool())
+            .unwrap_or(true);
+        self.cursor = rows
+            .last()
+            .and_then(|row| field_str(row, &format!("/{}", self.field)));
+        self.done = !has_more || self.cursor.is_none();
+    }
+
+    fn reset(&mut self) {
+        self.cursor = None;
+        self.done = false;
+    }
+}
+
+/// Pagination by a token of the next page in the response body, like
+/// Airtable's `offset` and Google APIs' `nextPageToken`
+pub struct PageTokenPaginator {
+    param: String,
+    token: String,
+    next_token: Option<String>,
+    done: bool,
+}
+
+impl PageTokenPaginator {
+    /// Create a paginator which sets query parameter `param` to the `token`
+    /// field in the response body, the field is a JSON pointer like `/offset`
+    pub fn new(param: &str, token: &str) -> Self {
+        Self {
+            param: param.to_owned(),
+            token: token.to_owned(),
+            next_token: None,
+            done: false,
+        }
+    }
+}
+
+impl Paginator for PageTokenPaginator {
+    fn next_url(&self, base_url: &Url) -> Option<Url> {
+        if self.done {
+            return None;
+        }
+        Some(match &self.next_token {
+            Some(token) => with_param(base_url, &self.param, token),
+            None => base_url.clone(),
+        })
+    }
+
+    fn update(&mut self, page: &Page, _rows: &[JsonValue]) {
+        self.next_token = field_str(&page.body, &self.token);
+        self.done = self.next_token.is_none();
+    }
+
+    fn reset(&mut self) {
+        self.next_token = None;
+        self.done = false;
+    }
+}
+
+/// Pagination by the `rel="next"` URL in the `Link` response header, like
+/// GitHub API
+#[derive(Default)]
+pub struct LinkHeaderPaginator {
+    next: Option<Url>,
+    done: bool,
+}
+
+impl LinkHeaderPaginator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // parse the next page URL from header like
+    // `<https://api.example.com/items?page=2>; rel="next", <...>; rel="last"`
+    fn parse_next(link: &str) -> Option<Url> {
+        link.split(',').find_map(|part| {
+            let (url, params) = part.trim().split_once(';')?;
+            let is_next = params.split(';').any(|param| {
+                let param = param.trim().replace(' ', "");
+                param == "rel=\"next\"" || param == "rel=next"
+            });
+            if !is_next {
+                return None;
+            }
+            let url = url.trim().strip_prefix('<')?.strip_suffix('>')?;
+            Url::parse(url).ok()
+        })
+    }
+}
+
+impl Paginator for LinkHeaderPaginator {
+    fn next_url(&self, base_url: &Url) -> Option<Url> {
+        if self.done {
+            return None;
+        }
+        Some(self.next.clone().unwrap_or_else(|| base_url.clone()))
+    }
+
+    fn update(&mut self, page: &Page, _rows: &[JsonValue]) {
+        self.next = page.link.as_deref().and_then(Self::parse_next);
+        self.done = self.next.is_none();
+    }
+
+    fn reset(&mut self) {
+        self.next = None;
+        self.done = false;
+    }
+}
+
+/// Pagination by offset and limit query parameters
+///
+/// All pages are fetched if a page has fewer rows than the limit.
+pub struct OffsetPaginator {
+    offset_param: String,
+    limit_param: String,
+    limit: usize,
+    offset: usize,
+    done: bool,
+}
+
+impl OffsetPaginator {
+    /// Create a paginator which sets query parameters `offset_param` and
+    /// `limit_param`, each page has at most `limit` rows
+    pub fn new(offset_param: &str, limit_param: &str, limit: usize) -> Self {
+        Self {
+            offset_param: offset_param.to_owned(),
+            limit_param: limit_param.to_owned(),
+            limit,
+            offset: 0,
+            done: false,
+        }
+    }
+}
+
+impl Paginator for OffsetPaginator {
+    fn next_url(&self, base_url: &Url) -> Option<Url> {
+        if self.done {
+            return None;
+        }
+        let url = with_param(base_url, &self.offset_param, &self.offset.to_string());
+        Some(with_param(&url, &self.limit_param, &self.limit.to_string()))
+    }
+
+    fn update(&mut self, _page: &Page, rows: &[JsonValue]) {
+        self.offset += rows.len();
+        self.done = rows.len() < self.limit;
+    }
+
+    fn reset(&mut self) {
+        self.offset = 0;
+        self.done = false;
+    }
+}
+
+/// Driver of the page requests
+pub struct Pages<P: Paginator> {
+    paginator: P,
+    base_url: Url,
+    rows: String,
+}
+
+impl<P: Paginator> Pages<P> {
+    /// Create a driver of the pages of a base URL, the rows of each page are
+    /// in the response body field `rows`, which is a JSON pointer like `/data`,
+    /// or empty if the response body is the rows array
+    pub fn new(paginator: P, base_url: Url, rows: &str) -> Self {
+        Self {
+            paginator,
+            base_url,
+            rows: rows.to_owned(),
+        }
+    }
+
+    /// Fetch the next page by `fetch`, return its rows, or `None` if all pages
+    /// are fetched
+    pub fn fetch_next<E>(
+        &mut self,
+        fetch: impl FnOnce(Url) -> Result<Page, E>,
+    ) -> Result<Option<Vec<JsonValue>>, E> {
+        let Some(url) = self.paginator.next_url(&self.base_url) else {
+            return Ok(None);
+        };
+        let page = fetch(url)?;
+        let rows = match page.body.pointer(&self.rows) {
+            Some(JsonValue::Array(rows)) => rows.clone(),
+            _ => Vec::new(),
+        };
+        self.paginator.update(&page, &rows);
+        Ok(Some(rows))
+    }
+
+    /// Fetch the next page and push its rows converted by `to_row` into the
+    /// buffer, return `false` if all pages are fetched
+    pub fn fill_next<E>(
+        &mut self,
+        buffer: &mut ScanResultBuffer,
+        fetch: impl FnOnce(Url) -> Result<Page, E>,
+        mut to_row: impl FnMut(&JsonValue) -> Result<Row, E>,
+    ) -> Result<bool, E> {
+        let Some(rows) = self.fetch_next(fetch)? else {
+            return Ok(false);
+        };
+        for row in &rows {
+            buffer.push(to_row(row)?);
+        }
+        Ok(true)
+    }
+
+    /// Restart from the first page
+    pub fn reset(&mut self) {
+        self.paginator.reset();
+    }
+}
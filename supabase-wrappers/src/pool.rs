@@ -0,0 +1,225 @@
+//! Connection pool of the database-backed FDWs
+//!
+//! Opening a connection to a remote database, especially with TLS, can take
+//! longer than the query itself. [`ConnectionPool`] keeps the idle connections
+//! in the backend, so the following queries on the same foreign server in the
+//! session reuse them instead of connecting again. The pool is set up by the
+//! foreign server options below:
+//!
+//! - `pool_max_size`: max number of idle connections kept for the foreign
+//!   server, default is 4, `0` disables the pool
+//! - `pool_idle_timeout`: number of seconds an idle connection is kept for,
+//!   default is 300
+//!
+//! The idle connections are keyed by the foreign server and a hash of its
+//! options and user mapping options, so a connection is never reused after the
+//! server is altered or by a user with different credentials. For example,
+//!
+//! ```rust,no_run
+//! # use supabase_wrappers::prelude::*;
+//! # struct Conn;
+//! # impl Conn { fn ping(&mut self) -> bool { true } }
+//! # fn connect() -> Result<Conn, FdwError> { Ok(Conn) }
+//! # fn new(server: ForeignServer) -> Result<(), FdwError> {
+//! let pool = ConnectionPool::from_server(&server)?;
+//!
+//! // reuse an idle connection if it is still alive, or open a new one
+//! let mut conn = pool.get(connect, |conn| conn.ping())?;
+//!
+//! // the connection is returned to the pool when it is dropped
+//! drop(conn);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The idle connections can be closed by [`close_connections`].
+
+use crate::instance::ForeignServer;
+use crate::options::{parse_option, OptionsError};
+use pgrx::prelude::*;
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+use std::ops::{Deref, DerefMut};
+use std::time::{Duration, Instant};
+
+const DEFAULT_MAX_SIZE: usize = 4;
+const DEFAULT_IDLE_TIMEOUT: u64 = 300;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct PoolKey {
+    server_oid: pg_sys::Oid,
+    options_hash: u64,
+}
+
+struct IdleConnection {
+    conn: Box<dyn Any>,
+    idle_since: Instant,
+}
+
+thread_local! {
+    static POOLS: RefCell<HashMap<PoolKey, Vec<IdleConnection>>> = RefCell::new(HashMap::new());
+}
+
+// remove the connections which are idle for longer than the timeout
+fn remove_expired(
+    pools: &mut HashMap<PoolKey, Vec<IdleConnection>>,
+    key: &PoolKey,
+    idle_timeout: Duration,
+) {
+    if let Some(conns) = pools.get_mut(key) {
+        conns.retain(|idle| idle.idle_since.elapsed() < idle_timeout);
+        if conns.is_empty() {
+            pools.remove(key);
+        }
+    }
+}
+
+/// Pool of the connections to a foreign server in current backend
+#[derive(Debug, Clone)]
+pub struct ConnectionPool {
+    key: PoolKey,
+    max_size: usize,
+    idle_timeout: Duration,
+}
+
+impl ConnectionPool {
+    /// Create the pool by the foreign server options `pool_max_size` and
+    /// `pool_idle_timeout`
+    pub fn from_server(server: &ForeignServer) -> Result<Self, OptionsError> {
+        let max_size = parse_option("pool_max_size", &server.options, |_: &usize| true)?
+            .unwrap_or(DEFAULT_MAX_SIZE);
+        let idle_timeout =
+            parse_option("pool_idle_timeout", &server.options, |secs: &u64| *secs > 0)?
+                .unwrap_or(DEFAULT_IDLE_TIMEOUT);
+
+        // options are hashed in name order, so the hash doesn't depend on the
+        // iteration order of the hash maps
+        let mut hasher = DefaultHasher::new();
+        server
+            .options
+            .iter()
+            .collect::<BTreeMap<_, _>>()
+            .hash(&mut hasher);
+        server
+            .user_mapping_options
+            .iter()
+            .collect::<BTreeMap<_, _>>()
+            .hash(&mut hasher);
+
+        Ok(Self {
+            key: PoolKey {
+                server_oid: server.server_oid,
+                options_hash: hasher.finish(),
+            },
+            max_size,
+            idle_timeout: Duration::from_secs(idle_timeout),
+        })
+    }
+
+    /// Get the foreign server oid of the pool
+    pub fn server_oid(&self) -> pg_sys::Oid {
+        self.key.server_oid
+    }
+
+    /// Get an idle connection which passes the `is_valid` check, or open a new
+    /// one by `connect` if there is no such connection
+    ///
+    /// The idle connections failing the check are closed.
+    pub fn get<C: 'static, E>(
+        &self,
+        connect: impl FnOnce() -> Result<C, E>,
+        mut is_valid: impl FnMut(&mut C) -> bool,
+    ) -> Result<PooledConnection<C>, E> {
+        while let Some(mut conn) = self.take_idle::<C>() {
+            if is_valid(&mut conn) {
+                return Ok(PooledConnection {
+                    conn: Some(conn),
+                    pool: self.clone(),
+                });
+            }
+        }
+        Ok(PooledConnection {
+            conn: Some(connect()?),
+            pool: self.clone(),
+        })
+    }
+
+    // take the most recently used idle connection
+    fn take_idle<C: 'static>(&self) -> Option<C> {
+        POOLS.with_borrow_mut(|pools| {
+            remove_expired(pools, &self.key, self.idle_timeout);
+            let conns = pools.get_mut(&self.key)?;
+            let idle = conns.pop()?;
+            if conns.is_empty() {
+                pools.remove(&self.key);
+            }
+            idle.conn.downcast::<C>().ok().map(|conn| *conn)
+        })
+    }
+
+    // put the connection back to the pool, it is closed if the pool is full
+    fn put_idle<C: 'static>(&self, conn: C) {
+        if self.max_size == 0 {
+            return;
+        }
+        // the pool may be already destroyed if the backend is exiting
+        let _ = POOLS.try_with(|pools| {
+            let mut pools = pools.borrow_mut();
+            remove_expired(&mut pools, &self.key, self.idle_timeout);
+            let conns = pools.entry(self.key.clone()).or_default();
+            if conns.len() < self.max_size {
+                conns.push(IdleConnection {
+                    conn: Box::new(conn),
+                    idle_since: Instant::now(),
+                });
+            }
+        });
+    }
+}
+
+/// A connection borrowed from [`ConnectionPool`]
+///
+/// It is returned to the pool when dropped, call [`discard`](Self::discard)
+/// instead if the connection is broken.
+pub struct PooledConnection<C: 'static> {
+    conn: Option<C>,
+    pool: ConnectionPool,
+}
+
+impl<C: 'static> PooledConnection<C> {
+    /// Close the connection instead of returning it to the pool
+    pub fn discard(mut self) {
+        self.conn.take();
+    }
+}
+
+impl<C: 'static> Deref for PooledConnection<C> {
+    type Target = C;
+
+    fn deref(&self) -> &Self::Target {
+        self.conn.as_ref().expect("connection is discarded")
+    }
+}
+
+impl<C: 'static> DerefMut for PooledConnection<C> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.conn.as_mut().expect("connection is discarded")
+    }
+}
+
+impl<C: 'static> Drop for PooledConnection<C> {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            self.pool.put_idle(conn);
+        }
+    }
+}
+
+/// Close all the idle connections in current backend, return the number of
+/// closed connections
+pub fn close_connections() -> usize {
+    POOLS.with_borrow_mut(|pools| pools.drain().map(|(_, conns)| conns.len()).sum())
+}
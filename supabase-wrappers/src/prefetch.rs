@@ -0,0 +1,97 @@
+//! Prefetch of the next page in background
+//!
+//! Paginated FDWs usually request the next page only after all rows of the
+//! current page are returned, so the network latency adds up with the executor
+//! time. [`Prefetch`] runs the next page request on the shared async runtime
+//! while the rows of current page are being returned, and the FDW waits for
+//! its result when the current page is consumed. For example,
+//!
+//! ```rust,no_run
+//! # use supabase_wrappers::prelude::*;
+//! # async fn fetch_page(page: usize) -> Vec<Row> { Vec::new() }
+//! # fn scan() -> Result<(), CreateRuntimeError> {
+//! let rt = create_async_runtime()?;
+//! let mut prefetch = Prefetch::new(&rt);
+//!
+//! // in begin_scan(), start requesting the first page
+//! prefetch.spawn(fetch_page(0));
+//!
+//! // in iter_scan(), when the current page is consumed
+//! if let Some(rows) = prefetch.wait() {
+//!     // start requesting the next page before returning rows of this page
+//!     prefetch.spawn(fetch_page(1));
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The future is run on the runtime's worker threads, so it must not call any
+//! Postgres functions. Things like rate limit and stats should be done before
+//! spawning the future or after waiting for its result.
+
+use crate::utils::Runtime;
+use std::future::Future;
+use tokio::task::JoinHandle;
+
+/// A future running in background, whose result is taken later
+pub struct Prefetch<T> {
+    rt: Runtime,
+    task: Option<JoinHandle<T>>,
+}
+
+impl<T: Send + 'static> Prefetch<T> {
+    /// Create an empty prefetch on the runtime
+    pub fn new(rt: &Runtime) -> Self {
+        Self {
+            rt: rt.clone(),
+            task: None,
+        }
+    }
+
+    /// Start running a future in background, the previous one is cancelled
+    /// if it is not taken yet
+    pub fn spawn<F>(&mut self, future: F)
+    where
+        F: Future<Output = T> + Send + 'static,
+    {
+        self.cancel();
+        self.task = Some(self.rt.spawn(future));
+    }
+
+    /// Check if there is a future spawned and not taken yet
+    pub fn is_pending(&self) -> bool {
+        self.task.is_some()
+    }
+
+    /// Wait for the spawned future to complete and take its result, `None` is
+    /// returned if no future is spawned
+    ///
+    /// The query can be cancelled while waiting.
+    pub fn wait(&mut self) -> Option<T> {
+        // the task is kept until it completes, so it is aborted on drop if
+        // the query is cancelled while waiting
+        let task = self.task.as_mut()?;
+        let result = self.rt.block_on(task);
+        self.task = None;
+        match result {
+            Ok(output) => Some(output),
+            Err(err) if err.is_panic() => std::panic::resume_unwind(err.into_panic()),
+            Err(_) => None,
+        }
+    }
+
+    /// Cancel the spawned future
+    pub fn cancel(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+    }
+}
+
+impl<T> Drop for Prefetch<T> {
+    fn drop(&mut self) {
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+    }
+}
@@ -1,17 +1,39 @@
 use crate::prelude::*;
 use pgrx::pg_sys::Oid;
 use pgrx::{
-    datum::{Array, Date, JsonB, Timestamp},
+    datum::{Array, Date, JsonB, Timestamp, TimestampWithTimeZone},
     is_a,
     list::PgList,
     pg_sys,
     pg_sys::Datum,
-    FromDatum, PgBuiltInOids, PgOid,
+    AnyNumeric, FromDatum, PgBuiltInOids, PgOid,
 };
 use std::ffi::CStr;
 use std::os::raw::c_int;
 
-use crate::interface::Param;
+use crate::interface::{Param, ParamKind};
+
+// qual extraction capabilities declared by the FDW
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct QualCaps {
+    // extract nested AND and NOT expressions
+    pub bool_expr_tree: bool,
+
+    // extract conditions on json attributes, e.g. "attrs->>'key' = 'value'"
+    pub json_path: bool,
+}
+
+// get collation name used by an operator, the default collation is ignored
+unsafe fn get_collate(coll_id: pg_sys::Oid) -> Option<String> {
+    if coll_id == pg_sys::InvalidOid || coll_id == pg_sys::DEFAULT_COLLATION_OID {
+        return None;
+    }
+    let name = pg_sys::get_collation_name(coll_id);
+    if name.is_null() {
+        return None;
+    }
+    Some(CStr::from_ptr(name).to_str().ok()?.to_owned())
+}
 
 // create array of Cell from constant datum array
 pub(crate) unsafe fn form_array_from_datum(
@@ -103,6 +125,37 @@ pub(crate) unsafe fn form_array_from_datum(
                 },
             )
         }
+        PgOid::BuiltIn(PgBuiltInOids::VARCHARARRAYOID) => {
+            Array::<String>::from_polymorphic_datum(datum, is_null, pg_sys::VARCHAROID).map(|arr| {
+                arr.iter()
+                    .filter(|v| v.is_some())
+                    .map(|v| Cell::String(v.expect("non-null array element")))
+                    .collect::<Vec<_>>()
+            })
+        }
+        PgOid::BuiltIn(PgBuiltInOids::NUMERICARRAYOID) => {
+            Array::<AnyNumeric>::from_polymorphic_datum(datum, is_null, pg_sys::NUMERICOID).map(
+                |arr| {
+                    arr.iter()
+                        .filter(|v| v.is_some())
+                        .map(|v| Cell::Numeric(v.expect("non-null array element")))
+                        .collect::<Vec<_>>()
+                },
+            )
+        }
+        PgOid::BuiltIn(PgBuiltInOids::TIMESTAMPTZARRAYOID) => {
+            Array::<TimestampWithTimeZone>::from_polymorphic_datum(
+                datum,
+                is_null,
+                pg_sys::TIMESTAMPTZOID,
+            )
+            .map(|arr| {
+                arr.iter()
+                    .filter(|v| v.is_some())
+                    .map(|v| Cell::Timestamptz(v.expect("non-null array element")))
+                    .collect::<Vec<_>>()
+            })
+        }
         PgOid::BuiltIn(PgBuiltInOids::JSONBARRAYOID) => {
             Array::<JsonB>::from_polymorphic_datum(datum, is_null, pg_sys::JSONBOID).map(|arr| {
                 arr.iter()
@@ -139,11 +192,76 @@ pub(crate) unsafe fn unnest_clause(node: *mut pg_sys::Node) -> *mut pg_sys::Node
     }
 }
 
+// extract column and json path from json attribute access expression, e.g.
+// "attrs->'a'->>'b'" or "attrs #>> '{a,b}'", returns empty path if the node
+// is a json column itself
+pub(crate) unsafe fn extract_json_path(
+    node: *mut pg_sys::Node,
+) -> Option<(*mut pg_sys::Var, Vec<String>)> {
+    let node = unnest_clause(node);
+
+    if is_a(node, pg_sys::NodeTag::T_Var) {
+        let var = node as *mut pg_sys::Var;
+        if (*var).vartype == pg_sys::JSONBOID || (*var).vartype == pg_sys::JSONOID {
+            return Some((var, Vec::new()));
+        }
+        return None;
+    }
+
+    if !is_a(node, pg_sys::NodeTag::T_OpExpr) {
+        return None;
+    }
+
+    let expr = node as *mut pg_sys::OpExpr;
+    let args: PgList<pg_sys::Node> = PgList::from_pg((*expr).args);
+    if args.len() != 2 {
+        return None;
+    }
+
+    let opr = get_operator((*expr).opno);
+    if opr.is_null() {
+        return None;
+    }
+
+    let key = unnest_clause(args.tail().unwrap());
+    if !is_a(key, pg_sys::NodeTag::T_Const) {
+        return None;
+    }
+    let key = key as *mut pg_sys::Const;
+
+    let keys = match pgrx::name_data_to_str(&(*opr).oprname) {
+        "->" | "->>" => match Cell::from_polymorphic_datum(
+            (*key).constvalue,
+            (*key).constisnull,
+            (*key).consttype,
+        )? {
+            Cell::String(key) => vec![key],
+            Cell::I32(idx) => vec![idx.to_string()],
+            _ => return None,
+        },
+        "#>" | "#>>" => {
+            form_array_from_datum((*key).constvalue, (*key).constisnull, (*key).consttype)?
+                .into_iter()
+                .map(|key| match key {
+                    Cell::String(key) => Some(key),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()?
+        }
+        _ => return None,
+    };
+
+    let (var, mut path) = extract_json_path(args.head().unwrap())?;
+    path.extend(keys);
+
+    Some((var, path))
+}
+
 pub(crate) unsafe fn extract_from_op_expr(
-    _root: *mut pg_sys::PlannerInfo,
     baserel_id: pg_sys::Oid,
     baserel_ids: pg_sys::Relids,
     expr: *mut pg_sys::OpExpr,
+    caps: QualCaps,
 ) -> Option<Qual> {
     let args: PgList<pg_sys::Node> = PgList::from_pg((*expr).args);
 
@@ -155,7 +273,7 @@ pub(crate) unsafe fn extract_from_op_expr(
 
     // get operator
     let opno = (*expr).opno;
-    let opr = get_operator(opno);
+    let mut opr = get_operator(opno);
     if opr.is_null() {
         report_warning("operator is empty");
         return None;
@@ -164,17 +282,30 @@ pub(crate) unsafe fn extract_from_op_expr(
     let mut left = unnest_clause(args.head().unwrap());
     let mut right = unnest_clause(args.tail().unwrap());
 
-    // swap operands if needed
-    if is_a(right, pg_sys::NodeTag::T_Var)
-        && !is_a(left, pg_sys::NodeTag::T_Var)
-        && (*opr).oprcom != Oid::INVALID
-    {
+    // check if the operand is a column or an attribute in json column
+    let is_column = |node: *mut pg_sys::Node| {
+        is_a(node, pg_sys::NodeTag::T_Var) || (caps.json_path && extract_json_path(node).is_some())
+    };
+
+    // swap operands if needed and use the commutator operator instead, e.g.
+    // "1 < col" => "col > 1"
+    if is_column(right) && !is_column(left) && (*opr).oprcom != Oid::INVALID {
         std::mem::swap(&mut left, &mut right);
+        opr = get_operator((*opr).oprcom);
+        if opr.is_null() {
+            return None;
+        }
     }
 
-    if is_a(left, pg_sys::NodeTag::T_Var) {
-        let left = left as *mut pg_sys::Var;
+    let column = if is_a(left, pg_sys::NodeTag::T_Var) {
+        Some((left as *mut pg_sys::Var, Vec::new()))
+    } else if caps.json_path {
+        extract_json_path(left)
+    } else {
+        None
+    };
 
+    if let Some((left, json_path)) = column {
         if pg_sys::bms_is_member((*left).varno as c_int, baserel_ids) && (*left).varattno >= 1 {
             let field = pg_sys::get_attname(baserel_id, (*left).varattno, false);
 
@@ -192,11 +323,22 @@ pub(crate) unsafe fn extract_from_op_expr(
                 // add a dummy value if this is query parameter, the actual value
                 // will be extracted from execution state
                 let right = right as *mut pg_sys::Param;
-                let param = Param {
-                    id: (*right).paramid as _,
-                    type_oid: (*right).paramtype,
+                let kind = match (*right).paramkind {
+                    pg_sys::ParamKind::PARAM_EXTERN => Some(ParamKind::Extern),
+                    pg_sys::ParamKind::PARAM_EXEC => Some(ParamKind::Exec),
+                    _ => None,
                 };
-                (Some(Cell::I64(0)), Some(param))
+                match kind {
+                    Some(kind) => {
+                        let param = Param {
+                            id: (*right).paramid as _,
+                            type_oid: (*right).paramtype,
+                            kind,
+                        };
+                        (Some(Cell::I64(0)), Some(param))
+                    }
+                    None => (None, None),
+                }
             } else {
                 (None, None)
             };
@@ -208,6 +350,10 @@ pub(crate) unsafe fn extract_from_op_expr(
                     value: Value::Cell(value),
                     use_or: false,
                     param,
+                    sub_quals: Vec::new(),
+                    json_path,
+                    type_oid: pg_sys::exprType(right),
+                    collate: get_collate((*expr).inputcollid),
                 };
                 return Some(qual);
             }
@@ -223,10 +369,19 @@ pub(crate) unsafe fn extract_from_op_expr(
 
 pub(crate) unsafe fn extract_from_null_test(
     baserel_id: pg_sys::Oid,
+    baserel_ids: pg_sys::Relids,
     expr: *mut pg_sys::NullTest,
 ) -> Option<Qual> {
-    let var = (*expr).arg as *mut pg_sys::Var;
-    if !is_a(var as _, pg_sys::NodeTag::T_Var) || (*var).varattno < 1 {
+    // row-wise null test, e.g. "row(a, b) is null", is not supported
+    if (*expr).argisrow {
+        return None;
+    }
+
+    let var = unnest_clause((*expr).arg as _) as *mut pg_sys::Var;
+    if !is_a(var as _, pg_sys::NodeTag::T_Var)
+        || !pg_sys::bms_is_member((*var).varno as c_int, baserel_ids)
+        || (*var).varattno < 1
+    {
         return None;
     }
 
@@ -244,13 +399,16 @@ pub(crate) unsafe fn extract_from_null_test(
         value: Value::Cell(Cell::String("null".to_string())),
         use_or: false,
         param: None,
+        sub_quals: Vec::new(),
+        json_path: Vec::new(),
+        type_oid: (*var).vartype,
+        collate: None,
     };
 
     Some(qual)
 }
 
 pub(crate) unsafe fn extract_from_scalar_array_op_expr(
-    _root: *mut pg_sys::PlannerInfo,
     baserel_id: pg_sys::Oid,
     baserel_ids: pg_sys::Relids,
     expr: *mut pg_sys::ScalarArrayOpExpr,
@@ -291,6 +449,47 @@ pub(crate) unsafe fn extract_from_scalar_array_op_expr(
                     value: Value::Array(value),
                     use_or: (*expr).useOr,
                     param: None,
+                    sub_quals: Vec::new(),
+                    json_path: Vec::new(),
+                    type_oid: pg_sys::get_element_type((*right).consttype),
+                    collate: get_collate((*expr).inputcollid),
+                };
+                return Some(qual);
+            }
+        }
+    }
+
+    // array parameter, e.g. "col = any($1)"
+    if is_a(left, pg_sys::NodeTag::T_Var) && is_a(right, pg_sys::NodeTag::T_Param) {
+        let left = left as *mut pg_sys::Var;
+        let right = right as *mut pg_sys::Param;
+
+        let kind = match (*right).paramkind {
+            pg_sys::ParamKind::PARAM_EXTERN => Some(ParamKind::Extern),
+            pg_sys::ParamKind::PARAM_EXEC => Some(ParamKind::Exec),
+            _ => None,
+        };
+
+        if let Some(kind) = kind {
+            if pg_sys::bms_is_member((*left).varno as c_int, baserel_ids) && (*left).varattno >= 1 {
+                let field = pg_sys::get_attname(baserel_id, (*left).varattno, false);
+
+                // add an empty array as dummy value, the actual value will be
+                // extracted from execution state
+                let qual = Qual {
+                    field: CStr::from_ptr(field).to_str().unwrap().to_string(),
+                    operator: pgrx::name_data_to_str(&(*opr).oprname).to_string(),
+                    value: Value::Array(Vec::new()),
+                    use_or: (*expr).useOr,
+                    param: Some(Param {
+                        id: (*right).paramid as _,
+                        type_oid: (*right).paramtype,
+                        kind,
+                    }),
+                    sub_quals: Vec::new(),
+                    json_path: Vec::new(),
+                    type_oid: pg_sys::get_element_type((*right).paramtype),
+                    collate: get_collate((*expr).inputcollid),
                 };
                 return Some(qual);
             }
@@ -298,14 +497,16 @@ pub(crate) unsafe fn extract_from_scalar_array_op_expr(
     }
 
     if let Some(stm) = pgrx::nodes::node_to_string(expr as _) {
-        report_warning(&format!("only support const scalar array in qual: {}", stm));
+        report_warning(&format!(
+            "only support const or parameter scalar array in qual: {}",
+            stm
+        ));
     }
 
     None
 }
 
 pub(crate) unsafe fn extract_from_var(
-    _root: *mut pg_sys::PlannerInfo,
     baserel_id: pg_sys::Oid,
     baserel_ids: pg_sys::Relids,
     var: *mut pg_sys::Var,
@@ -325,44 +526,98 @@ pub(crate) unsafe fn extract_from_var(
         value: Value::Cell(Cell::Bool(true)),
         use_or: false,
         param: None,
+        sub_quals: Vec::new(),
+        json_path: Vec::new(),
+        type_oid: pg_sys::BOOLOID,
+        collate: None,
     };
 
     Some(qual)
 }
 
-pub(crate) unsafe fn extract_from_bool_expr(
-    _root: *mut pg_sys::PlannerInfo,
+// extract boolean expression as a qual with sub-conditions, all of the
+// sub-conditions must be supported otherwise the whole expression cannot be
+// pushed down
+pub(crate) unsafe fn extract_from_bool_expr_tree(
     baserel_id: pg_sys::Oid,
     baserel_ids: pg_sys::Relids,
     expr: *mut pg_sys::BoolExpr,
+    caps: QualCaps,
 ) -> Option<Qual> {
     let args: PgList<pg_sys::Node> = PgList::from_pg((*expr).args);
 
-    if (*expr).boolop != pg_sys::BoolExprType::NOT_EXPR || args.len() != 1 {
-        return None;
-    }
+    let operator = match (*expr).boolop {
+        pg_sys::BoolExprType::AND_EXPR => "and",
+        pg_sys::BoolExprType::OR_EXPR => "or",
+        pg_sys::BoolExprType::NOT_EXPR => "not",
+        _ => return None,
+    };
 
-    let var = args.head().unwrap() as *mut pg_sys::Var;
-    if (*var).varattno < 1
-        || (*var).vartype != pg_sys::BOOLOID
-        || !pg_sys::bms_is_member((*var).varno as c_int, baserel_ids)
-    {
-        return None;
+    let mut sub_quals = Vec::new();
+    for arg in args.iter_ptr() {
+        let sub_qual = extract_qual(baserel_id, baserel_ids, arg, caps)?;
+        sub_quals.push(sub_qual);
     }
 
-    let field = pg_sys::get_attname(baserel_id, (*var).varattno, false);
-
     let qual = Qual {
-        field: CStr::from_ptr(field).to_str().unwrap().to_string(),
-        operator: "=".to_string(),
-        value: Value::Cell(Cell::Bool(false)),
+        field: String::default(),
+        operator: operator.to_string(),
+        value: Value::Array(Vec::new()),
         use_or: false,
         param: None,
+        sub_quals,
+        json_path: Vec::new(),
+        type_oid: pg_sys::InvalidOid,
+        collate: None,
     };
 
     Some(qual)
 }
 
+pub(crate) unsafe fn extract_from_bool_expr(
+    baserel_id: pg_sys::Oid,
+    baserel_ids: pg_sys::Relids,
+    expr: *mut pg_sys::BoolExpr,
+    caps: QualCaps,
+) -> Option<Qual> {
+    let args: PgList<pg_sys::Node> = PgList::from_pg((*expr).args);
+
+    if (*expr).boolop == pg_sys::BoolExprType::NOT_EXPR && args.len() == 1 {
+        // negated boolean column, e.g. "not bool_col"
+        let var = args.head().unwrap() as *mut pg_sys::Var;
+        if is_a(var as _, pg_sys::NodeTag::T_Var)
+            && (*var).varattno >= 1
+            && (*var).vartype == pg_sys::BOOLOID
+            && pg_sys::bms_is_member((*var).varno as c_int, baserel_ids)
+        {
+            let field = pg_sys::get_attname(baserel_id, (*var).varattno, false);
+
+            let qual = Qual {
+                field: CStr::from_ptr(field).to_str().unwrap().to_string(),
+                operator: "=".to_string(),
+                value: Value::Cell(Cell::Bool(false)),
+                use_or: false,
+                param: None,
+                sub_quals: Vec::new(),
+                json_path: Vec::new(),
+                type_oid: pg_sys::BOOLOID,
+                collate: None,
+            };
+
+            return Some(qual);
+        }
+    }
+
+    // OR, AND and NOT expressions are only extracted if the FDW accepts
+    // boolean expression tree, the FDWs not handling `sub_quals` would take
+    // the group as a simple condition otherwise
+    if caps.bool_expr_tree {
+        return extract_from_bool_expr_tree(baserel_id, baserel_ids, expr, caps);
+    }
+
+    None
+}
+
 pub(crate) unsafe fn extract_from_boolean_test(
     baserel_id: pg_sys::Oid,
     expr: *mut pg_sys::BooleanTest,
@@ -388,44 +643,284 @@ pub(crate) unsafe fn extract_from_boolean_test(
         value: Value::Cell(Cell::Bool(value)),
         use_or: false,
         param: None,
+        sub_quals: Vec::new(),
+        json_path: Vec::new(),
+        type_oid: pg_sys::BOOLOID,
+        collate: None,
     };
 
     Some(qual)
 }
 
+// extract a qual from an expression node
+pub(crate) unsafe fn extract_qual(
+    baserel_id: pg_sys::Oid,
+    baserel_ids: pg_sys::Relids,
+    expr: *mut pg_sys::Node,
+    caps: QualCaps,
+) -> Option<Qual> {
+    if is_a(expr, pg_sys::NodeTag::T_OpExpr) {
+        extract_from_op_expr(baserel_id, baserel_ids, expr as _, caps)
+    } else if is_a(expr, pg_sys::NodeTag::T_NullTest) {
+        extract_from_null_test(baserel_id, baserel_ids, expr as _)
+    } else if is_a(expr, pg_sys::NodeTag::T_ScalarArrayOpExpr) {
+        extract_from_scalar_array_op_expr(baserel_id, baserel_ids, expr as _)
+    } else if is_a(expr, pg_sys::NodeTag::T_Var) {
+        extract_from_var(baserel_id, baserel_ids, expr as _)
+    } else if is_a(expr, pg_sys::NodeTag::T_BoolExpr) {
+        extract_from_bool_expr(baserel_id, baserel_ids, expr as _, caps)
+    } else if is_a(expr, pg_sys::NodeTag::T_BooleanTest) {
+        extract_from_boolean_test(baserel_id, expr as _)
+    } else {
+        if let Some(stm) = pgrx::nodes::node_to_string(expr) {
+            report_warning(&format!("unsupported qual: {}", stm));
+        }
+        None
+    }
+}
+
+// extract "col between symmetric x and y" as two range quals
+//
+// Postgres expands it to "(col >= x and col <= y) or (col >= y and col <= x)",
+// if x and y are constants, the bounds are sorted and decomposed to
+// "col >= lower" and "col <= upper".
+pub(crate) unsafe fn extract_from_between_symmetric(
+    baserel_id: pg_sys::Oid,
+    baserel_ids: pg_sys::Relids,
+    expr: *mut pg_sys::BoolExpr,
+    caps: QualCaps,
+) -> Option<Vec<Qual>> {
+    if (*expr).boolop != pg_sys::BoolExprType::OR_EXPR {
+        return None;
+    }
+
+    // get the (lower, upper) range conditions in each branch
+    let mut branches = Vec::new();
+    for arg in PgList::<pg_sys::Node>::from_pg((*expr).args).iter_ptr() {
+        if !is_a(arg, pg_sys::NodeTag::T_BoolExpr)
+            || (*(arg as *mut pg_sys::BoolExpr)).boolop != pg_sys::BoolExprType::AND_EXPR
+        {
+            return None;
+        }
+        let conds = PgList::<pg_sys::Node>::from_pg((*(arg as *mut pg_sys::BoolExpr)).args);
+        if conds.len() != 2 || !conds.iter_ptr().all(|c| is_a(c, pg_sys::NodeTag::T_OpExpr)) {
+            return None;
+        }
+        branches.push((
+            conds.head()? as *mut pg_sys::OpExpr,
+            conds.tail()? as *mut pg_sys::OpExpr,
+        ));
+    }
+    if branches.len() != 2 {
+        return None;
+    }
+
+    // get the constant bound of a range condition, it must have the same type
+    // as the column
+    let bound = |cond: *mut pg_sys::OpExpr| -> Option<*mut pg_sys::Const> {
+        let args = PgList::<pg_sys::Node>::from_pg((*cond).args);
+        let col = args.head()?;
+        let value = unnest_clause(args.tail()?);
+        if !is_a(value, pg_sys::NodeTag::T_Const)
+            || (*(value as *mut pg_sys::Const)).constisnull
+            || (*(value as *mut pg_sys::Const)).consttype != pg_sys::exprType(col)
+        {
+            return None;
+        }
+        Some(value as _)
+    };
+    let (lower, upper) = branches[0];
+    let (x, y) = (bound(lower)?, bound(upper)?);
+    let (lower2, upper2) = branches[1];
+    if !pg_sys::equal(x as _, bound(upper2)? as _) || !pg_sys::equal(y as _, bound(lower2)? as _) {
+        return None;
+    }
+
+    // compare the bounds using the upper bound operator, i.e. "x <= y"
+    let ordered = bool::from_datum(
+        pg_sys::OidFunctionCall2Coll(
+            pg_sys::get_opcode((*upper).opno),
+            (*upper).inputcollid,
+            (*x).constvalue,
+            (*y).constvalue,
+        ),
+        false,
+    )?;
+    let (lower, upper) = if ordered {
+        (lower, upper)
+    } else {
+        (lower2, upper2)
+    };
+
+    let lower = extract_from_op_expr(baserel_id, baserel_ids, lower, caps)?;
+    let upper = extract_from_op_expr(baserel_id, baserel_ids, upper, caps)?;
+    if lower.operator != ">="
+        || upper.operator != "<="
+        || lower.field != upper.field
+        || lower.json_path != upper.json_path
+    {
+        return None;
+    }
+
+    Some(vec![lower, upper])
+}
+
+// extract quals and the restriction clauses they are extracted from
 pub(crate) unsafe fn extract_quals(
-    root: *mut pg_sys::PlannerInfo,
     baserel: *mut pg_sys::RelOptInfo,
     baserel_id: pg_sys::Oid,
-) -> Vec<Qual> {
+    caps: QualCaps,
+) -> (Vec<Qual>, Vec<*mut pg_sys::RestrictInfo>) {
     let mut quals = Vec::new();
+    let mut clauses = Vec::new();
 
     let conds = PgList::<pg_sys::RestrictInfo>::from_pg((*baserel).baserestrictinfo);
     for cond in conds.iter_ptr() {
         let expr = (*cond).clause as *mut pg_sys::Node;
-        let extracted = if is_a(expr, pg_sys::NodeTag::T_OpExpr) {
-            extract_from_op_expr(root, baserel_id, (*baserel).relids, expr as _)
-        } else if is_a(expr, pg_sys::NodeTag::T_NullTest) {
-            extract_from_null_test(baserel_id, expr as _)
-        } else if is_a(expr, pg_sys::NodeTag::T_ScalarArrayOpExpr) {
-            extract_from_scalar_array_op_expr(root, baserel_id, (*baserel).relids, expr as _)
-        } else if is_a(expr, pg_sys::NodeTag::T_Var) {
-            extract_from_var(root, baserel_id, (*baserel).relids, expr as _)
-        } else if is_a(expr, pg_sys::NodeTag::T_BoolExpr) {
-            extract_from_bool_expr(root, baserel_id, (*baserel).relids, expr as _)
-        } else if is_a(expr, pg_sys::NodeTag::T_BooleanTest) {
-            extract_from_boolean_test(baserel_id, expr as _)
-        } else {
-            if let Some(stm) = pgrx::nodes::node_to_string(expr) {
-                report_warning(&format!("unsupported qual: {}", stm));
+        for qual in extract_clause_quals(baserel_id, (*baserel).relids, expr, caps) {
+            quals.push(qual);
+            clauses.push(cond);
+        }
+    }
+
+    (quals, clauses)
+}
+
+// extract quals from a restriction clause expression, note that BETWEEN
+// SYMMETRIC is decomposed to two quals from the same clause
+pub(crate) unsafe fn extract_clause_quals(
+    baserel_id: pg_sys::Oid,
+    baserel_ids: pg_sys::Relids,
+    expr: *mut pg_sys::Node,
+    caps: QualCaps,
+) -> Vec<Qual> {
+    if is_a(expr, pg_sys::NodeTag::T_BoolExpr) {
+        if let Some(range) =
+            extract_from_between_symmetric(baserel_id, baserel_ids, expr as _, caps)
+        {
+            return range;
+        }
+    }
+
+    extract_qual(baserel_id, baserel_ids, expr, caps)
+        .into_iter()
+        .collect()
+}
+
+// collect executor parameters in quals, e.g. "id = (select 1)", their value
+// expressions are appended to `param_exprs` and parameter ids are changed to
+// the index in that list
+pub(crate) unsafe fn collect_exec_params(
+    quals: &mut [Qual],
+    param_exprs: &mut Vec<*mut pg_sys::Node>,
+) {
+    for qual in quals.iter_mut() {
+        if let Some(param) = &mut qual.param {
+            if param.kind == ParamKind::Exec {
+                let mut node = pgrx::PgBox::<pg_sys::Param>::alloc_node(pg_sys::NodeTag::T_Param);
+                node.paramkind = pg_sys::ParamKind::PARAM_EXEC;
+                node.paramid = param.id as _;
+                node.paramtype = param.type_oid;
+                node.paramtypmod = -1;
+                node.paramcollid = pg_sys::InvalidOid;
+                node.location = -1;
+
+                param.id = param_exprs.len();
+                param_exprs.push(node.into_pg() as _);
             }
-            None
-        };
+        }
+        collect_exec_params(&mut qual.sub_quals, param_exprs);
+    }
+}
+
+// check if the node is a column of other relations
+unsafe fn is_outer_var(node: *mut pg_sys::Node, baserel_ids: pg_sys::Relids) -> bool {
+    is_a(node, pg_sys::NodeTag::T_Var)
+        && (*(node as *mut pg_sys::Var)).varlevelsup == 0
+        && !pg_sys::bms_is_member((*(node as *mut pg_sys::Var)).varno as c_int, baserel_ids)
+}
+
+// check if the clause is a parameterizable join clause, e.g. "a.id = b.id",
+// where one side is column of this relation and the other side is column of
+// an outer relation
+pub(crate) unsafe fn is_param_join_clause(
+    baserel: *mut pg_sys::RelOptInfo,
+    rinfo: *mut pg_sys::RestrictInfo,
+) -> bool {
+    let clause = (*rinfo).clause as *mut pg_sys::Node;
+    if (*rinfo).pseudoconstant || !is_a(clause, pg_sys::NodeTag::T_OpExpr) {
+        return false;
+    }
+
+    let args: PgList<pg_sys::Node> = PgList::from_pg((*(clause as *mut pg_sys::OpExpr)).args);
+    if args.len() != 2 {
+        return false;
+    }
+
+    let left = unnest_clause(args.head().unwrap());
+    let right = unnest_clause(args.tail().unwrap());
+    let is_inner_var = |node: *mut pg_sys::Node| {
+        is_a(node, pg_sys::NodeTag::T_Var)
+            && pg_sys::bms_is_member(
+                (*(node as *mut pg_sys::Var)).varno as c_int,
+                (*baserel).relids,
+            )
+    };
+
+    (is_inner_var(left) && is_outer_var(right, (*baserel).relids))
+        || (is_outer_var(left, (*baserel).relids) && is_inner_var(right))
+}
+
+// extract quals from parameterized join clauses in scan clauses, the outer
+// relation's column in each clause is replaced by an executor parameter, and
+// the column is appended to `param_exprs` as the parameter value expression
+pub(crate) unsafe fn extract_join_quals(
+    baserel: *mut pg_sys::RelOptInfo,
+    baserel_id: pg_sys::Oid,
+    scan_clauses: *mut pg_sys::List,
+    caps: QualCaps,
+    param_exprs: &mut Vec<*mut pg_sys::Node>,
+) -> (Vec<Qual>, Vec<*mut pg_sys::RestrictInfo>) {
+    let mut quals = Vec::new();
+    let mut clauses = Vec::new();
+
+    let conds = PgList::<pg_sys::RestrictInfo>::from_pg(scan_clauses);
+    for cond in conds.iter_ptr() {
+        if pg_sys::list_member_ptr((*baserel).baserestrictinfo, cond as _)
+            || !is_param_join_clause(baserel, cond)
+        {
+            continue;
+        }
+
+        // make a copy of the clause and replace outer column with parameter
+        let expr = pg_sys::copyObjectImpl((*cond).clause as _) as *mut pg_sys::OpExpr;
+        let args: PgList<pg_sys::Node> = PgList::from_pg((*expr).args);
+        let mut new_args = PgList::<pg_sys::Node>::new();
+        let mut outer = std::ptr::null_mut();
+        for arg in args.iter_ptr() {
+            let var = unnest_clause(arg);
+            if is_outer_var(var, (*baserel).relids) {
+                let mut param = pgrx::PgBox::<pg_sys::Param>::alloc_node(pg_sys::NodeTag::T_Param);
+                param.paramkind = pg_sys::ParamKind::PARAM_EXEC;
+                param.paramid = param_exprs.len() as _;
+                param.paramtype = pg_sys::exprType(var);
+                param.paramtypmod = pg_sys::exprTypmod(var);
+                param.paramcollid = pg_sys::exprCollation(var);
+                param.location = -1;
+                new_args.push(param.into_pg() as _);
+                outer = var;
+            } else {
+                new_args.push(arg);
+            }
+        }
+        (*expr).args = new_args.into_pg();
 
-        if let Some(qual) = extracted {
+        if let Some(qual) = extract_qual(baserel_id, (*baserel).relids, expr as _, caps) {
             quals.push(qual);
+            clauses.push(cond);
+            param_exprs.push(outer);
         }
     }
 
-    quals
+    (quals, clauses)
 }
@@ -0,0 +1,130 @@
+//! Client-side rate limit of the remote requests
+//!
+//! SaaS APIs usually throttle requests aggressively, for example, Airtable
+//! allows 5 requests per second, and concurrent Postgres backends can easily
+//! exceed the limit. The foreign server option `requests_per_second` limits the
+//! requests sent to the remote server by a token bucket, which allows a burst
+//! up to one second of requests. FDWs call [`RateLimiter::wait`] before each
+//! request, for example,
+//!
+//! ```rust,no_run
+//! # use supabase_wrappers::prelude::*;
+//! # fn new(server: ForeignServer) -> Result<(), OptionsError> {
+//! let rate_limiter = RateLimiter::from_server(&server)?;
+//!
+//! // before sending each request
+//! rate_limiter.wait();
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The token buckets are in shared memory so the limit applies to all backends
+//! if the extension is loaded by `shared_preload_libraries`, see
+//! [`init_shared_memory`](crate::shmem::init_shared_memory), otherwise each
+//! backend has its own token bucket.
+
+use crate::instance::ForeignServer;
+use crate::options::{parse_option, OptionsError};
+use crate::shmem::{with_server_states, ServerStates};
+use pgrx::prelude::*;
+use pgrx::PgLwLock;
+use std::cell::RefCell;
+use std::time::Duration;
+
+// max sleep time between interrupt checks when waiting for a token
+const WAIT_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Bucket {
+    tokens: f64,
+    updated_at: pg_sys::TimestampTz,
+}
+
+impl Bucket {
+    fn new(rps: f64) -> Self {
+        Self {
+            tokens: burst(rps),
+            updated_at: now(),
+        }
+    }
+
+    // take a token and return the time to wait until it is available, the
+    // token is reserved even if it is not available yet, so the waiting
+    // requests are queued
+    fn take(&mut self, rps: f64) -> Duration {
+        let now = now();
+        let elapsed = (now - self.updated_at).max(0) as f64 / 1_000_000.0;
+        self.tokens = (self.tokens + elapsed * rps).min(burst(rps)) - 1.0;
+        self.updated_at = now;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / rps)
+        }
+    }
+}
+
+pub(crate) static RATE_LIMIT_BUCKETS: PgLwLock<ServerStates<Bucket>> = PgLwLock::new();
+
+thread_local! {
+    // token buckets used when shared memory is not available
+    static LOCAL_BUCKETS: RefCell<ServerStates<Bucket>> = RefCell::new(ServerStates::default());
+}
+
+// burst size of the token bucket
+fn burst(rps: f64) -> f64 {
+    rps.max(1.0)
+}
+
+fn now() -> pg_sys::TimestampTz {
+    unsafe { pg_sys::GetCurrentTimestamp() }
+}
+
+/// Rate limiter of the requests sent to a foreign server
+///
+/// The default rate limiter doesn't limit requests.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RateLimiter {
+    server_oid: pg_sys::Oid,
+    requests_per_second: Option<f64>,
+}
+
+impl RateLimiter {
+    /// Create a rate limiter by the foreign server option `requests_per_second`,
+    /// requests are not limited if it is not specified
+    pub fn from_server(server: &ForeignServer) -> Result<Self, OptionsError> {
+        let requests_per_second =
+            parse_option("requests_per_second", &server.options, |rps: &f64| {
+                rps.is_finite() && *rps > 0.0
+            })?;
+        Ok(Self {
+            server_oid: server.server_oid,
+            requests_per_second,
+        })
+    }
+
+    /// Wait until a request can be sent to the foreign server
+    ///
+    /// The query can be cancelled while waiting.
+    pub fn wait(&self) {
+        let Some(rps) = self.requests_per_second else {
+            return;
+        };
+        let mut delay = self.take(rps);
+        while !delay.is_zero() {
+            let interval = delay.min(WAIT_INTERVAL);
+            unsafe { pg_sys::pg_usleep(interval.as_micros() as _) };
+            check_for_interrupts!();
+            delay -= interval;
+        }
+    }
+
+    // take a token from the foreign server's bucket
+    fn take(&self, rps: f64) -> Duration {
+        with_server_states(&RATE_LIMIT_BUCKETS, &LOCAL_BUCKETS, |buckets| {
+            buckets
+                .get_or_insert_with(self.server_oid, || Bucket::new(rps))
+                .take(rps)
+        })
+    }
+}
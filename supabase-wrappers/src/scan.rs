@@ -1,34 +1,241 @@
 use pgrx::FromDatum;
 use pgrx::{
-    debug2, memcxt::PgMemoryContexts, pg_sys::Datum, pg_sys::Oid, prelude::*, IntoDatum,
-    PgSqlErrorCode,
+    debug2, list::PgList, memcxt::PgMemoryContexts, pg_sys::Datum, pg_sys::Oid, prelude::*,
+    AnyNumeric, IntoDatum, PgBuiltInOids, PgOid, PgSqlErrorCode, PgTupleDesc,
 };
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::marker::PhantomData;
 
 use pgrx::pg_sys::panic::ErrorReport;
 use std::os::raw::c_int;
 use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
 
+use crate::aggregate::*;
+use crate::audit::{audit_enabled, audit_record, AuditKind};
+use crate::cache::ScanCache;
+use crate::guc;
 use crate::instance;
-use crate::interface::{Cell, Column, Limit, Qual, Row, Sort, Value};
+use crate::interface::{
+    AggregatePlan, Cell, Column, Join, Limit, ParamKind, Qual, Row, Sort, Value,
+};
+use crate::join::*;
 use crate::limit::*;
+use crate::mask::{column_mask, is_masked_qual, mask_cell, MaskKind};
 use crate::memctx;
+use crate::metrics::{add_server_metric, ServerMetric};
 use crate::options::options_to_hashmap;
 use crate::polyfill;
 use crate::prelude::ForeignDataWrapper;
 use crate::qual::*;
 use crate::sort::*;
-use crate::utils::{self, report_error, ReportableError, SerdeList};
+use crate::utils::{self, report_error, report_notice, report_warning, ReportableError, SerdeList};
+use crate::write_buffer::{self, WriteOverlay};
+
+// shared state of a parallel scan, it is in dynamic shared memory and accessed
+// by the leader and all the parallel workers
+#[repr(C)]
+struct ParallelShared {
+    // number of partitions the scan is split into
+    partitions: usize,
+
+    // index of the next partition to be scanned
+    next_partition: AtomicUsize,
+}
+
+// parallel scan state in each process
+struct ParallelScan {
+    // shared state, it is null if the scan runs without parallel workers
+    shared: *mut ParallelShared,
+
+    // number of partitions and index of the next partition, only used when
+    // there is no shared state
+    partitions: Option<usize>,
+    next_partition: usize,
+
+    // partition being scanned
+    current: Option<usize>,
+}
+
+impl ParallelScan {
+    fn new() -> Self {
+        Self {
+            shared: ptr::null_mut(),
+            partitions: None,
+            next_partition: 0,
+            current: None,
+        }
+    }
+}
+
+// rows cached for rescan, so a rescan without parameter changes can replay
+// the rows instead of fetching them from remote again
+struct RescanBuffer {
+    // max number of rows to be cached
+    max_rows: usize,
+
+    // cached rows, they are dropped if there are more than max_rows
+    rows: Vec<Row>,
+    overflowed: bool,
+
+    // if all the rows are fetched and cached
+    complete: bool,
+
+    // index of the next row to be replayed, rows are fetched from remote if
+    // it is not set
+    replay_pos: Option<usize>,
+}
+
+impl RescanBuffer {
+    fn new(max_rows: usize) -> Self {
+        Self {
+            max_rows,
+            rows: Vec::new(),
+            overflowed: false,
+            complete: false,
+            replay_pos: None,
+        }
+    }
+}
+
+// get size of a datum in bytes by its attribute type
+unsafe fn datum_size(datum: Datum, att: &pg_sys::FormData_pg_attribute) -> usize {
+    match att.attlen {
+        len if len > 0 => len as usize,
+        -1 => pgrx::varlena::varsize_any(datum.cast_mut_ptr()),
+        _ => std::ffi::CStr::from_ptr(datum.cast_mut_ptr())
+            .to_bytes_with_nul()
+            .len(),
+    }
+}
+
+// safety limits of the scan result, set by foreign table options `max_rows`
+// and `max_result_bytes`, the scan is stopped with an error once any of them
+// is exceeded, or truncated with a warning if option `on_limit_exceeded` is
+// 'truncate'
+struct ScanLimits {
+    max_rows: Option<u64>,
+    max_result_bytes: Option<u64>,
+    truncate: bool,
+
+    // scanned rows and bytes so far
+    rows: u64,
+    bytes: u64,
+
+    // if the scan has been truncated
+    truncated: bool,
+}
+
+impl ScanLimits {
+    fn from_options(opts: &HashMap<String, String>) -> Option<Self> {
+        let parse = |name: &str| {
+            opts.get(name).map(|v| match v.parse::<u64>() {
+                Ok(v) => v,
+                Err(_) => {
+                    pgrx::error!("invalid option {}: {}", name, v);
+                }
+            })
+        };
+        let max_rows = parse("max_rows");
+        let max_result_bytes = parse("max_result_bytes");
+        let truncate = match opts.get("on_limit_exceeded").map(|v| v.as_str()) {
+            None | Some("error") => false,
+            Some("truncate") => true,
+            Some(v) => pgrx::error!("invalid option on_limit_exceeded: {}", v),
+        };
+        if max_rows.is_none() && max_result_bytes.is_none() {
+            return None;
+        }
+        Some(Self {
+            max_rows,
+            max_result_bytes,
+            truncate,
+            rows: 0,
+            bytes: 0,
+            truncated: false,
+        })
+    }
+
+    fn reset(&mut self) {
+        self.rows = 0;
+        self.bytes = 0;
+        self.truncated = false;
+    }
+
+    // add a row of `bytes` size to the scan result, return false if the scan
+    // should be stopped as the row exceeds the limits
+    fn add_row(&mut self, bytes: u64) -> bool {
+        self.rows += 1;
+        self.bytes += bytes;
+        let exceeded = if self.max_rows.is_some_and(|max| self.rows > max) {
+            format!("max_rows {}", self.max_rows.unwrap_or_default())
+        } else if self.max_result_bytes.is_some_and(|max| self.bytes > max) {
+            format!(
+                "max_result_bytes {}",
+                self.max_result_bytes.unwrap_or_default()
+            )
+        } else {
+            return true;
+        };
+
+        if self.truncate {
+            report_warning(&format!(
+                "foreign table scan result is truncated as it exceeded {}",
+                exceeded
+            ));
+            self.truncated = true;
+            false
+        } else {
+            ErrorReport::new(
+                PgSqlErrorCode::ERRCODE_PROGRAM_LIMIT_EXCEEDED,
+                format!("foreign table scan result exceeded {}", exceeded),
+                "",
+            )
+            .set_hint("increase the limit or set option on_limit_exceeded to 'truncate'")
+            .report(PgLogLevel::ERROR);
+            false
+        }
+    }
+}
+
+// direct modification of the foreign table, the whole UPDATE or DELETE
+// statement is executed remotely
+struct DirectModify {
+    // new row of UPDATE, or None for DELETE
+    new_row: Option<Row>,
+
+    // if the statement has been executed
+    #[cfg(not(feature = "pg13"))]
+    executed: bool,
+}
 
 // Fdw private state for scan
 struct FdwState<E: Into<ErrorReport>, W: ForeignDataWrapper<E>> {
     // foreign data wrapper instance
     instance: W,
 
+    // range table index of the foreign table
+    relid: pg_sys::Index,
+
     // query conditions
     quals: Vec<Qual>,
 
+    // restriction clauses where each qual is extracted from, only valid
+    // in planning phase
+    qual_clauses: Vec<*mut pg_sys::RestrictInfo>,
+
+    // qual caps declared by the FDW
+    qual_caps: QualCaps,
+
+    // if all the restriction clauses are extracted to quals, only valid in
+    // planning phase
+    all_quals_extracted: bool,
+
+    // value expressions of executor parameters in quals, only valid in
+    // execution phase
+    param_exprs: Vec<*mut pg_sys::ExprState>,
+
     // query target column list
     tgts: Vec<Column>,
 
@@ -38,9 +245,63 @@ struct FdwState<E: Into<ErrorReport>, W: ForeignDataWrapper<E>> {
     // limit
     limit: Option<Limit>,
 
+    // aggregate plan pushed down, the scan is an aggregate scan if it is set
+    agg_plan: Option<AggregatePlan>,
+
+    // aggregate scan result rows
+    agg_rows: VecDeque<Row>,
+
+    // join pushed down, the scan is a join scan if it is set
+    join: Option<Join>,
+
+    // range table index of the outer relation in join
+    join_outer_relid: pg_sys::Index,
+
+    // parallel scan state, the scan is a parallel scan if it is set
+    parallel: Option<ParallelScan>,
+
+    // rescan buffer, enabled by foreign table option `rescan_buffer`
+    rescan_buf: Option<RescanBuffer>,
+
+    // scan result safety limits
+    limits: Option<ScanLimits>,
+
+    // scan result cache, enabled by foreign table option `cache_ttl`
+    cache: Option<ScanCache>,
+
+    // direct modification, the scan is a direct UPDATE or DELETE if it is set
+    direct_modify: Option<DirectModify>,
+
+    // if pending writes in current transaction are overlaid onto the fetched
+    // rows, enabled by foreign table option `read_your_writes`
+    read_your_writes: bool,
+
+    // pending writes to be overlaid, only valid in execution phase
+    write_overlay: Option<WriteOverlay>,
+
     // foreign table options
     opts: HashMap<String, String>,
 
+    // foreign server of the table and number of rows fetched from it, the
+    // rows are added to the server metrics when the scan ends
+    server_oid: Oid,
+    rows_fetched: i64,
+
+    // foreign table and start time of the scan, used by the audit record
+    ftable_id: Oid,
+    scan_started_at: Option<Instant>,
+
+    // if the scan is in dry run, which reports the remote request instead of
+    // sending it, set by `wrappers.dry_run` when the scan begins
+    dry_run: bool,
+
+    // masks of the target columns for current user, set when the scan begins
+    masks: Vec<Option<MaskKind>>,
+
+    // estimated scan costs
+    startup_cost: f64,
+    total_cost: f64,
+
     // temporary memory context per foreign table, created under Wrappers root
     // memory context
     tmp_ctx: PgMemoryContexts,
@@ -56,11 +317,35 @@ impl<E: Into<ErrorReport>, W: ForeignDataWrapper<E>> FdwState<E, W> {
     unsafe fn new(foreigntableid: Oid, tmp_ctx: PgMemoryContexts) -> Self {
         Self {
             instance: instance::create_fdw_instance_from_table_id(foreigntableid),
+            relid: 0,
             quals: Vec::new(),
+            qual_clauses: Vec::new(),
+            qual_caps: QualCaps::default(),
+            all_quals_extracted: false,
+            param_exprs: Vec::new(),
             tgts: Vec::new(),
             sorts: Vec::new(),
             limit: None,
+            agg_plan: None,
+            agg_rows: VecDeque::new(),
+            join: None,
+            join_outer_relid: 0,
+            parallel: None,
+            rescan_buf: None,
+            limits: None,
+            cache: None,
+            direct_modify: None,
+            read_your_writes: false,
+            write_overlay: None,
             opts: HashMap::new(),
+            server_oid: (*pg_sys::GetForeignTable(foreigntableid)).serverid,
+            rows_fetched: 0,
+            ftable_id: foreigntableid,
+            scan_started_at: None,
+            dry_run: false,
+            masks: Vec::new(),
+            startup_cost: 0.0,
+            total_cost: 0.0,
             tmp_ctx,
             values: Vec::new(),
             nulls: Vec::new(),
@@ -69,6 +354,100 @@ impl<E: Into<ErrorReport>, W: ForeignDataWrapper<E>> FdwState<E, W> {
         }
     }
 
+    // add the fetched rows to the server metrics, and count the error if the
+    // result is an error, so the metrics are kept before the error is reported
+    fn record_metrics<T>(&mut self, result: Result<T, E>) -> Result<T, E> {
+        let rows = std::mem::take(&mut self.rows_fetched);
+        add_server_metric(self.server_oid, ServerMetric::RowsFetched, rows);
+        if result.is_err() {
+            add_server_metric(self.server_oid, ServerMetric::Errors, 1);
+        }
+        result
+    }
+
+    // write the audit record of the scan, with the rows fetched since the scan
+    // began, it must be called before the rows are added to the metrics
+    fn audit_scan<T>(&mut self, result: &Result<T, E>) {
+        let Some(started_at) = self.scan_started_at.take() else {
+            return;
+        };
+        if !audit_enabled() {
+            return;
+        }
+        let table = self.table_name();
+        audit_record(
+            self.server_oid,
+            AuditKind::Scan,
+            &format!("scan {}", table),
+            Some(self.rows_fetched),
+            started_at.elapsed(),
+            result.as_ref().err().map(|_| "scan failed"),
+        );
+    }
+
+    // name of the foreign table being scanned
+    fn table_name(&self) -> String {
+        unsafe {
+            let name = pg_sys::get_rel_name(self.ftable_id);
+            if name.is_null() {
+                String::new()
+            } else {
+                std::ffi::CStr::from_ptr(name)
+                    .to_string_lossy()
+                    .into_owned()
+            }
+        }
+    }
+
+    // the pushed down scan details, same as shown in EXPLAIN, and the remote
+    // request details reported by the FDW for a normal scan
+    fn scan_details(&self) -> Result<Vec<String>, E> {
+        let mut details = vec![
+            format!("quals = {:?}", self.quals),
+            format!("tgts = {:?}", self.tgts),
+            format!("sorts = {:?}", self.sorts),
+            format!("limit = {:?}", self.limit),
+        ];
+        if let Some(join) = &self.join {
+            let conds: Vec<String> = join
+                .conds
+                .iter()
+                .map(|cond| format!("{} = {}", cond.outer_field, cond.inner_field))
+                .collect();
+            details.push(format!("join = {:?} on {:?}", join.kind, conds));
+        }
+        if let Some(plan) = &self.agg_plan {
+            if !plan.group_by.is_empty() {
+                let group_by: Vec<&str> = plan.group_by.iter().map(|c| c.name.as_str()).collect();
+                details.push(format!("group_by = {:?}", group_by));
+            }
+            details.push(format!("aggregates = {:?}", plan.aggregates));
+        }
+        if let Some(modify) = &self.direct_modify {
+            details.push(match &modify.new_row {
+                Some(row) => format!("direct_modify = update {:?}", row.cols),
+                None => "direct_modify = delete".to_string(),
+            });
+        }
+        if self.agg_plan.is_none() && self.join.is_none() && self.direct_modify.is_none() {
+            for (key, value) in self.explain()? {
+                details.push(format!("{}: {}", key, value));
+            }
+        }
+        Ok(details)
+    }
+
+    // report the scan details as notice instead of sending the remote request
+    fn report_dry_run(&self) -> Result<(), E> {
+        let details = self.scan_details()?;
+        report_notice(&format!(
+            "wrappers dry run on {}:\n  {}",
+            self.table_name(),
+            details.join("\n  ")
+        ));
+        Ok(())
+    }
+
     #[inline]
     fn get_rel_size(&mut self) -> Result<(i64, i32), E> {
         self.instance.get_rel_size(
@@ -80,8 +459,150 @@ impl<E: Into<ErrorReport>, W: ForeignDataWrapper<E>> FdwState<E, W> {
         )
     }
 
+    #[inline]
+    fn estimate_size(&mut self) -> Result<(f64, i32, f64, f64), E> {
+        self.instance
+            .estimate_size(&self.quals, &self.tgts, &self.opts)
+    }
+
+    #[inline]
+    fn explain(&self) -> Result<Vec<(String, String)>, E> {
+        self.instance.explain(
+            &self.quals,
+            &self.tgts,
+            &self.sorts,
+            &self.limit,
+            &self.opts,
+        )
+    }
+
+    #[inline]
+    fn handled_quals(&mut self) -> Result<Vec<bool>, E> {
+        let mut handled = self.instance.handled_quals(&self.quals, &self.opts)?;
+        // conditions on masked columns are always re-checked locally on the
+        // masked values, so they cannot reveal the original values
+        for (qual, handled) in self.quals.iter().zip(handled.iter_mut()) {
+            if *handled && unsafe { is_masked_qual(self.ftable_id, qual) } {
+                *handled = false;
+            }
+        }
+        Ok(handled)
+    }
+
+    #[inline]
+    fn plan_aggregate(&mut self, plan: &AggregatePlan) -> Result<bool, E> {
+        self.instance.plan_aggregate(plan, &self.quals, &self.opts)
+    }
+
+    #[inline]
+    fn scan_aggregate(&mut self) -> Result<(), E> {
+        if let Some(plan) = &self.agg_plan {
+            let rows = self
+                .instance
+                .scan_aggregate(plan, &self.quals, &self.opts)?;
+            self.agg_rows = VecDeque::from(rows);
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn plan_limit(&mut self, limit: &Limit) -> Result<bool, E> {
+        self.instance
+            .plan_limit(&self.quals, &self.tgts, &self.sorts, limit, &self.opts)
+    }
+
+    #[inline]
+    fn plan_join(&mut self, join: &Join) -> Result<bool, E> {
+        self.instance.plan_join(join)
+    }
+
+    #[inline]
+    fn begin_join_scan(&mut self) -> Result<(), E> {
+        if let Some(join) = &self.join {
+            self.instance.begin_join_scan(join)?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn scan_partitions(&mut self) -> Result<usize, E> {
+        self.instance
+            .scan_partitions(&self.quals, &self.tgts, &self.opts)
+    }
+
+    // claim the next partition to be scanned, returns None if all the
+    // partitions are claimed
+    fn claim_partition(&mut self) -> Result<Option<usize>, E> {
+        let Some(parallel) = &mut self.parallel else {
+            return Ok(None);
+        };
+
+        if !parallel.shared.is_null() {
+            let shared = unsafe { &*parallel.shared };
+            let idx = shared.next_partition.fetch_add(1, Ordering::SeqCst);
+            return Ok((idx < shared.partitions).then_some(idx));
+        }
+
+        let partitions = match parallel.partitions {
+            Some(partitions) => partitions,
+            None => self
+                .instance
+                .scan_partitions(&self.quals, &self.tgts, &self.opts)?,
+        };
+        parallel.partitions = Some(partitions);
+        let idx = parallel.next_partition;
+        if idx < partitions {
+            parallel.next_partition += 1;
+            Ok(Some(idx))
+        } else {
+            Ok(None)
+        }
+    }
+
+    // fetch one row of the parallel scan, the partitions are claimed and
+    // scanned one after another until there is no partition left
+    fn iter_parallel_scan(&mut self) -> Result<Option<()>, E> {
+        loop {
+            if self.parallel.as_ref().is_some_and(|p| p.current.is_none()) {
+                let Some(partition) = self.claim_partition()? else {
+                    return Ok(None);
+                };
+                self.instance.begin_partition_scan(
+                    partition,
+                    &self.quals,
+                    &self.tgts,
+                    &self.opts,
+                )?;
+                if let Some(parallel) = &mut self.parallel {
+                    parallel.current = Some(partition);
+                }
+            }
+
+            if self.iter_scan()?.is_some() {
+                return Ok(Some(()));
+            }
+            self.end_partition_scan()?;
+        }
+    }
+
+    #[inline]
+    fn end_partition_scan(&mut self) -> Result<(), E> {
+        if let Some(parallel) = &mut self.parallel {
+            if parallel.current.take().is_some() {
+                return self.instance.end_scan();
+            }
+        }
+        Ok(())
+    }
+
     #[inline]
     fn begin_scan(&mut self) -> Result<(), E> {
+        // no need to begin scan on remote if the rows are cached
+        if let Some(cache) = &mut self.cache {
+            if cache.lookup(&self.quals, &self.tgts, &self.sorts, &self.limit) {
+                return Ok(());
+            }
+        }
         self.instance.begin_scan(
             &self.quals,
             &self.tgts,
@@ -91,18 +612,130 @@ impl<E: Into<ErrorReport>, W: ForeignDataWrapper<E>> FdwState<E, W> {
         )
     }
 
-    #[inline]
+    // fetch one row from remote, or from the scan result cache
     fn iter_scan(&mut self) -> Result<Option<()>, E> {
-        self.instance.iter_scan(&mut self.row)
+        let Some(cache) = &mut self.cache else {
+            return self.instance.iter_scan(&mut self.row);
+        };
+
+        if cache.is_replaying() {
+            return Ok(cache.next_row().map(|row| {
+                self.row = row;
+            }));
+        }
+
+        let fetched = self.instance.iter_scan(&mut self.row)?;
+        cache.record(fetched.map(|_| &self.row));
+        Ok(fetched)
+    }
+
+    // fetch one row and cache it in rescan buffer, or replay the cached rows
+    // after a rescan
+    fn iter_buffered_scan(&mut self) -> Result<Option<()>, E> {
+        if let Some(buf) = &mut self.rescan_buf {
+            if let Some(pos) = &mut buf.replay_pos {
+                return Ok(buf.rows.get(*pos).map(|row| {
+                    self.row = row.clone();
+                    *pos += 1;
+                }));
+            }
+        }
+
+        let fetched = self.iter_scan()?;
+        let Some(buf) = &mut self.rescan_buf else {
+            return Ok(fetched);
+        };
+        if fetched.is_none() {
+            buf.complete = !buf.overflowed;
+        } else if buf.overflowed {
+            // too many rows, stop caching
+        } else if buf.rows.len() < buf.max_rows {
+            buf.rows.push(self.row.clone());
+        } else {
+            buf.rows.clear();
+            buf.overflowed = true;
+        }
+        Ok(fetched)
+    }
+
+    // fetch one row from remote with the pending writes overlaid, the
+    // inserted rows are returned after all the remote rows
+    fn iter_overlaid_scan(&mut self) -> Result<Option<()>, E> {
+        loop {
+            match &self.write_overlay {
+                None => return self.iter_buffered_scan(),
+                Some(overlay) if overlay.remote_done() => break,
+                _ => {}
+            }
+
+            self.row.clear();
+            let fetched = self.iter_buffered_scan()?;
+            let Some(overlay) = &mut self.write_overlay else {
+                return Ok(fetched);
+            };
+            if fetched.is_none() {
+                overlay.set_remote_done();
+            } else if overlay.apply(&mut self.row) {
+                return Ok(fetched);
+            }
+        }
+
+        let Some(overlay) = &mut self.write_overlay else {
+            return Ok(None);
+        };
+        Ok(overlay.next_inserted(&self.tgts).map(|row| {
+            self.row = row;
+        }))
     }
 
     #[inline]
     fn re_scan(&mut self) -> Result<(), E> {
+        // replay the cached rows if all the rows are cached, otherwise restart
+        // the scan on remote and cache the rows again
+        if let Some(buf) = &mut self.rescan_buf {
+            if buf.complete {
+                buf.replay_pos = Some(0);
+                return Ok(());
+            }
+            *buf = RescanBuffer::new(buf.max_rows);
+        }
+        if let Some(cache) = &mut self.cache {
+            cache.rewind();
+            if cache.is_replaying() {
+                return Ok(());
+            }
+        }
         self.instance.re_scan()
     }
 
+    #[cfg(not(feature = "pg13"))]
+    #[inline]
+    fn plan_direct_modify(&mut self, new_row: Option<&Row>) -> Result<bool, E> {
+        self.instance
+            .plan_direct_modify(&self.quals, new_row, &self.opts)
+    }
+
+    #[cfg(not(feature = "pg13"))]
+    #[inline]
+    fn exec_direct_modify(&mut self) -> Result<u64, E> {
+        let new_row = self
+            .direct_modify
+            .as_ref()
+            .and_then(|modify| modify.new_row.as_ref());
+        self.instance
+            .exec_direct_modify(&self.quals, new_row, &self.opts)
+    }
+
     #[inline]
     fn end_scan(&mut self) -> Result<(), E> {
+        // scan is not begun on remote if the rows are cached
+        if self
+            .cache
+            .as_ref()
+            .is_some_and(|cache| cache.is_replaying())
+        {
+            return Ok(());
+        }
         self.instance.end_scan()
     }
 }
@@ -123,9 +756,14 @@ pub(super) extern "C" fn get_foreign_rel_size<E: Into<ErrorReport>, W: ForeignDa
 
         // create scan state
         let mut state = FdwState::<E, W>::new(foreigntableid, ctx);
+        state.relid = (*baserel).relid;
 
         // extract qual list
-        state.quals = extract_quals(root, baserel, foreigntableid);
+        state.qual_caps = QualCaps {
+            bool_expr_tree: W::bool_expr_tree(),
+            json_path: W::json_path_quals(),
+        };
+        (state.quals, state.qual_clauses) = extract_quals(baserel, foreigntableid, state.qual_caps);
 
         // extract target column list from target and restriction expression
         state.tgts = utils::extract_target_columns(root, baserel);
@@ -140,10 +778,56 @@ pub(super) extern "C" fn get_foreign_rel_size<E: Into<ErrorReport>, W: ForeignDa
         let ftable = pg_sys::GetForeignTable(foreigntableid);
         state.opts = options_to_hashmap((*ftable).options).report_unwrap();
 
-        // get estimate row count and mean row width
-        let (rows, width) = state.get_rel_size().report_unwrap();
-        (*baserel).rows = rows as f64;
-        (*(*baserel).reltarget).width = width;
+        // pending writes are matched with the fetched rows by rowid, so the
+        // rowid column is always fetched, and limit is applied locally as
+        // the number of rows changes after the overlay
+        state.read_your_writes = write_buffer::is_enabled(&state.opts);
+        if state.read_your_writes {
+            let rowid_name = &state.opts["rowid_column"];
+            if !state.tgts.iter().any(|tgt| &tgt.name == rowid_name) {
+                let rowid_name_c = PgMemoryContexts::CurrentMemoryContext.pstrdup(rowid_name);
+                let attnum = pg_sys::get_attnum(foreigntableid, rowid_name_c);
+                if attnum == pg_sys::InvalidAttrNumber as pg_sys::AttrNumber {
+                    report_error(
+                        PgSqlErrorCode::ERRCODE_FDW_ERROR,
+                        &format!("rowid_column attribute {:?} does not exist", rowid_name),
+                    );
+                }
+                state.tgts.push(Column {
+                    name: rowid_name.to_string(),
+                    num: attnum as usize,
+                    type_oid: utils::column_type_oid(pg_sys::get_atttype(foreigntableid, attnum)),
+                    options: utils::get_column_options(foreigntableid, attnum),
+                });
+            }
+            state.limit = None;
+        }
+
+        // get estimate row count, mean row width and scan costs
+        let (mut rows, mut width, startup_cost, total_cost) = state.estimate_size().report_unwrap();
+        let (rel_rows, rel_width) = state.get_rel_size().report_unwrap();
+        if rel_rows > 0 {
+            rows = rel_rows as f64;
+            width = rel_width;
+        }
+        (*baserel).rows = rows;
+        if width > 0 {
+            (*(*baserel).reltarget).width = width;
+        }
+
+        // add startup cost from foreign table options
+        let extra_cost = state
+            .opts
+            .get("startup_cost")
+            .map(|c| match c.parse::<f64>() {
+                Ok(v) => v,
+                Err(_) => {
+                    pgrx::error!("invalid option startup_cost: {}", c);
+                }
+            })
+            .unwrap_or(0.0);
+        state.startup_cost = startup_cost + extra_cost;
+        state.total_cost = total_cost + extra_cost;
 
         // install callback to drop the state when memory context is reset
         let ctx = state.tmp_ctx.value();
@@ -160,55 +844,698 @@ pub(super) extern "C" fn get_foreign_paths<E: Into<ErrorReport>, W: ForeignDataW
     debug2!("---> get_foreign_paths");
     unsafe {
         let state = PgBox::<FdwState<E, W>>::from_pg((*baserel).fdw_private as _);
+        let startup_cost = state.startup_cost;
+        let total_cost = state.total_cost;
+
+        // create a ForeignPath node and add it as the only possible path
+        let path = pg_sys::create_foreignscan_path(
+            root,
+            baserel,
+            ptr::null_mut(), // default pathtarget
+            (*baserel).rows,
+            startup_cost,
+            total_cost,
+            ptr::null_mut(), // no pathkeys
+            ptr::null_mut(), // no outer rel either
+            ptr::null_mut(), // no extra plan
+            #[cfg(feature = "pg17")]
+            ptr::null_mut(), // no restrict info
+            ptr::null_mut(), // no fdw_private data
+        );
+        (*path).path.parallel_safe = false;
+        pg_sys::add_path(baserel, &mut ((*path).path));
+
+        // create a partial path for parallel scan, only this path can run in
+        // parallel workers and its rows are divided among the processes
+        let workers = pg_sys::max_parallel_workers_per_gather;
+        if W::parallel_safe()
+            && !state.read_your_writes
+            && (*baserel).consider_parallel
+            && pg_sys::bms_num_members((*baserel).lateral_relids) == 0
+            && workers > 0
+        {
+            let divisor = (workers + 1) as f64;
+            let path = pg_sys::create_foreignscan_path(
+                root,
+                baserel,
+                ptr::null_mut(), // default pathtarget
+                (*baserel).rows / divisor,
+                startup_cost,
+                startup_cost + (total_cost - startup_cost) / divisor,
+                ptr::null_mut(), // no pathkeys
+                ptr::null_mut(), // no outer rel either
+                ptr::null_mut(), // no extra plan
+                #[cfg(feature = "pg17")]
+                ptr::null_mut(), // no restrict info
+                ptr::null_mut(), // no fdw_private data
+            );
+            (*path).path.parallel_aware = true;
+            (*path).path.parallel_safe = true;
+            (*path).path.parallel_workers = workers;
+            pg_sys::add_partial_path(baserel, &mut ((*path).path));
+        }
+
+        // create parameterized paths, so the foreign table can be the inner
+        // side of a nested loop join
+        if W::parameterized_scan() {
+            let row_cost = (total_cost - startup_cost) / (*baserel).rows.max(1.0);
+            add_parameterized_paths(root, baserel, startup_cost, row_cost);
+        }
+    }
+}
+
+// callback to match equivalence member which is a column of the relation
+#[pg_guard]
+unsafe extern "C" fn ec_member_matches_column(
+    _root: *mut pg_sys::PlannerInfo,
+    rel: *mut pg_sys::RelOptInfo,
+    _ec: *mut pg_sys::EquivalenceClass,
+    em: *mut pg_sys::EquivalenceMember,
+    _arg: *mut std::ffi::c_void,
+) -> bool {
+    let expr = (*em).em_expr as *mut pg_sys::Node;
+    pgrx::is_a(expr, pg_sys::NodeTag::T_Var)
+        && (*(expr as *mut pg_sys::Var)).varno as pg_sys::Index == (*rel).relid
+}
+
+// add a parameterized path for each set of outer relations which have
+// join clauses with this relation
+unsafe fn add_parameterized_paths(
+    root: *mut pg_sys::PlannerInfo,
+    baserel: *mut pg_sys::RelOptInfo,
+    startup_cost: f64,
+    row_cost: f64,
+) {
+    let mut clauses: Vec<*mut pg_sys::RestrictInfo> = Vec::new();
+
+    // join clauses, e.g. "a.id > b.id"
+    let joininfo = PgList::<pg_sys::RestrictInfo>::from_pg((*baserel).joininfo);
+    for rinfo in joininfo.iter_ptr() {
+        if pg_sys::join_clause_is_movable_to(rinfo, baserel) && is_param_join_clause(baserel, rinfo)
+        {
+            clauses.push(rinfo);
+        }
+    }
+
+    // join clauses derived from equivalence classes, e.g. "a.id = b.id"
+    if (*baserel).has_eclass_joins {
+        let ec_clauses = PgList::<pg_sys::RestrictInfo>::from_pg(
+            pg_sys::generate_implied_equalities_for_column(
+                root,
+                baserel,
+                Some(ec_member_matches_column),
+                ptr::null_mut(),
+                (*baserel).lateral_referencers,
+            ),
+        );
+        for rinfo in ec_clauses.iter_ptr() {
+            if is_param_join_clause(baserel, rinfo) {
+                clauses.push(rinfo);
+            }
+        }
+    }
+
+    let mut outer_relids: Vec<pg_sys::Relids> = Vec::new();
+    for rinfo in clauses {
+        let required_outer = pg_sys::bms_difference((*rinfo).clause_relids, (*baserel).relids);
+        if pg_sys::bms_num_members(required_outer) == 0
+            || outer_relids
+                .iter()
+                .any(|relids| pg_sys::bms_equal(*relids, required_outer))
+        {
+            continue;
+        }
+        outer_relids.push(required_outer);
+
+        let ppi = pg_sys::get_baserel_parampathinfo(root, baserel, required_outer);
+        let rows = (*ppi).ppi_rows;
+        let path = pg_sys::create_foreignscan_path(
+            root,
+            baserel,
+            ptr::null_mut(), // default pathtarget
+            rows,
+            startup_cost,
+            startup_cost + rows * row_cost,
+            ptr::null_mut(), // no pathkeys
+            required_outer,
+            ptr::null_mut(), // no extra plan
+            #[cfg(feature = "pg17")]
+            ptr::null_mut(), // no restrict info
+            ptr::null_mut(), // no fdw_private data
+        );
+        (*path).path.parallel_safe = false;
+        pg_sys::add_path(baserel, &mut ((*path).path));
+    }
+}
+
+// check if all the restriction clauses of the relation are fully handled by
+// the FDW, note that one clause can be extracted to multiple quals
+unsafe fn all_quals_handled<E: Into<ErrorReport>, W: ForeignDataWrapper<E>>(
+    state: &mut FdwState<E, W>,
+    baserel: *mut pg_sys::RelOptInfo,
+) -> bool {
+    let handled = state.handled_quals().report_unwrap();
+    let conds = PgList::<pg_sys::RestrictInfo>::from_pg((*baserel).baserestrictinfo);
+    let all_handled = conds.iter_ptr().all(|cond| {
+        let flags: Vec<bool> = state
+            .qual_clauses
+            .iter()
+            .enumerate()
+            .filter(|(_, clause)| **clause == cond)
+            .map(|(idx, _)| handled.get(idx).copied().unwrap_or(false))
+            .collect();
+        !flags.is_empty() && flags.iter().all(|handled| *handled)
+    });
+    all_handled
+}
+
+// check if the qual or any of its sub quals has a parameter
+fn has_param(qual: &Qual) -> bool {
+    qual.param.is_some() || qual.sub_quals.iter().any(has_param)
+}
+
+#[pg_guard]
+pub(super) extern "C" fn get_foreign_join_paths<E: Into<ErrorReport>, W: ForeignDataWrapper<E>>(
+    root: *mut pg_sys::PlannerInfo,
+    joinrel: *mut pg_sys::RelOptInfo,
+    outerrel: *mut pg_sys::RelOptInfo,
+    innerrel: *mut pg_sys::RelOptInfo,
+    jointype: pg_sys::JoinType::Type,
+    extra: *mut pg_sys::JoinPathExtraData,
+) {
+    debug2!("---> get_foreign_join_paths");
+    unsafe {
+        // only join of two foreign tables is supported, and the join is only
+        // considered once, row locking is not supported either as it needs
+        // to re-check the joined rows locally
+        if !(*joinrel).fdw_private.is_null()
+            || (*outerrel).reloptkind != pg_sys::RelOptKind::RELOPT_BASEREL
+            || (*innerrel).reloptkind != pg_sys::RelOptKind::RELOPT_BASEREL
+            || (*outerrel).fdw_private.is_null()
+            || (*innerrel).fdw_private.is_null()
+            || !(*root).rowMarks.is_null()
+            || pg_sys::bms_num_members((*joinrel).lateral_relids) > 0
+        {
+            return;
+        }
+
+        let rel_oid = |rel: *mut pg_sys::RelOptInfo| {
+            let rte = *(*root).simple_rte_array.add((*rel).relid as usize);
+            (*rte).relid
+        };
+        let outer_oid = rel_oid(outerrel);
+        if outer_oid == rel_oid(innerrel) {
+            return;
+        }
+
+        // all the conditions on both tables must be applied remotely, and
+        // parameters in them are not supported, pending writes cannot be
+        // overlaid onto the joined rows either
+        let mut outer_state = PgBox::<FdwState<E, W>>::from_pg((*outerrel).fdw_private as _);
+        let mut inner_state = PgBox::<FdwState<E, W>>::from_pg((*innerrel).fdw_private as _);
+        if outer_state.read_your_writes
+            || inner_state.read_your_writes
+            || !all_quals_handled(&mut outer_state, outerrel)
+            || !all_quals_handled(&mut inner_state, innerrel)
+            || outer_state.quals.iter().any(has_param)
+            || inner_state.quals.iter().any(has_param)
+        {
+            return;
+        }
+
+        let Some(mut join) = extract_join(
+            root,
+            joinrel,
+            outerrel,
+            innerrel,
+            jointype,
+            (*extra).restrictlist,
+        ) else {
+            return;
+        };
+        join.outer.quals = outer_state.quals.clone();
+        join.outer.options = outer_state.opts.clone();
+        join.inner.quals = inner_state.quals.clone();
+        join.inner.options = inner_state.opts.clone();
+
+        // masked columns cannot be joined remotely on their original values
+        if join
+            .outer
+            .columns
+            .iter()
+            .chain(join.inner.columns.iter())
+            .any(|col| col.options.contains_key("mask"))
+        {
+            return;
+        }
+
+        // create a new scan state for the join, it is dropped together with
+        // the outer table's scan state
+        let ctx = PgMemoryContexts::For(outer_state.tmp_ctx.value());
+        let mut state = FdwState::<E, W>::new(outer_oid, ctx);
+        state.opts = outer_state.opts.clone();
+        if !state.plan_join(&join).report_unwrap() {
+            return;
+        }
+
+        // the scan tuple is made of outer table columns followed by inner
+        // table columns
+        state.tgts = join
+            .outer
+            .columns
+            .iter()
+            .chain(join.inner.columns.iter())
+            .enumerate()
+            .map(|(idx, col)| Column {
+                num: idx + 1,
+                ..col.clone()
+            })
+            .collect();
+        state.join = Some(join);
+        state.join_outer_relid = (*outerrel).relid;
+
+        // the join runs once on remote, so it is cheaper than scanning the two
+        // tables separately
+        let startup_cost = outer_state.startup_cost + inner_state.startup_cost;
+        let rows = (*joinrel).rows;
+        let path = pg_sys::create_foreign_join_path(
+            root,
+            joinrel,
+            ptr::null_mut(), // default pathtarget
+            rows,
+            startup_cost,
+            startup_cost + rows,
+            ptr::null_mut(), // no pathkeys
+            ptr::null_mut(), // no outer rel either
+            ptr::null_mut(), // no extra plan
+            #[cfg(feature = "pg17")]
+            ptr::null_mut(), // no restrict info
+            ptr::null_mut(), // no fdw_private data
+        );
+        (*path).path.parallel_safe = false;
+        pg_sys::add_path(joinrel, path as _);
+
+        let ctx = state.tmp_ctx.value();
+        (*joinrel).fdw_private = PgMemoryContexts::For(ctx).leak_and_drop_on_delete(state) as _;
+    }
+}
+
+#[pg_guard]
+pub(super) extern "C" fn get_foreign_upper_paths<E: Into<ErrorReport>, W: ForeignDataWrapper<E>>(
+    root: *mut pg_sys::PlannerInfo,
+    stage: pg_sys::UpperRelationKind::Type,
+    input_rel: *mut pg_sys::RelOptInfo,
+    output_rel: *mut pg_sys::RelOptInfo,
+    extra: *mut std::ffi::c_void,
+) {
+    debug2!("---> get_foreign_upper_paths");
+    unsafe {
+        // the ordered relation shares the scan state, so the limit can be
+        // pushed down together with the sorts in final stage
+        if stage == pg_sys::UpperRelationKind::UPPERREL_ORDERED
+            && (*input_rel).reloptkind == pg_sys::RelOptKind::RELOPT_BASEREL
+            && (*output_rel).fdw_private.is_null()
+        {
+            (*output_rel).fdw_private = (*input_rel).fdw_private;
+            return;
+        }
+
+        if stage == pg_sys::UpperRelationKind::UPPERREL_FINAL {
+            add_final_path::<E, W>(root, input_rel, output_rel, extra as _);
+            return;
+        }
+
+        // only aggregate on a single foreign table is supported
+        if stage != pg_sys::UpperRelationKind::UPPERREL_GROUP_AGG
+            || (*input_rel).reloptkind != pg_sys::RelOptKind::RELOPT_BASEREL
+            || (*input_rel).fdw_private.is_null()
+            || !(*output_rel).fdw_private.is_null()
+        {
+            return;
+        }
+
+        let mut state = PgBox::<FdwState<E, W>>::from_pg((*input_rel).fdw_private as _);
+
+        // all the conditions must be applied remotely, because they cannot be
+        // re-checked locally after aggregation, pending writes cannot be
+        // overlaid onto the aggregated rows either
+        if state.read_your_writes || !all_quals_handled(&mut state, input_rel) {
+            return;
+        }
+
+        // masked columns must be aggregated locally on the masked values
+        if state
+            .tgts
+            .iter()
+            .any(|tgt| tgt.options.contains_key("mask"))
+        {
+            return;
+        }
+
+        let target = (*root).upper_targets[pg_sys::UpperRelationKind::UPPERREL_GROUP_AGG as usize];
+        let rte = *(*root).simple_rte_array.add((*input_rel).relid as usize);
+        let Some(plan) = extract_aggregate_plan(root, input_rel, (*rte).relid, target) else {
+            return;
+        };
+        if !state.plan_aggregate(&plan).report_unwrap() {
+            return;
+        }
 
-        // get startup cost from foreign table options
-        let startup_cost = state
-            .opts
-            .get("startup_cost")
-            .map(|c| match c.parse::<f64>() {
-                Ok(v) => v,
-                Err(_) => {
-                    pgrx::error!("invalid option startup_cost: {}", c);
-                }
-            })
-            .unwrap_or(0.0);
-        let total_cost = startup_cost + (*baserel).rows;
+        // the aggregate scan returns only one row if there is no grouping,
+        // otherwise use the input rows as an upper bound of the groups
+        let rows = if plan.group_by.is_empty() {
+            1.0
+        } else {
+            (*input_rel).rows
+        };
+        state.agg_plan = Some(plan);
 
-        // create a ForeignPath node and add it as the only possible path
-        let path = pg_sys::create_foreignscan_path(
+        let startup_cost = state.startup_cost;
+        let path = pg_sys::create_foreign_upper_path(
             root,
-            baserel,
-            ptr::null_mut(), // default pathtarget
-            (*baserel).rows,
+            output_rel,
+            target,
+            rows,
             startup_cost,
-            total_cost,
+            startup_cost + rows,
             ptr::null_mut(), // no pathkeys
-            ptr::null_mut(), // no outer rel either
             ptr::null_mut(), // no extra plan
             #[cfg(feature = "pg17")]
             ptr::null_mut(), // no restrict info
             ptr::null_mut(), // no fdw_private data
         );
-        pg_sys::add_path(baserel, &mut ((*path).path));
+        (*path).path.parallel_safe = false;
+        pg_sys::add_path(output_rel, path as _);
+
+        (*output_rel).fdw_private = (*input_rel).fdw_private;
+    }
+}
+
+// add a final path which applies LIMIT and OFFSET remotely, so Postgres doesn't
+// need to apply them locally, it is only added for simple query on a single
+// foreign table, where the FDW accepts the limit and fully applies the quals
+// and sorts remotely
+unsafe fn add_final_path<E: Into<ErrorReport>, W: ForeignDataWrapper<E>>(
+    root: *mut pg_sys::PlannerInfo,
+    input_rel: *mut pg_sys::RelOptInfo,
+    output_rel: *mut pg_sys::RelOptInfo,
+    extra: *mut pg_sys::FinalPathExtraData,
+) {
+    let parse = (*root).parse;
+    if !(*extra).limit_needed
+        || (*input_rel).fdw_private.is_null()
+        || !(*output_rel).fdw_private.is_null()
+        || !(*root).rowMarks.is_null()
+        || (*parse).hasAggs
+        || (*parse).hasWindowFuncs
+        || (*parse).hasTargetSRFs
+        || !(*parse).groupClause.is_null()
+        || !(*parse).groupingSets.is_null()
+        || !(*parse).havingQual.is_null()
+        || !(*parse).distinctClause.is_null()
+        || !(*parse).setOperations.is_null()
+    {
+        return;
+    }
+
+    // input is the base relation, or the ordered relation if there is ORDER BY
+    let is_base = (*input_rel).reloptkind == pg_sys::RelOptKind::RELOPT_BASEREL;
+    if (*parse).sortClause.is_null() != is_base {
+        return;
+    }
+
+    // OFFSET must be a constant, otherwise it is not in the extracted limit
+    let limit_offset = (*parse).limitOffset;
+    if !limit_offset.is_null() && !pgrx::is_a(limit_offset, pg_sys::NodeTag::T_Const) {
+        return;
+    }
+
+    let mut state = PgBox::<FdwState<E, W>>::from_pg((*input_rel).fdw_private as _);
+    let Some(limit) = state.limit.clone() else {
+        return;
+    };
+    let baserel = *(*root).simple_rel_array.add(state.relid as usize);
+    let pathkeys = PgList::<pg_sys::PathKey>::from_pg((*root).query_pathkeys);
+    if state.agg_plan.is_some()
+        || state.sorts.len() != pathkeys.len()
+        || !all_quals_handled(&mut state, baserel)
+        || !state.plan_limit(&limit).report_unwrap()
+    {
+        return;
     }
+
+    // only the limited rows are fetched
+    let rows = (limit.count as f64).min((*baserel).rows);
+    let startup_cost = state.startup_cost;
+    let row_cost = (state.total_cost - startup_cost) / (*baserel).rows.max(1.0);
+    let path = pg_sys::create_foreign_upper_path(
+        root,
+        output_rel,
+        (*root).upper_targets[pg_sys::UpperRelationKind::UPPERREL_FINAL as usize],
+        rows,
+        startup_cost,
+        startup_cost + rows * row_cost,
+        (*root).query_pathkeys,
+        ptr::null_mut(), // no extra plan
+        #[cfg(feature = "pg17")]
+        ptr::null_mut(), // no restrict info
+        ptr::null_mut(), // no fdw_private data
+    );
+    (*path).path.parallel_safe = false;
+    pg_sys::add_path(output_rel, path as _);
+
+    (*output_rel).fdw_private = (*input_rel).fdw_private;
 }
 
 #[pg_guard]
 pub(super) extern "C" fn get_foreign_plan<E: Into<ErrorReport>, W: ForeignDataWrapper<E>>(
-    _root: *mut pg_sys::PlannerInfo,
+    root: *mut pg_sys::PlannerInfo,
     baserel: *mut pg_sys::RelOptInfo,
-    _foreigntableid: pg_sys::Oid,
-    _best_path: *mut pg_sys::ForeignPath,
+    foreigntableid: pg_sys::Oid,
+    best_path: *mut pg_sys::ForeignPath,
     tlist: *mut pg_sys::List,
     scan_clauses: *mut pg_sys::List,
     outer_plan: *mut pg_sys::Plan,
 ) -> *mut pg_sys::ForeignScan {
     debug2!("---> get_foreign_plan");
     unsafe {
-        let state = PgBox::<FdwState<E, W>>::from_pg((*baserel).fdw_private as _);
+        let mut state = PgBox::<FdwState<E, W>>::from_pg((*baserel).fdw_private as _);
+
+        // collect executor parameters in quals, their value expressions will be
+        // evaluated in execution phase
+        let mut param_exprs: Vec<*mut pg_sys::Node> = Vec::new();
+        collect_exec_params(&mut state.quals, &mut param_exprs);
+
+        // limited scan on the final relation, its scan tuple is made of all the
+        // target columns, and all the quals, sorts and limit are applied remotely
+        if (*baserel).reloptkind == pg_sys::RelOptKind::RELOPT_UPPER_REL && state.agg_plan.is_none()
+        {
+            let relid = state.relid;
+            let rte = *(*root).simple_rte_array.add(relid as usize);
+            let mut fdw_scan_tlist = PgList::<pg_sys::TargetEntry>::new();
+            for (idx, tgt) in state.tgts.iter_mut().enumerate() {
+                let mut type_oid = pg_sys::InvalidOid;
+                let mut typmod = -1;
+                let mut coll_id = pg_sys::InvalidOid;
+                pg_sys::get_atttypetypmodcoll(
+                    (*rte).relid,
+                    tgt.num as _,
+                    &mut type_oid,
+                    &mut typmod,
+                    &mut coll_id,
+                );
+                let var = pg_sys::makeVar(relid as _, tgt.num as _, type_oid, typmod, coll_id, 0);
+                fdw_scan_tlist.push(pg_sys::makeTargetEntry(
+                    var as _,
+                    (idx + 1) as _,
+                    ptr::null_mut(),
+                    false,
+                ));
+                tgt.num = idx + 1;
+            }
+            let mut fdw_exprs = PgList::<pg_sys::Node>::new();
+            for expr in param_exprs {
+                fdw_exprs.push(expr);
+            }
+            state.qual_clauses.clear();
+
+            let ctx = PgMemoryContexts::For(state.tmp_ctx.value());
+            let fdw_private = FdwState::serialize_to_list(state, ctx);
+
+            return pg_sys::make_foreignscan(
+                tlist,
+                ptr::null_mut(),
+                0,
+                fdw_exprs.into_pg(),
+                fdw_private as _,
+                fdw_scan_tlist.into_pg(),
+                ptr::null_mut(),
+                outer_plan,
+            );
+        }
+
+        // aggregate scan on the upper relation, its scan tuple is made of the
+        // grouping columns followed by the aggregate results, and all the quals
+        // are applied remotely
+        if (*baserel).reloptkind == pg_sys::RelOptKind::RELOPT_UPPER_REL {
+            let target =
+                (*root).upper_targets[pg_sys::UpperRelationKind::UPPERREL_GROUP_AGG as usize];
+            let exprs = PgList::<pg_sys::Expr>::from_pg((*target).exprs);
+            let (aggrefs, vars): (Vec<_>, Vec<_>) = exprs
+                .iter_ptr()
+                .partition(|expr| pgrx::is_a(*expr as _, pg_sys::NodeTag::T_Aggref));
+            let mut fdw_scan_tlist = PgList::<pg_sys::TargetEntry>::new();
+            for (idx, expr) in vars.into_iter().chain(aggrefs).enumerate() {
+                fdw_scan_tlist.push(pg_sys::makeTargetEntry(
+                    expr,
+                    (idx + 1) as _,
+                    ptr::null_mut(),
+                    false,
+                ));
+            }
+            let mut fdw_exprs = PgList::<pg_sys::Node>::new();
+            for expr in param_exprs {
+                fdw_exprs.push(expr);
+            }
+            state.qual_clauses.clear();
+
+            let ctx = PgMemoryContexts::For(state.tmp_ctx.value());
+            let fdw_private = FdwState::serialize_to_list(state, ctx);
+
+            return pg_sys::make_foreignscan(
+                tlist,
+                ptr::null_mut(),
+                0,
+                fdw_exprs.into_pg(),
+                fdw_private as _,
+                fdw_scan_tlist.into_pg(),
+                ptr::null_mut(),
+                outer_plan,
+            );
+        }
+
+        // join scan on the join relation, its scan tuple is made of the outer
+        // table columns followed by the inner table columns, and all the join
+        // clauses and quals are applied remotely
+        if (*baserel).reloptkind == pg_sys::RelOptKind::RELOPT_JOINREL {
+            let exprs = PgList::<pg_sys::Expr>::from_pg((*(*baserel).reltarget).exprs);
+            let (outer_vars, inner_vars): (Vec<_>, Vec<_>) = exprs.iter_ptr().partition(|expr| {
+                (*(*expr as *mut pg_sys::Var)).varno as pg_sys::Index == state.join_outer_relid
+            });
+            let mut fdw_scan_tlist = PgList::<pg_sys::TargetEntry>::new();
+            for (idx, expr) in outer_vars.into_iter().chain(inner_vars).enumerate() {
+                fdw_scan_tlist.push(pg_sys::makeTargetEntry(
+                    expr,
+                    (idx + 1) as _,
+                    ptr::null_mut(),
+                    false,
+                ));
+            }
+            state.qual_clauses.clear();
+
+            let ctx = PgMemoryContexts::For(state.tmp_ctx.value());
+            let fdw_private = FdwState::serialize_to_list(state, ctx);
+
+            return pg_sys::make_foreignscan(
+                tlist,
+                ptr::null_mut(),
+                0,
+                ptr::null_mut(),
+                fdw_private as _,
+                fdw_scan_tlist.into_pg(),
+                ptr::null_mut(),
+                outer_plan,
+            );
+        }
+
+        // aggregate scan path is not chosen
+        state.agg_plan = None;
+
+        // limit is applied locally, so the offset rows must be fetched too
+        if let Some(limit) = &mut state.limit {
+            limit.count += limit.offset;
+            limit.offset = 0;
+        }
+
+        // extract quals from join clauses if this is a parameterized scan
+        if !(*best_path).path.param_info.is_null() {
+            let (quals, clauses) = extract_join_quals(
+                baserel,
+                foreigntableid,
+                scan_clauses,
+                state.qual_caps,
+                &mut param_exprs,
+            );
+            state.quals.extend(quals);
+            state.qual_clauses.extend(clauses);
+        }
+        let mut fdw_exprs = PgList::<pg_sys::Node>::new();
+        for expr in param_exprs {
+            fdw_exprs.push(expr);
+        }
+
+        // remove the clauses fully handled by the FDW, so they won't be
+        // re-checked locally, note that one clause can be extracted to multiple
+        // quals, e.g. BETWEEN SYMMETRIC, it is only removed if all of them
+        // are handled, all the clauses are kept for the overlaid pending
+        // writes as they are not filtered by the FDW
+        let handled = if state.read_your_writes {
+            Vec::new()
+        } else {
+            state.handled_quals().report_unwrap()
+        };
+        let mut handled_clauses = Vec::new();
+        let mut unhandled_clauses = Vec::new();
+        for (idx, clause) in state.qual_clauses.iter().enumerate() {
+            if handled.get(idx).copied().unwrap_or(false) {
+                handled_clauses.push(*clause);
+            } else {
+                unhandled_clauses.push(*clause);
+            }
+        }
+        let mut local_clauses = PgList::<pg_sys::RestrictInfo>::new();
+        for clause in PgList::<pg_sys::RestrictInfo>::from_pg(scan_clauses).iter_ptr() {
+            if !handled_clauses.contains(&clause) || unhandled_clauses.contains(&clause) {
+                local_clauses.push(clause);
+            }
+        }
+
+        // direct modification needs all the clauses to be applied remotely,
+        // see plan_direct_modify, it is not used if the modified rows need to
+        // be overlaid onto later scans
+        state.all_quals_extracted = !state.read_your_writes
+            && PgList::<pg_sys::RestrictInfo>::from_pg(scan_clauses)
+                .iter_ptr()
+                .all(|clause| state.qual_clauses.contains(&clause));
+
+        // parallel scan doesn't push down sorts and limit, and parallel workers
+        // rebuild the scan state from the relation, qual clauses and target
+        // columns, see rebuild_worker_state
+        let mut parallel_info = PgList::<pg_sys::Node>::new();
+        if (*best_path).path.parallel_aware {
+            state.sorts.clear();
+            state.limit = None;
+
+            let mut clauses = PgList::<pg_sys::Node>::new();
+            let mut seen = Vec::new();
+            for rinfo in state.qual_clauses.iter() {
+                if !seen.contains(rinfo) {
+                    seen.push(*rinfo);
+                    clauses.push((**rinfo).clause as _);
+                }
+            }
+            let mut attnums = PgList::<pg_sys::Node>::new();
+            for tgt in state.tgts.iter() {
+                attnums.push(make_int4_const(tgt.num as i32));
+            }
+            parallel_info.push(make_int4_const((*baserel).relid as i32));
+            parallel_info.push(clauses.into_pg() as _);
+            parallel_info.push(attnums.into_pg() as _);
+        }
+        state.qual_clauses.clear();
 
         // make foreign scan plan
-        let scan_clauses = pg_sys::extract_actual_clauses(scan_clauses, false);
+        let scan_clauses = pg_sys::extract_actual_clauses(local_clauses.into_pg(), false);
 
         // 'serialize' state to list, basically what we're doing here is to store
         // the state pointer as an integer constant in the list, so it can be
@@ -218,14 +1545,18 @@ pub(super) extern "C" fn get_foreign_plan<E: Into<ErrorReport>, W: ForeignDataWr
         // the state's temporary memory context (state.tmp_ctx) is reset at the
         // beginning of next query run.
         let ctx = PgMemoryContexts::For(state.tmp_ctx.value());
-        let fdw_private = FdwState::serialize_to_list(state, ctx);
+        let mut fdw_private =
+            PgList::<pg_sys::Node>::from_pg(FdwState::serialize_to_list(state, ctx));
+        for info in parallel_info.iter_ptr() {
+            fdw_private.push(info);
+        }
 
         pg_sys::make_foreignscan(
             tlist,
             scan_clauses,
             (*baserel).relid,
-            ptr::null_mut(),
-            fdw_private as _,
+            fdw_exprs.into_pg(),
+            fdw_private.into_pg(),
             ptr::null_mut(),
             ptr::null_mut(),
             outer_plan,
@@ -233,6 +1564,25 @@ pub(super) extern "C" fn get_foreign_plan<E: Into<ErrorReport>, W: ForeignDataWr
     }
 }
 
+// make an int4 constant node
+unsafe fn make_int4_const(value: i32) -> *mut pg_sys::Node {
+    pg_sys::makeConst(
+        pg_sys::INT4OID,
+        -1,
+        pg_sys::InvalidOid,
+        4,
+        value.into_datum().unwrap(),
+        false,
+        true,
+    ) as _
+}
+
+// get the int4 value of a constant node
+unsafe fn get_int4_const(node: *mut pg_sys::Node) -> i32 {
+    let cst = node as *mut pg_sys::Const;
+    i32::from_datum((*cst).constvalue, (*cst).constisnull).unwrap()
+}
+
 #[pg_guard]
 pub(super) extern "C" fn explain_foreign_scan<E: Into<ErrorReport>, W: ForeignDataWrapper<E>>(
     node: *mut pg_sys::ForeignScanState,
@@ -262,33 +1612,228 @@ pub(super) extern "C" fn explain_foreign_scan<E: Into<ErrorReport>, W: ForeignDa
 
         let value = ctx.pstrdup(&format!("limit = {:?}", state.limit));
         pg_sys::ExplainPropertyText(label, value, es);
+
+        if let Some(join) = &state.join {
+            let conds: Vec<String> = join
+                .conds
+                .iter()
+                .map(|cond| format!("{} = {}", cond.outer_field, cond.inner_field))
+                .collect();
+            let value = ctx.pstrdup(&format!("join = {:?} on {:?}", join.kind, conds));
+            pg_sys::ExplainPropertyText(label, value, es);
+        }
+
+        if let Some(plan) = &state.agg_plan {
+            if !plan.group_by.is_empty() {
+                let group_by: Vec<&str> = plan.group_by.iter().map(|c| c.name.as_str()).collect();
+                let value = ctx.pstrdup(&format!("group_by = {:?}", group_by));
+                pg_sys::ExplainPropertyText(label, value, es);
+            }
+
+            let value = ctx.pstrdup(&format!("aggregates = {:?}", plan.aggregates));
+            pg_sys::ExplainPropertyText(label, value, es);
+        }
+
+        if let Some(modify) = &state.direct_modify {
+            let value = match &modify.new_row {
+                Some(row) => format!("direct_modify = update {:?}", row.cols),
+                None => "direct_modify = delete".to_string(),
+            };
+            let value = ctx.pstrdup(&value);
+            pg_sys::ExplainPropertyText(label, value, es);
+        }
+
+        // show the remote request reported by the FDW in verbose mode
+        if (*es).verbose
+            && state.agg_plan.is_none()
+            && state.join.is_none()
+            && state.direct_modify.is_none()
+        {
+            for (key, value) in state.explain().report_unwrap() {
+                let label = ctx.pstrdup(&key);
+                let value = ctx.pstrdup(&value);
+                pg_sys::ExplainPropertyText(label, value, es);
+            }
+        }
     }
 }
 
-// extract paramter value and assign it to qual in scan state
+// extract paramter value and assign it to qual in scan state, executor
+// parameters are only evaluated if `exec_params` is true
 unsafe fn assign_paramenter_value<E: Into<ErrorReport>, W: ForeignDataWrapper<E>>(
     node: *mut pg_sys::ForeignScanState,
     state: &mut FdwState<E, W>,
+    exec_params: bool,
 ) {
     // get parameter list in execution state
     let estate = (*node).ss.ps.state;
     let plist_info = (*estate).es_param_list_info;
-    if plist_info.is_null() {
-        return;
+    let plist = if plist_info.is_null() {
+        &[]
+    } else {
+        let params_cnt = (*plist_info).numParams as usize;
+        (*plist_info).params.as_slice(params_cnt)
+    };
+
+    // evaluate executor parameter values
+    let mut exec_values = Vec::new();
+    if exec_params {
+        let econtext = (*node).ss.ps.ps_ExprContext;
+        for expr_state in state.param_exprs.iter() {
+            let mut is_null = true;
+            let mut value = Datum::from(0usize);
+            if let Some(evalfunc) = (**expr_state).evalfunc {
+                value = evalfunc(*expr_state, econtext, &mut is_null);
+            }
+            exec_values.push((value, is_null));
+        }
     }
-    let params_cnt = (*plist_info).numParams as usize;
-    let plist = (*plist_info).params.as_slice(params_cnt);
-    assert!(state.quals.iter().filter(|q| q.param.is_some()).count() <= params_cnt);
 
-    // assign parameter value to qual
-    for qual in &mut state.quals.iter_mut() {
-        if let Some(param) = &qual.param {
-            let p: pg_sys::ParamExternData = plist[param.id - 1];
-            if let Some(value) = Cell::from_polymorphic_datum(p.value, p.isnull, p.ptype) {
-                qual.value = Value::Cell(value);
+    // assign parameter value to qual and its sub-conditions
+    fn assign(
+        quals: &mut [Qual],
+        plist: &[pg_sys::ParamExternData],
+        exec_values: &[(Datum, bool)],
+    ) {
+        for qual in quals.iter_mut() {
+            if let Some(param) = &qual.param {
+                let datum = match param.kind {
+                    ParamKind::Extern => {
+                        if plist.is_empty() {
+                            None
+                        } else {
+                            assert!(param.id <= plist.len());
+                            let p: pg_sys::ParamExternData = plist[param.id - 1];
+                            Some((p.value, p.isnull, p.ptype))
+                        }
+                    }
+                    ParamKind::Exec => exec_values
+                        .get(param.id)
+                        .map(|(value, is_null)| (*value, *is_null, param.type_oid)),
+                };
+
+                // array parameter has array value, e.g. "col = any($1)"
+                if let Some((value, is_null, type_oid)) = datum {
+                    let value = match qual.value {
+                        Value::Cell(_) => unsafe {
+                            Cell::from_polymorphic_datum(value, is_null, type_oid).map(Value::Cell)
+                        },
+                        Value::Array(_) => unsafe {
+                            form_array_from_datum(value, is_null, type_oid).map(Value::Array)
+                        },
+                    };
+                    if let Some(value) = value {
+                        qual.value = value;
+                    }
+                }
             }
+            assign(&mut qual.sub_quals, plist, exec_values);
+        }
+    }
+    assign(&mut state.quals, plist, &exec_values);
+}
+
+// convert an aggregate result cell to datum of its result type, numeric cell
+// is converted if it is in a different numeric type
+unsafe fn aggregate_cell_to_datum(cell: Cell, type_oid: pg_sys::Oid) -> Option<Datum> {
+    let to_numeric = |cell: &Cell| -> Option<AnyNumeric> {
+        match cell {
+            Cell::I8(v) => Some(AnyNumeric::from(*v as i64)),
+            Cell::I16(v) => Some(AnyNumeric::from(*v as i64)),
+            Cell::I32(v) => Some(AnyNumeric::from(*v as i64)),
+            Cell::I64(v) => Some(AnyNumeric::from(*v)),
+            Cell::F32(v) => AnyNumeric::try_from(*v as f64).ok(),
+            Cell::F64(v) => AnyNumeric::try_from(*v).ok(),
+            Cell::Numeric(v) => Some(v.clone()),
+            _ => None,
+        }
+    };
+
+    match (PgOid::from(type_oid), &cell) {
+        (PgOid::BuiltIn(PgBuiltInOids::INT2OID), Cell::I16(_))
+        | (PgOid::BuiltIn(PgBuiltInOids::INT4OID), Cell::I32(_))
+        | (PgOid::BuiltIn(PgBuiltInOids::INT8OID), Cell::I64(_))
+        | (PgOid::BuiltIn(PgBuiltInOids::FLOAT4OID), Cell::F32(_))
+        | (PgOid::BuiltIn(PgBuiltInOids::FLOAT8OID), Cell::F64(_))
+        | (PgOid::BuiltIn(PgBuiltInOids::NUMERICOID), Cell::Numeric(_)) => cell.into_datum(),
+        (PgOid::BuiltIn(PgBuiltInOids::INT2OID), _) => {
+            i16::try_from(to_numeric(&cell)?).ok()?.into_datum()
+        }
+        (PgOid::BuiltIn(PgBuiltInOids::INT4OID), _) => {
+            i32::try_from(to_numeric(&cell)?).ok()?.into_datum()
+        }
+        (PgOid::BuiltIn(PgBuiltInOids::INT8OID), _) => {
+            i64::try_from(to_numeric(&cell)?).ok()?.into_datum()
+        }
+        (PgOid::BuiltIn(PgBuiltInOids::FLOAT4OID), _) => {
+            f32::try_from(to_numeric(&cell)?).ok()?.into_datum()
         }
+        (PgOid::BuiltIn(PgBuiltInOids::FLOAT8OID), _) => {
+            f64::try_from(to_numeric(&cell)?).ok()?.into_datum()
+        }
+        (PgOid::BuiltIn(PgBuiltInOids::NUMERICOID), _) => to_numeric(&cell)?.into_datum(),
+        _ => cell.into_datum(),
+    }
+}
+
+// rebuild the scan state in a parallel worker, because the state pointer in
+// the plan is only valid in the leader process, the parallel scan info is
+// appended after the state pointer by get_foreign_plan
+unsafe fn rebuild_worker_state<E: Into<ErrorReport>, W: ForeignDataWrapper<E>>(
+    node: *mut pg_sys::ForeignScanState,
+    plan: *mut pg_sys::ForeignScan,
+) -> PgBox<FdwState<E, W>> {
+    let foreigntableid = (*(*node).ss.ss_currentRelation).rd_id;
+    let ctx_name = format!(
+        "Wrappers_scan_{}_{}",
+        foreigntableid.as_u32(),
+        (*plan).scan.plan.plan_node_id
+    );
+    let ctx = memctx::refresh_wrappers_memctx(&ctx_name);
+    let mut state = FdwState::<E, W>::new(foreigntableid, ctx);
+
+    let ftable = pg_sys::GetForeignTable(foreigntableid);
+    state.opts = options_to_hashmap((*ftable).options).report_unwrap();
+
+    let fdw_private = PgList::<pg_sys::Node>::from_pg((*plan).fdw_private);
+    let relid = get_int4_const(fdw_private.get_ptr(1).unwrap());
+    let clauses = PgList::<pg_sys::Node>::from_pg(fdw_private.get_ptr(2).unwrap() as _);
+    let attnums = PgList::<pg_sys::Node>::from_pg(fdw_private.get_ptr(3).unwrap() as _);
+
+    // extract quals from the clauses in the same order as in planning phase,
+    // so the executor parameters match the plan's fdw_exprs
+    state.qual_caps = QualCaps {
+        bool_expr_tree: W::bool_expr_tree(),
+        json_path: W::json_path_quals(),
+    };
+    let relids = pg_sys::bms_make_singleton(relid);
+    for clause in clauses.iter_ptr() {
+        state.quals.extend(extract_clause_quals(
+            foreigntableid,
+            relids,
+            clause,
+            state.qual_caps,
+        ));
     }
+    let mut param_exprs = Vec::new();
+    collect_exec_params(&mut state.quals, &mut param_exprs);
+
+    for attnum in attnums.iter_ptr() {
+        let attno = get_int4_const(attnum) as pg_sys::AttrNumber;
+        let attname = pg_sys::get_attname(foreigntableid, attno, false);
+        state.tgts.push(Column {
+            name: std::ffi::CStr::from_ptr(attname)
+                .to_str()
+                .unwrap()
+                .to_owned(),
+            num: attno as usize,
+            type_oid: utils::column_type_oid(pg_sys::get_atttype(foreigntableid, attno)),
+            options: utils::get_column_options(foreigntableid, attno),
+        });
+    }
+
+    let ctx = state.tmp_ctx.value();
+    PgBox::from_pg(PgMemoryContexts::For(ctx).leak_and_drop_on_delete(state))
 }
 
 #[pg_guard]
@@ -300,18 +1845,110 @@ pub(super) extern "C" fn begin_foreign_scan<E: Into<ErrorReport>, W: ForeignData
     unsafe {
         let scan_state = (*node).ss;
         let plan = scan_state.ps.plan as *mut pg_sys::ForeignScan;
-        let mut state = FdwState::<E, W>::deserialize_from_list((*plan).fdw_private as _);
+        let parallel_aware = (*plan).scan.plan.parallel_aware;
+        let mut state = if parallel_aware && pg_sys::ParallelWorkerNumber >= 0 {
+            rebuild_worker_state::<E, W>(node, plan)
+        } else {
+            FdwState::<E, W>::deserialize_from_list((*plan).fdw_private as _)
+        };
         assert!(!state.is_null());
 
-        // assign parameter values to qual
-        assign_paramenter_value(node, &mut state);
+        // partitions of parallel scan are begun in iterate_foreign_scan
+        if parallel_aware {
+            state.parallel = Some(ParallelScan::new());
+        }
+
+        // rescan buffer is only used when the rows don't change between
+        // rescans, that is, a normal scan without executor parameters
+        let rescan_rows = state
+            .opts
+            .get("rescan_buffer")
+            .map(|v| match v.parse::<usize>() {
+                Ok(v) => v,
+                Err(_) => {
+                    pgrx::error!("invalid option rescan_buffer: {}", v);
+                }
+            })
+            .unwrap_or(0);
+        if rescan_rows > 0
+            && state.agg_plan.is_none()
+            && state.join.is_none()
+            && state.parallel.is_none()
+            && (*plan).fdw_exprs.is_null()
+        {
+            state.rescan_buf = Some(RescanBuffer::new(rescan_rows));
+        }
+
+        // result limits are only applied to a normal scan, aggregate and join
+        // scans are not limited
+        if state.agg_plan.is_none() && state.join.is_none() {
+            state.limits = ScanLimits::from_options(&state.opts);
+        }
+
+        // scan result cache is only used by a normal scan on the foreign table
+        state.cache = None;
+        if state.agg_plan.is_none()
+            && state.join.is_none()
+            && state.parallel.is_none()
+            && !(*node).ss.ss_currentRelation.is_null()
+        {
+            let ftable_id = (*(*node).ss.ss_currentRelation).rd_id;
+            state.cache = ScanCache::from_options(ftable_id, &state.opts);
+        }
+
+        // take a snapshot of the pending writes in current transaction, they
+        // are only overlaid onto a normal scan on the foreign table
+        state.write_overlay = None;
+        if state.read_your_writes && !(*node).ss.ss_currentRelation.is_null() {
+            let ftable_id = (*(*node).ss.ss_currentRelation).rd_id;
+            state.write_overlay = WriteOverlay::new(ftable_id, &state.opts["rowid_column"]);
+        }
+
+        // masks are resolved by the role running the scan, not the role
+        // planning it, as the plan can be cached
+        state.masks = state
+            .tgts
+            .iter()
+            .map(column_mask)
+            .collect::<Result<_, _>>()
+            .report_unwrap();
+
+        // initialize executor parameter value expressions
+        let param_exprs = PgList::<pg_sys::ExprState>::from_pg(pg_sys::ExecInitExprList(
+            (*plan).fdw_exprs,
+            node as _,
+        ));
+        state.param_exprs = param_exprs.iter_ptr().collect();
 
-        // begin scan if it is not EXPLAIN statement
-        if eflags & pg_sys::EXEC_FLAG_EXPLAIN_ONLY as c_int <= 0 {
-            state.begin_scan().report_unwrap();
+        // assign parameter values to qual, executor parameters are not
+        // available if it is EXPLAIN statement
+        let explain_only = eflags & pg_sys::EXEC_FLAG_EXPLAIN_ONLY as c_int > 0;
+        assign_paramenter_value(node, &mut state, !explain_only);
 
-            let rel = scan_state.ss_currentRelation;
-            let tup_desc = (*rel).rd_att;
+        // begin scan if it is not EXPLAIN statement, or only report the remote
+        // request in dry run
+        state.dry_run = !explain_only && guc::dry_run();
+        if state.dry_run {
+            state.report_dry_run().report_unwrap();
+        } else if !explain_only {
+            add_server_metric(state.server_oid, ServerMetric::Scans, 1);
+            state.scan_started_at = Some(Instant::now());
+            let tup_desc = if state.agg_plan.is_some() {
+                // aggregate scan has no relation, use the scan tuple instead
+                state.scan_aggregate().report_unwrap();
+                (*scan_state.ss_ScanTupleSlot).tts_tupleDescriptor
+            } else if state.join.is_some() {
+                // join scan has no relation either
+                state.begin_join_scan().report_unwrap();
+                (*scan_state.ss_ScanTupleSlot).tts_tupleDescriptor
+            } else {
+                if state.parallel.is_none() {
+                    let result = state.begin_scan();
+                    state.record_metrics(result).report_unwrap();
+                }
+                // limited scan on the final relation has no relation either
+                (*scan_state.ss_ScanTupleSlot).tts_tupleDescriptor
+            };
             let natts = (*tup_desc).natts as usize;
 
             // initialize scan result lists
@@ -338,8 +1975,77 @@ pub(super) extern "C" fn iterate_foreign_scan<E: Into<ErrorReport>, W: ForeignDa
         let slot = (*node).ss.ss_ScanTupleSlot;
         polyfill::exec_clear_tuple(slot);
 
+        // dry run scan returns no rows
+        if state.dry_run {
+            return slot;
+        }
+
+        // fill the scan tuple with grouping columns and aggregate results in order
+        let st = &mut *state;
+        if let Some(plan) = &st.agg_plan {
+            if let Some(mut row) = st.agg_rows.pop_front() {
+                let group_cnt = plan.group_by.len();
+                if row.cells.len() != group_cnt + plan.aggregates.len() {
+                    report_error(
+                        PgSqlErrorCode::ERRCODE_FDW_INVALID_COLUMN_NUMBER,
+                        "aggregate result number not match",
+                    );
+                    return slot;
+                }
+
+                let tup_desc = PgTupleDesc::from_pg_unchecked((*slot).tts_tupleDescriptor);
+                for (i, cell) in row.cells.drain(..).enumerate() {
+                    match cell {
+                        Some(cell) if i < group_cnt => {
+                            let type_oid = tup_desc.get(i).unwrap().atttypid;
+                            st.values[i] = utils::cell_to_datum(cell, type_oid).unwrap();
+                            st.nulls[i] = false;
+                        }
+                        Some(cell) => {
+                            let type_oid = plan.aggregates[i - group_cnt].type_oid;
+                            match aggregate_cell_to_datum(cell, type_oid) {
+                                Some(datum) => {
+                                    st.values[i] = datum;
+                                    st.nulls[i] = false;
+                                }
+                                None => {
+                                    report_error(
+                                        PgSqlErrorCode::ERRCODE_FDW_INVALID_DATA_TYPE,
+                                        "cannot convert aggregate result to its result type",
+                                    );
+                                    return slot;
+                                }
+                            }
+                        }
+                        None => st.nulls[i] = true,
+                    }
+                }
+
+                (*slot).tts_values = st.values.as_mut_ptr();
+                (*slot).tts_isnull = st.nulls.as_mut_ptr();
+                pg_sys::ExecStoreVirtualTuple(slot);
+            }
+            return slot;
+        }
+
+        // a truncated scan returns no more rows
+        if state.limits.as_ref().is_some_and(|limits| limits.truncated) {
+            return slot;
+        }
+
         state.row.clear();
-        if state.iter_scan().report_unwrap().is_some() {
+        let fetched = if state.parallel.is_some() {
+            state.iter_parallel_scan()
+        } else {
+            state.iter_overlaid_scan()
+        };
+        let fetched = if fetched.is_err() {
+            state.record_metrics(fetched)
+        } else {
+            fetched
+        };
+        if fetched.report_unwrap().is_some() {
+            state.rows_fetched += 1;
             if state.row.cols.len() != state.tgts.len() {
                 report_error(
                     PgSqlErrorCode::ERRCODE_FDW_INVALID_COLUMN_NUMBER,
@@ -348,18 +2054,52 @@ pub(super) extern "C" fn iterate_foreign_scan<E: Into<ErrorReport>, W: ForeignDa
                 return slot;
             }
 
+            let tup_desc = PgTupleDesc::from_pg_unchecked((*slot).tts_tupleDescriptor);
             for i in 0..state.row.cells.len() {
                 let att_idx = state.tgts[i].num - 1;
-                let cell = state.row.cells.get_unchecked_mut(i);
-                match cell.take() {
+                let cell = state.row.cells.get_unchecked_mut(i).take();
+                let tgt = &state.tgts[i];
+                let cell = cell.map(|cell| {
+                    if tgt.coerce_to_text() {
+                        utils::coerce_cell(cell, tgt.type_oid)
+                    } else {
+                        cell
+                    }
+                });
+                let cell = match state.masks.get(i).copied().flatten() {
+                    Some(kind) => cell.and_then(|cell| mask_cell(cell, kind)),
+                    None => cell,
+                };
+                match cell {
                     Some(cell) => {
-                        state.values[att_idx] = cell.into_datum().unwrap();
+                        let type_oid = tup_desc.get(att_idx).unwrap().atttypid;
+                        state.values[att_idx] = utils::cell_to_datum(cell, type_oid).unwrap();
                         state.nulls[att_idx] = false;
                     }
                     None => state.nulls[att_idx] = true,
                 }
             }
 
+            if state.limits.is_some() {
+                let bytes = state
+                    .tgts
+                    .iter()
+                    .map(|tgt| tgt.num - 1)
+                    .filter(|att_idx| !state.nulls[*att_idx])
+                    .map(|att_idx| {
+                        datum_size(state.values[att_idx], tup_desc.get(att_idx).unwrap())
+                    })
+                    .sum::<usize>();
+                let st = &mut *state;
+                if st
+                    .limits
+                    .as_mut()
+                    .is_some_and(|limits| !limits.add_row(bytes as u64))
+                {
+                    return slot;
+                }
+            }
+
             (*slot).tts_values = state.values.as_mut_ptr();
             (*slot).tts_isnull = state.nulls.as_mut_ptr();
             pg_sys::ExecStoreVirtualTuple(slot);
@@ -378,7 +2118,38 @@ pub(super) extern "C" fn re_scan_foreign_scan<E: Into<ErrorReport>, W: ForeignDa
         let fdw_state = (*node).fdw_state as *mut FdwState<E, W>;
         if !fdw_state.is_null() {
             let mut state = PgBox::<FdwState<E, W>>::from_pg(fdw_state);
-            state.re_scan().report_unwrap();
+            if state.dry_run {
+                return;
+            }
+            if let Some(overlay) = &mut state.write_overlay {
+                overlay.reset();
+            }
+            if let Some(limits) = &mut state.limits {
+                limits.reset();
+            }
+            if state.agg_plan.is_some() {
+                assign_paramenter_value(node, &mut state, true);
+                state.scan_aggregate().report_unwrap();
+            } else if state.join.is_some() {
+                state.end_scan().report_unwrap();
+                state.begin_join_scan().report_unwrap();
+            } else if state.parallel.is_some() {
+                // restart from the first partition, the shared partition
+                // counter is reset by re_initialize_dsm_foreign_scan
+                assign_paramenter_value(node, &mut state, true);
+                state.end_partition_scan().report_unwrap();
+                if let Some(parallel) = &mut state.parallel {
+                    parallel.next_partition = 0;
+                }
+            } else if state.param_exprs.is_empty() {
+                state.re_scan().report_unwrap();
+            } else {
+                // executor parameter values may change, e.g. in a nested loop
+                // join, so restart the scan with new values
+                assign_paramenter_value(node, &mut state, true);
+                state.end_scan().report_unwrap();
+                state.begin_scan().report_unwrap();
+            }
         }
     }
 }
@@ -394,7 +2165,277 @@ pub(super) extern "C" fn end_foreign_scan<E: Into<ErrorReport>, W: ForeignDataWr
             return;
         }
 
+        // aggregate scan doesn't begin a normal scan, and parallel scan only
+        // ends the partition being scanned
         let mut state = PgBox::<FdwState<E, W>>::from_pg(fdw_state);
-        state.end_scan().report_unwrap();
+        if state.dry_run {
+            return;
+        }
+        let result = if state.parallel.is_some() {
+            state.end_partition_scan()
+        } else if state.agg_plan.is_none() {
+            state.end_scan()
+        } else {
+            Ok(())
+        };
+        state.audit_scan(&result);
+        state.record_metrics(result).report_unwrap();
+    }
+}
+
+#[pg_guard]
+pub(super) extern "C" fn is_foreign_scan_parallel_safe<
+    E: Into<ErrorReport>,
+    W: ForeignDataWrapper<E>,
+>(
+    _root: *mut pg_sys::PlannerInfo,
+    _rel: *mut pg_sys::RelOptInfo,
+    _rte: *mut pg_sys::RangeTblEntry,
+) -> bool {
+    debug2!("---> is_foreign_scan_parallel_safe");
+    W::parallel_safe()
+}
+
+#[pg_guard]
+pub(super) extern "C" fn estimate_dsm_foreign_scan<
+    E: Into<ErrorReport>,
+    W: ForeignDataWrapper<E>,
+>(
+    _node: *mut pg_sys::ForeignScanState,
+    _pcxt: *mut pg_sys::ParallelContext,
+) -> pg_sys::Size {
+    debug2!("---> estimate_dsm_foreign_scan");
+    std::mem::size_of::<ParallelShared>()
+}
+
+#[pg_guard]
+pub(super) extern "C" fn initialize_dsm_foreign_scan<
+    E: Into<ErrorReport>,
+    W: ForeignDataWrapper<E>,
+>(
+    node: *mut pg_sys::ForeignScanState,
+    _pcxt: *mut pg_sys::ParallelContext,
+    coordinate: *mut std::ffi::c_void,
+) {
+    debug2!("---> initialize_dsm_foreign_scan");
+    unsafe {
+        // the partitions are split once by the leader and shared with workers
+        let mut state = PgBox::<FdwState<E, W>>::from_pg((*node).fdw_state as _);
+        let partitions = state.scan_partitions().report_unwrap();
+        let shared = coordinate as *mut ParallelShared;
+        ptr::write(
+            shared,
+            ParallelShared {
+                partitions,
+                next_partition: AtomicUsize::new(0),
+            },
+        );
+        if let Some(parallel) = &mut state.parallel {
+            parallel.shared = shared;
+        }
+    }
+}
+
+#[pg_guard]
+pub(super) extern "C" fn re_initialize_dsm_foreign_scan<
+    E: Into<ErrorReport>,
+    W: ForeignDataWrapper<E>,
+>(
+    _node: *mut pg_sys::ForeignScanState,
+    _pcxt: *mut pg_sys::ParallelContext,
+    coordinate: *mut std::ffi::c_void,
+) {
+    debug2!("---> re_initialize_dsm_foreign_scan");
+    unsafe {
+        let shared = coordinate as *mut ParallelShared;
+        (*shared).next_partition.store(0, Ordering::SeqCst);
+    }
+}
+
+#[pg_guard]
+pub(super) extern "C" fn initialize_worker_foreign_scan<
+    E: Into<ErrorReport>,
+    W: ForeignDataWrapper<E>,
+>(
+    node: *mut pg_sys::ForeignScanState,
+    _toc: *mut pg_sys::shm_toc,
+    coordinate: *mut std::ffi::c_void,
+) {
+    debug2!("---> initialize_worker_foreign_scan");
+    unsafe {
+        let mut state = PgBox::<FdwState<E, W>>::from_pg((*node).fdw_state as _);
+        if let Some(parallel) = &mut state.parallel {
+            parallel.shared = coordinate as *mut ParallelShared;
+        }
+    }
+}
+
+// plan to execute the whole UPDATE or DELETE statement remotely, it is only
+// possible when the statement has no RETURNING clause, its subplan is a
+// foreign scan on the target table whose clauses are all extracted to quals,
+// the new values are constants and the FDW accepts it
+#[cfg(not(feature = "pg13"))]
+#[pg_guard]
+pub(super) extern "C" fn plan_direct_modify<E: Into<ErrorReport>, W: ForeignDataWrapper<E>>(
+    root: *mut pg_sys::PlannerInfo,
+    plan: *mut pg_sys::ModifyTable,
+    result_relation: pg_sys::Index,
+    _subplan_index: c_int,
+) -> bool {
+    debug2!("---> plan_direct_modify");
+    unsafe {
+        // inherited or partitioned target table is not supported
+        let operation = (*plan).operation;
+        if (operation != pg_sys::CmdType::CMD_UPDATE && operation != pg_sys::CmdType::CMD_DELETE)
+            || !(*plan).returningLists.is_null()
+            || (*(*root).parse).resultRelation as pg_sys::Index != result_relation
+        {
+            return false;
+        }
+
+        let subplan = (*plan).plan.lefttree;
+        if subplan.is_null() || !pgrx::is_a(subplan as _, pg_sys::NodeTag::T_ForeignScan) {
+            return false;
+        }
+        let fscan = subplan as *mut pg_sys::ForeignScan;
+        if (*fscan).scan.scanrelid != result_relation || (*fscan).scan.plan.parallel_aware {
+            return false;
+        }
+
+        let mut state = FdwState::<E, W>::deserialize_from_list((*fscan).fdw_private as _);
+        if state.is_null()
+            || !state.all_quals_extracted
+            || state.agg_plan.is_some()
+            || state.join.is_some()
+        {
+            return false;
+        }
+
+        // the new values are the first entries in the processed target list
+        let new_row = if operation == pg_sys::CmdType::CMD_UPDATE {
+            let rte = pg_sys::planner_rt_fetch(result_relation, root);
+            let tgts = PgList::<pg_sys::TargetEntry>::from_pg((*root).processed_tlist);
+            let colnos = PgList::<pg_sys::Node>::from_pg((*root).update_colnos);
+            let mut row = Row::new();
+            for (tgt, attnum) in tgts.iter_ptr().zip(colnos.iter_int()) {
+                let expr = (*tgt).expr as *mut pg_sys::Node;
+                if !pgrx::is_a(expr, pg_sys::NodeTag::T_Const) {
+                    return false;
+                }
+                let value = expr as *mut pg_sys::Const;
+                let cell = Cell::from_polymorphic_datum(
+                    (*value).constvalue,
+                    (*value).constisnull,
+                    (*value).consttype,
+                );
+                if cell.is_none() && !(*value).constisnull {
+                    return false;
+                }
+                let attname = pg_sys::get_attname((*rte).relid, attnum as _, false);
+                row.push(std::ffi::CStr::from_ptr(attname).to_str().unwrap(), cell);
+            }
+            Some(row)
+        } else {
+            None
+        };
+
+        if !state.plan_direct_modify(new_row.as_ref()).report_unwrap() {
+            return false;
+        }
+        state.direct_modify = Some(DirectModify {
+            new_row,
+            executed: false,
+        });
+
+        // all the quals are applied remotely
+        (*fscan).operation = operation;
+        (*fscan).resultRelation = result_relation;
+        (*fscan).scan.plan.qual = ptr::null_mut();
+
+        true
+    }
+}
+
+#[cfg(not(feature = "pg13"))]
+#[pg_guard]
+pub(super) extern "C" fn begin_direct_modify<E: Into<ErrorReport>, W: ForeignDataWrapper<E>>(
+    node: *mut pg_sys::ForeignScanState,
+    eflags: c_int,
+) {
+    debug2!("---> begin_direct_modify");
+    unsafe {
+        let plan = (*node).ss.ps.plan as *mut pg_sys::ForeignScan;
+        let mut state = FdwState::<E, W>::deserialize_from_list((*plan).fdw_private as _);
+        assert!(!state.is_null());
+
+        // initialize executor parameter value expressions
+        let param_exprs = PgList::<pg_sys::ExprState>::from_pg(pg_sys::ExecInitExprList(
+            (*plan).fdw_exprs,
+            node as _,
+        ));
+        state.param_exprs = param_exprs.iter_ptr().collect();
+
+        // the statement is executed in the first iteration
+        let explain_only = eflags & pg_sys::EXEC_FLAG_EXPLAIN_ONLY as c_int > 0;
+        assign_paramenter_value(node, &mut state, !explain_only);
+
+        (*node).fdw_state = state.into_pg() as _;
+    }
+}
+
+#[cfg(not(feature = "pg13"))]
+#[pg_guard]
+pub(super) extern "C" fn iterate_direct_modify<E: Into<ErrorReport>, W: ForeignDataWrapper<E>>(
+    node: *mut pg_sys::ForeignScanState,
+) -> *mut pg_sys::TupleTableSlot {
+    debug2!("---> iterate_direct_modify");
+    unsafe {
+        let mut state = PgBox::<FdwState<E, W>>::from_pg((*node).fdw_state as _);
+
+        let executed = state
+            .direct_modify
+            .as_ref()
+            .map(|modify| modify.executed)
+            .unwrap_or(true);
+        if !executed && guc::dry_run() {
+            // only report the modification in dry run
+            state.report_dry_run().report_unwrap();
+            if let Some(modify) = &mut state.direct_modify {
+                modify.executed = true;
+            }
+        } else if !executed {
+            let affected = state.exec_direct_modify().report_unwrap();
+            if let Some(modify) = &mut state.direct_modify {
+                modify.executed = true;
+            }
+
+            // report the affected rows as command status and in EXPLAIN ANALYZE
+            (*(*node).ss.ps.state).es_processed += affected;
+            let instr = (*node).ss.ps.instrument;
+            if !instr.is_null() {
+                (*instr).tuplecount += affected as f64;
+            }
+
+            // cached scan results are stale after the modification
+            if !(*node).ss.ss_currentRelation.is_null() {
+                crate::cache::invalidate_cache((*(*node).ss.ss_currentRelation).rd_id);
+            }
+        }
+
+        // no RETURNING, so no rows are returned
+        let slot = (*node).ss.ss_ScanTupleSlot;
+        polyfill::exec_clear_tuple(slot);
+        slot
     }
 }
+
+#[cfg(not(feature = "pg13"))]
+#[pg_guard]
+pub(super) extern "C" fn end_direct_modify<E: Into<ErrorReport>, W: ForeignDataWrapper<E>>(
+    _node: *mut pg_sys::ForeignScanState,
+) {
+    debug2!("---> end_direct_modify");
+
+    // nothing to clean up, the scan state is dropped when its memory context
+    // is reset
+}
@@ -0,0 +1,299 @@
+//! Scan result buffer which spills to temporary files
+//!
+//! Many FDWs fetch the whole scan result before returning rows, for example,
+//! from a paginated API. Keeping all the rows in a `Vec<Row>` can run the
+//! backend out of memory for a huge result set. [`ScanResultBuffer`] keeps the
+//! rows in a Postgres tuplestore instead, which spills to temporary files when
+//! the rows exceed the memory threshold set by the foreign table option
+//! `spill_threshold_kb`, default is `work_mem`. For example,
+//!
+//! ```rust,no_run
+//! # use supabase_wrappers::prelude::*;
+//! # use std::collections::HashMap;
+//! # fn scan(rows: Vec<Row>, columns: &[Column], options: &HashMap<String, String>) -> Result<(), OptionsError> {
+//! // in begin_scan()
+//! let mut scan_result = ScanResultBuffer::new(columns, options)?;
+//! for src in rows {
+//!     scan_result.push(src);
+//! }
+//!
+//! // in iter_scan()
+//! let mut row = Row::new();
+//! if let Some(src) = scan_result.next_row() {
+//!     row.replace_with(src);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The buffer lives within a transaction, its content is discarded at the end
+//! of the transaction, or the subtransaction it is filled in.
+
+use crate::interface::{Cell, Column, Row};
+use crate::options::{parse_option, OptionsError};
+use crate::utils::cell_to_datum;
+use pgrx::memcxt::PgMemoryContexts;
+use pgrx::pg_sys::AsPgCStr;
+use pgrx::prelude::*;
+use std::cell::{Cell as StdCell, RefCell};
+use std::collections::HashMap;
+use std::os::raw::c_void;
+use std::ptr;
+
+// a tuplestore and the resources to read and write it, all of them are
+// allocated in its own memory context under the top transaction context
+struct Store {
+    ctx: pg_sys::MemoryContext,
+    row_ctx: pg_sys::MemoryContext,
+    tupdesc: pg_sys::TupleDesc,
+    slot: *mut pg_sys::TupleTableSlot,
+    state: *mut pg_sys::Tuplestorestate,
+    subxact_id: pg_sys::SubTransactionId,
+}
+
+impl Store {
+    unsafe fn new(columns: &[Column], max_kbytes: i32) -> Self {
+        let ctx = pg_sys::AllocSetContextCreateExtended(
+            pg_sys::TopTransactionContext,
+            c"WrappersScanResultBuffer".as_ptr(),
+            pg_sys::ALLOCSET_DEFAULT_MINSIZE as usize,
+            pg_sys::ALLOCSET_DEFAULT_INITSIZE as usize,
+            pg_sys::ALLOCSET_DEFAULT_MAXSIZE as usize,
+        );
+        PgMemoryContexts::For(ctx).switch_to(|_| {
+            let row_ctx = pg_sys::AllocSetContextCreateExtended(
+                ctx,
+                c"WrappersScanResultBufferRow".as_ptr(),
+                pg_sys::ALLOCSET_DEFAULT_MINSIZE as usize,
+                pg_sys::ALLOCSET_DEFAULT_INITSIZE as usize,
+                pg_sys::ALLOCSET_DEFAULT_MAXSIZE as usize,
+            );
+            let tupdesc = pg_sys::CreateTemplateTupleDesc(columns.len() as _);
+            for (i, col) in columns.iter().enumerate() {
+                pg_sys::TupleDescInitEntry(
+                    tupdesc,
+                    (i + 1) as _,
+                    col.name.as_str().as_pg_cstr(),
+                    col.type_oid,
+                    -1,
+                    0,
+                );
+            }
+            let slot = pg_sys::MakeSingleTupleTableSlot(tupdesc, &pg_sys::TTSOpsMinimalTuple);
+            let state = pg_sys::tuplestore_begin_heap(false, false, max_kbytes);
+            Self {
+                ctx,
+                row_ctx,
+                tupdesc,
+                slot,
+                state,
+                subxact_id: pg_sys::GetCurrentSubTransactionId(),
+            }
+        })
+    }
+
+    // release the temporary files and memory
+    unsafe fn end(self) {
+        pg_sys::tuplestore_end(self.state);
+        pg_sys::MemoryContextDelete(self.ctx);
+    }
+}
+
+thread_local! {
+    static CALLBACK_REGISTERED: StdCell<bool> = const { StdCell::new(false) };
+    static NEXT_STORE_ID: StdCell<u64> = const { StdCell::new(1) };
+    static STORES: RefCell<HashMap<u64, Store>> = RefCell::new(HashMap::new());
+}
+
+// the stores must be ended before the transaction's resource owner closes
+// their temporary files, which is after the transaction callbacks are called
+#[pg_guard]
+extern "C" fn xact_callback(event: pg_sys::XactEvent::Type, _arg: *mut c_void) {
+    match event {
+        pg_sys::XactEvent::XACT_EVENT_COMMIT
+        | pg_sys::XactEvent::XACT_EVENT_PARALLEL_COMMIT
+        | pg_sys::XactEvent::XACT_EVENT_ABORT
+        | pg_sys::XactEvent::XACT_EVENT_PARALLEL_ABORT
+        | pg_sys::XactEvent::XACT_EVENT_PREPARE => {
+            let stores: Vec<Store> =
+                STORES.with_borrow_mut(|stores| stores.drain().map(|(_, s)| s).collect());
+            stores.into_iter().for_each(|s| unsafe { s.end() });
+        }
+        _ => {}
+    }
+}
+
+#[pg_guard]
+extern "C" fn subxact_callback(
+    event: pg_sys::SubXactEvent::Type,
+    my_subid: pg_sys::SubTransactionId,
+    parent_subid: pg_sys::SubTransactionId,
+    _arg: *mut c_void,
+) {
+    match event {
+        pg_sys::SubXactEvent::SUBXACT_EVENT_ABORT_SUB => {
+            let stores: Vec<Store> = STORES.with_borrow_mut(|stores| {
+                let ids: Vec<u64> = stores
+                    .iter()
+                    .filter(|(_, s)| s.subxact_id == my_subid)
+                    .map(|(id, _)| *id)
+                    .collect();
+                ids.iter().filter_map(|id| stores.remove(id)).collect()
+            });
+            stores.into_iter().for_each(|s| unsafe { s.end() });
+        }
+        pg_sys::SubXactEvent::SUBXACT_EVENT_COMMIT_SUB => {
+            // resources of the committed subtransaction are handed over to its parent
+            STORES.with_borrow_mut(|stores| {
+                stores
+                    .values_mut()
+                    .filter(|s| s.subxact_id == my_subid)
+                    .for_each(|s| s.subxact_id = parent_subid)
+            });
+        }
+        _ => {}
+    }
+}
+
+fn register_callbacks() {
+    if !CALLBACK_REGISTERED.get() {
+        unsafe {
+            pg_sys::RegisterXactCallback(Some(xact_callback), ptr::null_mut());
+            pg_sys::RegisterSubXactCallback(Some(subxact_callback), ptr::null_mut());
+        }
+        CALLBACK_REGISTERED.set(true);
+    }
+}
+
+/// Buffer of scan result rows, which spills to temporary files when the rows
+/// exceed the memory threshold
+///
+/// The rows are stored in the order of the columns it is created with, and
+/// are returned in the same order as they are pushed.
+pub struct ScanResultBuffer {
+    columns: Vec<Column>,
+    max_kbytes: i32,
+    store_id: Option<u64>,
+    len: usize,
+}
+
+impl ScanResultBuffer {
+    /// Create an empty buffer for the target columns, the memory threshold is
+    /// set by the foreign table option `spill_threshold_kb`
+    pub fn new(
+        columns: &[Column],
+        options: &HashMap<String, String>,
+    ) -> Result<Self, OptionsError> {
+        let max_kbytes = parse_option("spill_threshold_kb", options, |kb: &i32| *kb > 0)?
+            .unwrap_or(unsafe { pg_sys::work_mem });
+        Ok(Self {
+            columns: columns.to_vec(),
+            max_kbytes,
+            store_id: None,
+            len: 0,
+        })
+    }
+
+    /// Number of rows pushed into the buffer
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn with_store<R>(&self, f: impl FnOnce(&Store) -> R) -> Option<R> {
+        let id = self.store_id?;
+        STORES.with_borrow(|stores| stores.get(&id).map(f))
+    }
+
+    /// Append a row to the end of the buffer
+    ///
+    /// The cells are matched to the columns by column name, missing cells are
+    /// stored as `null`.
+    pub fn push(&mut self, row: Row) {
+        if self.with_store(|_| ()).is_none() {
+            // the store is discarded by transaction end, start a new one
+            register_callbacks();
+            let store = unsafe { Store::new(&self.columns, self.max_kbytes) };
+            let id = NEXT_STORE_ID.get();
+            NEXT_STORE_ID.set(id + 1);
+            STORES.with_borrow_mut(|stores| stores.insert(id, store));
+            self.store_id = Some(id);
+            self.len = 0;
+        }
+
+        let Row { cols, mut cells } = row;
+        let columns = &self.columns;
+        self.with_store(|store| unsafe {
+            let mut row_ctx = PgMemoryContexts::For(store.row_ctx);
+            row_ctx.switch_to(|_| {
+                let mut values = Vec::with_capacity(columns.len());
+                let mut nulls = Vec::with_capacity(columns.len());
+                for col in columns {
+                    let datum = cols
+                        .iter()
+                        .position(|c| c == &col.name)
+                        .and_then(|i| cells[i].take())
+                        .and_then(|cell| cell_to_datum(cell, col.type_oid));
+                    values.push(datum.unwrap_or(pg_sys::Datum::from(0)));
+                    nulls.push(datum.is_none());
+                }
+                pg_sys::tuplestore_putvalues(
+                    store.state,
+                    store.tupdesc,
+                    values.as_mut_ptr(),
+                    nulls.as_mut_ptr(),
+                );
+            });
+            row_ctx.reset();
+        });
+        self.len += 1;
+    }
+
+    /// Take the next row from the buffer, `None` is returned if all the rows
+    /// have been taken
+    pub fn next_row(&mut self) -> Option<Row> {
+        let columns = &self.columns;
+        self.with_store(|store| unsafe {
+            if !pg_sys::tuplestore_gettupleslot(store.state, true, false, store.slot) {
+                return None;
+            }
+            let natts = columns.len();
+            if natts > 0 {
+                pg_sys::slot_getsomeattrs_int(store.slot, natts as _);
+            }
+            let values = std::slice::from_raw_parts((*store.slot).tts_values, natts);
+            let nulls = std::slice::from_raw_parts((*store.slot).tts_isnull, natts);
+            let mut row = Row::new();
+            for (i, col) in columns.iter().enumerate() {
+                let cell = Cell::from_polymorphic_datum(values[i], nulls[i], col.type_oid);
+                row.push(&col.name, cell);
+            }
+            Some(row)
+        })
+        .flatten()
+    }
+
+    /// Rewind the buffer to read the rows from the beginning again
+    pub fn rewind(&mut self) {
+        self.with_store(|store| unsafe { pg_sys::tuplestore_rescan(store.state) });
+    }
+
+    /// Remove all the rows and release the temporary files
+    pub fn clear(&mut self) {
+        if let Some(id) = self.store_id.take() {
+            if let Some(store) = STORES.with_borrow_mut(|stores| stores.remove(&id)) {
+                unsafe { store.end() };
+            }
+        }
+        self.len = 0;
+    }
+}
+
+impl Drop for ScanResultBuffer {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
@@ -0,0 +1,135 @@
+//! Secret providers
+//!
+//! Credentials like `api_key` can be kept out of the foreign server definition
+//! and referenced by option `api_key_id` instead. Where the referenced secret
+//! is read from is decided by the foreign server option `secret_provider`:
+//!
+//! - `vault` (default): the secret id or name in [Vault](https://github.com/supabase/vault)
+//! - `env`: name of an environment variable of the Postgres server process
+//! - `file`: name of a file in the directory set by `wrappers.secret_dir`, the
+//!   trailing newline of the file content is removed
+//!
+//! Other secret stores can be supported by implementing [`SecretProvider`].
+//! For example,
+//!
+//! ```rust,no_run
+//! # use supabase_wrappers::prelude::*;
+//! # use std::collections::HashMap;
+//! # let options: HashMap<String, String> = HashMap::new();
+//! if let Some(key_id) = options.get("api_key_id") {
+//!     // read from Vault, environment variable or file, by option `secret_provider`
+//!     let api_key = get_secret(&options, key_id);
+//! }
+//! ```
+
+use crate::guc;
+use crate::options::OptionsError;
+use crate::redact::register_secret;
+use crate::utils::{get_vault_secret, get_vault_secret_by_name, report_error, ReportableError};
+use pgrx::PgSqlErrorCode;
+use std::collections::HashMap;
+use std::path::{Component, Path};
+
+/// A store of the secrets referenced by the foreign server options
+pub trait SecretProvider {
+    /// Get the secret by its id, or `None` if it is not found
+    fn get_secret(&self, id: &str) -> Option<String>;
+
+    /// Get the secret by its name, it is same as by id if the provider doesn't
+    /// distinguish them
+    fn get_secret_by_name(&self, name: &str) -> Option<String> {
+        self.get_secret(name)
+    }
+}
+
+/// Secrets in Vault, referenced by secret id or name
+pub struct VaultSecretProvider;
+
+impl SecretProvider for VaultSecretProvider {
+    fn get_secret(&self, id: &str) -> Option<String> {
+        get_vault_secret(id)
+    }
+
+    fn get_secret_by_name(&self, name: &str) -> Option<String> {
+        get_vault_secret_by_name(name)
+    }
+}
+
+/// Secrets in environment variables of the Postgres server process
+pub struct EnvSecretProvider;
+
+impl SecretProvider for EnvSecretProvider {
+    fn get_secret(&self, id: &str) -> Option<String> {
+        std::env::var(id)
+            .ok()
+            .inspect(|secret| register_secret(secret))
+    }
+}
+
+/// Secrets in files under the directory set by `wrappers.secret_dir`, like
+/// the secrets mounted by Kubernetes or Docker
+pub struct FileSecretProvider;
+
+impl SecretProvider for FileSecretProvider {
+    fn get_secret(&self, id: &str) -> Option<String> {
+        let Some(dir) = guc::secret_dir() else {
+            report_error(
+                PgSqlErrorCode::ERRCODE_FDW_ERROR,
+                "wrappers.secret_dir must be set to use the file secret provider",
+            );
+            return None;
+        };
+
+        // only plain file names in the directory are allowed, so the server
+        // options cannot read arbitrary files on the server
+        let path = Path::new(id);
+        let mut components = path.components();
+        if !matches!(
+            (components.next(), components.next()),
+            (Some(Component::Normal(_)), None)
+        ) {
+            report_error(
+                PgSqlErrorCode::ERRCODE_FDW_INVALID_ATTRIBUTE_VALUE,
+                &format!("invalid secret file name \"{}\"", id),
+            );
+            return None;
+        }
+
+        std::fs::read_to_string(Path::new(&dir).join(path))
+            .ok()
+            .map(|content| content.trim_end_matches(['\r', '\n']).to_owned())
+            .inspect(|secret| register_secret(secret))
+    }
+}
+
+/// Create the secret provider by the option `secret_provider`, it is Vault if
+/// the option is not specified
+pub fn secret_provider(
+    options: &HashMap<String, String>,
+) -> Result<Box<dyn SecretProvider>, OptionsError> {
+    match options.get("secret_provider").map(|s| s.as_str()) {
+        None | Some("vault") => Ok(Box::new(VaultSecretProvider)),
+        Some("env") => Ok(Box::new(EnvSecretProvider)),
+        Some("file") => Ok(Box::new(FileSecretProvider)),
+        Some(other) => Err(OptionsError::OptionValueIsInvalid(
+            "secret_provider".to_string(),
+            other.to_string(),
+        )),
+    }
+}
+
+/// Get secret by id from the provider set by the option `secret_provider`
+///
+/// Report error and stop current transaction if the option is invalid.
+pub fn get_secret(options: &HashMap<String, String>, id: &str) -> Option<String> {
+    secret_provider(options).report_unwrap().get_secret(id)
+}
+
+/// Get secret by name from the provider set by the option `secret_provider`
+///
+/// Report error and stop current transaction if the option is invalid.
+pub fn get_secret_by_name(options: &HashMap<String, String>, name: &str) -> Option<String> {
+    secret_provider(options)
+        .report_unwrap()
+        .get_secret_by_name(name)
+}
@@ -0,0 +1,145 @@
+//! Shared memory of Wrappers
+//!
+//! Some states, like the rate limit of a foreign server, should be shared by
+//! all backends. They are kept in shared memory, which can only be allocated
+//! when the extension is loaded by `shared_preload_libraries`, so the extension
+//! should call [`init_shared_memory`] in its `_PG_init()`. For example,
+//!
+//! ```rust,no_run
+//! use pgrx::prelude::*;
+//! use supabase_wrappers::prelude::init_shared_memory;
+//!
+//! #[pg_guard]
+//! pub extern "C" fn _PG_init() {
+//!     init_shared_memory();
+//! }
+//! ```
+//!
+//! If the extension is not preloaded, each backend falls back to keep its own
+//! states in local memory.
+
+use crate::circuit_breaker::CIRCUITS;
+use crate::metrics::SERVER_METRICS;
+use crate::rate_limit::RATE_LIMIT_BUCKETS;
+use pgrx::prelude::*;
+use pgrx::{pg_shmem_init, PGRXSharedMemory, PgLwLock, PgSharedMemoryInitialization};
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::LocalKey;
+
+// max number of foreign servers which can have shared states
+const MAX_SERVER_SLOTS: usize = 128;
+
+// set in postmaster and inherited by the forked backends
+static SHMEM_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Allocate shared memory for Wrappers, it must be called in `_PG_init()`
+///
+/// This function does nothing if the extension is not being loaded by
+/// `shared_preload_libraries`.
+pub fn init_shared_memory() {
+    if unsafe { !pg_sys::process_shared_preload_libraries_in_progress } {
+        return;
+    }
+    pg_shmem_init!(RATE_LIMIT_BUCKETS);
+    pg_shmem_init!(CIRCUITS);
+    pg_shmem_init!(SERVER_METRICS);
+    SHMEM_ENABLED.store(true, Ordering::Relaxed);
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct ServerSlot<T> {
+    db_oid: pg_sys::Oid,
+    server_oid: pg_sys::Oid,
+    used_at: pg_sys::TimestampTz,
+    state: T,
+}
+
+/// A fixed-size table of per foreign server states
+///
+/// Foreign server oid is only unique in a database, so the states are keyed by
+/// both database and foreign server. When the table is full, the least
+/// recently used state is evicted.
+#[derive(Clone, Copy)]
+pub(crate) struct ServerStates<T>([ServerSlot<T>; MAX_SERVER_SLOTS]);
+
+impl<T: Copy + Default> Default for ServerStates<T> {
+    fn default() -> Self {
+        Self([ServerSlot::default(); MAX_SERVER_SLOTS])
+    }
+}
+
+unsafe impl<T: Copy + Default> PGRXSharedMemory for ServerStates<T> {}
+
+impl<T: Copy + Default> ServerStates<T> {
+    // get state of a foreign server in current database, or create it by
+    // `init` if it doesn't exist
+    pub(crate) fn get_or_insert_with(
+        &mut self,
+        server_oid: pg_sys::Oid,
+        init: impl FnOnce() -> T,
+    ) -> &mut T {
+        let db_oid = unsafe { pg_sys::MyDatabaseId };
+        let now = unsafe { pg_sys::GetCurrentTimestamp() };
+        let idx = match self
+            .0
+            .iter()
+            .position(|s| s.db_oid == db_oid && s.server_oid == server_oid)
+        {
+            Some(idx) => idx,
+            None => {
+                // use a free slot, or evict the least recently used one
+                let idx = self
+                    .0
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, s)| (s.server_oid != pg_sys::InvalidOid, s.used_at))
+                    .map(|(idx, _)| idx)
+                    .unwrap_or_default();
+                self.0[idx] = ServerSlot {
+                    db_oid,
+                    server_oid,
+                    used_at: now,
+                    state: init(),
+                };
+                idx
+            }
+        };
+        let slot = &mut self.0[idx];
+        slot.used_at = now;
+        &mut slot.state
+    }
+
+    // get states of all foreign servers in current database
+    pub(crate) fn current_database(&self) -> Vec<(pg_sys::Oid, T)> {
+        let db_oid = unsafe { pg_sys::MyDatabaseId };
+        self.0
+            .iter()
+            .filter(|s| s.db_oid == db_oid && s.server_oid != pg_sys::InvalidOid)
+            .map(|s| (s.server_oid, s.state))
+            .collect()
+    }
+
+    // remove state of a foreign server in current database
+    pub(crate) fn remove(&mut self, server_oid: pg_sys::Oid) {
+        let db_oid = unsafe { pg_sys::MyDatabaseId };
+        self.0
+            .iter_mut()
+            .filter(|s| s.db_oid == db_oid && s.server_oid == server_oid)
+            .for_each(|s| *s = ServerSlot::default());
+    }
+}
+
+// access the states in shared memory if it is enabled, otherwise access the
+// states in backend local memory
+pub(crate) fn with_server_states<T: Copy + Default, R>(
+    shared: &'static PgLwLock<ServerStates<T>>,
+    local: &'static LocalKey<RefCell<ServerStates<T>>>,
+    f: impl FnOnce(&mut ServerStates<T>) -> R,
+) -> R {
+    if SHMEM_ENABLED.load(Ordering::Relaxed) {
+        f(&mut shared.exclusive())
+    } else {
+        local.with(|states| f(&mut states.borrow_mut()))
+    }
+}
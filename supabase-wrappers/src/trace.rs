@@ -0,0 +1,72 @@
+//! Debug tracing of the remote requests
+//!
+//! Setting `wrappers.debug_level` traces the remote requests sent by the FDWs
+//! and their responses at `DEBUG1`, so a misbehaving FDW can be debugged
+//! without recompiling it. The levels are:
+//!
+//! - `0`: no tracing, the default
+//! - `1`: trace each request and the response status or error
+//! - `2`: also trace the request and response bodies, truncated to
+//!   [`TRACE_BODY_LIMIT`] characters
+//!
+//! The secrets in the traces are redacted, see [`redact`](crate::redact). The
+//! requests can be sent from the async runtime worker threads, where Postgres
+//! logging cannot be used, so the traces are queued by [`trace`] and written
+//! to the log by [`flush_traces`] in Postgres thread. For example,
+//!
+//! ```rust,no_run
+//! # use supabase_wrappers::prelude::*;
+//! # fn send(url: &str) -> String { String::new() }
+//! let url = "https://api.example.com/v1/users";
+//! if trace_enabled(1) {
+//!     trace(format!("> GET {}", url));
+//! }
+//! let body = send(url);
+//! if trace_enabled(2) {
+//!     trace(format!("< {}", truncate_trace(&body)));
+//! }
+//! flush_traces();
+//! ```
+
+use crate::guc;
+use crate::redact::redact;
+use std::sync::Mutex;
+
+/// Max number of characters of a body in the traces
+pub const TRACE_BODY_LIMIT: usize = 1000;
+
+// trace messages waiting to be written to the log
+static TRACES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Check if the remote requests are traced at the level, by
+/// `wrappers.debug_level`
+pub fn trace_enabled(level: i32) -> bool {
+    guc::debug_level() >= level
+}
+
+/// Queue a trace message, it can be called in any thread
+pub fn trace(message: impl Into<String>) {
+    if let Ok(mut traces) = TRACES.lock() {
+        traces.push(message.into());
+    }
+}
+
+/// Truncate a request or response body to [`TRACE_BODY_LIMIT`] characters
+pub fn truncate_trace(body: &str) -> String {
+    match body.char_indices().nth(TRACE_BODY_LIMIT) {
+        Some((idx, _)) => format!("{}... ({} bytes)", &body[..idx], body.len()),
+        None => body.to_owned(),
+    }
+}
+
+/// Write the queued trace messages to the log at `DEBUG1`, it must be called
+/// in Postgres thread
+pub fn flush_traces() {
+    let traces = match TRACES.lock() {
+        Ok(mut traces) => std::mem::take(&mut *traces),
+        Err(_) => return,
+    };
+    for message in traces {
+        pgrx::debug1!("wrappers trace: {}", redact(&message));
+    }
+}
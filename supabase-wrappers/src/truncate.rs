@@ -0,0 +1,33 @@
+use pgrx::pg_sys::panic::ErrorReport;
+use pgrx::{debug2, prelude::*, PgList};
+
+use crate::instance;
+use crate::options::options_to_hashmap;
+use crate::prelude::ForeignDataWrapper;
+use crate::utils::ReportableError;
+use crate::write_buffer;
+use crate::xact;
+
+#[pg_guard]
+pub(super) extern "C" fn exec_foreign_truncate<E: Into<ErrorReport>, W: ForeignDataWrapper<E>>(
+    rels: *mut pg_sys::List,
+    _behavior: pg_sys::DropBehavior::Type,
+    _restart_seqs: bool,
+) {
+    debug2!("---> exec_foreign_truncate");
+    unsafe {
+        // the relations are all foreign tables belonging to the same foreign
+        // server, truncate them one by one
+        let rels: PgList<pg_sys::RelationData> = PgList::from_pg(rels);
+        for rel in rels.iter_ptr() {
+            let ftable_id = (*rel).rd_id;
+            let ftable = pg_sys::GetForeignTable(ftable_id);
+            let opts = options_to_hashmap((*ftable).options).report_unwrap();
+
+            let mut instance: W = instance::create_fdw_instance_from_table_id(ftable_id);
+            instance.truncate(&opts).report_unwrap();
+            write_buffer::clear_table(ftable_id);
+            xact::register_instance(instance);
+        }
+    }
+}
@@ -1,17 +1,27 @@
 //! Helper functions for working with Wrappers
 //!
 
-use crate::interface::{Cell, Column, Row};
+use crate::guc;
+use crate::interface::{Cell, CellFormatter, Column, Qual, Row};
+use crate::options::options_to_hashmap;
+use crate::redact::{redact, redact_report, register_secret};
+use pgrx::datum::{Interval, Range, RangeBound, RangeSubType, TimestampWithTimeZone};
 use pgrx::pg_sys::panic::{ErrorReport, ErrorReportable};
-use pgrx::prelude::PgBuiltInOids;
+use pgrx::prelude::{PgBuiltInOids, PgHeapTuple};
 use pgrx::spi::Spi;
 use pgrx::IntoDatum;
 use pgrx::*;
-use std::ffi::CStr;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::future::Future;
 use std::num::NonZeroUsize;
+use std::ops::Deref;
 use std::ptr;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 use thiserror::Error;
-use tokio::runtime::{Builder, Runtime};
+use tokio::runtime::Builder;
 use uuid::Uuid;
 
 /// Log debug message to Postgres log.
@@ -23,7 +33,7 @@ use uuid::Uuid;
 /// See more details in [Postgres documents](https://www.postgresql.org/docs/current/runtime-config-logging.html#RUNTIME-CONFIG-LOGGING-WHEN).
 #[inline]
 pub fn log_debug1(msg: &str) {
-    debug1!("wrappers: {}", msg);
+    debug1!("wrappers: {}", redact(msg));
 }
 
 /// Report info to Postgres using `ereport!`
@@ -41,7 +51,7 @@ pub fn report_info(msg: &str) {
     ereport!(
         PgLogLevel::INFO,
         PgSqlErrorCode::ERRCODE_SUCCESSFUL_COMPLETION,
-        msg,
+        &redact(msg),
         "Wrappers"
     );
 }
@@ -61,7 +71,7 @@ pub fn report_notice(msg: &str) {
     ereport!(
         PgLogLevel::NOTICE,
         PgSqlErrorCode::ERRCODE_SUCCESSFUL_COMPLETION,
-        msg,
+        &redact(msg),
         "Wrappers"
     );
 }
@@ -81,7 +91,7 @@ pub fn report_warning(msg: &str) {
     ereport!(
         PgLogLevel::WARNING,
         PgSqlErrorCode::ERRCODE_WARNING,
-        msg,
+        &redact(msg),
         "Wrappers"
     );
 }
@@ -104,7 +114,7 @@ pub fn report_warning(msg: &str) {
 /// ```
 #[inline]
 pub fn report_error(code: PgSqlErrorCode, msg: &str) {
-    ereport!(PgLogLevel::ERROR, code, msg, "Wrappers");
+    ereport!(PgLogLevel::ERROR, code, &redact(msg), "Wrappers");
 }
 
 #[derive(Error, Debug)]
@@ -120,12 +130,74 @@ impl From<CreateRuntimeError> for ErrorReport {
     }
 }
 
-/// Create a Tokio async runtime
+// interval to check for query cancellation while blocking on a future
+const INTERRUPT_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+// number of worker threads of the shared async runtime
+const RUNTIME_WORKER_THREADS: usize = 2;
+
+thread_local! {
+    // async runtime shared by all the FDW instances in current backend, it is
+    // created on first use and shut down on backend exit
+    static SHARED_RUNTIME: RefCell<Option<tokio::runtime::Runtime>> = const { RefCell::new(None) };
+}
+
+#[pg_guard]
+extern "C" fn shutdown_shared_runtime(_code: std::os::raw::c_int, _arg: pg_sys::Datum) {
+    if let Some(rt) = SHARED_RUNTIME.with_borrow_mut(|rt| rt.take()) {
+        rt.shutdown_background();
+    }
+}
+
+/// Async runtime which is aware of statement cancellation
+///
+/// A handle of the Tokio runtime shared by the backend, its [`block_on`](Runtime::block_on)
+/// checks for Postgres interrupts periodically while the future is running, so
+/// a hung remote call can be cancelled by `Ctrl+C` or `statement_timeout`.
+/// Other Tokio runtime handle methods are available through `Deref`.
+#[derive(Debug, Clone)]
+pub struct Runtime(tokio::runtime::Handle);
+
+impl Runtime {
+    /// Run a future to completion
+    ///
+    /// If the statement is cancelled while the future is running, the future
+    /// is dropped, which aborts the in-flight remote request, and the
+    /// cancellation error is raised.
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        let mut future = std::pin::pin!(future);
+        loop {
+            let output = self.0.block_on(async {
+                tokio::time::timeout(INTERRUPT_CHECK_INTERVAL, future.as_mut())
+                    .await
+                    .ok()
+            });
+            if let Some(output) = output {
+                return output;
+            }
+            check_for_interrupts!();
+        }
+    }
+}
+
+impl Deref for Runtime {
+    type Target = tokio::runtime::Handle;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Get the Tokio async runtime shared by the backend
 ///
 /// Use this runtime to run async code in `block` mode. Run blocked code is
 /// required by Postgres callback functions which is fine because Postgres
 /// process is single-threaded.
 ///
+/// The runtime is created on first call and shared by all the FDW instances in
+/// the backend, so it is cheap to call this function for each instance. The
+/// runtime is shut down when the backend exits.
+///
 /// For example,
 ///
 /// ```rust,no_run
@@ -149,16 +221,93 @@ impl From<CreateRuntimeError> for ErrorReport {
 /// # Ok(())
 /// # }
 /// ```
-#[inline]
 pub fn create_async_runtime() -> Result<Runtime, CreateRuntimeError> {
-    Ok(Builder::new_current_thread().enable_all().build()?)
+    SHARED_RUNTIME.with_borrow_mut(|shared| {
+        if let Some(rt) = shared {
+            return Ok(Runtime(rt.handle().clone()));
+        }
+        let rt = Builder::new_multi_thread()
+            .worker_threads(RUNTIME_WORKER_THREADS)
+            .thread_name("wrappers-runtime")
+            .enable_all()
+            .build()?;
+        let handle = rt.handle().clone();
+        *shared = Some(rt);
+        unsafe { pg_sys::on_proc_exit(Some(shutdown_shared_runtime), pg_sys::Datum::from(0)) };
+        Ok(Runtime(handle))
+    })
+}
+
+struct CachedSecret {
+    secret: String,
+    expires_at: Instant,
+}
+
+thread_local! {
+    // decrypted Vault secrets keyed by the current user and the secret id or
+    // name, so a secret read by one role is not leaked to another role in the
+    // same backend
+    static VAULT_SECRETS: RefCell<HashMap<(pg_sys::Oid, String), CachedSecret>> =
+        RefCell::new(HashMap::new());
+}
+
+// get the secret from the backend cache, or query it from Vault by `query`
+// and cache it for `wrappers.secret_cache_ttl`
+fn cached_vault_secret(key: String, query: impl FnOnce() -> Option<String>) -> Option<String> {
+    let Some(ttl) = guc::secret_cache_ttl() else {
+        return query();
+    };
+    let key = (unsafe { pg_sys::GetUserId() }, key);
+    let now = Instant::now();
+    let cached = VAULT_SECRETS.with_borrow(|secrets| {
+        secrets
+            .get(&key)
+            .filter(|cached| cached.expires_at > now)
+            .map(|cached| cached.secret.clone())
+    });
+    if cached.is_some() {
+        return cached;
+    }
+
+    let secret = query();
+    VAULT_SECRETS.with_borrow_mut(|secrets| match &secret {
+        Some(secret) => {
+            secrets.insert(
+                key,
+                CachedSecret {
+                    secret: secret.clone(),
+                    expires_at: now + ttl,
+                },
+            );
+        }
+        None => {
+            secrets.remove(&key);
+        }
+    });
+    secret
+}
+
+/// Remove all the cached Vault secrets in current backend
+///
+/// The secrets are read from Vault again next time, so a rotated secret takes
+/// effect immediately instead of after `wrappers.secret_cache_ttl`.
+pub fn flush_vault_secrets() {
+    VAULT_SECRETS.with_borrow_mut(|secrets| secrets.clear());
 }
 
 /// Get decrypted secret from Vault by secret ID
 ///
 /// Get decrypted secret as string from Vault by secret ID. Vault is an extension for storing
 /// encrypted secrets, [see more details](https://github.com/supabase/vault).
+///
+/// The secret is cached in the backend for `wrappers.secret_cache_ttl` seconds.
 pub fn get_vault_secret(secret_id: &str) -> Option<String> {
+    cached_vault_secret(format!("id:{}", secret_id), || {
+        query_vault_secret(secret_id)
+    })
+}
+
+fn query_vault_secret(secret_id: &str) -> Option<String> {
     match Uuid::try_parse(secret_id) {
         Ok(sid) => {
             let sid = sid.into_bytes();
@@ -169,7 +318,7 @@ pub fn get_vault_secret(secret_id: &str) -> Option<String> {
                     pgrx::Uuid::from_bytes(sid).into_datum(),
                 )],
             ) {
-                Ok(decrypted) => decrypted,
+                Ok(decrypted) => decrypted.inspect(|secret| register_secret(secret)),
                 Err(err) => {
                     report_error(
                         PgSqlErrorCode::ERRCODE_FDW_ERROR,
@@ -193,12 +342,20 @@ pub fn get_vault_secret(secret_id: &str) -> Option<String> {
 ///
 /// Get decrypted secret as string from Vault by secret name. Vault is an extension for storing
 /// encrypted secrets, [see more details](https://github.com/supabase/vault).
+///
+/// The secret is cached in the backend for `wrappers.secret_cache_ttl` seconds.
 pub fn get_vault_secret_by_name(secret_name: &str) -> Option<String> {
+    cached_vault_secret(format!("name:{}", secret_name), || {
+        query_vault_secret_by_name(secret_name)
+    })
+}
+
+fn query_vault_secret_by_name(secret_name: &str) -> Option<String> {
     match Spi::get_one_with_args::<String>(
         "select decrypted_secret from vault.decrypted_secrets where name = $1",
         vec![(PgBuiltInOids::TEXTOID.oid(), secret_name.into_datum())],
     ) {
-        Ok(decrypted) => decrypted,
+        Ok(decrypted) => decrypted.inspect(|secret| register_secret(secret)),
         Err(err) => {
             report_error(
                 PgSqlErrorCode::ERRCODE_FDW_ERROR,
@@ -209,6 +366,295 @@ pub fn get_vault_secret_by_name(secret_name: &str) -> Option<String> {
     }
 }
 
+// number of microseconds between Unix epoch (1970-01-01) and Postgres epoch (2000-01-01)
+const PG_EPOCH_OFFSET_MICROS: i64 = 946_684_800_000_000;
+
+/// Parse a RFC 3339 timestamp string to a timestamptz
+///
+/// The time zone offset in the string is kept when converting to the absolute
+/// point in time, for example, `2024-01-02T03:04:05.123+08:00` and
+/// `2024-01-01T19:04:05.123Z` are the same timestamptz. Returns `None` if the
+/// string is not a valid RFC 3339 timestamp.
+///
+/// ```rust,no_run
+/// # use supabase_wrappers::prelude::timestamptz_from_rfc3339;
+/// let ts = timestamptz_from_rfc3339("2024-01-02T03:04:05+08:00");
+/// ```
+pub fn timestamptz_from_rfc3339(s: &str) -> Option<TimestampWithTimeZone> {
+    let b = s.as_bytes();
+    let len = b.len();
+    let has_zone = matches!(b.last(), Some(b'Z' | b'z'))
+        || (len >= 6 && matches!(b[len - 6], b'+' | b'-') && b[len - 3] == b':');
+    let is_rfc3339 = len >= 20
+        && b[4] == b'-'
+        && b[7] == b'-'
+        && matches!(b[10], b'T' | b't' | b' ')
+        && b[13] == b':'
+        && b[16] == b':'
+        && has_zone;
+    if !is_rfc3339 {
+        return None;
+    }
+    TimestampWithTimeZone::from_str(s).ok()
+}
+
+/// Convert the number of milliseconds since Unix epoch to a timestamptz
+///
+/// Returns `None` if the value is out of the timestamptz range.
+///
+/// ```rust,no_run
+/// # use supabase_wrappers::prelude::timestamptz_from_epoch_millis;
+/// let ts = timestamptz_from_epoch_millis(1_704_164_645_123);
+/// ```
+pub fn timestamptz_from_epoch_millis(ms: i64) -> Option<TimestampWithTimeZone> {
+    ms.checked_mul(1000)
+        .and_then(|us| us.checked_sub(PG_EPOCH_OFFSET_MICROS))
+        .and_then(|us| TimestampWithTimeZone::try_from(us).ok())
+}
+
+/// Parse an ISO 8601 duration string to an interval
+///
+/// The string is in the format with designators, like `P1Y2M3DT4H5M6.5S`.
+/// Returns `None` if the string is not a valid duration.
+///
+/// ```rust,no_run
+/// # use supabase_wrappers::prelude::interval_from_iso8601;
+/// let itvl = interval_from_iso8601("P3DT4H");
+/// ```
+pub fn interval_from_iso8601(s: &str) -> Option<Interval> {
+    if s.len() < 3 || !s.starts_with('P') {
+        return None;
+    }
+    Interval::from_str(s).ok()
+}
+
+/// Convert a number of seconds to an interval
+///
+/// Fractional seconds are kept up to microseconds. Returns `None` if the value
+/// is out of the interval range.
+pub fn interval_from_secs(secs: f64) -> Option<Interval> {
+    let micros = (secs * 1_000_000.0).round();
+    if !micros.is_finite() || micros < i64::MIN as f64 || micros > i64::MAX as f64 {
+        return None;
+    }
+    Interval::new(0, 0, micros as i64).ok()
+}
+
+/// Convert a number of milliseconds to an interval
+///
+/// Returns `None` if the value is out of the interval range.
+pub fn interval_from_millis(ms: i64) -> Option<Interval> {
+    ms.checked_mul(1000)
+        .and_then(|us| Interval::new(0, 0, us).ok())
+}
+
+/// Build a range from its lower and upper bounds
+///
+/// A `None` bound means the range is unbounded on that side, and the inclusive
+/// flag of an unbounded side is ignored. For example, a `[start, end)` period
+/// can be built as:
+///
+/// ```rust,no_run
+/// # use supabase_wrappers::prelude::{make_range, Cell};
+/// let cell = Cell::I64Range(make_range(Some(1), true, Some(10), false));
+/// ```
+pub fn make_range<T: RangeSubType>(
+    lower: Option<T>,
+    lower_inc: bool,
+    upper: Option<T>,
+    upper_inc: bool,
+) -> Range<T> {
+    let bound = |v: Option<T>, inc: bool| match v {
+        None => RangeBound::Infinite,
+        Some(v) if inc => RangeBound::Inclusive(v),
+        Some(v) => RangeBound::Exclusive(v),
+    };
+    Range::new(bound(lower, lower_inc), bound(upper, upper_inc))
+}
+
+/// SQL dialect of a remote data source
+///
+/// It is used by the helper functions which need to generate dialect-specific
+/// SQL text, such as [`escape_like_pattern`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SqlDialect {
+    Postgres,
+    ClickHouse,
+    BigQuery,
+    SqlServer,
+}
+
+// write a character into a string literal, escaping it for the dialect
+fn push_quoted_char(ret: &mut String, c: char, dialect: SqlDialect) {
+    match (dialect, c) {
+        (SqlDialect::ClickHouse | SqlDialect::BigQuery, '\'' | '\\') => {
+            ret.push('\\');
+            ret.push(c);
+        }
+        (_, '\'') => ret.push_str("''"),
+        _ => ret.push(c),
+    }
+}
+
+/// Quote a string as a SQL string literal for the given dialect
+///
+/// Single quotes are doubled for Postgres and SQL Server, while ClickHouse and
+/// BigQuery escape single quote and backslash with a backslash.
+///
+/// For example,
+///
+/// ```rust,no_run
+/// # use supabase_wrappers::prelude::{quote_literal, SqlDialect};
+/// assert_eq!(quote_literal("it's", SqlDialect::SqlServer), "'it''s'");
+/// assert_eq!(quote_literal("it's", SqlDialect::BigQuery), r"'it\'s'");
+/// ```
+pub fn quote_literal(s: &str, dialect: SqlDialect) -> String {
+    let mut ret = String::with_capacity(s.len() + 2);
+    ret.push('\'');
+    for c in s.chars() {
+        push_quoted_char(&mut ret, c, dialect);
+    }
+    ret.push('\'');
+    ret
+}
+
+/// Format a cell as a SQL literal for the given dialect
+///
+/// - strings and json values are quoted by [`quote_literal`]
+/// - booleans are formatted as `1` or `0` for SQL Server, which has no boolean literal
+/// - other cells are formatted as their [`Display`](std::fmt::Display) output
+pub fn format_cell(cell: &Cell, dialect: SqlDialect) -> String {
+    match (cell, dialect) {
+        (Cell::String(v), _) => quote_literal(v, dialect),
+        (Cell::Json(v), _) => quote_literal(&v.0.to_string(), dialect),
+        (Cell::Bool(v), SqlDialect::SqlServer) => format!("{}", *v as u8),
+        _ => format!("{}", cell),
+    }
+}
+
+/// A [`CellFormatter`] which formats cells by [`format_cell`] for the given dialect
+///
+/// It can be used with [`Qual::deparse_with_fmt`] to deparse quals to remote SQL.
+pub struct SqlCellFormatter {
+    dialect: SqlDialect,
+}
+
+impl SqlCellFormatter {
+    pub fn new(dialect: SqlDialect) -> Self {
+        Self { dialect }
+    }
+}
+
+impl CellFormatter for SqlCellFormatter {
+    fn fmt_cell(&mut self, cell: &Cell) -> String {
+        format_cell(cell, self.dialect)
+    }
+}
+
+/// Escape a `LIKE` pattern to a quoted string literal for the given SQL dialect
+///
+/// The pattern is in Postgres syntax, which uses `%` and `_` as wildcards and
+/// backslash as the escape character, for example, the pattern in `col like 'a\_b%'`.
+/// The returned string is a single-quoted literal which can be put after `like`
+/// in the deparsed SQL, with the wildcards, escapes and quotes rewritten for
+/// the target dialect:
+///
+/// - ClickHouse and BigQuery also use backslash in string literals, so it is
+///   doubled to survive the literal parsing.
+/// - SQL Server has no escape character by default, so escaped wildcards are
+///   wrapped in brackets and a literal `[` is bracketed too.
+///
+/// For example,
+///
+/// ```rust,no_run
+/// # use supabase_wrappers::prelude::{escape_like_pattern, SqlDialect};
+/// assert_eq!(escape_like_pattern(r"it's 100\%", SqlDialect::Postgres), r"'it''s 100\%'");
+/// assert_eq!(escape_like_pattern(r"it's 100\%", SqlDialect::ClickHouse), r"'it\'s 100\\%'");
+/// assert_eq!(escape_like_pattern(r"it's 100\%", SqlDialect::SqlServer), r"'it''s 100[%]'");
+/// ```
+pub fn escape_like_pattern(pattern: &str, dialect: SqlDialect) -> String {
+    let quote = |ret: &mut String, c: char| push_quoted_char(ret, c, dialect);
+
+    let mut ret = String::with_capacity(pattern.len() + 2);
+    let mut chars = pattern.chars();
+
+    ret.push('\'');
+    while let Some(c) = chars.next() {
+        match (dialect, c) {
+            (SqlDialect::SqlServer, '\\') => match chars.next() {
+                Some(e @ ('%' | '_' | '[')) => ret.push_str(&format!("[{}]", e)),
+                Some(e) => quote(&mut ret, e),
+                None => ret.push('\\'),
+            },
+            (SqlDialect::SqlServer, '[') => ret.push_str("[[]"),
+            (_, '\\') => {
+                // keep the escape sequence, a trailing backslash is taken literally
+                quote(&mut ret, '\\');
+                quote(&mut ret, chars.next().unwrap_or('\\'));
+            }
+            _ => quote(&mut ret, c),
+        }
+    }
+    ret.push('\'');
+
+    ret
+}
+
+/// Deparse quals to a SQL condition connected by `and`
+///
+/// Each qual is deparsed by `f`, except the inclusive range quals on the same
+/// column, like `col >= 1` and `col <= 3` which are decomposed from `col between 1 and 3`,
+/// they are deparsed together by [`Qual::deparse_between`] using formatter `t`.
+///
+/// For example,
+///
+/// ```rust,no_run
+/// # use supabase_wrappers::prelude::*;
+/// struct MyFormatter {}
+///
+/// impl CellFormatter for MyFormatter {
+///     fn fmt_cell(&mut self, cell: &Cell) -> String {
+///         format!("{}", cell)
+///     }
+/// }
+///
+/// # let quals: Vec<Qual> = Vec::new();
+/// let cond = deparse_quals(
+///     &quals,
+///     |qual| qual.deparse_with_fmt(&mut MyFormatter {}),
+///     &mut MyFormatter {},
+/// );
+/// ```
+pub fn deparse_quals<F, T>(quals: &[Qual], mut f: F, t: &mut T) -> String
+where
+    F: FnMut(&Qual) -> String,
+    T: CellFormatter,
+{
+    let mut merged = vec![false; quals.len()];
+    let mut conds = Vec::new();
+    for (idx, qual) in quals.iter().enumerate() {
+        if merged[idx] {
+            continue;
+        }
+        let between = quals
+            .iter()
+            .enumerate()
+            .skip(idx + 1)
+            .filter(|(upper_idx, _)| !merged[*upper_idx])
+            .find_map(|(upper_idx, upper)| {
+                qual.deparse_between(upper, t).map(|cond| (upper_idx, cond))
+            });
+        match between {
+            Some((upper_idx, cond)) => {
+                merged[upper_idx] = true;
+                conds.push(cond);
+            }
+            None => conds.push(f(qual)),
+        }
+    }
+    conds.join(" and ")
+}
+
 pub(super) unsafe fn tuple_table_slot_to_row(slot: *mut pg_sys::TupleTableSlot) -> Row {
     let tup_desc = PgTupleDesc::from_pg_copy((*slot).tts_tupleDescriptor);
 
@@ -227,6 +673,162 @@ pub(super) unsafe fn tuple_table_slot_to_row(slot: *mut pg_sys::TupleTableSlot)
     row
 }
 
+// get the column type exposed to FDWs, a domain is exposed as its base type
+// and an enum is exposed as text, so FDWs can handle them as builtin types
+pub(super) unsafe fn column_type_oid(type_oid: pg_sys::Oid) -> pg_sys::Oid {
+    if type_oid.as_u32() < pg_sys::FirstNormalObjectId {
+        return type_oid;
+    }
+    let base_oid = pg_sys::getBaseType(type_oid);
+    if pg_sys::type_is_enum(base_oid) {
+        pg_sys::TEXTOID
+    } else {
+        base_oid
+    }
+}
+
+// convert a cell returned by FDW to datum of the column type, the enum value
+// is converted from its text by the type input function
+pub(super) unsafe fn cell_to_datum(cell: Cell, type_oid: pg_sys::Oid) -> Option<pg_sys::Datum> {
+    if let Cell::Composite(row) = cell {
+        return composite_to_datum(row, type_oid);
+    }
+    if type_oid.as_u32() >= pg_sys::FirstNormalObjectId
+        && pg_sys::type_is_enum(pg_sys::getBaseType(type_oid))
+    {
+        let text = match cell {
+            Cell::String(s) => s,
+            cell => cell.to_string(),
+        };
+        let text = CString::new(text).ok()?;
+        let mut typinput = pg_sys::InvalidOid;
+        let mut typioparam = pg_sys::InvalidOid;
+        pg_sys::getTypeInputInfo(type_oid, &mut typinput, &mut typioparam);
+        return Some(pg_sys::OidInputFunctionCall(
+            typinput,
+            text.as_ptr() as _,
+            typioparam,
+            -1,
+        ));
+    }
+    cell.into_datum()
+}
+
+// convert a composite cell to datum of the composite type, its cells are
+// matched to the type attributes by name and unmatched attributes are null
+unsafe fn composite_to_datum(row: Row, type_oid: pg_sys::Oid) -> Option<pg_sys::Datum> {
+    let tupdesc = PgTupleDesc::for_composite_type_by_oid(pg_sys::getBaseType(type_oid))?;
+    let mut cells: HashMap<String, Cell> = row
+        .cols
+        .into_iter()
+        .zip(row.cells)
+        .filter_map(|(col, cell)| cell.map(|cell| (col, cell)))
+        .collect();
+    let datums = tupdesc
+        .iter()
+        .map(|att| {
+            if att.is_dropped() {
+                return None;
+            }
+            cells
+                .remove(att.name())
+                .and_then(|cell| cell_to_datum(cell, att.atttypid))
+        })
+        .collect::<Vec<_>>();
+    PgHeapTuple::from_datums(tupdesc, datums)
+        .ok()?
+        .into_composite_datum()
+}
+
+/// Get attributes of a composite type as a column list
+///
+/// Returns `None` if the type is not a composite type or a domain over it. It
+/// can be used to build a [`Cell::Composite`] field-by-field for a column
+/// declared as a user-defined composite type, for example,
+///
+/// ```rust,no_run
+/// # use supabase_wrappers::prelude::*;
+/// # use std::collections::HashMap;
+/// # let col = Column::default();
+/// # let obj: HashMap<String, String> = HashMap::new();
+/// if let Some(fields) = composite_type_columns(col.type_oid) {
+///     let mut row = Row::new();
+///     for field in &fields {
+///         row.push(&field.name, obj.get(&field.name).cloned().map(Cell::String));
+///     }
+///     let cell = Cell::Composite(row);
+/// }
+/// ```
+pub fn composite_type_columns(type_oid: pg_sys::Oid) -> Option<Vec<Column>> {
+    unsafe {
+        if type_oid.as_u32() < pg_sys::FirstNormalObjectId {
+            return None;
+        }
+        let base_oid = pg_sys::getBaseType(type_oid);
+        if !pg_sys::type_is_rowtype(base_oid) {
+            return None;
+        }
+        let tupdesc = PgTupleDesc::for_composite_type_by_oid(base_oid)?;
+        let cols = tupdesc
+            .iter()
+            .filter(|att| !att.is_dropped())
+            .map(|att| Column {
+                name: att.name().to_owned(),
+                num: att.attnum as usize,
+                type_oid: column_type_oid(att.atttypid),
+                ..Default::default()
+            })
+            .collect();
+        Some(cols)
+    }
+}
+
+// convert a cell of the column with `coerce 'text'` option to the column type,
+// a cell in other types is stringified and a jsonb column gets a json string
+pub(super) fn coerce_cell(cell: Cell, type_oid: pg_sys::Oid) -> Cell {
+    let text = match cell {
+        Cell::String(_) if type_oid == pg_sys::TEXTOID => return cell,
+        Cell::Json(_) if type_oid == pg_sys::JSONBOID => return cell,
+        Cell::String(s) => s,
+        Cell::Json(v) => v.0.to_string(),
+        cell => {
+            let s = cell.to_string();
+            s.strip_prefix('\'')
+                .and_then(|v| v.strip_suffix('\''))
+                .map(|v| v.to_owned())
+                .unwrap_or(s)
+        }
+    };
+    if type_oid == pg_sys::JSONBOID {
+        Cell::Json(JsonB(text.into()))
+    } else {
+        Cell::String(text)
+    }
+}
+
+// get column options of a foreign table attribute, the `coerce` option is
+// only allowed to be 'text' on a text or jsonb column
+pub(super) unsafe fn get_column_options(
+    relid: pg_sys::Oid,
+    attnum: pg_sys::AttrNumber,
+) -> HashMap<String, String> {
+    let options =
+        options_to_hashmap(pg_sys::GetForeignColumnOptions(relid, attnum)).report_unwrap();
+    if let Some(coerce) = options.get("coerce") {
+        let type_oid = column_type_oid(pg_sys::get_atttype(relid, attnum));
+        if coerce != "text" || (type_oid != pg_sys::TEXTOID && type_oid != pg_sys::JSONBOID) {
+            report_error(
+                PgSqlErrorCode::ERRCODE_FDW_INVALID_ATTRIBUTE_VALUE,
+                &format!(
+                    "invalid column option coerce '{}', only 'text' is supported on text or jsonb column",
+                    coerce
+                ),
+            );
+        }
+    }
+    options
+}
+
 // extract target column name and attribute no list
 pub(super) unsafe fn extract_target_columns(
     root: *mut pg_sys::PlannerInfo,
@@ -273,11 +875,12 @@ pub(super) unsafe fn extract_target_columns(
                 continue;
             }
 
-            let type_oid = pg_sys::get_atttype((*rte).relid, attno);
+            let type_oid = column_type_oid(pg_sys::get_atttype((*rte).relid, attno));
             ret.push(Column {
                 name: CStr::from_ptr(attname).to_str().unwrap().to_owned(),
                 num: attno as usize,
                 type_oid,
+                options: get_column_options((*rte).relid, attno),
             });
         }
     }
@@ -338,6 +941,6 @@ impl<T, E: Into<ErrorReport>> ReportableError for Result<T, E> {
     type Output = T;
 
     fn report_unwrap(self) -> Self::Output {
-        self.map_err(|e| e.into()).unwrap_or_report()
+        self.map_err(|e| redact_report(e.into())).unwrap_or_report()
     }
 }
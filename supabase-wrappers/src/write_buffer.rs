@@ -0,0 +1,198 @@
+use pgrx::pg_sys::Oid;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use crate::interface::{Cell, Column, Row};
+use crate::xact;
+
+// writes made to a foreign table in current transaction, rows are identified
+// by the text of their rowid cells
+#[derive(Clone, Default)]
+struct PendingWrites {
+    // inserted rows with all the table columns, updates and deletes on them
+    // are applied in place
+    inserted: Vec<Row>,
+
+    // new values of the updated remote rows
+    updated: Vec<(String, Row)>,
+
+    // deleted remote rows
+    deleted: Vec<String>,
+}
+
+impl PendingWrites {
+    fn inserted_pos(&self, rowid_name: &str, key: &str) -> Option<usize> {
+        self.inserted
+            .iter()
+            .position(|row| rowid_key(row, rowid_name).as_deref() == Some(key))
+    }
+}
+
+thread_local! {
+    static PENDING_WRITES: RefCell<HashMap<Oid, PendingWrites>> = RefCell::new(HashMap::new());
+}
+
+// get if read-your-writes is enabled by foreign table option
+// `read_your_writes`, it needs `rowid_column` to identify the rows
+pub(crate) fn is_enabled(opts: &HashMap<String, String>) -> bool {
+    let enabled = match opts.get("read_your_writes").map(|v| v.as_str()) {
+        Some("true") => true,
+        Some("false") | None => false,
+        Some(v) => {
+            pgrx::error!("invalid option read_your_writes: {}", v);
+        }
+    };
+    if enabled && !opts.contains_key("rowid_column") {
+        pgrx::error!("option 'rowid_column' is required for read_your_writes");
+    }
+    enabled
+}
+
+fn rowid_key(row: &Row, rowid_name: &str) -> Option<String> {
+    row.iter()
+        .find(|(col, _)| col.as_str() == rowid_name)
+        .and_then(|(_, cell)| cell.as_ref().map(|cell| cell.to_string()))
+}
+
+fn with_table_writes<F: FnOnce(&mut PendingWrites)>(ftable_id: Oid, f: F) {
+    xact::register_callback();
+    PENDING_WRITES.with_borrow_mut(|writes| f(writes.entry(ftable_id).or_default()));
+}
+
+pub(crate) fn record_insert(ftable_id: Oid, rowid_name: &str, row: &Row) {
+    let row = row.clone();
+    with_table_writes(ftable_id, |writes| {
+        // the inserted row replaces any previous write on the same rowid
+        if let Some(key) = rowid_key(&row, rowid_name) {
+            if let Some(pos) = writes.inserted_pos(rowid_name, &key) {
+                writes.inserted.remove(pos);
+            }
+            writes.updated.retain(|(k, _)| *k != key);
+            writes.deleted.retain(|k| *k != key);
+        }
+        writes.inserted.push(row);
+    });
+}
+
+pub(crate) fn record_update(ftable_id: Oid, rowid_name: &str, rowid: &Cell, new_row: &Row) {
+    let key = rowid.to_string();
+    let new_row = new_row.clone();
+    with_table_writes(ftable_id, |writes| {
+        let target = match writes.inserted_pos(rowid_name, &key) {
+            Some(pos) => &mut writes.inserted[pos],
+            None => match writes.updated.iter().position(|(k, _)| *k == key) {
+                Some(pos) => &mut writes.updated[pos].1,
+                None => {
+                    writes.updated.push((key, new_row));
+                    return;
+                }
+            },
+        };
+        for (col, cell) in new_row.iter() {
+            match target.cols.iter().position(|c| c == col) {
+                Some(idx) => target.cells[idx] = cell.clone(),
+                None => target.push(col, cell.clone()),
+            }
+        }
+    });
+}
+
+pub(crate) fn record_delete(ftable_id: Oid, rowid_name: &str, rowid: &Cell) {
+    let key = rowid.to_string();
+    with_table_writes(ftable_id, |writes| {
+        if let Some(pos) = writes.inserted_pos(rowid_name, &key) {
+            writes.inserted.remove(pos);
+        }
+        writes.updated.retain(|(k, _)| *k != key);
+        if !writes.deleted.contains(&key) {
+            writes.deleted.push(key);
+        }
+    });
+}
+
+// drop the pending writes of a foreign table, e.g. it is truncated
+#[cfg(not(feature = "pg13"))]
+pub(crate) fn clear_table(ftable_id: Oid) {
+    PENDING_WRITES.with_borrow_mut(|writes| writes.remove(&ftable_id));
+}
+
+// drop all the pending writes when transaction ends
+pub(super) fn clear() {
+    PENDING_WRITES.with_borrow_mut(|writes| writes.clear());
+}
+
+// pending writes overlaid onto the rows fetched from remote, it is a snapshot
+// taken when the scan begins
+pub(crate) struct WriteOverlay {
+    rowid_name: String,
+    writes: PendingWrites,
+
+    // if all the remote rows are fetched, then the inserted rows are returned
+    remote_done: bool,
+
+    // index of the next inserted row to be returned
+    next_insert: usize,
+}
+
+impl WriteOverlay {
+    pub(crate) fn new(ftable_id: Oid, rowid_name: &str) -> Option<Self> {
+        let writes = PENDING_WRITES.with_borrow(|writes| writes.get(&ftable_id).cloned())?;
+        Some(Self {
+            rowid_name: rowid_name.to_string(),
+            writes,
+            remote_done: false,
+            next_insert: 0,
+        })
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.remote_done = false;
+        self.next_insert = 0;
+    }
+
+    pub(crate) fn remote_done(&self) -> bool {
+        self.remote_done
+    }
+
+    pub(crate) fn set_remote_done(&mut self) {
+        self.remote_done = true;
+    }
+
+    // apply the pending writes to a row fetched from remote, return false if
+    // the row should be skipped, that is, it is deleted or it will be
+    // returned as an inserted row
+    pub(crate) fn apply(&self, row: &mut Row) -> bool {
+        let Some(key) = rowid_key(row, &self.rowid_name) else {
+            return true;
+        };
+        if self.writes.deleted.contains(&key)
+            || self.writes.inserted_pos(&self.rowid_name, &key).is_some()
+        {
+            return false;
+        }
+        if let Some((_, new_row)) = self.writes.updated.iter().find(|(k, _)| *k == key) {
+            for (col, cell) in new_row.iter() {
+                if let Some(idx) = row.cols.iter().position(|c| c == col) {
+                    row.cells[idx] = cell.clone();
+                }
+            }
+        }
+        true
+    }
+
+    // get the next inserted row, with only the target columns in order
+    pub(crate) fn next_inserted(&mut self, tgts: &[Column]) -> Option<Row> {
+        let src = self.writes.inserted.get(self.next_insert)?;
+        self.next_insert += 1;
+
+        let mut row = Row::new();
+        for tgt in tgts {
+            let cell = src
+                .iter()
+                .find(|(col, _)| col.as_str() == tgt.name)
+                .and_then(|(_, cell)| cell.clone());
+            row.push(&tgt.name, cell);
+        }
+        Some(row)
+    }
+}
@@ -0,0 +1,116 @@
+use pgrx::pg_sys::panic::ErrorReport;
+use pgrx::{debug2, prelude::*, PgLogLevel, PgSqlErrorCode};
+use std::cell::{Cell, RefCell};
+use std::os::raw::c_void;
+use std::ptr;
+
+use crate::prelude::ForeignDataWrapper;
+use crate::utils::{report_error, ReportableError};
+use crate::write_buffer;
+
+// a type-erased foreign data wrapper instance which modified foreign tables
+// in current transaction, waiting for the transaction end
+#[derive(Clone, Copy)]
+struct PendingInstance {
+    instance: *mut c_void,
+    commit: unsafe fn(*mut c_void),
+    rollback: unsafe fn(*mut c_void),
+    drop: unsafe fn(*mut c_void),
+}
+
+thread_local! {
+    static CALLBACK_REGISTERED: Cell<bool> = const { Cell::new(false) };
+    static PENDING_INSTANCES: RefCell<Vec<PendingInstance>> = const { RefCell::new(Vec::new()) };
+}
+
+unsafe fn commit_instance<E: Into<ErrorReport>, W: ForeignDataWrapper<E>>(instance: *mut c_void) {
+    let instance = &mut *(instance as *mut W);
+    instance.commit().report_unwrap();
+}
+
+unsafe fn rollback_instance<E: Into<ErrorReport>, W: ForeignDataWrapper<E>>(instance: *mut c_void) {
+    let instance = &mut *(instance as *mut W);
+    if let Err(err) = instance.rollback() {
+        // the transaction is already aborting, so don't raise another error
+        let err: ErrorReport = err.into();
+        err.report(PgLogLevel::WARNING);
+    }
+}
+
+unsafe fn drop_instance<W>(instance: *mut c_void) {
+    drop(Box::from_raw(instance as *mut W));
+}
+
+// register the transaction callback once for the backend
+pub(crate) fn register_callback() {
+    if !CALLBACK_REGISTERED.get() {
+        unsafe {
+            pg_sys::RegisterXactCallback(Some(xact_callback), ptr::null_mut());
+        }
+        CALLBACK_REGISTERED.set(true);
+    }
+}
+
+// hand over the instance which finished a modification to current
+// transaction, its commit() or rollback() will be called at transaction end
+// and then it will be dropped
+pub(crate) fn register_instance<E: Into<ErrorReport>, W: ForeignDataWrapper<E>>(instance: W) {
+    register_callback();
+
+    let pending = PendingInstance {
+        instance: Box::into_raw(Box::new(instance)) as _,
+        commit: commit_instance::<E, W>,
+        rollback: rollback_instance::<E, W>,
+        drop: drop_instance::<W>,
+    };
+    PENDING_INSTANCES.with_borrow_mut(|instances| instances.push(pending));
+}
+
+#[pg_guard]
+extern "C" fn xact_callback(event: pg_sys::XactEvent::Type, _arg: *mut c_void) {
+    match event {
+        pg_sys::XactEvent::XACT_EVENT_PRE_COMMIT
+        | pg_sys::XactEvent::XACT_EVENT_PARALLEL_PRE_COMMIT => {
+            debug2!("---> xact_callback: pre commit");
+
+            // an instance is removed only after it is committed, so if commit
+            // fails the failed one and the rest are all rolled back on abort
+            while let Some(pending) =
+                PENDING_INSTANCES.with_borrow(|instances| instances.first().copied())
+            {
+                unsafe {
+                    (pending.commit)(pending.instance);
+                    PENDING_INSTANCES.with_borrow_mut(|instances| instances.remove(0));
+                    (pending.drop)(pending.instance);
+                }
+            }
+        }
+        pg_sys::XactEvent::XACT_EVENT_COMMIT
+        | pg_sys::XactEvent::XACT_EVENT_PARALLEL_COMMIT
+        | pg_sys::XactEvent::XACT_EVENT_PREPARE => {
+            write_buffer::clear();
+        }
+        pg_sys::XactEvent::XACT_EVENT_ABORT | pg_sys::XactEvent::XACT_EVENT_PARALLEL_ABORT => {
+            debug2!("---> xact_callback: abort");
+
+            write_buffer::clear();
+
+            let pendings = PENDING_INSTANCES.take();
+            for pending in pendings {
+                unsafe {
+                    (pending.rollback)(pending.instance);
+                    (pending.drop)(pending.instance);
+                }
+            }
+        }
+        pg_sys::XactEvent::XACT_EVENT_PRE_PREPARE => {
+            if PENDING_INSTANCES.with_borrow(|instances| !instances.is_empty()) {
+                report_error(
+                    PgSqlErrorCode::ERRCODE_FEATURE_NOT_SUPPORTED,
+                    "cannot PREPARE a transaction that has modified foreign tables",
+                );
+            }
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,623 @@
+#[allow(warnings)]
+mod bindings;
+use serde_json::{json, Map as JsonMap, Value as JsonValue};
+use urlencoding::encode;
+
+use bindings::{
+    exports::supabase::wrappers::routines::Guest,
+    supabase::wrappers::{
+        http, stats, time,
+        types::{Cell, Column, Context, FdwError, FdwResult, OptionsType, Row, TypeOid, Value},
+        utils,
+    },
+};
+
+// next page position of a paginated list request
+#[derive(Debug, Clone)]
+enum Page {
+    Number(i64),
+    Cursor(String),
+}
+
+// list request url and the parent id field injected to each row
+type ListTarget = (String, Option<(&'static str, String)>);
+
+#[derive(Debug, Default)]
+struct CloudflareFdw {
+    base_url: String,
+    account_id: String,
+    headers: Vec<(String, String)>,
+    object: String,
+    zone_id: Option<String>,
+    bucket: Option<String>,
+    namespace_id: Option<String>,
+    next_page: Option<Page>,
+    src_rows: Vec<JsonValue>,
+    src_idx: usize,
+}
+
+static mut INSTANCE: *mut CloudflareFdw = std::ptr::null_mut::<CloudflareFdw>();
+static FDW_NAME: &str = "CloudflareFdw";
+
+// default list page size
+// ref: https://developers.cloudflare.com/fundamentals/api/how-to/make-api-calls/#pagination
+static PAGE_SIZE: usize = 50;
+
+impl CloudflareFdw {
+    fn init() {
+        let instance = Self::default();
+        unsafe {
+            INSTANCE = Box::leak(Box::new(instance));
+        }
+    }
+
+    fn this_mut() -> &'static mut Self {
+        unsafe { &mut (*INSTANCE) }
+    }
+
+    // get parent object id from table option first, then from pushed down quals
+    fn parent_id(ctx: &Context, opt: &Option<String>, field: &str) -> Option<String> {
+        if opt.is_some() {
+            return opt.clone();
+        }
+        ctx.get_quals()
+            .iter()
+            .find(|q| q.field() == field && q.operator() == "=" && !q.use_or())
+            .and_then(|q| match q.value() {
+                Value::Cell(Cell::String(s)) => Some(s),
+                _ => None,
+            })
+    }
+
+    // get the list request url for current object
+    fn list_url(&self, ctx: &Context) -> Result<ListTarget, FdwError> {
+        let account_id = encode(&self.account_id);
+        let ret = match self.object.as_str() {
+            "zones" => (format!("{}/zones", self.base_url), None),
+            "dns_records" => {
+                let zone_id = Self::parent_id(ctx, &self.zone_id, "zone_id")
+                    .ok_or("'zone_id' must be specified in table option or where clause")?;
+                (
+                    format!("{}/zones/{}/dns_records", self.base_url, encode(&zone_id)),
+                    Some(("zone_id", zone_id)),
+                )
+            }
+            "r2_buckets" => (
+                format!("{}/accounts/{}/r2/buckets", self.base_url, account_id),
+                None,
+            ),
+            "r2_objects" => {
+                let bucket = Self::parent_id(ctx, &self.bucket, "bucket")
+                    .ok_or("'bucket' must be specified in table option or where clause")?;
+                (
+                    format!(
+                        "{}/accounts/{}/r2/buckets/{}/objects",
+                        self.base_url,
+                        account_id,
+                        encode(&bucket)
+                    ),
+                    Some(("bucket", bucket)),
+                )
+            }
+            "kv_namespaces" => (
+                format!(
+                    "{}/accounts/{}/storage/kv/namespaces",
+                    self.base_url, account_id
+                ),
+                None,
+            ),
+            "kv_keys" => {
+                let ns_id = Self::parent_id(ctx, &self.namespace_id, "namespace_id")
+                    .ok_or("'namespace_id' must be specified in table option or where clause")?;
+                (
+                    format!(
+                        "{}/accounts/{}/storage/kv/namespaces/{}/keys",
+                        self.base_url,
+                        account_id,
+                        encode(&ns_id)
+                    ),
+                    Some(("namespace_id", ns_id)),
+                )
+            }
+            _ => return Err(format!("object '{}' is not supported", self.object)),
+        };
+        Ok(ret)
+    }
+
+    // check if current object list uses page number based pagination, others
+    // use cursor based pagination
+    fn paged_by_number(&self) -> bool {
+        matches!(
+            self.object.as_str(),
+            "zones" | "dns_records" | "kv_namespaces"
+        )
+    }
+
+    // send request, retry when rate limited and check for API errors
+    fn send_request(&self, req: &http::Request) -> Result<JsonValue, FdwError> {
+        // max delay times when encouter HTTP 429 - Too Many Requests response
+        const MAX_DELAY_TIMES: usize = 5;
+        let mut delay_times = 0;
+
+        loop {
+            let resp = match req.method {
+                http::Method::Get => http::get(req)?,
+                http::Method::Post => http::post(req)?,
+                http::Method::Patch => http::patch(req)?,
+                http::Method::Delete => http::delete(req)?,
+                http::Method::Put => http::put(req)?,
+            };
+
+            // idle for 2 seconds for retry when got rate limited error
+            // ref: https://developers.cloudflare.com/fundamentals/api/reference/limits/
+            if resp.status_code == 429 {
+                delay_times += 1;
+                if delay_times >= MAX_DELAY_TIMES {
+                    return Err("API rate limit exceeded".to_owned());
+                }
+                time::sleep(2000);
+                continue;
+            }
+
+            http::error_for_status(&resp).map_err(|err| format!("{}: {}", err, resp.body))?;
+
+            let resp_json: JsonValue =
+                serde_json::from_str(&resp.body).map_err(|e| e.to_string())?;
+
+            // check for API request errors, GraphQL API reports errors in 'errors' field only
+            let success = resp_json["success"].as_bool().unwrap_or(true);
+            if let Some(errors) = resp_json["errors"].as_array() {
+                if !success || !errors.is_empty() {
+                    return Err(format!("API request failed with error {:?}", errors));
+                }
+            }
+
+            stats::inc_stats(FDW_NAME, stats::Metric::BytesIn, resp.body.len() as i64);
+
+            return Ok(resp_json);
+        }
+    }
+
+    // make list request to Cloudflare REST API
+    fn fetch_list(&mut self, ctx: &Context) -> FdwResult {
+        let (url, inject) = self.list_url(ctx)?;
+        let url = match &self.next_page {
+            Some(Page::Number(page)) => format!("{}?per_page={}&page={}", url, PAGE_SIZE, page),
+            Some(Page::Cursor(cursor)) => format!("{}?cursor={}", url, encode(cursor)),
+            None if self.paged_by_number() => format!("{}?per_page={}", url, PAGE_SIZE),
+            None => url,
+        };
+        let req = http::Request {
+            method: http::Method::Get,
+            url,
+            headers: self.headers.clone(),
+            body: String::default(),
+        };
+        let resp_json = self.send_request(&req)?;
+
+        // R2 bucket list is wrapped in an object
+        let result = resp_json
+            .pointer("/result/buckets")
+            .or_else(|| resp_json.pointer("/result"))
+            .and_then(|v| v.as_array())
+            .ok_or("cannot get query result data")?;
+        self.src_rows = result
+            .iter()
+            .map(|row| {
+                let mut row = row.clone();
+                if let (Some((field, value)), Some(obj)) = (&inject, row.as_object_mut()) {
+                    obj.insert(field.to_string(), JsonValue::String(value.clone()));
+                }
+                row
+            })
+            .collect();
+        self.src_idx = 0;
+
+        // deal with pagination to save next page position
+        let result_info = resp_json.pointer("/result_info");
+        let cursor = result_info
+            .and_then(|v| v.get("cursor"))
+            .and_then(|v| v.as_str())
+            .filter(|v| !v.is_empty());
+        let page = result_info
+            .and_then(|v| v.get("page"))
+            .and_then(|v| v.as_i64());
+        let total_pages = result_info
+            .and_then(|v| v.get("total_pages"))
+            .and_then(|v| v.as_i64());
+        self.next_page = if self.src_rows.is_empty() {
+            None
+        } else if !self.paged_by_number() {
+            cursor.map(|c| Page::Cursor(c.to_owned()))
+        } else {
+            match (page, total_pages) {
+                (Some(page), Some(total_pages)) if page < total_pages => {
+                    Some(Page::Number(page + 1))
+                }
+                _ => None,
+            }
+        };
+
+        Ok(())
+    }
+
+    // make zone analytics request to Cloudflare GraphQL API, the date range
+    // quals are pushed down as query filter
+    // ref: https://developers.cloudflare.com/analytics/graphql-api/
+    fn fetch_analytics(&mut self, ctx: &Context) -> FdwResult {
+        let zone_id = Self::parent_id(ctx, &self.zone_id, "zone_id")
+            .ok_or("'zone_id' must be specified in table option or where clause")?;
+
+        // date filter fields, each key only appears once in the input object
+        let mut filter: Vec<(&str, String)> = Vec::new();
+        for qual in ctx.get_quals().iter().filter(|q| q.field() == "date") {
+            let key = match qual.operator().as_str() {
+                "=" => "date",
+                ">" => "date_gt",
+                ">=" => "date_geq",
+                "<" => "date_lt",
+                "<=" => "date_leq",
+                _ => continue,
+            };
+            if let Value::Cell(Cell::Date(secs)) = qual.value() {
+                let date = time::epoch_ms_to_rfc3339(secs * 1_000_000)?;
+                filter.retain(|(k, _)| *k != key);
+                filter.push((key, date[..10].to_owned()));
+            }
+        }
+
+        // GraphQL API requires a time range, default to the last 7 days
+        if filter.is_empty() {
+            let start = time::epoch_secs() - 7 * 24 * 3600;
+            let date = time::epoch_ms_to_rfc3339(start * 1_000_000)?;
+            filter.push(("date_geq", date[..10].to_owned()));
+        }
+
+        let query = format!(
+            r#"query {{
+                viewer {{
+                    zones(filter: {{ zoneTag: {} }}) {{
+                        httpRequests1dGroups(limit: 10000, filter: {}, orderBy: [date_ASC]) {{
+                            dimensions {{ date }}
+                            sum {{ requests bytes cachedRequests cachedBytes threats pageViews }}
+                            uniq {{ uniques }}
+                        }}
+                    }}
+                }}
+            }}"#,
+            // zone id is quoted and escaped as a JSON string
+            json!(zone_id),
+            // GraphQL input object keys are not quoted, values are quoted as
+            // JSON strings
+            format!(
+                "{{ {} }}",
+                filter
+                    .iter()
+                    .map(|(key, value)| format!("{}: {}", key, json!(value)))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        );
+        let req = http::Request {
+            method: http::Method::Post,
+            url: format!("{}/graphql", self.base_url),
+            headers: self.headers.clone(),
+            body: json!({ "query": query }).to_string(),
+        };
+        let resp_json = self.send_request(&req)?;
+
+        // flatten each group to a row
+        let groups = resp_json
+            .pointer("/data/viewer/zones/0/httpRequests1dGroups")
+            .and_then(|v| v.as_array())
+            .cloned()
+            .unwrap_or_default();
+        self.src_rows = groups
+            .iter()
+            .map(|g| {
+                json!({
+                    "zone_id": zone_id,
+                    "date": g.pointer("/dimensions/date"),
+                    "requests": g.pointer("/sum/requests"),
+                    "bytes": g.pointer("/sum/bytes"),
+                    "cached_requests": g.pointer("/sum/cachedRequests"),
+                    "cached_bytes": g.pointer("/sum/cachedBytes"),
+                    "threats": g.pointer("/sum/threats"),
+                    "page_views": g.pointer("/sum/pageViews"),
+                    "uniques": g.pointer("/uniq/uniques"),
+                })
+            })
+            .collect();
+        self.src_idx = 0;
+        self.next_page = None;
+
+        Ok(())
+    }
+
+    fn fetch_source_data(&mut self, ctx: &Context) -> FdwResult {
+        if self.object == "zone_analytics" {
+            self.fetch_analytics(ctx)
+        } else {
+            self.fetch_list(ctx)
+        }
+    }
+
+    // convert Cloudflare API response data field to a cell
+    fn src_to_cell(&self, src_row: &JsonValue, tgt_col: &Column) -> Result<Option<Cell>, FdwError> {
+        let tgt_col_name = tgt_col.name();
+
+        // put all properties into 'attrs' JSON column
+        if &tgt_col_name == "attrs" {
+            return Ok(Some(Cell::Json(src_row.to_string())));
+        }
+
+        // optional fields may be omitted in the response, take them as null
+        let Some(src) = src_row.as_object().and_then(|v| v.get(&tgt_col_name)) else {
+            return Ok(None);
+        };
+
+        // column type mapping
+        let cell = match tgt_col.type_oid() {
+            TypeOid::Bool => src.as_bool().map(Cell::Bool),
+            TypeOid::I8 => src.as_i64().map(|v| Cell::I8(v as i8)),
+            TypeOid::I16 => src.as_i64().map(|v| Cell::I16(v as i16)),
+            TypeOid::F32 => src.as_f64().map(|v| Cell::F32(v as f32)),
+            TypeOid::I32 => src.as_i64().map(|v| Cell::I32(v as i32)),
+            TypeOid::F64 => src.as_f64().map(Cell::F64),
+            TypeOid::I64 => src.as_i64().map(Cell::I64),
+            TypeOid::Numeric => src.as_f64().map(Cell::Numeric),
+            TypeOid::String => src.as_str().map(|v| Cell::String(v.to_owned())),
+            TypeOid::Date => {
+                // date can be in 'YYYY-MM-DD' or RFC3339 format
+                if let Some(s) = src.as_str().and_then(|s| s.get(..10)) {
+                    let ts = time::parse_from_rfc3339(&format!("{}T00:00:00Z", s))?;
+                    Some(Cell::Date(ts / 1_000_000))
+                } else {
+                    None
+                }
+            }
+            TypeOid::Timestamp => {
+                if let Some(s) = src.as_str() {
+                    let ts = time::parse_from_rfc3339(s)?;
+                    Some(Cell::Timestamp(ts))
+                } else {
+                    None
+                }
+            }
+            TypeOid::Timestamptz => {
+                if let Some(s) = src.as_str() {
+                    let ts = time::parse_from_rfc3339(s)?;
+                    Some(Cell::Timestamptz(ts))
+                } else {
+                    None
+                }
+            }
+            TypeOid::Json => {
+                if src.is_object() || src.is_array() {
+                    Some(Cell::Json(src.to_string()))
+                } else {
+                    None
+                }
+            }
+        };
+
+        Ok(cell)
+    }
+
+    // convert a row to DNS record JSON string, which is used as request body
+    // ref: https://developers.cloudflare.com/api/operations/dns-records-for-a-zone-create-dns-record
+    fn row_to_body(&self, row: &Row) -> Result<String, FdwError> {
+        let mut map = JsonMap::new();
+
+        for (col_name, cell) in row.cols().iter().zip(row.cells().iter()) {
+            // zone_id is part of url and id is generated by Cloudflare
+            if col_name == "zone_id" || col_name == "id" || col_name == "attrs" {
+                continue;
+            }
+            let value = match cell {
+                Some(Cell::Bool(v)) => JsonValue::Bool(*v),
+                Some(Cell::I32(v)) => json!(v),
+                Some(Cell::I64(v)) => json!(v),
+                Some(Cell::String(v)) => JsonValue::String(v.to_string()),
+                Some(Cell::Json(v)) => {
+                    serde_json::from_str::<JsonValue>(v).map_err(|e| e.to_string())?
+                }
+                Some(_) => {
+                    return Err(format!("column '{}' type is not supported", col_name));
+                }
+                None => JsonValue::Null,
+            };
+            map.insert(col_name.to_owned(), value);
+        }
+
+        Ok(JsonValue::Object(map).to_string())
+    }
+
+    // get zone id for modifying DNS records, the updated row only has the
+    // changed columns so update and delete need the zone id table option
+    fn modify_zone_id(&self, row: Option<&Row>) -> Result<String, FdwError> {
+        if let Some(zone_id) = &self.zone_id {
+            return Ok(zone_id.clone());
+        }
+        let Some(row) = row else {
+            return Err(
+                "'zone_id' table option is required to update or delete DNS records".to_owned(),
+            );
+        };
+        row.cols()
+            .iter()
+            .zip(row.cells())
+            .find(|(col, _)| *col == "zone_id")
+            .and_then(|(_, cell)| match cell {
+                Some(Cell::String(s)) => Some(s),
+                _ => None,
+            })
+            .ok_or("'zone_id' must be specified in table option or inserted row".to_owned())
+    }
+
+    fn modify_dns_record(
+        &self,
+        method: http::Method,
+        zone_id: &str,
+        id: Option<&str>,
+        body: String,
+    ) -> FdwResult {
+        let mut url = format!("{}/zones/{}/dns_records", self.base_url, encode(zone_id));
+        if let Some(id) = id {
+            url.push_str(&format!("/{}", encode(id)));
+        }
+        let req = http::Request {
+            method,
+            url,
+            headers: self.headers.clone(),
+            body,
+        };
+        self.send_request(&req)?;
+        stats::inc_stats(FDW_NAME, stats::Metric::RowsOut, 1);
+        Ok(())
+    }
+}
+
+impl Guest for CloudflareFdw {
+    fn host_version_requirement() -> String {
+        // semver ref: https://docs.rs/semver/latest/semver/enum.Op.html
+        "^0.1.0".to_string()
+    }
+
+    fn init(ctx: &Context) -> FdwResult {
+        Self::init();
+        let this = Self::this_mut();
+
+        // get foreign server options
+        let opts = ctx.get_options(OptionsType::Server);
+        this.base_url = opts.require_or("api_url", "https://api.cloudflare.com/client/v4");
+        this.account_id = opts.require("account_id")?;
+        let api_token = match opts.get("api_token") {
+            Some(key) => key,
+            None => {
+                let token_id = opts.require("api_token_id")?;
+                utils::get_vault_secret(&token_id).unwrap_or_default()
+            }
+        };
+
+        // Cloudflare API authentication
+        // ref: https://developers.cloudflare.com/fundamentals/api/get-started/create-token/
+        this.headers.push((
+            "user-agent".to_owned(),
+            "Wrappers Cloudflare FDW".to_string(),
+        ));
+        this.headers
+            .push(("content-type".to_owned(), "application/json".to_string()));
+        this.headers
+            .push(("authorization".to_owned(), format!("Bearer {}", api_token)));
+
+        stats::inc_stats(FDW_NAME, stats::Metric::CreateTimes, 1);
+
+        Ok(())
+    }
+
+    fn begin_scan(ctx: &Context) -> FdwResult {
+        let this = Self::this_mut();
+        let opts = ctx.get_options(OptionsType::Table);
+        this.object = opts.require("object")?;
+        this.zone_id = opts.get("zone_id");
+        this.bucket = opts.get("bucket");
+        this.namespace_id = opts.get("namespace_id");
+
+        this.next_page = None;
+        this.fetch_source_data(ctx)
+    }
+
+    fn iter_scan(ctx: &Context, row: &Row) -> Result<Option<u32>, FdwError> {
+        let this = Self::this_mut();
+
+        // if all source rows are consumed
+        if this.src_idx >= this.src_rows.len() {
+            stats::inc_stats(FDW_NAME, stats::Metric::RowsIn, this.src_rows.len() as i64);
+            stats::inc_stats(FDW_NAME, stats::Metric::RowsOut, this.src_rows.len() as i64);
+
+            // if no more pages, stop the iter scan
+            if this.next_page.is_none() {
+                return Ok(None);
+            }
+
+            // otherwise, make another call to get next page data
+            this.fetch_source_data(ctx)?;
+            if this.src_rows.is_empty() {
+                return Ok(None);
+            }
+        }
+
+        // convert source row to Postgres row
+        let src_row = &this.src_rows[this.src_idx];
+        for tgt_col in ctx.get_columns() {
+            let cell = this.src_to_cell(src_row, &tgt_col)?;
+            row.push(cell.as_ref());
+        }
+
+        this.src_idx += 1;
+
+        Ok(Some(0))
+    }
+
+    fn re_scan(ctx: &Context) -> FdwResult {
+        let this = Self::this_mut();
+        this.next_page = None;
+        this.fetch_source_data(ctx)
+    }
+
+    fn end_scan(_ctx: &Context) -> FdwResult {
+        let this = Self::this_mut();
+        this.src_idx = 0;
+        this.src_rows.clear();
+        Ok(())
+    }
+
+    fn begin_modify(ctx: &Context) -> FdwResult {
+        let this = Self::this_mut();
+        let opts = ctx.get_options(OptionsType::Table);
+        this.object = opts.require("object")?;
+        this.zone_id = opts.get("zone_id");
+        if this.object != "dns_records" {
+            return Err(format!(
+                "object '{}' does not support data modify",
+                this.object
+            ));
+        }
+        Ok(())
+    }
+
+    fn insert(_ctx: &Context, row: &Row) -> FdwResult {
+        let this = Self::this_mut();
+        let zone_id = this.modify_zone_id(Some(row))?;
+        let body = this.row_to_body(row)?;
+        this.modify_dns_record(http::Method::Post, &zone_id, None, body)
+    }
+
+    fn update(_ctx: &Context, rowid: Cell, row: &Row) -> FdwResult {
+        let this = Self::this_mut();
+        let id = match rowid {
+            Cell::String(s) => s.clone(),
+            _ => return Err("invalid rowid column value".to_string()),
+        };
+        let zone_id = this.modify_zone_id(None)?;
+        let body = this.row_to_body(row)?;
+        this.modify_dns_record(http::Method::Patch, &zone_id, Some(&id), body)
+    }
+
+    fn delete(_ctx: &Context, rowid: Cell) -> FdwResult {
+        let this = Self::this_mut();
+        let id = match rowid {
+            Cell::String(s) => s.clone(),
+            _ => return Err("invalid rowid column value".to_string()),
+        };
+        let zone_id = this.modify_zone_id(None)?;
+        this.modify_dns_record(http::Method::Delete, &zone_id, Some(&id), String::default())
+    }
+
+    fn end_modify(_ctx: &Context) -> FdwResult {
+        Ok(())
+    }
+}
+
+bindings::export!(CloudflareFdw with_types_in bindings);
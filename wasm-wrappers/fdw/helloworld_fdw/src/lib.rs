@@ -30,7 +30,10 @@ impl HelloWorldFdw {
 impl Guest for HelloWorldFdw {
     fn host_version_requirement() -> String {
         // semver ref: https://docs.rs/semver/latest/semver/enum.Op.html
-        "^0.1.0".to_string()
+        //
+        // require the minimum host version of every host function used, see:
+        // https://github.com/supabase/wrappers/blob/main/docs/guides/wasm-advanced.md#version-compatibility
+        "^0.1.8".to_string()
     }
 
     fn init(_ctx: &Context) -> FdwResult {
@@ -0,0 +1,693 @@
+#[allow(warnings)]
+mod bindings;
+use serde_json::{json, Value as JsonValue};
+use urlencoding::encode;
+
+use bindings::{
+    exports::supabase::wrappers::routines::Guest,
+    supabase::wrappers::{
+        http, stats, time,
+        types::{Cell, Column, Context, FdwError, FdwResult, OptionsType, Row, TypeOid, Value},
+    },
+};
+
+#[derive(Debug, Default)]
+struct RegistryFdw {
+    headers: Vec<(String, String)>,
+    request_delay_ms: u64,
+    request_cnt: usize,
+    registry: String,
+    object: String,
+    api_url: String,
+    names: Vec<String>,
+    version: Option<String>,
+    fetch_downloads: bool,
+    src_rows: Vec<JsonValue>,
+    src_idx: usize,
+}
+
+static mut INSTANCE: *mut RegistryFdw = std::ptr::null_mut::<RegistryFdw>();
+static FDW_NAME: &str = "RegistryFdw";
+
+// max number of retries when rate limited by the registry
+static MAX_RETRIES: usize = 3;
+
+// parse a PyPI requirement string, for example:
+//   requests (>=2.0)
+//   charset-normalizer<4,>=2
+//   PySocks!=1.5.7,>=1.5.6; extra == "socks"
+// ref: https://packaging.python.org/en/latest/specifications/dependency-specifiers/
+fn parse_pypi_requirement(req: &str) -> (String, String, String, bool) {
+    let (spec, marker) = req.split_once(';').unwrap_or((req, ""));
+    let spec = spec.trim();
+    let name_end = spec
+        .find(|c: char| !(c.is_ascii_alphanumeric() || "._-".contains(c)))
+        .unwrap_or(spec.len());
+    let name = spec[..name_end].to_owned();
+    let requirement = spec[name_end..]
+        .trim()
+        .trim_start_matches('(')
+        .trim_end_matches(')')
+        .trim()
+        .to_owned();
+    let optional = marker.contains("extra");
+    let kind = if optional { "extra" } else { "normal" };
+    (name, requirement, kind.to_owned(), optional)
+}
+
+impl RegistryFdw {
+    fn init() {
+        let instance = Self::default();
+        unsafe {
+            INSTANCE = Box::leak(Box::new(instance));
+        }
+    }
+
+    fn this_mut() -> &'static mut Self {
+        unsafe { &mut (*INSTANCE) }
+    }
+
+    fn default_api_url(registry: &str) -> Result<&'static str, FdwError> {
+        match registry {
+            "crates" => Ok("https://crates.io/api/v1"),
+            "npm" => Ok("https://registry.npmjs.org"),
+            "pypi" => Ok("https://pypi.org/pypi"),
+            _ => Err(format!("registry '{}' is not supported", registry)),
+        }
+    }
+
+    // make a request to registry, be polite by keeping a delay between requests
+    // and backing off when rate limited
+    fn make_request(&mut self, url: &str) -> Result<Option<JsonValue>, FdwError> {
+        let req = http::Request {
+            method: http::Method::Get,
+            url: url.to_owned(),
+            headers: self.headers.clone(),
+            body: String::default(),
+        };
+
+        let mut retries = 0;
+        loop {
+            if self.request_cnt > 0 {
+                time::sleep(self.request_delay_ms);
+            }
+            self.request_cnt += 1;
+
+            let resp = http::get(&req)?;
+
+            // package not found, treat it as empty result
+            if resp.status_code == 404 {
+                return Ok(None);
+            }
+
+            // rate limited, wait for the time specified by the registry and retry
+            if resp.status_code == 429 && retries < MAX_RETRIES {
+                let retry_after = resp
+                    .headers
+                    .iter()
+                    .find(|h| h.0.eq_ignore_ascii_case("retry-after"))
+                    .and_then(|h| h.1.parse::<u64>().ok())
+                    .unwrap_or(1 << retries);
+                time::sleep(retry_after * 1000);
+                retries += 1;
+                continue;
+            }
+
+            http::error_for_status(&resp).map_err(|err| format!("{}: {}", err, resp.body))?;
+
+            stats::inc_stats(FDW_NAME, stats::Metric::BytesIn, resp.body.len() as i64);
+
+            let resp_json: JsonValue =
+                serde_json::from_str(&resp.body).map_err(|e| e.to_string())?;
+            return Ok(Some(resp_json));
+        }
+    }
+
+    // get package names and version from quals, name can be pushed down
+    // using '=' or 'in' operator
+    fn pushdown_quals(&mut self, ctx: &Context) -> FdwResult {
+        self.names.clear();
+        self.version = None;
+
+        for qual in ctx.get_quals() {
+            match (
+                qual.field().as_str(),
+                qual.operator().as_str(),
+                qual.value(),
+            ) {
+                ("name", "=", Value::Cell(Cell::String(s))) if !qual.use_or() => {
+                    self.names = vec![s];
+                }
+                ("name", "=", Value::Array(cells)) if qual.use_or() => {
+                    self.names = cells
+                        .iter()
+                        .filter_map(|c| match c {
+                            Cell::String(s) => Some(s.clone()),
+                            _ => None,
+                        })
+                        .collect();
+                }
+                ("version", "=", Value::Cell(Cell::String(s))) if !qual.use_or() => {
+                    self.version = Some(s);
+                }
+                _ => {}
+            }
+        }
+
+        if self.names.is_empty() {
+            return Err("'name' must be specified in where clause".to_owned());
+        }
+
+        Ok(())
+    }
+
+    // ---- crates.io ----
+    // ref: https://crates.io/data-access
+
+    fn fetch_crate(&mut self, name: &str) -> Result<Option<JsonValue>, FdwError> {
+        let url = format!("{}/crates/{}", self.api_url, encode(name));
+        self.make_request(&url)
+    }
+
+    fn crates_packages(&mut self, name: &str) -> Result<Vec<JsonValue>, FdwError> {
+        let Some(resp) = self.fetch_crate(name)? else {
+            return Ok(Vec::new());
+        };
+        let krate = &resp["crate"];
+        let latest = krate["max_stable_version"]
+            .as_str()
+            .or(krate["max_version"].as_str())
+            .unwrap_or_default();
+        let license = resp["versions"]
+            .as_array()
+            .and_then(|vers| vers.iter().find(|v| v["num"] == latest))
+            .map(|v| v["license"].clone())
+            .unwrap_or_default();
+        Ok(vec![json!({
+            "name": krate["name"],
+            "description": krate["description"],
+            "latest_version": latest,
+            "license": license,
+            "homepage": krate["homepage"],
+            "repository": krate["repository"],
+            "downloads": krate["downloads"],
+            "created_at": krate["created_at"],
+            "updated_at": krate["updated_at"],
+            "attrs": krate,
+        })])
+    }
+
+    fn crates_versions(&mut self, name: &str) -> Result<Vec<JsonValue>, FdwError> {
+        let Some(resp) = self.fetch_crate(name)? else {
+            return Ok(Vec::new());
+        };
+        let rows = resp["versions"]
+            .as_array()
+            .map(|vers| {
+                vers.iter()
+                    .map(|v| {
+                        json!({
+                            "name": v["crate"],
+                            "version": v["num"],
+                            "license": v["license"],
+                            "published_at": v["created_at"],
+                            "yanked": v["yanked"],
+                            "downloads": v["downloads"],
+                            "attrs": v,
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(rows)
+    }
+
+    fn crates_dependencies(&mut self, name: &str) -> Result<Vec<JsonValue>, FdwError> {
+        let version = match &self.version {
+            Some(version) => version.clone(),
+            None => {
+                let Some(resp) = self.fetch_crate(name)? else {
+                    return Ok(Vec::new());
+                };
+                let krate = &resp["crate"];
+                krate["max_stable_version"]
+                    .as_str()
+                    .or(krate["max_version"].as_str())
+                    .unwrap_or_default()
+                    .to_owned()
+            }
+        };
+        let url = format!(
+            "{}/crates/{}/{}/dependencies",
+            self.api_url,
+            encode(name),
+            encode(&version)
+        );
+        let Some(resp) = self.make_request(&url)? else {
+            return Ok(Vec::new());
+        };
+        let rows = resp["dependencies"]
+            .as_array()
+            .map(|deps| {
+                deps.iter()
+                    .map(|d| {
+                        json!({
+                            "name": name,
+                            "version": version,
+                            "dependency": d["crate_id"],
+                            "requirement": d["req"],
+                            "kind": d["kind"],
+                            "optional": d["optional"],
+                            "attrs": d,
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(rows)
+    }
+
+    // ---- npm ----
+    // ref: https://github.com/npm/registry/blob/main/docs/REGISTRY-API.md
+
+    fn fetch_npm_package(&mut self, name: &str) -> Result<Option<JsonValue>, FdwError> {
+        let url = format!("{}/{}", self.api_url, encode(name));
+        self.make_request(&url)
+    }
+
+    // get last month download count of a npm package
+    // ref: https://github.com/npm/registry/blob/main/docs/download-counts.md
+    fn fetch_npm_downloads(&mut self, name: &str) -> Result<JsonValue, FdwError> {
+        // scoped package name is not encoded as a whole by the downloads API
+        let name = name.split('/').map(encode).collect::<Vec<_>>().join("/");
+        let url = format!("https://api.npmjs.org/downloads/point/last-month/{}", name);
+        let downloads = self
+            .make_request(&url)?
+            .map(|resp| resp["downloads"].clone())
+            .unwrap_or_default();
+        Ok(downloads)
+    }
+
+    fn npm_packages(&mut self, name: &str) -> Result<Vec<JsonValue>, FdwError> {
+        let Some(mut pkg) = self.fetch_npm_package(name)? else {
+            return Ok(Vec::new());
+        };
+        let downloads = if self.fetch_downloads {
+            self.fetch_npm_downloads(name)?
+        } else {
+            JsonValue::Null
+        };
+        let latest = pkg["dist-tags"]["latest"].clone();
+        let repository = match &pkg["repository"] {
+            JsonValue::Object(repo) => repo.get("url").cloned().unwrap_or_default(),
+            repo => repo.clone(),
+        };
+        let mut row = json!({
+            "name": pkg["name"],
+            "description": pkg["description"],
+            "latest_version": latest,
+            "license": pkg["license"],
+            "homepage": pkg["homepage"],
+            "repository": repository,
+            "downloads": downloads,
+            "created_at": pkg["time"]["created"],
+            "updated_at": pkg["time"]["modified"],
+        });
+
+        // remove large fields from attrs
+        if let Some(obj) = pkg.as_object_mut() {
+            obj.remove("versions");
+            obj.remove("time");
+            obj.remove("readme");
+        }
+        row["attrs"] = pkg;
+        Ok(vec![row])
+    }
+
+    fn npm_versions(&mut self, name: &str) -> Result<Vec<JsonValue>, FdwError> {
+        let Some(pkg) = self.fetch_npm_package(name)? else {
+            return Ok(Vec::new());
+        };
+        let rows = pkg["versions"]
+            .as_object()
+            .map(|vers| {
+                vers.iter()
+                    .map(|(num, v)| {
+                        json!({
+                            "name": pkg["name"],
+                            "version": num,
+                            "license": v["license"],
+                            "published_at": pkg["time"][num],
+                            "yanked": v.get("deprecated").is_some(),
+                            "downloads": null,
+                            "attrs": v,
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(rows)
+    }
+
+    fn npm_dependencies(&mut self, name: &str) -> Result<Vec<JsonValue>, FdwError> {
+        let tag = self.version.clone().unwrap_or("latest".to_owned());
+        let url = format!("{}/{}/{}", self.api_url, encode(name), encode(&tag));
+        let Some(ver) = self.make_request(&url)? else {
+            return Ok(Vec::new());
+        };
+
+        let mut rows = Vec::new();
+        for (field, kind, optional) in [
+            ("dependencies", "normal", false),
+            ("devDependencies", "dev", false),
+            ("peerDependencies", "peer", false),
+            ("optionalDependencies", "normal", true),
+        ] {
+            let Some(deps) = ver[field].as_object() else {
+                continue;
+            };
+            for (dep, req) in deps {
+                rows.push(json!({
+                    "name": ver["name"],
+                    "version": ver["version"],
+                    "dependency": dep,
+                    "requirement": req,
+                    "kind": kind,
+                    "optional": optional,
+                    "attrs": { dep: req },
+                }));
+            }
+        }
+        Ok(rows)
+    }
+
+    // ---- PyPI ----
+    // ref: https://warehouse.pypa.io/api-reference/json.html
+
+    fn fetch_pypi_project(
+        &mut self,
+        name: &str,
+        version: Option<&str>,
+    ) -> Result<Option<JsonValue>, FdwError> {
+        let url = match version {
+            Some(version) => format!("{}/{}/{}/json", self.api_url, encode(name), encode(version)),
+            None => format!("{}/{}/json", self.api_url, encode(name)),
+        };
+        self.make_request(&url)
+    }
+
+    // get first upload time of a PyPI release
+    fn pypi_upload_time(files: &JsonValue) -> JsonValue {
+        files
+            .as_array()
+            .and_then(|files| {
+                files
+                    .iter()
+                    .filter_map(|f| f["upload_time_iso_8601"].as_str())
+                    .min()
+            })
+            .map(|t| json!(t))
+            .unwrap_or_default()
+    }
+
+    fn pypi_packages(&mut self, name: &str) -> Result<Vec<JsonValue>, FdwError> {
+        let Some(proj) = self.fetch_pypi_project(name, None)? else {
+            return Ok(Vec::new());
+        };
+        let info = &proj["info"];
+        let repository = info["project_urls"]
+            .as_object()
+            .and_then(|urls| {
+                urls.iter()
+                    .find(|(k, _)| {
+                        let k = k.to_lowercase();
+                        k == "source" || k == "repository" || k == "source code"
+                    })
+                    .map(|(_, v)| v.clone())
+            })
+            .unwrap_or_default();
+        let releases = proj["releases"].as_object().cloned().unwrap_or_default();
+        let created_at = releases
+            .values()
+            .filter_map(|files| Self::pypi_upload_time(files).as_str().map(|s| s.to_owned()))
+            .min();
+        let updated_at = releases
+            .values()
+            .filter_map(|files| Self::pypi_upload_time(files).as_str().map(|s| s.to_owned()))
+            .max();
+        Ok(vec![json!({
+            "name": info["name"],
+            "description": info["summary"],
+            "latest_version": info["version"],
+            "license": info["license"],
+            "homepage": info["home_page"],
+            "repository": repository,
+            "downloads": null,
+            "created_at": created_at,
+            "updated_at": updated_at,
+            "attrs": info,
+        })])
+    }
+
+    fn pypi_versions(&mut self, name: &str) -> Result<Vec<JsonValue>, FdwError> {
+        let Some(proj) = self.fetch_pypi_project(name, None)? else {
+            return Ok(Vec::new());
+        };
+        let releases = proj["releases"].as_object().cloned().unwrap_or_default();
+        let rows = releases
+            .iter()
+            .map(|(num, files)| {
+                let yanked = files
+                    .as_array()
+                    .map(|files| !files.is_empty() && files.iter().all(|f| f["yanked"] == true))
+                    .unwrap_or_default();
+                json!({
+                    "name": proj["info"]["name"],
+                    "version": num,
+                    "license": null,
+                    "published_at": Self::pypi_upload_time(files),
+                    "yanked": yanked,
+                    "downloads": null,
+                    "attrs": { "files": files },
+                })
+            })
+            .collect();
+        Ok(rows)
+    }
+
+    fn pypi_dependencies(&mut self, name: &str) -> Result<Vec<JsonValue>, FdwError> {
+        let version = self.version.clone();
+        let Some(proj) = self.fetch_pypi_project(name, version.as_deref())? else {
+            return Ok(Vec::new());
+        };
+        let info = &proj["info"];
+        let rows = info["requires_dist"]
+            .as_array()
+            .map(|reqs| {
+                reqs.iter()
+                    .filter_map(|r| r.as_str())
+                    .map(|r| {
+                        let (dep, req, kind, optional) = parse_pypi_requirement(r);
+                        json!({
+                            "name": info["name"],
+                            "version": info["version"],
+                            "dependency": dep,
+                            "requirement": req,
+                            "kind": kind,
+                            "optional": optional,
+                            "attrs": { "requires_dist": r },
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(rows)
+    }
+
+    fn fetch_source_data(&mut self) -> FdwResult {
+        self.src_rows.clear();
+        self.src_idx = 0;
+
+        let names = self.names.clone();
+        for name in names.iter() {
+            let rows = match (self.registry.as_str(), self.object.as_str()) {
+                ("crates", "packages") => self.crates_packages(name)?,
+                ("crates", "versions") => self.crates_versions(name)?,
+                ("crates", "dependencies") => self.crates_dependencies(name)?,
+                ("npm", "packages") => self.npm_packages(name)?,
+                ("npm", "versions") => self.npm_versions(name)?,
+                ("npm", "dependencies") => self.npm_dependencies(name)?,
+                ("pypi", "packages") => self.pypi_packages(name)?,
+                ("pypi", "versions") => self.pypi_versions(name)?,
+                ("pypi", "dependencies") => self.pypi_dependencies(name)?,
+                _ => return Err(format!("object '{}' is not supported", self.object)),
+            };
+            self.src_rows.extend(rows);
+        }
+
+        stats::inc_stats(FDW_NAME, stats::Metric::RowsIn, self.src_rows.len() as i64);
+
+        Ok(())
+    }
+
+    // convert normalized registry data to a cell
+    fn src_to_cell(&self, src_row: &JsonValue, tgt_col: &Column) -> Result<Option<Cell>, FdwError> {
+        let tgt_col_name = tgt_col.name();
+
+        let src = src_row
+            .as_object()
+            .and_then(|v| v.get(&tgt_col_name))
+            .ok_or(format!("source column '{}' not found", tgt_col_name))?;
+
+        // column type mapping
+        let cell = match tgt_col.type_oid() {
+            TypeOid::Bool => src.as_bool().map(Cell::Bool),
+            TypeOid::I32 => src.as_i64().map(|v| Cell::I32(v as i32)),
+            TypeOid::I64 => src.as_i64().map(Cell::I64),
+            TypeOid::F64 => src.as_f64().map(Cell::F64),
+            TypeOid::Numeric => src.as_f64().map(Cell::Numeric),
+            TypeOid::String => match src {
+                JsonValue::Null => None,
+                JsonValue::String(s) => Some(Cell::String(s.to_owned())),
+                _ => Some(Cell::String(src.to_string())),
+            },
+            TypeOid::Timestamp => {
+                if let Some(s) = src.as_str() {
+                    let ts = time::parse_from_rfc3339(s)?;
+                    Some(Cell::Timestamp(ts))
+                } else {
+                    None
+                }
+            }
+            TypeOid::Timestamptz => {
+                if let Some(s) = src.as_str() {
+                    let ts = time::parse_from_rfc3339(s)?;
+                    Some(Cell::Timestamptz(ts))
+                } else {
+                    None
+                }
+            }
+            TypeOid::Json => {
+                if src.is_null() {
+                    None
+                } else {
+                    Some(Cell::Json(src.to_string()))
+                }
+            }
+            _ => {
+                return Err(format!(
+                    "target column '{}' type is not supported",
+                    tgt_col_name
+                ));
+            }
+        };
+
+        Ok(cell)
+    }
+}
+
+impl Guest for RegistryFdw {
+    fn host_version_requirement() -> String {
+        // semver ref: https://docs.rs/semver/latest/semver/enum.Op.html
+        "^0.1.0".to_string()
+    }
+
+    fn init(ctx: &Context) -> FdwResult {
+        Self::init();
+        let this = Self::this_mut();
+
+        let opts = ctx.get_options(OptionsType::Server);
+
+        // crates.io requires a user agent which identifies the client
+        // ref: https://crates.io/data-access#api
+        let user_agent = opts.require_or(
+            "user_agent",
+            "Wrappers Package Registry FDW (https://github.com/supabase/wrappers)",
+        );
+        this.headers.push(("user-agent".to_owned(), user_agent));
+        this.headers
+            .push(("accept".to_owned(), "application/json".to_string()));
+
+        // default to 1 request per second as required by crates.io crawler policy
+        this.request_delay_ms = opts
+            .require_or("request_delay_ms", "1000")
+            .parse::<u64>()
+            .map_err(|e| format!("invalid request_delay_ms option: {}", e))?;
+
+        stats::inc_stats(FDW_NAME, stats::Metric::CreateTimes, 1);
+
+        Ok(())
+    }
+
+    fn begin_scan(ctx: &Context) -> FdwResult {
+        let this = Self::this_mut();
+        let opts = ctx.get_options(OptionsType::Table);
+        this.registry = opts.require("registry")?;
+        this.object = opts.require("object")?;
+        this.api_url = opts.require_or("api_url", Self::default_api_url(&this.registry)?);
+        this.api_url = this.api_url.trim_end_matches('/').to_owned();
+        this.request_cnt = 0;
+
+        // npm download counts need an extra request, only fetch it when it is selected
+        this.fetch_downloads = ctx
+            .get_columns()
+            .iter()
+            .any(|col| col.name() == "downloads");
+
+        this.pushdown_quals(ctx)?;
+        this.fetch_source_data()
+    }
+
+    fn iter_scan(ctx: &Context, row: &Row) -> Result<Option<u32>, FdwError> {
+        let this = Self::this_mut();
+
+        if this.src_idx >= this.src_rows.len() {
+            stats::inc_stats(FDW_NAME, stats::Metric::RowsOut, this.src_rows.len() as i64);
+            return Ok(None);
+        }
+
+        let src_row = &this.src_rows[this.src_idx];
+        for tgt_col in ctx.get_columns() {
+            let cell = this.src_to_cell(src_row, &tgt_col)?;
+            row.push(cell.as_ref());
+        }
+
+        this.src_idx += 1;
+
+        Ok(Some(0))
+    }
+
+    fn re_scan(ctx: &Context) -> FdwResult {
+        let this = Self::this_mut();
+        this.pushdown_quals(ctx)?;
+        this.fetch_source_data()
+    }
+
+    fn end_scan(_ctx: &Context) -> FdwResult {
+        let this = Self::this_mut();
+        this.src_rows.clear();
+        Ok(())
+    }
+
+    fn begin_modify(_ctx: &Context) -> FdwResult {
+        Err("modify on foreign table is not supported".to_owned())
+    }
+
+    fn insert(_ctx: &Context, _row: &Row) -> FdwResult {
+        Ok(())
+    }
+
+    fn update(_ctx: &Context, _rowid: Cell, _row: &Row) -> FdwResult {
+        Ok(())
+    }
+
+    fn delete(_ctx: &Context, _rowid: Cell) -> FdwResult {
+        Ok(())
+    }
+
+    fn end_modify(_ctx: &Context) -> FdwResult {
+        Ok(())
+    }
+}
+
+bindings::export!(RegistryFdw with_types_in bindings);
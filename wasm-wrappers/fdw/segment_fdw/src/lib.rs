@@ -0,0 +1,434 @@
+#[allow(warnings)]
+mod bindings;
+use serde_json::{Map as JsonMap, Value as JsonValue};
+use urlencoding::encode;
+
+use bindings::{
+    exports::supabase::wrappers::routines::Guest,
+    supabase::wrappers::{
+        http, stats, time,
+        types::{Cell, Column, Context, FdwError, FdwResult, OptionsType, Row, TypeOid, Value},
+        utils,
+    },
+};
+
+#[derive(Debug, Default)]
+struct SegmentFdw {
+    base_url: String,
+    tracking_url: String,
+    headers: Vec<(String, String)>,
+    write_key: Option<String>,
+    object: String,
+    space_id: Option<String>,
+    cursor: Option<String>,
+    src_rows: Vec<JsonValue>,
+    src_idx: usize,
+}
+
+static mut INSTANCE: *mut SegmentFdw = std::ptr::null_mut::<SegmentFdw>();
+static FDW_NAME: &str = "SegmentFdw";
+
+// maximum page size of Segment Public API list endpoints
+// ref: https://docs.segmentapis.com/tag/Pagination
+static PAGE_SIZE: usize = 200;
+
+// convert a snake_case column name to the camelCase name used by Segment
+fn to_camel_case(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len());
+    let mut upper = false;
+    for c in s.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            ret.push(c.to_ascii_uppercase());
+            upper = false;
+        } else {
+            ret.push(c);
+        }
+    }
+    ret
+}
+
+impl SegmentFdw {
+    fn init() {
+        let instance = Self::default();
+        unsafe {
+            INSTANCE = Box::leak(Box::new(instance));
+        }
+    }
+
+    fn this_mut() -> &'static mut Self {
+        unsafe { &mut (*INSTANCE) }
+    }
+
+    // find a qual by field name and return its string value
+    fn qual_value(ctx: &Context, field: &str) -> Result<Option<String>, FdwError> {
+        let Some(qual) = ctx
+            .get_quals()
+            .into_iter()
+            .find(|q| q.field() == field && q.operator() == "=" && !q.use_or())
+        else {
+            return Ok(None);
+        };
+        let ret = match qual.value() {
+            Value::Cell(Cell::String(s)) => s,
+            Value::Cell(Cell::Timestamp(ts)) | Value::Cell(Cell::Timestamptz(ts)) => {
+                time::epoch_ms_to_rfc3339(ts)?
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(ret))
+    }
+
+    // get the request url path and the JSON pointer to result data for current object
+    fn endpoint(&self, ctx: &Context) -> Result<(String, &'static str), FdwError> {
+        let ret = match self.object.as_str() {
+            "sources" => ("sources".to_owned(), "/data/sources"),
+            "destinations" => ("destinations".to_owned(), "/data/destinations"),
+            "warehouses" => ("warehouses".to_owned(), "/data/warehouses"),
+            "audiences" => {
+                let space_id = self
+                    .space_id
+                    .as_ref()
+                    .ok_or("table option 'space_id' is required for audiences")?;
+                (
+                    format!("spaces/{}/audiences", encode(space_id)),
+                    "/data/audiences",
+                )
+            }
+            "delivery_metrics" => {
+                // delivery metrics is reported per source and destination pair,
+                // so both ids must be pushed down
+                // ref: https://docs.segmentapis.com/tag/Destinations#operation/getDeliveryMetricsSummaryFromDestination
+                let dest_id = Self::qual_value(ctx, "destination_id")?
+                    .ok_or("'destination_id' must be specified in where clause")?;
+                let source_id = Self::qual_value(ctx, "source_id")?
+                    .ok_or("'source_id' must be specified in where clause")?;
+                let mut path = format!(
+                    "destinations/{}/delivery-metrics?sourceId={}",
+                    encode(&dest_id),
+                    encode(&source_id)
+                );
+                if let Some(start_time) = Self::qual_value(ctx, "start_time")? {
+                    path.push_str(&format!("&startTime={}", encode(&start_time)));
+                }
+                if let Some(end_time) = Self::qual_value(ctx, "end_time")? {
+                    path.push_str(&format!("&endTime={}", encode(&end_time)));
+                }
+                (path, "/data/deliveryMetricsSummary")
+            }
+            "track_events" => {
+                return Err("object 'track_events' is insert-only".to_owned());
+            }
+            _ => return Err(format!("object '{}' is not supported", self.object)),
+        };
+        Ok(ret)
+    }
+
+    // make the request to Segment Public API
+    fn make_request(&mut self, ctx: &Context) -> FdwResult {
+        let (path, data_ptr) = self.endpoint(ctx)?;
+        let is_list = self.object != "delivery_metrics";
+
+        let mut url = format!("{}/{}", self.base_url, path);
+        if is_list {
+            url.push_str(&format!("?pagination.count={}", PAGE_SIZE));
+            if let Some(ref cursor) = self.cursor {
+                url.push_str(&format!("&pagination.cursor={}", encode(cursor)));
+            }
+        }
+
+        let req = http::Request {
+            method: http::Method::Get,
+            url,
+            headers: self.headers.clone(),
+            body: String::default(),
+        };
+        let resp = http::get(&req)?;
+        http::error_for_status(&resp).map_err(|err| format!("{}: {}", err, resp.body))?;
+        let resp_json: JsonValue = serde_json::from_str(&resp.body).map_err(|e| e.to_string())?;
+
+        stats::inc_stats(FDW_NAME, stats::Metric::BytesIn, resp.body.len() as i64);
+
+        // save source rows
+        let data = resp_json
+            .pointer(data_ptr)
+            .ok_or("cannot get query result data")?;
+        self.src_rows = match data {
+            JsonValue::Array(rows) => rows.clone(),
+            JsonValue::Object(obj) => {
+                // single summary object, add the pushed down ids back so they
+                // can be selected as columns
+                let mut obj = obj.clone();
+                for field in ["destination_id", "source_id", "start_time", "end_time"] {
+                    if let Some(v) = Self::qual_value(ctx, field)? {
+                        obj.insert(field.to_owned(), JsonValue::String(v));
+                    }
+                }
+                vec![JsonValue::Object(obj)]
+            }
+            _ => Vec::new(),
+        };
+        self.src_idx = 0;
+
+        // deal with pagination to save next page cursor
+        self.cursor = if is_list {
+            resp_json
+                .pointer("/data/pagination/next")
+                .and_then(|v| v.as_str())
+                .map(|v| v.to_owned())
+        } else {
+            None
+        };
+
+        Ok(())
+    }
+
+    // convert Segment response data field to a cell
+    // ref: https://docs.segmentapis.com/
+    fn src_to_cell(&self, src_row: &JsonValue, tgt_col: &Column) -> Result<Option<Cell>, FdwError> {
+        let tgt_col_name = tgt_col.name();
+
+        // put all properties into 'attrs' JSON column
+        if &tgt_col_name == "attrs" {
+            return Ok(Some(Cell::Json(src_row.to_string())));
+        }
+
+        // Segment omits optional fields in its response, so a missing source
+        // field is taken as null instead of an error
+        let Some(src) = src_row.as_object().and_then(|v| {
+            v.get(&tgt_col_name)
+                .or_else(|| v.get(&to_camel_case(&tgt_col_name)))
+        }) else {
+            return Ok(None);
+        };
+
+        // column type mapping
+        let cell = match tgt_col.type_oid() {
+            TypeOid::Bool => src.as_bool().map(Cell::Bool),
+            TypeOid::I8 => src.as_i64().map(|v| Cell::I8(v as i8)),
+            TypeOid::I16 => src.as_i64().map(|v| Cell::I16(v as i16)),
+            TypeOid::F32 => src.as_f64().map(|v| Cell::F32(v as f32)),
+            TypeOid::I32 => src.as_i64().map(|v| Cell::I32(v as i32)),
+            TypeOid::F64 => src.as_f64().map(Cell::F64),
+            TypeOid::I64 => src.as_i64().map(Cell::I64),
+            TypeOid::Numeric => src.as_f64().map(Cell::Numeric),
+            TypeOid::String => src.as_str().map(|v| Cell::String(v.to_owned())),
+            TypeOid::Date => {
+                if let Some(s) = src.as_str() {
+                    let ts = time::parse_from_rfc3339(s)?;
+                    Some(Cell::Date(ts / 1_000_000))
+                } else {
+                    None
+                }
+            }
+            TypeOid::Timestamp => {
+                if let Some(s) = src.as_str() {
+                    let ts = time::parse_from_rfc3339(s)?;
+                    Some(Cell::Timestamp(ts))
+                } else {
+                    None
+                }
+            }
+            TypeOid::Timestamptz => {
+                if let Some(s) = src.as_str() {
+                    let ts = time::parse_from_rfc3339(s)?;
+                    Some(Cell::Timestamptz(ts))
+                } else {
+                    None
+                }
+            }
+            TypeOid::Json => {
+                if src.is_object() || src.is_array() {
+                    Some(Cell::Json(src.to_string()))
+                } else {
+                    None
+                }
+            }
+        };
+
+        Ok(cell)
+    }
+
+    // convert a row to a Segment track call JSON string
+    // ref: https://segment.com/docs/connections/sources/catalog/libraries/server/http-api/#track
+    fn row_to_body(&self, row: &Row) -> Result<String, FdwError> {
+        let mut map = JsonMap::new();
+
+        for (col_name, cell) in row.cols().iter().zip(row.cells().iter()) {
+            if let Some(cell) = cell {
+                let value = match cell {
+                    Cell::String(v) => JsonValue::String(v.to_string()),
+                    Cell::Timestamp(v) => JsonValue::String(time::epoch_ms_to_rfc3339(*v)?),
+                    Cell::Timestamptz(v) => JsonValue::String(time::epoch_ms_to_rfc3339(*v)?),
+                    Cell::Json(v) => {
+                        serde_json::from_str::<JsonValue>(v).map_err(|e| e.to_string())?
+                    }
+                    _ => {
+                        return Err(format!("column '{}' type is not supported", col_name));
+                    }
+                };
+                map.insert(to_camel_case(col_name), value);
+            }
+        }
+
+        if !map.contains_key("event") {
+            return Err("column 'event' is required for track events".to_owned());
+        }
+        if !map.contains_key("userId") && !map.contains_key("anonymousId") {
+            return Err("either 'user_id' or 'anonymous_id' must be specified".to_owned());
+        }
+
+        let write_key = self
+            .write_key
+            .as_ref()
+            .ok_or("server option 'write_key' or 'write_key_id' is required")?;
+        map.insert("writeKey".to_owned(), JsonValue::String(write_key.clone()));
+
+        Ok(JsonValue::Object(map).to_string())
+    }
+}
+
+impl Guest for SegmentFdw {
+    fn host_version_requirement() -> String {
+        // semver ref: https://docs.rs/semver/latest/semver/enum.Op.html
+        "^0.1.0".to_string()
+    }
+
+    fn init(ctx: &Context) -> FdwResult {
+        Self::init();
+        let this = Self::this_mut();
+
+        let opts = ctx.get_options(OptionsType::Server);
+        this.base_url = opts.require_or("api_url", "https://api.segmentapis.com");
+        this.tracking_url = opts.require_or("tracking_url", "https://api.segment.io/v1");
+        let api_token = match opts.get("api_token") {
+            Some(token) => token,
+            None => {
+                let token_id = opts.require("api_token_id")?;
+                utils::get_vault_secret(&token_id).unwrap_or_default()
+            }
+        };
+
+        // write key is only needed for sending track events
+        this.write_key = opts.get("write_key").or_else(|| {
+            opts.get("write_key_id")
+                .and_then(|key_id| utils::get_vault_secret(&key_id))
+        });
+
+        // Segment Public API authentication
+        // ref: https://docs.segmentapis.com/tag/Getting-Started#section/Get-an-API-token
+        this.headers
+            .push(("user-agent".to_owned(), "Wrappers Segment FDW".to_string()));
+        this.headers
+            .push(("content-type".to_owned(), "application/json".to_string()));
+        this.headers
+            .push(("authorization".to_owned(), format!("Bearer {}", api_token)));
+
+        stats::inc_stats(FDW_NAME, stats::Metric::CreateTimes, 1);
+
+        Ok(())
+    }
+
+    fn begin_scan(ctx: &Context) -> FdwResult {
+        let this = Self::this_mut();
+        let opts = ctx.get_options(OptionsType::Table);
+        this.object = opts.require("object")?;
+        this.space_id = opts.get("space_id");
+
+        this.cursor = None;
+        this.make_request(ctx)?;
+
+        Ok(())
+    }
+
+    fn iter_scan(ctx: &Context, row: &Row) -> Result<Option<u32>, FdwError> {
+        let this = Self::this_mut();
+
+        // if all local rows are consumed
+        if this.src_idx >= this.src_rows.len() {
+            stats::inc_stats(FDW_NAME, stats::Metric::RowsIn, this.src_rows.len() as i64);
+            stats::inc_stats(FDW_NAME, stats::Metric::RowsOut, this.src_rows.len() as i64);
+
+            // if no more pages, stop the iter scan
+            if this.cursor.is_none() {
+                return Ok(None);
+            }
+
+            // otherwise, make another call to get next page data
+            this.make_request(ctx)?;
+            if this.src_rows.is_empty() {
+                return Ok(None);
+            }
+        }
+
+        // convert Segment row to Postgres row
+        let src_row = &this.src_rows[this.src_idx];
+        for tgt_col in ctx.get_columns() {
+            let cell = this.src_to_cell(src_row, &tgt_col)?;
+            row.push(cell.as_ref());
+        }
+
+        this.src_idx += 1;
+
+        Ok(Some(0))
+    }
+
+    fn re_scan(ctx: &Context) -> FdwResult {
+        let this = Self::this_mut();
+        this.cursor = None;
+        this.make_request(ctx)
+    }
+
+    fn end_scan(_ctx: &Context) -> FdwResult {
+        let this = Self::this_mut();
+        this.src_rows.clear();
+        Ok(())
+    }
+
+    fn begin_modify(ctx: &Context) -> FdwResult {
+        let this = Self::this_mut();
+        let opts = ctx.get_options(OptionsType::Table);
+        this.object = opts.require("object")?;
+        if this.object != "track_events" {
+            return Err(format!(
+                "object '{}' does not support data modify",
+                this.object
+            ));
+        }
+        Ok(())
+    }
+
+    fn insert(_ctx: &Context, row: &Row) -> FdwResult {
+        let this = Self::this_mut();
+        let url = format!("{}/track", this.tracking_url);
+        let body = this.row_to_body(row)?;
+        let req = http::Request {
+            method: http::Method::Post,
+            url,
+            headers: vec![
+                ("user-agent".to_owned(), "Wrappers Segment FDW".to_string()),
+                ("content-type".to_owned(), "application/json".to_string()),
+            ],
+            body,
+        };
+        let resp = http::post(&req)?;
+        http::error_for_status(&resp).map_err(|err| format!("{}: {}", err, resp.body))?;
+        stats::inc_stats(FDW_NAME, stats::Metric::RowsOut, 1);
+        Ok(())
+    }
+
+    fn update(_ctx: &Context, _rowid: Cell, _row: &Row) -> FdwResult {
+        Err("update on foreign table is not supported".to_owned())
+    }
+
+    fn delete(_ctx: &Context, _rowid: Cell) -> FdwResult {
+        Err("delete on foreign table is not supported".to_owned())
+    }
+
+    fn end_modify(_ctx: &Context) -> FdwResult {
+        Ok(())
+    }
+}
+
+bindings::export!(SegmentFdw with_types_in bindings);
@@ -0,0 +1,348 @@
+#[allow(warnings)]
+mod bindings;
+use serde_json::Value as JsonValue;
+use urlencoding::encode;
+
+use bindings::{
+    exports::supabase::wrappers::routines::Guest,
+    supabase::wrappers::{
+        http, stats, time,
+        types::{Cell, Column, Context, FdwError, FdwResult, OptionsType, Row, TypeOid, Value},
+        utils,
+    },
+};
+
+#[derive(Debug, Default)]
+struct SparqlFdw {
+    endpoint: String,
+    headers: Vec<(String, String)>,
+    query: String,
+    src_rows: Vec<JsonValue>,
+    src_idx: usize,
+}
+
+static mut INSTANCE: *mut SparqlFdw = std::ptr::null_mut::<SparqlFdw>();
+static FDW_NAME: &str = "SparqlFdw";
+
+// check if a variable is used in the query template, both '?var' and '$var'
+// forms are accepted
+// ref: https://www.w3.org/TR/sparql11-query/#rVAR1
+fn has_variable(query: &str, var: &str) -> bool {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    ['?', '$'].iter().any(|prefix| {
+        let pat = format!("{}{}", prefix, var);
+        query.match_indices(&pat).any(|(idx, _)| {
+            !query[idx + pat.len()..]
+                .chars()
+                .next()
+                .map(is_word)
+                .unwrap_or(false)
+        })
+    })
+}
+
+// check if the query template already has a LIMIT clause
+fn has_limit(query: &str) -> bool {
+    query
+        .split_whitespace()
+        .any(|w| w.eq_ignore_ascii_case("limit"))
+}
+
+// render a cell as SPARQL term, strings which are absolute http(s) IRIs are
+// rendered as IRI, other strings are rendered as simple literal
+// ref: https://www.w3.org/TR/sparql11-query/#rRDFLiteral
+fn cell_to_term(cell: &Cell) -> Option<String> {
+    match cell {
+        Cell::Bool(v) => Some(v.to_string()),
+        Cell::I8(v) => Some(v.to_string()),
+        Cell::I16(v) => Some(v.to_string()),
+        Cell::I32(v) => Some(v.to_string()),
+        Cell::I64(v) => Some(v.to_string()),
+        Cell::String(s) if s.starts_with("http://") || s.starts_with("https://") => {
+            if s.chars()
+                .any(|c| c.is_whitespace() || "<>\"{}|^`\\".contains(c))
+            {
+                None
+            } else {
+                Some(format!("<{}>", s))
+            }
+        }
+        Cell::String(s) => {
+            let escaped = s
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n")
+                .replace('\r', "\\r");
+            Some(format!("\"{}\"", escaped))
+        }
+        _ => None,
+    }
+}
+
+impl SparqlFdw {
+    fn init() {
+        let instance = Self::default();
+        unsafe {
+            INSTANCE = Box::leak(Box::new(instance));
+        }
+    }
+
+    fn this_mut() -> &'static mut Self {
+        unsafe { &mut (*INSTANCE) }
+    }
+
+    // build the final query from template
+    //
+    // Quals with '=' or 'in' operator on columns which are also variables in
+    // the template are pushed down as a trailing VALUES clause, which binds
+    // the variables before solution modifiers are applied. LIMIT is appended
+    // if the template doesn't have one.
+    // ref: https://www.w3.org/TR/sparql11-query/#inline-data
+    fn build_query(ctx: &Context, template: &str) -> String {
+        let mut query = template.trim().to_owned();
+
+        let mut vars: Vec<String> = Vec::new();
+        let mut terms: Vec<Vec<String>> = Vec::new();
+        for qual in ctx.get_quals() {
+            let field = qual.field();
+            if qual.operator() != "=" || vars.contains(&field) || !has_variable(template, &field) {
+                continue;
+            }
+            let values = match qual.value() {
+                Value::Cell(cell) if !qual.use_or() => cell_to_term(&cell).map(|t| vec![t]),
+                Value::Array(cells) if qual.use_or() => cells.iter().map(cell_to_term).collect(),
+                _ => None,
+            };
+            if let Some(values) = values {
+                vars.push(field);
+                terms.push(values);
+            }
+        }
+
+        if let Some(limit) = ctx.get_limit() {
+            if !has_limit(&query) {
+                query.push_str(&format!("\nLIMIT {}", limit.count() + limit.offset()));
+            }
+        }
+
+        if !vars.is_empty() {
+            // make cartesian product of all the bound values
+            let rows = terms.iter().fold(vec![Vec::new()], |acc, values| {
+                acc.iter()
+                    .flat_map(|row| {
+                        values.iter().map(move |v| {
+                            let mut row: Vec<String> = row.clone();
+                            row.push(v.clone());
+                            row
+                        })
+                    })
+                    .collect::<Vec<_>>()
+            });
+            query.push_str(&format!(
+                "\nVALUES ({}) {{ {} }}",
+                vars.iter()
+                    .map(|v| format!("?{}", v))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                rows.iter()
+                    .map(|row| format!("({})", row.join(" ")))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ));
+        }
+
+        query
+    }
+
+    fn fetch_source_data(&mut self) -> FdwResult {
+        // ref: https://www.w3.org/TR/sparql11-protocol/#query-via-post-urlencoded
+        let req = http::Request {
+            method: http::Method::Post,
+            url: self.endpoint.clone(),
+            headers: self.headers.clone(),
+            body: format!("query={}", encode(&self.query)),
+        };
+        let resp = http::post(&req)?;
+        http::error_for_status(&resp).map_err(|err| format!("{}: {}", err, resp.body))?;
+        let resp_json: JsonValue = serde_json::from_str(&resp.body).map_err(|e| e.to_string())?;
+
+        stats::inc_stats(FDW_NAME, stats::Metric::BytesIn, resp.body.len() as i64);
+
+        // ref: https://www.w3.org/TR/sparql11-results-json/
+        self.src_rows = resp_json
+            .pointer("/results/bindings")
+            .and_then(|v| v.as_array().cloned())
+            .ok_or("cannot get query result bindings")?;
+        self.src_idx = 0;
+
+        stats::inc_stats(FDW_NAME, stats::Metric::RowsIn, self.src_rows.len() as i64);
+
+        Ok(())
+    }
+
+    // convert a SPARQL result binding to a cell
+    fn src_to_cell(&self, src_row: &JsonValue, tgt_col: &Column) -> Result<Option<Cell>, FdwError> {
+        let tgt_col_name = tgt_col.name();
+
+        // unbound variable is null
+        let Some(binding) = src_row.get(&tgt_col_name) else {
+            return Ok(None);
+        };
+
+        // put the whole RDF term into JSON column
+        if matches!(tgt_col.type_oid(), TypeOid::Json) {
+            return Ok(Some(Cell::Json(binding.to_string())));
+        }
+
+        let value = binding["value"].as_str().unwrap_or_default();
+        let parse_err = |e: String| {
+            format!(
+                "cannot convert '{}' to column '{}' type: {}",
+                value, tgt_col_name, e
+            )
+        };
+
+        // column type mapping
+        let cell = match tgt_col.type_oid() {
+            TypeOid::Bool => Cell::Bool(value == "true" || value == "1"),
+            TypeOid::I32 => Cell::I32(value.parse().map_err(|e| parse_err(format!("{e}")))?),
+            TypeOid::I64 => Cell::I64(value.parse().map_err(|e| parse_err(format!("{e}")))?),
+            TypeOid::F32 => Cell::F32(value.parse().map_err(|e| parse_err(format!("{e}")))?),
+            TypeOid::F64 => Cell::F64(value.parse().map_err(|e| parse_err(format!("{e}")))?),
+            TypeOid::Numeric => {
+                Cell::Numeric(value.parse().map_err(|e| parse_err(format!("{e}")))?)
+            }
+            TypeOid::String => Cell::String(value.to_owned()),
+            TypeOid::Date => {
+                // xsd:date and xsd:dateTime are both accepted
+                let date = value.get(..10).unwrap_or(value);
+                let ts = time::parse_from_str(date, "%Y-%m-%d").map_err(parse_err)?;
+                Cell::Date(ts / 1_000_000)
+            }
+            TypeOid::Timestamp => {
+                Cell::Timestamp(time::parse_from_rfc3339(value).map_err(parse_err)?)
+            }
+            TypeOid::Timestamptz => {
+                Cell::Timestamptz(time::parse_from_rfc3339(value).map_err(parse_err)?)
+            }
+            _ => {
+                return Err(format!(
+                    "target column '{}' type is not supported",
+                    tgt_col_name
+                ));
+            }
+        };
+
+        Ok(Some(cell))
+    }
+}
+
+impl Guest for SparqlFdw {
+    fn host_version_requirement() -> String {
+        // semver ref: https://docs.rs/semver/latest/semver/enum.Op.html
+        "^0.1.0".to_string()
+    }
+
+    fn init(ctx: &Context) -> FdwResult {
+        Self::init();
+        let this = Self::this_mut();
+
+        let opts = ctx.get_options(OptionsType::Server);
+        this.endpoint = opts.require("endpoint")?;
+
+        // Wikidata and some other public endpoints require a user agent
+        // ref: https://meta.wikimedia.org/wiki/User-Agent_policy
+        let user_agent = opts.require_or(
+            "user_agent",
+            "Wrappers SPARQL FDW (https://github.com/supabase/wrappers)",
+        );
+        this.headers.push(("user-agent".to_owned(), user_agent));
+        this.headers.push((
+            "content-type".to_owned(),
+            "application/x-www-form-urlencoded".to_string(),
+        ));
+        this.headers.push((
+            "accept".to_owned(),
+            "application/sparql-results+json".to_string(),
+        ));
+
+        // optional bearer token authentication
+        let api_key = match opts.get("api_key") {
+            Some(key) => Some(key),
+            None => opts
+                .get("api_key_id")
+                .and_then(|key_id| utils::get_vault_secret(&key_id)),
+        };
+        if let Some(api_key) = api_key {
+            this.headers
+                .push(("authorization".to_owned(), format!("Bearer {}", api_key)));
+        }
+
+        stats::inc_stats(FDW_NAME, stats::Metric::CreateTimes, 1);
+
+        Ok(())
+    }
+
+    fn begin_scan(ctx: &Context) -> FdwResult {
+        let this = Self::this_mut();
+        let opts = ctx.get_options(OptionsType::Table);
+        let template = opts.require("query")?;
+        this.query = Self::build_query(ctx, &template);
+        this.fetch_source_data()
+    }
+
+    fn iter_scan(ctx: &Context, row: &Row) -> Result<Option<u32>, FdwError> {
+        let this = Self::this_mut();
+
+        if this.src_idx >= this.src_rows.len() {
+            stats::inc_stats(FDW_NAME, stats::Metric::RowsOut, this.src_rows.len() as i64);
+            return Ok(None);
+        }
+
+        let src_row = &this.src_rows[this.src_idx];
+        for tgt_col in ctx.get_columns() {
+            let cell = this.src_to_cell(src_row, &tgt_col)?;
+            row.push(cell.as_ref());
+        }
+
+        this.src_idx += 1;
+
+        Ok(Some(0))
+    }
+
+    fn re_scan(ctx: &Context) -> FdwResult {
+        let this = Self::this_mut();
+        let opts = ctx.get_options(OptionsType::Table);
+        let template = opts.require("query")?;
+        this.query = Self::build_query(ctx, &template);
+        this.fetch_source_data()
+    }
+
+    fn end_scan(_ctx: &Context) -> FdwResult {
+        let this = Self::this_mut();
+        this.src_rows.clear();
+        Ok(())
+    }
+
+    fn begin_modify(_ctx: &Context) -> FdwResult {
+        Err("modify on foreign table is not supported".to_owned())
+    }
+
+    fn insert(_ctx: &Context, _row: &Row) -> FdwResult {
+        Ok(())
+    }
+
+    fn update(_ctx: &Context, _rowid: Cell, _row: &Row) -> FdwResult {
+        Ok(())
+    }
+
+    fn delete(_ctx: &Context, _rowid: Cell) -> FdwResult {
+        Ok(())
+    }
+
+    fn end_modify(_ctx: &Context) -> FdwResult {
+        Ok(())
+    }
+}
+
+bindings::export!(SparqlFdw with_types_in bindings);
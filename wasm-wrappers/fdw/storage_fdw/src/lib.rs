@@ -0,0 +1,408 @@
+#[allow(warnings)]
+mod bindings;
+use serde_json::{json, Value as JsonValue};
+use urlencoding::encode;
+
+use bindings::{
+    exports::supabase::wrappers::routines::Guest,
+    supabase::wrappers::{
+        http, stats, time,
+        types::{Cell, Column, Context, FdwError, FdwResult, OptionsType, Row, TypeOid, Value},
+        utils,
+    },
+};
+
+#[derive(Debug, Default)]
+struct StorageFdw {
+    base_url: String,
+    headers: Vec<(String, String)>,
+    object: String,
+    bucket: Option<String>,
+    prefix: String,
+    search: String,
+    offset: usize,
+    has_more: bool,
+    sign_urls: bool,
+    expires_in: i64,
+    src_rows: Vec<JsonValue>,
+    src_idx: usize,
+}
+
+static mut INSTANCE: *mut StorageFdw = std::ptr::null_mut::<StorageFdw>();
+static FDW_NAME: &str = "StorageFdw";
+
+// page size of object list request
+static PAGE_SIZE: usize = 100;
+
+impl StorageFdw {
+    fn init() {
+        let instance = Self::default();
+        unsafe {
+            INSTANCE = Box::leak(Box::new(instance));
+        }
+    }
+
+    fn this_mut() -> &'static mut Self {
+        unsafe { &mut (*INSTANCE) }
+    }
+
+    // translate quals to bucket, prefix and search parameters of object list request
+    //
+    // Storage object list is not recursive, the 'prefix' is a folder path and
+    // 'search' filters object names in that folder, so a qual like
+    // "name like 'docs/report%'" is pushed down as prefix 'docs' and search
+    // 'report'. Postgres will re-check the quals locally.
+    fn pushdown_quals(&mut self, ctx: &Context) -> FdwResult {
+        self.bucket = ctx.get_options(OptionsType::Table).get("bucket");
+        self.prefix.clear();
+        self.search.clear();
+
+        for qual in ctx.get_quals() {
+            if qual.use_or() {
+                continue;
+            }
+            let value = match qual.value() {
+                Value::Cell(Cell::String(s)) => s,
+                _ => continue,
+            };
+            match (qual.field().as_str(), qual.operator().as_str()) {
+                ("bucket_id", "=") if self.bucket.is_none() => {
+                    self.bucket = Some(value);
+                }
+                ("name", "=") => {
+                    let (prefix, name) = value.rsplit_once('/').unwrap_or(("", &value));
+                    self.prefix = prefix.to_owned();
+                    self.search = name.to_owned();
+                }
+                ("name", "~~") => {
+                    // only take the literal part before any wildcard
+                    let literal = value
+                        .split(['%', '_', '\\'])
+                        .next()
+                        .unwrap_or_default()
+                        .to_owned();
+                    let (prefix, search) = literal.rsplit_once('/').unwrap_or(("", &literal));
+                    self.prefix = prefix.to_owned();
+                    self.search = search.to_owned();
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn fetch_buckets(&mut self) -> FdwResult {
+        // ref: https://supabase.github.io/storage/#/bucket/get_bucket
+        let req = http::Request {
+            method: http::Method::Get,
+            url: format!("{}/bucket", self.base_url),
+            headers: self.headers.clone(),
+            body: String::default(),
+        };
+        let resp = http::get(&req)?;
+        http::error_for_status(&resp).map_err(|err| format!("{}: {}", err, resp.body))?;
+        let resp_json: JsonValue = serde_json::from_str(&resp.body).map_err(|e| e.to_string())?;
+
+        stats::inc_stats(FDW_NAME, stats::Metric::BytesIn, resp.body.len() as i64);
+
+        self.src_rows = resp_json
+            .as_array()
+            .cloned()
+            .ok_or("cannot get query result data")?;
+        self.src_idx = 0;
+        self.has_more = false;
+
+        Ok(())
+    }
+
+    fn fetch_objects(&mut self) -> FdwResult {
+        let bucket = self
+            .bucket
+            .clone()
+            .ok_or("'bucket_id' must be specified in table option or where clause")?;
+
+        // ref: https://supabase.github.io/storage/#/object/post_object_list__bucketName_
+        let body = json!({
+            "prefix": self.prefix,
+            "search": self.search,
+            "limit": PAGE_SIZE,
+            "offset": self.offset,
+            "sortBy": { "column": "name", "order": "asc" },
+        });
+        let req = http::Request {
+            method: http::Method::Post,
+            url: format!("{}/object/list/{}", self.base_url, encode(&bucket)),
+            headers: self.headers.clone(),
+            body: body.to_string(),
+        };
+        let resp = http::post(&req)?;
+        http::error_for_status(&resp).map_err(|err| format!("{}: {}", err, resp.body))?;
+        let resp_json: JsonValue = serde_json::from_str(&resp.body).map_err(|e| e.to_string())?;
+
+        stats::inc_stats(FDW_NAME, stats::Metric::BytesIn, resp.body.len() as i64);
+
+        let objects = resp_json.as_array().ok_or("cannot get query result data")?;
+        self.has_more = objects.len() >= PAGE_SIZE;
+        self.offset += objects.len();
+
+        // make object name a full path and flatten size and mime type from metadata
+        self.src_rows = objects
+            .iter()
+            .map(|obj| {
+                let mut obj = obj.clone();
+                let name = obj["name"].as_str().unwrap_or_default();
+                let path = if self.prefix.is_empty() {
+                    name.to_owned()
+                } else {
+                    format!("{}/{}", self.prefix, name)
+                };
+                obj["name"] = json!(path);
+                obj["bucket_id"] = json!(bucket);
+                obj["size"] = obj.pointer("/metadata/size").cloned().unwrap_or_default();
+                obj["mime_type"] = obj
+                    .pointer("/metadata/mimetype")
+                    .cloned()
+                    .unwrap_or_default();
+                obj["signed_url"] = JsonValue::Null;
+                obj
+            })
+            .collect();
+        self.src_idx = 0;
+
+        if self.sign_urls {
+            self.sign_objects(&bucket)?;
+        }
+
+        Ok(())
+    }
+
+    // create signed urls for current page of objects in one batch request
+    // ref: https://supabase.github.io/storage/#/object/post_object_sign__bucketName_
+    fn sign_objects(&mut self, bucket: &str) -> FdwResult {
+        let paths = self
+            .src_rows
+            .iter()
+            .filter(|obj| !obj["id"].is_null())
+            .filter_map(|obj| obj["name"].as_str())
+            .collect::<Vec<_>>();
+        if paths.is_empty() {
+            return Ok(());
+        }
+
+        let req = http::Request {
+            method: http::Method::Post,
+            url: format!("{}/object/sign/{}", self.base_url, encode(bucket)),
+            headers: self.headers.clone(),
+            body: json!({ "expiresIn": self.expires_in, "paths": paths }).to_string(),
+        };
+        let resp = http::post(&req)?;
+        http::error_for_status(&resp).map_err(|err| format!("{}: {}", err, resp.body))?;
+        let resp_json: JsonValue = serde_json::from_str(&resp.body).map_err(|e| e.to_string())?;
+
+        let signed = resp_json
+            .as_array()
+            .ok_or("cannot get signed url result data")?;
+        for obj in self.src_rows.iter_mut() {
+            let url = signed
+                .iter()
+                .find(|s| s["path"] == obj["name"])
+                .and_then(|s| s["signedURL"].as_str())
+                .map(|s| format!("{}{}", self.base_url, s));
+            obj["signed_url"] = json!(url);
+        }
+
+        Ok(())
+    }
+
+    fn fetch_source_data(&mut self) -> FdwResult {
+        match self.object.as_str() {
+            "buckets" => self.fetch_buckets(),
+            "objects" => self.fetch_objects(),
+            _ => Err(format!("object '{}' is not supported", self.object)),
+        }
+    }
+
+    // convert Storage API response data field to a cell
+    fn src_to_cell(&self, src_row: &JsonValue, tgt_col: &Column) -> Result<Option<Cell>, FdwError> {
+        let tgt_col_name = tgt_col.name();
+
+        // put all properties into 'attrs' JSON column
+        if &tgt_col_name == "attrs" {
+            return Ok(Some(Cell::Json(src_row.to_string())));
+        }
+
+        let src = src_row
+            .as_object()
+            .and_then(|v| v.get(&tgt_col_name))
+            .ok_or(format!("source column '{}' not found", tgt_col_name))?;
+
+        // column type mapping
+        let cell = match tgt_col.type_oid() {
+            TypeOid::Bool => src.as_bool().map(Cell::Bool),
+            TypeOid::I32 => src.as_i64().map(|v| Cell::I32(v as i32)),
+            TypeOid::I64 => src.as_i64().map(Cell::I64),
+            TypeOid::F64 => src.as_f64().map(Cell::F64),
+            TypeOid::Numeric => src.as_f64().map(Cell::Numeric),
+            TypeOid::String => src.as_str().map(|v| Cell::String(v.to_owned())),
+            TypeOid::Timestamp => {
+                if let Some(s) = src.as_str() {
+                    let ts = time::parse_from_rfc3339(s)?;
+                    Some(Cell::Timestamp(ts))
+                } else {
+                    None
+                }
+            }
+            TypeOid::Timestamptz => {
+                if let Some(s) = src.as_str() {
+                    let ts = time::parse_from_rfc3339(s)?;
+                    Some(Cell::Timestamptz(ts))
+                } else {
+                    None
+                }
+            }
+            TypeOid::Json => {
+                if src.is_object() || src.is_array() {
+                    Some(Cell::Json(src.to_string()))
+                } else {
+                    None
+                }
+            }
+            _ => {
+                return Err(format!(
+                    "target column '{}' type is not supported",
+                    tgt_col_name
+                ));
+            }
+        };
+
+        Ok(cell)
+    }
+}
+
+impl Guest for StorageFdw {
+    fn host_version_requirement() -> String {
+        // semver ref: https://docs.rs/semver/latest/semver/enum.Op.html
+        "^0.1.0".to_string()
+    }
+
+    fn init(ctx: &Context) -> FdwResult {
+        Self::init();
+        let this = Self::this_mut();
+
+        let opts = ctx.get_options(OptionsType::Server);
+        let project_url = opts.require("project_url")?;
+        this.base_url = opts.require_or(
+            "api_url",
+            &format!("{}/storage/v1", project_url.trim_end_matches('/')),
+        );
+        let api_key = match opts.get("api_key") {
+            Some(key) => key,
+            None => {
+                let key_id = opts.require("api_key_id")?;
+                utils::get_vault_secret(&key_id).unwrap_or_default()
+            }
+        };
+
+        // Storage API authentication, service role key is needed to bypass RLS
+        // ref: https://supabase.com/docs/guides/storage/security/access-control
+        this.headers
+            .push(("user-agent".to_owned(), "Wrappers Storage FDW".to_string()));
+        this.headers
+            .push(("content-type".to_owned(), "application/json".to_string()));
+        this.headers.push(("apikey".to_owned(), api_key.clone()));
+        this.headers
+            .push(("authorization".to_owned(), format!("Bearer {}", api_key)));
+
+        stats::inc_stats(FDW_NAME, stats::Metric::CreateTimes, 1);
+
+        Ok(())
+    }
+
+    fn begin_scan(ctx: &Context) -> FdwResult {
+        let this = Self::this_mut();
+        let opts = ctx.get_options(OptionsType::Table);
+        this.object = opts.require("object")?;
+        this.expires_in = opts
+            .require_or("signed_url_expires_in", "3600")
+            .parse::<i64>()
+            .map_err(|e| format!("invalid signed_url_expires_in option: {}", e))?;
+
+        // only generate signed urls when it is selected
+        this.sign_urls = ctx
+            .get_columns()
+            .iter()
+            .any(|col| col.name() == "signed_url");
+
+        this.pushdown_quals(ctx)?;
+        this.offset = 0;
+        this.fetch_source_data()
+    }
+
+    fn iter_scan(ctx: &Context, row: &Row) -> Result<Option<u32>, FdwError> {
+        let this = Self::this_mut();
+
+        // if all local rows are consumed
+        if this.src_idx >= this.src_rows.len() {
+            stats::inc_stats(FDW_NAME, stats::Metric::RowsIn, this.src_rows.len() as i64);
+            stats::inc_stats(FDW_NAME, stats::Metric::RowsOut, this.src_rows.len() as i64);
+
+            // if no more pages, stop the iter scan
+            if !this.has_more {
+                return Ok(None);
+            }
+
+            // otherwise, make another call to get next page data
+            this.fetch_source_data()?;
+            if this.src_rows.is_empty() {
+                return Ok(None);
+            }
+        }
+
+        // convert Storage row to Postgres row
+        let src_row = &this.src_rows[this.src_idx];
+        for tgt_col in ctx.get_columns() {
+            let cell = this.src_to_cell(src_row, &tgt_col)?;
+            row.push(cell.as_ref());
+        }
+
+        this.src_idx += 1;
+
+        Ok(Some(0))
+    }
+
+    fn re_scan(ctx: &Context) -> FdwResult {
+        let this = Self::this_mut();
+        this.pushdown_quals(ctx)?;
+        this.offset = 0;
+        this.fetch_source_data()
+    }
+
+    fn end_scan(_ctx: &Context) -> FdwResult {
+        let this = Self::this_mut();
+        this.src_rows.clear();
+        Ok(())
+    }
+
+    fn begin_modify(_ctx: &Context) -> FdwResult {
+        Err("modify on foreign table is not supported".to_owned())
+    }
+
+    fn insert(_ctx: &Context, _row: &Row) -> FdwResult {
+        Ok(())
+    }
+
+    fn update(_ctx: &Context, _rowid: Cell, _row: &Row) -> FdwResult {
+        Ok(())
+    }
+
+    fn delete(_ctx: &Context, _rowid: Cell) -> FdwResult {
+        Ok(())
+    }
+
+    fn end_modify(_ctx: &Context) -> FdwResult {
+        Ok(())
+    }
+}
+
+bindings::export!(StorageFdw with_types_in bindings);
@@ -12,3 +12,8 @@ awslocal s3 cp /data/test_data.jsonl s3://test/test_data.jsonl
 awslocal s3 cp /data/test_data.jsonl.bz2 s3://test/test_data.jsonl.bz2
 awslocal s3 cp /data/test_data.parquet s3://test/test_data.parquet
 awslocal s3 cp /data/test_data.parquet.gz s3://test/test_data.parquet.gz
+
+# upload test data files under a prefix, each object is a partition of parallel scan
+awslocal s3 cp /data/test_data.csv s3://test/parts/test_data_1.csv
+awslocal s3 cp /data/test_data.csv s3://test/parts/test_data_2.csv
+awslocal s3 cp /data/test_data.csv s3://test/parts/test_data_3.csv
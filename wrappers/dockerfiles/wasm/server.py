@@ -190,6 +190,197 @@ class MockServer(BaseHTTPRequestHandler):
     }
 }
             '''
+        elif fdw == "segment":
+            # query values are compared after decoding, so any value not
+            # percent-encoded by the FDW, like '+' in cursor and timestamp,
+            # will not match
+            url = urlparse(req_path)
+            qs = parse_qs(url.query)
+            if url.path == "/sources":
+                cursor = qs.get("pagination.cursor")
+                if cursor is None:
+                    body = '''
+{
+  "data": {
+    "sources": [{
+      "id": "rh5BDZp6QDHvXFCkibm1pR",
+      "slug": "test-source",
+      "name": "Test Source",
+      "workspaceId": "9aQ1Lj62S4bomZKLF4DPqW",
+      "enabled": true,
+      "writeKeys": ["4hmXi8O2PCnB2HsQgXG6kvj4Qp0R6ZhF"]
+    }],
+    "pagination": {
+      "current": "MA==",
+      "next": "MQ+/==",
+      "totalEntries": 2
+    }
+  }
+}
+                    '''
+                elif cursor == ["MQ+/=="]:
+                    body = '''
+{
+  "data": {
+    "sources": [{
+      "id": "qQEHquLrjRDN9j1ByrChyn",
+      "slug": "test-source-2",
+      "name": "Test Source 2",
+      "workspaceId": "9aQ1Lj62S4bomZKLF4DPqW",
+      "enabled": false,
+      "writeKeys": []
+    }],
+    "pagination": {
+      "current": "MQ+/==",
+      "totalEntries": 2
+    }
+  }
+}
+                    '''
+                else:
+                    self.send_response(400)
+                    self.end_headers()
+                    return
+            elif url.path == "/destinations/fP7qoQw2HTWt9WdMr718gn/delivery-metrics":
+                if qs.get("sourceId") != ["rh5BDZp6QDHvXFCkibm1pR"] \
+                        or qs.get("startTime") != ["2024-06-01T00:00:00+00:00"]:
+                    self.send_response(400)
+                    self.end_headers()
+                    return
+                body = '''
+{
+  "data": {
+    "deliveryMetricsSummary": {
+      "sourceId": "rh5BDZp6QDHvXFCkibm1pR",
+      "destinationMetadataId": "5f7dd8e302173ff732db5cc4",
+      "metrics": [{
+        "metricName": "successes",
+        "total": 42,
+        "breakdown": []
+      }]
+    }
+  }
+}
+                '''
+            else:
+                self.send_response(404)
+                self.end_headers()
+                return
+        elif fdw == "cloudflare":
+            url = urlparse(req_path)
+            qs = parse_qs(url.query)
+            if url.path == "/zones/023e105f4ecef8ad9ca31a8372d0c353/dns_records":
+                body = '''
+{
+  "result": [{
+    "id": "372e67954025e0ba6aaa6d586b9e0b59",
+    "zone_id": "023e105f4ecef8ad9ca31a8372d0c353",
+    "name": "www.example.com",
+    "type": "A",
+    "content": "198.51.100.4",
+    "proxied": false,
+    "ttl": 3600
+  }],
+  "result_info": {
+    "page": 1,
+    "per_page": 50,
+    "count": 1,
+    "total_count": 1,
+    "total_pages": 1
+  },
+  "errors": [],
+  "messages": [],
+  "success": true
+}
+                '''
+            elif url.path == "/accounts/023e105f4ecef8ad9ca31a8372d0c353/storage/kv/namespaces/0f2ac74b498b48028cb68387c421e279/keys":
+                cursor = qs.get("cursor")
+                if cursor is None:
+                    body = '''
+{
+  "result": [{
+    "name": "My-Key",
+    "expiration": 1577836800,
+    "metadata": {"someMetadataKey": "someMetadataValue"}
+  }],
+  "result_info": {
+    "count": 1,
+    "cursor": "6Ck1la0VxJ0djhidm1MdX2FyD+/="
+  },
+  "errors": [],
+  "messages": [],
+  "success": true
+}
+                    '''
+                elif cursor == ["6Ck1la0VxJ0djhidm1MdX2FyD+/="]:
+                    body = '''
+{
+  "result": [{
+    "name": "My-Key-2"
+  }],
+  "result_info": {
+    "count": 1,
+    "cursor": ""
+  },
+  "errors": [],
+  "messages": [],
+  "success": true
+}
+                    '''
+                else:
+                    self.send_response(400)
+                    self.end_headers()
+                    return
+            else:
+                self.send_response(404)
+                self.end_headers()
+                return
+        elif fdw == "registry":
+            # package name and version are path segments, '/' in npm scoped
+            # package name and '+' in version build metadata must be encoded
+            if req_path == "/crates/wasi/0.13.3%2Bwasi-0.2.2/dependencies":
+                body = '''
+{
+  "dependencies": [{
+    "id": 11784392,
+    "version_id": 1457016,
+    "crate_id": "wit-bindgen-rt",
+    "req": "^0.33.0",
+    "optional": false,
+    "default_features": true,
+    "features": ["bitflags"],
+    "target": null,
+    "kind": "normal",
+    "downloads": 0
+  }]
+}
+                '''
+            elif req_path == "/npm/%40supabase%2Fsupabase-js":
+                body = '''
+{
+  "_id": "@supabase/supabase-js",
+  "name": "@supabase/supabase-js",
+  "description": "Isomorphic Javascript client for Supabase",
+  "dist-tags": {
+    "latest": "2.45.4"
+  },
+  "license": "MIT",
+  "homepage": "https://github.com/supabase/supabase-js",
+  "repository": {
+    "type": "git",
+    "url": "git+https://github.com/supabase/supabase-js.git"
+  },
+  "time": {
+    "created": "2020-03-19T09:06:03.528Z",
+    "modified": "2024-09-12T10:11:56.482Z"
+  },
+  "versions": {}
+}
+                '''
+            else:
+                self.send_response(404)
+                self.end_headers()
+                return
         else:
             self.send_response(404)
             return
@@ -318,6 +509,68 @@ class MockServer(BaseHTTPRequestHandler):
   "errors": [],
   "messages": [],
   "success": true
+}
+            '''
+        elif fdw == "storage":
+            # bucket name is cut off as url fragment if it is not percent-encoded
+            if req_path == "/object/list/reports%232024":
+                body = '''
+[
+  {
+    "name": "q1.pdf",
+    "id": "8a3c8e9f-1d2b-4c6e-9a8f-0b1c2d3e4f50",
+    "updated_at": "2024-04-01T08:00:00.000Z",
+    "created_at": "2024-04-01T08:00:00.000Z",
+    "last_accessed_at": "2024-04-01T08:00:00.000Z",
+    "metadata": {
+      "size": 1024,
+      "mimetype": "application/pdf"
+    }
+  }
+]
+                '''
+            elif req_path == "/object/sign/reports%232024":
+                body = '''
+[
+  {
+    "error": null,
+    "path": "q1.pdf",
+    "signedURL": "/object/sign/reports%232024/q1.pdf?token=abc"
+  }
+]
+                '''
+            else:
+                self.send_response(404)
+                self.end_headers()
+                return
+        elif fdw == "sparql":
+            # the query is sent as url-encoded form body, the pushed down
+            # literal must be decoded intact
+            length = int(self.headers.get("Content-Length", 0))
+            form = parse_qs(self.rfile.read(length).decode("utf-8"))
+            query = form.get("query", [""])[0]
+            if 'VALUES (?label) { ("AT&T + Co") }' not in query:
+                self.send_response(400)
+                self.end_headers()
+                return
+            body = '''
+{
+  "head": {
+    "vars": ["item", "label"]
+  },
+  "results": {
+    "bindings": [{
+      "item": {
+        "type": "uri",
+        "value": "http://www.wikidata.org/entity/Q35476"
+      },
+      "label": {
+        "xml:lang": "en",
+        "type": "literal",
+        "value": "AT&T + Co"
+      }
+    }]
+  }
 }
             '''
         else:
@@ -329,6 +582,36 @@ class MockServer(BaseHTTPRequestHandler):
         return
 
 
+    def modify(self):
+        (fdw, req_path) = self.get_fdw_req_path()
+
+        if fdw == "cloudflare" and req_path == "/zones/023e105f4ecef8ad9ca31a8372d0c353/dns_records/372e67954025e0ba6aaa6d586b9e0b59":
+            body = '''
+{
+  "result": {
+    "id": "372e67954025e0ba6aaa6d586b9e0b59"
+  },
+  "errors": [],
+  "messages": [],
+  "success": true
+}
+            '''
+        else:
+            self.send_response(404)
+            self.end_headers()
+            return
+
+        self.response(body)
+
+
+    def do_PATCH(self):
+        self.modify()
+
+
+    def do_DELETE(self):
+        self.modify()
+
+
 if __name__ == "__main__":
     # Create web server
     webServer = HTTPServer((hostName, serverPort), MockServer)
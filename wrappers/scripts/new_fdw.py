@@ -0,0 +1,568 @@
+#!/usr/bin/env python3
+"""Generate the skeleton of a new native foreign data wrapper.
+
+Usage:
+
+    python3 wrappers/scripts/new_fdw.py <name> [--auth api_key|oauth2|aws]
+
+For example, `new_fdw.py acme --auth oauth2` creates `AcmeFdw` in
+`wrappers/src/fdw/acme_fdw/`, with the error type, typed options, HTTP client
+set up for the auth style, empty scan and modify functions and a test, and
+adds the `acme_fdw` feature to `wrappers/Cargo.toml` and the module to
+`wrappers/src/fdw/mod.rs`. Search `TODO` in the generated files for the parts
+to be filled in.
+"""
+
+import argparse
+import re
+import subprocess
+import sys
+from datetime import date
+from pathlib import Path
+from string import Template
+
+WRAPPERS_DIR = Path(__file__).resolve().parent.parent
+FDW_DIR = WRAPPERS_DIR / "src" / "fdw"
+
+# options, extra errors, imports, struct fields and client setup of each auth
+# style
+AUTH_STYLES = {
+    "api_key": {
+        "features": [],
+        "schema": """\
+        server(
+            api_url(required, example = "https://api.example.com/v1"),
+            api_key(secret, example = "xxx")
+        ),""",
+        "server_options": """\
+    api_url: String,
+    #[option(secret)]
+    api_key: String,""",
+        "errors": """\
+    #[error("invalid api_key header: {0}")]
+    InvalidApiKeyHeader(#[from] InvalidHeaderValue),
+""",
+        "error_imports": "use http::header::InvalidHeaderValue;\n",
+        "error_arms": "",
+        "prelude": "",
+        "imports": """\
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use reqwest_middleware::ClientWithMiddleware;
+""",
+        "fields": """\
+    client: ClientWithMiddleware,""",
+        "new": """\
+        let mut headers = HeaderMap::new();
+        let mut auth_value = HeaderValue::from_str(&format!("Bearer {}", opts.api_key))?;
+        auth_value.set_sensitive(true);
+        headers.insert(AUTHORIZATION, auth_value);
+        let client = create_http_client(&server.options, headers)?;
+""",
+        "init": """\
+            client,""",
+        "send": """\
+        let resp = self.rt.block_on(self.client.get(url).send());
+""",
+    },
+    "oauth2": {
+        "features": ['"supabase-wrappers/oauth2"'],
+        "schema": """\
+        server(
+            api_url(required, example = "https://api.example.com/v1"),
+            token_url(required, example = "https://auth.example.com/oauth/token"),
+            client_id(required, example = "xxx"),
+            client_secret(secret, example = "xxx")
+        ),""",
+        "server_options": """\
+    api_url: String,""",
+        "errors": """\
+    #[error("invalid authorization header: {0}")]
+    InvalidAuthHeader(#[from] InvalidHeaderValue),
+
+    #[error("{0}")]
+    OAuth2Error(#[from] OAuth2Error),
+""",
+        "error_imports": "use http::header::InvalidHeaderValue;\n",
+        "error_arms": "            $ErrorType::OAuth2Error(e) => e.into(),\n",
+        "prelude": "OAuth2Error, ",
+        "imports": """\
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+""",
+        "fields": """\
+    oauth2: OAuth2Client,
+    server_options: HashMap<String, String>,""",
+        "new": """\
+        let mut options = server.options.clone();
+        options.extend(server.user_mapping_options.clone());
+        let oauth2 = OAuth2Client::from_options(&options)?
+            .ok_or_else(|| OptionsError::OptionNameNotFound("token_url".to_string()))?;
+""",
+        "init": """\
+            oauth2,
+            server_options: server.options.clone(),""",
+        "send": """\
+        // the access token is cached, and refreshed if it is expiring
+        let mut headers = HeaderMap::new();
+        let mut auth_value =
+            HeaderValue::from_str(&format!("Bearer {}", self.oauth2.access_token()?))?;
+        auth_value.set_sensitive(true);
+        headers.insert(AUTHORIZATION, auth_value);
+        let client = create_http_client(&self.server_options, headers)?;
+        let resp = self.rt.block_on(client.get(url).send());
+""",
+    },
+    "aws": {
+        "features": ['"supabase-wrappers/aws"'],
+        "schema": """\
+        server(
+            api_url(required, example = "https://xxx.execute-api.us-east-1.amazonaws.com/v1"),
+            aws_region(example = "us-east-1"),
+            aws_access_key_id(example = "xxx"),
+            aws_secret_access_key(secret, example = "xxx")
+        ),""",
+        "server_options": """\
+    api_url: String,""",
+        "errors": """\
+    #[error("{0}")]
+    AwsError(#[from] AwsError),
+""",
+        "error_imports": "",
+        "error_arms": "            $ErrorType::AwsError(e) => e.into(),\n",
+        "prelude": "AwsError, ",
+        "imports": """\
+use reqwest::header::HeaderMap;
+use reqwest_middleware::ClientWithMiddleware;
+""",
+        "fields": """\
+    client: ClientWithMiddleware,
+    provider: AwsCredentialsProvider,
+    signer: SigV4Signer,""",
+        "new": """\
+        let provider = AwsCredentialsProvider::from_options(&server.options)?;
+        // TODO: change to the signing name of the AWS service
+        let signer = SigV4Signer::new("execute-api", provider.region());
+        let client = create_http_client(&server.options, HeaderMap::new())?;
+""",
+        "init": """\
+            client,
+            provider,
+            signer,""",
+        "send": """\
+        // the temporary credentials are cached, and refreshed if expiring
+        let mut req = self.client.get(url).build()?;
+        self.signer.sign(&mut req, &self.provider.credentials()?)?;
+        let resp = self.rt.block_on(self.client.execute(req));
+""",
+    },
+}
+
+MOD_RS = Template(
+    """\
+#![allow(clippy::module_inception)]
+mod ${name}_fdw;
+mod tests;
+
+${error_imports}use pgrx::pg_sys::panic::ErrorReport;
+use pgrx::prelude::PgSqlErrorCode;
+use thiserror::Error;
+
+use crate::fdw::http_client::HttpClientError;
+use crate::fdw::request_error::{request_error, request_middleware_error};
+use supabase_wrappers::prelude::{
+    ${prelude}CreateRuntimeError, FdwError, JsonToRowError, OptionsError,
+};
+
+#[derive(Error, Debug)]
+enum $ErrorType {
+    #[error("invalid ${title} response: {0}")]
+    InvalidResponse(String),
+
+${errors}
+    #[error("{0}")]
+    OptionsError(#[from] OptionsError),
+
+    #[error("{0}")]
+    FdwError(#[from] FdwError),
+
+    #[error("{0}")]
+    JsonToRowError(#[from] JsonToRowError),
+
+    #[error("{0}")]
+    CreateRuntimeError(#[from] CreateRuntimeError),
+
+    #[error("{0}")]
+    HttpClientError(#[from] HttpClientError),
+
+    #[error("parse url failed: {0}")]
+    UrlParseError(#[from] url::ParseError),
+
+    #[error("request failed: {0}")]
+    RequestError(#[from] reqwest::Error),
+
+    #[error("request middleware failed: {0}")]
+    RequestMiddlewareError(#[from] reqwest_middleware::Error),
+
+    #[error("parse JSON response failed: {0}")]
+    JsonParseError(#[from] serde_json::Error),
+}
+
+impl From<$ErrorType> for ErrorReport {
+    fn from(value: $ErrorType) -> Self {
+        let msg = format!("{value}");
+        match value {
+            $ErrorType::FdwError(e) => e.into(),
+            $ErrorType::HttpClientError(e) => e.into(),
+${error_arms}\
+            $ErrorType::RequestError(e) => request_error(&e, msg).into(),
+            $ErrorType::RequestMiddlewareError(e) => request_middleware_error(&e, msg).into(),
+            $ErrorType::OptionsError(_) => FdwError::InvalidOption(msg).into(),
+            $ErrorType::JsonToRowError(e) => FdwError::from(e).into(),
+            _ => ErrorReport::new(PgSqlErrorCode::ERRCODE_FDW_ERROR, msg, ""),
+        }
+    }
+}
+
+type ${Camel}FdwResult<T> = Result<T, $ErrorType>;
+"""
+)
+
+FDW_RS = Template(
+    """\
+use crate::fdw::http_client::{create_http_client, record_request};
+use crate::stats;
+${imports}use reqwest::Url;
+use serde_json::value::Value as JsonValue;
+use std::collections::HashMap;
+
+use supabase_wrappers::prelude::*;
+
+use super::{$ErrorType, ${Camel}FdwResult};
+
+#[wrappers_fdw(
+    version = "0.1.0",
+    author = "Supabase",
+    website = "https://github.com/supabase/wrappers/tree/main/wrappers/src/fdw/${name}_fdw",
+    error_type = "$ErrorType",
+    options(
+${schema}
+        table(object(required, example = "users"))
+    )
+)]
+pub(crate) struct ${Camel}Fdw {
+    rt: Runtime,
+    api_url: String,
+${fields}
+    circuit_breaker: CircuitBreaker,
+    rows: std::vec::IntoIter<Row>,
+}
+
+#[derive(FdwOptions)]
+struct ${Camel}ServerOptions {
+${server_options}
+}
+
+#[derive(FdwOptions)]
+struct ${Camel}TableOptions {
+    object: String,
+}
+
+impl ${Camel}Fdw {
+    const FDW_NAME: &'static str = "${Camel}Fdw";
+
+    // TODO: change to the response format of the API, here the records are
+    // in the `data` array of the response body
+    fn resp_to_rows(body: &JsonValue, columns: &[Column]) -> ${Camel}FdwResult<Vec<Row>> {
+        body.get("data")
+            .and_then(|data| data.as_array())
+            .ok_or_else(|| $ErrorType::InvalidResponse(body.to_string()))?
+            .iter()
+            .map(|record| Ok(json_to_row(record, columns)?))
+            .collect()
+    }
+}
+
+impl ForeignDataWrapper<$ErrorType> for ${Camel}Fdw {
+    fn new(server: ForeignServer) -> ${Camel}FdwResult<Self> {
+        let opts = ${Camel}ServerOptions::from_server(&server)?;
+${new}
+        stats::inc_stats(Self::FDW_NAME, stats::Metric::CreateTimes, 1);
+
+        Ok(Self {
+            rt: create_async_runtime()?,
+            api_url: opts.api_url,
+${init}
+            circuit_breaker: CircuitBreaker::from_server(&server)?,
+            rows: Vec::new().into_iter(),
+        })
+    }
+
+    fn begin_scan(
+        &mut self,
+        _quals: &[Qual],
+        columns: &[Column],
+        _sorts: &[Sort],
+        _limit: &Option<Limit>,
+        options: &HashMap<String, String>,
+    ) -> ${Camel}FdwResult<()> {
+        let object = ${Camel}TableOptions::from_options(options)?.object;
+        let url = Url::parse(&format!("{}/{}", self.api_url.trim_end_matches('/'), object))?;
+
+        // TODO: push down the quals, sorts and limit, and fetch the other
+        // pages of the results
+        self.circuit_breaker.check()?;
+${send}\
+        let resp = record_request(&self.circuit_breaker, resp)?.error_for_status()?;
+        let body: JsonValue = self.rt.block_on(resp.json())?;
+        let rows = Self::resp_to_rows(&body, columns)?;
+
+        stats::inc_stats(Self::FDW_NAME, stats::Metric::RowsIn, rows.len() as i64);
+        stats::inc_stats(Self::FDW_NAME, stats::Metric::RowsOut, rows.len() as i64);
+        self.rows = rows.into_iter();
+
+        Ok(())
+    }
+
+    fn iter_scan(&mut self, row: &mut Row) -> ${Camel}FdwResult<Option<()>> {
+        Ok(self.rows.next().map(|src_row| row.replace_with(src_row)))
+    }
+
+    fn end_scan(&mut self) -> ${Camel}FdwResult<()> {
+        self.rows = Vec::new().into_iter();
+        Ok(())
+    }
+
+    // TODO: implement the data modify functions below, or remove them if the
+    // foreign tables are read only
+
+    fn begin_modify(&mut self, _options: &HashMap<String, String>) -> ${Camel}FdwResult<()> {
+        Ok(())
+    }
+
+    fn insert(&mut self, _src: &Row) -> ${Camel}FdwResult<()> {
+        Ok(())
+    }
+
+    fn update(&mut self, _rowid: &Cell, _new_row: &Row) -> ${Camel}FdwResult<()> {
+        Ok(())
+    }
+
+    fn delete(&mut self, _rowid: &Cell) -> ${Camel}FdwResult<()> {
+        Ok(())
+    }
+
+    fn end_modify(&mut self) -> ${Camel}FdwResult<()> {
+        Ok(())
+    }
+}
+"""
+)
+
+TESTS_RS = Template(
+    """\
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+
+    #[pg_test]
+    fn ${name}_smoketest() {
+        Spi::connect(|mut c| {
+            c.update(
+                r#"CREATE FOREIGN DATA WRAPPER ${name}_wrapper
+                         HANDLER ${name}_fdw_handler VALIDATOR ${name}_fdw_validator"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"CREATE SERVER my_${name}_server
+                         FOREIGN DATA WRAPPER ${name}_wrapper
+                         OPTIONS (
+${test_server_options}
+                         )"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"
+                CREATE FOREIGN TABLE ${name}_users (
+                  id text,
+                  name text,
+                  attrs jsonb
+                )
+                SERVER my_${name}_server
+                OPTIONS (
+                  object 'users'
+                )
+             "#,
+                None,
+                None,
+            )
+            .unwrap();
+
+            let name = c
+                .select("select name from ${name}_fdw_meta()", None, None)
+                .unwrap()
+                .first()
+                .get_one::<&str>()
+                .unwrap();
+            assert_eq!(name, Some("${Camel}Fdw"));
+
+            // TODO: query the foreign table from a mock server in
+            // wrappers/dockerfiles
+        });
+    }
+}
+"""
+)
+
+TEST_SERVER_OPTIONS = {
+    "api_key": [
+        ("api_url", "http://localhost:4000/v1"),
+        ("api_key", "test_key"),
+    ],
+    "oauth2": [
+        ("api_url", "http://localhost:4000/v1"),
+        ("token_url", "http://localhost:4000/oauth/token"),
+        ("client_id", "test_client"),
+        ("client_secret", "test_secret"),
+    ],
+    "aws": [
+        ("api_url", "http://localhost:4000/v1"),
+        ("aws_region", "us-east-1"),
+        ("aws_access_key_id", "test"),
+        ("aws_secret_access_key", "test"),
+    ],
+}
+
+README_MD = Template(
+    """\
+# ${title} Foreign Data Wrapper
+
+This is a foreign data wrapper for ${title}. It is developed using [Wrappers](https://github.com/supabase/wrappers).
+
+## Changelog
+
+| Version | Date       | Notes                                                |
+| ------- | ---------- | ---------------------------------------------------- |
+| 0.1.0   | ${today} | Initial version                                      |
+"""
+)
+
+# dependencies of the HTTP-based FDWs, same as stripe_fdw
+FEATURE_DEPS = [
+    '"http"',
+    '"reqwest"',
+    '"reqwest-middleware"',
+    '"reqwest-retry"',
+    '"task-local-extensions"',
+    '"serde_json"',
+    '"thiserror"',
+    '"url"',
+]
+
+
+def camel_case(name):
+    return "".join(part.capitalize() for part in name.split("_"))
+
+
+def add_feature(name, auth):
+    """Add the FDW feature to Cargo.toml, and to the native_fdws feature"""
+    path = WRAPPERS_DIR / "Cargo.toml"
+    text = path.read_text()
+    deps = AUTH_STYLES[auth]["features"] + FEATURE_DEPS
+    feature = "{}_fdw = [\n{}\n]\n".format(
+        name, "\n".join("    {},".format(dep) for dep in deps)
+    )
+    text = text.replace("native_fdws = [\n", feature + "native_fdws = [\n", 1)
+    text = re.sub(
+        r"(native_fdws = \[\n(?:    .*\n)*)\]",
+        r'\1    "{}_fdw",\n]'.format(name),
+        text,
+        count=1,
+    )
+    path.write_text(text)
+
+
+def add_module(name):
+    """Declare the FDW module, and enable the shared HTTP modules for it"""
+    path = FDW_DIR / "mod.rs"
+    text = path.read_text()
+    decl = '#[cfg(feature = "{0}_fdw")]\nmod {0}_fdw;\n\n'.format(name)
+    text = text.replace("#[cfg(any(\n", decl + "#[cfg(any(\n", 1)
+    for shared in ("request_error", "http_client"):
+        text = re.sub(
+            r'(    feature = "[a-z0-9_]+")\n\)\)\]\nmod {};'.format(shared),
+            r'\1,\n    feature = "{}_fdw"\n))]\nmod {};'.format(name, shared),
+            text,
+            count=1,
+        )
+    path.write_text(text)
+
+
+def main():
+    parser = argparse.ArgumentParser(
+        description="Generate the skeleton of a new native foreign data wrapper"
+    )
+    parser.add_argument("name", help="FDW name in snake case, e.g. acme")
+    parser.add_argument(
+        "--auth",
+        choices=sorted(AUTH_STYLES),
+        default="api_key",
+        help="how the FDW authenticates to the remote API (default: api_key)",
+    )
+    args = parser.parse_args()
+
+    name = args.name.lower().removesuffix("_fdw")
+    if not re.fullmatch(r"[a-z][a-z0-9_]*", name):
+        sys.exit("invalid FDW name '{}', use snake case like 'acme'".format(args.name))
+    fdw_dir = FDW_DIR / "{}_fdw".format(name)
+    if fdw_dir.exists():
+        sys.exit("{} already exists".format(fdw_dir))
+
+    auth = AUTH_STYLES[args.auth]
+    camel = camel_case(name)
+    values = {
+        "name": name,
+        "Camel": camel,
+        "title": camel,
+        "ErrorType": "{}FdwError".format(camel),
+        "today": date.today().isoformat(),
+        "test_server_options": ",\n".join(
+            "                           {} '{}'".format(k, v)
+            for k, v in TEST_SERVER_OPTIONS[args.auth]
+        ),
+    }
+    # the auth snippets can have placeholders too
+    values.update(
+        {k: Template(v).substitute(values) for k, v in auth.items() if k != "features"}
+    )
+
+    fdw_dir.mkdir()
+    sources = {
+        "mod.rs": MOD_RS,
+        "{}_fdw.rs".format(name): FDW_RS,
+        "tests.rs": TESTS_RS,
+    }
+    for file_name, template in sources.items():
+        (fdw_dir / file_name).write_text(template.substitute(values))
+    (fdw_dir / "README.md").write_text(README_MD.substitute(values))
+    add_feature(name, args.auth)
+    add_module(name)
+
+    # the snippets of auth styles are not aligned with rustfmt
+    try:
+        subprocess.run(
+            ["rustfmt", "--edition", "2021", *(fdw_dir / f for f in sources)],
+            check=False,
+        )
+    except FileNotFoundError:
+        print("rustfmt is not found, run `cargo fmt` to format the generated files")
+
+    print("created {}Fdw in {}".format(camel, fdw_dir.relative_to(WRAPPERS_DIR.parent)))
+    print("run it with: cargo pgrx run pg15 --features {}_fdw".format(name))
+
+
+if __name__ == "__main__":
+    main()
@@ -22,3 +22,54 @@ COMMENT ON COLUMN wrappers_fdw_stats.bytes_in IS 'Total bytes input from origin'
 COMMENT ON COLUMN wrappers_fdw_stats.bytes_out IS 'Total bytes output to Postgres';
 COMMENT ON COLUMN wrappers_fdw_stats.metadata IS 'Metadata specific for the FDW';
 
+
+DROP TABLE IF EXISTS wrappers_sync_jobs;
+
+CREATE TABLE wrappers_sync_jobs (
+  id                bigint GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+  foreign_table     regclass NOT NULL,
+  target            regclass NOT NULL,
+  mode              text NOT NULL DEFAULT 'full' CHECK (mode IN ('full', 'incremental', 'append')),
+  watermark         text NULL,
+  sync_interval     interval NOT NULL DEFAULT '1 hour',
+  enabled           boolean NOT NULL DEFAULT true,
+  owner             name NOT NULL DEFAULT current_user,
+  last_run_at       timestamptz NULL,
+  last_status       text NULL,
+  last_rows         bigint NULL,
+  last_duration     interval NULL,
+  last_error        text NULL,
+  created_at        timestamptz NOT NULL DEFAULT timezone('utc'::text, now()),
+  UNIQUE (foreign_table, target)
+);
+
+COMMENT ON TABLE wrappers_sync_jobs IS 'Wrappers scheduled sync jobs run by the background worker';
+COMMENT ON COLUMN wrappers_sync_jobs.mode IS 'Sync mode: full, incremental or append';
+COMMENT ON COLUMN wrappers_sync_jobs.watermark IS 'Watermark column of the incremental sync';
+COMMENT ON COLUMN wrappers_sync_jobs.sync_interval IS 'Interval between the runs of the job';
+COMMENT ON COLUMN wrappers_sync_jobs.owner IS 'Role the job runs as';
+COMMENT ON COLUMN wrappers_sync_jobs.last_status IS 'Status of the last run: succeeded or failed';
+COMMENT ON COLUMN wrappers_sync_jobs.last_rows IS 'Number of rows synced by the last run';
+COMMENT ON COLUMN wrappers_sync_jobs.last_error IS 'Error message of the last failed run';
+
+-- only superusers can create the jobs run as other roles
+CREATE OR REPLACE FUNCTION wrappers_sync_jobs_check_owner()
+RETURNS trigger
+LANGUAGE plpgsql
+AS $$
+BEGIN
+  IF NEW.owner <> current_user
+     AND (TG_OP = 'INSERT' OR NEW.owner IS DISTINCT FROM OLD.owner)
+     AND NOT (SELECT rolsuper FROM pg_catalog.pg_roles WHERE rolname = current_user) THEN
+    RAISE EXCEPTION 'sync job owner must be the current user';
+  END IF;
+  RETURN NEW;
+END;
+$$;
+
+DROP TRIGGER IF EXISTS wrappers_sync_jobs_check_owner ON wrappers_sync_jobs;
+CREATE TRIGGER wrappers_sync_jobs_check_owner
+  BEFORE INSERT OR UPDATE ON wrappers_sync_jobs
+  FOR EACH ROW EXECUTE FUNCTION wrappers_sync_jobs_check_owner();
+
+SELECT pg_catalog.pg_extension_config_dump('wrappers_sync_jobs', '');
@@ -0,0 +1,9 @@
+use pgrx::prelude::*;
+use supabase_wrappers::prelude::invalidate_cache;
+
+/// Invalidate the cached scan results of a foreign table in current backend,
+/// so the next scan fetches the rows from remote again
+#[pg_extern]
+fn wrappers_cache_invalidate(table: pg_sys::Oid) {
+    invalidate_cache(table);
+}
@@ -0,0 +1,104 @@
+use pgrx::prelude::*;
+use pgrx::spi::quote_identifier;
+
+type CapabilitiesRow = (
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    bool,
+    bool,
+    bool,
+    bool,
+    bool,
+    bool,
+    bool,
+    bool,
+);
+
+/// List the foreign data wrappers compiled into the extension, with their
+/// metadata and capabilities
+#[pg_extern]
+fn wrappers_fdw_capabilities() -> TableIterator<
+    'static,
+    (
+        name!(name, Option<String>),
+        name!(version, Option<String>),
+        name!(author, Option<String>),
+        name!(website, Option<String>),
+        name!(read, bool),
+        name!(write, bool),
+        name!(import_schema, bool),
+        name!(qual_pushdown, bool),
+        name!(sort_pushdown, bool),
+        name!(limit_pushdown, bool),
+        name!(aggregate_pushdown, bool),
+        name!(join_pushdown, bool),
+    ),
+> {
+    let rows = Spi::connect(|client| {
+        // the meta and capabilities functions of each FDW are created by the
+        // wrappers_fdw macro, e.g. stripe_fdw_meta and stripe_fdw_capabilities
+        let funcs = client
+            .select(
+                "select n.nspname::text, p.proname::text
+                 from pg_catalog.pg_proc p
+                 join pg_catalog.pg_namespace n on n.oid = p.pronamespace
+                 join pg_catalog.pg_depend d
+                   on d.classid = 'pg_catalog.pg_proc'::regclass
+                  and d.objid = p.oid and d.deptype = 'e'
+                 join pg_catalog.pg_extension e
+                   on e.oid = d.refobjid and e.extname = 'wrappers'
+                 where p.proname like '%\\_fdw\\_capabilities' and p.pronargs = 0
+                 order by p.proname",
+                None,
+                None,
+            )?
+            .map(|row| {
+                Ok((
+                    row.get::<String>(1)?.unwrap_or_default(),
+                    row.get::<String>(2)?.unwrap_or_default(),
+                ))
+            })
+            .collect::<pgrx::spi::Result<Vec<_>>>()?;
+
+        let mut rows: Vec<CapabilitiesRow> = Vec::new();
+        for (schema, func) in funcs {
+            let Some(prefix) = func.strip_suffix("_capabilities") else {
+                continue;
+            };
+            let sql = format!(
+                "select m.name, m.version, m.author, m.website, c.read, c.write,
+                        c.import_schema, c.qual_pushdown, c.sort_pushdown,
+                        c.limit_pushdown, c.aggregate_pushdown, c.join_pushdown
+                 from {schema}.{meta}() m, {schema}.{caps}() c",
+                schema = quote_identifier(&schema),
+                meta = quote_identifier(format!("{}_meta", prefix)),
+                caps = quote_identifier(&func),
+            );
+            for row in client.select(&sql, None, None)? {
+                let flag = |idx: usize| -> pgrx::spi::Result<bool> {
+                    Ok(row.get::<bool>(idx)?.unwrap_or_default())
+                };
+                rows.push((
+                    row.get::<String>(1)?,
+                    row.get::<String>(2)?,
+                    row.get::<String>(3)?,
+                    row.get::<String>(4)?,
+                    flag(5)?,
+                    flag(6)?,
+                    flag(7)?,
+                    flag(8)?,
+                    flag(9)?,
+                    flag(10)?,
+                    flag(11)?,
+                    flag(12)?,
+                ));
+            }
+        }
+        Ok::<_, pgrx::spi::Error>(rows)
+    })
+    .unwrap_or_else(|err| error!("list foreign data wrappers failed: {}", err));
+
+    TableIterator::new(rows)
+}
@@ -0,0 +1,15 @@
+use pgrx::prelude::*;
+use std::ffi::CString;
+use supabase_wrappers::prelude::reset_circuit;
+
+/// Reset the circuit breaker of a foreign server, so queries can send requests
+/// to it again without waiting for the cool-down period
+#[pg_extern]
+fn wrappers_reset_circuit(server: &str) {
+    let server_name = CString::new(server).expect("server name should not contain nul byte");
+    let server_oid = unsafe {
+        let fserver = pg_sys::GetForeignServerByName(server_name.as_ptr(), false);
+        (*fserver).serverid
+    };
+    reset_circuit(server_oid);
+}
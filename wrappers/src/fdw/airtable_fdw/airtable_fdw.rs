@@ -1,30 +1,28 @@
+use crate::fdw::http_client::{create_http_client, record_request};
 use crate::stats;
 use pgrx::pg_sys;
 use reqwest::{self, header};
-use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
-use reqwest_retry::{policies::ExponentialBackoff, RetryTransientMiddleware};
+use reqwest_middleware::ClientWithMiddleware;
 use std::collections::HashMap;
 use url::Url;
 
+use supabase_wrappers::pagination::{Page, PageTokenPaginator, Pages};
 use supabase_wrappers::prelude::*;
 
-use super::result::AirtableResponse;
+use super::result::AirtableRecord;
 use super::{AirtableFdwError, AirtableFdwResult};
 
-fn create_client(api_key: &str) -> Result<ClientWithMiddleware, AirtableFdwError> {
+fn create_client(
+    api_key: &str,
+    options: &HashMap<String, String>,
+) -> Result<ClientWithMiddleware, AirtableFdwError> {
     let mut headers = header::HeaderMap::new();
     let value = format!("Bearer {}", api_key);
     let mut auth_value =
         header::HeaderValue::from_str(&value).map_err(|_| AirtableFdwError::InvalidApiKeyHeader)?;
     auth_value.set_sensitive(true);
     headers.insert(header::AUTHORIZATION, auth_value);
-    let client = reqwest::Client::builder()
-        .default_headers(headers)
-        .build()?;
-    let retry_policy = ExponentialBackoff::builder().build_with_max_retries(3);
-    Ok(ClientBuilder::new(client)
-        .with(RetryTransientMiddleware::new_with_policy(retry_policy))
-        .build())
+    Ok(create_http_client(options, headers)?)
 }
 
 #[wrappers_fdw(
@@ -36,8 +34,12 @@ fn create_client(api_key: &str) -> Result<ClientWithMiddleware, AirtableFdwError
 pub(crate) struct AirtableFdw {
     rt: Runtime,
     client: Option<ClientWithMiddleware>,
+    rate_limiter: RateLimiter,
+    circuit_breaker: CircuitBreaker,
     base_url: String,
-    scan_result: Option<Vec<Row>>,
+    pages: Option<Pages<PageTokenPaginator>>,
+    scan_result: Option<ScanResultBuffer>,
+    tgt_cols: Vec<Column>,
 }
 
 impl AirtableFdw {
@@ -54,37 +56,28 @@ impl AirtableFdw {
         }
     }
 
-    fn set_limit_offset(
-        &self,
-        url: &str,
-        page_size: Option<usize>,
-        offset: Option<&str>,
-    ) -> AirtableFdwResult<String> {
-        let mut params = Vec::new();
-        if let Some(page_size) = page_size {
-            params.push(("pageSize", format!("{}", page_size)));
-        }
-        if let Some(offset) = offset {
-            params.push(("offset", offset.to_string()));
-        }
-
-        Ok(Url::parse_with_params(url, &params).map(|x| x.into())?)
-    }
-
-    // convert response body text to rows
-    fn parse_resp(
-        &self,
-        resp_body: &str,
-        columns: &[Column],
-    ) -> AirtableFdwResult<(Vec<Row>, Option<String>)> {
-        let response: AirtableResponse = serde_json::from_str(resp_body)?;
-        let mut result = Vec::new();
-
-        for record in response.records.iter() {
-            result.push(record.to_row(columns)?);
-        }
+    // fetch a page of records
+    fn fetch_page(&self, url: Url) -> AirtableFdwResult<Page> {
+        let Some(client) = &self.client else {
+            return Ok(Page::default());
+        };
 
-        Ok((result, response.offset))
+        self.circuit_breaker.check()?;
+        self.rate_limiter.wait();
+        let body = record_request(
+            &self.circuit_breaker,
+            self.rt.block_on(client.get(url).send()).and_then(|resp| {
+                resp.error_for_status()
+                    .and_then(|resp| self.rt.block_on(resp.text()))
+                    .map_err(reqwest_middleware::Error::from)
+            }),
+        )?;
+        stats::inc_stats(Self::FDW_NAME, stats::Metric::BytesIn, body.len() as i64);
+
+        Ok(Page {
+            body: serde_json::from_str(&body)?,
+            link: None,
+        })
     }
 }
 
@@ -96,13 +89,13 @@ impl ForeignDataWrapper<AirtableFdwError> for AirtableFdw {
             .get("api_url")
             .map(|t| t.to_owned())
             .unwrap_or_else(|| "https://api.airtable.com/v0".to_string());
-
-        let client = match server.options.get("api_key") {
-            Some(api_key) => Some(create_client(api_key)?),
+        let cred_opts = server.credential_options(&["api_key", "api_key_id"]);
+        let client = match cred_opts.get("api_key") {
+            Some(api_key) => Some(create_client(api_key, &server.options)?),
             None => {
-                let key_id = require_option("api_key_id", &server.options)?;
-                if let Some(api_key) = get_vault_secret(key_id) {
-                    Some(create_client(&api_key)?)
+                let key_id = require_option("api_key_id", cred_opts)?;
+                if let Some(api_key) = get_secret(&server.options, key_id) {
+                    Some(create_client(&api_key, &server.options)?)
                 } else {
                     None
                 }
@@ -114,8 +107,12 @@ impl ForeignDataWrapper<AirtableFdwError> for AirtableFdw {
         Ok(Self {
             rt: create_async_runtime()?,
             client,
+            rate_limiter: RateLimiter::from_server(&server)?,
+            circuit_breaker: CircuitBreaker::from_server(&server)?,
             base_url,
+            pages: None,
             scan_result: None,
+            tgt_cols: Vec::new(),
         })
     }
 
@@ -130,57 +127,56 @@ impl ForeignDataWrapper<AirtableFdwError> for AirtableFdw {
         let base_id = require_option("base_id", options)?;
         let table_id = require_option("table_id", options)?;
         let view_id = options.get("view_id");
-        let url = self.build_url(base_id, table_id, view_id);
-
-        let mut rows = Vec::new();
-        if let Some(client) = &self.client {
-            let mut offset: Option<String> = None;
-
-            loop {
-                // Fetch all of the rows upfront. Arguably, this could be done in batches (and invoked each
-                // time iter_scan() runs out of rows) to pipeline the I/O, but we'd have to manage more
-                // state so starting with the simpler solution.
-                let url = self.set_limit_offset(&url, None, offset.as_deref())?;
-
-                let body = self.rt.block_on(client.get(&url).send()).and_then(|resp| {
-                    resp.error_for_status()
-                        .and_then(|resp| self.rt.block_on(resp.text()))
-                        .map_err(reqwest_middleware::Error::from)
-                })?;
+        let url = Url::parse(&self.build_url(base_id, table_id, view_id))?;
+
+        // the pages are fetched in iter_scan() when the rows of previous page
+        // are all returned, the next page is requested by the `offset` token
+        // in the response
+        self.pages = Some(Pages::new(
+            PageTokenPaginator::new("offset", "/offset"),
+            url,
+            "/records",
+        ));
+        self.scan_result = Some(ScanResultBuffer::new(columns, options)?);
+        self.tgt_cols = columns.to_vec();
 
-                let (new_rows, new_offset) = self.parse_resp(&body, columns)?;
-                rows.extend(new_rows);
-
-                stats::inc_stats(Self::FDW_NAME, stats::Metric::BytesIn, body.len() as i64);
-
-                if let Some(new_offset) = new_offset {
-                    offset = Some(new_offset);
-                } else {
-                    break;
-                }
-            }
-        }
-
-        stats::inc_stats(Self::FDW_NAME, stats::Metric::RowsIn, rows.len() as i64);
-        stats::inc_stats(Self::FDW_NAME, stats::Metric::RowsOut, rows.len() as i64);
-
-        self.scan_result = Some(rows);
         Ok(())
     }
 
     fn iter_scan(&mut self, row: &mut Row) -> AirtableFdwResult<Option<()>> {
-        if let Some(ref mut result) = self.scan_result {
-            if !result.is_empty() {
-                return Ok(result
-                    .drain(0..1)
-                    .last()
-                    .map(|src_row| row.replace_with(src_row)));
+        let (Some(mut pages), Some(mut scan_result)) = (self.pages.take(), self.scan_result.take())
+        else {
+            return Ok(None);
+        };
+
+        let result = loop {
+            if let Some(src_row) = scan_result.next_row() {
+                row.replace_with(src_row);
+                break Ok(Some(()));
             }
-        }
-        Ok(None)
+            let tgt_cols = &self.tgt_cols;
+            match pages.fill_next(
+                &mut scan_result,
+                |url| self.fetch_page(url),
+                |record| {
+                    stats::inc_stats(Self::FDW_NAME, stats::Metric::RowsIn, 1);
+                    stats::inc_stats(Self::FDW_NAME, stats::Metric::RowsOut, 1);
+                    serde_json::from_value::<AirtableRecord>(record.clone())?.to_row(tgt_cols)
+                },
+            ) {
+                Ok(true) => continue,
+                Ok(false) => break Ok(None),
+                Err(err) => break Err(err),
+            }
+        };
+
+        self.pages = Some(pages);
+        self.scan_result = Some(scan_result);
+        result
     }
 
     fn end_scan(&mut self) -> AirtableFdwResult<()> {
+        self.pages.take();
         self.scan_result.take();
         Ok(())
     }
@@ -7,7 +7,9 @@ use pgrx::pg_sys::panic::ErrorReport;
 use pgrx::prelude::PgSqlErrorCode;
 use thiserror::Error;
 
-use supabase_wrappers::prelude::{CreateRuntimeError, OptionsError};
+use crate::fdw::http_client::HttpClientError;
+use crate::fdw::request_error::{request_error, request_middleware_error};
+use supabase_wrappers::prelude::{CreateRuntimeError, FdwError, OptionsError};
 
 #[derive(Error, Debug)]
 enum AirtableFdwError {
@@ -20,6 +22,9 @@ enum AirtableFdwError {
     #[error("{0}")]
     CreateRuntimeError(#[from] CreateRuntimeError),
 
+    #[error("{0}")]
+    HttpClientError(#[from] HttpClientError),
+
     #[error("parse url failed: {0}")]
     UrlParseError(#[from] url::ParseError),
 
@@ -38,6 +43,9 @@ enum AirtableFdwError {
     #[error("{0}")]
     OptionsError(#[from] OptionsError),
 
+    #[error("{0}")]
+    FdwError(#[from] FdwError),
+
     #[error("{0}")]
     NumericConversionError(#[from] pgrx::numeric::Error),
 }
@@ -46,7 +54,18 @@ impl From<AirtableFdwError> for ErrorReport {
     fn from(value: AirtableFdwError) -> Self {
         match value {
             AirtableFdwError::CreateRuntimeError(e) => e.into(),
+            AirtableFdwError::HttpClientError(e) => e.into(),
             AirtableFdwError::OptionsError(e) => e.into(),
+            AirtableFdwError::FdwError(e) => e.into(),
+            AirtableFdwError::RequestError(ref e) => request_error(e, format!("{value}")).into(),
+            AirtableFdwError::RequestMiddlewareError(ref e) => {
+                request_middleware_error(e, format!("{value}")).into()
+            }
+            AirtableFdwError::UnsupportedColumnType(_)
+            | AirtableFdwError::ColumnTypeNotMatch(_)
+            | AirtableFdwError::NumericConversionError(_) => {
+                FdwError::TypeMismatch(format!("{value}")).into()
+            }
             _ => ErrorReport::new(PgSqlErrorCode::ERRCODE_FDW_ERROR, format!("{value}"), ""),
         }
     }
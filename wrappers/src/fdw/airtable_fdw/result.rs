@@ -10,12 +10,6 @@ use supabase_wrappers::prelude::*;
 
 use super::{AirtableFdwError, AirtableFdwResult};
 
-#[derive(Deserialize, Debug)]
-pub struct AirtableResponse {
-    pub records: Vec<AirtableRecord>,
-    pub offset: Option<String>,
-}
-
 #[derive(Debug)]
 pub struct AirtableFields(HashMap<String, Value>);
 
@@ -169,11 +163,9 @@ impl AirtableRecord {
                 pg_sys::NUMERICOID => match self.fields.0.get(&col.name) {
                     Some(val) => {
                         if let Value::Number(v) = val {
-                            let n = match v.as_f64() {
-                                Some(n) => Some(Cell::Numeric(pgrx::AnyNumeric::try_from(n)?)),
-                                None => None,
-                            };
-                            Ok(n)
+                            // convert from the number text to keep its precision
+                            let n = pgrx::AnyNumeric::try_from(v.to_string().as_str())?;
+                            Ok(Some(Cell::Numeric(n)))
                         } else {
                             Err(())
                         }
@@ -219,9 +211,7 @@ impl AirtableRecord {
                     || Ok(None),
                     |val| {
                         if let Value::String(v) = val {
-                            let n = pgrx::prelude::TimestampWithTimeZone::from_str(v.as_str())
-                                .ok()
-                                .map(Cell::Timestamptz);
+                            let n = timestamptz_from_rfc3339(v.as_str()).map(Cell::Timestamptz);
                             Ok(n)
                         } else {
                             Err(())
@@ -1,11 +1,12 @@
 use crate::fdw::auth0_fdw::auth0_client::row::ResultPayload;
+use crate::fdw::http_client::{create_http_client, record_request, HttpClientError};
+use crate::fdw::request_error::{request_error, request_middleware_error};
 use http::{HeaderMap, HeaderName, HeaderValue};
 use pgrx::pg_sys::panic::ErrorReport;
 use pgrx::PgSqlErrorCode;
 use reqwest::Url;
-use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
-use reqwest_retry::policies::ExponentialBackoff;
-use reqwest_retry::RetryTransientMiddleware;
+use reqwest_middleware::ClientWithMiddleware;
+use std::collections::HashMap;
 use supabase_wrappers::prelude::*;
 use thiserror::Error;
 use url::ParseError;
@@ -15,19 +16,32 @@ pub(crate) mod row;
 pub(crate) struct Auth0Client {
     url: Url,
     client: ClientWithMiddleware,
+    rate_limiter: RateLimiter,
+    circuit_breaker: CircuitBreaker,
 }
 
 pub(crate) mod rows_iterator;
 
 impl Auth0Client {
-    pub(crate) fn new(url: &str, api_key: &str) -> Result<Self, Auth0ClientError> {
+    pub(crate) fn new(
+        url: &str,
+        api_key: &str,
+        options: &HashMap<String, String>,
+        rate_limiter: RateLimiter,
+        circuit_breaker: CircuitBreaker,
+    ) -> Result<Self, Auth0ClientError> {
         Ok(Self {
             url: Url::parse(url)?,
-            client: Self::create_client(api_key)?,
+            client: Self::create_client(api_key, options)?,
+            rate_limiter,
+            circuit_breaker,
         })
     }
 
-    fn create_client(api_key: &str) -> Result<ClientWithMiddleware, Auth0ClientError> {
+    fn create_client(
+        api_key: &str,
+        options: &HashMap<String, String>,
+    ) -> Result<ClientWithMiddleware, Auth0ClientError> {
         let mut headers = HeaderMap::new();
         let header_name = HeaderName::from_static("authorization"); // Use 'authorization' instead of 'api-key'
                                                                     // Format the API key as a Bearer token
@@ -36,13 +50,7 @@ impl Auth0Client {
             .map_err(|_| Auth0ClientError::InvalidApiKeyHeader)?;
         api_key_value.set_sensitive(true);
         headers.insert(header_name, api_key_value);
-        let client = reqwest::Client::builder()
-            .default_headers(headers)
-            .build()?;
-        let retry_policy = ExponentialBackoff::builder().build_with_max_retries(3);
-        Ok(ClientBuilder::new(client)
-            .with(RetryTransientMiddleware::new_with_policy(retry_policy))
-            .build())
+        Ok(create_http_client(options, headers)?)
     }
 
     pub fn get_client(&self) -> &ClientWithMiddleware {
@@ -56,22 +64,27 @@ impl Auth0Client {
     ) -> Result<ResultPayload, Auth0ClientError> {
         let rt = create_async_runtime()?;
 
-        rt.block_on(async {
-            let mut url = self.url.clone();
-            url.query_pairs_mut().append_pair("page", &page.to_string());
-            if let Some(per_page) = per_page {
-                url.query_pairs_mut()
-                    .append_pair("per_page", &per_page.to_string());
-            }
+        let mut url = self.url.clone();
+        url.query_pairs_mut().append_pair("page", &page.to_string());
+        if let Some(per_page) = per_page {
+            url.query_pairs_mut()
+                .append_pair("per_page", &per_page.to_string());
+        }
 
-            url.query_pairs_mut().append_pair("include_totals", "true");
+        url.query_pairs_mut().append_pair("include_totals", "true");
 
-            let response = self.get_client().get(url.as_str()).send().await?;
-            let response = response.error_for_status()?;
-            let payload = response.json::<ResultPayload>().await?;
+        self.circuit_breaker.check()?;
+        self.rate_limiter.wait();
+        let response = record_request(
+            &self.circuit_breaker,
+            rt.block_on(async {
+                let response = self.get_client().get(url.as_str()).send().await?;
+                Ok(response.error_for_status()?)
+            }),
+        )?;
+        let payload = rt.block_on(response.json::<ResultPayload>())?;
 
-            Ok(payload)
-        })
+        Ok(payload)
     }
 }
 #[derive(Error, Debug)]
@@ -79,6 +92,12 @@ pub(crate) enum Auth0ClientError {
     #[error("{0}")]
     CreateRuntimeError(#[from] CreateRuntimeError),
 
+    #[error("{0}")]
+    FdwError(#[from] FdwError),
+
+    #[error("{0}")]
+    HttpClientError(#[from] HttpClientError),
+
     #[error("invalid api_key header")]
     InvalidApiKeyHeader,
 
@@ -99,10 +118,14 @@ impl From<Auth0ClientError> for ErrorReport {
     fn from(value: Auth0ClientError) -> Self {
         match value {
             Auth0ClientError::CreateRuntimeError(e) => e.into(),
+            Auth0ClientError::FdwError(e) => e.into(),
+            Auth0ClientError::HttpClientError(e) => e.into(),
+            Auth0ClientError::ReqwestError(ref e) => request_error(e, format!("{value}")).into(),
+            Auth0ClientError::ReqwestMiddlewareError(ref e) => {
+                request_middleware_error(e, format!("{value}")).into()
+            }
             Auth0ClientError::UrlParseError(_)
             | Auth0ClientError::InvalidApiKeyHeader
-            | Auth0ClientError::ReqwestError(_)
-            | Auth0ClientError::ReqwestMiddlewareError(_)
             | Auth0ClientError::SerdeError(_) => {
                 ErrorReport::new(PgSqlErrorCode::ERRCODE_FDW_ERROR, format!("{value}"), "")
             }
@@ -7,6 +7,7 @@ use std::collections::HashMap;
 use supabase_wrappers::prelude::*;
 
 use crate::fdw::auth0_fdw::auth0_client::Auth0ClientError;
+use crate::fdw::request_error::{request_error, request_middleware_error};
 use pgrx::pg_sys::panic::ErrorReport;
 use pgrx::PgSqlErrorCode;
 use thiserror::Error;
@@ -20,10 +21,19 @@ use thiserror::Error;
 pub(crate) struct Auth0Fdw {
     // row counter
     url: String,
-    api_key: String,
+    credentials: Credentials,
+    server_options: HashMap<String, String>,
+    rate_limiter: RateLimiter,
+    circuit_breaker: CircuitBreaker,
     rows_iterator: Option<RowsIterator>,
 }
 
+// API key, or OAuth2 client credentials to get the access token
+enum Credentials {
+    ApiKey(String),
+    OAuth2(OAuth2Client),
+}
+
 #[derive(Error, Debug)]
 enum Auth0FdwError {
     #[error("{0}")]
@@ -47,15 +57,18 @@ enum Auth0FdwError {
     #[error("{0}")]
     OptionsError(#[from] OptionsError),
 
+    #[error("{0}")]
+    OAuth2Error(#[from] OAuth2Error),
+
     #[error("{0}")]
     NumericConversionError(#[from] pgrx::numeric::Error),
-    #[error("no secret found in vault with id {0}")]
+    #[error("no secret found with id {0}")]
     SecretNotFound(String),
 
     #[error("`url` option must be set")]
     URLOptionMissing,
 
-    #[error("exactly one of `api_key` or `api_key_id` options must be set")]
+    #[error("exactly one of `api_key`, `api_key_id` or `client_id` options must be set")]
     SetOneOfApiKeyAndApiKeyIdSet,
 }
 
@@ -64,9 +77,19 @@ impl From<Auth0FdwError> for ErrorReport {
         match value {
             Auth0FdwError::CreateRuntimeError(e) => e.into(),
             Auth0FdwError::OptionsError(e) => e.into(),
+            Auth0FdwError::OAuth2Error(e) => e.into(),
             Auth0FdwError::Auth0ClientError(e) => e.into(),
-            Auth0FdwError::SecretNotFound(_) => {
-                ErrorReport::new(PgSqlErrorCode::ERRCODE_FDW_ERROR, format!("{value}"), "")
+            Auth0FdwError::RequestError(ref e) => request_error(e, format!("{value}")).into(),
+            Auth0FdwError::RequestMiddlewareError(ref e) => {
+                request_middleware_error(e, format!("{value}")).into()
+            }
+            Auth0FdwError::SecretNotFound(_)
+            | Auth0FdwError::URLOptionMissing
+            | Auth0FdwError::SetOneOfApiKeyAndApiKeyIdSet => {
+                FdwError::InvalidOption(format!("{value}")).into()
+            }
+            Auth0FdwError::NumericConversionError(_) => {
+                FdwError::TypeMismatch(format!("{value}")).into()
             }
             _ => ErrorReport::new(PgSqlErrorCode::ERRCODE_FDW_ERROR, format!("{value}"), ""),
         }
@@ -96,20 +119,31 @@ impl ForeignDataWrapper<Auth0FdwError> for Auth0Fdw {
 
     fn new(server: ForeignServer) -> Result<Self, Auth0FdwError> {
         let url = require_option("url", &server.options)?.to_string();
-        let api_key = if let Some(api_key) = server.options.get("api_key") {
-            api_key.clone()
+        let cred_opts = server.credential_options(&["api_key", "api_key_id"]);
+        let credentials = if let Some(oauth2) = OAuth2Client::from_options(&server.options)? {
+            Credentials::OAuth2(oauth2)
+        } else if let Some(api_key) = cred_opts.get("api_key") {
+            Credentials::ApiKey(api_key.clone())
         } else {
-            let api_key_id = server
-                .options
+            let api_key_id = cred_opts
                 .get("api_key_id")
                 .expect("`api_key_id` must be set if `api_key` is not");
-            get_vault_secret(api_key_id).ok_or(Auth0FdwError::SecretNotFound(api_key_id.clone()))?
+            Credentials::ApiKey(
+                get_secret(&server.options, api_key_id)
+                    .ok_or(Auth0FdwError::SecretNotFound(api_key_id.clone()))?,
+            )
         };
 
+        let rate_limiter = RateLimiter::from_server(&server)?;
+        let circuit_breaker = CircuitBreaker::from_server(&server)?;
+
         stats::inc_stats(Self::FDW_NAME, stats::Metric::CreateTimes, 1);
         Ok(Self {
             url,
-            api_key,
+            credentials,
+            server_options: server.options.clone(),
+            rate_limiter,
+            circuit_breaker,
             rows_iterator: None,
         })
     }
@@ -122,7 +156,18 @@ impl ForeignDataWrapper<Auth0FdwError> for Auth0Fdw {
         _limit: &Option<Limit>,
         _options: &HashMap<String, String>,
     ) -> Auth0FdwResult<()> {
-        let auth0_client = Auth0Client::new(&self.url, &self.api_key)?;
+        // the access token is refreshed if it is expiring
+        let api_key = match &self.credentials {
+            Credentials::ApiKey(api_key) => api_key.clone(),
+            Credentials::OAuth2(oauth2) => oauth2.access_token()?,
+        };
+        let auth0_client = Auth0Client::new(
+            &self.url,
+            &api_key,
+            &self.server_options,
+            self.rate_limiter,
+            self.circuit_breaker.clone(),
+        )?;
         self.rows_iterator = Some(RowsIterator::new(columns.to_vec(), 50, auth0_client));
 
         Ok(())
@@ -151,9 +196,13 @@ impl ForeignDataWrapper<Auth0FdwError> for Auth0Fdw {
             if oid == FOREIGN_SERVER_RELATION_ID {
                 let api_key_exists = check_options_contain(&options, "api_key").is_ok();
                 let api_key_id_exists = check_options_contain(&options, "api_key_id").is_ok();
+                let client_id_exists = check_options_contain(&options, "client_id").is_ok();
                 let url_exists = check_options_contain(&options, "url").is_ok();
-                if (api_key_exists && api_key_id_exists) || (!api_key_exists && !api_key_id_exists)
-                {
+                let credentials_count = [api_key_exists, api_key_id_exists, client_id_exists]
+                    .iter()
+                    .filter(|exists| **exists)
+                    .count();
+                if credentials_count != 1 {
                     return Err(Auth0FdwError::SetOneOfApiKeyAndApiKeyIdSet);
                 }
                 if !url_exists {
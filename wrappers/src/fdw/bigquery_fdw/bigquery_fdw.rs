@@ -10,7 +10,6 @@ use gcp_bigquery_client::{
     },
     Client,
 };
-use pgrx::prelude::PgSqlErrorCode;
 use pgrx::prelude::{AnyNumeric, Date, Timestamp};
 use serde_json::json;
 use std::collections::HashMap;
@@ -24,8 +23,10 @@ fn field_to_cell(rs: &ResultSet, field: &TableFieldSchema) -> BigQueryFdwResult<
         FieldType::Boolean => rs.get_bool_by_name(&field.name)?.map(Cell::Bool),
         FieldType::Int64 | FieldType::Integer => rs.get_i64_by_name(&field.name)?.map(Cell::I64),
         FieldType::Float64 | FieldType::Float => rs.get_f64_by_name(&field.name)?.map(Cell::F64),
-        FieldType::Numeric => match rs.get_f64_by_name(&field.name)? {
-            Some(v) => Some(Cell::Numeric(AnyNumeric::try_from(v)?)),
+        // BigQuery returns numeric value as a decimal string, so it is converted
+        // from the string to keep its precision
+        FieldType::Numeric => match rs.get_string_by_name(&field.name)? {
+            Some(v) => Some(Cell::Numeric(AnyNumeric::try_from(v.as_str())?)),
             None => None,
         },
         FieldType::String => rs.get_string_by_name(&field.name)?.map(Cell::String),
@@ -70,6 +71,17 @@ pub(crate) struct BigQueryFdw {
 impl BigQueryFdw {
     const FDW_NAME: &'static str = "BigQueryFdw";
 
+    fn deparse_qual(qual: &Qual) -> String {
+        // deparse each sub-condition in boolean expression, e.g. "a = 1 or b = 2"
+        if qual.is_bool_expr() {
+            return qual.deparse_bool_expr(Self::deparse_qual);
+        }
+
+        qual.deparse_like(SqlDialect::BigQuery).unwrap_or_else(|| {
+            qual.deparse_with_fmt(&mut SqlCellFormatter::new(SqlDialect::BigQuery))
+        })
+    }
+
     fn deparse(
         &self,
         quals: &[Qual],
@@ -97,7 +109,7 @@ impl BigQueryFdw {
         } else {
             let cond = quals
                 .iter()
-                .map(|q| q.deparse())
+                .map(Self::deparse_qual)
                 .collect::<Vec<String>>()
                 .join(" and ");
             format!("select {} from {} where {}", tgts, table, cond)
@@ -175,6 +187,7 @@ impl ForeignDataWrapper<BigQueryFdwError> for BigQueryFdw {
             .map(|t| t.to_owned())
             .unwrap_or_else(|| "https://bigquery.googleapis.com/bigquery/v2".to_string());
 
+        let cred_opts = server.credential_options(&["sa_key", "sa_key_id"]);
         let sa_key_json = match mock_auth {
             true => {
                 // Key file is not required if we're mocking auth
@@ -186,11 +199,11 @@ impl ForeignDataWrapper<BigQueryFdwError> for BigQueryFdw {
                 serde_json::to_string_pretty(&dummy_auth_config)
                     .expect("dummy auth config should not fail to serialize")
             }
-            false => match server.options.get("sa_key") {
+            false => match cred_opts.get("sa_key") {
                 Some(sa_key) => sa_key.to_owned(),
                 None => {
-                    let sa_key_id = require_option("sa_key_id", &server.options)?;
-                    match get_vault_secret(sa_key_id) {
+                    let sa_key_id = require_option("sa_key_id", cred_opts)?;
+                    match get_secret(&server.options, sa_key_id) {
                         Some(sa_key) => sa_key,
                         None => return Ok(ret),
                     }
@@ -198,37 +211,32 @@ impl ForeignDataWrapper<BigQueryFdwError> for BigQueryFdw {
             },
         };
 
-        let sa_key = match yup_oauth2::parse_service_account_key(sa_key_json.as_bytes()) {
-            Ok(sa_key) => sa_key,
-            Err(err) => {
-                report_error(
-                    PgSqlErrorCode::ERRCODE_FDW_ERROR,
-                    &format!("parse service account key JSON failed: {}", err),
-                );
-                return Ok(ret);
-            }
-        };
+        let sa_key = yup_oauth2::parse_service_account_key(sa_key_json.as_bytes())
+            .map_err(BigQueryFdwError::InvalidServiceAccountKey)?;
 
-        ret.client = match ret.rt.block_on(
-            ClientBuilder::new()
-                .with_v2_base_url(api_endpoint)
-                .build_from_service_account_key(sa_key, false),
-        ) {
-            Ok(client) => Some(client),
-            Err(err) => {
-                report_error(
-                    PgSqlErrorCode::ERRCODE_FDW_ERROR,
-                    &format!("create client failed: {}", err),
-                );
-                None
-            }
-        };
+        ret.client = Some(
+            ret.rt.block_on(
+                ClientBuilder::new()
+                    .with_v2_base_url(api_endpoint)
+                    .build_from_service_account_key(sa_key, false),
+            )?,
+        );
 
         stats::inc_stats(Self::FDW_NAME, stats::Metric::CreateTimes, 1);
 
         Ok(ret)
     }
 
+    fn capabilities() -> FdwCapabilities {
+        FdwCapabilities {
+            write: true,
+            qual_pushdown: true,
+            sort_pushdown: true,
+            limit_pushdown: true,
+            ..Default::default()
+        }
+    }
+
     fn get_rel_size(
         &mut self,
         _quals: &[Qual],
@@ -240,6 +248,10 @@ impl ForeignDataWrapper<BigQueryFdwError> for BigQueryFdw {
         Ok((0, 0))
     }
 
+    fn bool_expr_tree() -> bool {
+        true
+    }
+
     fn begin_scan(
         &mut self,
         quals: &[Qual],
@@ -256,16 +268,12 @@ impl ForeignDataWrapper<BigQueryFdwError> for BigQueryFdw {
             .map(|t| t.to_owned())
             .unwrap_or_else(|| "US".to_string());
 
-        let mut timeout: i32 = 30_000;
-        if let Some(timeout_str) = options.get("timeout") {
-            match timeout_str.parse::<i32>() {
-                Ok(t) => timeout = t,
-                Err(_) => report_error(
-                    PgSqlErrorCode::ERRCODE_FDW_ERROR,
-                    &format!("invalid timeout value: {}", timeout_str),
-                ),
-            }
-        }
+        let timeout = match options.get("timeout") {
+            Some(timeout_str) => timeout_str
+                .parse::<i32>()
+                .map_err(|_| BigQueryFdwError::InvalidTimeout(timeout_str.to_owned()))?,
+            None => 30_000,
+        };
 
         if let Some(client) = &self.client {
             let sql = self.deparse(quals, columns, sorts, limit);
@@ -274,50 +282,39 @@ impl ForeignDataWrapper<BigQueryFdwError> for BigQueryFdw {
             req.timeout_ms = Some(timeout);
 
             // execute query on BigQuery
-            match self.rt.block_on(client.job().query(&self.project_id, req)) {
-                Ok(rs) => {
-                    let resp = rs.query_response();
-                    if resp.job_complete == Some(false) {
-                        report_error(
-                            PgSqlErrorCode::ERRCODE_FDW_ERROR,
-                            &format!("query timeout {}ms expired", timeout),
-                        );
-                    } else {
-                        stats::inc_stats(
-                            Self::FDW_NAME,
-                            stats::Metric::RowsIn,
-                            resp.total_rows
-                                .as_ref()
-                                .and_then(|v| v.parse::<i64>().ok())
-                                .unwrap_or(0i64),
-                        );
-                        stats::inc_stats(
-                            Self::FDW_NAME,
-                            stats::Metric::RowsOut,
-                            resp.total_rows
-                                .as_ref()
-                                .and_then(|v| v.parse::<i64>().ok())
-                                .unwrap_or(0i64),
-                        );
-                        stats::inc_stats(
-                            Self::FDW_NAME,
-                            stats::Metric::BytesIn,
-                            resp.total_bytes_processed
-                                .as_ref()
-                                .and_then(|v| v.parse::<i64>().ok())
-                                .unwrap_or(0i64),
-                        );
-                        self.scan_result = Some(rs);
-                    }
-                }
-                Err(err) => {
-                    self.scan_result = None;
-                    report_error(
-                        PgSqlErrorCode::ERRCODE_FDW_ERROR,
-                        &format!("query failed: {}", err),
-                    );
-                }
+            self.scan_result = None;
+            let rs = self
+                .rt
+                .block_on(client.job().query(&self.project_id, req))?;
+            let resp = rs.query_response();
+            if resp.job_complete == Some(false) {
+                return Err(BigQueryFdwError::QueryTimeout(timeout));
             }
+            stats::inc_stats(
+                Self::FDW_NAME,
+                stats::Metric::RowsIn,
+                resp.total_rows
+                    .as_ref()
+                    .and_then(|v| v.parse::<i64>().ok())
+                    .unwrap_or(0i64),
+            );
+            stats::inc_stats(
+                Self::FDW_NAME,
+                stats::Metric::RowsOut,
+                resp.total_rows
+                    .as_ref()
+                    .and_then(|v| v.parse::<i64>().ok())
+                    .unwrap_or(0i64),
+            );
+            stats::inc_stats(
+                Self::FDW_NAME,
+                stats::Metric::BytesIn,
+                resp.total_bytes_processed
+                    .as_ref()
+                    .and_then(|v| v.parse::<i64>().ok())
+                    .unwrap_or(0i64),
+            );
+            self.scan_result = Some(rs);
         }
 
         Ok(())
@@ -352,10 +349,7 @@ impl ForeignDataWrapper<BigQueryFdwError> for BigQueryFdw {
                                 }
                                 Err(err) => {
                                     self.scan_result = None;
-                                    report_error(
-                                        PgSqlErrorCode::ERRCODE_FDW_ERROR,
-                                        &format!("fetch query result failed: {}", err),
-                                    );
+                                    return Err(err.into());
                                 }
                             }
                         }
@@ -393,7 +387,7 @@ impl ForeignDataWrapper<BigQueryFdwError> for BigQueryFdw {
                         Cell::I64(v) => row_json[col_name] = json!(v),
                         Cell::F32(v) => row_json[col_name] = json!(v),
                         Cell::F64(v) => row_json[col_name] = json!(v),
-                        Cell::Numeric(v) => row_json[col_name] = json!(v),
+                        Cell::Numeric(v) => row_json[col_name] = json!(v.to_string()),
                         Cell::String(v) => row_json[col_name] = json!(v),
                         Cell::Date(v) => row_json[col_name] = json!(v),
                         Cell::Timestamp(v) => row_json[col_name] = json!(v),
@@ -411,17 +405,12 @@ impl ForeignDataWrapper<BigQueryFdwError> for BigQueryFdw {
             insert_request.add_row(None, row_json)?;
 
             // execute insert job on BigQuery
-            if let Err(err) = self.rt.block_on(client.tabledata().insert_all(
+            self.rt.block_on(client.tabledata().insert_all(
                 &self.project_id,
                 &self.dataset_id,
                 &self.table,
                 insert_request,
-            )) {
-                report_error(
-                    PgSqlErrorCode::ERRCODE_FDW_ERROR,
-                    &format!("insert failed: {}", err),
-                );
-            }
+            ))?;
         }
 
         Ok(())
@@ -453,12 +442,7 @@ impl ForeignDataWrapper<BigQueryFdwError> for BigQueryFdw {
             let query_job = client.job().query(&self.project_id, QueryRequest::new(sql));
 
             // execute update on BigQuery
-            if let Err(err) = self.rt.block_on(query_job) {
-                report_error(
-                    PgSqlErrorCode::ERRCODE_FDW_ERROR,
-                    &format!("update failed: {}", err),
-                );
-            }
+            self.rt.block_on(query_job)?;
         }
         Ok(())
     }
@@ -473,12 +457,7 @@ impl ForeignDataWrapper<BigQueryFdwError> for BigQueryFdw {
             let query_job = client.job().query(&self.project_id, QueryRequest::new(sql));
 
             // execute delete on BigQuery
-            if let Err(err) = self.rt.block_on(query_job) {
-                report_error(
-                    PgSqlErrorCode::ERRCODE_FDW_ERROR,
-                    &format!("update failed: {}", err),
-                );
-            }
+            self.rt.block_on(query_job)?;
         }
         Ok(())
     }
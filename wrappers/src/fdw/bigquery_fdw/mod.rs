@@ -23,6 +23,15 @@ enum BigQueryFdwError {
     #[error("field {0} type not supported")]
     UnsupportedFieldType(String),
 
+    #[error("parse service account key JSON failed: {0}")]
+    InvalidServiceAccountKey(std::io::Error),
+
+    #[error("invalid timeout value: {0}")]
+    InvalidTimeout(String),
+
+    #[error("query timeout {0}ms expired")]
+    QueryTimeout(i32),
+
     #[error("{0}")]
     NumericConversionError(#[from] pgrx::numeric::Error),
 
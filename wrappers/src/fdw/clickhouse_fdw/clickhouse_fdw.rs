@@ -124,22 +124,63 @@ fn field_to_cell(row: &types::Row<types::Complex>, i: usize) -> ClickHouseFdwRes
                 sql_type.to_string().into(),
             )),
         },
+        SqlType::Array(v) => match v {
+            SqlType::Int32 => {
+                let value = row.get::<Vec<i32>, usize>(i)?;
+                Ok(Some(Cell::I32Array(value.into_iter().map(Some).collect())))
+            }
+            SqlType::Int64 => {
+                let value = row.get::<Vec<i64>, usize>(i)?;
+                Ok(Some(Cell::I64Array(value.into_iter().map(Some).collect())))
+            }
+            SqlType::Float64 => {
+                let value = row.get::<Vec<f64>, usize>(i)?;
+                Ok(Some(Cell::F64Array(value.into_iter().map(Some).collect())))
+            }
+            SqlType::String => {
+                let value = row.get::<Vec<String>, usize>(i)?;
+                Ok(Some(Cell::StringArray(
+                    value.into_iter().map(Some).collect(),
+                )))
+            }
+            _ => Err(ClickHouseFdwError::UnsupportedColumnType(
+                sql_type.to_string().into(),
+            )),
+        },
         _ => Err(ClickHouseFdwError::UnsupportedColumnType(
             sql_type.to_string().into(),
         )),
     }
 }
 
+// add timeouts to the connection string as its parameters, unless they are
+// already specified in it
+fn conn_str_with_timeouts(conn_str: &str, timeouts: &Timeouts) -> String {
+    let mut ret = conn_str.to_owned();
+    for (param, timeout) in [
+        ("connection_timeout", timeouts.connect),
+        ("query_timeout", timeouts.request),
+    ] {
+        if !ret.contains(&format!("{}=", param)) {
+            let sep = if ret.contains('?') { '&' } else { '?' };
+            ret.push_str(&format!("{}{}={}s", sep, param, timeout.as_secs()));
+        }
+    }
+    ret
+}
+
 #[wrappers_fdw(
     version = "0.1.5",
     author = "Supabase",
     website = "https://github.com/supabase/wrappers/tree/main/wrappers/src/fdw/clickhouse_fdw",
-    error_type = "ClickHouseFdwError"
+    error_type = "ClickHouseFdwError",
+    import_schema
 )]
 pub(crate) struct ClickHouseFdw {
     rt: Runtime,
     conn_str: String,
-    client: Option<ClientHandle>,
+    pool: ConnectionPool,
+    client: Option<PooledConnection<ClientHandle>>,
     table: String,
     rowid_col: String,
     tgt_cols: Vec<Column>,
@@ -151,9 +192,28 @@ pub(crate) struct ClickHouseFdw {
 impl ClickHouseFdw {
     const FDW_NAME: &'static str = "ClickHouseFdw";
 
+    fn deparse_qual(qual: &Qual) -> String {
+        // deparse each sub-condition in boolean expression, e.g. "a = 1 or b = 2"
+        if qual.is_bool_expr() {
+            return qual.deparse_bool_expr(Self::deparse_qual);
+        }
+
+        qual.deparse_like(SqlDialect::ClickHouse)
+            .unwrap_or_else(|| {
+                qual.deparse_with_fmt(&mut SqlCellFormatter::new(SqlDialect::ClickHouse))
+            })
+    }
+
     fn create_client(&mut self) -> ClickHouseFdwResult<()> {
-        let pool = Pool::new(self.conn_str.as_str());
-        self.client = Some(self.rt.block_on(pool.get_handle())?);
+        // return the current connection first, so it can be reused
+        self.client.take();
+
+        let (rt, conn_str) = (&self.rt, &self.conn_str);
+        let client = self.pool.get(
+            || rt.block_on(Pool::new(conn_str.as_str()).get_handle()),
+            |client| rt.block_on(client.ping()).is_ok(),
+        )?;
+        self.client = Some(client);
         Ok(())
     }
 
@@ -174,6 +234,66 @@ impl ClickHouseFdw {
         Ok(new)
     }
 
+    // deparse a table in join to a sub query with its quals applied
+    fn deparse_join_table(table: &JoinTable) -> ClickHouseFdwResult<String> {
+        let mut sql = format!("select * from {}", require_option("table", &table.options)?);
+        if !table.quals.is_empty() {
+            let cond = table
+                .quals
+                .iter()
+                .map(Self::deparse_qual)
+                .collect::<Vec<String>>()
+                .join(" and ");
+            sql.push_str(&format!(" where {}", cond));
+        }
+        Ok(sql)
+    }
+
+    // run query and fetch whole query result to local
+    fn run_query(&mut self, sql: &str) -> ClickHouseFdwResult<()> {
+        if let Some(ref mut client) = self.client {
+            let block = audit_request(
+                self.pool.server_oid(),
+                sql,
+                || self.rt.block_on(client.query(sql).fetch_all()),
+                |block| Some(block.row_count() as i64),
+            )?;
+            stats::inc_stats(
+                Self::FDW_NAME,
+                stats::Metric::RowsIn,
+                block.row_count() as i64,
+            );
+            stats::inc_stats(
+                Self::FDW_NAME,
+                stats::Metric::RowsOut,
+                block.row_count() as i64,
+            );
+            self.scan_blk = Some(block);
+        }
+        Ok(())
+    }
+
+    // map ClickHouse column type to Postgres type, see field_to_cell for the
+    // supported types
+    fn import_column_type(ch_type: &str) -> Option<&'static str> {
+        let ch_type = ch_type
+            .strip_prefix("Nullable(")
+            .and_then(|t| t.strip_suffix(')'))
+            .unwrap_or(ch_type);
+        match ch_type {
+            "UInt8" => Some("boolean"),
+            "Int16" => Some("smallint"),
+            "UInt16" | "Int32" => Some("integer"),
+            "UInt32" | "Int64" | "UInt64" => Some("bigint"),
+            "Float32" => Some("real"),
+            "Float64" => Some("double precision"),
+            "String" => Some("text"),
+            "Date" => Some("date"),
+            t if t.starts_with("DateTime") => Some("timestamp"),
+            _ => None,
+        }
+    }
+
     fn deparse(
         &mut self,
         quals: &[Qual],
@@ -226,7 +346,7 @@ impl ClickHouseFdw {
             let cond = quals
                 .iter()
                 .filter(|q| !self.params.iter().any(|p| p.field == q.field))
-                .map(|q| q.deparse())
+                .map(Self::deparse_qual)
                 .collect::<Vec<String>>()
                 .join(" and ");
 
@@ -245,13 +365,13 @@ impl ClickHouseFdw {
             sql.push_str(&format!(" order by {}", order_by));
         }
 
-        // push down limits
-        // Note: Postgres will take limit and offset locally after reading rows
-        // from remote, so we calculate the real limit and only use it without
-        // pushing down offset.
+        // push down limits, offset is only set if the limit is accepted by
+        // plan_limit, otherwise it is applied locally by Postgres
         if let Some(limit) = limit {
-            let real_limit = limit.offset + limit.count;
-            sql.push_str(&format!(" limit {}", real_limit));
+            sql.push_str(&format!(" limit {}", limit.count));
+            if limit.offset > 0 {
+                sql.push_str(&format!(" offset {}", limit.offset));
+            }
         }
 
         Ok(sql)
@@ -261,19 +381,23 @@ impl ClickHouseFdw {
 impl ForeignDataWrapper<ClickHouseFdwError> for ClickHouseFdw {
     fn new(server: ForeignServer) -> ClickHouseFdwResult<Self> {
         let rt = create_async_runtime()?;
-        let conn_str = match server.options.get("conn_string") {
+        let cred_opts = server.credential_options(&["conn_string", "conn_string_id"]);
+        let conn_str = match cred_opts.get("conn_string") {
             Some(conn_str) => conn_str.to_owned(),
             None => {
-                let conn_str_id = require_option("conn_string_id", &server.options)?;
-                get_vault_secret(conn_str_id).unwrap_or_default()
+                let conn_str_id = require_option("conn_string_id", cred_opts)?;
+                get_secret(&server.options, conn_str_id).unwrap_or_default()
             }
         };
+        let timeouts = Timeouts::from_options(&server.options)?;
+        let conn_str = conn_str_with_timeouts(&conn_str, &timeouts);
 
         stats::inc_stats(Self::FDW_NAME, stats::Metric::CreateTimes, 1);
 
         Ok(Self {
             rt,
             conn_str,
+            pool: ConnectionPool::from_server(&server)?,
             client: None,
             table: String::default(),
             rowid_col: String::default(),
@@ -284,6 +408,110 @@ impl ForeignDataWrapper<ClickHouseFdwError> for ClickHouseFdw {
         })
     }
 
+    fn capabilities() -> FdwCapabilities {
+        FdwCapabilities {
+            write: true,
+            qual_pushdown: true,
+            sort_pushdown: true,
+            limit_pushdown: true,
+            aggregate_pushdown: true,
+            join_pushdown: true,
+            ..Default::default()
+        }
+    }
+
+    fn bool_expr_tree() -> bool {
+        true
+    }
+
+    fn plan_limit(
+        &mut self,
+        _quals: &[Qual],
+        columns: &[Column],
+        sorts: &[Sort],
+        _limit: &Limit,
+        _options: &HashMap<String, String>,
+    ) -> ClickHouseFdwResult<bool> {
+        // strings are sorted by bytes in ClickHouse, which may not match the
+        // collation in Postgres
+        let text_types = [
+            pgrx::pg_sys::TEXTOID,
+            pgrx::pg_sys::VARCHAROID,
+            pgrx::pg_sys::BPCHAROID,
+        ];
+        Ok(sorts.iter().all(|sort| {
+            columns
+                .iter()
+                .any(|col| col.num == sort.field_no && !text_types.contains(&col.type_oid))
+        }))
+    }
+
+    fn plan_aggregate(
+        &mut self,
+        _plan: &AggregatePlan,
+        _quals: &[Qual],
+        options: &HashMap<String, String>,
+    ) -> ClickHouseFdwResult<bool> {
+        // parameterized query needs quals to fill in its parameters, so it
+        // cannot be aggregated remotely
+        let table = require_option("table", options)?;
+        Ok(!table.contains("${"))
+    }
+
+    fn scan_aggregate(
+        &mut self,
+        plan: &AggregatePlan,
+        quals: &[Qual],
+        options: &HashMap<String, String>,
+    ) -> ClickHouseFdwResult<Vec<Row>> {
+        self.create_client()?;
+
+        self.table = require_option("table", options)?.to_string();
+
+        let mut sql = format!("select {} from {}", plan.deparse_targets(), &self.table);
+        if !quals.is_empty() {
+            let cond = quals
+                .iter()
+                .map(Self::deparse_qual)
+                .collect::<Vec<String>>()
+                .join(" and ");
+            sql.push_str(&format!(" where {}", cond));
+        }
+        if let Some(group_by) = plan.deparse_group_by() {
+            sql.push_str(&format!(" {}", group_by));
+        }
+
+        // aggregates on empty set return null as Postgres does
+        sql.push_str(" settings aggregate_functions_null_for_empty = 1");
+
+        let mut rows = Vec::new();
+        if let Some(ref mut client) = self.client {
+            let block = audit_request(
+                self.pool.server_oid(),
+                &sql,
+                || self.rt.block_on(client.query(&sql).fetch_all()),
+                |block| Some(block.row_count() as i64),
+            )?;
+            for src_row in block.rows() {
+                let mut row = Row::new();
+                for i in 0..block.columns().len() {
+                    let cell = field_to_cell(&src_row, i)?;
+                    let col_name = src_row.name(i)?;
+                    row.push(col_name, cell);
+                }
+                rows.push(row);
+            }
+            stats::inc_stats(
+                Self::FDW_NAME,
+                stats::Metric::RowsIn,
+                block.row_count() as i64,
+            );
+            stats::inc_stats(Self::FDW_NAME, stats::Metric::RowsOut, rows.len() as i64);
+        }
+
+        Ok(rows)
+    }
+
     fn begin_scan(
         &mut self,
         quals: &[Qual],
@@ -300,24 +528,72 @@ impl ForeignDataWrapper<ClickHouseFdwError> for ClickHouseFdw {
 
         let sql = self.deparse(quals, columns, sorts, limit)?;
 
-        if let Some(ref mut client) = self.client {
-            // for simplicity purpose, we fetch whole query result to local,
-            // may need optimization in the future.
-            let block = self.rt.block_on(client.query(&sql).fetch_all())?;
-            stats::inc_stats(
-                Self::FDW_NAME,
-                stats::Metric::RowsIn,
-                block.row_count() as i64,
-            );
-            stats::inc_stats(
-                Self::FDW_NAME,
-                stats::Metric::RowsOut,
-                block.row_count() as i64,
-            );
-            self.scan_blk = Some(block);
+        // for simplicity purpose, we fetch whole query result to local,
+        // may need optimization in the future.
+        self.run_query(&sql)
+    }
+
+    fn plan_join(&mut self, join: &Join) -> ClickHouseFdwResult<bool> {
+        // parameterized query needs quals to fill in its parameters, so it
+        // cannot be joined remotely
+        for table in [&join.outer, &join.inner] {
+            if require_option("table", &table.options)?.contains("${") {
+                return Ok(false);
+            }
         }
+        Ok(true)
+    }
 
-        Ok(())
+    fn begin_join_scan(&mut self, join: &Join) -> ClickHouseFdwResult<()> {
+        self.create_client()?;
+
+        self.row_idx = 0;
+        self.params.clear();
+
+        // columns are aliased as they may have the same name in both tables
+        let mut tgts = Vec::new();
+        self.tgt_cols.clear();
+        for (alias, table) in [
+            ("_wrappers_outer", &join.outer),
+            ("_wrappers_inner", &join.inner),
+        ] {
+            for col in table.columns.iter() {
+                let name = format!("_wrappers_c{}", self.tgt_cols.len());
+                tgts.push(format!("{}.{} as {}", alias, col.name, name));
+                self.tgt_cols.push(Column {
+                    name,
+                    ..col.clone()
+                });
+            }
+        }
+        if tgts.is_empty() {
+            tgts.push("1 as _wrappers_c".to_string());
+        }
+
+        let conds = join
+            .conds
+            .iter()
+            .map(|cond| {
+                format!(
+                    "_wrappers_outer.{} = _wrappers_inner.{}",
+                    cond.outer_field, cond.inner_field
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(" and ");
+
+        // non-matched rows in outer join are filled with null as Postgres does
+        let sql = format!(
+            "select {} from ({}) as _wrappers_outer {} ({}) as _wrappers_inner on {} \
+             settings join_use_nulls = 1",
+            tgts.join(", "),
+            Self::deparse_join_table(&join.outer)?,
+            join.kind.deparse(),
+            Self::deparse_join_table(&join.inner)?,
+            conds
+        );
+
+        self.run_query(&sql)
     }
 
     fn iter_scan(&mut self, row: &mut Row) -> ClickHouseFdwResult<Option<()>> {
@@ -364,96 +640,152 @@ impl ForeignDataWrapper<ClickHouseFdwError> for ClickHouseFdw {
     }
 
     fn insert(&mut self, src: &Row) -> ClickHouseFdwResult<()> {
+        self.insert_rows(std::slice::from_ref(src))
+    }
+
+    fn insert_rows(&mut self, rows: &[Row]) -> ClickHouseFdwResult<()> {
         if let Some(ref mut client) = self.client {
             // use a dummy query to probe column types
             let sql = format!("select * from {} where false", self.table);
-            let probe = self.rt.block_on(client.query(&sql).fetch_all())?;
-
-            // add row to block
-            let mut row = Vec::new();
-            for (col_name, cell) in src.iter() {
-                let col_name = col_name.to_owned();
-                let tgt_col = probe.get_column(col_name.as_ref())?;
-                let is_nullable = matches!(tgt_col.sql_type(), SqlType::Nullable(_));
-
-                let value = cell
-                    .as_ref()
-                    .map(|c| match c {
-                        Cell::Bool(v) => {
-                            let val = if is_nullable {
-                                types::Value::from(Some(*v))
-                            } else {
-                                types::Value::from(*v)
-                            };
-                            Ok(val)
-                        }
-                        Cell::F64(v) => {
-                            let val = if is_nullable {
-                                types::Value::from(Some(*v))
-                            } else {
-                                types::Value::from(*v)
-                            };
-                            Ok(val)
-                        }
-                        Cell::I64(v) => {
-                            let val = if is_nullable {
-                                types::Value::from(Some(*v))
-                            } else {
-                                types::Value::from(*v)
-                            };
-                            Ok(val)
-                        }
-                        Cell::String(v) => {
-                            let s = v.as_str();
-                            let val = if is_nullable {
-                                types::Value::from(Some(s))
-                            } else {
-                                types::Value::from(s)
-                            };
-                            Ok(val)
-                        }
-                        Cell::Date(_) => {
-                            let s = c.to_string().replace('\'', "");
-                            let tm = NaiveDate::parse_from_str(&s, "%Y-%m-%d")?;
-                            let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
-                            let duration = tm - epoch;
-                            let dt = duration.num_days() as u16;
-                            let val = if is_nullable {
-                                types::Value::from(Some(dt))
-                            } else {
-                                types::Value::Date(dt)
-                            };
-                            Ok(val)
-                        }
-                        Cell::Timestamp(_) => {
-                            let s = c.to_string().replace('\'', "");
-                            let naive_tm = NaiveDateTime::parse_from_str(&s, "%Y-%m-%d %H:%M:%S")?;
-                            let tm: DateTime<Utc> =
-                                DateTime::from_naive_utc_and_offset(naive_tm, Utc);
-                            let val = if is_nullable {
-                                types::Value::Nullable(either::Either::Right(Box::new(tm.into())))
-                            } else {
-                                types::Value::from(tm)
-                            };
-                            Ok(val)
-                        }
-                        _ => Err(ClickHouseFdwError::UnsupportedColumnType(c.to_string())),
-                    })
-                    .transpose()?;
+            let probe = audit_request(
+                self.pool.server_oid(),
+                &sql,
+                || self.rt.block_on(client.query(&sql).fetch_all()),
+                |block| Some(block.row_count() as i64),
+            )?;
+
+            // add all the rows to one block, so they are inserted in one request
+            let mut block = Block::new();
+            for src in rows {
+                let mut row = Vec::new();
+                for (col_name, cell) in src.iter() {
+                    let col_name = col_name.to_owned();
+                    let tgt_col = probe.get_column(col_name.as_ref())?;
+                    let is_nullable = matches!(tgt_col.sql_type(), SqlType::Nullable(_));
+
+                    let value = cell
+                        .as_ref()
+                        .map(|c| match c {
+                            Cell::Bool(v) => {
+                                let val = if is_nullable {
+                                    types::Value::from(Some(*v))
+                                } else {
+                                    types::Value::from(*v)
+                                };
+                                Ok(val)
+                            }
+                            Cell::F64(v) => {
+                                let val = if is_nullable {
+                                    types::Value::from(Some(*v))
+                                } else {
+                                    types::Value::from(*v)
+                                };
+                                Ok(val)
+                            }
+                            Cell::I64(v) => {
+                                let val = if is_nullable {
+                                    types::Value::from(Some(*v))
+                                } else {
+                                    types::Value::from(*v)
+                                };
+                                Ok(val)
+                            }
+                            Cell::String(v) => {
+                                let s = v.as_str();
+                                let val = if is_nullable {
+                                    types::Value::from(Some(s))
+                                } else {
+                                    types::Value::from(s)
+                                };
+                                Ok(val)
+                            }
+                            Cell::Date(_) => {
+                                let s = c.to_string().replace('\'', "");
+                                let tm = NaiveDate::parse_from_str(&s, "%Y-%m-%d")?;
+                                let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+                                let duration = tm - epoch;
+                                let dt = duration.num_days() as u16;
+                                let val = if is_nullable {
+                                    types::Value::from(Some(dt))
+                                } else {
+                                    types::Value::Date(dt)
+                                };
+                                Ok(val)
+                            }
+                            Cell::Timestamp(_) => {
+                                let s = c.to_string().replace('\'', "");
+                                let naive_tm =
+                                    NaiveDateTime::parse_from_str(&s, "%Y-%m-%d %H:%M:%S")?;
+                                let tm: DateTime<Utc> =
+                                    DateTime::from_naive_utc_and_offset(naive_tm, Utc);
+                                let val = if is_nullable {
+                                    types::Value::Nullable(either::Either::Right(Box::new(
+                                        tm.into(),
+                                    )))
+                                } else {
+                                    types::Value::from(tm)
+                                };
+                                Ok(val)
+                            }
+                            _ => Err(ClickHouseFdwError::UnsupportedColumnType(c.to_string())),
+                        })
+                        .transpose()?;
 
-                if let Some(v) = value {
-                    row.push((col_name, v));
+                    if let Some(v) = value {
+                        row.push((col_name, v));
+                    }
                 }
+                block.push(row)?;
             }
-            let mut block = Block::new();
-            block.push(row)?;
 
             // execute query on ClickHouse
-            self.rt.block_on(client.insert(&self.table, block))?;
+            audit_request(
+                self.pool.server_oid(),
+                &format!("insert into {}", self.table),
+                || self.rt.block_on(client.insert(&self.table, block)),
+                |_| Some(rows.len() as i64),
+            )?;
         }
         Ok(())
     }
 
+    fn upsert(&mut self, row: &Row, on_conflict: OnConflict) -> ClickHouseFdwResult<bool> {
+        // ClickHouse has no unique constraint, so the conflict is detected
+        // by the rowid column
+        let rowid = row
+            .iter()
+            .find(|(col, _)| *col == &self.rowid_col)
+            .and_then(|(_, cell)| cell.clone());
+        let mut exists = false;
+        if let (Some(rowid), Some(client)) = (&rowid, &mut self.client) {
+            let sql = format!(
+                "select count() as cnt from {} where {} = {}",
+                self.table, self.rowid_col, rowid
+            );
+            let block = audit_request(
+                self.pool.server_oid(),
+                &sql,
+                || self.rt.block_on(client.query(&sql).fetch_all()),
+                |block| Some(block.row_count() as i64),
+            )?;
+            if let Some(row) = block.rows().next() {
+                exists = row.get::<u64, _>("cnt")? > 0;
+            }
+        }
+
+        match (rowid, on_conflict) {
+            (Some(rowid), OnConflict::DoUpdate) if exists => {
+                self.update(&rowid, row)?;
+                Ok(true)
+            }
+            _ if exists => Ok(false),
+            _ => {
+                self.insert(row)?;
+                Ok(true)
+            }
+        }
+    }
+
     fn update(&mut self, rowid: &Cell, new_row: &Row) -> ClickHouseFdwResult<()> {
         if let Some(ref mut client) = self.client {
             let mut sets = Vec::new();
@@ -476,7 +808,12 @@ impl ForeignDataWrapper<ClickHouseFdwError> for ClickHouseFdw {
             );
 
             // execute query on ClickHouse
-            self.rt.block_on(client.execute(&sql))?;
+            audit_request(
+                self.pool.server_oid(),
+                &sql,
+                || self.rt.block_on(client.execute(&sql)),
+                |_| None,
+            )?;
         }
         Ok(())
     }
@@ -489,8 +826,182 @@ impl ForeignDataWrapper<ClickHouseFdwError> for ClickHouseFdw {
             );
 
             // execute query on ClickHouse
-            self.rt.block_on(client.execute(&sql))?;
+            audit_request(
+                self.pool.server_oid(),
+                &sql,
+                || self.rt.block_on(client.execute(&sql)),
+                |_| None,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn plan_direct_modify(
+        &mut self,
+        _quals: &[Qual],
+        _new_row: Option<&Row>,
+        options: &HashMap<String, String>,
+    ) -> ClickHouseFdwResult<bool> {
+        // query in the table option cannot be modified
+        let table = require_option("table", options)?;
+        Ok(!table.starts_with('('))
+    }
+
+    fn exec_direct_modify(
+        &mut self,
+        quals: &[Qual],
+        new_row: Option<&Row>,
+        options: &HashMap<String, String>,
+    ) -> ClickHouseFdwResult<u64> {
+        self.create_client()?;
+
+        let table = require_option("table", options)?;
+        let cond = if quals.is_empty() {
+            "1".to_string()
+        } else {
+            quals
+                .iter()
+                .map(Self::deparse_qual)
+                .collect::<Vec<String>>()
+                .join(" and ")
+        };
+
+        let mut affected = 0;
+        if let Some(ref mut client) = self.client {
+            // mutation doesn't return the number of affected rows, so count
+            // them before executing it
+            let sql = format!("select count() as cnt from {} where {}", table, cond);
+            let block = audit_request(
+                self.pool.server_oid(),
+                &sql,
+                || self.rt.block_on(client.query(&sql).fetch_all()),
+                |block| Some(block.row_count() as i64),
+            )?;
+            if let Some(row) = block.rows().next() {
+                affected = row.get::<u64, _>("cnt")?;
+            }
+
+            let sql = match new_row {
+                Some(new_row) => {
+                    let sets = new_row
+                        .iter()
+                        .map(|(col, cell)| match cell {
+                            Some(cell) => format!("{} = {}", col, cell),
+                            None => format!("{} = null", col),
+                        })
+                        .collect::<Vec<String>>()
+                        .join(", ");
+                    format!("alter table {} update {} where {}", table, sets, cond)
+                }
+                None => format!("alter table {} delete where {}", table, cond),
+            };
+            audit_request(
+                self.pool.server_oid(),
+                &sql,
+                || self.rt.block_on(client.execute(&sql)),
+                |_| None,
+            )?;
+        }
+        Ok(affected)
+    }
+
+    fn truncate(&mut self, options: &HashMap<String, String>) -> ClickHouseFdwResult<()> {
+        let table = require_option("table", options)?;
+        if table.starts_with('(') {
+            return Err(ClickHouseFdwError::TruncateQuery(table.to_string()));
+        }
+
+        self.create_client()?;
+        if let Some(ref mut client) = self.client {
+            let sql = format!("truncate table {}", table);
+            audit_request(
+                self.pool.server_oid(),
+                &sql,
+                || self.rt.block_on(client.execute(&sql)),
+                |_| None,
+            )?;
         }
         Ok(())
     }
+
+    fn health_check(&mut self) -> ClickHouseFdwResult<Option<String>> {
+        self.create_client()?;
+        if let Some(ref mut client) = self.client {
+            let block = self
+                .rt
+                .block_on(client.query("select version()").fetch_all())?;
+            if let Some(row) = block.rows().next() {
+                return Ok(Some(row.get::<String, usize>(0)?));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl ListTables<ClickHouseFdwError> for ClickHouseFdw {
+    // list the tables in a ClickHouse database, the columns with unsupported
+    // types are skipped
+    fn list_tables(
+        &mut self,
+        stmt: &ImportForeignSchemaStmt,
+    ) -> ClickHouseFdwResult<Vec<RemoteTable>> {
+        self.create_client()?;
+
+        // table name, columns and primary key columns
+        let mut tables: Vec<(String, Vec<(String, String)>, Vec<String>)> = Vec::new();
+        if let Some(ref mut client) = self.client {
+            let sql = format!(
+                "select table, name, type, is_in_primary_key from system.columns \
+                 where database = '{}' order by table, position",
+                stmt.remote_schema.replace('\'', "''")
+            );
+            let block = audit_request(
+                self.pool.server_oid(),
+                &sql,
+                || self.rt.block_on(client.query(&sql).fetch_all()),
+                |block| Some(block.row_count() as i64),
+            )?;
+            for row in block.rows() {
+                let table: String = row.get("table")?;
+                if !stmt.is_table_included(&table) {
+                    continue;
+                }
+                let name: String = row.get("name")?;
+                let ch_type: String = row.get("type")?;
+                let is_in_primary_key: u8 = row.get("is_in_primary_key")?;
+                let Some(pg_type) = Self::import_column_type(&ch_type) else {
+                    continue;
+                };
+
+                if tables.last().map(|(t, _, _)| t != &table).unwrap_or(true) {
+                    tables.push((table, Vec::new(), Vec::new()));
+                }
+                if let Some((_, cols, keys)) = tables.last_mut() {
+                    if is_in_primary_key != 0 {
+                        keys.push(name.clone());
+                    }
+                    cols.push((name, pg_type.to_string()));
+                }
+            }
+        }
+
+        Ok(tables
+            .into_iter()
+            .map(|(table, columns, keys)| {
+                let mut options = vec![(
+                    "table".to_string(),
+                    format!("{}.{}", stmt.remote_schema, table),
+                )];
+                // a single column primary key can be used as rowid column
+                if keys.len() == 1 {
+                    options.push(("rowid_column".to_string(), keys[0].clone()));
+                }
+                RemoteTable {
+                    name: table,
+                    columns,
+                    options,
+                }
+            })
+            .collect())
+    }
 }
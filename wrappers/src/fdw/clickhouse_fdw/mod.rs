@@ -20,6 +20,9 @@ enum ClickHouseFdwError {
     #[error("column data type '{0}' is not supported")]
     UnsupportedColumnType(String),
 
+    #[error("cannot truncate query '{0}', only table can be truncated")]
+    TruncateQuery(String),
+
     #[error("datetime conversion error: {0}")]
     DatetimeConversionError(#[from] DateTimeConversionError),
 
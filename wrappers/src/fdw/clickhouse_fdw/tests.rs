@@ -244,6 +244,148 @@ mod tests {
                 "test3"
             );
 
+            // aggregates and grouping are pushed down to ClickHouse
+            let explain = c
+                .select(
+                    "EXPLAIN SELECT name, count(*) FROM test_table GROUP BY name",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("QUERY PLAN").unwrap())
+                .collect::<Vec<_>>()
+                .join("\n");
+            assert!(explain.contains("group_by"));
+            let row = c
+                .select("SELECT count(*), count(name) FROM test_table", None, None)
+                .unwrap()
+                .first();
+            assert_eq!(row.get::<i64>(1).unwrap(), Some(5));
+            assert_eq!(row.get::<i64>(2).unwrap(), Some(4));
+            let results = c
+                .select(
+                    "SELECT name, count(*) AS cnt FROM test_table GROUP BY name ORDER BY name",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .map(|r| {
+                    (
+                        r.get_by_name::<&str, _>("name").unwrap(),
+                        r.get_by_name::<i64, _>("cnt").unwrap(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(
+                results,
+                vec![
+                    (Some("test"), Some(1)),
+                    (Some("test2"), Some(1)),
+                    (Some("test3"), Some(1)),
+                    (Some("test4"), Some(1)),
+                    (None, Some(1)),
+                ]
+            );
+
+            // multi-row insert is sent to ClickHouse in batches
+            c.update(
+                "ALTER FOREIGN TABLE test_table OPTIONS (ADD batch_size '2')",
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                "INSERT INTO test_table (id, name) VALUES (100, 'x1'), (101, 'x2'), (102, 'x3')",
+                None,
+                None,
+            )
+            .unwrap();
+            assert_eq!(
+                c.select(
+                    "SELECT count(*) FROM test_table WHERE name LIKE 'x%'",
+                    None,
+                    None
+                )
+                .unwrap()
+                .first()
+                .get_one::<i64>()
+                .unwrap(),
+                Some(3)
+            );
+
+            // update and delete are executed remotely as a whole statement
+            let explain = c
+                .select("EXPLAIN DELETE FROM test_table WHERE id = 42", None, None)
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("QUERY PLAN").unwrap())
+                .collect::<Vec<_>>()
+                .join("\n");
+            assert!(explain.contains("direct_modify = delete"));
+            c.update(
+                "UPDATE test_table SET name = 'z' WHERE id >= 101",
+                None,
+                None,
+            )
+            .unwrap();
+            c.update("DELETE FROM test_table WHERE id = 42", None, None)
+                .unwrap();
+            assert_eq!(
+                c.select(
+                    "SELECT count(*) FROM test_table WHERE name = 'z'",
+                    None,
+                    None
+                )
+                .unwrap()
+                .first()
+                .get_one::<i64>()
+                .unwrap(),
+                Some(2)
+            );
+            assert_eq!(
+                c.select("SELECT count(*) FROM test_table WHERE id = 42", None, None)
+                    .unwrap()
+                    .first()
+                    .get_one::<i64>()
+                    .unwrap(),
+                Some(0)
+            );
+
+            // conflicting rows are detected by the rowid column
+            c.update(
+                "INSERT INTO test_table (id, name) VALUES (100, 'dup'), (103, 'x4') ON CONFLICT DO NOTHING",
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                "ALTER FOREIGN TABLE test_table OPTIONS (ADD upsert 'true')",
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                "INSERT INTO test_table (id, name) VALUES (100, 'up')",
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                "ALTER FOREIGN TABLE test_table OPTIONS (DROP upsert)",
+                None,
+                None,
+            )
+            .unwrap();
+            let results = c
+                .select(
+                    "SELECT name FROM test_table WHERE id IN (100, 103) ORDER BY id",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("name").unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec!["up", "x4"]);
+
             let remote_value: Option<String> = rt
                 .block_on(async {
                     handle
@@ -257,6 +399,179 @@ mod tests {
                 })
                 .expect("value");
             assert_eq!(remote_value, Some("test".to_string()));
+
+            // rows routed to a foreign table partition are inserted without
+            // the planning phase, the same way as COPY FROM
+            c.update(
+                "CREATE TABLE test_parent (id bigint, name text) PARTITION BY RANGE (id)",
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                "ALTER TABLE test_parent ATTACH PARTITION test_table FOR VALUES FROM (200) TO (300)",
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                "INSERT INTO test_parent (id, name) VALUES (200, 'y1'), (201, 'y2')",
+                None,
+                None,
+            )
+            .unwrap();
+            assert_eq!(
+                c.select(
+                    "SELECT count(*) FROM test_table WHERE name LIKE 'y%'",
+                    None,
+                    None
+                )
+                .unwrap()
+                .first()
+                .get_one::<i64>()
+                .unwrap(),
+                Some(2)
+            );
+
+            // pending writes in the transaction are overlaid onto the scan, and
+            // the rows also fetched from remote are not duplicated
+            c.update(
+                "ALTER FOREIGN TABLE test_table OPTIONS (ADD read_your_writes 'true')",
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                "INSERT INTO test_table (id, name) VALUES (250, 'w1')",
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                "UPDATE test_table SET name = 'w2' WHERE id = 250",
+                None,
+                None,
+            )
+            .unwrap();
+            let results = c
+                .select("SELECT name FROM test_table WHERE id = 250", None, None)
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("name").unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec!["w2"]);
+            c.update(
+                "ALTER FOREIGN TABLE test_table OPTIONS (DROP read_your_writes)",
+                None,
+                None,
+            )
+            .unwrap();
+
+            // truncate is sent to ClickHouse
+            c.update("TRUNCATE test_table", None, None).unwrap();
+            assert_eq!(
+                c.select("SELECT count(*) FROM test_table", None, None)
+                    .unwrap()
+                    .first()
+                    .get_one::<i64>()
+                    .unwrap(),
+                Some(0)
+            );
+
+            // foreign tables are created from ClickHouse table metadata
+            c.update("CREATE SCHEMA ch_import", None, None).unwrap();
+            c.update(
+                r#"IMPORT FOREIGN SCHEMA "default" LIMIT TO (test_table)
+                   FROM SERVER my_clickhouse_server INTO ch_import"#,
+                None,
+                None,
+            )
+            .unwrap();
+            let results = c
+                .select(
+                    r#"SELECT column_name::text, data_type::text FROM information_schema.columns
+                       WHERE table_schema = 'ch_import' AND table_name = 'test_table'
+                       ORDER BY ordinal_position"#,
+                    None,
+                    None,
+                )
+                .unwrap()
+                .map(|r| {
+                    (
+                        r.get_by_name::<&str, _>("column_name").unwrap().unwrap(),
+                        r.get_by_name::<&str, _>("data_type").unwrap().unwrap(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec![("id", "bigint"), ("name", "text")]);
+            assert_eq!(
+                c.select("SELECT count(*) FROM ch_import.test_table", None, None)
+                    .unwrap()
+                    .first()
+                    .get_one::<i64>()
+                    .unwrap(),
+                Some(0)
+            );
+
+            // array columns
+            rt.block_on(async {
+                handle.execute("DROP TABLE IF EXISTS test_arrays").await?;
+                handle
+                    .execute(
+                        "CREATE TABLE test_arrays (id Int64, tags Array(String), scores Array(Int64)) engine = Memory",
+                    )
+                    .await?;
+                handle
+                    .execute("INSERT INTO test_arrays VALUES (1, ['a', 'b'], [10, 20])")
+                    .await
+            })
+            .expect("test_arrays in ClickHouse");
+            c.update(
+                r#"
+                  CREATE FOREIGN TABLE test_arrays (
+                    id bigint,
+                    tags text[],
+                    scores bigint[]
+                  )
+                  SERVER my_clickhouse_server
+                  OPTIONS (
+                    table 'test_arrays'
+                  )
+             "#,
+                None,
+                None,
+            )
+            .unwrap();
+            let results = c
+                .select(
+                    "SELECT array_to_string(tags, ',') AS tags, scores[2] AS score FROM test_arrays",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .map(|r| {
+                    (
+                        r.get_by_name::<&str, _>("tags").unwrap().unwrap(),
+                        r.get_by_name::<i64, _>("score").unwrap().unwrap(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec![("a,b", 20)]);
+            let results = c
+                .select(
+                    "SELECT id FROM test_arrays WHERE tags = array['a', 'b']",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<i64, _>("id").unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec![1]);
+        });
+
+        // a query in the table option cannot be truncated
+        let result = std::panic::catch_unwind(|| {
+            Spi::connect(|mut c| c.update("TRUNCATE test_cust_sql", None, None).is_err())
         });
+        assert!(result.is_err());
     }
 }
@@ -2,7 +2,7 @@
 use aws_sdk_cognitoidentityprovider::primitives::DateTime;
 use aws_sdk_cognitoidentityprovider::types::{AttributeType, UserType};
 use serde_json::{json, Value};
-use supabase_wrappers::prelude::{Cell, Column, Row};
+use supabase_wrappers::prelude::{timestamptz_from_epoch_millis, Cell, Column, Row};
 
 use super::super::CognitoFdwError;
 
@@ -24,10 +24,9 @@ fn serialize_attributes(attributes: &Vec<AttributeType>) -> Value {
 
 fn convert_to_timestamp(dt: DateTime) -> Cell {
     let millis = dt.to_millis().expect("timestamp should be valid");
-    // convert Unix epoch to Postgres epoch
-    let ts = pgrx::prelude::Timestamp::try_from(millis * 1000 - 946_684_800_000_000)
+    let ts = timestamptz_from_epoch_millis(millis)
         .expect("timestamp should be converted Postgres epoch");
-    Cell::Timestamp(ts)
+    Cell::Timestamp(ts.to_utc())
 }
 
 impl IntoRow for UserType {
@@ -1,7 +1,9 @@
-use std::env;
 use std::sync::Arc;
 
-use aws_sdk_cognitoidentityprovider::{config::BehaviorVersion, Client};
+use aws_sdk_cognitoidentityprovider::{
+    config::{BehaviorVersion, Credentials, Region},
+    Client,
+};
 
 use crate::stats;
 use pgrx::pg_sys;
@@ -33,26 +35,46 @@ impl ForeignDataWrapper<CognitoFdwError> for CognitoFdw {
         let user_pool_id = require_option("user_pool_id", &server.options)?.to_string();
         let aws_region = require_option("region", &server.options)?.to_string();
 
-        let aws_access_key_id = require_option("aws_access_key_id", &server.options)?.to_string();
+        let cred_opts = server.credential_options(&[
+            "aws_access_key_id",
+            "aws_secret_access_key",
+            "api_key_id",
+        ]);
+        let aws_access_key_id = require_option("aws_access_key_id", cred_opts)?.to_string();
         let aws_secret_access_key =
-            if let Some(aws_secret_access_key) = server.options.get("aws_secret_access_key") {
+            if let Some(aws_secret_access_key) = cred_opts.get("aws_secret_access_key") {
                 aws_secret_access_key.clone()
             } else {
-                let aws_secret_access_key = server
-                    .options
+                let aws_secret_access_key = cred_opts
                     .get("api_key_id")
                     .expect("`api_key_id` must be set if `aws_secret_access_key` is not");
-                get_vault_secret(aws_secret_access_key).ok_or(CognitoFdwError::SecretNotFound(
-                    aws_secret_access_key.clone(),
-                ))?
+                get_secret(&server.options, aws_secret_access_key).ok_or(
+                    CognitoFdwError::SecretNotFound(aws_secret_access_key.clone()),
+                )?
             };
 
+        // the keys can be used to assume a role by option `aws_role_arn`
+        let provider = AwsCredentialsProvider::from_options(&server.options)?
+            .with_static_credentials(AwsCredentials::new(
+                &aws_access_key_id,
+                &aws_secret_access_key,
+            ))
+            .with_region(&aws_region);
+        let creds = provider.credentials()?;
+
         let rt = create_async_runtime()?;
         let client = rt.block_on(async {
-            env::set_var("AWS_ACCESS_KEY_ID", aws_access_key_id);
-            env::set_var("AWS_SECRET_ACCESS_KEY", aws_secret_access_key);
-            env::set_var("AWS_REGION", aws_region);
-            let config = aws_config::load_defaults(BehaviorVersion::latest()).await;
+            let config = aws_config::defaults(BehaviorVersion::latest())
+                .region(Region::new(aws_region))
+                .credentials_provider(Credentials::new(
+                    creds.access_key_id,
+                    creds.secret_access_key,
+                    creds.session_token,
+                    creds.expires_at,
+                    "wrappers",
+                ))
+                .load()
+                .await;
 
             let mut builder = config.to_builder();
             if let Some(endpoint_url) = server.options.get("endpoint_url") {
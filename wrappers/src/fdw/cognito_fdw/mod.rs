@@ -7,7 +7,7 @@ use pgrx::pg_sys::panic::ErrorReport;
 use pgrx::PgSqlErrorCode;
 use thiserror::Error;
 
-use supabase_wrappers::prelude::{CreateRuntimeError, OptionsError};
+use supabase_wrappers::prelude::{AwsError, CreateRuntimeError, OptionsError};
 
 #[derive(Error, Debug)]
 enum CognitoFdwError {
@@ -20,6 +20,9 @@ enum CognitoFdwError {
     #[error("{0}")]
     CreateRuntimeError(#[from] CreateRuntimeError),
 
+    #[error("{0}")]
+    AwsError(#[from] AwsError),
+
     #[error("parse url failed: {0}")]
     UrlParseError(#[from] url::ParseError),
 
@@ -38,7 +41,7 @@ enum CognitoFdwError {
     #[error("{0}")]
     NumericConversionError(#[from] pgrx::numeric::Error),
 
-    #[error("no secret found in vault with id {0}")]
+    #[error("no secret found with id {0}")]
     SecretNotFound(String),
 
     #[error("both `api_key` and `api_secret_key` options must be set")]
@@ -50,7 +53,10 @@ enum CognitoFdwError {
 
 impl From<CognitoFdwError> for ErrorReport {
     fn from(value: CognitoFdwError) -> Self {
-        ErrorReport::new(PgSqlErrorCode::ERRCODE_FDW_ERROR, format!("{value}"), "")
+        match value {
+            CognitoFdwError::AwsError(e) => e.into(),
+            _ => ErrorReport::new(PgSqlErrorCode::ERRCODE_FDW_ERROR, format!("{value}"), ""),
+        }
     }
 }
 
@@ -1,9 +1,9 @@
+use crate::fdw::http_client::{create_http_client, record_request};
 use crate::stats;
 use pgrx::{pg_sys, prelude::*, JsonB};
 use regex::Regex;
 use reqwest::{self, header};
-use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
-use reqwest_retry::{policies::ExponentialBackoff, RetryTransientMiddleware};
+use reqwest_middleware::ClientWithMiddleware;
 use serde_json::Value as JsonValue;
 use std::collections::HashMap;
 use std::str::FromStr;
@@ -60,7 +60,8 @@ fn body_to_rows(
                     "timestamp" => Some(
                         v.as_str()
                             .and_then(|a| a.parse::<i64>().ok())
-                            .map(|ms| to_timestamp(ms as f64 / 1000.0).to_utc())
+                            .and_then(timestamptz_from_epoch_millis)
+                            .map(|ts| ts.to_utc())
                             .map(Cell::Timestamp)
                             .ok_or(FirebaseFdwError::InvalidTimestampFormat(v.to_string()))?,
                     ),
@@ -138,6 +139,8 @@ pub(crate) struct FirebaseFdw {
     rt: Runtime,
     project_id: String,
     client: Option<ClientWithMiddleware>,
+    rate_limiter: RateLimiter,
+    circuit_breaker: CircuitBreaker,
     scan_result: Vec<Row>,
 }
 
@@ -216,19 +219,22 @@ impl ForeignDataWrapper<FirebaseFdwError> for FirebaseFdw {
             rt: create_async_runtime()?,
             project_id: require_option("project_id", &server.options)?.to_string(),
             client: None,
+            rate_limiter: RateLimiter::from_server(&server)?,
+            circuit_breaker: CircuitBreaker::from_server(&server)?,
             scan_result: Vec::default(),
         };
 
         // get oauth2 access token if it is directly defined in options
-        let token = if let Some(access_token) = server.options.get("access_token") {
+        let cred_opts = server.credential_options(&["access_token", "sa_key", "sa_key_id"]);
+        let token = if let Some(access_token) = cred_opts.get("access_token") {
             access_token.to_owned()
         } else {
             // otherwise, get it from the options or Vault
-            let sa_key = match server.options.get("sa_key") {
+            let sa_key = match cred_opts.get("sa_key") {
                 Some(sa_key) => sa_key.to_owned(),
                 None => {
-                    let sa_key_id = require_option("sa_key_id", &server.options)?;
-                    match get_vault_secret(sa_key_id) {
+                    let sa_key_id = require_option("sa_key_id", cred_opts)?;
+                    match get_secret(&server.options, sa_key_id) {
                         Some(sa_key) => sa_key,
                         None => return Ok(ret),
                     }
@@ -248,14 +254,7 @@ impl ForeignDataWrapper<FirebaseFdwError> for FirebaseFdw {
             .map_err(|_| FirebaseFdwError::InvalidApiKeyHeader)?;
         auth_value.set_sensitive(true);
         headers.insert(header::AUTHORIZATION, auth_value);
-        let client = reqwest::Client::builder()
-            .default_headers(headers)
-            .build()?;
-        let retry_policy = ExponentialBackoff::builder().build_with_max_retries(3);
-        let client = ClientBuilder::new(client)
-            .with(RetryTransientMiddleware::new_with_policy(retry_policy))
-            .build();
-        ret.client = Some(client);
+        ret.client = Some(create_http_client(&server.options, headers)?);
 
         stats::inc_stats(Self::FDW_NAME, stats::Metric::CreateTimes, 1);
 
@@ -286,17 +285,22 @@ impl ForeignDataWrapper<FirebaseFdwError> for FirebaseFdw {
             loop {
                 let url = self.build_url(obj, &next_page, options);
 
-                let body = self.rt.block_on(client.get(&url).send()).and_then(|resp| {
-                    stats::inc_stats(
-                        Self::FDW_NAME,
-                        stats::Metric::BytesIn,
-                        resp.content_length().unwrap_or(0) as i64,
-                    );
-
-                    resp.error_for_status()
-                        .and_then(|resp| self.rt.block_on(resp.text()))
-                        .map_err(reqwest_middleware::Error::from)
-                })?;
+                self.circuit_breaker.check()?;
+                self.rate_limiter.wait();
+                let body = record_request(
+                    &self.circuit_breaker,
+                    self.rt.block_on(client.get(&url).send()).and_then(|resp| {
+                        stats::inc_stats(
+                            Self::FDW_NAME,
+                            stats::Metric::BytesIn,
+                            resp.content_length().unwrap_or(0) as i64,
+                        );
+
+                        resp.error_for_status()
+                            .and_then(|resp| self.rt.block_on(resp.text()))
+                            .map_err(reqwest_middleware::Error::from)
+                    }),
+                )?;
 
                 let json: JsonValue = serde_json::from_str(&body)?;
                 let mut rows = resp_to_rows(obj, &json, columns)?;
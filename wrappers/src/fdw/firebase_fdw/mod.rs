@@ -7,7 +7,9 @@ use pgrx::prelude::PgSqlErrorCode;
 use std::num::ParseIntError;
 use thiserror::Error;
 
-use supabase_wrappers::prelude::{CreateRuntimeError, OptionsError};
+use crate::fdw::http_client::HttpClientError;
+use crate::fdw::request_error::{request_error, request_middleware_error};
+use supabase_wrappers::prelude::{CreateRuntimeError, FdwError, OptionsError};
 
 #[derive(Error, Debug)]
 enum FirebaseFdwError {
@@ -35,9 +37,15 @@ enum FirebaseFdwError {
     #[error("{0}")]
     CreateRuntimeError(#[from] CreateRuntimeError),
 
+    #[error("{0}")]
+    HttpClientError(#[from] HttpClientError),
+
     #[error("{0}")]
     OptionsError(#[from] OptionsError),
 
+    #[error("{0}")]
+    FdwError(#[from] FdwError),
+
     #[error("invalid api_key header")]
     InvalidApiKeyHeader,
 
@@ -56,7 +64,22 @@ enum FirebaseFdwError {
 
 impl From<FirebaseFdwError> for ErrorReport {
     fn from(value: FirebaseFdwError) -> Self {
-        ErrorReport::new(PgSqlErrorCode::ERRCODE_FDW_ERROR, format!("{value}"), "")
+        let msg = format!("{value}");
+        match value {
+            FirebaseFdwError::FdwError(e) => e.into(),
+            FirebaseFdwError::HttpClientError(e) => e.into(),
+            FirebaseFdwError::RequestError(e) => request_error(&e, msg).into(),
+            FirebaseFdwError::RequestMiddlewareError(e) => request_middleware_error(&e, msg).into(),
+            FirebaseFdwError::InvalidServiceAccount(_)
+            | FirebaseFdwError::NoTokenFound(_)
+            | FirebaseFdwError::OAuthTokenError(_) => FdwError::Auth(msg).into(),
+            FirebaseFdwError::OptionsError(_) | FirebaseFdwError::LimitOptionParseError(_) => {
+                FdwError::InvalidOption(msg).into()
+            }
+            FirebaseFdwError::UnsupportedColumnType(_)
+            | FirebaseFdwError::InvalidTimestampFormat(_) => FdwError::TypeMismatch(msg).into(),
+            _ => ErrorReport::new(PgSqlErrorCode::ERRCODE_FDW_ERROR, msg, ""),
+        }
     }
 }
 
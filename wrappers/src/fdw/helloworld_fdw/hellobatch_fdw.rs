@@ -0,0 +1,112 @@
+use pgrx::pg_sys::panic::ErrorReport;
+use pgrx::PgSqlErrorCode;
+use std::num::ParseIntError;
+use supabase_wrappers::interface::v2;
+use supabase_wrappers::prelude::*;
+
+// A test FDW on the v2 interface, it is run through the v1 adapter and only
+// built for the tests
+#[wrappers_fdw(
+    version = "0.1.0",
+    author = "Supabase",
+    website = "https://github.com/supabase/wrappers/tree/main/wrappers/src/fdw/helloworld_fdw",
+    error_type = "HelloBatchFdwError",
+    interface = "v2"
+)]
+pub(crate) struct HelloBatchFdw {
+    // total number of rows, set by foreign table option `rows`
+    total: i64,
+
+    // id of the next row
+    next_id: i64,
+
+    // number of fetch_rows calls returned rows
+    fetches: i64,
+
+    // target column list
+    tgt_cols: Vec<Column>,
+}
+
+enum HelloBatchFdwError {
+    InvalidRows(ParseIntError),
+    InsertBatch(usize),
+}
+
+impl From<ParseIntError> for HelloBatchFdwError {
+    fn from(value: ParseIntError) -> Self {
+        Self::InvalidRows(value)
+    }
+}
+
+impl From<HelloBatchFdwError> for ErrorReport {
+    fn from(value: HelloBatchFdwError) -> Self {
+        let msg = match value {
+            HelloBatchFdwError::InvalidRows(err) => format!("invalid rows option: {err}"),
+            HelloBatchFdwError::InsertBatch(n) => format!("received a batch of {n} rows"),
+        };
+        ErrorReport::new(PgSqlErrorCode::ERRCODE_FDW_ERROR, msg, "")
+    }
+}
+
+type HelloBatchFdwResult<T> = Result<T, HelloBatchFdwError>;
+
+impl v2::ForeignDataWrapper<HelloBatchFdwError> for HelloBatchFdw {
+    fn new(_server: ForeignServer) -> HelloBatchFdwResult<Self> {
+        Ok(Self {
+            total: 0,
+            next_id: 1,
+            fetches: 0,
+            tgt_cols: Vec::new(),
+        })
+    }
+
+    fn begin_scan(&mut self, req: &v2::ScanRequest) -> HelloBatchFdwResult<()> {
+        self.total = require_option_or("rows", req.options, "3").parse()?;
+        self.next_id = 1;
+        self.fetches = 0;
+        self.tgt_cols = req.columns.to_vec();
+        Ok(())
+    }
+
+    fn fetch_rows(&mut self, max_rows: usize) -> HelloBatchFdwResult<Vec<Row>> {
+        let end = self.total.min(self.next_id + max_rows as i64 - 1);
+        if self.next_id > end {
+            return Ok(Vec::new());
+        }
+
+        // the batch column is the sequence number of this call, so the tests
+        // can check how the adapter splits the scan
+        self.fetches += 1;
+        let rows = (self.next_id..=end)
+            .map(|id| {
+                let mut row = Row::new();
+                for tgt_col in &self.tgt_cols {
+                    match tgt_col.name.as_str() {
+                        "id" => row.push("id", Some(Cell::I64(id))),
+                        "batch" => row.push("batch", Some(Cell::I64(self.fetches))),
+                        _ => {}
+                    }
+                }
+                row
+            })
+            .collect();
+        self.next_id = end + 1;
+
+        Ok(rows)
+    }
+
+    fn re_scan(&mut self) -> HelloBatchFdwResult<()> {
+        self.next_id = 1;
+        self.fetches = 0;
+        Ok(())
+    }
+
+    fn end_scan(&mut self) -> HelloBatchFdwResult<()> {
+        Ok(())
+    }
+
+    // nothing is stored, the batch size is reported back to the tests
+    fn insert_rows(&mut self, rows: &[Row]) -> HelloBatchFdwResult<()> {
+        Err(HelloBatchFdwError::InsertBatch(rows.len()))
+    }
+}
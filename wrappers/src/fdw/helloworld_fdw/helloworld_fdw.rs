@@ -8,7 +8,10 @@ use supabase_wrappers::prelude::*;
     version = "0.1.1",
     author = "Supabase",
     website = "https://github.com/supabase/wrappers/tree/main/wrappers/src/fdw/helloworld_fdw",
-    error_type = "HelloWorldFdwError"
+    error_type = "HelloWorldFdwError",
+    smoke_sql = "create server hello_server foreign data wrapper hello_world_fdw_wrapper;
+                 create foreign table hello (id bigint, col text) server hello_server;
+                 select * from hello"
 )]
 pub(crate) struct HelloWorldFdw {
     // row counter
@@ -1,2 +1,5 @@
 #![allow(clippy::module_inception)]
+#[cfg(any(test, feature = "pg_test"))]
+mod hellobatch_fdw;
 mod helloworld_fdw;
+mod tests;
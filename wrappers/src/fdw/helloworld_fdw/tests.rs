@@ -0,0 +1,185 @@
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+
+    #[pg_test]
+    fn hellobatch_scan_test() {
+        Spi::connect(|mut c| {
+            c.update(
+                r#"CREATE FOREIGN DATA WRAPPER hellobatch_wrapper
+                         HANDLER hello_batch_fdw_handler VALIDATOR hello_batch_fdw_validator"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"CREATE SERVER my_hellobatch_server
+                         FOREIGN DATA WRAPPER hellobatch_wrapper"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"
+                  CREATE FOREIGN TABLE hello_batch (
+                    id bigint,
+                    batch bigint
+                  )
+                  SERVER my_hellobatch_server
+                  OPTIONS (
+                    rows '2500'
+                  )
+             "#,
+                None,
+                None,
+            )
+            .unwrap();
+
+            // the v1 adapter fetches 1000 rows in each batch
+            let results = c
+                .select(
+                    "SELECT count(*) AS cnt, max(id) AS max_id, max(batch) AS batches FROM hello_batch",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .map(|r| {
+                    (
+                        r.get_by_name::<i64, _>("cnt").unwrap().unwrap(),
+                        r.get_by_name::<i64, _>("max_id").unwrap().unwrap(),
+                        r.get_by_name::<i64, _>("batches").unwrap().unwrap(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec![(2500, 2500, 3)]);
+
+            let results = c
+                .select(
+                    "SELECT batch FROM hello_batch WHERE id IN (1000, 1001, 2500) ORDER BY id",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<i64, _>("batch").unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec![1, 2, 3]);
+        });
+    }
+
+    #[pg_test(error = "received a batch of 2 rows")]
+    fn hellobatch_insert_test() {
+        Spi::connect(|mut c| {
+            c.update(
+                r#"CREATE FOREIGN DATA WRAPPER hellobatch_wrapper
+                         HANDLER hello_batch_fdw_handler VALIDATOR hello_batch_fdw_validator"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"CREATE SERVER my_hellobatch_server
+                         FOREIGN DATA WRAPPER hellobatch_wrapper"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"
+                  CREATE FOREIGN TABLE hello_batch (
+                    id bigint,
+                    batch bigint
+                  )
+                  SERVER my_hellobatch_server
+                  OPTIONS (
+                    rowid_column 'id',
+                    batch_size '2'
+                  )
+             "#,
+                None,
+                None,
+            )
+            .unwrap();
+
+            // the rows are passed to the v2 FDW in batches of batch_size
+            c.update(
+                "INSERT INTO hello_batch (id) VALUES (1), (2), (3)",
+                None,
+                None,
+            )
+            .unwrap();
+        });
+    }
+
+    #[pg_test]
+    fn helloworld_merge_join_test() {
+        Spi::connect(|mut c| {
+            c.update(
+                r#"CREATE FOREIGN DATA WRAPPER helloworld_wrapper
+                         HANDLER hello_world_fdw_handler VALIDATOR hello_world_fdw_validator"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"CREATE SERVER my_helloworld_server
+                         FOREIGN DATA WRAPPER helloworld_wrapper"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"
+                  CREATE FOREIGN TABLE hello (
+                    id bigint,
+                    col text
+                  )
+                  SERVER my_helloworld_server
+             "#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                "CREATE TABLE hello_keys (id bigint); INSERT INTO hello_keys VALUES (0), (0), (0)",
+                None,
+                None,
+            )
+            .unwrap();
+
+            // the merge join restores the inner side for the duplicated keys,
+            // which is done by the local Sort or Material node on top of the
+            // foreign scan
+            c.update(
+                "SET LOCAL enable_hashjoin = off; SET LOCAL enable_nestloop = off",
+                None,
+                None,
+            )
+            .unwrap();
+            let cnt = c
+                .select(
+                    "SELECT count(*) FROM hello_keys k JOIN hello h ON k.id = h.id",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .first()
+                .get_one::<i64>()
+                .unwrap();
+            assert_eq!(cnt, Some(3));
+
+            // scrollable cursor materializes the foreign scan locally
+            let plan = c
+                .select(
+                    "EXPLAIN DECLARE hello_cur SCROLL CURSOR FOR SELECT * FROM hello",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get::<&str>(1).unwrap())
+                .collect::<Vec<_>>()
+                .join("\n");
+            assert!(plan.contains("Materialize"), "{}", plan);
+        });
+    }
+}
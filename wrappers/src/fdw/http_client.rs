@@ -0,0 +1,482 @@
+//! Shared HTTP client builder of the API based FDWs
+
+use pgrx::pg_sys::panic::ErrorReport;
+use pgrx::{pg_sys, PgSqlErrorCode};
+use reqwest::{
+    header::HeaderMap, Certificate, Identity, NoProxy, Proxy, Request, Response,
+    ResponseBuilderExt, StatusCode,
+};
+use reqwest_middleware::{ClientBuilder, ClientWithMiddleware, Next};
+use reqwest_retry::{
+    default_on_request_failure, default_on_request_success, policies::ExponentialBackoff,
+    RetryTransientMiddleware, Retryable, RetryableStrategy,
+};
+use std::collections::HashMap;
+use std::env;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use supabase_wrappers::prelude::{
+    add_server_metric, audit_enabled, audit_remote_request, debug_level, flush_traces, get_secret,
+    get_secret_by_name, log_remote_requests, redact, trace, truncate_trace, CircuitBreaker,
+    FdwError, OptionsError, RetryOptions, ServerMetric, Timeouts,
+};
+use task_local_extensions::Extensions;
+use thiserror::Error;
+
+// max backoff between retries
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(30);
+
+// a remote request to be logged and audited
+struct RequestLog {
+    method: String,
+    url: String,
+    result: Result<StatusCode, String>,
+    duration: Duration,
+}
+
+// remote requests waiting to be logged, the requests can be sent from the
+// async runtime worker threads, so they are logged later in Postgres thread
+static REQUEST_LOGS: Mutex<Vec<RequestLog>> = Mutex::new(Vec::new());
+
+// metrics of the remote requests not yet added to the foreign server, they are
+// added in Postgres thread for the same reason as the request logs
+struct RequestMetrics {
+    requests: i64,
+    bytes_received: i64,
+    wait_time_us: i64,
+}
+
+static REQUEST_METRICS: Mutex<RequestMetrics> = Mutex::new(RequestMetrics {
+    requests: 0,
+    bytes_received: 0,
+    wait_time_us: 0,
+});
+
+// default retry strategy, except rate limited request is not retried if
+// `retry_on_429` is off
+struct RetryStrategy {
+    on_429: bool,
+}
+
+impl RetryableStrategy for RetryStrategy {
+    fn handle(
+        &self,
+        res: &Result<reqwest::Response, reqwest_middleware::Error>,
+    ) -> Option<Retryable> {
+        match res {
+            Ok(resp) if !self.on_429 && resp.status() == StatusCode::TOO_MANY_REQUESTS => {
+                Some(Retryable::Fatal)
+            }
+            Ok(resp) => default_on_request_success(resp),
+            Err(err) => default_on_request_failure(err),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub(crate) enum HttpClientError {
+    #[error("{0}")]
+    OptionsError(#[from] OptionsError),
+
+    #[error("invalid option {0}: {1}")]
+    InvalidOption(&'static str, reqwest::Error),
+
+    #[error("secret of option {0} is not found")]
+    SecretNotFound(String),
+
+    #[error("create http client failed: {0}")]
+    ClientError(#[from] reqwest::Error),
+}
+
+impl From<HttpClientError> for ErrorReport {
+    fn from(value: HttpClientError) -> Self {
+        let msg = format!("{value}");
+        match value {
+            HttpClientError::OptionsError(e) => e.into(),
+            HttpClientError::InvalidOption(..) | HttpClientError::SecretNotFound(_) => {
+                FdwError::InvalidOption(msg).into()
+            }
+            HttpClientError::ClientError(_) => {
+                ErrorReport::new(PgSqlErrorCode::ERRCODE_FDW_ERROR, msg, "")
+            }
+        }
+    }
+}
+
+// get proxy option from the foreign server options, or from the environment
+// variable of the same name in upper or lower case
+fn proxy_option(options: &HashMap<String, String>, name: &str) -> Option<String> {
+    options
+        .get(name)
+        .cloned()
+        .or_else(|| env::var(name.to_uppercase()).ok())
+        .or_else(|| env::var(name).ok())
+        .filter(|v| !v.is_empty())
+}
+
+// get PEM content from the option, or from the secret provider by the secret ID
+// or name in option `<name>_id` or `<name>_name`
+fn pem_option(
+    options: &HashMap<String, String>,
+    name: &str,
+) -> Result<Option<String>, HttpClientError> {
+    if let Some(pem) = options.get(name) {
+        return Ok(Some(pem.to_owned()));
+    }
+    for (suffix, get_secret) in [
+        (
+            "_id",
+            get_secret as fn(&HashMap<String, String>, &str) -> Option<String>,
+        ),
+        ("_name", get_secret_by_name),
+    ] {
+        let opt_name = format!("{}{}", name, suffix);
+        if let Some(secret) = options.get(&opt_name) {
+            return get_secret(options, secret)
+                .map(Some)
+                .ok_or(HttpClientError::SecretNotFound(opt_name));
+        }
+    }
+    Ok(None)
+}
+
+/// Create a HTTP client by the foreign server options
+///
+/// The client sends the default headers and is set up by the standard
+/// foreign server options:
+///
+/// - `connect_timeout` and `request_timeout`, see [`Timeouts`]
+/// - `retry_count`, `retry_backoff_ms` and `retry_on_429`, see [`RetryOptions`]
+/// - `http_proxy` and `https_proxy`: proxy URL for HTTP and HTTPS requests
+/// - `no_proxy`: comma-separated hosts which bypass the proxy
+/// - `ssl_ca`: PEM encoded CA certificates to verify the server certificate,
+///   in addition to the system CA certificates
+/// - `ssl_cert` and `ssl_key`: PEM encoded client certificate and its PKCS#8
+///   private key for mutual TLS authentication
+///
+/// The proxy options fall back to the environment variables of the same name.
+/// Each request is logged to the server log if `wrappers.log_remote_requests`
+/// is on when the client is created, and audited if `wrappers.audit_log` is on.
+/// The requests and responses are traced if `wrappers.debug_level` is set when
+/// the client is created.
+/// The TLS options can also be stored in Vault and referenced by the secret ID
+/// or name in option `<option>_id` or `<option>_name`, e.g. `ssl_key_id`.
+pub(crate) fn create_http_client(
+    options: &HashMap<String, String>,
+    headers: HeaderMap,
+) -> Result<ClientWithMiddleware, HttpClientError> {
+    let timeouts = Timeouts::from_options(options)?;
+    let retry = RetryOptions::from_options(options)?;
+
+    let mut builder = reqwest::Client::builder()
+        .default_headers(headers)
+        .connect_timeout(timeouts.connect)
+        .timeout(timeouts.request);
+
+    let no_proxy = proxy_option(options, "no_proxy").and_then(|v| NoProxy::from_string(&v));
+    if let Some(url) = proxy_option(options, "http_proxy") {
+        let proxy =
+            Proxy::http(&url).map_err(|e| HttpClientError::InvalidOption("http_proxy", e))?;
+        builder = builder.proxy(proxy.no_proxy(no_proxy.clone()));
+    }
+    if let Some(url) = proxy_option(options, "https_proxy") {
+        let proxy =
+            Proxy::https(&url).map_err(|e| HttpClientError::InvalidOption("https_proxy", e))?;
+        builder = builder.proxy(proxy.no_proxy(no_proxy));
+    }
+
+    if let Some(ca) = pem_option(options, "ssl_ca")? {
+        let certs = Certificate::from_pem_bundle(ca.as_bytes())
+            .map_err(|e| HttpClientError::InvalidOption("ssl_ca", e))?;
+        for cert in certs {
+            builder = builder.add_root_certificate(cert);
+        }
+    }
+    match (
+        pem_option(options, "ssl_cert")?,
+        pem_option(options, "ssl_key")?,
+    ) {
+        (Some(cert), Some(key)) => {
+            let identity = Identity::from_pkcs8_pem(cert.as_bytes(), key.as_bytes())
+                .map_err(|e| HttpClientError::InvalidOption("ssl_cert", e))?;
+            builder = builder.identity(identity);
+        }
+        (Some(_), None) => {
+            return Err(OptionsError::OptionNameNotFound("ssl_key".to_string()).into());
+        }
+        (None, Some(_)) => {
+            return Err(OptionsError::OptionNameNotFound("ssl_cert".to_string()).into());
+        }
+        (None, None) => {}
+    }
+
+    Ok(with_middlewares(
+        builder.build()?,
+        &retry,
+        log_remote_requests() || audit_enabled(),
+        debug_level(),
+    ))
+}
+
+// wrap a client with the exponential backoff retry by the retry options, the
+// request logging if the request log or audit is enabled, and the tracing by
+// the debug level
+fn with_middlewares(
+    client: reqwest::Client,
+    retry: &RetryOptions,
+    log_requests: bool,
+    debug_level: i32,
+) -> ClientWithMiddleware {
+    let retry_policy = ExponentialBackoff::builder()
+        .retry_bounds(retry.backoff, retry.backoff.max(MAX_RETRY_BACKOFF))
+        .build_with_max_retries(retry.count);
+    let strategy = RetryStrategy {
+        on_429: retry.on_429,
+    };
+    // the middlewares after the retry middleware see each retry
+    let mut builder = ClientBuilder::new(client)
+        .with(RetryTransientMiddleware::new_with_policy_and_strategy(
+            retry_policy,
+            strategy,
+        ))
+        .with(count_request);
+    if log_requests {
+        builder = builder.with(log_request);
+    }
+    if debug_level >= 1 {
+        builder = builder.with(trace_request);
+    }
+    if debug_level >= 2 {
+        builder = builder.with(trace_body);
+    }
+    builder.build()
+}
+
+// middleware to count each request, its response size and duration
+fn count_request<'a>(
+    req: Request,
+    extensions: &'a mut Extensions,
+    next: Next<'a>,
+) -> Pin<Box<dyn Future<Output = reqwest_middleware::Result<Response>> + Send + 'a>> {
+    Box::pin(async move {
+        let started_at = Instant::now();
+        let resp = next.run(req, extensions).await;
+        if let Ok(mut metrics) = REQUEST_METRICS.lock() {
+            metrics.requests += 1;
+            metrics.wait_time_us += started_at.elapsed().as_micros() as i64;
+            if let Ok(resp) = &resp {
+                metrics.bytes_received += resp.content_length().unwrap_or_default() as i64;
+            }
+        }
+        resp
+    })
+}
+
+// middleware to record method, URL, result and duration of each request
+fn log_request<'a>(
+    req: Request,
+    extensions: &'a mut Extensions,
+    next: Next<'a>,
+) -> Pin<Box<dyn Future<Output = reqwest_middleware::Result<Response>> + Send + 'a>> {
+    Box::pin(async move {
+        let method = req.method().to_string();
+        let url = req.url().to_string();
+        let started_at = Instant::now();
+        let resp = next.run(req, extensions).await;
+        let result = match &resp {
+            Ok(resp) => Ok(resp.status()),
+            Err(err) => Err(err.to_string()),
+        };
+        if let Ok(mut logs) = REQUEST_LOGS.lock() {
+            logs.push(RequestLog {
+                method,
+                url,
+                result,
+                duration: started_at.elapsed(),
+            });
+        }
+        resp
+    })
+}
+
+// the headers whose values are not traced
+fn is_sensitive_header(name: &str) -> bool {
+    name == "authorization"
+        || name == "proxy-authorization"
+        || name == "cookie"
+        || ["key", "token", "secret"]
+            .iter()
+            .any(|word| name.contains(word))
+}
+
+// middleware to trace method, URL and headers of each request, and the
+// response status or error
+fn trace_request<'a>(
+    req: Request,
+    extensions: &'a mut Extensions,
+    next: Next<'a>,
+) -> Pin<Box<dyn Future<Output = reqwest_middleware::Result<Response>> + Send + 'a>> {
+    Box::pin(async move {
+        let headers = req
+            .headers()
+            .iter()
+            .map(|(name, value)| {
+                let value = if is_sensitive_header(name.as_str()) {
+                    "********"
+                } else {
+                    value.to_str().unwrap_or("<binary>")
+                };
+                format!("{}: {}", name, value)
+            })
+            .collect::<Vec<_>>();
+        trace(format!(
+            "> {} {} [{}]",
+            req.method(),
+            req.url(),
+            headers.join(", ")
+        ));
+        let started_at = Instant::now();
+        let resp = next.run(req, extensions).await;
+        match &resp {
+            Ok(resp) => trace(format!(
+                "< {} in {} ms",
+                resp.status(),
+                started_at.elapsed().as_millis()
+            )),
+            Err(err) => trace(format!(
+                "< error in {} ms: {}",
+                started_at.elapsed().as_millis(),
+                err
+            )),
+        }
+        resp
+    })
+}
+
+// middleware to trace the request and response bodies, the response is read
+// and rebuilt so it can still be read by the FDW
+fn trace_body<'a>(
+    req: Request,
+    extensions: &'a mut Extensions,
+    next: Next<'a>,
+) -> Pin<Box<dyn Future<Output = reqwest_middleware::Result<Response>> + Send + 'a>> {
+    Box::pin(async move {
+        if let Some(body) = req.body().and_then(|body| body.as_bytes()) {
+            trace(format!(
+                "> body: {}",
+                truncate_trace(&String::from_utf8_lossy(body))
+            ));
+        }
+        let resp = next.run(req, extensions).await?;
+        let (status, version, url, headers) = (
+            resp.status(),
+            resp.version(),
+            resp.url().clone(),
+            resp.headers().clone(),
+        );
+        let body = resp.bytes().await?;
+        trace(format!(
+            "< body: {}",
+            truncate_trace(&String::from_utf8_lossy(&body))
+        ));
+        let mut builder = http::Response::builder()
+            .status(status)
+            .version(version)
+            .url(url);
+        if let Some(resp_headers) = builder.headers_mut() {
+            *resp_headers = headers;
+        }
+        builder
+            .body(body)
+            .map(Response::from)
+            .map_err(|err| reqwest_middleware::Error::Middleware(err.into()))
+    })
+}
+
+// write the recorded requests to the server log, and the audit records of
+// them if audit is enabled
+fn flush_request_logs(server_oid: pg_sys::Oid) {
+    let logs = match REQUEST_LOGS.lock() {
+        Ok(mut logs) => std::mem::take(&mut *logs),
+        Err(_) => return,
+    };
+    let (log_requests, audit) = (log_remote_requests(), audit_enabled());
+    for log in logs {
+        let operation = format!("{} {}", log.method, log.url);
+        let result = match &log.result {
+            Ok(status) => status.to_string(),
+            Err(err) => format!("error: {}", err),
+        };
+        if log_requests {
+            let msg = format!(
+                "{} {} in {} ms",
+                operation,
+                result,
+                log.duration.as_millis()
+            );
+            pgrx::log!("wrappers remote request: {}", redact(&msg));
+        }
+        if audit {
+            let error = match &log.result {
+                Ok(status) if status.is_client_error() || status.is_server_error() => Some(result),
+                Ok(_) => None,
+                Err(_) => Some(result),
+            };
+            audit_remote_request(server_oid, &operation, None, log.duration, error.as_deref());
+        }
+    }
+}
+
+// add the counted requests to the foreign server metrics, the requests more
+// than one are the retries of the request
+fn flush_request_metrics(server_oid: pg_sys::Oid) {
+    let metrics = match REQUEST_METRICS.lock() {
+        Ok(mut metrics) => RequestMetrics {
+            requests: std::mem::take(&mut metrics.requests),
+            bytes_received: std::mem::take(&mut metrics.bytes_received),
+            wait_time_us: std::mem::take(&mut metrics.wait_time_us),
+        },
+        Err(_) => return,
+    };
+    add_server_metric(server_oid, ServerMetric::RemoteRequests, metrics.requests);
+    add_server_metric(
+        server_oid,
+        ServerMetric::Retries,
+        (metrics.requests - 1).max(0),
+    );
+    add_server_metric(
+        server_oid,
+        ServerMetric::BytesReceived,
+        metrics.bytes_received,
+    );
+    add_server_metric(server_oid, ServerMetric::WaitTimeUs, metrics.wait_time_us);
+}
+
+/// Record result of a request to the circuit breaker
+///
+/// Only the failures showing the remote server is unavailable, like connection
+/// error, timeout and HTTP 5xx, are counted. Other results mean the server is
+/// working, so they close the circuit. The recorded request logs are also
+/// written to the server log and audit log, the request traces are written to
+/// the log, and the request metrics are added to the foreign server.
+pub(crate) fn record_request<T>(
+    circuit_breaker: &CircuitBreaker,
+    result: reqwest_middleware::Result<T>,
+) -> reqwest_middleware::Result<T> {
+    flush_request_logs(circuit_breaker.server_oid());
+    flush_request_metrics(circuit_breaker.server_oid());
+    flush_traces();
+    match &result {
+        Err(err)
+            if err.is_connect()
+                || err.is_timeout()
+                || err.status().is_some_and(|s| s.is_server_error()) =>
+        {
+            circuit_breaker.record_failure()
+        }
+        _ => circuit_breaker.record_success(),
+    }
+    result
+}
@@ -1,36 +1,29 @@
+use crate::fdw::http_client::{create_http_client, record_request};
 use crate::stats;
-use pgrx::{
-    pg_sys,
-    prelude::{AnyNumeric, Date, Timestamp, TimestampWithTimeZone},
-};
+use pgrx::{pg_sys, JsonB};
 use reqwest::{
     self,
     header::{HeaderMap, HeaderName, HeaderValue},
     StatusCode, Url,
 };
-use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
-use reqwest_retry::{policies::ExponentialBackoff, RetryTransientMiddleware};
+use reqwest_middleware::ClientWithMiddleware;
 use serde_json::value::Value as JsonValue;
 use std::collections::HashMap;
-use std::str::FromStr;
 
 use supabase_wrappers::prelude::*;
 
 use super::{LogflareFdwError, LogflareFdwResult};
 
-fn create_client(api_key: &str) -> LogflareFdwResult<ClientWithMiddleware> {
+fn create_client(
+    api_key: &str,
+    options: &HashMap<String, String>,
+) -> LogflareFdwResult<ClientWithMiddleware> {
     let mut headers = HeaderMap::new();
     let header_name = HeaderName::from_static("x-api-key");
     let mut auth_value = HeaderValue::from_str(api_key)?;
     auth_value.set_sensitive(true);
     headers.insert(header_name, auth_value);
-    let client = reqwest::Client::builder()
-        .default_headers(headers)
-        .build()?;
-    let retry_policy = ExponentialBackoff::builder().build_with_max_retries(3);
-    Ok(ClientBuilder::new(client)
-        .with(RetryTransientMiddleware::new_with_policy(retry_policy))
-        .build())
+    Ok(create_http_client(options, headers)?)
 }
 
 fn extract_params(quals: &[Qual]) -> Option<Vec<Qual>> {
@@ -47,65 +40,45 @@ fn extract_params(quals: &[Qual]) -> Option<Vec<Qual>> {
     Some(params)
 }
 
-fn json_value_to_cell(tgt_col: &Column, v: &JsonValue) -> LogflareFdwResult<Cell> {
-    match tgt_col.type_oid {
-        pg_sys::BOOLOID => v.as_bool().map(Cell::Bool),
-        pg_sys::CHAROID => v.as_i64().and_then(|s| i8::try_from(s).ok()).map(Cell::I8),
-        pg_sys::INT2OID => v
-            .as_i64()
-            .and_then(|s| i16::try_from(s).ok())
-            .map(Cell::I16),
-        pg_sys::FLOAT4OID => v.as_f64().map(|s| s as f32).map(Cell::F32),
-        pg_sys::INT4OID => v
-            .as_i64()
-            .and_then(|s| i32::try_from(s).ok())
-            .map(Cell::I32),
-        pg_sys::FLOAT8OID => v.as_f64().map(Cell::F64),
-        pg_sys::INT8OID => v.as_i64().map(Cell::I64),
-        pg_sys::NUMERICOID => v
-            .as_f64()
-            .and_then(|s| AnyNumeric::try_from(s).ok())
-            .map(Cell::Numeric),
-        pg_sys::TEXTOID => v.as_str().map(|s| s.to_owned()).map(Cell::String),
-        pg_sys::DATEOID => v
-            .as_str()
-            .and_then(|s| Date::from_str(s).ok())
-            .map(Cell::Date),
-        pg_sys::TIMESTAMPOID => v
-            .as_str()
-            .and_then(|s| Timestamp::from_str(s).ok())
-            .map(Cell::Timestamp),
-        pg_sys::TIMESTAMPTZOID => v
-            .as_str()
-            .and_then(|s| TimestampWithTimeZone::from_str(s).ok())
-            .map(Cell::Timestamptz),
-        _ => {
-            return Err(LogflareFdwError::UnsupportedColumnType(
-                tgt_col.name.clone(),
-            ))
-        }
-    }
-    .ok_or(LogflareFdwError::ColumnTypeNotMatch(tgt_col.name.clone()))
-}
-
 #[wrappers_fdw(
     version = "0.1.1",
     author = "Supabase",
     website = "https://github.com/supabase/wrappers/tree/main/wrappers/src/fdw/logflare_fdw",
-    error_type = "LogflareFdwError"
+    error_type = "LogflareFdwError",
+    options(
+        server(
+            api_url(example = "https://api.logflare.app/api/endpoints/query/"),
+            api_key(secret, example = "xxx-xxx-xxx")
+        ),
+        table(endpoint(required, example = "my.custom.endpoint"))
+    )
 )]
 pub(crate) struct LogflareFdw {
-    rt: Runtime,
     base_url: Url,
     client: Option<ClientWithMiddleware>,
-    scan_result: Vec<Row>,
+    rate_limiter: RateLimiter,
+    circuit_breaker: CircuitBreaker,
+    scan_result: Option<ScanResultBuffer>,
+    tgt_cols: Vec<Column>,
+    prefetch: Prefetch<reqwest_middleware::Result<(Option<u64>, JsonValue)>>,
     params: Vec<Qual>,
 }
 
+#[derive(FdwOptions)]
+struct LogflareServerOptions {
+    #[option(default = "https://api.logflare.app/api/endpoints/query/")]
+    api_url: String,
+    #[option(secret)]
+    api_key: String,
+}
+
+#[derive(FdwOptions)]
+struct LogflareTableOptions {
+    endpoint: String,
+}
+
 impl LogflareFdw {
     const FDW_NAME: &'static str = "LogflareFdw";
-    const BASE_URL: &'static str = "https://api.logflare.app/api/endpoints/query/";
-
     fn build_url(&self, endpoint: &str) -> LogflareFdwResult<Option<Url>> {
         let mut url = self.base_url.join(endpoint)?;
         for param in &self.params {
@@ -156,7 +129,11 @@ impl LogflareFdw {
                             for tgt_col in tgt_cols {
                                 let cell: Option<Cell> = if tgt_col.name == "_result" {
                                     // add _result meta cell
-                                    Some(Cell::String(record.to_string()))
+                                    if tgt_col.type_oid == pg_sys::JSONBOID {
+                                        Some(Cell::Json(JsonB(record.clone())))
+                                    } else {
+                                        Some(Cell::String(record.to_string()))
+                                    }
                                 } else if tgt_col.name.starts_with("_param_") {
                                     // add param cell
                                     self.params.iter().find_map(|p| {
@@ -172,13 +149,9 @@ impl LogflareFdw {
                                     })
                                 } else {
                                     // add normal cell
-                                    if let Some(s) = r.get(&tgt_col.name) {
-                                        match json_value_to_cell(tgt_col, s) {
-                                            Ok(cell) => Some(cell),
-                                            Err(err) => return Err(err),
-                                        }
-                                    } else {
-                                        None
+                                    match r.get(&tgt_col.name) {
+                                        Some(s) => json_to_cell(s, tgt_col)?,
+                                        None => None,
                                     }
                                 };
                                 row.push(&tgt_col.name, cell);
@@ -194,38 +167,37 @@ impl LogflareFdw {
 
 impl ForeignDataWrapper<LogflareFdwError> for LogflareFdw {
     fn new(server: ForeignServer) -> LogflareFdwResult<Self> {
-        let base_url = server
-            .options
-            .get("api_url")
-            .map(|t| t.to_owned())
-            .map(|s| {
-                if s.ends_with('/') {
-                    s
-                } else {
-                    format!("{}/", s)
-                }
-            })
-            .unwrap_or_else(|| LogflareFdw::BASE_URL.to_string());
-        let client = match server.options.get("api_key") {
-            Some(api_key) => Some(create_client(api_key)),
-            None => {
-                let key_id = require_option("api_key_id", &server.options)?;
-                get_vault_secret(key_id).map(|api_key| create_client(&api_key))
-            }
-        }
-        .transpose()?;
+        let opts = LogflareServerOptions::from_server(&server)?;
+        let base_url = if opts.api_url.ends_with('/') {
+            opts.api_url
+        } else {
+            format!("{}/", opts.api_url)
+        };
+        let client = Some(create_client(&opts.api_key, &server.options)?);
 
         stats::inc_stats(Self::FDW_NAME, stats::Metric::CreateTimes, 1);
 
+        let rt = create_async_runtime()?;
+
         Ok(LogflareFdw {
-            rt: create_async_runtime()?,
+            prefetch: Prefetch::new(&rt),
             base_url: Url::parse(&base_url)?,
             client,
-            scan_result: Vec::default(),
+            rate_limiter: RateLimiter::from_server(&server)?,
+            circuit_breaker: CircuitBreaker::from_server(&server)?,
+            scan_result: None,
+            tgt_cols: Vec::default(),
             params: Vec::default(),
         })
     }
 
+    fn capabilities() -> FdwCapabilities {
+        FdwCapabilities {
+            qual_pushdown: true,
+            ..Default::default()
+        }
+    }
+
     fn begin_scan(
         &mut self,
         quals: &[Qual],
@@ -234,7 +206,11 @@ impl ForeignDataWrapper<LogflareFdwError> for LogflareFdw {
         _limit: &Option<Limit>,
         options: &HashMap<String, String>,
     ) -> LogflareFdwResult<()> {
-        let endpoint = require_option("endpoint", options)?;
+        let endpoint = LogflareTableOptions::from_options(options)?.endpoint;
+
+        self.prefetch.cancel();
+        self.scan_result = Some(ScanResultBuffer::new(columns, options)?);
+        self.tgt_cols = columns.to_vec();
 
         // extract params
         self.params = if let Some(params) = extract_params(quals) {
@@ -245,71 +221,70 @@ impl ForeignDataWrapper<LogflareFdwError> for LogflareFdw {
 
         if let Some(client) = &self.client {
             // build url
-            let url = self.build_url(endpoint)?;
+            let url = self.build_url(&endpoint)?;
             if url.is_none() {
                 return Ok(());
             }
             let url = url.unwrap();
 
-            // make api call
-            let body: JsonValue = self.rt.block_on(client.get(url).send()).and_then(|resp| {
-                stats::inc_stats(
-                    Self::FDW_NAME,
-                    stats::Metric::BytesIn,
-                    resp.content_length().unwrap_or(0) as i64,
-                );
+            // make api call in background, so the requests of multiple
+            // foreign tables in a query are sent concurrently, its result is
+            // taken in iter_scan()
+            self.circuit_breaker.check()?;
+            self.rate_limiter.wait();
+            let client = client.clone();
+            self.prefetch.spawn(async move {
+                let resp = client.get(url).send().await?;
+                let content_length = resp.content_length();
 
                 if resp.status() == StatusCode::NOT_FOUND {
                     // if it is 404 error, we should treat it as an empty
                     // result rather than a request error
-                    return Ok(JsonValue::Null);
+                    return Ok((content_length, JsonValue::Null));
                 }
 
-                resp.error_for_status()
-                    .and_then(|resp| self.rt.block_on(resp.json()))
-                    .map_err(reqwest_middleware::Error::from)
-            })?;
-            if body.is_null() {
-                return Ok(());
-            }
-
-            let result = self.resp_to_rows(&body, columns)?;
-            if !result.is_empty() {
-                stats::inc_stats(Self::FDW_NAME, stats::Metric::RowsIn, result.len() as i64);
-                stats::inc_stats(Self::FDW_NAME, stats::Metric::RowsOut, result.len() as i64);
-            }
-            self.scan_result = result;
+                let body = resp.error_for_status()?.json().await?;
+                Ok((content_length, body))
+            });
         }
 
         Ok(())
     }
 
     fn iter_scan(&mut self, row: &mut Row) -> LogflareFdwResult<Option<()>> {
-        if self.scan_result.is_empty() {
-            Ok(None)
-        } else {
-            Ok(self
-                .scan_result
-                .drain(0..1)
-                .last()
-                .map(|src_row| row.replace_with(src_row)))
-        }
-    }
+        if let Some(resp) = self.prefetch.wait() {
+            let (content_length, body) = record_request(&self.circuit_breaker, resp)?;
+            stats::inc_stats(
+                Self::FDW_NAME,
+                stats::Metric::BytesIn,
+                content_length.unwrap_or(0) as i64,
+            );
 
-    fn end_scan(&mut self) -> LogflareFdwResult<()> {
-        Ok(())
-    }
-
-    fn validator(
-        options: Vec<Option<String>>,
-        catalog: Option<pg_sys::Oid>,
-    ) -> LogflareFdwResult<()> {
-        if let Some(oid) = catalog {
-            if oid == FOREIGN_TABLE_RELATION_ID {
-                check_options_contain(&options, "endpoint")?;
+            if !body.is_null() {
+                let tgt_cols = self.tgt_cols.clone();
+                let result = self.resp_to_rows(&body, &tgt_cols)?;
+                if !result.is_empty() {
+                    stats::inc_stats(Self::FDW_NAME, stats::Metric::RowsIn, result.len() as i64);
+                    stats::inc_stats(Self::FDW_NAME, stats::Metric::RowsOut, result.len() as i64);
+                }
+                if let Some(scan_result) = &mut self.scan_result {
+                    for row in result {
+                        scan_result.push(row);
+                    }
+                }
             }
         }
 
+        Ok(self
+            .scan_result
+            .as_mut()
+            .and_then(|scan_result| scan_result.next_row())
+            .map(|src_row| row.replace_with(src_row)))
+    }
+
+    fn end_scan(&mut self) -> LogflareFdwResult<()> {
+        self.scan_result.take();
+        self.prefetch.cancel();
         Ok(())
     }
 }
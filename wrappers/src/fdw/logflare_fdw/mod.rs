@@ -6,7 +6,9 @@ use pgrx::pg_sys::panic::ErrorReport;
 use pgrx::prelude::PgSqlErrorCode;
 use thiserror::Error;
 
-use supabase_wrappers::prelude::{CreateRuntimeError, OptionsError};
+use crate::fdw::http_client::HttpClientError;
+use crate::fdw::request_error::{request_error, request_middleware_error};
+use supabase_wrappers::prelude::{CreateRuntimeError, FdwError, JsonToRowError, OptionsError};
 
 #[derive(Error, Debug)]
 enum LogflareFdwError {
@@ -16,21 +18,24 @@ enum LogflareFdwError {
     #[error("parameter '{0}' doesn't supports array value")]
     NoArrayParameter(String),
 
-    #[error("column '{0}' data type is not supported")]
-    UnsupportedColumnType(String),
-
-    #[error("column '{0}' data type not match")]
-    ColumnTypeNotMatch(String),
-
     #[error("invalid Logflare response: {0}")]
     InvalidResponse(String),
 
     #[error("{0}")]
     OptionsError(#[from] OptionsError),
 
+    #[error("{0}")]
+    FdwError(#[from] FdwError),
+
+    #[error("{0}")]
+    JsonToRowError(#[from] JsonToRowError),
+
     #[error("{0}")]
     CreateRuntimeError(#[from] CreateRuntimeError),
 
+    #[error("{0}")]
+    HttpClientError(#[from] HttpClientError),
+
     #[error("parse url failed: {0}")]
     UrlParseError(#[from] url::ParseError),
 
@@ -49,7 +54,18 @@ enum LogflareFdwError {
 
 impl From<LogflareFdwError> for ErrorReport {
     fn from(value: LogflareFdwError) -> Self {
-        ErrorReport::new(PgSqlErrorCode::ERRCODE_FDW_ERROR, format!("{value}"), "")
+        let msg = format!("{value}");
+        match value {
+            LogflareFdwError::FdwError(e) => e.into(),
+            LogflareFdwError::HttpClientError(e) => e.into(),
+            LogflareFdwError::RequestError(e) => request_error(&e, msg).into(),
+            LogflareFdwError::RequestMiddlewareError(e) => request_middleware_error(&e, msg).into(),
+            LogflareFdwError::OptionsError(_)
+            | LogflareFdwError::NoEqualParameter(_)
+            | LogflareFdwError::NoArrayParameter(_) => FdwError::InvalidOption(msg).into(),
+            LogflareFdwError::JsonToRowError(e) => FdwError::from(e).into(),
+            _ => ErrorReport::new(PgSqlErrorCode::ERRCODE_FDW_ERROR, msg, ""),
+        }
     }
 }
 
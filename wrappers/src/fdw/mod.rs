@@ -34,5 +34,27 @@ mod redis_fdw;
 #[cfg(feature = "cognito_fdw")]
 mod cognito_fdw;
 
+#[cfg(feature = "mqtt_fdw")]
+mod mqtt_fdw;
+
 #[cfg(feature = "wasm_fdw")]
 mod wasm_fdw;
+
+#[cfg(any(
+    feature = "stripe_fdw",
+    feature = "firebase_fdw",
+    feature = "airtable_fdw",
+    feature = "logflare_fdw",
+    feature = "auth0_fdw"
+))]
+mod request_error;
+
+#[cfg(any(
+    feature = "stripe_fdw",
+    feature = "firebase_fdw",
+    feature = "airtable_fdw",
+    feature = "logflare_fdw",
+    feature = "auth0_fdw",
+    feature = "wasm_fdw"
+))]
+mod http_client;
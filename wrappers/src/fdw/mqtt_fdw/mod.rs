@@ -0,0 +1,66 @@
+#![allow(clippy::module_inception)]
+mod mqtt_fdw;
+mod tests;
+
+use pgrx::pg_sys::panic::ErrorReport;
+use pgrx::prelude::PgSqlErrorCode;
+use rumqttc::{ClientError, ConnectionError};
+use thiserror::Error;
+
+use supabase_wrappers::prelude::{CreateRuntimeError, OptionsError};
+
+#[derive(Error, Debug)]
+enum MqttFdwError {
+    #[error("'{0}' scan mode is not supported")]
+    UnsupportedMode(String),
+
+    #[error("invalid QoS level '{0}', must be 0, 1 or 2")]
+    InvalidQos(String),
+
+    #[error("invalid option '{0}': {1}")]
+    InvalidOption(String, String),
+
+    #[error("column '{0}' name is not supported")]
+    UnsupportedColumnName(String),
+
+    #[error("column '{0}' data type is not supported")]
+    UnsupportedColumnType(String),
+
+    #[error("topic '{0}' cannot contain wildcards for publishing")]
+    WildcardTopic(String),
+
+    #[error("column 'payload' is required for publishing")]
+    MissingPayload,
+
+    #[error("timed out waiting for broker acknowledgement")]
+    PublishTimeout,
+
+    #[error("{0}")]
+    ClientError(#[from] ClientError),
+
+    #[error("{0}")]
+    ConnectionError(Box<ConnectionError>),
+
+    #[error("parse JSON payload failed: {0}")]
+    JsonError(#[from] serde_json::Error),
+
+    #[error("{0}")]
+    CreateRuntimeError(#[from] CreateRuntimeError),
+
+    #[error("{0}")]
+    OptionsError(#[from] OptionsError),
+}
+
+impl From<ConnectionError> for MqttFdwError {
+    fn from(value: ConnectionError) -> Self {
+        MqttFdwError::ConnectionError(Box::new(value))
+    }
+}
+
+impl From<MqttFdwError> for ErrorReport {
+    fn from(value: MqttFdwError) -> Self {
+        ErrorReport::new(PgSqlErrorCode::ERRCODE_FDW_ERROR, format!("{value}"), "")
+    }
+}
+
+type MqttFdwResult<T> = Result<T, MqttFdwError>;
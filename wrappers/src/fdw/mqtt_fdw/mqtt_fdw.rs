@@ -0,0 +1,401 @@
+use crate::stats;
+use pgrx::{datum::to_timestamp, pg_sys, JsonB, PgBuiltInOids};
+use rumqttc::{
+    AsyncClient, Event, EventLoop, MqttOptions, Outgoing, Packet, Publish, QoS, Transport,
+};
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::{timeout, Instant};
+
+use supabase_wrappers::prelude::*;
+
+use super::{MqttFdwError, MqttFdwResult};
+
+// parse QoS level from string
+fn parse_qos(s: &str) -> MqttFdwResult<QoS> {
+    match s {
+        "0" => Ok(QoS::AtMostOnce),
+        "1" => Ok(QoS::AtLeastOnce),
+        "2" => Ok(QoS::ExactlyOnce),
+        _ => Err(MqttFdwError::InvalidQos(s.to_owned())),
+    }
+}
+
+// parse a numeric option with default value
+fn parse_num_option(
+    options: &HashMap<String, String>,
+    name: &str,
+    default: u64,
+) -> MqttFdwResult<u64> {
+    options
+        .get(name)
+        .map(|v| {
+            v.parse::<u64>()
+                .map_err(|e| MqttFdwError::InvalidOption(name.to_owned(), e.to_string()))
+        })
+        .unwrap_or(Ok(default))
+}
+
+// check target columns to make sure match the spec
+fn check_target_columns(cols: &[Column]) -> MqttFdwResult<()> {
+    for col in cols {
+        let supported = match col.name.as_str() {
+            "topic" => col.type_oid == PgBuiltInOids::TEXTOID.value(),
+            "payload" => [
+                PgBuiltInOids::BYTEAOID.value(),
+                PgBuiltInOids::JSONBOID.value(),
+                PgBuiltInOids::TEXTOID.value(),
+            ]
+            .contains(&col.type_oid),
+            "qos" => col.type_oid == PgBuiltInOids::INT4OID.value(),
+            "retain" => col.type_oid == PgBuiltInOids::BOOLOID.value(),
+            "received_at" => col.type_oid == PgBuiltInOids::TIMESTAMPTZOID.value(),
+            _ => return Err(MqttFdwError::UnsupportedColumnName(col.name.clone())),
+        };
+        if !supported {
+            return Err(MqttFdwError::UnsupportedColumnType(col.name.clone()));
+        }
+    }
+    Ok(())
+}
+
+// a message received from broker
+struct Message {
+    publish: Publish,
+    received_at: f64,
+}
+
+#[wrappers_fdw(
+    version = "0.1.0",
+    author = "Supabase",
+    website = "https://github.com/supabase/wrappers/tree/main/wrappers/src/fdw/mqtt_fdw",
+    error_type = "MqttFdwError"
+)]
+pub(crate) struct MqttFdw {
+    rt: Runtime,
+    mqtt_opts: MqttOptions,
+    tgt_cols: Vec<Column>,
+    messages: VecDeque<Message>,
+
+    // client and event loop used for publishing
+    client: Option<(AsyncClient, EventLoop)>,
+    topic: Option<String>,
+    qos: QoS,
+    retain: bool,
+}
+
+impl MqttFdw {
+    const FDW_NAME: &'static str = "MqttFdw";
+    const CHANNEL_CAP: usize = 64;
+    const DEFAULT_WINDOW_MS: u64 = 1000;
+    const ACK_TIMEOUT: Duration = Duration::from_secs(10);
+
+    // subscribe to the topic filter and collect messages until the window
+    // elapsed or enough messages are received
+    fn collect_messages(
+        &mut self,
+        topic: &str,
+        qos: QoS,
+        retained_only: bool,
+        window: Duration,
+        max_messages: Option<usize>,
+    ) -> MqttFdwResult<()> {
+        let (client, mut eventloop) = AsyncClient::new(self.mqtt_opts.clone(), Self::CHANNEL_CAP);
+        let mut messages = VecDeque::new();
+
+        self.rt.block_on(async {
+            client.subscribe(topic, qos).await?;
+
+            let deadline = Instant::now() + window;
+            loop {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                let event = match timeout(remaining, eventloop.poll()).await {
+                    Ok(event) => event?,
+                    Err(_) => break,
+                };
+
+                if let Event::Incoming(Packet::Publish(publish)) = event {
+                    // retained messages are delivered right after subscribed,
+                    // the first live message means all retained messages are received
+                    if retained_only && !publish.retain {
+                        break;
+                    }
+
+                    let received_at = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs_f64();
+                    messages.push_back(Message {
+                        publish,
+                        received_at,
+                    });
+
+                    if max_messages.is_some_and(|max| messages.len() >= max) {
+                        break;
+                    }
+                }
+            }
+
+            // disconnect is best-effort, the connection will be dropped anyway
+            let _ = client.disconnect().await;
+
+            Ok::<(), MqttFdwError>(())
+        })?;
+
+        stats::inc_stats(Self::FDW_NAME, stats::Metric::RowsIn, messages.len() as i64);
+        stats::inc_stats(
+            Self::FDW_NAME,
+            stats::Metric::BytesIn,
+            messages
+                .iter()
+                .map(|m| m.publish.payload.len() as i64)
+                .sum(),
+        );
+
+        self.messages = messages;
+
+        Ok(())
+    }
+
+    fn payload_to_cell(&self, payload: &[u8], type_oid: pg_sys::Oid) -> MqttFdwResult<Cell> {
+        let cell = if type_oid == PgBuiltInOids::BYTEAOID.value() {
+            Cell::Bytea(payload.to_vec())
+        } else if type_oid == PgBuiltInOids::JSONBOID.value() {
+            Cell::Json(JsonB(serde_json::from_slice(payload)?))
+        } else {
+            Cell::String(String::from_utf8_lossy(payload).to_string())
+        };
+        Ok(cell)
+    }
+
+    fn cell_to_payload(cell: &Cell) -> Vec<u8> {
+        match cell {
+            Cell::Bytea(v) => v.clone(),
+            Cell::Json(v) => v.0.to_string().into_bytes(),
+            Cell::String(v) => v.clone().into_bytes(),
+            _ => cell.to_string().into_bytes(),
+        }
+    }
+}
+
+impl ForeignDataWrapper<MqttFdwError> for MqttFdw {
+    fn new(server: ForeignServer) -> MqttFdwResult<Self> {
+        let rt = create_async_runtime()?;
+
+        let host = require_option("host", &server.options)?;
+        let port = parse_num_option(&server.options, "port", 1883)?;
+        let client_id = server
+            .options
+            .get("client_id")
+            .cloned()
+            .unwrap_or_else(|| format!("wrappers-mqtt-fdw-{}", std::process::id()));
+
+        let mut mqtt_opts = MqttOptions::new(client_id, host, port as u16);
+        mqtt_opts.set_keep_alive(Duration::from_secs(30));
+
+        let cred_opts = server.credential_options(&["username", "password", "password_id"]);
+        if let Some(username) = cred_opts.get("username") {
+            let password = match cred_opts.get("password") {
+                Some(password) => password.to_owned(),
+                None => match cred_opts.get("password_id") {
+                    Some(password_id) => {
+                        get_secret(&server.options, password_id).unwrap_or_default()
+                    }
+                    None => String::default(),
+                },
+            };
+            mqtt_opts.set_credentials(username, password);
+        }
+
+        if server.options.get("tls").map(|v| v.as_str()) == Some("true") {
+            mqtt_opts.set_transport(Transport::tls_with_default_config());
+        }
+
+        stats::inc_stats(Self::FDW_NAME, stats::Metric::CreateTimes, 1);
+
+        Ok(MqttFdw {
+            rt,
+            mqtt_opts,
+            tgt_cols: Vec::new(),
+            messages: VecDeque::new(),
+            client: None,
+            topic: None,
+            qos: QoS::AtMostOnce,
+            retain: false,
+        })
+    }
+
+    fn capabilities() -> FdwCapabilities {
+        FdwCapabilities {
+            write: true,
+            limit_pushdown: true,
+            ..Default::default()
+        }
+    }
+
+    fn begin_scan(
+        &mut self,
+        _quals: &[Qual],
+        columns: &[Column],
+        _sorts: &[Sort],
+        limit: &Option<Limit>,
+        options: &HashMap<String, String>,
+    ) -> MqttFdwResult<()> {
+        check_target_columns(columns)?;
+
+        let topic = require_option("topic", options)?.to_string();
+        let qos = parse_qos(require_option_or("qos", options, "0"))?;
+        let mode = require_option_or("mode", options, "subscribe");
+        let window = parse_num_option(options, "window_ms", Self::DEFAULT_WINDOW_MS)?;
+
+        // stop collecting early if there is a limit or max_messages
+        let max_messages = options
+            .get("max_messages")
+            .map(|_| parse_num_option(options, "max_messages", 0))
+            .transpose()?
+            .map(|v| v as usize)
+            .or_else(|| limit.as_ref().map(|l| (l.count + l.offset) as usize));
+
+        let retained_only = match mode {
+            "subscribe" => false,
+            "retained" => true,
+            _ => return Err(MqttFdwError::UnsupportedMode(mode.to_owned())),
+        };
+
+        self.tgt_cols = columns.to_vec();
+        self.collect_messages(
+            &topic,
+            qos,
+            retained_only,
+            Duration::from_millis(window),
+            max_messages,
+        )
+    }
+
+    fn iter_scan(&mut self, row: &mut Row) -> MqttFdwResult<Option<()>> {
+        let Some(msg) = self.messages.pop_front() else {
+            return Ok(None);
+        };
+
+        for tgt_col in &self.tgt_cols {
+            let cell = match tgt_col.name.as_str() {
+                "topic" => Cell::String(msg.publish.topic.clone()),
+                "payload" => self.payload_to_cell(&msg.publish.payload, tgt_col.type_oid)?,
+                "qos" => Cell::I32(msg.publish.qos as i32),
+                "retain" => Cell::Bool(msg.publish.retain),
+                "received_at" => Cell::Timestamptz(to_timestamp(msg.received_at)),
+                _ => unreachable!(),
+            };
+            row.push(&tgt_col.name, Some(cell));
+        }
+
+        stats::inc_stats(Self::FDW_NAME, stats::Metric::RowsOut, 1);
+
+        Ok(Some(()))
+    }
+
+    fn re_scan(&mut self) -> MqttFdwResult<()> {
+        // messages are not replayable, rescan returns nothing
+        self.messages.clear();
+        Ok(())
+    }
+
+    fn end_scan(&mut self) -> MqttFdwResult<()> {
+        self.messages.clear();
+        Ok(())
+    }
+
+    fn begin_modify(&mut self, options: &HashMap<String, String>) -> MqttFdwResult<()> {
+        self.topic = options.get("topic").cloned();
+        self.qos = parse_qos(require_option_or("qos", options, "0"))?;
+        self.retain = options.get("retain").map(|v| v.as_str()) == Some("true");
+        self.client = Some(AsyncClient::new(self.mqtt_opts.clone(), Self::CHANNEL_CAP));
+        Ok(())
+    }
+
+    fn insert(&mut self, src: &Row) -> MqttFdwResult<()> {
+        let mut topic = self.topic.clone();
+        let mut qos = self.qos;
+        let mut retain = self.retain;
+        let mut payload = None;
+
+        for (col, cell) in src.iter() {
+            match (col.as_str(), cell) {
+                ("topic", Some(Cell::String(v))) => topic = Some(v.clone()),
+                ("payload", Some(cell)) => payload = Some(Self::cell_to_payload(cell)),
+                ("qos", Some(Cell::I32(v))) => qos = parse_qos(&v.to_string())?,
+                ("retain", Some(Cell::Bool(v))) => retain = *v,
+                _ => {}
+            }
+        }
+
+        let topic = topic.ok_or(OptionsError::OptionNameNotFound("topic".to_string()))?;
+        if topic.contains(['#', '+']) {
+            return Err(MqttFdwError::WildcardTopic(topic));
+        }
+        let payload = payload.ok_or(MqttFdwError::MissingPayload)?;
+        let payload_len = payload.len();
+
+        let Some((client, eventloop)) = &mut self.client else {
+            return Ok(());
+        };
+
+        self.rt.block_on(async {
+            client.publish(topic, qos, retain, payload).await?;
+
+            // drive the event loop until the message is delivered according
+            // to its QoS level
+            let wait = async {
+                loop {
+                    match (qos, eventloop.poll().await?) {
+                        (QoS::AtMostOnce, Event::Outgoing(Outgoing::Publish(_)))
+                        | (QoS::AtLeastOnce, Event::Incoming(Packet::PubAck(_)))
+                        | (QoS::ExactlyOnce, Event::Incoming(Packet::PubComp(_))) => {
+                            return Ok::<(), MqttFdwError>(())
+                        }
+                        _ => {}
+                    }
+                }
+            };
+            timeout(Self::ACK_TIMEOUT, wait)
+                .await
+                .map_err(|_| MqttFdwError::PublishTimeout)?
+        })?;
+
+        stats::inc_stats(Self::FDW_NAME, stats::Metric::RowsOut, 1);
+        stats::inc_stats(Self::FDW_NAME, stats::Metric::BytesOut, payload_len as i64);
+
+        Ok(())
+    }
+
+    fn end_modify(&mut self) -> MqttFdwResult<()> {
+        if let Some((client, mut eventloop)) = self.client.take() {
+            self.rt.block_on(async {
+                if client.disconnect().await.is_ok() {
+                    // flush the disconnect packet, ignore any error as we are closing
+                    let _ = timeout(Duration::from_secs(1), async {
+                        while let Ok(event) = eventloop.poll().await {
+                            if matches!(event, Event::Outgoing(Outgoing::Disconnect)) {
+                                break;
+                            }
+                        }
+                    })
+                    .await;
+                }
+            });
+        }
+        Ok(())
+    }
+
+    fn validator(options: Vec<Option<String>>, catalog: Option<pg_sys::Oid>) -> MqttFdwResult<()> {
+        if let Some(oid) = catalog {
+            if oid == FOREIGN_SERVER_RELATION_ID {
+                check_options_contain(&options, "host")?;
+            } else if oid == FOREIGN_TABLE_RELATION_ID {
+                check_options_contain(&options, "topic")?;
+            }
+        }
+
+        Ok(())
+    }
+}
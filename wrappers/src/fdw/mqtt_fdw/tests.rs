@@ -0,0 +1,137 @@
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::pg_test;
+    use pgrx::prelude::*;
+
+    #[pg_test]
+    fn mqtt_smoketest() {
+        Spi::connect(|mut c| {
+            c.update(
+                r#"CREATE FOREIGN DATA WRAPPER mqtt_wrapper
+                         HANDLER mqtt_fdw_handler VALIDATOR mqtt_fdw_validator"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"CREATE SERVER mqtt_server
+                         FOREIGN DATA WRAPPER mqtt_wrapper
+                         OPTIONS (
+                           host '127.0.0.1',
+                           port '1883'
+                         )"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"
+                  CREATE FOREIGN TABLE mqtt_sensors (
+                    topic text,
+                    payload jsonb,
+                    qos integer,
+                    retain boolean,
+                    received_at timestamptz
+                  )
+                    server mqtt_server
+                    options (
+                      topic 'wrappers/test/#',
+                      mode 'retained',
+                      qos '1',
+                      retain 'true',
+                      rowid_column 'topic'
+                    );
+             "#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"
+                  CREATE FOREIGN TABLE mqtt_raw (
+                    topic text,
+                    payload bytea
+                  )
+                    server mqtt_server
+                    options (
+                      topic 'wrappers/test/#',
+                      mode 'retained',
+                      rowid_column 'topic'
+                    );
+             "#,
+                None,
+                None,
+            )
+            .unwrap();
+
+            // publish retained messages
+            c.update(
+                r#"INSERT INTO mqtt_sensors (topic, payload)
+                   VALUES
+                     ('wrappers/test/temp', '{"value": 21.5}'),
+                     ('wrappers/test/humidity', '{"value": 48}')
+                "#,
+                None,
+                None,
+            )
+            .unwrap();
+
+            let results = c
+                .select("SELECT topic FROM mqtt_sensors ORDER BY topic", None, None)
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("topic").unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(
+                results,
+                vec!["wrappers/test/humidity", "wrappers/test/temp"]
+            );
+
+            let results = c
+                .select(
+                    "SELECT payload->>'value' as value FROM mqtt_sensors WHERE topic = 'wrappers/test/temp'",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("value").unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec!["21.5"]);
+
+            let results = c
+                .select(
+                    "SELECT bool_and(retain) as retain FROM mqtt_sensors",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<bool, _>("retain").unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec![true]);
+
+            let results = c
+                .select(
+                    "SELECT length(payload) as len FROM mqtt_raw WHERE topic = 'wrappers/test/humidity'",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<i32, _>("len").unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec![13]);
+        });
+
+        // publishing to a topic filter with wildcards is not allowed
+        let result = std::panic::catch_unwind(|| {
+            Spi::connect(|mut c| {
+                c.update(
+                    "INSERT INTO mqtt_raw (topic, payload) VALUES ('wrappers/#', 'x')",
+                    None,
+                    None,
+                )
+                .is_err()
+            })
+        });
+        assert!(result.is_err());
+    }
+}
@@ -4,9 +4,10 @@ mod tests;
 
 use pgrx::pg_sys::panic::ErrorReport;
 use pgrx::prelude::PgSqlErrorCode;
+use std::time::Duration;
 use thiserror::Error;
 
-use supabase_wrappers::prelude::{CreateRuntimeError, OptionsError};
+use supabase_wrappers::prelude::{CreateRuntimeError, FdwError, OptionsError};
 
 #[derive(Error, Debug)]
 enum MssqlFdwError {
@@ -30,11 +31,22 @@ enum MssqlFdwError {
 
     #[error("{0}")]
     IoError(#[from] std::io::Error),
+
+    #[error("connect to SQL Server timed out after {0:?}")]
+    ConnectTimeout(Duration),
+
+    #[error("query timed out after {0:?}")]
+    RequestTimeout(Duration),
 }
 
 impl From<MssqlFdwError> for ErrorReport {
     fn from(value: MssqlFdwError) -> Self {
-        ErrorReport::new(PgSqlErrorCode::ERRCODE_FDW_ERROR, format!("{value}"), "")
+        match value {
+            MssqlFdwError::ConnectTimeout(_) | MssqlFdwError::RequestTimeout(_) => {
+                FdwError::Transient(format!("{value}")).into()
+            }
+            _ => ErrorReport::new(PgSqlErrorCode::ERRCODE_FDW_ERROR, format!("{value}"), ""),
+        }
     }
 }
 
@@ -1,14 +1,14 @@
 use crate::stats;
-use num_traits::cast::ToPrimitive;
-use pgrx::{prelude::to_timestamp, PgBuiltInOids, PgOid};
+use pgrx::{pg_sys, prelude::to_timestamp, PgBuiltInOids, PgOid};
 use std::collections::HashMap;
 use tiberius::{
     numeric::Decimal,
     time::chrono::{NaiveDate, NaiveDateTime},
-    Client, Config,
+    Client, ColumnData, Config, Uuid,
 };
 use tokio::net::TcpStream;
-use tokio_util::compat::TokioAsyncWriteCompatExt;
+use tokio::time::timeout;
+use tokio_util::compat::{Compat, TokioAsyncWriteCompatExt};
 
 use supabase_wrappers::prelude::*;
 
@@ -18,7 +18,14 @@ use super::{MssqlFdwError, MssqlFdwResult};
 fn field_to_cell(src_row: &tiberius::Row, tgt_col: &Column) -> MssqlFdwResult<Option<Cell>> {
     let col_name = tgt_col.name.as_str();
 
-    let ret = match PgOid::from(tgt_col.type_oid) {
+    // column with 'coerce' option is fetched as text and converted by Wrappers
+    let type_oid = if tgt_col.coerce_to_text() {
+        pg_sys::TEXTOID
+    } else {
+        tgt_col.type_oid
+    };
+
+    let ret = match PgOid::from(type_oid) {
         PgOid::BuiltIn(PgBuiltInOids::BOOLOID) => {
             src_row.try_get::<bool, &str>(col_name)?.map(Cell::Bool)
         }
@@ -46,8 +53,7 @@ fn field_to_cell(src_row: &tiberius::Row, tgt_col: &Column) -> MssqlFdwResult<Op
         }
         PgOid::BuiltIn(PgBuiltInOids::NUMERICOID) => src_row
             .try_get::<Decimal, &str>(col_name)?
-            .and_then(|v| v.to_i128())
-            .map(pgrx::AnyNumeric::from)
+            .and_then(|v| pgrx::AnyNumeric::try_from(v.to_string().as_str()).ok())
             .map(Cell::Numeric),
         PgOid::BuiltIn(PgBuiltInOids::TEXTOID) => src_row
             .try_get::<&str, &str>(col_name)?
@@ -72,6 +78,12 @@ fn field_to_cell(src_row: &tiberius::Row, tgt_col: &Column) -> MssqlFdwResult<Op
                 Cell::Timestamptz(ts)
             })
         }
+        PgOid::BuiltIn(PgBuiltInOids::BYTEAOID) => src_row
+            .try_get::<&[u8], &str>(col_name)?
+            .map(|v| Cell::Bytea(v.to_vec())),
+        PgOid::BuiltIn(PgBuiltInOids::UUIDOID) => src_row
+            .try_get::<Uuid, &str>(col_name)?
+            .map(|v| Cell::Uuid(pgrx::Uuid::from_bytes(*v.as_bytes()))),
         _ => {
             return Err(MssqlFdwError::UnsupportedColumnType(tgt_col.name.clone()));
         }
@@ -80,16 +92,48 @@ fn field_to_cell(src_row: &tiberius::Row, tgt_col: &Column) -> MssqlFdwResult<Op
     Ok(ret)
 }
 
-struct MssqlCellFormatter {}
+// convert a numeric aggregate result to a cell, it will be converted to the
+// aggregate result type by Wrappers
+fn numeric_field_to_cell(data: &ColumnData) -> Option<Cell> {
+    match data {
+        ColumnData::U8(v) => v.map(|v| Cell::I16(v as i16)),
+        ColumnData::I16(v) => v.map(Cell::I16),
+        ColumnData::I32(v) => v.map(Cell::I32),
+        ColumnData::I64(v) => v.map(Cell::I64),
+        ColumnData::F32(v) => v.map(Cell::F32),
+        ColumnData::F64(v) => v.map(Cell::F64),
+        ColumnData::Numeric(v) => v
+            .and_then(|v| pgrx::AnyNumeric::try_from(v.to_string().as_str()).ok())
+            .map(Cell::Numeric),
+        _ => None,
+    }
+}
 
-impl CellFormatter for MssqlCellFormatter {
-    fn fmt_cell(&mut self, cell: &Cell) -> String {
-        match cell {
-            // format boolean type to 0 or 1
-            Cell::Bool(v) => format!("{}", *v as u8),
-            _ => format!("{}", cell),
-        }
+// check if values of a type are compared in the same way on SQL Server, string
+// is excluded because SQL Server collation is case-insensitive by default
+fn is_same_ordering(type_oid: pg_sys::Oid) -> bool {
+    matches!(
+        PgOid::from(type_oid),
+        PgOid::BuiltIn(
+            PgBuiltInOids::BOOLOID
+                | PgBuiltInOids::INT2OID
+                | PgBuiltInOids::INT4OID
+                | PgBuiltInOids::INT8OID
+                | PgBuiltInOids::FLOAT4OID
+                | PgBuiltInOids::FLOAT8OID
+                | PgBuiltInOids::NUMERICOID
+                | PgBuiltInOids::DATEOID
+                | PgBuiltInOids::TIMESTAMPOID
+        )
+    )
+}
+
+// check if a qual has the same semantics on SQL Server
+fn is_qual_handled(qual: &Qual) -> bool {
+    if qual.is_bool_expr() {
+        return qual.sub_quals.iter().all(is_qual_handled);
     }
+    qual.is_null_test() || is_same_ordering(qual.type_oid)
 }
 
 #[wrappers_fdw(
@@ -101,6 +145,8 @@ impl CellFormatter for MssqlCellFormatter {
 pub(crate) struct MssqlFdw {
     rt: Runtime,
     config: Config,
+    timeouts: Timeouts,
+    pool: ConnectionPool,
     table: String,
     tgt_cols: Vec<Column>,
     scan_result: Vec<tiberius::Row>,
@@ -110,8 +156,147 @@ pub(crate) struct MssqlFdw {
 impl MssqlFdw {
     const FDW_NAME: &'static str = "MssqlFdw";
 
-    fn deparse(
+    fn deparse_qual(qual: &Qual) -> String {
+        // boolean is formatted to 0 or 1 and string is quoted for SQL Server
+        let mut fmt = SqlCellFormatter::new(SqlDialect::SqlServer);
+
+        // deparse each sub-condition in boolean expression, e.g. "a = 1 or b = 2"
+        if qual.is_bool_expr() {
+            return qual.deparse_bool_expr(Self::deparse_qual);
+        }
+
+        // deparse pattern matching qual, e.g. "col ilike 'a\_%'" => "lower(col) like 'a[_]%'"
+        if let Some(cond) = qual.deparse_like(SqlDialect::SqlServer) {
+            return cond;
+        }
+
+        if let Value::Cell(cell) = &qual.value {
+            // deparse boolean test qual, e.g. "bool_col is true" => "bool_col = 1"
+            if let Cell::Bool(_) = cell {
+                if qual.operator == "is" {
+                    return format!("{} = {}", qual.field, fmt.fmt_cell(cell));
+                } else if qual.operator == "is not" {
+                    return format!("{} <> {}", qual.field, fmt.fmt_cell(cell));
+                }
+            }
+        }
+        qual.deparse_with_fmt(&mut fmt)
+    }
+
+    fn connect(&self) -> MssqlFdwResult<Client<Compat<TcpStream>>> {
+        let connect = async {
+            let tcp = TcpStream::connect(self.config.get_addr()).await?;
+            tcp.set_nodelay(true)?;
+            let client = Client::connect(self.config.clone(), tcp.compat_write()).await?;
+            Ok(client)
+        };
+        self.rt
+            .block_on(timeout(self.timeouts.connect, connect))
+            .map_err(|_| MssqlFdwError::ConnectTimeout(self.timeouts.connect))?
+    }
+
+    // get an idle client from the pool, or create a new one
+    fn create_client(&self) -> MssqlFdwResult<PooledConnection<Client<Compat<TcpStream>>>> {
+        self.pool.get(
+            || self.connect(),
+            |client| {
+                let ping = async { client.simple_query("select 1").await?.into_results().await };
+                matches!(
+                    self.rt.block_on(timeout(self.timeouts.request, ping)),
+                    Ok(Ok(_))
+                )
+            },
+        )
+    }
+
+    // run query on remote sql server and get its first result set
+    fn query(
         &self,
+        client: &mut Client<Compat<TcpStream>>,
+        sql: String,
+    ) -> MssqlFdwResult<Vec<tiberius::Row>> {
+        audit_request(
+            self.pool.server_oid(),
+            &sql,
+            || {
+                let query = async { client.simple_query(&sql).await?.into_first_result().await };
+                let rows = self
+                    .rt
+                    .block_on(timeout(self.timeouts.request, query))
+                    .map_err(|_| MssqlFdwError::RequestTimeout(self.timeouts.request))??;
+                Ok(rows)
+            },
+            |rows| Some(rows.len() as i64),
+        )
+    }
+
+    // deparse target column with its alias, column with 'coerce' option is cast
+    // to string on SQL Server so any remote type can be fetched
+    fn deparse_column(col_ref: &str, col: &Column, alias: &str) -> String {
+        let expr = if col.coerce_to_text() {
+            format!("cast({} as nvarchar(max))", col_ref)
+        } else {
+            col_ref.to_string()
+        };
+        if expr == alias {
+            expr
+        } else {
+            format!("{} as {}", expr, alias)
+        }
+    }
+
+    // deparse aggregate, count is deparsed to "count_big" so its result is bigint
+    fn deparse_aggregate(aggregate: &Aggregate) -> String {
+        match aggregate.kind {
+            AggregateKind::Count => {
+                format!("count_big({})", aggregate.field.as_deref().unwrap_or("*"))
+            }
+            _ => aggregate.deparse(),
+        }
+    }
+
+    // run query on remote sql server and store full result set locally
+    fn run_query(&mut self, sql: String) -> MssqlFdwResult<()> {
+        self.iter_idx = 0;
+
+        // create sql server client
+        let mut client = self.create_client()?;
+
+        self.scan_result = self.query(&mut client, sql)?;
+
+        stats::inc_stats(
+            Self::FDW_NAME,
+            stats::Metric::RowsIn,
+            self.scan_result.len() as i64,
+        );
+        stats::inc_stats(
+            Self::FDW_NAME,
+            stats::Metric::RowsOut,
+            self.scan_result.len() as i64,
+        );
+
+        Ok(())
+    }
+
+    // deparse a table in join to a sub query with its quals applied
+    fn deparse_join_table(table: &JoinTable) -> MssqlFdwResult<String> {
+        let mut sql = format!(
+            "select * from {} as _wrappers_tbl",
+            require_option("table", &table.options)?
+        );
+        if !table.quals.is_empty() {
+            let cond = deparse_quals(
+                &table.quals,
+                Self::deparse_qual,
+                &mut SqlCellFormatter::new(SqlDialect::SqlServer),
+            );
+            sql.push_str(&format!(" where {}", cond));
+        }
+        Ok(sql)
+    }
+
+    fn deparse(
+        table: &str,
         quals: &[Qual],
         columns: &[Column],
         sorts: &[Sort],
@@ -122,33 +307,19 @@ impl MssqlFdw {
         } else {
             columns
                 .iter()
-                .map(|c| c.name.clone())
+                .map(|c| Self::deparse_column(&c.name, c, &c.name))
                 .collect::<Vec<String>>()
                 .join(", ")
         };
 
-        let mut sql = format!("select {} from {} as _wrappers_tbl", tgts, &self.table);
+        let mut sql = format!("select {} from {} as _wrappers_tbl", tgts, table);
 
         if !quals.is_empty() {
-            let cond = quals
-                .iter()
-                .map(|q| {
-                    let oper = q.operator.as_str();
-                    let mut fmt = MssqlCellFormatter {};
-                    if let Value::Cell(cell) = &q.value {
-                        // deparse boolean test qual, e.g. "bool_col is true" => "bool_col = 1"
-                        if let Cell::Bool(_) = cell {
-                            if oper == "is" {
-                                return format!("{} = {}", q.field, fmt.fmt_cell(cell));
-                            } else if oper == "is not" {
-                                return format!("{} <> {}", q.field, fmt.fmt_cell(cell));
-                            }
-                        }
-                    }
-                    q.deparse_with_fmt(&mut fmt)
-                })
-                .collect::<Vec<String>>()
-                .join(" and ");
+            let cond = deparse_quals(
+                quals,
+                Self::deparse_qual,
+                &mut SqlCellFormatter::new(SqlDialect::SqlServer),
+            );
 
             if !cond.is_empty() {
                 sql.push_str(&format!(" where {}", cond));
@@ -160,7 +331,17 @@ impl MssqlFdw {
             let order_by = sorts
                 .iter()
                 .map(|sort| {
-                    let mut clause = sort.field.to_string();
+                    // SQL Server sorts nulls first in ascending order and last
+                    // in descending order, so sort them explicitly otherwise
+                    let mut clause = String::new();
+                    if sort.nulls_first == sort.reversed {
+                        let (nulls, others) = if sort.nulls_first { (0, 1) } else { (1, 0) };
+                        clause.push_str(&format!(
+                            "case when {} is null then {} else {} end, ",
+                            sort.field, nulls, others
+                        ));
+                    }
+                    clause.push_str(&sort.field);
                     if sort.reversed {
                         clause.push_str(" desc");
                     } else {
@@ -173,10 +354,8 @@ impl MssqlFdw {
             sql.push_str(&format!(" order by {}", order_by));
         }
 
-        // push down limits
-        // Note: Postgres will take limit and offset locally after reading rows
-        // from remote, so we calculate the real limit and only use it without
-        // pushing down offset.
+        // push down limits, offset is only set if the limit is accepted by
+        // plan_limit, otherwise it is applied locally by Postgres
         if let Some(limit) = limit {
             if sorts.is_empty() {
                 return Err(MssqlFdwError::SyntaxError(
@@ -184,10 +363,9 @@ impl MssqlFdw {
                 ));
             }
 
-            let real_limit = limit.offset + limit.count;
             sql.push_str(&format!(
-                " offset 0 rows fetch next {} rows only",
-                real_limit
+                " offset {} rows fetch next {} rows only",
+                limit.offset, limit.count
             ));
         }
 
@@ -198,20 +376,24 @@ impl MssqlFdw {
 impl ForeignDataWrapper<MssqlFdwError> for MssqlFdw {
     fn new(server: ForeignServer) -> MssqlFdwResult<Self> {
         let rt = create_async_runtime()?;
-        let conn_str = match server.options.get("conn_string") {
+        let cred_opts = server.credential_options(&["conn_string", "conn_string_id"]);
+        let conn_str = match cred_opts.get("conn_string") {
             Some(conn_str) => conn_str.to_owned(),
             None => {
-                let conn_str_id = require_option("conn_string_id", &server.options)?;
-                get_vault_secret(conn_str_id).unwrap_or_default()
+                let conn_str_id = require_option("conn_string_id", cred_opts)?;
+                get_secret(&server.options, conn_str_id).unwrap_or_default()
             }
         };
         let config = Config::from_ado_string(&conn_str)?;
+        let timeouts = Timeouts::from_options(&server.options)?;
 
         stats::inc_stats(Self::FDW_NAME, stats::Metric::CreateTimes, 1);
 
         Ok(MssqlFdw {
             rt,
             config,
+            timeouts,
+            pool: ConnectionPool::from_server(&server)?,
             table: String::default(),
             tgt_cols: Vec::new(),
             scan_result: Vec::new(),
@@ -219,6 +401,135 @@ impl ForeignDataWrapper<MssqlFdwError> for MssqlFdw {
         })
     }
 
+    fn capabilities() -> FdwCapabilities {
+        FdwCapabilities {
+            qual_pushdown: true,
+            sort_pushdown: true,
+            limit_pushdown: true,
+            aggregate_pushdown: true,
+            join_pushdown: true,
+            ..Default::default()
+        }
+    }
+
+    fn bool_expr_tree() -> bool {
+        true
+    }
+
+    fn parameterized_scan() -> bool {
+        true
+    }
+
+    fn handled_quals(
+        &mut self,
+        quals: &[Qual],
+        _options: &HashMap<String, String>,
+    ) -> MssqlFdwResult<Vec<bool>> {
+        Ok(quals.iter().map(is_qual_handled).collect())
+    }
+
+    fn plan_limit(
+        &mut self,
+        _quals: &[Qual],
+        columns: &[Column],
+        sorts: &[Sort],
+        _limit: &Limit,
+        _options: &HashMap<String, String>,
+    ) -> MssqlFdwResult<bool> {
+        // offset needs order by in SQL Server, and the sort columns must be
+        // ordered in the same way as in Postgres
+        Ok(!sorts.is_empty()
+            && sorts.iter().all(|sort| {
+                sort.collate.is_none()
+                    && columns
+                        .iter()
+                        .any(|col| col.num == sort.field_no && is_same_ordering(col.type_oid))
+            }))
+    }
+
+    fn plan_aggregate(
+        &mut self,
+        plan: &AggregatePlan,
+        _quals: &[Qual],
+        _options: &HashMap<String, String>,
+    ) -> MssqlFdwResult<bool> {
+        // grouping and min/max must compare values in the same way as Postgres
+        let group_ok = plan
+            .group_by
+            .iter()
+            .all(|col| is_same_ordering(col.type_oid));
+        let aggs_ok = plan.aggregates.iter().all(|agg| match agg.kind {
+            AggregateKind::Min | AggregateKind::Max => is_same_ordering(agg.type_oid),
+            AggregateKind::Count | AggregateKind::Sum => true,
+        });
+        Ok(group_ok && aggs_ok)
+    }
+
+    fn scan_aggregate(
+        &mut self,
+        plan: &AggregatePlan,
+        quals: &[Qual],
+        options: &HashMap<String, String>,
+    ) -> MssqlFdwResult<Vec<Row>> {
+        self.table = require_option("table", options)?.to_string();
+
+        // compile aggregate query to run on remote
+        let tgts =
+            plan.group_by
+                .iter()
+                .map(|col| col.name.clone())
+                .chain(
+                    plan.aggregates.iter().enumerate().map(|(idx, agg)| {
+                        format!("{} as _agg_{}", Self::deparse_aggregate(agg), idx)
+                    }),
+                )
+                .collect::<Vec<String>>()
+                .join(", ");
+        let mut sql = format!("select {} from {} as _wrappers_tbl", tgts, &self.table);
+        if !quals.is_empty() {
+            let cond = deparse_quals(
+                quals,
+                Self::deparse_qual,
+                &mut SqlCellFormatter::new(SqlDialect::SqlServer),
+            );
+            sql.push_str(&format!(" where {}", cond));
+        }
+        if let Some(group_by) = plan.deparse_group_by() {
+            sql.push_str(&format!(" {}", group_by));
+        }
+
+        let mut client = self.create_client()?;
+        let result = self.query(&mut client, sql)?;
+
+        let mut rows = Vec::with_capacity(result.len());
+        for src_row in result.iter() {
+            let mut row = Row::new();
+            for col in plan.group_by.iter() {
+                row.push(&col.name, field_to_cell(src_row, col)?);
+            }
+            let data = src_row.cells().skip(plan.group_by.len());
+            for (idx, (agg, (_, data))) in plan.aggregates.iter().zip(data).enumerate() {
+                let col = Column {
+                    name: format!("_agg_{}", idx),
+                    num: plan.group_by.len() + idx + 1,
+                    type_oid: agg.type_oid,
+                    ..Default::default()
+                };
+                let cell = match agg.kind {
+                    AggregateKind::Min | AggregateKind::Max => field_to_cell(src_row, &col)?,
+                    AggregateKind::Count | AggregateKind::Sum => numeric_field_to_cell(data),
+                };
+                row.push(&col.name, cell);
+            }
+            rows.push(row);
+        }
+
+        stats::inc_stats(Self::FDW_NAME, stats::Metric::RowsIn, rows.len() as i64);
+        stats::inc_stats(Self::FDW_NAME, stats::Metric::RowsOut, rows.len() as i64);
+
+        Ok(rows)
+    }
+
     fn begin_scan(
         &mut self,
         quals: &[Qual],
@@ -230,39 +541,76 @@ impl ForeignDataWrapper<MssqlFdwError> for MssqlFdw {
         self.table = require_option("table", options)?.to_string();
         self.tgt_cols = columns.to_vec();
 
-        self.iter_idx = 0;
+        // compile sql query to run on remote
+        let sql = Self::deparse(&self.table, quals, columns, sorts, limit)?;
 
-        // create sql server client
-        let tcp = self
-            .rt
-            .block_on(TcpStream::connect(self.config.get_addr()))?;
-        tcp.set_nodelay(true)?;
-        let mut client = self
-            .rt
-            .block_on(Client::connect(self.config.clone(), tcp.compat_write()))?;
+        self.run_query(sql)
+    }
 
-        // compile sql query to run on remote
-        let sql = self.deparse(quals, columns, sorts, limit)?;
+    fn explain(
+        &self,
+        quals: &[Qual],
+        columns: &[Column],
+        sorts: &[Sort],
+        limit: &Option<Limit>,
+        options: &HashMap<String, String>,
+    ) -> MssqlFdwResult<Vec<(String, String)>> {
+        let table = require_option("table", options)?;
+        let sql = Self::deparse(table, quals, columns, sorts, limit)?;
+        Ok(vec![("Remote SQL".to_string(), sql)])
+    }
+
+    fn plan_join(&mut self, join: &Join) -> MssqlFdwResult<bool> {
+        // join conditions must compare values in the same way as Postgres
+        Ok(join
+            .conds
+            .iter()
+            .all(|cond| is_same_ordering(cond.type_oid)))
+    }
 
-        // run query on remote sql server and store full result set locally
-        self.scan_result = self.rt.block_on(
-            self.rt
-                .block_on(client.simple_query(sql))?
-                .into_first_result(),
-        )?;
+    fn begin_join_scan(&mut self, join: &Join) -> MssqlFdwResult<()> {
+        // columns are aliased as they may have the same name in both tables
+        let mut tgts = Vec::new();
+        self.tgt_cols.clear();
+        for (alias, table) in [
+            ("_wrappers_outer", &join.outer),
+            ("_wrappers_inner", &join.inner),
+        ] {
+            for col in table.columns.iter() {
+                let name = format!("_wrappers_c{}", self.tgt_cols.len());
+                let col_ref = format!("{}.{}", alias, col.name);
+                tgts.push(Self::deparse_column(&col_ref, col, &name));
+                self.tgt_cols.push(Column {
+                    name,
+                    ..col.clone()
+                });
+            }
+        }
+        if tgts.is_empty() {
+            tgts.push("1 as _wrappers_c".to_string());
+        }
 
-        stats::inc_stats(
-            Self::FDW_NAME,
-            stats::Metric::RowsIn,
-            self.scan_result.len() as i64,
-        );
-        stats::inc_stats(
-            Self::FDW_NAME,
-            stats::Metric::RowsOut,
-            self.scan_result.len() as i64,
+        let conds = join
+            .conds
+            .iter()
+            .map(|cond| {
+                format!(
+                    "_wrappers_outer.{} = _wrappers_inner.{}",
+                    cond.outer_field, cond.inner_field
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(" and ");
+        let sql = format!(
+            "select {} from ({}) as _wrappers_outer {} ({}) as _wrappers_inner on {}",
+            tgts.join(", "),
+            Self::deparse_join_table(&join.outer)?,
+            join.kind.deparse(),
+            Self::deparse_join_table(&join.inner)?,
+            conds
         );
 
-        Ok(())
+        self.run_query(sql)
     }
 
     fn iter_scan(&mut self, row: &mut Row) -> MssqlFdwResult<Option<()>> {
@@ -293,4 +641,15 @@ impl ForeignDataWrapper<MssqlFdwError> for MssqlFdw {
         self.scan_result.clear();
         Ok(())
     }
+
+    fn health_check(&mut self) -> MssqlFdwResult<Option<String>> {
+        let mut client = self.create_client()?;
+        let rows = self.query(&mut client, "select @@version".to_string())?;
+        // the version string has build and OS info in the following lines
+        Ok(rows
+            .first()
+            .and_then(|row| row.get::<&str, _>(0))
+            .and_then(|version| version.lines().next())
+            .map(|version| version.trim().to_owned()))
+    }
 }
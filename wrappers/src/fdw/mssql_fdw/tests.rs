@@ -57,6 +57,26 @@ mod tests {
         })
         .expect("insert test data");
 
+        rt.block_on(async {
+            client
+                .execute(
+                    r#"
+                    CREATE TABLE devices (
+                        id uniqueidentifier,
+                        name varchar(30),
+                        data varbinary(16),
+                        price numeric(12, 4),
+                        spec xml
+                    );
+                    INSERT INTO devices(id, name, data, price, spec) VALUES ('6f9619ff-8b86-d011-b42d-00c04fc964ff', 'foo', 0xDEADBEEF, 1234.5678, '<spec><size>10</size></spec>');
+                    INSERT INTO devices(id, name, data, price, spec) VALUES ('a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11', 'bar', NULL, NULL, NULL);
+                    "#,
+                    &[],
+                )
+                .await
+        })
+        .expect("create uuid test table in SQL Server");
+
         Spi::connect(|mut c| {
             c.update(
                 r#"CREATE FOREIGN DATA WRAPPER mssql_wrapper
@@ -108,6 +128,158 @@ mod tests {
             )
             .unwrap();
 
+            c.update(
+                r#"
+                  CREATE FOREIGN TABLE mssql_devices (
+                    id uuid,
+                    name text,
+                    data bytea,
+                    price numeric
+                  )
+                  SERVER mssql_server
+                  OPTIONS (
+                    table 'devices'
+                  )
+             "#,
+                None,
+                None,
+            )
+            .unwrap();
+
+            // enum and domain columns
+            c.update(
+                "CREATE TYPE user_name AS ENUM ('foo', 'bar', 'baz')",
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                "CREATE DOMAIN user_id AS bigint CHECK (VALUE > 0)",
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"
+                  CREATE FOREIGN TABLE mssql_users_enum (
+                    id user_id,
+                    name user_name
+                  )
+                  SERVER mssql_server
+                  OPTIONS (
+                    table 'users'
+                  )
+             "#,
+                None,
+                None,
+            )
+            .unwrap();
+            let results = c
+                .select(
+                    "SELECT id::bigint AS id, name::text AS name FROM mssql_users_enum WHERE name = 'bar'::user_name",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .map(|r| {
+                    (
+                        r.get_by_name::<i64, _>("id").unwrap().unwrap(),
+                        r.get_by_name::<&str, _>("name").unwrap().unwrap(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec![(43, "bar")]);
+
+            // unsupported remote types are fetched as text by 'coerce' option
+            c.update(
+                r#"
+                  CREATE FOREIGN TABLE mssql_devices_coerced (
+                    name jsonb OPTIONS (coerce 'text'),
+                    price text OPTIONS (coerce 'text'),
+                    spec text OPTIONS (coerce 'text')
+                  )
+                  SERVER mssql_server
+                  OPTIONS (
+                    table 'devices'
+                  )
+             "#,
+                None,
+                None,
+            )
+            .unwrap();
+            let mut results = c
+                .select(
+                    "SELECT name #>> '{}' AS name, price, spec FROM mssql_devices_coerced",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .map(|r| {
+                    (
+                        r.get_by_name::<&str, _>("name").unwrap().unwrap(),
+                        r.get_by_name::<&str, _>("price").unwrap(),
+                        r.get_by_name::<&str, _>("spec").unwrap(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            results.sort();
+            assert_eq!(
+                results,
+                vec![
+                    ("bar", None, None),
+                    (
+                        "foo",
+                        Some("1234.5678"),
+                        Some("<spec><size>10</size></spec>")
+                    )
+                ]
+            );
+
+            let results = c
+                .select(
+                    "SELECT id::text FROM mssql_devices WHERE name = 'foo'",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("id").unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec!["6f9619ff-8b86-d011-b42d-00c04fc964ff"]);
+
+            let results = c
+                .select(
+                    "SELECT data FROM mssql_devices WHERE name = 'foo'",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<Vec<u8>, _>("data").unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec![vec![0xde, 0xad, 0xbe, 0xef]]);
+
+            // numeric value keeps its fraction digits
+            let results = c
+                .select(
+                    "SELECT price::text AS price FROM mssql_devices WHERE name = 'foo'",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("price").unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec!["1234.5678"]);
+
+            let results = c
+                .select(
+                    "SELECT name FROM mssql_devices WHERE id = 'a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11'",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("name").unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec!["bar"]);
+
             let results = c
                 .select("SELECT name FROM mssql_users WHERE id = 42", None, None)
                 .unwrap()
@@ -133,6 +305,31 @@ mod tests {
                 .collect::<Vec<_>>();
             assert_eq!(results, vec!["bar", "baz"]);
 
+            // limit and offset are applied remotely without local Limit node
+            let explain = c
+                .select(
+                    "EXPLAIN SELECT name FROM mssql_users ORDER BY id LIMIT 2 OFFSET 1",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("QUERY PLAN").unwrap())
+                .collect::<Vec<_>>();
+            assert!(!explain
+                .iter()
+                .any(|line| line.trim_start().starts_with("Limit")));
+
+            let results = c
+                .select(
+                    "SELECT name FROM mssql_users ORDER BY id DESC LIMIT 1 OFFSET 1",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("name").unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec!["bar"]);
+
             let results = c
                 .select(
                     "SELECT name FROM mssql_users WHERE name like 'ba%' ORDER BY id",
@@ -155,6 +352,61 @@ mod tests {
                 .collect::<Vec<_>>();
             assert_eq!(results, vec!["foo"]);
 
+            let results = c
+                .select(
+                    "SELECT name FROM mssql_users WHERE name ilike 'BA%' ORDER BY id",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("name").unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec!["bar", "baz"]);
+
+            let results = c
+                .select(
+                    r"SELECT name FROM mssql_users WHERE name like 'ba\_'",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("name").unwrap())
+                .collect::<Vec<_>>();
+            assert!(results.is_empty());
+
+            let results = c
+                .select(
+                    "SELECT name FROM mssql_users WHERE dt is not null ORDER BY id",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("name").unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec!["foo", "bar", "baz"]);
+
+            let results = c
+                .select(
+                    "SELECT name FROM mssql_users WHERE name is null",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("name").unwrap())
+                .collect::<Vec<_>>();
+            assert!(results.is_empty());
+
+            let results = c
+                .select(
+                    "SELECT name FROM mssql_users WHERE (id = 43 and is_admin is true) or (id = 44 and name = 'baz') ORDER BY id",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("name").unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec!["bar", "baz"]);
+
             let results = c
                 .select(
                     "SELECT name FROM mssql_users_cust_sql ORDER BY id",
@@ -176,6 +428,222 @@ mod tests {
                 .filter_map(|r| r.get_by_name::<&str, _>("name").unwrap())
                 .collect::<Vec<_>>();
             assert_eq!(results, vec!["bar"]);
+
+            let results = c
+                .select(
+                    "SELECT name FROM mssql_users WHERE id = 42 or is_admin is true ORDER BY id",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("name").unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec!["foo", "bar"]);
+
+            let results = c
+                .select(
+                    "SELECT name FROM mssql_users WHERE id in (42, 44) ORDER BY id",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("name").unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec!["foo", "baz"]);
+
+            let results = c
+                .select(
+                    "SELECT name FROM mssql_users WHERE name = 'o''brien'",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("name").unwrap())
+                .collect::<Vec<_>>();
+            assert!(results.is_empty());
+
+            let results = c
+                .select(
+                    "SELECT name FROM mssql_users WHERE id between symmetric 44 and 43 ORDER BY id",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("name").unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec!["bar", "baz"]);
+
+            let results = c
+                .select("SELECT name FROM mssql_users WHERE 43 > id", None, None)
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("name").unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec!["foo"]);
+
+            // generic plan keeps the array parameter in qual
+            c.update("SET plan_cache_mode = force_generic_plan", None, None)
+                .unwrap();
+            c.update(
+                "PREPARE users_by_ids(bigint[]) AS SELECT name FROM mssql_users WHERE id = any($1) ORDER BY id",
+                None,
+                None,
+            )
+            .unwrap();
+            let results = c
+                .select("EXECUTE users_by_ids('{42, 44}')", None, None)
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("name").unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec!["foo", "baz"]);
+            c.update("DEALLOCATE users_by_ids", None, None).unwrap();
+            c.update("RESET plan_cache_mode", None, None).unwrap();
+
+            // force nested loop join so the join condition is pushed down
+            // as a parameterized scan
+            c.update("CREATE TEMP TABLE local_ids (id bigint)", None, None)
+                .unwrap();
+            c.update("INSERT INTO local_ids VALUES (42), (44)", None, None)
+                .unwrap();
+            c.update("SET enable_hashjoin = off", None, None).unwrap();
+            c.update("SET enable_mergejoin = off", None, None).unwrap();
+            c.update("SET enable_material = off", None, None).unwrap();
+            let results = c
+                .select(
+                    "SELECT u.name FROM local_ids l JOIN mssql_users u ON u.id = l.id ORDER BY l.id",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("name").unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec!["foo", "baz"]);
+
+            // rows are replayed from rescan buffer in nested loop join
+            c.update(
+                "ALTER FOREIGN TABLE mssql_users_cust_sql OPTIONS (ADD rescan_buffer '10')",
+                None,
+                None,
+            )
+            .unwrap();
+            let results = c
+                .select(
+                    "SELECT s.name FROM local_ids l CROSS JOIN mssql_users_cust_sql s ORDER BY l.id, s.id",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("name").unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec!["foo", "bar", "foo", "bar"]);
+            c.update(
+                "ALTER FOREIGN TABLE mssql_users_cust_sql OPTIONS (DROP rescan_buffer)",
+                None,
+                None,
+            )
+            .unwrap();
+            c.update("RESET enable_hashjoin", None, None).unwrap();
+            c.update("RESET enable_mergejoin", None, None).unwrap();
+            c.update("RESET enable_material", None, None).unwrap();
+
+            let results = c
+                .select(
+                    "SELECT name FROM mssql_users WHERE name not in ('foo', 'baz')",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("name").unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec!["bar"]);
+
+            // remote query is shown in verbose explain
+            let explain = c
+                .select(
+                    "EXPLAIN VERBOSE SELECT name FROM mssql_users WHERE id = 42",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("QUERY PLAN").unwrap())
+                .collect::<Vec<_>>()
+                .join("\n");
+            assert!(explain.contains("Remote SQL: select"));
+            assert!(explain.contains("where id = 42"));
+
+            // simple aggregates are pushed down when all quals are handled
+            let explain = c
+                .select(
+                    "EXPLAIN SELECT count(*), sum(id), min(name), max(id) FROM mssql_users WHERE id > 42",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("QUERY PLAN").unwrap())
+                .collect::<Vec<_>>()
+                .join("\n");
+            assert!(explain.contains("aggregates"));
+            let row = c
+                .select(
+                    "SELECT count(*), sum(id), min(name), max(id) FROM mssql_users WHERE id > 42",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .first();
+            assert_eq!(row.get::<i64>(1).unwrap(), Some(2));
+            assert_eq!(
+                row.get::<AnyNumeric>(2).unwrap(),
+                Some(AnyNumeric::from(87))
+            );
+            assert_eq!(row.get::<&str>(3).unwrap(), Some("bar"));
+            assert_eq!(row.get::<i64>(4).unwrap(), Some(44));
+
+            let results = c
+                .select(
+                    "SELECT is_admin, count(*) AS cnt FROM mssql_users GROUP BY is_admin ORDER BY is_admin",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .map(|r| {
+                    (
+                        r.get_by_name::<bool, _>("is_admin").unwrap(),
+                        r.get_by_name::<i64, _>("cnt").unwrap(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec![(Some(false), Some(2)), (Some(true), Some(1))]);
+        });
+
+        // join of foreign tables on the same server is pushed down
+        Spi::connect(|c| {
+            let sql = "SELECT u.name, s.name AS sname FROM mssql_users u \
+                LEFT JOIN mssql_users_cust_sql s ON s.id = u.id ORDER BY u.id";
+            let explain = c
+                .select(&format!("EXPLAIN {}", sql), None, None)
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("QUERY PLAN").unwrap())
+                .collect::<Vec<_>>()
+                .join("\n");
+            assert!(explain.contains("join = Left"));
+            let results = c
+                .select(sql, None, None)
+                .unwrap()
+                .map(|r| {
+                    (
+                        r.get_by_name::<&str, _>("name").unwrap(),
+                        r.get_by_name::<&str, _>("sname").unwrap(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(
+                results,
+                vec![
+                    (Some("foo"), Some("foo")),
+                    (Some("bar"), Some("bar")),
+                    (Some("baz"), None)
+                ]
+            );
         });
 
         let result = std::panic::catch_unwind(|| {
@@ -1,6 +1,6 @@
 use crate::stats;
 use pgrx::{JsonB, PgBuiltInOids};
-use redis::{Client, Commands, Connection, TlsCertificates};
+use redis::{Client, Commands, Connection, ConnectionLike, InfoDict, TlsCertificates};
 use serde_json::json;
 use serde_json::value::Value as JsonValue;
 use std::collections::HashMap;
@@ -48,7 +48,9 @@ fn check_target_columns(
 )]
 pub(crate) struct RedisFdw {
     client: Client,
-    conn: Option<Connection>,
+    timeouts: Timeouts,
+    pool: ConnectionPool,
+    conn: Option<PooledConnection<Connection>>,
     src_type: String,
     src_key: String,
     tgt_cols: Vec<Column>,
@@ -87,11 +89,23 @@ impl RedisFdw {
             if self.scan_result.is_empty() {
                 let start = self.iter_idx;
                 let stop = self.iter_idx + Self::BUF_SIZE - 1;
-                self.scan_result = if self.src_type == "list" {
-                    conn.lrange(&self.src_key, start, stop)?
+                let cmd = if self.src_type == "list" {
+                    "LRANGE"
                 } else {
-                    conn.zrange(&self.src_key, start, stop)?
+                    "ZRANGE"
                 };
+                self.scan_result = audit_request(
+                    self.pool.server_oid(),
+                    &format!("{} {} {} {}", cmd, self.src_key, start, stop),
+                    || {
+                        if self.src_type == "list" {
+                            conn.lrange(&self.src_key, start, stop)
+                        } else {
+                            conn.zrange(&self.src_key, start, stop)
+                        }
+                    },
+                    |result: &Vec<String>| Some(result.len() as i64),
+                )?;
                 if self.scan_result.is_empty() {
                     return Ok(None);
                 }
@@ -152,8 +166,19 @@ impl RedisFdw {
     fn fetch_row_stream(&mut self) -> RedisFdwResult<Option<Row>> {
         if let Some(ref mut conn) = &mut self.conn {
             if self.iter_idx as usize >= self.scan_result_stream.len() {
-                self.scan_result_stream =
-                    conn.xrange_count(&self.src_key, &self.iter_idx_stream, "+", Self::BUF_SIZE)?;
+                self.scan_result_stream = audit_request(
+                    self.pool.server_oid(),
+                    &format!(
+                        "XRANGE {} {} + COUNT {}",
+                        self.src_key,
+                        self.iter_idx_stream,
+                        Self::BUF_SIZE
+                    ),
+                    || conn.xrange_count(&self.src_key, &self.iter_idx_stream, "+", Self::BUF_SIZE),
+                    |result: &Vec<HashMap<String, HashMap<String, String>>>| {
+                        Some(result.len() as i64)
+                    },
+                )?;
                 if self.scan_result_stream.is_empty() {
                     return Ok(None);
                 }
@@ -239,13 +264,31 @@ impl RedisFdw {
     }
 }
 
+impl RedisFdw {
+    // get an idle connection from the pool, or create a new one
+    fn connect(&self) -> RedisFdwResult<PooledConnection<Connection>> {
+        self.pool.get(
+            || -> RedisFdwResult<Connection> {
+                let conn = self
+                    .client
+                    .get_connection_with_timeout(self.timeouts.connect)?;
+                conn.set_read_timeout(Some(self.timeouts.request))?;
+                conn.set_write_timeout(Some(self.timeouts.request))?;
+                Ok(conn)
+            },
+            |conn| conn.check_connection(),
+        )
+    }
+}
+
 impl ForeignDataWrapper<RedisFdwError> for RedisFdw {
     fn new(server: ForeignServer) -> RedisFdwResult<Self> {
-        let conn_url = match server.options.get("conn_url") {
+        let cred_opts = server.credential_options(&["conn_url", "conn_url_id"]);
+        let conn_url = match cred_opts.get("conn_url") {
             Some(url) => url.to_owned(),
             None => {
-                let conn_url_id = require_option("conn_url_id", &server.options)?;
-                get_vault_secret(conn_url_id).unwrap_or_default()
+                let conn_url_id = require_option("conn_url_id", cred_opts)?;
+                get_secret(&server.options, conn_url_id).unwrap_or_default()
             }
         };
 
@@ -260,11 +303,14 @@ impl ForeignDataWrapper<RedisFdwError> for RedisFdw {
         } else {
             Client::open(conn_url)
         }?;
+        let timeouts = Timeouts::from_options(&server.options)?;
 
         stats::inc_stats(Self::FDW_NAME, stats::Metric::CreateTimes, 1);
 
         Ok(RedisFdw {
             client,
+            timeouts,
+            pool: ConnectionPool::from_server(&server)?,
             conn: None,
             src_type: String::default(),
             src_key: String::default(),
@@ -287,7 +333,9 @@ impl ForeignDataWrapper<RedisFdwError> for RedisFdw {
         let src_type = require_option("src_type", options)?.to_string();
         let src_key = require_option("src_key", options)?.to_string();
 
-        let mut conn = self.client.get_connection()?;
+        // return the current connection first, so it can be reused
+        self.conn.take();
+        let mut conn = self.connect()?;
 
         self.reset();
 
@@ -307,7 +355,15 @@ impl ForeignDataWrapper<RedisFdwError> for RedisFdw {
                     &[PgBuiltInOids::TEXTOID],
                     src_type.as_str(),
                 )?;
-                self.scan_result = conn.sscan(&src_key)?.collect::<Vec<String>>();
+                self.scan_result = audit_request(
+                    self.pool.server_oid(),
+                    &format!("SSCAN {}", src_key),
+                    || {
+                        conn.sscan(&src_key)
+                            .map(|iter| iter.collect::<Vec<String>>())
+                    },
+                    |result| Some(result.len() as i64),
+                )?;
                 stats::inc_stats(Self::FDW_NAME, stats::Metric::RowsIn, 1_i64);
                 stats::inc_stats(
                     Self::FDW_NAME,
@@ -322,7 +378,12 @@ impl ForeignDataWrapper<RedisFdwError> for RedisFdw {
                     &[PgBuiltInOids::TEXTOID, PgBuiltInOids::TEXTOID],
                     src_type.as_str(),
                 )?;
-                self.scan_result = conn.hgetall(&src_key)?;
+                self.scan_result = audit_request(
+                    self.pool.server_oid(),
+                    &format!("HGETALL {}", src_key),
+                    || conn.hgetall(&src_key),
+                    |result: &Vec<String>| Some(result.len() as i64 / 2),
+                )?;
                 stats::inc_stats(Self::FDW_NAME, stats::Metric::RowsIn, 1_i64);
                 stats::inc_stats(
                     Self::FDW_NAME,
@@ -345,7 +406,15 @@ impl ForeignDataWrapper<RedisFdwError> for RedisFdw {
                     &[PgBuiltInOids::TEXTOID, PgBuiltInOids::JSONBOID],
                     src_type.as_str(),
                 )?;
-                self.scan_result = conn.scan_match(&src_key)?.collect::<Vec<String>>();
+                self.scan_result = audit_request(
+                    self.pool.server_oid(),
+                    &format!("SCAN MATCH {}", src_key),
+                    || {
+                        conn.scan_match(&src_key)
+                            .map(|iter| iter.collect::<Vec<String>>())
+                    },
+                    |result| Some(result.len() as i64),
+                )?;
                 stats::inc_stats(Self::FDW_NAME, stats::Metric::RowsIn, 1_i64);
                 stats::inc_stats(
                     Self::FDW_NAME,
@@ -393,6 +462,13 @@ impl ForeignDataWrapper<RedisFdwError> for RedisFdw {
     fn end_scan(&mut self) -> RedisFdwResult<()> {
         self.scan_result.clear();
         self.scan_result_stream.clear();
+        self.conn.take();
         Ok(())
     }
+
+    fn health_check(&mut self) -> RedisFdwResult<Option<String>> {
+        let mut conn = self.connect()?;
+        let info: InfoDict = redis::cmd("INFO").arg("server").query(&mut *conn)?;
+        Ok(info.get("redis_version"))
+    }
 }
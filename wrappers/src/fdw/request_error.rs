@@ -0,0 +1,24 @@
+//! Map failed HTTP requests of the API based FDWs to [`FdwError`]
+
+use supabase_wrappers::prelude::FdwError;
+
+// classify a failed request by its response status, or as transient if it
+// failed on connection or timeout before getting any response
+fn classify(status: Option<reqwest::StatusCode>, is_transient: bool, message: String) -> FdwError {
+    match status {
+        Some(status) => FdwError::from_http_status(status.as_u16(), message),
+        None if is_transient => FdwError::Transient(message),
+        None => FdwError::Other(message),
+    }
+}
+
+pub(crate) fn request_error(err: &reqwest::Error, message: String) -> FdwError {
+    classify(err.status(), err.is_connect() || err.is_timeout(), message)
+}
+
+pub(crate) fn request_middleware_error(
+    err: &reqwest_middleware::Error,
+    message: String,
+) -> FdwError {
+    classify(err.status(), err.is_connect() || err.is_timeout(), message)
+}
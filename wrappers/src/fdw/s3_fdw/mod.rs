@@ -6,11 +6,12 @@ mod tests;
 use aws_sdk_s3::config::http::HttpResponse;
 use aws_sdk_s3::error::SdkError;
 use aws_sdk_s3::operation::get_object::GetObjectError;
+use aws_sdk_s3::operation::list_objects_v2::ListObjectsV2Error;
 use pgrx::pg_sys::panic::ErrorReport;
 use pgrx::prelude::PgSqlErrorCode;
 use thiserror::Error;
 
-use supabase_wrappers::prelude::{CreateRuntimeError, OptionsError};
+use supabase_wrappers::prelude::{AwsError, CreateRuntimeError, OptionsError};
 
 #[derive(Error, Debug)]
 enum S3FdwError {
@@ -50,12 +51,18 @@ enum S3FdwError {
     #[error("{0}")]
     CreateRuntimeError(#[from] CreateRuntimeError),
 
+    #[error("{0}")]
+    AwsError(#[from] AwsError),
+
     #[error("parse uri failed: {0}")]
     UriParseError(#[from] http::uri::InvalidUri),
 
     #[error("request failed: {0}")]
     RequestError(#[from] SdkError<GetObjectError, HttpResponse>),
 
+    #[error("list objects failed: {0}")]
+    ListObjectsError(#[from] SdkError<ListObjectsV2Error, HttpResponse>),
+
     #[error("parse JSON response failed: {0}")]
     JsonParseError(#[from] serde_json::Error),
 
@@ -65,7 +72,10 @@ enum S3FdwError {
 
 impl From<S3FdwError> for ErrorReport {
     fn from(value: S3FdwError) -> Self {
-        ErrorReport::new(PgSqlErrorCode::ERRCODE_FDW_ERROR, format!("{value}"), "")
+        match value {
+            S3FdwError::AwsError(e) => e.into(),
+            _ => ErrorReport::new(PgSqlErrorCode::ERRCODE_FDW_ERROR, format!("{value}"), ""),
+        }
     }
 }
 
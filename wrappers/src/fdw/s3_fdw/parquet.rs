@@ -182,32 +182,31 @@ impl S3Parquet {
             })
             .await;
 
-        let stream = task
-            .expect("create parquet batch stream builder failed")
-            .expect("create parquet batch stream builder failed")
-            .and_then(|builder| {
-                // get parquet file metadata
-                let file_metadata = builder.metadata().file_metadata();
-                let schema = file_metadata.schema_descr();
-                let cols = schema.columns();
-
-                // find target column indexes in parquest columns
-                let project_indexes = tgt_cols
-                    .iter()
-                    .map(|tgt_col| {
-                        cols.iter()
-                            .position(|col| col.name() == tgt_col.name)
-                            .unwrap_or_else(|| {
-                                panic!("column '{}' not found in parquet file", tgt_col.name)
-                            })
-                    })
-                    .collect::<Vec<usize>>();
+        let builder = task.and_then(|t| t).map_err(|err| {
+            parquet::errors::ParquetError::General(format!(
+                "create parquet batch stream builder failed: {}",
+                err
+            ))
+        })??;
+
+        // get parquet file metadata
+        let file_metadata = builder.metadata().file_metadata();
+        let schema = file_metadata.schema_descr();
+        let cols = schema.columns();
 
-                // set up projections for the builder
-                let mask = ProjectionMask::roots(schema, project_indexes);
-                builder.with_projection(mask).build()
+        // find target column indexes in parquest columns
+        let project_indexes = tgt_cols
+            .iter()
+            .map(|tgt_col| {
+                cols.iter()
+                    .position(|col| col.name() == tgt_col.name)
+                    .ok_or(S3FdwError::ColumnNotFound(tgt_col.name.clone()))
             })
-            .map_err(|err| parquet::errors::ParquetError::General(err.to_string()))?;
+            .collect::<S3FdwResult<Vec<usize>>>()?;
+
+        // set up projections for the builder
+        let mask = ProjectionMask::roots(schema, project_indexes);
+        let stream = builder.with_projection(mask).build()?;
 
         self.stream = Some(stream);
         self.batch = None;
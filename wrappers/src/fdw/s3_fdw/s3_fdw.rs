@@ -1,12 +1,11 @@
 use crate::stats;
 use async_compression::tokio::bufread::{BzDecoder, GzipDecoder, XzDecoder, ZlibDecoder};
-use aws_config::BehaviorVersion;
+use aws_config::{BehaviorVersion, Region};
 use aws_sdk_s3 as s3;
 use http::Uri;
-use pgrx::pg_sys;
+use pgrx::{pg_sys, JsonB};
 use serde_json::{self, Value as JsonValue};
 use std::collections::{HashMap, VecDeque};
-use std::env;
 use std::io::Cursor;
 use std::pin::Pin;
 use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader};
@@ -24,8 +23,23 @@ enum Parser {
     Parquet(S3Parquet),
 }
 
+// extract s3 bucket and object path from uri option
+fn parse_uri(options: &HashMap<String, String>) -> S3FdwResult<(String, String)> {
+    let uri = require_option("uri", options)?.parse::<Uri>()?;
+    if uri.scheme_str() != Option::Some("s3") || uri.host().is_none() || uri.path().is_empty() {
+        return Err(S3FdwError::InvalidS3Uri(uri.to_string()));
+    }
+    // exclude 1st "/" char in the path as s3 object path doesn't like it
+    Ok((
+        uri.host()
+            .expect("host is not None as tested in if condition above")
+            .to_owned(),
+        uri.path()[1..].to_string(),
+    ))
+}
+
 #[wrappers_fdw(
-    version = "0.1.4",
+    version = "0.1.5",
     author = "Supabase",
     website = "https://github.com/supabase/wrappers/tree/main/wrappers/src/fdw/s3_fdw",
     error_type = "S3FdwError"
@@ -40,6 +54,12 @@ pub(crate) struct S3Fdw {
 
     // local string buffer for CSV and JSONL
     buf: String,
+
+    // bucket and the remaining keys of the objects to be read, and the
+    // foreign table options
+    bucket: String,
+    keys: VecDeque<String>,
+    opts: HashMap<String, String>,
 }
 
 impl S3Fdw {
@@ -49,207 +69,79 @@ impl S3Fdw {
     // Note: this is not a hard limit, just an indication of full buffer
     const BUF_SIZE: usize = 256 * 1024;
 
-    // fetch remote data to local string line buffer when it is empty and set
-    // up record parser.
-    // Returns:
-    //   Some - still have records to read
-    //   None - no more records
-    fn refill(&mut self) -> S3FdwResult<Option<()>> {
-        if !self.buf.is_empty() {
-            return Ok(Some(()));
+    // list the keys of the objects to be read, a single object is read if
+    // the object path doesn't end with '/', otherwise all the objects under
+    // the path prefix are read
+    fn list_objects(&self, bucket: &str, object: &str) -> S3FdwResult<Vec<String>> {
+        if !object.is_empty() && !object.ends_with('/') {
+            return Ok(vec![object.to_owned()]);
         }
 
-        if let Some(ref mut rdr) = self.rdr {
-            // fetch remote data by lines and fill in local buffer
-            let mut total_lines = 0;
-            let mut total_bytes = 0;
+        let mut keys = Vec::new();
+        if let Some(client) = &self.client {
+            let mut token = None;
             loop {
-                let num_bytes = self.rt.block_on(rdr.read_line(&mut self.buf))?;
-                total_lines += 1;
-                total_bytes += num_bytes;
-                if num_bytes == 0 || self.buf.len() > Self::BUF_SIZE {
-                    break;
-                }
-            }
-
-            stats::inc_stats(Self::FDW_NAME, stats::Metric::RowsIn, total_lines);
-            stats::inc_stats(Self::FDW_NAME, stats::Metric::BytesIn, total_bytes as i64);
-        }
-
-        if self.buf.is_empty() {
-            return Ok(None);
-        }
-
-        match &mut self.parser {
-            Parser::Csv(rdr) => {
-                let mut buf: Vec<u8> = Vec::new();
-                buf.extend(self.buf.as_bytes());
-                *rdr = csv::ReaderBuilder::new()
-                    .has_headers(false)
-                    .from_reader(Cursor::new(buf));
-            }
-            Parser::JsonLine(records) => {
-                // enclose json lines into a json string and then parse it
-                let s = self
-                    .buf
-                    .split('\n')
-                    .map(|s| s.trim())
-                    .collect::<Vec<&str>>()
-                    .join(",");
-                let json_str = format!("{{ \"rows\": [{}] }}", s.trim_end_matches(','));
-                let rows = serde_json::from_str::<JsonValue>(&json_str)?;
-                let rows = rows
-                    .get("rows")
-                    .and_then(|arr| arr.as_array())
-                    .ok_or(S3FdwError::ReadJsonlError(json_str))?;
-                *records = VecDeque::from(rows.to_vec());
-            }
-            _ => unreachable!(),
-        }
-
-        Ok(Some(()))
-    }
-}
-
-impl ForeignDataWrapper<S3FdwError> for S3Fdw {
-    fn new(server: ForeignServer) -> S3FdwResult<Self> {
-        // cannot use create_async_runtime() as the runtime needs to be created
-        // for multiple threads
-        let rt = tokio::runtime::Runtime::new()
-            .map_err(CreateRuntimeError::FailedToCreateAsyncRuntime)?;
-        let mut ret = S3Fdw {
-            rt,
-            client: None,
-            rdr: None,
-            parser: Parser::JsonLine(VecDeque::new()),
-            tgt_cols: Vec::new(),
-            rows_out: 0,
-            buf: String::new(),
-        };
-
-        // get is_mock flag
-        let is_mock: bool = server.options.get("is_mock") == Some(&"true".to_string());
-
-        // get credentials
-        let creds = if is_mock {
-            // LocalStack uses hardcoded credentials
-            Some(("test".to_string(), "test".to_string()))
-        } else {
-            match server.options.get("vault_access_key_id") {
-                Some(vault_access_key_id) => {
-                    // if using credentials stored in Vault
-                    let vault_secret_access_key =
-                        require_option("vault_secret_access_key", &server.options)?;
-                    get_vault_secret(vault_access_key_id)
-                        .zip(get_vault_secret(vault_secret_access_key))
-                }
-                None => {
-                    // if using credentials directly specified
-                    let aws_access_key_id =
-                        require_option("aws_access_key_id", &server.options)?.to_string();
-                    let aws_secret_access_key =
-                        require_option("aws_secret_access_key", &server.options)?.to_string();
-                    Some((aws_access_key_id, aws_secret_access_key))
+                let resp = self.rt.block_on(
+                    client
+                        .list_objects_v2()
+                        .bucket(bucket)
+                        .prefix(object)
+                        .set_continuation_token(token)
+                        .send(),
+                )?;
+                keys.extend(
+                    resp.contents()
+                        .iter()
+                        .filter_map(|obj| obj.key())
+                        .filter(|key| !key.ends_with('/'))
+                        .map(|key| key.to_owned()),
+                );
+                match resp.next_continuation_token() {
+                    Some(next) => token = Some(next.to_owned()),
+                    None => break,
                 }
             }
-        };
-
-        let Some(creds) = creds else {
-            return Ok(ret);
-        };
-
-        // get region
-        let default_region = "us-east-1".to_string();
-        let region = if is_mock {
-            default_region
-        } else {
-            server
-                .options
-                .get("aws_region")
-                .map(|t| t.to_owned())
-                .unwrap_or(default_region)
-        };
-
-        // set AWS environment variables and create shared config from them
-        env::set_var("AWS_ACCESS_KEY_ID", creds.0);
-        env::set_var("AWS_SECRET_ACCESS_KEY", creds.1);
-        env::set_var("AWS_REGION", region);
-
-        let mut config_loader = aws_config::defaults(BehaviorVersion::latest());
-
-        // endpoint_url not supported as env var in rust https://github.com/awslabs/aws-sdk-rust/issues/932
-        if let Some(endpoint_url) = server.options.get("endpoint_url") {
-            if endpoint_url.ends_with('/') {
-                config_loader = config_loader.endpoint_url(endpoint_url);
-            } else {
-                config_loader = config_loader.endpoint_url(format!("{}/", endpoint_url));
-            };
         }
-
-        // get path_style_url flag
-        //
-        // path style has been deprecated, but other s3-compatible services are
-        // still using it.
-        //
-        // Examples:
-        // path_style: https://s3.amazonaws.com/bucket/image.png
-        // virtual-hosted style: https://bucket.s3.amazonaws.com/image.png
-        //
-        // ref: https://docs.aws.amazon.com/AmazonS3/latest/userguide/VirtualHosting.html
-        let path_style_url: bool =
-            server.options.get("path_style_url") == Some(&"true".to_string());
-
-        let config = ret.rt.block_on(config_loader.load());
-
-        stats::inc_stats(Self::FDW_NAME, stats::Metric::CreateTimes, 1);
-
-        // create S3 client
-        let mut s3_config_builder = s3::config::Builder::from(&config);
-        let client = if is_mock {
-            s3_config_builder = s3_config_builder
-                .endpoint_url("http://localhost:4566/")
-                .force_path_style(true);
-            s3::Client::from_conf(s3_config_builder.build())
-        } else {
-            s3_config_builder = s3_config_builder.force_path_style(path_style_url);
-            s3::Client::from_conf(s3_config_builder.build())
-        };
-        ret.client = Some(client);
-
-        Ok(ret)
+        Ok(keys)
     }
 
-    fn begin_scan(
+    fn start_scan(
         &mut self,
-        _quals: &[Qual],
+        bucket: String,
+        keys: Vec<String>,
         columns: &[Column],
-        _sorts: &[Sort],
-        _limit: &Option<Limit>,
         options: &HashMap<String, String>,
     ) -> S3FdwResult<()> {
-        // extract s3 bucket and object path from uri option
-        let (bucket, object) = {
-            let uri = require_option("uri", options)?.parse::<Uri>()?;
-            if uri.scheme_str() != Option::Some("s3")
-                || uri.host().is_none()
-                || uri.path().is_empty()
-            {
-                return Err(S3FdwError::InvalidS3Uri(uri.to_string()));
-            }
-            // exclude 1st "/" char in the path as s3 object path doesn't like it
-            (
-                uri.host()
-                    .expect("host is not None as tested in if condition above")
-                    .to_owned(),
-                uri.path()[1..].to_string(),
-            )
-        };
-
-        let has_header: bool = options.get("has_header") == Some(&"true".to_string());
-
+        self.bucket = bucket;
+        self.keys = keys.into();
+        self.opts = options.clone();
         self.tgt_cols = columns.to_vec();
+        self.open_next_object()?;
+        Ok(())
+    }
 
+    // open the next object to be read and set up record parser
+    // Returns:
+    //   true - an object is opened
+    //   false - no more objects
+    fn open_next_object(&mut self) -> S3FdwResult<bool> {
+        self.rdr.take();
+        self.buf.clear();
+        self.parser = Parser::JsonLine(VecDeque::new());
+        match self.keys.pop_front() {
+            Some(key) => {
+                self.open_object(&key)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn open_object(&mut self, key: &str) -> S3FdwResult<()> {
         if let Some(client) = &self.client {
+            let options = &self.opts;
+            let (bucket, object) = (&self.bucket, key);
+
             // initialise parser according to format option
             let format = require_option("format", options)?;
             // create dummy parser
@@ -262,7 +154,7 @@ impl ForeignDataWrapper<S3FdwError> for S3Fdw {
 
             let stream = self
                 .rt
-                .block_on(client.get_object().bucket(&bucket).key(&object).send())?
+                .block_on(client.get_object().bucket(bucket).key(object).send())?
                 .body
                 .into_async_read();
 
@@ -294,8 +186,8 @@ impl ForeignDataWrapper<S3FdwError> for S3Fdw {
                     // open async read stream
                     self.rt.block_on(s3parquet.open_async_stream(
                         client,
-                        &bucket,
-                        &object,
+                        bucket,
+                        object,
                         &self.tgt_cols,
                     ))?;
                 }
@@ -306,7 +198,7 @@ impl ForeignDataWrapper<S3FdwError> for S3Fdw {
 
             // skip csv header line if needed
             if let Parser::Csv(_) = self.parser {
-                if has_header {
+                if options.get("has_header") == Some(&"true".to_string()) {
                     let mut header = String::new();
                     self.rt.block_on(rdr.read_line(&mut header))?;
                 }
@@ -318,7 +210,8 @@ impl ForeignDataWrapper<S3FdwError> for S3Fdw {
         Ok(())
     }
 
-    fn iter_scan(&mut self, row: &mut Row) -> S3FdwResult<Option<()>> {
+    // read one record from the current object
+    fn read_row(&mut self, row: &mut Row) -> S3FdwResult<Option<()>> {
         // read parquet record
         if let Parser::Parquet(ref mut s3parquet) = &mut self.parser {
             if self.rt.block_on(s3parquet.refill())?.is_none() {
@@ -327,8 +220,6 @@ impl ForeignDataWrapper<S3FdwError> for S3Fdw {
             let ret = s3parquet.read_into_row(row, &self.tgt_cols)?;
             if ret.is_some() {
                 self.rows_out += 1;
-            } else {
-                stats::inc_stats(Self::FDW_NAME, stats::Metric::RowsOut, self.rows_out);
             }
             return Ok(ret);
         }
@@ -365,19 +256,13 @@ impl ForeignDataWrapper<S3FdwError> for S3Fdw {
                                         .get(&col.name)
                                         .map(|val| match val {
                                             JsonValue::Null => None,
-                                            JsonValue::Bool(v) => Some(Cell::String(v.to_string())),
-                                            JsonValue::Number(v) => {
-                                                Some(Cell::String(v.to_string()))
+                                            v if col.type_oid == pg_sys::JSONBOID => {
+                                                Some(Cell::Json(JsonB(v.clone())))
                                             }
                                             JsonValue::String(v) => {
                                                 Some(Cell::String(v.to_owned()))
                                             }
-                                            JsonValue::Array(v) => {
-                                                Some(Cell::String(format!("{:?}", v)))
-                                            }
-                                            JsonValue::Object(v) => {
-                                                Some(Cell::String(format!("{:?}", v)))
-                                            }
+                                            v => Some(Cell::String(v.to_string())),
                                         })
                                         .unwrap_or(None);
                                     row.push(&col.name, cell);
@@ -396,14 +281,212 @@ impl ForeignDataWrapper<S3FdwError> for S3Fdw {
             }
         }
 
-        stats::inc_stats(Self::FDW_NAME, stats::Metric::RowsOut, self.rows_out);
-
         Ok(None)
     }
 
+    // fetch remote data to local string line buffer when it is empty and set
+    // up record parser.
+    // Returns:
+    //   Some - still have records to read
+    //   None - no more records
+    fn refill(&mut self) -> S3FdwResult<Option<()>> {
+        if !self.buf.is_empty() {
+            return Ok(Some(()));
+        }
+
+        if let Some(ref mut rdr) = self.rdr {
+            // fetch remote data by lines and fill in local buffer
+            let mut total_lines = 0;
+            let mut total_bytes = 0;
+            loop {
+                let num_bytes = self.rt.block_on(rdr.read_line(&mut self.buf))?;
+                total_lines += 1;
+                total_bytes += num_bytes;
+                if num_bytes == 0 || self.buf.len() > Self::BUF_SIZE {
+                    break;
+                }
+            }
+
+            stats::inc_stats(Self::FDW_NAME, stats::Metric::RowsIn, total_lines);
+            stats::inc_stats(Self::FDW_NAME, stats::Metric::BytesIn, total_bytes as i64);
+        }
+
+        if self.buf.is_empty() {
+            return Ok(None);
+        }
+
+        match &mut self.parser {
+            Parser::Csv(rdr) => {
+                let mut buf: Vec<u8> = Vec::new();
+                buf.extend(self.buf.as_bytes());
+                *rdr = csv::ReaderBuilder::new()
+                    .has_headers(false)
+                    .from_reader(Cursor::new(buf));
+            }
+            Parser::JsonLine(records) => {
+                // enclose json lines into a json string and then parse it
+                let s = self
+                    .buf
+                    .split('\n')
+                    .map(|s| s.trim())
+                    .collect::<Vec<&str>>()
+                    .join(",");
+                let json_str = format!("{{ \"rows\": [{}] }}", s.trim_end_matches(','));
+                let rows = serde_json::from_str::<JsonValue>(&json_str)?;
+                let rows = rows
+                    .get("rows")
+                    .and_then(|arr| arr.as_array())
+                    .ok_or(S3FdwError::ReadJsonlError(json_str))?;
+                *records = VecDeque::from(rows.to_vec());
+            }
+            _ => unreachable!(),
+        }
+
+        Ok(Some(()))
+    }
+}
+
+impl ForeignDataWrapper<S3FdwError> for S3Fdw {
+    fn new(server: ForeignServer) -> S3FdwResult<Self> {
+        let rt = create_async_runtime()?;
+        let mut ret = S3Fdw {
+            rt,
+            client: None,
+            rdr: None,
+            parser: Parser::JsonLine(VecDeque::new()),
+            tgt_cols: Vec::new(),
+            rows_out: 0,
+            buf: String::new(),
+            bucket: String::new(),
+            keys: VecDeque::new(),
+            opts: HashMap::new(),
+        };
+
+        // get is_mock flag
+        let is_mock: bool = server.options.get("is_mock") == Some(&"true".to_string());
+
+        // get credentials and region
+        let mut provider = AwsCredentialsProvider::from_options(&server.options)?;
+        if is_mock {
+            // LocalStack uses hardcoded credentials
+            provider = provider
+                .with_static_credentials(AwsCredentials::new("test", "test"))
+                .with_region("us-east-1");
+        }
+        let creds = provider.credentials()?;
+
+        let mut config_loader = aws_config::defaults(BehaviorVersion::latest())
+            .region(Region::new(provider.region().to_owned()))
+            .credentials_provider(s3::config::Credentials::new(
+                creds.access_key_id,
+                creds.secret_access_key,
+                creds.session_token,
+                creds.expires_at,
+                "wrappers",
+            ));
+
+        // endpoint_url not supported as env var in rust https://github.com/awslabs/aws-sdk-rust/issues/932
+        if let Some(endpoint_url) = server.options.get("endpoint_url") {
+            if endpoint_url.ends_with('/') {
+                config_loader = config_loader.endpoint_url(endpoint_url);
+            } else {
+                config_loader = config_loader.endpoint_url(format!("{}/", endpoint_url));
+            };
+        }
+
+        // get path_style_url flag
+        //
+        // path style has been deprecated, but other s3-compatible services are
+        // still using it.
+        //
+        // Examples:
+        // path_style: https://s3.amazonaws.com/bucket/image.png
+        // virtual-hosted style: https://bucket.s3.amazonaws.com/image.png
+        //
+        // ref: https://docs.aws.amazon.com/AmazonS3/latest/userguide/VirtualHosting.html
+        let path_style_url: bool =
+            server.options.get("path_style_url") == Some(&"true".to_string());
+
+        let config = ret.rt.block_on(config_loader.load());
+
+        stats::inc_stats(Self::FDW_NAME, stats::Metric::CreateTimes, 1);
+
+        // create S3 client
+        let mut s3_config_builder = s3::config::Builder::from(&config);
+        let client = if is_mock {
+            s3_config_builder = s3_config_builder
+                .endpoint_url("http://localhost:4566/")
+                .force_path_style(true);
+            s3::Client::from_conf(s3_config_builder.build())
+        } else {
+            s3_config_builder = s3_config_builder.force_path_style(path_style_url);
+            s3::Client::from_conf(s3_config_builder.build())
+        };
+        ret.client = Some(client);
+
+        Ok(ret)
+    }
+
+    fn begin_scan(
+        &mut self,
+        _quals: &[Qual],
+        columns: &[Column],
+        _sorts: &[Sort],
+        _limit: &Option<Limit>,
+        options: &HashMap<String, String>,
+    ) -> S3FdwResult<()> {
+        let (bucket, object) = parse_uri(options)?;
+        let keys = self.list_objects(&bucket, &object)?;
+        self.start_scan(bucket, keys, columns, options)
+    }
+
+    fn parallel_safe() -> bool {
+        true
+    }
+
+    fn scan_partitions(
+        &mut self,
+        _quals: &[Qual],
+        _columns: &[Column],
+        options: &HashMap<String, String>,
+    ) -> S3FdwResult<usize> {
+        // each object is scanned as a partition
+        let (bucket, object) = parse_uri(options)?;
+        Ok(self.list_objects(&bucket, &object)?.len().max(1))
+    }
+
+    fn begin_partition_scan(
+        &mut self,
+        partition: usize,
+        _quals: &[Qual],
+        columns: &[Column],
+        options: &HashMap<String, String>,
+    ) -> S3FdwResult<()> {
+        // the objects are listed in the same key order in every process
+        let (bucket, object) = parse_uri(options)?;
+        let keys = self.list_objects(&bucket, &object)?;
+        let keys = keys.into_iter().nth(partition).into_iter().collect();
+        self.start_scan(bucket, keys, columns, options)
+    }
+
+    fn iter_scan(&mut self, row: &mut Row) -> S3FdwResult<Option<()>> {
+        loop {
+            if self.read_row(row)?.is_some() {
+                return Ok(Some(()));
+            }
+
+            // the current object is finished, continue with the next one
+            if !self.open_next_object()? {
+                stats::inc_stats(Self::FDW_NAME, stats::Metric::RowsOut, self.rows_out);
+                return Ok(None);
+            }
+        }
+    }
+
     fn end_scan(&mut self) -> S3FdwResult<()> {
         // release local resources
         self.rdr.take();
+        self.keys.clear();
         self.parser = Parser::JsonLine(VecDeque::new());
         Ok(())
     }
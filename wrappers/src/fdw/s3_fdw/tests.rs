@@ -174,6 +174,34 @@ mod tests {
             check_test_table("s3_test_table_jsonl");
             check_test_table("s3_test_table_jsonl_bz");
 
+            // JSONL values can be read as jsonb
+            c.update(
+                r#"
+                CREATE FOREIGN TABLE s3_test_table_jsonl_jsonb (
+                  name text,
+                  age jsonb
+                )
+                SERVER s3_server
+                OPTIONS (
+                    uri 's3://test/test_data.jsonl',
+                    format 'jsonl'
+                  )
+             "#,
+                None,
+                None,
+            )
+            .unwrap();
+            let results = c
+                .select(
+                    "SELECT jsonb_typeof(age) AS age_type FROM s3_test_table_jsonl_jsonb WHERE name = 'Alex'",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("age_type").unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec!["number"]);
+
             let check_parquet_table = |table| {
                 let sql = format!("SELECT * FROM {} ORDER BY id LIMIT 1", table);
                 let results = c
@@ -192,4 +220,90 @@ mod tests {
             check_parquet_table("s3_test_table_parquet_gz");
         });
     }
+
+    #[pg_test]
+    fn s3_parallel_scan_test() {
+        Spi::connect(|mut c| {
+            c.update(
+                r#"CREATE FOREIGN DATA WRAPPER s3_wrapper
+                     HANDLER s3_fdw_handler VALIDATOR s3_fdw_validator"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"CREATE SERVER s3_server
+                     FOREIGN DATA WRAPPER s3_wrapper
+                     OPTIONS (
+                       aws_access_key_id 'test',
+                       aws_secret_access_key 'test',
+                       aws_region 'us-east-1',
+                       is_mock 'true'
+                     )"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"
+                CREATE FOREIGN TABLE s3_test_table_parts (
+                  name text,
+                  sex text,
+                  age text,
+                  height text,
+                  weight text
+                )
+                SERVER s3_server
+                OPTIONS (
+                    uri 's3://test/parts/',
+                    format 'csv',
+                    has_header 'true'
+                  )
+             "#,
+                None,
+                None,
+            )
+            .unwrap();
+
+            let count_sql =
+                "SELECT count(*), count(*) FILTER (WHERE name = 'Alex') FROM s3_test_table_parts";
+
+            // all the objects under the prefix are read
+            let counts = c
+                .select(count_sql, None, None)
+                .unwrap()
+                .first()
+                .get_two::<i64, i64>()
+                .unwrap();
+            assert_eq!(counts, (Some(9), Some(3)));
+
+            // force a parallel plan, each object is scanned as a partition
+            c.update(
+                "SET LOCAL parallel_setup_cost = 0;
+                 SET LOCAL parallel_tuple_cost = 0;
+                 SET LOCAL max_parallel_workers_per_gather = 2",
+                None,
+                None,
+            )
+            .unwrap();
+            let plan = c
+                .select(
+                    "EXPLAIN SELECT count(*) FROM s3_test_table_parts",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get::<&str>(1).unwrap())
+                .collect::<Vec<_>>()
+                .join("\n");
+            assert!(plan.contains("Gather"), "{}", plan);
+            let counts = c
+                .select(count_sql, None, None)
+                .unwrap()
+                .first()
+                .get_two::<i64, i64>()
+                .unwrap();
+            assert_eq!(counts, (Some(9), Some(3)));
+        });
+    }
 }
@@ -7,7 +7,9 @@ use pgrx::pg_sys::panic::ErrorReport;
 use pgrx::prelude::PgSqlErrorCode;
 use thiserror::Error;
 
-use supabase_wrappers::prelude::{CreateRuntimeError, OptionsError};
+use crate::fdw::http_client::HttpClientError;
+use crate::fdw::request_error::{request_error, request_middleware_error};
+use supabase_wrappers::prelude::{CreateRuntimeError, FdwError, OptionsError};
 
 #[derive(Error, Debug)]
 enum StripeFdwError {
@@ -20,9 +22,15 @@ enum StripeFdwError {
     #[error("{0}")]
     OptionsError(#[from] OptionsError),
 
+    #[error("{0}")]
+    FdwError(#[from] FdwError),
+
     #[error("{0}")]
     CreateRuntimeError(#[from] CreateRuntimeError),
 
+    #[error("{0}")]
+    HttpClientError(#[from] HttpClientError),
+
     #[error("parse url failed: {0}")]
     UrlParseError(#[from] url::ParseError),
 
@@ -38,6 +46,9 @@ enum StripeFdwError {
     #[error("invalid api_key header: {0}")]
     InvalidApiKeyHeader(#[from] InvalidHeaderValue),
 
+    #[error("either api_key_id or api_key_name option is required")]
+    ApiKeyNotFound,
+
     #[error("invalid response")]
     InvalidResponse,
 
@@ -47,7 +58,18 @@ enum StripeFdwError {
 
 impl From<StripeFdwError> for ErrorReport {
     fn from(value: StripeFdwError) -> Self {
-        ErrorReport::new(PgSqlErrorCode::ERRCODE_FDW_ERROR, format!("{value}"), "")
+        let msg = format!("{value}");
+        match value {
+            StripeFdwError::FdwError(e) => e.into(),
+            StripeFdwError::HttpClientError(e) => e.into(),
+            StripeFdwError::RequestError(e) => request_error(&e, msg).into(),
+            StripeFdwError::RequestMiddlewareError(e) => request_middleware_error(&e, msg).into(),
+            StripeFdwError::UnsupportedColumnType(_) => FdwError::TypeMismatch(msg).into(),
+            StripeFdwError::OptionsError(_) | StripeFdwError::ApiKeyNotFound => {
+                FdwError::InvalidOption(msg).into()
+            }
+            _ => ErrorReport::new(PgSqlErrorCode::ERRCODE_FDW_ERROR, msg, ""),
+        }
     }
 }
 
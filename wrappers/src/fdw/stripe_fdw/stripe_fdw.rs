@@ -1,8 +1,8 @@
+use crate::fdw::http_client::{create_http_client, record_request};
 use crate::stats;
-use pgrx::{datum::datetime_support::to_timestamp, pg_sys, JsonB};
+use pgrx::{datum::datetime_support::to_timestamp, pg_sys, AnyNumeric, JsonB};
 use reqwest::{self, header, StatusCode, Url};
-use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
-use reqwest_retry::{policies::ExponentialBackoff, RetryTransientMiddleware};
+use reqwest_middleware::ClientWithMiddleware;
 use serde_json::{json, Map as JsonMap, Number, Value as JsonValue};
 use std::collections::HashMap;
 
@@ -13,6 +13,7 @@ use super::{StripeFdwError, StripeFdwResult};
 fn create_client(
     api_key: &str,
     api_version: Option<&str>,
+    options: &HashMap<String, String>,
 ) -> StripeFdwResult<ClientWithMiddleware> {
     let mut headers = header::HeaderMap::new();
     let value = format!("Bearer {}", api_key);
@@ -22,13 +23,77 @@ fn create_client(
     if let Some(version) = api_version {
         headers.insert("Stripe-Version", header::HeaderValue::from_str(version)?);
     }
-    let client = reqwest::Client::builder()
-        .default_headers(headers)
-        .build()?;
-    let retry_policy = ExponentialBackoff::builder().build_with_max_retries(3);
-    Ok(ClientBuilder::new(client)
-        .with(RetryTransientMiddleware::new_with_policy(retry_policy))
-        .build())
+    Ok(create_http_client(options, headers)?)
+}
+
+// convert a JSON value to cell of the column type
+fn json_to_cell(value: &JsonValue, type_oid: pg_sys::Oid) -> Option<Cell> {
+    match type_oid {
+        pg_sys::BOOLOID => value.as_bool().map(Cell::Bool),
+        pg_sys::INT8OID => value.as_i64().map(Cell::I64),
+        pg_sys::NUMERICOID => match value {
+            JsonValue::Number(v) => AnyNumeric::try_from(v.to_string().as_str()).ok(),
+            JsonValue::String(v) => AnyNumeric::try_from(v.as_str()).ok(),
+            _ => None,
+        }
+        .map(Cell::Numeric),
+        pg_sys::TIMESTAMPOID => value.as_i64().map(|a| {
+            let ts = to_timestamp(a as f64);
+            Cell::Timestamp(ts.to_utc())
+        }),
+        pg_sys::TIMESTAMPTZOID => value
+            .as_i64()
+            .map(|a| Cell::Timestamptz(to_timestamp(a as f64))),
+        pg_sys::TSTZRANGEOID => value.as_object().map(|obj| {
+            // period object, e.g. {"start": 1700000000, "end": 1702592000},
+            // both of its ends are inclusive
+            let bound = |key| {
+                obj.get(key)
+                    .and_then(|v| v.as_i64())
+                    .map(|a| to_timestamp(a as f64))
+            };
+            Cell::TimestamptzRange(make_range(bound("start"), true, bound("end"), true))
+        }),
+        pg_sys::INTERVALOID => match value {
+            JsonValue::String(v) => interval_from_iso8601(v).map(Cell::Interval),
+            v => v.as_f64().and_then(interval_from_secs).map(Cell::Interval),
+        },
+        pg_sys::JSONBOID => Some(Cell::Json(JsonB(value.clone()))),
+        pg_sys::INT8ARRAYOID => value
+            .as_array()
+            .map(|arr| Cell::I64Array(arr.iter().map(|v| v.as_i64()).collect())),
+        pg_sys::TEXTARRAYOID => value.as_array().map(|arr| {
+            Cell::StringArray(
+                arr.iter()
+                    .map(|v| match v {
+                        JsonValue::Null => None,
+                        JsonValue::String(v) => Some(v.to_owned()),
+                        v => Some(v.to_string()),
+                    })
+                    .collect(),
+            )
+        }),
+        _ => {
+            if let Some(fields) = composite_type_columns(type_oid) {
+                // sub-object is mapped to composite type field-by-field
+                return value.as_object().map(|obj| {
+                    let mut row = Row::new();
+                    for field in &fields {
+                        let cell = obj
+                            .get(&field.name)
+                            .and_then(|v| json_to_cell(v, field.type_oid));
+                        row.push(&field.name, cell);
+                    }
+                    Cell::Composite(row)
+                });
+            }
+            match value {
+                JsonValue::Null => None,
+                JsonValue::String(v) => Some(Cell::String(v.to_owned())),
+                v => Some(Cell::String(v.to_string())),
+            }
+        }
+    }
 }
 
 fn body_to_rows(
@@ -116,6 +181,14 @@ fn body_to_rows(
                 // put all properties into 'attrs' JSON column
                 let attrs = serde_json::from_str(&obj.to_string())?;
                 row.push("attrs", Some(Cell::Json(JsonB(attrs))));
+            } else if let Some(path) = tgt_col.options.get("path") {
+                // other columns are extracted from the object by the column
+                // option 'path', e.g. 'address.city'
+                let cell = path
+                    .split('.')
+                    .try_fold(obj, |v, key| v.get(key))
+                    .and_then(|v| json_to_cell(v, tgt_col.type_oid));
+                row.push(&tgt_col.name, cell);
             }
         }
 
@@ -177,11 +250,60 @@ fn row_to_body(row: &Row) -> StripeFdwResult<JsonValue> {
     Ok(JsonValue::Object(map))
 }
 
+// get fields can be pushed down as query parameters for an object
+// ref: https://stripe.com/docs/api/[object]/list
+fn get_pushdown_fields(obj: &str) -> StripeFdwResult<Vec<&'static str>> {
+    let fields = match obj {
+        "accounts" => vec![],
+        "balance" => vec![],
+        "balance_transactions" => vec!["type"],
+        "charges" => vec!["customer"],
+        "customers" => vec!["email"],
+        "disputes" => vec!["charge", "payment_intent"],
+        "events" => vec!["type"],
+        "files" => vec!["purpose"],
+        "file_links" => vec![],
+        "invoices" => vec!["customer", "status", "subscription"],
+        "mandates" => vec![],
+        "payment_intents" => vec!["customer"],
+        "payouts" => vec!["status"],
+        "prices" => vec!["active", "currency", "product", "type"],
+        "products" => vec!["active"],
+        "refunds" => vec!["charge", "payment_intent"],
+        "setup_attempts" => vec!["setup_intent"],
+        "setup_intents" => vec!["customer", "payment_method"],
+        "subscriptions" => vec!["customer", "price", "status"],
+        "tokens" => vec![],
+        "topups" => vec!["status"],
+        "transfers" => vec!["destination"],
+        "billing/meters" => vec![],
+        "checkout/sessions" => vec!["customer", "payment_intent", "subscription"],
+        _ => {
+            return Err(StripeFdwError::ObjectNotImplemented(obj.to_string()));
+        }
+    };
+    Ok(fields)
+}
+
+// check if a qual is pushed down as query parameter or object id, those
+// filters are exact matches on Stripe side
+fn is_pushed_down(qual: &Qual, quals: &[Qual], fields: &[&str]) -> bool {
+    let is_equal = qual.operator == "=" && !qual.use_or && qual.json_path.is_empty();
+    match &qual.value {
+        Value::Cell(Cell::String(_)) if qual.field == "id" => is_equal && quals.len() == 1,
+        Value::Cell(Cell::Bool(_)) | Value::Cell(Cell::String(_)) => {
+            is_equal && fields.contains(&qual.field.as_str())
+        }
+        _ => false,
+    }
+}
+
 fn pushdown_quals(
     url: &mut Url,
     obj: &str,
     quals: &[Qual],
     fields: Vec<&str>,
+    attr_fields: Vec<&str>,
     page_size: i64,
     cursor: &Option<String>,
 ) {
@@ -218,6 +340,25 @@ fn pushdown_quals(
         }
     }
 
+    // pushdown quals on attributes in 'attrs' column, nested attribute is
+    // converted to Stripe's query parameter format, e.g.
+    // "attrs->'recurring'->>'interval'" => "recurring[interval]"
+    for qual in quals {
+        if qual.field != "attrs" || qual.json_path.is_empty() {
+            continue;
+        }
+        let param = qual.json_path[1..]
+            .iter()
+            .fold(qual.json_path[0].clone(), |acc, key| {
+                format!("{}[{}]", acc, key)
+            });
+        if attr_fields.contains(&param.as_str()) && qual.operator == "=" && !qual.use_or {
+            if let Value::Cell(Cell::String(s)) = &qual.value {
+                url.query_pairs_mut().append_pair(&param, s);
+            }
+        }
+    }
+
     // add pagination parameters except for 'balance' object
     if obj != "balance" {
         url.query_pairs_mut()
@@ -254,17 +395,49 @@ fn inc_stats_request_cnt(stats_metadata: &mut JsonB) -> StripeFdwResult<()> {
     Ok(())
 }
 
+// response of a page request, which is the content length and body text
+type PageResponse = reqwest_middleware::Result<(Option<u64>, String)>;
+
+// state of a paginated scan, the next page is prefetched while the rows of
+// current page are being returned
+struct Pagination {
+    obj: String,
+    quals: Vec<Qual>,
+    columns: Vec<Column>,
+
+    // max number of pages to fetch and number of pages fetched so far
+    page_cnt: i64,
+    page: i64,
+}
+
 #[wrappers_fdw(
     version = "0.1.11",
     author = "Supabase",
     website = "https://github.com/supabase/wrappers/tree/main/wrappers/src/fdw/stripe_fdw",
-    error_type = "StripeFdwError"
+    error_type = "StripeFdwError",
+    smoke_sql = "create server stripe_smoke_server foreign data wrapper stripe_fdw_wrapper
+                   options (api_url 'http://localhost:12111/v1', api_key 'sk_test_xxx');
+                 create foreign table stripe_smoke_customers (id text, email text, attrs jsonb)
+                   server stripe_smoke_server options (object 'customers');
+                 select * from stripe_smoke_customers",
+    options(
+        server(
+            api_url(example = "https://api.stripe.com/v1/"),
+            api_version(example = "2024-06-20"),
+            api_key(secret, example = "sk_test_xxx")
+        ),
+        table(object(required, example = "customers"))
+    )
 )]
 pub(crate) struct StripeFdw {
     rt: Runtime,
     base_url: Url,
     client: Option<ClientWithMiddleware>,
+    rate_limiter: RateLimiter,
+    circuit_breaker: CircuitBreaker,
     scan_result: Option<Vec<Row>>,
+    pagination: Option<Pagination>,
+    prefetch: Prefetch<PageResponse>,
     obj: String,
     rowid_col: String,
     iter_idx: usize,
@@ -272,6 +445,7 @@ pub(crate) struct StripeFdw {
 
 impl StripeFdw {
     const FDW_NAME: &'static str = "StripeFdw";
+    const PAGE_SIZE: i64 = 100; // maximum page size limit for Stripe API
 
     fn build_url(
         &self,
@@ -283,41 +457,100 @@ impl StripeFdw {
         let mut url = self.base_url.join(obj)?;
 
         // pushdown quals other than id
-        // ref: https://stripe.com/docs/api/[object]/list
-        let fields = match obj {
-            "accounts" => vec![],
-            "balance" => vec![],
-            "balance_transactions" => vec!["type"],
-            "charges" => vec!["customer"],
-            "customers" => vec!["email"],
-            "disputes" => vec!["charge", "payment_intent"],
-            "events" => vec!["type"],
-            "files" => vec!["purpose"],
-            "file_links" => vec![],
-            "invoices" => vec!["customer", "status", "subscription"],
-            "mandates" => vec![],
-            "payment_intents" => vec!["customer"],
-            "payouts" => vec!["status"],
-            "prices" => vec!["active", "currency", "product", "type"],
-            "products" => vec!["active"],
-            "refunds" => vec!["charge", "payment_intent"],
-            "setup_attempts" => vec!["setup_intent"],
-            "setup_intents" => vec!["customer", "payment_method"],
-            "subscriptions" => vec!["customer", "price", "status"],
-            "tokens" => vec![],
-            "topups" => vec!["status"],
-            "transfers" => vec!["destination"],
-            "billing/meters" => vec![],
-            "checkout/sessions" => vec!["customer", "payment_intent", "subscription"],
-            _ => {
-                return Err(StripeFdwError::ObjectNotImplemented(obj.to_string()));
-            }
+        let fields = get_pushdown_fields(obj)?;
+
+        // pushdown quals on attributes in 'attrs' column
+        let attr_fields = match obj {
+            "charges" => vec!["transfer_group"],
+            "invoices" => vec!["collection_method"],
+            "prices" => vec!["recurring[interval]", "recurring[usage_type]"],
+            "subscriptions" => vec!["collection_method"],
+            "checkout/sessions" => vec!["customer_details[email]"],
+            _ => vec![],
         };
-        pushdown_quals(&mut url, obj, quals, fields, page_size, cursor);
+
+        pushdown_quals(&mut url, obj, quals, fields, attr_fields, page_size, cursor);
 
         Ok(Some(url))
     }
 
+    // send request of a page in background
+    fn prefetch_page(&mut self, cursor: &Option<String>) -> StripeFdwResult<()> {
+        let (Some(client), Some(pagination)) = (&self.client, &self.pagination) else {
+            return Ok(());
+        };
+        let url = self.build_url(&pagination.obj, &pagination.quals, Self::PAGE_SIZE, cursor)?;
+        let Some(url) = url else {
+            return Ok(());
+        };
+
+        let mut stats_metadata = get_stats_metadata();
+        inc_stats_request_cnt(&mut stats_metadata)?;
+        set_stats_metadata(stats_metadata);
+        self.circuit_breaker.check()?;
+        self.rate_limiter.wait();
+
+        // make api call
+        let client = client.clone();
+        self.prefetch.spawn(async move {
+            let resp = client.get(url).send().await?;
+            let content_length = resp.content_length();
+
+            if resp.status() == StatusCode::NOT_FOUND {
+                // if it is 404 error, we should treat it as an empty
+                // result rather than a request error
+                return Ok((content_length, String::new()));
+            }
+
+            let body = resp.error_for_status()?.text().await?;
+            Ok((content_length, body))
+        });
+
+        Ok(())
+    }
+
+    // wait for the prefetched page and append its rows to scan result, then
+    // prefetch the next page, return false if there are no more rows
+    fn fetch_page(&mut self) -> StripeFdwResult<bool> {
+        let Some(resp) = self.prefetch.wait() else {
+            return Ok(false);
+        };
+        let (content_length, body) = record_request(&self.circuit_breaker, resp)?;
+        stats::inc_stats(
+            Self::FDW_NAME,
+            stats::Metric::BytesIn,
+            content_length.unwrap_or(0) as i64,
+        );
+        if body.is_empty() {
+            return Ok(false);
+        }
+
+        // convert response body to rows
+        let Some(pagination) = &self.pagination else {
+            return Ok(false);
+        };
+        let (rows, starting_after, has_more) =
+            self.resp_to_rows(&pagination.obj, &body, &pagination.columns)?;
+        if rows.is_empty() {
+            return Ok(false);
+        }
+
+        // save stats
+        stats::inc_stats(Self::FDW_NAME, stats::Metric::RowsIn, rows.len() as i64);
+        stats::inc_stats(Self::FDW_NAME, stats::Metric::RowsOut, rows.len() as i64);
+
+        self.scan_result.get_or_insert_with(Vec::new).extend(rows);
+
+        if let Some(pagination) = &mut self.pagination {
+            pagination.page += 1;
+            if has_more == Some(true) && pagination.page < pagination.page_cnt {
+                self.prefetch_page(&starting_after)?;
+            }
+        }
+
+        Ok(true)
+    }
+
     // convert response body text to rows
     fn resp_to_rows(
         &self,
@@ -629,6 +862,120 @@ impl StripeFdw {
             _ => Err(StripeFdwError::ObjectNotImplemented(obj.to_string())),
         }
     }
+
+    // create an object and return the response body, None is returned if
+    // there is nothing to create
+    fn create_object(&mut self, src: &Row) -> StripeFdwResult<Option<String>> {
+        if let Some(ref mut client) = self.client {
+            let url = self.base_url.join(&self.obj)?;
+            let body = row_to_body(src)?;
+            if body.is_null() {
+                return Ok(None);
+            }
+
+            let mut stats_metadata = get_stats_metadata();
+
+            inc_stats_request_cnt(&mut stats_metadata)?;
+            self.circuit_breaker.check()?;
+            self.rate_limiter.wait();
+
+            // call Stripe API
+            let body = record_request(
+                &self.circuit_breaker,
+                self.rt
+                    .block_on(client.post(url).form(&body).send())
+                    .and_then(|resp| {
+                        resp.error_for_status()
+                            .and_then(|resp| {
+                                stats::inc_stats(
+                                    Self::FDW_NAME,
+                                    stats::Metric::BytesIn,
+                                    resp.content_length().unwrap_or(0) as i64,
+                                );
+                                self.rt.block_on(resp.text())
+                            })
+                            .map_err(reqwest_middleware::Error::from)
+                    }),
+            )?;
+
+            let json: JsonValue = serde_json::from_str(&body)?;
+            if let Some(id) = json.get("id").and_then(|v| v.as_str()) {
+                report_info(&format!("inserted {} {}", self.obj, id));
+            }
+
+            set_stats_metadata(stats_metadata);
+
+            return Ok(Some(body));
+        }
+        Ok(None)
+    }
+
+    // update an object and return the response body, None is returned if
+    // there is nothing to update
+    fn update_object(&mut self, rowid: &Cell, new_row: &Row) -> StripeFdwResult<Option<String>> {
+        if let Some(ref mut client) = self.client {
+            let mut stats_metadata = get_stats_metadata();
+
+            match rowid {
+                Cell::String(rowid) => {
+                    let url = self.base_url.join(&format!("{}/", self.obj))?.join(rowid)?;
+                    let body = row_to_body(new_row)?;
+                    if body.is_null() {
+                        return Ok(None);
+                    }
+
+                    inc_stats_request_cnt(&mut stats_metadata)?;
+                    self.circuit_breaker.check()?;
+                    self.rate_limiter.wait();
+
+                    // call Stripe API
+                    let body = record_request(
+                        &self.circuit_breaker,
+                        self.rt
+                            .block_on(client.post(url).form(&body).send())
+                            .and_then(|resp| {
+                                resp.error_for_status()
+                                    .and_then(|resp| {
+                                        stats::inc_stats(
+                                            Self::FDW_NAME,
+                                            stats::Metric::BytesIn,
+                                            resp.content_length().unwrap_or(0) as i64,
+                                        );
+                                        self.rt.block_on(resp.text())
+                                    })
+                                    .map_err(reqwest_middleware::Error::from)
+                            }),
+                    )?;
+
+                    let json: JsonValue = serde_json::from_str(&body)?;
+                    if let Some(id) = json.get("id").and_then(|v| v.as_str()) {
+                        report_info(&format!("updated {} {}", self.obj, id));
+                    }
+
+                    set_stats_metadata(stats_metadata);
+
+                    return Ok(Some(body));
+                }
+                _ => unreachable!(),
+            }
+        }
+        Ok(None)
+    }
+
+    // convert the object in response body to a row with the given columns
+    fn body_to_row(
+        &self,
+        body: Option<String>,
+        columns: &[Column],
+    ) -> StripeFdwResult<Option<Row>> {
+        match body {
+            Some(body) => {
+                let (rows, _, _) = self.resp_to_rows(&self.obj, &body, columns)?;
+                Ok(rows.into_iter().next())
+            }
+            None => Ok(None),
+        }
+    }
 }
 
 impl ForeignDataWrapper<StripeFdwError> for StripeFdw {
@@ -648,42 +995,94 @@ impl ForeignDataWrapper<StripeFdwError> for StripeFdw {
             })
             .unwrap_or_else(|| "https://api.stripe.com/v1/".to_string());
         let api_version = server.options.get("api_version").map(|t| t.as_str());
-        let client = match server.options.get("api_key") {
-            Some(api_key) => Some(create_client(api_key, api_version)),
-            None => server
-                .options
+
+        // the API key in user mapping takes precedence, so each Postgres role
+        // can use its own key
+        let key_opts = server.credential_options(&["api_key", "api_key_id", "api_key_name"]);
+        let client = match key_opts.get("api_key") {
+            Some(api_key) => Some(create_client(api_key, api_version, &server.options)),
+            None => key_opts
                 .get("api_key_id")
-                .and_then(|key_id| get_vault_secret(key_id))
+                .and_then(|key_id| get_secret(&server.options, key_id))
                 .or_else(|| {
-                    server
-                        .options
+                    key_opts
                         .get("api_key_name")
-                        .and_then(|key_name| get_vault_secret_by_name(key_name))
+                        .and_then(|key_name| get_secret_by_name(&server.options, key_name))
                 })
-                .map(|api_key| create_client(&api_key, api_version))
-                .or_else(|| {
-                    report_error(
-                        pgrx::PgSqlErrorCode::ERRCODE_FDW_ERROR,
-                        "either api_key_id or api_key_name option is required",
-                    );
-                    None
-                }),
+                .map(|api_key| create_client(&api_key, api_version, &server.options))
+                .ok_or(StripeFdwError::ApiKeyNotFound)
+                .map(Some)?,
         }
         .transpose()?;
 
         stats::inc_stats(Self::FDW_NAME, stats::Metric::CreateTimes, 1);
 
+        let rt = create_async_runtime()?;
+
         Ok(StripeFdw {
-            rt: create_async_runtime()?,
+            prefetch: Prefetch::new(&rt),
+            rt,
             base_url: Url::parse(&base_url)?,
             client,
+            rate_limiter: RateLimiter::from_server(&server)?,
+            circuit_breaker: CircuitBreaker::from_server(&server)?,
             scan_result: None,
+            pagination: None,
             obj: String::default(),
             rowid_col: String::default(),
             iter_idx: 0,
         })
     }
 
+    fn capabilities() -> FdwCapabilities {
+        FdwCapabilities {
+            write: true,
+            qual_pushdown: true,
+            limit_pushdown: true,
+            ..Default::default()
+        }
+    }
+
+    fn json_path_quals() -> bool {
+        true
+    }
+
+    fn handled_quals(
+        &mut self,
+        quals: &[Qual],
+        options: &HashMap<String, String>,
+    ) -> StripeFdwResult<Vec<bool>> {
+        let obj = require_option("object", options)?;
+        let fields = get_pushdown_fields(obj)?;
+        Ok(quals
+            .iter()
+            .map(|qual| {
+                // subscription status filter accepts extra values like 'ended'
+                // which don't match any actual status, so it needs local re-check
+                // ref: https://docs.stripe.com/api/subscriptions/list#list_subscriptions-status
+                if obj == "subscriptions" && qual.field == "status" {
+                    return false;
+                }
+                is_pushed_down(qual, quals, &fields)
+            })
+            .collect())
+    }
+
+    fn explain(
+        &self,
+        quals: &[Qual],
+        _columns: &[Column],
+        _sorts: &[Sort],
+        _limit: &Option<Limit>,
+        options: &HashMap<String, String>,
+    ) -> StripeFdwResult<Vec<(String, String)>> {
+        let obj = require_option("object", options)?;
+        let url = self.build_url(obj, quals, 100, &None)?;
+        Ok(url
+            .map(|url| vec![("Remote URL".to_string(), url.to_string())])
+            .unwrap_or_default())
+    }
+
     fn begin_scan(
         &mut self,
         quals: &[Qual],
@@ -695,93 +1094,50 @@ impl ForeignDataWrapper<StripeFdwError> for StripeFdw {
         let obj = require_option("object", options)?;
 
         self.iter_idx = 0;
+        self.scan_result = None;
+        self.prefetch.cancel();
 
-        if let Some(client) = &self.client {
-            let page_size = 100; // maximum page size limit for Stripe API
+        if self.client.is_some() {
             let page_cnt = if let Some(limit) = limit {
                 if limit.count == 0 {
                     return Ok(());
                 }
-                (limit.offset + limit.count) / page_size + 1
+                (limit.offset + limit.count) / Self::PAGE_SIZE + 1
             } else {
                 // if no limit specified, fetch all records
                 i64::MAX
             };
-            let mut page = 0;
-            let mut result = Vec::new();
-            let mut cursor: Option<String> = None;
-            let mut stats_metadata = get_stats_metadata();
-
-            while page < page_cnt {
-                // build url
-                let url = self.build_url(obj, quals, page_size, &cursor)?;
-                let Some(url) = url else {
-                    return Ok(());
-                };
-
-                inc_stats_request_cnt(&mut stats_metadata)?;
-
-                // make api call
-                let body = self.rt.block_on(client.get(url).send()).and_then(|resp| {
-                    stats::inc_stats(
-                        Self::FDW_NAME,
-                        stats::Metric::BytesIn,
-                        resp.content_length().unwrap_or(0) as i64,
-                    );
-
-                    if resp.status() == StatusCode::NOT_FOUND {
-                        // if it is 404 error, we should treat it as an empty
-                        // result rather than a request error
-                        return Ok(String::new());
-                    }
 
-                    resp.error_for_status()
-                        .and_then(|resp| self.rt.block_on(resp.text()))
-                        .map_err(reqwest_middleware::Error::from)
-                })?;
-                if body.is_empty() {
-                    break;
-                }
-
-                // convert response body to rows
-                let (rows, starting_after, has_more) = self.resp_to_rows(obj, &body, columns)?;
-                if rows.is_empty() {
-                    break;
-                }
-                result.extend(rows);
-                match has_more {
-                    Some(has_more) => {
-                        if !has_more {
-                            break;
-                        }
-                    }
-                    None => break,
-                }
-                cursor = starting_after;
-
-                page += 1;
-            }
-
-            // save stats
-            stats::inc_stats(Self::FDW_NAME, stats::Metric::RowsIn, result.len() as i64);
-            stats::inc_stats(Self::FDW_NAME, stats::Metric::RowsOut, result.len() as i64);
-            set_stats_metadata(stats_metadata);
-
-            self.scan_result = Some(result);
+            // fetch the first page, the following pages are fetched in
+            // iter_scan() when the rows are consumed
+            self.scan_result = Some(Vec::new());
+            self.pagination = Some(Pagination {
+                obj: obj.to_owned(),
+                quals: quals.to_vec(),
+                columns: columns.to_vec(),
+                page_cnt,
+                page: 0,
+            });
+            self.prefetch_page(&None)?;
         }
 
         Ok(())
     }
 
     fn iter_scan(&mut self, row: &mut Row) -> StripeFdwResult<Option<()>> {
-        if let Some(ref mut result) = self.scan_result {
+        loop {
+            let Some(result) = &self.scan_result else {
+                return Ok(None);
+            };
             if self.iter_idx < result.len() {
                 row.replace_with(result[self.iter_idx].clone());
                 self.iter_idx += 1;
                 return Ok(Some(()));
             }
+            if !self.fetch_page()? {
+                return Ok(None);
+            }
         }
-        Ok(None)
     }
 
     fn re_scan(&mut self) -> StripeFdwResult<()> {
@@ -791,6 +1147,8 @@ impl ForeignDataWrapper<StripeFdwError> for StripeFdw {
 
     fn end_scan(&mut self) -> StripeFdwResult<()> {
         self.scan_result.take();
+        self.pagination.take();
+        self.prefetch.cancel();
         Ok(())
     }
 
@@ -801,88 +1159,30 @@ impl ForeignDataWrapper<StripeFdwError> for StripeFdw {
     }
 
     fn insert(&mut self, src: &Row) -> StripeFdwResult<()> {
-        if let Some(ref mut client) = self.client {
-            let url = self.base_url.join(&self.obj)?;
-            let body = row_to_body(src)?;
-            if body.is_null() {
-                return Ok(());
-            }
-
-            let mut stats_metadata = get_stats_metadata();
-
-            inc_stats_request_cnt(&mut stats_metadata)?;
-
-            // call Stripe API
-            let body = self
-                .rt
-                .block_on(client.post(url).form(&body).send())
-                .and_then(|resp| {
-                    resp.error_for_status()
-                        .and_then(|resp| {
-                            stats::inc_stats(
-                                Self::FDW_NAME,
-                                stats::Metric::BytesIn,
-                                resp.content_length().unwrap_or(0) as i64,
-                            );
-                            self.rt.block_on(resp.text())
-                        })
-                        .map_err(reqwest_middleware::Error::from)
-                })?;
-
-            let json: JsonValue = serde_json::from_str(&body)?;
-            if let Some(id) = json.get("id").and_then(|v| v.as_str()) {
-                report_info(&format!("inserted {} {}", self.obj, id));
-            }
-
-            set_stats_metadata(stats_metadata);
-        }
+        self.create_object(src)?;
         Ok(())
     }
 
-    fn update(&mut self, rowid: &Cell, new_row: &Row) -> StripeFdwResult<()> {
-        if let Some(ref mut client) = self.client {
-            let mut stats_metadata = get_stats_metadata();
-
-            match rowid {
-                Cell::String(rowid) => {
-                    let url = self.base_url.join(&format!("{}/", self.obj))?.join(rowid)?;
-                    let body = row_to_body(new_row)?;
-                    if body.is_null() {
-                        return Ok(());
-                    }
-
-                    inc_stats_request_cnt(&mut stats_metadata)?;
-
-                    // call Stripe API
-                    let body = self
-                        .rt
-                        .block_on(client.post(url).form(&body).send())
-                        .and_then(|resp| {
-                            resp.error_for_status()
-                                .and_then(|resp| {
-                                    stats::inc_stats(
-                                        Self::FDW_NAME,
-                                        stats::Metric::BytesIn,
-                                        resp.content_length().unwrap_or(0) as i64,
-                                    );
-                                    self.rt.block_on(resp.text())
-                                })
-                                .map_err(reqwest_middleware::Error::from)
-                        })?;
-
-                    let json: JsonValue = serde_json::from_str(&body)?;
-                    if let Some(id) = json.get("id").and_then(|v| v.as_str()) {
-                        report_info(&format!("updated {} {}", self.obj, id));
-                    }
-                }
-                _ => unreachable!(),
-            }
+    fn insert_returning(&mut self, src: &Row, columns: &[Column]) -> StripeFdwResult<Option<Row>> {
+        let body = self.create_object(src)?;
+        self.body_to_row(body, columns)
+    }
 
-            set_stats_metadata(stats_metadata);
-        }
+    fn update(&mut self, rowid: &Cell, new_row: &Row) -> StripeFdwResult<()> {
+        self.update_object(rowid, new_row)?;
         Ok(())
     }
 
+    fn update_returning(
+        &mut self,
+        rowid: &Cell,
+        new_row: &Row,
+        columns: &[Column],
+    ) -> StripeFdwResult<Option<Row>> {
+        let body = self.update_object(rowid, new_row)?;
+        self.body_to_row(body, columns)
+    }
+
     fn delete(&mut self, rowid: &Cell) -> StripeFdwResult<()> {
         if let Some(ref mut client) = self.client {
             let mut stats_metadata = get_stats_metadata();
@@ -892,23 +1192,27 @@ impl ForeignDataWrapper<StripeFdwError> for StripeFdw {
                     let url = self.base_url.join(&format!("{}/", self.obj))?.join(rowid)?;
 
                     inc_stats_request_cnt(&mut stats_metadata)?;
+                    self.circuit_breaker.check()?;
+                    self.rate_limiter.wait();
 
                     // call Stripe API
-                    let body = self
-                        .rt
-                        .block_on(client.delete(url).send())
-                        .and_then(|resp| {
-                            resp.error_for_status()
-                                .and_then(|resp| {
-                                    stats::inc_stats(
-                                        Self::FDW_NAME,
-                                        stats::Metric::BytesIn,
-                                        resp.content_length().unwrap_or(0) as i64,
-                                    );
-                                    self.rt.block_on(resp.text())
-                                })
-                                .map_err(reqwest_middleware::Error::from)
-                        })?;
+                    let body = record_request(
+                        &self.circuit_breaker,
+                        self.rt
+                            .block_on(client.delete(url).send())
+                            .and_then(|resp| {
+                                resp.error_for_status()
+                                    .and_then(|resp| {
+                                        stats::inc_stats(
+                                            Self::FDW_NAME,
+                                            stats::Metric::BytesIn,
+                                            resp.content_length().unwrap_or(0) as i64,
+                                        );
+                                        self.rt.block_on(resp.text())
+                                    })
+                                    .map_err(reqwest_middleware::Error::from)
+                            }),
+                    )?;
 
                     let json: JsonValue = serde_json::from_str(&body)?;
                     if let Some(id) = json.get("id").and_then(|v| v.as_str()) {
@@ -923,16 +1227,27 @@ impl ForeignDataWrapper<StripeFdwError> for StripeFdw {
         Ok(())
     }
 
-    fn validator(
-        options: Vec<Option<String>>,
-        catalog: Option<pg_sys::Oid>,
-    ) -> StripeFdwResult<()> {
-        if let Some(oid) = catalog {
-            if oid == FOREIGN_TABLE_RELATION_ID {
-                check_options_contain(&options, "object")?;
-            }
-        }
-
-        Ok(())
+    fn health_check(&mut self) -> StripeFdwResult<Option<String>> {
+        let Some(ref mut client) = self.client else {
+            return Ok(None);
+        };
+        let url = self.base_url.join("balance")?;
+
+        self.circuit_breaker.check()?;
+        self.rate_limiter.wait();
+
+        // retrieving the balance is the lightest authenticated request
+        let resp = record_request(
+            &self.circuit_breaker,
+            self.rt.block_on(client.get(url).send()).and_then(|resp| {
+                resp.error_for_status()
+                    .map_err(reqwest_middleware::Error::from)
+            }),
+        )?;
+        Ok(resp
+            .headers()
+            .get("stripe-version")
+            .and_then(|version| version.to_str().ok())
+            .map(|version| version.to_owned()))
     }
 }
@@ -714,6 +714,41 @@ mod tests {
                 vec![((("cus_QXg1o8vcGmoR32", 1000), "usd"), "draft")]
             );
 
+            // pushdown quals on attributes in 'attrs' column
+            let results = c
+                .select(
+                    "SELECT customer FROM stripe_invoices WHERE attrs->>'collection_method' = 'charge_automatically'",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("customer").unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec!["cus_QXg1o8vcGmoR32"]);
+
+            let results = c
+                .select(
+                    "SELECT customer FROM stripe_invoices WHERE attrs->>'collection_method' = 'send_invoice'",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("customer").unwrap())
+                .collect::<Vec<_>>();
+            assert!(results.is_empty());
+
+            // quals fully handled by Stripe are not re-checked locally
+            let results = c
+                .select(
+                    "EXPLAIN SELECT * FROM stripe_invoices WHERE customer = 'cus_QXg1o8vcGmoR32'",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("QUERY PLAN").unwrap())
+                .collect::<Vec<_>>();
+            assert!(!results.iter().any(|line| line.contains("Filter:")));
+
             let results = c
                 .select("SELECT * FROM stripe_payment_intents", None, None)
                 .unwrap()
@@ -890,6 +925,24 @@ mod tests {
                 )]
             );
 
+            // the object created by Stripe is returned by RETURNING clause
+            let inserted_id = c
+                .update(
+                    r#"
+                    INSERT INTO stripe_customers(email, name)
+                    VALUES ('test@test.com', 'test name')
+                    RETURNING id
+                    "#,
+                    None,
+                    None,
+                )
+                .unwrap()
+                .first()
+                .get_one::<String>()
+                .unwrap()
+                .unwrap();
+            assert!(inserted_id.starts_with("cus_"));
+
             // Stripe mock container is currently stateless, so we cannot test
             // data modify for now but will keep the code below for future use.
             //
@@ -978,4 +1031,890 @@ mod tests {
             */
         });
     }
+
+    #[pg_test]
+    fn stripe_user_mapping_and_column_path_test() {
+        Spi::connect(|mut c| {
+            c.update(
+                r#"CREATE FOREIGN DATA WRAPPER stripe_wrapper
+                         HANDLER stripe_fdw_handler VALIDATOR stripe_fdw_validator"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"CREATE SERVER my_stripe_server
+                         FOREIGN DATA WRAPPER stripe_wrapper
+                         OPTIONS (
+                           api_url 'http://localhost:12111/v1'
+                         )"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"CREATE USER MAPPING FOR CURRENT_USER
+                         SERVER my_stripe_server
+                         OPTIONS (
+                           api_key 'sk_test_51LUmojFkiV6mfx3cpEzG9VaxhA86SA4DIj3b62RKHnRC0nhPp2JBbAmQ1izsX9RKD8rlzvw2xpY54AwZtXmWciif00Qi8J0w3O'
+                         )"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"
+                CREATE FOREIGN TABLE stripe_customers (
+                  id text,
+                  customer_id text OPTIONS (path 'id'),
+                  object_type text OPTIONS (path 'object'),
+                  attrs jsonb
+                )
+                SERVER my_stripe_server
+                OPTIONS (
+                    object 'customers'
+                  )
+             "#,
+                None,
+                None,
+            )
+            .unwrap();
+
+            let results = c
+                .select(
+                    "SELECT customer_id, object_type FROM stripe_customers",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .map(|r| {
+                    (
+                        r.get_by_name::<&str, _>("customer_id").unwrap().unwrap(),
+                        r.get_by_name::<&str, _>("object_type").unwrap().unwrap(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec![("cus_QXg1o8vcGmoR32", "customer")]);
+        });
+    }
+
+    #[pg_test]
+    fn stripe_or_conditions_test() {
+        Spi::connect(|mut c| {
+            c.update(
+                r#"CREATE FOREIGN DATA WRAPPER stripe_wrapper
+                         HANDLER stripe_fdw_handler VALIDATOR stripe_fdw_validator"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"CREATE SERVER my_stripe_server
+                         FOREIGN DATA WRAPPER stripe_wrapper
+                         OPTIONS (
+                           api_url 'http://localhost:12111/v1',
+                           api_key 'sk_test_51LUmojFkiV6mfx3cpEzG9VaxhA86SA4DIj3b62RKHnRC0nhPp2JBbAmQ1izsX9RKD8rlzvw2xpY54AwZtXmWciif00Qi8J0w3O'
+                         )"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"
+                CREATE FOREIGN TABLE stripe_customers (
+                  id text,
+                  email text,
+                  attrs jsonb
+                )
+                SERVER my_stripe_server
+                OPTIONS (
+                    object 'customers'
+                  )
+             "#,
+                None,
+                None,
+            )
+            .unwrap();
+
+            // the OR conditions are not passed to the FDW, they are filtered
+            // locally by Postgres
+            let results = c
+                .select(
+                    "SELECT id FROM stripe_customers
+                     WHERE id = 'cus_QXg1o8vcGmoR32' OR email = 'nobody@test.com'",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("id").unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec!["cus_QXg1o8vcGmoR32"]);
+
+            // the id lookup still gets the id condition as its only qual
+            let results = c
+                .select(
+                    "SELECT id FROM stripe_customers
+                     WHERE id = 'cus_QXg1o8vcGmoR32'
+                       AND (email IS NULL OR email <> 'nobody@test.com')",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("id").unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec!["cus_QXg1o8vcGmoR32"]);
+        });
+    }
+
+    #[pg_test]
+    fn stripe_composite_column_test() {
+        Spi::connect(|mut c| {
+            c.update(
+                r#"CREATE FOREIGN DATA WRAPPER stripe_wrapper
+                         HANDLER stripe_fdw_handler VALIDATOR stripe_fdw_validator"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"CREATE SERVER my_stripe_server
+                         FOREIGN DATA WRAPPER stripe_wrapper
+                         OPTIONS (
+                           api_url 'http://localhost:12111/v1',
+                           api_key 'sk_test_51LUmojFkiV6mfx3cpEzG9VaxhA86SA4DIj3b62RKHnRC0nhPp2JBbAmQ1izsX9RKD8rlzvw2xpY54AwZtXmWciif00Qi8J0w3O'
+                         )"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"CREATE TYPE stripe_charge_outcome AS (
+                     network_status text,
+                     risk_level text,
+                     type text
+                   )"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"
+                CREATE FOREIGN TABLE stripe_charges (
+                  id text,
+                  outcome stripe_charge_outcome OPTIONS (path 'outcome'),
+                  attrs jsonb
+                )
+                SERVER my_stripe_server
+                OPTIONS (
+                    object 'charges'
+                  )
+             "#,
+                None,
+                None,
+            )
+            .unwrap();
+
+            let results = c
+                .select(
+                    "SELECT (outcome).risk_level, (outcome).type FROM stripe_charges",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .map(|r| {
+                    (
+                        r.get_by_name::<&str, _>("risk_level").unwrap().unwrap(),
+                        r.get_by_name::<&str, _>("type").unwrap().unwrap(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec![("normal", "authorized")]);
+        });
+    }
+
+    #[pg_test]
+    fn stripe_scan_limits_test() {
+        Spi::connect(|mut c| {
+            c.update(
+                r#"CREATE FOREIGN DATA WRAPPER stripe_wrapper
+                         HANDLER stripe_fdw_handler VALIDATOR stripe_fdw_validator"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"CREATE SERVER my_stripe_server
+                         FOREIGN DATA WRAPPER stripe_wrapper
+                         OPTIONS (
+                           api_url 'http://localhost:12111/v1',
+                           api_key 'sk_test_51LUmojFkiV6mfx3cpEzG9VaxhA86SA4DIj3b62RKHnRC0nhPp2JBbAmQ1izsX9RKD8rlzvw2xpY54AwZtXmWciif00Qi8J0w3O'
+                         )"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"
+                CREATE FOREIGN TABLE stripe_customers_max_rows (
+                  id text,
+                  email text
+                )
+                SERVER my_stripe_server
+                OPTIONS (
+                    object 'customers',
+                    max_rows '0',
+                    on_limit_exceeded 'truncate'
+                  )
+             "#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"
+                CREATE FOREIGN TABLE stripe_customers_max_bytes (
+                  id text,
+                  email text
+                )
+                SERVER my_stripe_server
+                OPTIONS (
+                    object 'customers',
+                    max_result_bytes '1',
+                    on_limit_exceeded 'truncate'
+                  )
+             "#,
+                None,
+                None,
+            )
+            .unwrap();
+
+            let results = c
+                .select("SELECT * FROM stripe_customers_max_rows", None, None)
+                .unwrap()
+                .collect::<Vec<_>>();
+            assert!(results.is_empty());
+
+            let results = c
+                .select("SELECT * FROM stripe_customers_max_bytes", None, None)
+                .unwrap()
+                .collect::<Vec<_>>();
+            assert!(results.is_empty());
+        });
+    }
+
+    #[pg_test]
+    fn stripe_rate_limit_test() {
+        Spi::connect(|mut c| {
+            c.update(
+                r#"CREATE FOREIGN DATA WRAPPER stripe_wrapper
+                         HANDLER stripe_fdw_handler VALIDATOR stripe_fdw_validator"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"CREATE SERVER my_stripe_server
+                         FOREIGN DATA WRAPPER stripe_wrapper
+                         OPTIONS (
+                           api_url 'http://localhost:12111/v1',
+                           api_key 'sk_test_51LUmojFkiV6mfx3cpEzG9VaxhA86SA4DIj3b62RKHnRC0nhPp2JBbAmQ1izsX9RKD8rlzvw2xpY54AwZtXmWciif00Qi8J0w3O',
+                           requests_per_second '2'
+                         )"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"
+                CREATE FOREIGN TABLE stripe_customers (
+                  id text,
+                  email text
+                )
+                SERVER my_stripe_server
+                OPTIONS (
+                    object 'customers'
+                  )
+             "#,
+                None,
+                None,
+            )
+            .unwrap();
+
+            // the burst allows 2 requests, the 3rd one has to wait for 0.5s
+            let start = std::time::Instant::now();
+            for _ in 0..3 {
+                let results = c
+                    .select("SELECT * FROM stripe_customers", None, None)
+                    .unwrap()
+                    .collect::<Vec<_>>();
+                assert!(!results.is_empty());
+            }
+            assert!(start.elapsed() >= std::time::Duration::from_millis(400));
+        });
+    }
+
+    #[pg_test]
+    fn stripe_cache_test() {
+        Spi::connect(|mut c| {
+            c.update(
+                r#"CREATE FOREIGN DATA WRAPPER stripe_wrapper
+                         HANDLER stripe_fdw_handler VALIDATOR stripe_fdw_validator"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"CREATE SERVER my_stripe_server
+                         FOREIGN DATA WRAPPER stripe_wrapper
+                         OPTIONS (
+                           api_url 'http://localhost:12111/v1',
+                           api_key 'sk_test_51LUmojFkiV6mfx3cpEzG9VaxhA86SA4DIj3b62RKHnRC0nhPp2JBbAmQ1izsX9RKD8rlzvw2xpY54AwZtXmWciif00Qi8J0w3O',
+                           requests_per_second '1'
+                         )"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"
+                CREATE FOREIGN TABLE stripe_customers (
+                  id text,
+                  email text
+                )
+                SERVER my_stripe_server
+                OPTIONS (
+                    object 'customers',
+                    cache_ttl '60'
+                  )
+             "#,
+                None,
+                None,
+            )
+            .unwrap();
+
+            let query = "SELECT id FROM stripe_customers ORDER BY id";
+            let expected = c
+                .select(query, None, None)
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("id").unwrap())
+                .collect::<Vec<_>>();
+            assert!(!expected.is_empty());
+
+            // the cached rows are returned without waiting for rate limit
+            let start = std::time::Instant::now();
+            for _ in 0..2 {
+                let results = c
+                    .select(query, None, None)
+                    .unwrap()
+                    .filter_map(|r| r.get_by_name::<&str, _>("id").unwrap())
+                    .collect::<Vec<_>>();
+                assert_eq!(results, expected);
+            }
+            assert!(start.elapsed() < std::time::Duration::from_millis(900));
+
+            // the rows are fetched from remote again after invalidation
+            c.update(
+                "SELECT wrappers_cache_invalidate('stripe_customers'::regclass)",
+                None,
+                None,
+            )
+            .unwrap();
+            let start = std::time::Instant::now();
+            let results = c
+                .select(query, None, None)
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("id").unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(results, expected);
+            assert!(start.elapsed() >= std::time::Duration::from_millis(900));
+        });
+    }
+
+    #[pg_test]
+    fn stripe_server_stats_test() {
+        Spi::connect(|mut c| {
+            c.update(
+                r#"CREATE FOREIGN DATA WRAPPER stripe_wrapper
+                         HANDLER stripe_fdw_handler VALIDATOR stripe_fdw_validator"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"CREATE SERVER my_stripe_server
+                         FOREIGN DATA WRAPPER stripe_wrapper
+                         OPTIONS (
+                           api_url 'http://localhost:12111/v1',
+                           api_key 'sk_test_51LUmojFkiV6mfx3cpEzG9VaxhA86SA4DIj3b62RKHnRC0nhPp2JBbAmQ1izsX9RKD8rlzvw2xpY54AwZtXmWciif00Qi8J0w3O'
+                         )"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"
+                CREATE FOREIGN TABLE stripe_customers (
+                  id text,
+                  email text
+                )
+                SERVER my_stripe_server
+                OPTIONS (
+                    object 'customers'
+                  )
+             "#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update("SELECT wrappers_stats_reset()", None, None)
+                .unwrap();
+
+            let rows = c
+                .select("SELECT * FROM stripe_customers", None, None)
+                .unwrap()
+                .len() as i64;
+            assert!(rows > 0);
+
+            let stats = c
+                .select(
+                    "SELECT fdw_name, scans, rows_fetched, remote_requests, errors
+                     FROM wrappers_stats() WHERE server_name = 'my_stripe_server'",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .first();
+            assert_eq!(
+                stats.get_by_name::<&str, _>("fdw_name").unwrap(),
+                Some("stripe_wrapper")
+            );
+            assert_eq!(stats.get_by_name::<i64, _>("scans").unwrap(), Some(1));
+            assert_eq!(
+                stats.get_by_name::<i64, _>("rows_fetched").unwrap(),
+                Some(rows)
+            );
+            assert!(stats.get_by_name::<i64, _>("remote_requests").unwrap() >= Some(1));
+            assert_eq!(stats.get_by_name::<i64, _>("errors").unwrap(), Some(0));
+        });
+    }
+
+    #[pg_test]
+    fn stripe_health_check_test() {
+        Spi::connect(|mut c| {
+            c.update(
+                r#"CREATE FOREIGN DATA WRAPPER stripe_wrapper
+                         HANDLER stripe_fdw_handler VALIDATOR stripe_fdw_validator"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"CREATE SERVER my_stripe_server
+                         FOREIGN DATA WRAPPER stripe_wrapper
+                         OPTIONS (
+                           api_url 'http://localhost:12111/v1',
+                           api_key 'sk_test_51LUmojFkiV6mfx3cpEzG9VaxhA86SA4DIj3b62RKHnRC0nhPp2JBbAmQ1izsX9RKD8rlzvw2xpY54AwZtXmWciif00Qi8J0w3O'
+                         )"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"CREATE SERVER my_stripe_server_down
+                         FOREIGN DATA WRAPPER stripe_wrapper
+                         OPTIONS (
+                           api_url 'http://localhost:1/v1',
+                           api_key 'sk_test_51LUmojFkiV6mfx3cpEzG9VaxhA86SA4DIj3b62RKHnRC0nhPp2JBbAmQ1izsX9RKD8rlzvw2xpY54AwZtXmWciif00Qi8J0w3O'
+                         )"#,
+                None,
+                None,
+            )
+            .unwrap();
+
+            let check = c
+                .select(
+                    "SELECT * FROM wrappers_check_server('my_stripe_server')",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .first();
+            assert_eq!(check.get_by_name::<&str, _>("status").unwrap(), Some("ok"));
+            assert!(check.get_by_name::<f64, _>("latency_ms").unwrap() >= Some(0.0));
+            assert_eq!(check.get_by_name::<&str, _>("message").unwrap(), None);
+
+            let check = c
+                .select(
+                    "SELECT * FROM wrappers_check_server('my_stripe_server_down')",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .first();
+            assert_eq!(
+                check.get_by_name::<&str, _>("status").unwrap(),
+                Some("error")
+            );
+            assert!(check.get_by_name::<&str, _>("message").unwrap().is_some());
+        });
+    }
+
+    #[pg_test]
+    fn stripe_dry_run_test() {
+        Spi::connect(|mut c| {
+            c.update(
+                r#"CREATE FOREIGN DATA WRAPPER stripe_wrapper
+                         HANDLER stripe_fdw_handler VALIDATOR stripe_fdw_validator"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"CREATE SERVER my_stripe_server
+                         FOREIGN DATA WRAPPER stripe_wrapper
+                         OPTIONS (
+                           api_url 'http://localhost:12111/v1',
+                           api_key 'sk_test_51LUmojFkiV6mfx3cpEzG9VaxhA86SA4DIj3b62RKHnRC0nhPp2JBbAmQ1izsX9RKD8rlzvw2xpY54AwZtXmWciif00Qi8J0w3O'
+                         )"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"
+                CREATE FOREIGN TABLE stripe_customers (
+                  id text,
+                  email text
+                )
+                SERVER my_stripe_server
+                OPTIONS (
+                    object 'customers'
+                  )
+             "#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update("SELECT wrappers_stats_reset()", None, None)
+                .unwrap();
+
+            // no request is sent and no rows are returned in dry run
+            c.update("SET wrappers.dry_run = on", None, None).unwrap();
+            let results = c
+                .select(
+                    "SELECT * FROM stripe_customers WHERE email = 'test@test.com'",
+                    None,
+                    None,
+                )
+                .unwrap();
+            assert_eq!(results.len(), 0);
+            let requests = c
+                .select(
+                    "SELECT coalesce(sum(remote_requests), 0)::bigint FROM wrappers_stats()",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .first()
+                .get_one::<i64>()
+                .unwrap();
+            assert_eq!(requests, Some(0));
+
+            c.update("SET wrappers.dry_run = off", None, None).unwrap();
+            let results = c
+                .select("SELECT * FROM stripe_customers", None, None)
+                .unwrap();
+            assert!(!results.is_empty());
+        });
+    }
+
+    #[pg_test]
+    fn stripe_column_mask_test() {
+        Spi::connect(|mut c| {
+            c.update(
+                r#"CREATE FOREIGN DATA WRAPPER stripe_wrapper
+                         HANDLER stripe_fdw_handler VALIDATOR stripe_fdw_validator"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"CREATE SERVER my_stripe_server
+                         FOREIGN DATA WRAPPER stripe_wrapper
+                         OPTIONS (
+                           api_url 'http://localhost:12111/v1',
+                           api_key 'sk_test_51LUmojFkiV6mfx3cpEzG9VaxhA86SA4DIj3b62RKHnRC0nhPp2JBbAmQ1izsX9RKD8rlzvw2xpY54AwZtXmWciif00Qi8J0w3O'
+                         )"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update("CREATE ROLE stripe_mask_reader", None, None)
+                .unwrap();
+            c.update(
+                r#"
+                CREATE FOREIGN TABLE stripe_customers (
+                  id text,
+                  partial_id text OPTIONS (path 'id', mask 'partial'),
+                  hashed_id text OPTIONS (path 'id', mask 'hash'),
+                  null_id text OPTIONS (path 'id', mask 'null'),
+                  unmasked_id text OPTIONS (path 'id', mask 'null', unmask_roles 'stripe_mask_reader')
+                )
+                SERVER my_stripe_server
+                OPTIONS (
+                    object 'customers'
+                  )
+             "#,
+                None,
+                None,
+            )
+            .unwrap();
+
+            let results = c
+                .select(
+                    "SELECT id, partial_id, hashed_id, null_id, unmasked_id FROM stripe_customers",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .map(|r| {
+                    (
+                        r.get::<String>(1).unwrap().unwrap(),
+                        r.get::<String>(2).unwrap().unwrap(),
+                        r.get::<String>(3).unwrap().unwrap(),
+                        r.get::<String>(4).unwrap(),
+                        r.get::<String>(5).unwrap(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            assert!(!results.is_empty());
+
+            // conditions are checked on the masked values
+            let matched = c
+                .select(
+                    "SELECT * FROM stripe_customers WHERE partial_id = $1",
+                    None,
+                    Some(vec![(
+                        PgBuiltInOids::TEXTOID.oid(),
+                        results[0].0.clone().into_datum(),
+                    )]),
+                )
+                .unwrap();
+            assert_eq!(matched.len(), 0);
+
+            for (id, partial_id, hashed_id, null_id, unmasked_id) in results {
+                assert_eq!(partial_id.len(), id.len());
+                assert!(partial_id.starts_with('*'));
+                assert_eq!(&partial_id[id.len() - 4..], &id[id.len() - 4..]);
+                assert_eq!(hashed_id.len(), 64);
+                assert_ne!(hashed_id, id);
+                assert_eq!(null_id, None);
+                // superuser has the privileges of all roles
+                assert_eq!(unmasked_id, Some(id));
+            }
+        });
+    }
+
+    #[pg_test]
+    fn stripe_capabilities_test() {
+        Spi::connect(|c| {
+            let caps = c
+                .select(
+                    "SELECT * FROM wrappers_fdw_capabilities() WHERE name = 'StripeFdw'",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .first();
+            assert!(caps.get_by_name::<&str, _>("version").unwrap().is_some());
+            assert_eq!(caps.get_by_name::<bool, _>("read").unwrap(), Some(true));
+            assert_eq!(caps.get_by_name::<bool, _>("write").unwrap(), Some(true));
+            assert_eq!(
+                caps.get_by_name::<bool, _>("qual_pushdown").unwrap(),
+                Some(true)
+            );
+            assert_eq!(
+                caps.get_by_name::<bool, _>("aggregate_pushdown").unwrap(),
+                Some(false)
+            );
+        });
+    }
+
+    #[pg_test]
+    fn stripe_meta_options_test() {
+        Spi::connect(|c| {
+            let opts = c
+                .select(
+                    "SELECT level, required, example
+                     FROM stripe_fdw_options()
+                     WHERE name = 'object'",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .first();
+            assert_eq!(opts.get::<&str>(1).unwrap(), Some("table"));
+            assert_eq!(opts.get::<bool>(2).unwrap(), Some(true));
+            assert_eq!(opts.get::<&str>(3).unwrap(), Some("customers"));
+        });
+    }
+
+    #[pg_test]
+    fn stripe_validate_server_test() {
+        Spi::connect(|mut c| {
+            // the validator is added after the objects are created, like the
+            // options changed by an extension upgrade
+            c.update(
+                r#"CREATE FOREIGN DATA WRAPPER stripe_wrapper
+                         HANDLER stripe_fdw_handler"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"CREATE SERVER my_stripe_server
+                         FOREIGN DATA WRAPPER stripe_wrapper
+                         OPTIONS (
+                           api_url 'http://localhost:12111/v1',
+                           api_key 'sk_test_51LUmojFkiV6mfx3cpEzG9VaxhA86SA4DIj3b62RKHnRC0nhPp2JBbAmQ1izsX9RKD8rlzvw2xpY54AwZtXmWciif00Qi8J0w3O'
+                         )"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"CREATE FOREIGN TABLE stripe_customers (id text)
+                         SERVER my_stripe_server"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                "ALTER FOREIGN DATA WRAPPER stripe_wrapper VALIDATOR stripe_fdw_validator",
+                None,
+                None,
+            )
+            .unwrap();
+
+            let results = c
+                .select(
+                    "SELECT object_type, option, severity, action, replacement
+                     FROM wrappers_validate_server('my_stripe_server')
+                     ORDER BY object_type",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .map(|r| {
+                    (
+                        r.get::<String>(1).unwrap().unwrap(),
+                        r.get::<String>(2).unwrap(),
+                        r.get::<String>(3).unwrap().unwrap(),
+                        r.get::<String>(4).unwrap(),
+                        r.get::<String>(5).unwrap(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(
+                results,
+                vec![
+                    (
+                        "foreign table".to_string(),
+                        None,
+                        "error".to_string(),
+                        Some("fix".to_string()),
+                        None
+                    ),
+                    (
+                        "server".to_string(),
+                        Some("api_key".to_string()),
+                        "deprecated".to_string(),
+                        Some("replace".to_string()),
+                        Some("api_key_id".to_string())
+                    ),
+                ]
+            );
+        });
+    }
+
+    #[pg_test]
+    fn stripe_sync_test() {
+        Spi::connect(|mut c| {
+            c.update(
+                r#"CREATE FOREIGN DATA WRAPPER stripe_wrapper
+                         HANDLER stripe_fdw_handler VALIDATOR stripe_fdw_validator"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"CREATE SERVER my_stripe_server
+                         FOREIGN DATA WRAPPER stripe_wrapper
+                         OPTIONS (
+                           api_url 'http://localhost:12111/v1',
+                           api_key 'sk_test_51LUmojFkiV6mfx3cpEzG9VaxhA86SA4DIj3b62RKHnRC0nhPp2JBbAmQ1izsX9RKD8rlzvw2xpY54AwZtXmWciif00Qi8J0w3O'
+                         )"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"
+                CREATE FOREIGN TABLE stripe_customers (
+                  id text,
+                  email text,
+                  created timestamp
+                )
+                SERVER my_stripe_server
+                OPTIONS (
+                    object 'customers'
+                  )
+             "#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                "CREATE TABLE local_customers (id text primary key, email text, created timestamp)",
+                None,
+                None,
+            )
+            .unwrap();
+
+            let remote = c
+                .select("SELECT * FROM stripe_customers", None, None)
+                .unwrap()
+                .len() as i64;
+            let synced = c
+                .select(
+                    "SELECT wrappers_sync('stripe_customers'::regclass, 'local_customers'::regclass)",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .first()
+                .get_one::<i64>()
+                .unwrap();
+            assert_eq!(synced, Some(remote));
+            let local = c
+                .select("SELECT * FROM local_customers", None, None)
+                .unwrap()
+                .len() as i64;
+            assert_eq!(local, remote);
+
+            // the rows already synced are skipped in append mode
+            let synced = c
+                .select(
+                    "SELECT wrappers_sync('stripe_customers'::regclass, 'local_customers'::regclass,
+                                          'append')",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .first()
+                .get_one::<i64>()
+                .unwrap();
+            assert_eq!(synced, Some(0));
+        });
+    }
 }
@@ -1,12 +1,12 @@
+use super::super::bindings::supabase::wrappers::http::{self, HostResponseStream, ResponseStream};
+use super::FdwHost;
+use crate::fdw::http_client::{create_http_client, record_request};
 use reqwest::{
     header::{HeaderMap, HeaderName, HeaderValue},
     Response, StatusCode,
 };
-use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
-use reqwest_retry::{policies::ExponentialBackoff, RetryTransientMiddleware};
-
-use super::super::bindings::supabase::wrappers::http;
-use super::FdwHost;
+use wasmtime::component::Resource;
+use wasmtime::Result as WasmResult;
 
 // convert guest headers to HeaderMap
 fn guest_to_header_map(headers: &http::Headers) -> HeaderMap {
@@ -33,41 +33,48 @@ fn header_map_to_guest(headers: &HeaderMap) -> http::Headers {
         .collect()
 }
 
-// create http request client with backoff retry
-fn create_client(req: &http::Request) -> Result<ClientWithMiddleware, String> {
-    let headers = guest_to_header_map(&req.headers);
-    let client = reqwest::Client::builder()
-        .default_headers(headers)
-        .gzip(true)
-        .build()
-        .map_err(|e| e.to_string())?;
-    let retry_policy = ExponentialBackoff::builder().build_with_max_retries(3);
-    Ok(ClientBuilder::new(client)
-        .with(RetryTransientMiddleware::new_with_policy(retry_policy))
-        .build())
-}
-
 impl FdwHost {
+    // send a http request and return the response before reading its body
+    fn send_request(&mut self, req: http::Request) -> Result<Response, http::HttpError> {
+        let headers = guest_to_header_map(&req.headers);
+        let client = create_http_client(&self.svr_opts, headers).map_err(|e| e.to_string())?;
+        self.circuit_breaker.check().map_err(|e| e.to_string())?;
+        self.rate_limiter.wait();
+        let resp = self.rt.block_on(
+            match req.method {
+                http::Method::Get => client.get(req.url),
+                http::Method::Post => client.post(req.url),
+                http::Method::Put => client.put(req.url),
+                http::Method::Patch => client.patch(req.url),
+                http::Method::Delete => client.delete(req.url),
+            }
+            .body(req.body)
+            .send(),
+        );
+        // the guest handles HTTP error status itself, so count HTTP 5xx
+        // response as failure here
+        let resp = match resp {
+            Ok(resp) if resp.status().is_server_error() => {
+                self.circuit_breaker.record_failure();
+                resp
+            }
+            resp => record_request(&self.circuit_breaker, resp).map_err(|e| e.to_string())?,
+        };
+        Ok(resp)
+    }
+
     // make a http request
     fn http_request(&mut self, req: http::Request) -> http::HttpResult {
-        let client = create_client(&req)?;
-        let resp = self
-            .rt
-            .block_on(
-                match req.method {
-                    http::Method::Get => client.get(req.url),
-                    http::Method::Post => client.post(req.url),
-                    http::Method::Put => client.put(req.url),
-                    http::Method::Patch => client.patch(req.url),
-                    http::Method::Delete => client.delete(req.url),
-                }
-                .body(req.body)
-                .send(),
-            )
-            .map_err(|e| e.to_string())?;
+        let resp = self.send_request(req)?;
         self.convert_to_guest_response(resp)
     }
 
+    fn response_stream(&mut self, rep: &Resource<ResponseStream>) -> &mut Response {
+        self.streams
+            .get_mut(&rep.rep())
+            .expect("response stream should exist")
+    }
+
     // convert reqwest response to guest response
     fn convert_to_guest_response(&mut self, resp: Response) -> http::HttpResult {
         let url = resp.url().to_string();
@@ -110,6 +117,16 @@ impl http::Host for FdwHost {
         self.http_request(req)
     }
 
+    fn send_stream(
+        &mut self,
+        req: http::Request,
+    ) -> Result<Resource<ResponseStream>, http::HttpError> {
+        let resp = self.send_request(req)?;
+        self.next_stream_id += 1;
+        self.streams.insert(self.next_stream_id, resp);
+        Ok(Resource::new_own(self.next_stream_id))
+    }
+
     fn error_for_status(&mut self, resp: http::Response) -> Result<(), http::HttpError> {
         let status = StatusCode::from_u16(resp.status_code).map_err(|e| e.to_string())?;
         if status.is_client_error() || status.is_server_error() {
@@ -122,3 +139,33 @@ impl http::Host for FdwHost {
         }
     }
 }
+
+impl HostResponseStream for FdwHost {
+    fn url(&mut self, rep: Resource<ResponseStream>) -> String {
+        self.response_stream(&rep).url().to_string()
+    }
+
+    fn status_code(&mut self, rep: Resource<ResponseStream>) -> u16 {
+        self.response_stream(&rep).status().as_u16()
+    }
+
+    fn headers(&mut self, rep: Resource<ResponseStream>) -> http::Headers {
+        header_map_to_guest(self.response_stream(&rep).headers())
+    }
+
+    fn read(&mut self, rep: Resource<ResponseStream>) -> Result<Option<Vec<u8>>, http::HttpError> {
+        let resp = self
+            .streams
+            .get_mut(&rep.rep())
+            .expect("response stream should exist");
+        self.rt
+            .block_on(resp.chunk())
+            .map(|chunk| chunk.map(|chunk| chunk.to_vec()))
+            .map_err(|e| e.to_string())
+    }
+
+    fn drop(&mut self, rep: Resource<ResponseStream>) -> WasmResult<()> {
+        self.streams.remove(&rep.rep());
+        Ok(())
+    }
+}
@@ -1,4 +1,5 @@
-use jwt_simple::prelude::*;
+use std::time::Duration;
+use supabase_wrappers::prelude::{JwtClaims, JwtKey};
 
 use super::super::bindings::supabase::wrappers::jwt;
 use super::FdwHost;
@@ -11,24 +12,21 @@ impl jwt::Host for FdwHost {
         key: String,
         ttl_hours: u32,
     ) -> jwt::JwtResult {
-        let mut claims = Claims::create(Duration::from_hours(ttl_hours as u64));
+        let mut claims = JwtClaims::default();
         for (claim, value) in payload {
             match claim.as_str() {
                 "iss" => {
-                    claims = claims.with_issuer(value);
+                    claims.issuer = Some(value);
                 }
                 "sub" => {
-                    claims = claims.with_subject(value);
+                    claims.subject = Some(value);
                 }
                 _ => return Err(format!("claim {} not implemented", claim)),
             }
         }
 
-        match algo.as_str() {
-            "RS256" => RS256KeyPair::from_pem(&key),
-            _ => return Err(format!("algorithm {} not implemented", algo)),
-        }
-        .and_then(|keypair| keypair.sign(claims))
-        .map_err(|e| e.to_string())
+        JwtKey::new(&algo, &key)
+            .and_then(|key| key.sign(&claims, Duration::from_secs(ttl_hours as u64 * 3600)))
+            .map_err(|e| e.to_string())
     }
 }
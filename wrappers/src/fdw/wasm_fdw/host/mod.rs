@@ -24,13 +24,21 @@ use super::bindings::{
 #[derive(Debug)]
 pub(super) struct FdwHost {
     pub rt: Runtime,
+    pub server_oid: pg_sys::Oid,
     pub svr_opts: HashMap<String, String>,
+    pub rate_limiter: RateLimiter,
+    pub circuit_breaker: CircuitBreaker,
     pub tbl_opts: HashMap<String, String>,
     pub row: Row,
     pub columns: Vec<Column>,
     pub quals: Vec<Qual>,
     pub sorts: Vec<Sort>,
     pub limit: Option<Limit>,
+
+    // the HTTP responses being read by the guest in chunks, by their
+    // resource rep
+    pub streams: HashMap<u32, reqwest::Response>,
+    pub next_stream_id: u32,
 }
 
 impl FdwHost {
@@ -46,13 +54,18 @@ impl FdwHost {
     pub(super) fn new(rt: Runtime) -> Self {
         Self {
             rt,
+            server_oid: pg_sys::InvalidOid,
             svr_opts: HashMap::new(),
+            rate_limiter: RateLimiter::default(),
+            circuit_breaker: CircuitBreaker::default(),
             tbl_opts: HashMap::new(),
             row: Row::default(),
             columns: Vec::new(),
             quals: Vec::new(),
             sorts: Vec::new(),
             limit: None,
+            streams: HashMap::new(),
+            next_stream_id: 0,
         }
     }
 }
@@ -271,6 +284,35 @@ impl HostOptions for FdwHost {
         require_option_or(&key, opts, &default).to_owned()
     }
 
+    fn get_secret(
+        &mut self,
+        rep: Resource<Options>,
+        key: String,
+    ) -> Result<Option<String>, GuestFdwError> {
+        let opts = match rep.rep() {
+            Self::SVR_OPTS_REP => self.svr_opts.clone(),
+            Self::TBL_OPTS_REP => {
+                // the secret provider is always set in the server options
+                let mut opts = self.tbl_opts.clone();
+                if let Some(provider) = self.svr_opts.get("secret_provider") {
+                    opts.insert("secret_provider".to_string(), provider.to_owned());
+                }
+                opts
+            }
+            _ => unreachable!(),
+        };
+        secret_option(&key, &opts).map_err(|e| e.to_string())
+    }
+
+    fn require_secret(
+        &mut self,
+        rep: Resource<Options>,
+        key: String,
+    ) -> Result<String, GuestFdwError> {
+        self.get_secret(rep, key.clone())?
+            .ok_or_else(|| OptionsError::OptionNameNotFound(key).to_string())
+    }
+
     fn drop(&mut self, _rep: Resource<Options>) -> WasmResult<()> {
         Ok(())
     }
@@ -1,8 +1,12 @@
 use pgrx::prelude::PgSqlErrorCode;
+use std::time::Duration;
 
 use supabase_wrappers::prelude::*;
 
-use super::super::bindings::supabase::wrappers::{types::Cell as GuestCell, utils};
+use super::super::bindings::supabase::wrappers::{
+    types::Cell as GuestCell,
+    utils::{self, LogLevel},
+};
 use super::FdwHost;
 
 impl utils::Host for FdwHost {
@@ -22,6 +26,19 @@ impl utils::Host for FdwHost {
         report_error(PgSqlErrorCode::ERRCODE_FDW_ERROR, &msg);
     }
 
+    fn log(&mut self, level: LogLevel, msg: String) {
+        match level {
+            LogLevel::Debug => {
+                if debug_level() > 0 {
+                    log_debug1(&msg);
+                }
+            }
+            LogLevel::Info => report_info(&msg),
+            LogLevel::Notice => report_notice(&msg),
+            LogLevel::Warning => report_warning(&msg),
+        }
+    }
+
     fn cell_to_string(&mut self, cell: Option<GuestCell>) -> String {
         match cell {
             Some(c) => Cell::try_from(c)
@@ -32,6 +49,19 @@ impl utils::Host for FdwHost {
     }
 
     fn get_vault_secret(&mut self, secret_id: String) -> Option<String> {
-        get_vault_secret(&secret_id)
+        get_secret(&self.svr_opts, &secret_id)
+    }
+
+    fn cache_get(&mut self, key: String) -> Option<String> {
+        cache_get(self.server_oid, &key)
+    }
+
+    fn cache_set(&mut self, key: String, value: String, ttl: u32) {
+        cache_set(
+            self.server_oid,
+            &key,
+            value,
+            Duration::from_secs(ttl.into()),
+        );
     }
 }
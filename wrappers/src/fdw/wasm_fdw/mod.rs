@@ -1,6 +1,8 @@
 #![allow(clippy::module_inception)]
 mod bindings;
 mod host;
+mod oci;
+mod signature;
 mod tests;
 mod wasm_fdw;
 
@@ -20,6 +22,12 @@ enum WasmFdwError {
     #[error("guest fdw error: {0}")]
     GuestFdw(GuestFdwError),
 
+    #[error("package signature verification failed: {0}")]
+    Signature(String),
+
+    #[error("OCI registry error: {0}")]
+    Oci(String),
+
     #[error("semver error: {0}")]
     Semver(#[from] semver::Error),
 
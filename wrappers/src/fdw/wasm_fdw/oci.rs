@@ -0,0 +1,282 @@
+use bytes::Bytes;
+use reqwest::header::{HeaderValue, ACCEPT, AUTHORIZATION, WWW_AUTHENTICATE};
+use reqwest::StatusCode;
+use serde_json::Value as JsonValue;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+use supabase_wrappers::prelude::Runtime;
+
+use super::{WasmFdwError, WasmFdwResult};
+
+// media types of the image manifest accepted from the registry
+const MANIFEST_MEDIA_TYPES: &str =
+    "application/vnd.oci.image.manifest.v1+json, application/vnd.docker.distribution.manifest.v2+json";
+
+// media type of the Wasm component layer, pushed by `wkg oci push`
+const WASM_LAYER_MEDIA_TYPE: &str = "application/wasm";
+
+fn oci_error(msg: impl Into<String>) -> WasmFdwError {
+    WasmFdwError::Oci(msg.into())
+}
+
+// check it is a 'sha256:<hex>' digest and return the lower case hex part
+fn parse_digest(digest: &str) -> WasmFdwResult<String> {
+    digest
+        .strip_prefix("sha256:")
+        .filter(|hex| hex.len() == 64 && hex.chars().all(|c| c.is_ascii_hexdigit()))
+        .map(|hex| hex.to_ascii_lowercase())
+        .ok_or_else(|| oci_error(format!("unsupported digest '{}'", digest)))
+}
+
+/// Reference of a package in an OCI registry, in the format of
+/// `oci://<registry>/<repository>[:<tag>][@sha256:<digest>]`
+#[derive(Debug)]
+pub(super) struct OciReference {
+    registry: String,
+    repository: String,
+    tag: String,
+    // hex of the pinned manifest digest
+    digest: Option<String>,
+}
+
+impl OciReference {
+    pub(super) fn parse(url: &str) -> WasmFdwResult<Self> {
+        let invalid = || oci_error(format!("invalid OCI reference '{}'", url));
+
+        let reference = url.strip_prefix("oci://").ok_or_else(invalid)?;
+        let (name, digest) = match reference.split_once('@') {
+            Some((name, digest)) => (name, Some(parse_digest(digest)?)),
+            None => (reference, None),
+        };
+        let (registry, path) = name.split_once('/').ok_or_else(invalid)?;
+        let (repository, tag) = path.rsplit_once(':').unwrap_or((path, "latest"));
+        if registry.is_empty() || repository.is_empty() || tag.is_empty() {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            // Docker Hub API isn't on its canonical host name
+            registry: match registry {
+                "docker.io" => "registry-1.docker.io".to_string(),
+                _ => registry.to_string(),
+            },
+            repository: repository.to_string(),
+            tag: tag.to_string(),
+            digest,
+        })
+    }
+
+    /// Hex of the pinned manifest digest
+    pub(super) fn digest(&self) -> Option<&str> {
+        self.digest.as_deref()
+    }
+
+    // the local registries usually don't have TLS enabled
+    fn base_url(&self) -> String {
+        let host = self.registry.split(':').next().unwrap_or_default();
+        let scheme = match host {
+            "localhost" | "127.0.0.1" => "http",
+            _ => "https",
+        };
+        format!("{}://{}/v2/{}", scheme, self.registry, self.repository)
+    }
+}
+
+// parse the `WWW-Authenticate` challenge, like
+// `Bearer realm="https://ghcr.io/token",service="ghcr.io",scope="..."`,
+// to its lower case scheme and parameters
+fn parse_challenge(header: &str) -> Option<(String, HashMap<String, String>)> {
+    let header = header.trim();
+    let (scheme, mut rest) = header.split_once(' ').unwrap_or((header, ""));
+    let mut params = HashMap::new();
+    while let Some((key, value)) = rest.split_once('=') {
+        let value = value.trim_start();
+        let (value, remaining) = match value.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted.find('"')?;
+                (&quoted[..end], &quoted[end + 1..])
+            }
+            None => value.split_at(value.find(',').unwrap_or(value.len())),
+        };
+        params.insert(
+            key.trim_matches(|c: char| c == ',' || c.is_whitespace())
+                .to_ascii_lowercase(),
+            value.to_string(),
+        );
+        rest = remaining;
+    }
+    Some((scheme.to_ascii_lowercase(), params))
+}
+
+fn sensitive_header(value: &str) -> WasmFdwResult<HeaderValue> {
+    let mut value = HeaderValue::from_str(value)
+        .map_err(|_| oci_error("invalid registry authorization header"))?;
+    value.set_sensitive(true);
+    Ok(value)
+}
+
+/// Client pulling a Wasm package from an OCI registry
+///
+/// The anonymous request is tried first, if it is rejected the client
+/// authenticates by the challenge in the response. For the token
+/// authentication used by most registries, `credential` is sent to the token
+/// service, e.g. `Basic <base64 of username:password>`, or the token is
+/// requested anonymously if it is not set. For the basic authentication,
+/// `credential` is sent to the registry directly.
+pub(super) struct OciClient<'a> {
+    rt: &'a Runtime,
+    http: reqwest::Client,
+    reference: &'a OciReference,
+    credential: Option<&'a str>,
+    authorization: Option<HeaderValue>,
+}
+
+impl<'a> OciClient<'a> {
+    pub(super) fn new(
+        rt: &'a Runtime,
+        reference: &'a OciReference,
+        credential: Option<&'a str>,
+    ) -> Self {
+        Self {
+            rt,
+            http: reqwest::Client::new(),
+            reference,
+            credential,
+            authorization: None,
+        }
+    }
+
+    fn send(&self, url: &str, accept: &str) -> WasmFdwResult<reqwest::Response> {
+        let mut req = self.http.get(url).header(ACCEPT, accept);
+        if let Some(authorization) = &self.authorization {
+            req = req.header(AUTHORIZATION, authorization.clone());
+        }
+        Ok(self.rt.block_on(req.send())?)
+    }
+
+    fn get(&mut self, path: &str, accept: &str) -> WasmFdwResult<Bytes> {
+        let url = format!("{}/{}", self.reference.base_url(), path);
+        let mut resp = self.send(&url, accept)?;
+        if resp.status() == StatusCode::UNAUTHORIZED && self.authorization.is_none() {
+            self.authorization = Some(self.authenticate(&resp)?);
+            resp = self.send(&url, accept)?;
+        }
+        if !resp.status().is_success() {
+            return Err(oci_error(format!(
+                "request {} failed: {}",
+                url,
+                resp.status()
+            )));
+        }
+        Ok(self.rt.block_on(resp.bytes())?)
+    }
+
+    // get the authorization header by the challenge in the rejected response
+    fn authenticate(&self, resp: &reqwest::Response) -> WasmFdwResult<HeaderValue> {
+        let (scheme, params) = resp
+            .headers()
+            .get(WWW_AUTHENTICATE)
+            .and_then(|header| header.to_str().ok())
+            .and_then(parse_challenge)
+            .ok_or_else(|| oci_error("invalid registry authentication challenge"))?;
+
+        match scheme.as_str() {
+            "basic" => {
+                let credential = self
+                    .credential
+                    .ok_or_else(|| oci_error("registry requires authentication"))?;
+                sensitive_header(credential)
+            }
+            "bearer" => {
+                let realm = params
+                    .get("realm")
+                    .ok_or_else(|| oci_error("registry token realm is not found"))?;
+                let scope = params
+                    .get("scope")
+                    .cloned()
+                    .unwrap_or_else(|| format!("repository:{}:pull", self.reference.repository));
+                let mut query = vec![("scope", scope)];
+                if let Some(service) = params.get("service") {
+                    query.push(("service", service.clone()));
+                }
+
+                let mut req = self.http.get(realm).query(&query);
+                if let Some(credential) = self.credential {
+                    req = req.header(AUTHORIZATION, sensitive_header(credential)?);
+                }
+                let resp = self.rt.block_on(req.send())?;
+                if !resp.status().is_success() {
+                    return Err(oci_error(format!(
+                        "registry token request failed: {}",
+                        resp.status()
+                    )));
+                }
+                let body: JsonValue = self.rt.block_on(resp.json())?;
+                let token = body
+                    .get("token")
+                    .or_else(|| body.get("access_token"))
+                    .and_then(|token| token.as_str())
+                    .ok_or_else(|| oci_error("registry token is not found"))?;
+                sensitive_header(&format!("Bearer {}", token))
+            }
+            _ => Err(oci_error(format!(
+                "unsupported registry authentication scheme '{}'",
+                scheme
+            ))),
+        }
+    }
+
+    /// Pull the package manifest and return the hex digest of its Wasm
+    /// layer, the manifest is verified if its digest is pinned
+    pub(super) fn pull_layer_digest(&mut self) -> WasmFdwResult<String> {
+        let manifest_ref = match self.reference.digest() {
+            Some(digest) => format!("sha256:{}", digest),
+            None => self.reference.tag.clone(),
+        };
+        let body = self.get(&format!("manifests/{}", manifest_ref), MANIFEST_MEDIA_TYPES)?;
+        if let Some(digest) = self.reference.digest() {
+            if hex::encode(Sha256::digest(&body)) != digest {
+                return Err(oci_error("manifest digest mismatch"));
+            }
+        }
+
+        let manifest: JsonValue = serde_json::from_slice(&body)
+            .map_err(|e| oci_error(format!("invalid manifest: {}", e)))?;
+        let layers = manifest
+            .get("layers")
+            .and_then(|layers| layers.as_array())
+            .ok_or_else(|| oci_error("manifest has no layers"))?;
+        let media_type = |layer: &JsonValue| {
+            layer
+                .get("mediaType")
+                .and_then(|t| t.as_str())
+                .unwrap_or_default()
+                .to_string()
+        };
+        let layer = layers
+            .iter()
+            .find(|layer| {
+                let media_type = media_type(layer);
+                media_type == WASM_LAYER_MEDIA_TYPE || media_type.ends_with("+wasm")
+            })
+            .or_else(|| layers.first().filter(|_| layers.len() == 1))
+            .ok_or_else(|| oci_error("Wasm layer is not found in manifest"))?;
+        let digest = layer
+            .get("digest")
+            .and_then(|digest| digest.as_str())
+            .ok_or_else(|| oci_error("layer digest is not found in manifest"))?;
+
+        parse_digest(digest)
+    }
+
+    /// Pull the layer blob by its hex digest, the content is verified
+    /// against the digest
+    pub(super) fn pull_blob(&mut self, digest: &str) -> WasmFdwResult<Bytes> {
+        let bytes = self.get(&format!("blobs/sha256:{}", digest), "*/*")?;
+        if hex::encode(Sha256::digest(&bytes)) != digest {
+            return Err(oci_error("layer digest mismatch"));
+        }
+        Ok(bytes)
+    }
+}
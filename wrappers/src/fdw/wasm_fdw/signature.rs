@@ -0,0 +1,193 @@
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use ed25519_compact::{PublicKey, Signature};
+use std::collections::HashMap;
+
+use supabase_wrappers::prelude::wasm_trusted_keys;
+
+use super::{WasmFdwError, WasmFdwResult};
+
+// minisign signature algorithms, the package is signed directly by 'Ed' or
+// its BLAKE2b-512 hash is signed by 'ED'
+const ALG_PURE: &[u8] = b"Ed";
+const ALG_HASHED: &[u8] = b"ED";
+
+const KEY_ID_LEN: usize = 8;
+
+fn sig_error(msg: impl Into<String>) -> WasmFdwError {
+    WasmFdwError::Signature(msg.into())
+}
+
+// decode a base64 line and check its length
+fn decode(line: Option<&str>, what: &str, len: usize) -> WasmFdwResult<Vec<u8>> {
+    let bytes = line
+        .and_then(|line| STANDARD.decode(line.trim()).ok())
+        .ok_or_else(|| sig_error(format!("invalid {}", what)))?;
+    if bytes.len() != len {
+        return Err(sig_error(format!("invalid {}", what)));
+    }
+    Ok(bytes)
+}
+
+// a minisign public key
+struct TrustedKey {
+    id: Vec<u8>,
+    key: PublicKey,
+}
+
+impl TrustedKey {
+    // parse the base64 public key, or the content of a minisign public key file
+    fn parse(s: &str) -> WasmFdwResult<Self> {
+        let line = s
+            .lines()
+            .map(|line| line.trim())
+            .find(|line| !line.is_empty() && !line.starts_with("untrusted comment:"));
+        let bytes = decode(
+            line,
+            "minisign public key",
+            2 + KEY_ID_LEN + PublicKey::BYTES,
+        )?;
+        if &bytes[..2] != ALG_PURE {
+            return Err(sig_error("unsupported minisign public key algorithm"));
+        }
+        let key = PublicKey::from_slice(&bytes[2 + KEY_ID_LEN..])
+            .map_err(|_| sig_error("invalid minisign public key"))?;
+        Ok(Self {
+            id: bytes[2..2 + KEY_ID_LEN].to_vec(),
+            key,
+        })
+    }
+
+    // the key id isn't bound to the key, so both of them must be the same
+    fn is_same(&self, other: &TrustedKey) -> bool {
+        self.id == other.id && *self.key == *other.key
+    }
+
+    fn verify(&self, message: &[u8], signature: &[u8]) -> WasmFdwResult<()> {
+        let signature =
+            Signature::from_slice(signature).map_err(|_| sig_error("invalid signature"))?;
+        self.key
+            .verify(message, &signature)
+            .map_err(|_| sig_error("signature mismatch"))
+    }
+}
+
+/// Verifier of the package signature, in
+/// [minisign](https://jedisct1.github.io/minisign/) format
+///
+/// The package must be signed if server option `fdw_package_pubkey` or
+/// `wrappers.wasm_trusted_keys` is set, by the key in the option, or by one
+/// of the trusted keys. If both are set, the key in the option must be one of
+/// the trusted keys. The detached signature is in server option
+/// `fdw_package_signature`, or in the `.minisig` file next to the package.
+pub(super) struct PackageVerifier {
+    keys: Vec<TrustedKey>,
+    signature: Option<String>,
+}
+
+impl PackageVerifier {
+    /// Create a verifier from the server options, return `None` if the
+    /// package doesn't need to be signed
+    pub(super) fn from_options(options: &HashMap<String, String>) -> WasmFdwResult<Option<Self>> {
+        Self::with_trusted_keys(options, &wasm_trusted_keys())
+    }
+
+    // create a verifier from the server options and the trusted keys in
+    // `wrappers.wasm_trusted_keys`
+    pub(super) fn with_trusted_keys(
+        options: &HashMap<String, String>,
+        trusted_keys: &[String],
+    ) -> WasmFdwResult<Option<Self>> {
+        let mut trusted = trusted_keys
+            .iter()
+            .map(|key| TrustedKey::parse(key))
+            .collect::<WasmFdwResult<Vec<_>>>()?;
+        let keys = match options.get("fdw_package_pubkey") {
+            Some(pubkey) => {
+                let key = TrustedKey::parse(pubkey)?;
+                if trusted.is_empty() {
+                    vec![key]
+                } else {
+                    // use the trusted key itself, not the key in the option
+                    let pos = trusted
+                        .iter()
+                        .position(|t| t.is_same(&key))
+                        .ok_or_else(|| {
+                            sig_error("fdw_package_pubkey is not in wrappers.wasm_trusted_keys")
+                        })?;
+                    vec![trusted.swap_remove(pos)]
+                }
+            }
+            None => trusted,
+        };
+        if keys.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            keys,
+            signature: options.get("fdw_package_signature").cloned(),
+        }))
+    }
+
+    /// Hex ids of the keys the package can be signed by
+    pub(super) fn key_ids(&self) -> Vec<String> {
+        self.keys.iter().map(|key| hex::encode(&key.id)).collect()
+    }
+
+    /// Verify the package by the signature in server option, or by the
+    /// detached signature got by `fetch_signature`, return the hex id of the
+    /// key it is signed by
+    pub(super) fn verify(
+        &self,
+        package: &[u8],
+        fetch_signature: impl FnOnce() -> WasmFdwResult<String>,
+    ) -> WasmFdwResult<String> {
+        let signature = match &self.signature {
+            Some(signature) => signature.clone(),
+            None => fetch_signature()?,
+        };
+
+        // the minisign signature has 4 lines: untrusted comment, signature,
+        // trusted comment and global signature
+        let mut lines = signature.lines().filter(|line| !line.trim().is_empty());
+        if !lines
+            .next()
+            .is_some_and(|line| line.starts_with("untrusted comment:"))
+        {
+            return Err(sig_error("invalid minisign signature"));
+        }
+        let sig = decode(
+            lines.next(),
+            "minisign signature",
+            2 + KEY_ID_LEN + Signature::BYTES,
+        )?;
+        let key = self
+            .keys
+            .iter()
+            .find(|key| key.id == sig[2..2 + KEY_ID_LEN])
+            .ok_or_else(|| {
+                sig_error(format!(
+                    "package is signed by untrusted key {}",
+                    hex::encode(&sig[2..2 + KEY_ID_LEN])
+                ))
+            })?;
+        let sig_bytes = &sig[2 + KEY_ID_LEN..];
+        match &sig[..2] {
+            ALG_PURE => key.verify(package, sig_bytes)?,
+            ALG_HASHED => key.verify(blake2b_simd::blake2b(package).as_bytes(), sig_bytes)?,
+            _ => return Err(sig_error("unsupported minisign signature algorithm")),
+        }
+
+        // the global signature signs the signature and trusted comment
+        let trusted_comment = lines
+            .next()
+            .and_then(|line| line.strip_prefix("trusted comment: "))
+            .ok_or_else(|| sig_error("invalid minisign trusted comment"))?;
+        let global_sig = decode(lines.next(), "minisign global signature", Signature::BYTES)?;
+        key.verify(
+            &[sig_bytes, trusted_comment.as_bytes()].concat(),
+            &global_sig,
+        )?;
+
+        Ok(hex::encode(&key.id))
+    }
+}
@@ -1,7 +1,21 @@
 #[cfg(any(test, feature = "pg_test"))]
 #[pgrx::pg_schema]
 mod tests {
+    use base64::{engine::general_purpose::STANDARD, Engine as _};
+    use ed25519_compact::{KeyPair, Seed};
     use pgrx::prelude::*;
+    use std::collections::HashMap;
+    use supabase_wrappers::prelude::create_async_runtime;
+
+    use super::super::bindings::supabase::wrappers::utils::Host as _;
+    use super::super::host::FdwHost;
+    use super::super::signature::PackageVerifier;
+
+    // a minisign public key with the key id and the key from the seed
+    fn minisign_pubkey(key_id: &[u8; 8], seed: u8) -> String {
+        let key_pair = KeyPair::from_seed(Seed::new([seed; 32]));
+        STANDARD.encode([b"Ed".as_slice(), key_id, key_pair.pk.as_slice()].concat())
+    }
 
     #[pg_test]
     fn wasm_smoketest() {
@@ -23,7 +37,8 @@ mod tests {
                        fdw_package_url 'file://../../../wasm-wrappers/fdw/paddle_fdw/target/wasm32-unknown-unknown/release/paddle_fdw.wasm',
                        fdw_package_name 'supabase:paddle-fdw',
                        fdw_package_version '0.1.1',
-                       fdw_package_checksum 'c5ac70bb2eef33693787b7d4efce9a83cde8d4fa40889d2037403a51263ba657',
+                       -- checksum of the released package, it is also verified for the local build if specified
+                       --fdw_package_checksum 'c5ac70bb2eef33693787b7d4efce9a83cde8d4fa40889d2037403a51263ba657',
                        --api_url 'https://sandbox-api.paddle.com',
                        api_url 'http://localhost:8096/paddle',
                        api_key '1234567890'
@@ -32,6 +47,8 @@ mod tests {
                 None,
             )
             .unwrap();
+            c.select("SELECT wasm_fdw_preload('paddle_server')", None, None)
+                .unwrap();
             c.update(
                 r#"
                   CREATE FOREIGN TABLE paddle_customers (
@@ -65,6 +82,529 @@ mod tests {
                 .filter_map(|r| r.get_by_name::<&str, _>("email").unwrap())
                 .collect::<Vec<_>>();
             assert_eq!(results, vec!["test@test.com"]);
+
+            // Segment FDW test
+            c.update(
+                r#"CREATE SERVER segment_server
+                     FOREIGN DATA WRAPPER wasm_wrapper
+                     OPTIONS (
+                       fdw_package_url 'file://../../../wasm-wrappers/fdw/segment_fdw/target/wasm32-unknown-unknown/release/segment_fdw.wasm',
+                       fdw_package_name 'supabase:segment-fdw',
+                       fdw_package_version '0.1.0',
+                       api_url 'http://localhost:8096/segment',
+                       api_token '1234567890'
+                     )"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.select("SELECT wasm_fdw_preload('segment_server')", None, None)
+                .unwrap();
+            c.update(
+                r#"
+                  CREATE FOREIGN TABLE segment_sources (
+                    id text,
+                    name text,
+                    enabled boolean,
+                    write_keys jsonb
+                  )
+                  SERVER segment_server
+                  OPTIONS (
+                    object 'sources'
+                  )
+             "#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"
+                  CREATE FOREIGN TABLE segment_delivery_metrics (
+                    destination_id text,
+                    source_id text,
+                    start_time timestamptz,
+                    metrics jsonb
+                  )
+                  SERVER segment_server
+                  OPTIONS (
+                    object 'delivery_metrics'
+                  )
+             "#,
+                None,
+                None,
+            )
+            .unwrap();
+
+            // the second page is fetched with the next page cursor
+            let results = c
+                .select("SELECT * FROM segment_sources ORDER BY name", None, None)
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("name").unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec!["Test Source", "Test Source 2"]);
+
+            let results = c
+                .select(
+                    r#"SELECT metrics->0->>'total' AS total FROM segment_delivery_metrics
+                       WHERE destination_id = 'fP7qoQw2HTWt9WdMr718gn'
+                         AND source_id = 'rh5BDZp6QDHvXFCkibm1pR'
+                         AND start_time = '2024-06-01 00:00:00+00'"#,
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("total").unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec!["42"]);
+
+            // Cloudflare FDW test
+            c.update(
+                r#"CREATE SERVER cloudflare_server
+                     FOREIGN DATA WRAPPER wasm_wrapper
+                     OPTIONS (
+                       fdw_package_url 'file://../../../wasm-wrappers/fdw/cloudflare_fdw/target/wasm32-unknown-unknown/release/cloudflare_fdw.wasm',
+                       fdw_package_name 'supabase:cloudflare-fdw',
+                       fdw_package_version '0.1.0',
+                       api_url 'http://localhost:8096/cloudflare',
+                       account_id '023e105f4ecef8ad9ca31a8372d0c353',
+                       api_token '1234567890'
+                     )"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.select("SELECT wasm_fdw_preload('cloudflare_server')", None, None)
+                .unwrap();
+            c.update(
+                r#"
+                  CREATE FOREIGN TABLE cloudflare_kv_keys (
+                    namespace_id text,
+                    name text,
+                    expiration bigint,
+                    metadata jsonb
+                  )
+                  SERVER cloudflare_server
+                  OPTIONS (
+                    object 'kv_keys'
+                  )
+             "#,
+                None,
+                None,
+            )
+            .unwrap();
+
+            // the second page is fetched with the next page cursor
+            let results = c
+                .select(
+                    r#"SELECT * FROM cloudflare_kv_keys
+                       WHERE namespace_id = '0f2ac74b498b48028cb68387c421e279'
+                       ORDER BY name"#,
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("name").unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec!["My-Key", "My-Key-2"]);
+
+            c.update(
+                r#"
+                  CREATE FOREIGN TABLE cloudflare_dns_records (
+                    id text,
+                    zone_id text,
+                    name text,
+                    type text,
+                    content text
+                  )
+                  SERVER cloudflare_server
+                  OPTIONS (
+                    object 'dns_records',
+                    zone_id '023e105f4ecef8ad9ca31a8372d0c353',
+                    rowid_column 'id'
+                  )
+             "#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"UPDATE cloudflare_dns_records SET content = '198.51.100.5'
+                   WHERE id = '372e67954025e0ba6aaa6d586b9e0b59'"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                "DELETE FROM cloudflare_dns_records WHERE id = '372e67954025e0ba6aaa6d586b9e0b59'",
+                None,
+                None,
+            )
+            .unwrap();
+
+            // Storage FDW test
+            c.update(
+                r#"CREATE SERVER storage_server
+                     FOREIGN DATA WRAPPER wasm_wrapper
+                     OPTIONS (
+                       fdw_package_url 'file://../../../wasm-wrappers/fdw/storage_fdw/target/wasm32-unknown-unknown/release/storage_fdw.wasm',
+                       fdw_package_name 'supabase:storage-fdw',
+                       fdw_package_version '0.1.0',
+                       project_url 'http://localhost:8096',
+                       api_url 'http://localhost:8096/storage',
+                       api_key '1234567890'
+                     )"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.select("SELECT wasm_fdw_preload('storage_server')", None, None)
+                .unwrap();
+            c.update(
+                r#"
+                  CREATE FOREIGN TABLE storage_objects (
+                    bucket_id text,
+                    name text,
+                    size bigint,
+                    signed_url text
+                  )
+                  SERVER storage_server
+                  OPTIONS (
+                    object 'objects'
+                  )
+             "#,
+                None,
+                None,
+            )
+            .unwrap();
+
+            let results = c
+                .select(
+                    "SELECT * FROM storage_objects WHERE bucket_id = 'reports#2024'",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("signed_url").unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(
+                results,
+                vec!["http://localhost:8096/storage/object/sign/reports%232024/q1.pdf?token=abc"]
+            );
+
+            // Registry FDW test
+            c.update(
+                r#"CREATE SERVER registry_server
+                     FOREIGN DATA WRAPPER wasm_wrapper
+                     OPTIONS (
+                       fdw_package_url 'file://../../../wasm-wrappers/fdw/registry_fdw/target/wasm32-unknown-unknown/release/registry_fdw.wasm',
+                       fdw_package_name 'supabase:registry-fdw',
+                       fdw_package_version '0.1.0',
+                       request_delay_ms '0'
+                     )"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.select("SELECT wasm_fdw_preload('registry_server')", None, None)
+                .unwrap();
+            c.update(
+                r#"
+                  CREATE FOREIGN TABLE crates_dependencies (
+                    name text,
+                    version text,
+                    dependency text,
+                    requirement text,
+                    kind text,
+                    optional boolean
+                  )
+                  SERVER registry_server
+                  OPTIONS (
+                    registry 'crates',
+                    object 'dependencies',
+                    api_url 'http://localhost:8096/registry/crates'
+                  )
+             "#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"
+                  CREATE FOREIGN TABLE npm_packages (
+                    name text,
+                    latest_version text,
+                    license text
+                  )
+                  SERVER registry_server
+                  OPTIONS (
+                    registry 'npm',
+                    object 'packages',
+                    api_url 'http://localhost:8096/registry/npm'
+                  )
+             "#,
+                None,
+                None,
+            )
+            .unwrap();
+
+            let results = c
+                .select(
+                    r#"SELECT * FROM crates_dependencies
+                       WHERE name = 'wasi' AND version = '0.13.3+wasi-0.2.2'"#,
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("dependency").unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec!["wit-bindgen-rt"]);
+
+            let results = c
+                .select(
+                    "SELECT * FROM npm_packages WHERE name = '@supabase/supabase-js'",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("latest_version").unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec!["2.45.4"]);
+
+            // SPARQL FDW test
+            c.update(
+                r#"CREATE SERVER sparql_server
+                     FOREIGN DATA WRAPPER wasm_wrapper
+                     OPTIONS (
+                       fdw_package_url 'file://../../../wasm-wrappers/fdw/sparql_fdw/target/wasm32-unknown-unknown/release/sparql_fdw.wasm',
+                       fdw_package_name 'supabase:sparql-fdw',
+                       fdw_package_version '0.1.0',
+                       endpoint 'http://localhost:8096/sparql'
+                     )"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.select("SELECT wasm_fdw_preload('sparql_server')", None, None)
+                .unwrap();
+            c.update(
+                r#"
+                  CREATE FOREIGN TABLE sparql_labels (
+                    item text,
+                    label text
+                  )
+                  SERVER sparql_server
+                  OPTIONS (
+                    query 'SELECT ?item ?label WHERE { ?item rdfs:label ?label }'
+                  )
+             "#,
+                None,
+                None,
+            )
+            .unwrap();
+
+            let results = c
+                .select(
+                    "SELECT * FROM sparql_labels WHERE label = 'AT&T + Co'",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .filter_map(|r| r.get_by_name::<&str, _>("item").unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(results, vec!["http://www.wikidata.org/entity/Q35476"]);
         });
     }
+
+    #[pg_test(
+        error = "guest fdw error: 'zone_id' table option is required to update or delete DNS records"
+    )]
+    fn wasm_cloudflare_zone_id_test() {
+        Spi::connect(|mut c| {
+            c.update(
+                r#"CREATE FOREIGN DATA WRAPPER wasm_wrapper
+                     HANDLER wasm_fdw_handler VALIDATOR wasm_fdw_validator"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"CREATE SERVER cloudflare_server
+                     FOREIGN DATA WRAPPER wasm_wrapper
+                     OPTIONS (
+                       fdw_package_url 'file://../../../wasm-wrappers/fdw/cloudflare_fdw/target/wasm32-unknown-unknown/release/cloudflare_fdw.wasm',
+                       fdw_package_name 'supabase:cloudflare-fdw',
+                       fdw_package_version '0.1.0',
+                       api_url 'http://localhost:8096/cloudflare',
+                       account_id '023e105f4ecef8ad9ca31a8372d0c353',
+                       api_token '1234567890'
+                     )"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"CREATE FOREIGN TABLE cloudflare_dns_records (id text, zone_id text)
+                     SERVER cloudflare_server
+                     OPTIONS (object 'dns_records', rowid_column 'id')"#,
+                None,
+                None,
+            )
+            .unwrap();
+
+            // zone id in where clause is enough for query but not for delete
+            c.update(
+                r#"DELETE FROM cloudflare_dns_records
+                   WHERE zone_id = '023e105f4ecef8ad9ca31a8372d0c353'
+                     AND id = '372e67954025e0ba6aaa6d586b9e0b59'"#,
+                None,
+                None,
+            )
+            .unwrap();
+        });
+    }
+
+    #[pg_test(
+        error = "guest fdw error: package file '/etc/hosts' is not in wrappers.wasm_package_dirs"
+    )]
+    fn wasm_package_dir_test() {
+        Spi::connect(|mut c| {
+            c.update(
+                r#"CREATE FOREIGN DATA WRAPPER wasm_wrapper
+                     HANDLER wasm_fdw_handler VALIDATOR wasm_fdw_validator"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"CREATE SERVER local_server
+                     FOREIGN DATA WRAPPER wasm_wrapper
+                     OPTIONS (
+                       fdw_package_url 'file:///etc/hosts',
+                       fdw_package_name 'supabase:paddle-fdw',
+                       fdw_package_version '0.1.1'
+                     )"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"CREATE FOREIGN TABLE local_table (id text)
+                     SERVER local_server
+                     OPTIONS (object 'customers')"#,
+                None,
+                None,
+            )
+            .unwrap();
+
+            c.select("SELECT * FROM local_table", None, None).unwrap();
+        });
+    }
+
+    #[pg_test]
+    fn wasm_cache_clear_test() {
+        let removed = Spi::get_one::<i64>("SELECT wasm_fdw_cache_clear()").unwrap();
+        assert!(removed.is_some_and(|n| n >= 0));
+    }
+
+    #[pg_test(
+        error = "preload Wasm package failed: package signature verification failed: invalid minisign public key"
+    )]
+    fn wasm_package_pubkey_test() {
+        Spi::connect(|mut c| {
+            c.update(
+                r#"CREATE FOREIGN DATA WRAPPER wasm_wrapper
+                     HANDLER wasm_fdw_handler VALIDATOR wasm_fdw_validator"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"CREATE SERVER signed_server
+                     FOREIGN DATA WRAPPER wasm_wrapper
+                     OPTIONS (
+                       fdw_package_url 'file://../../../wasm-wrappers/fdw/paddle_fdw/target/wasm32-unknown-unknown/release/paddle_fdw.wasm',
+                       fdw_package_name 'supabase:paddle-fdw',
+                       fdw_package_version '0.1.1',
+                       fdw_package_pubkey 'invalid'
+                     )"#,
+                None,
+                None,
+            )
+            .unwrap();
+
+            c.select("SELECT wasm_fdw_preload('signed_server')", None, None)
+                .unwrap();
+        });
+    }
+
+    #[pg_test(
+        error = "preload Wasm package failed: guest fdw error: package checksum or digest must be specified for OCI packages"
+    )]
+    fn wasm_oci_package_test() {
+        Spi::connect(|mut c| {
+            c.update(
+                r#"CREATE FOREIGN DATA WRAPPER wasm_wrapper
+                     HANDLER wasm_fdw_handler VALIDATOR wasm_fdw_validator"#,
+                None,
+                None,
+            )
+            .unwrap();
+            c.update(
+                r#"CREATE SERVER oci_server
+                     FOREIGN DATA WRAPPER wasm_wrapper
+                     OPTIONS (
+                       fdw_package_url 'oci://ghcr.io/supabase/paddle-fdw:0.1.1',
+                       fdw_package_name 'supabase:paddle-fdw',
+                       fdw_package_version '0.1.1'
+                     )"#,
+                None,
+                None,
+            )
+            .unwrap();
+
+            c.select("SELECT wasm_fdw_preload('oci_server')", None, None)
+                .unwrap();
+        });
+    }
+
+    #[pg_test]
+    fn wasm_trusted_key_test() {
+        let key_id = [1, 2, 3, 4, 5, 6, 7, 8];
+        let trusted = vec![minisign_pubkey(&key_id, 1)];
+
+        // the trusted key itself is accepted
+        let options = HashMap::from([("fdw_package_pubkey".to_string(), trusted[0].clone())]);
+        let verifier = PackageVerifier::with_trusted_keys(&options, &trusted).unwrap();
+        assert_eq!(verifier.unwrap().key_ids(), vec!["0102030405060708"]);
+
+        // a different key with the same key id is rejected
+        let options = HashMap::from([(
+            "fdw_package_pubkey".to_string(),
+            minisign_pubkey(&key_id, 2),
+        )]);
+        let err = PackageVerifier::with_trusted_keys(&options, &trusted)
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "package signature verification failed: fdw_package_pubkey is not in wrappers.wasm_trusted_keys"
+        );
+    }
+
+    #[pg_test]
+    fn wasm_guest_cache_test() {
+        let mut host = FdwHost::new(create_async_runtime().unwrap());
+        host.server_oid = pg_sys::Oid::from(1000);
+        host.cache_set("token".to_string(), "abc".to_string(), 60);
+        assert_eq!(host.cache_get("token".to_string()), Some("abc".to_string()));
+
+        // the values are scoped by foreign server
+        let mut other = FdwHost::new(create_async_runtime().unwrap());
+        other.server_oid = pg_sys::Oid::from(1001);
+        assert_eq!(other.cache_get("token".to_string()), None);
+
+        // the value is removed if ttl is 0
+        host.cache_set("token".to_string(), "abc".to_string(), 0);
+        assert_eq!(host.cache_get("token".to_string()), None);
+    }
 }
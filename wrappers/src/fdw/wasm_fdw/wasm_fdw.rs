@@ -1,8 +1,11 @@
 use bytes::Bytes;
-use pgrx::pg_sys;
+use pgrx::spi::Spi;
+use pgrx::{error, pg_extern, pg_sys, IntoDatum, PgBuiltInOids};
+use reqwest::header::{HeaderValue, AUTHORIZATION};
 use semver::{Version, VersionReq};
 use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use std::ffi::CStr;
 use std::fs;
 use std::path::{Path, PathBuf};
 use warg_client as warg;
@@ -17,8 +20,17 @@ use super::bindings::{
     Wrappers,
 };
 use super::host::FdwHost;
+use super::oci::{OciClient, OciReference};
+use super::signature::PackageVerifier;
 use super::{WasmFdwError, WasmFdwResult};
 
+// directory of the compiled components cache, relative to the data directory
+const CACHE_DIR: &str = "pg_wrappers/wasm_cache";
+const CACHE_FILE_EXT: &str = "cwasm";
+
+// length of the SHA-256 digest at the beginning of the cache files
+const DIGEST_LEN: usize = 32;
+
 // check minimal host version requirement, e.g, ">=1.2.3"
 fn check_version_requirement(ver_req: &str) -> WasmFdwResult<()> {
     let req = VersionReq::parse(ver_req)?;
@@ -43,6 +55,53 @@ fn load_component_from_file(
     Component::from_file(engine, file_path).map_err(|_| WasmFdwError::InvalidWasmComponent)
 }
 
+// load a local wasm file, it must be in one of the directories in
+// `wrappers.wasm_package_dirs` if that is set, and its checksum and signature
+// are verified if they are required
+fn load_component_from_local(
+    engine: &Engine,
+    file_path: &str,
+    checksum: Option<&str>,
+    verifier: Option<&PackageVerifier>,
+) -> WasmFdwResult<Component> {
+    let path = fs::canonicalize(file_path)
+        .map_err(|e| format!("cannot access package file '{}': {}", file_path, e))?;
+
+    let allowed_dirs = wasm_package_dirs();
+    if !allowed_dirs.is_empty()
+        && !allowed_dirs
+            .iter()
+            .filter_map(|dir| fs::canonicalize(dir).ok())
+            .any(|dir| path.starts_with(dir))
+    {
+        return Err(format!(
+            "package file '{}' is not in wrappers.wasm_package_dirs",
+            file_path
+        )
+        .into());
+    }
+
+    if checksum.is_none() && verifier.is_none() {
+        return load_component_from_file(engine, path);
+    }
+
+    let bytes = fs::read(&path)?;
+    if let Some(checksum) = checksum {
+        if hex::encode(Sha256::digest(&bytes)) != checksum {
+            return Err("component verification failed".to_string().into());
+        }
+    }
+    if let Some(verifier) = verifier {
+        verifier.verify(&bytes, || {
+            let sig_path = format!("{}.minisig", path.display());
+            fs::read_to_string(&sig_path).map_err(|e| {
+                WasmFdwError::Signature(format!("cannot read signature file '{}': {}", sig_path, e))
+            })
+        })?;
+    }
+    Component::from_binary(engine, &bytes).map_err(|_| WasmFdwError::InvalidWasmComponent)
+}
+
 fn download_component(
     rt: &Runtime,
     engine: &Engine,
@@ -50,19 +109,26 @@ fn download_component(
     name: &str,
     version: &str,
     checksum: Option<&str>,
+    verifier: Option<&PackageVerifier>,
+    auth: Option<&str>,
 ) -> WasmFdwResult<Component> {
     // handle local file paths
     if let Some(file_path) = url.strip_prefix("file://") {
-        return load_component_from_file(engine, file_path);
+        return load_component_from_local(engine, file_path, checksum, verifier);
     }
 
     // handle warg registry URLs
     if url.starts_with("warg://") || url.starts_with("wargs://") {
-        return download_from_warg(rt, engine, url, name, version);
+        return download_from_warg(rt, engine, url, name, version, verifier);
+    }
+
+    // handle OCI registry references
+    if url.starts_with("oci://") {
+        return download_from_oci(rt, engine, url, checksum, verifier, auth);
     }
 
     // handle direct URLs with caching
-    download_from_url(rt, engine, url, name, version, checksum)
+    download_from_url(rt, engine, url, checksum, verifier, auth)
 }
 
 fn download_from_warg(
@@ -71,6 +137,7 @@ fn download_from_warg(
     url: &str,
     name: &str,
     version: &str,
+    verifier: Option<&PackageVerifier>,
 ) -> WasmFdwResult<Component> {
     let url = url
         .replacen("warg://", "http://", 1)
@@ -97,76 +164,215 @@ fn download_from_warg(
         .block_on(client.download(&pkg_name, &ver))?
         .ok_or_else(|| format!("{}@{} not found on {}", name, version, url))?;
 
-    load_component_from_file(engine, pkg.path)
+    match verifier {
+        Some(verifier) => {
+            let bytes = fs::read(&pkg.path)?;
+            verifier.verify(&bytes, || {
+                Err(WasmFdwError::Signature(
+                    "fdw_package_signature must be specified for warg packages".to_string(),
+                ))
+            })?;
+            Component::from_binary(engine, &bytes).map_err(|_| WasmFdwError::InvalidWasmComponent)
+        }
+        None => load_component_from_file(engine, pkg.path),
+    }
 }
 
 fn download_from_url(
     rt: &Runtime,
     engine: &Engine,
     url: &str,
-    name: &str,
-    version: &str,
     checksum: Option<&str>,
+    verifier: Option<&PackageVerifier>,
+    auth: Option<&str>,
 ) -> WasmFdwResult<Component> {
     // validate URL
     let url = url
         .parse::<reqwest::Url>()
         .map_err(|e| format!("invalid URL '{}': {}", url, e))?;
 
-    // calculate cache path
-    let cache_path = get_cache_path(url.as_str(), name, version)?;
+    // ensure checksum is provided for remote downloads
+    let checksum = checksum
+        .ok_or_else(|| "package checksum must be specified for remote downloads".to_string())?;
 
-    // return cached component if it exists and is valid
-    if cache_path.exists() {
-        if let Ok(component) = load_component_from_file(engine, &cache_path) {
+    if let Some(component) = find_in_cache(engine, checksum, verifier)? {
+        return Ok(component);
+    }
+
+    // download, verify and compile component
+    let bytes = download_and_verify(rt, url.clone(), checksum, auth)?;
+    let key_id = verifier
+        .map(|verifier| verifier.verify(&bytes, || download_signature(rt, &url, auth)))
+        .transpose()?;
+    compile_and_cache(engine, &bytes, checksum, key_id.as_deref())
+}
+
+// pull the package from an OCI registry, the package must be pinned by the
+// manifest digest in the reference or by the checksum, which is the digest of
+// its Wasm layer
+fn download_from_oci(
+    rt: &Runtime,
+    engine: &Engine,
+    url: &str,
+    checksum: Option<&str>,
+    verifier: Option<&PackageVerifier>,
+    auth: Option<&str>,
+) -> WasmFdwResult<Component> {
+    let reference = OciReference::parse(url)?;
+    if checksum.is_none() && reference.digest().is_none() {
+        return Err(
+            "package checksum or digest must be specified for OCI packages"
+                .to_string()
+                .into(),
+        );
+    }
+
+    // the cache can be checked before pulling the manifest if the layer
+    // digest is known
+    if let Some(checksum) = checksum {
+        if let Some(component) = find_in_cache(engine, checksum, verifier)? {
             return Ok(component);
         }
-        // if loading fails, remove invalid cache file
-        let _ = fs::remove_file(&cache_path);
     }
 
-    // ensure checksum is provided for remote downloads
-    let checksum = checksum
-        .ok_or_else(|| "package checksum must be specified for remote downloads".to_string())?;
+    let mut client = OciClient::new(rt, &reference, auth);
+    let digest = client.pull_layer_digest()?;
+    match checksum {
+        Some(checksum) if !checksum.eq_ignore_ascii_case(&digest) => {
+            return Err("component verification failed".to_string().into());
+        }
+        Some(_) => {}
+        None => {
+            if let Some(component) = find_in_cache(engine, &digest, verifier)? {
+                return Ok(component);
+            }
+        }
+    }
 
-    // download and verify component
-    let bytes = download_and_verify(rt, url, checksum)?;
+    let bytes = client.pull_blob(&digest)?;
+    let key_id = verifier
+        .map(|verifier| {
+            verifier.verify(&bytes, || {
+                Err(WasmFdwError::Signature(
+                    "fdw_package_signature must be specified for OCI packages".to_string(),
+                ))
+            })
+        })
+        .transpose()?;
+    compile_and_cache(engine, &bytes, &digest, key_id.as_deref())
+}
 
-    // save to cache
-    save_to_cache(&cache_path, &bytes)?;
+// return the cached component if it exists and is valid, the component
+// verified by a signature is cached with its key id
+fn find_in_cache(
+    engine: &Engine,
+    checksum: &str,
+    verifier: Option<&PackageVerifier>,
+) -> WasmFdwResult<Option<Component>> {
+    let key_ids = match verifier {
+        Some(verifier) => verifier.key_ids().into_iter().map(Some).collect(),
+        None => vec![None],
+    };
+    for key_id in &key_ids {
+        let cache_path = get_cache_path(checksum, key_id.as_deref())?;
+        if let Some(component) = load_from_cache(engine, &cache_path) {
+            return Ok(Some(component));
+        }
+    }
+    Ok(None)
+}
 
-    // load component
-    load_component_from_file(engine, &cache_path).inspect_err(|_| {
-        let _ = fs::remove_file(&cache_path);
-    })
+// compile the verified package and save it to cache
+fn compile_and_cache(
+    engine: &Engine,
+    bytes: &[u8],
+    checksum: &str,
+    key_id: Option<&str>,
+) -> WasmFdwResult<Component> {
+    let cache_path = get_cache_path(checksum, key_id)?;
+    let component =
+        Component::from_binary(engine, bytes).map_err(|_| WasmFdwError::InvalidWasmComponent)?;
+
+    // save compiled component to cache, the component can still be used if
+    // it cannot be cached
+    if let Err(err) = component
+        .serialize()
+        .map_err(WasmFdwError::from)
+        .and_then(|compiled| save_to_cache(&cache_path, &compiled))
+    {
+        report_warning(&format!("cannot cache Wasm component: {}", err));
+    }
+
+    Ok(component)
 }
 
-fn get_cache_path(url: &str, name: &str, version: &str) -> WasmFdwResult<PathBuf> {
-    let hash = Sha256::digest(format!(
-        "{}:{}:{}@{}",
-        unsafe { pg_sys::GetUserId().as_u32() },
-        url,
-        name,
-        version
-    ));
+// directory of the compiled components, it is under the data directory so the
+// components are shared by all backends and kept after restart
+fn cache_dir() -> PathBuf {
+    let data_dir = unsafe { CStr::from_ptr(pg_sys::DataDir) };
+    Path::new(&*data_dir.to_string_lossy()).join(CACHE_DIR)
+}
 
-    let file_name = hex::encode(hash);
-    let mut path = dirs::cache_dir().ok_or_else(|| "no cache directory found".to_string())?;
+// the compiled components are keyed by the package checksum, and the id of
+// the key verified its signature
+fn get_cache_path(checksum: &str, key_id: Option<&str>) -> WasmFdwResult<PathBuf> {
+    if checksum.len() != 64 || !checksum.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("invalid package checksum '{}'", checksum).into());
+    }
 
-    path.push(file_name);
-    path.set_extension("wasm");
+    let file_name = match key_id {
+        Some(key_id) => format!("{}.{}", checksum.to_ascii_lowercase(), key_id),
+        None => checksum.to_ascii_lowercase(),
+    };
+    let mut path = cache_dir();
+    path.push(format!("{}.{}", file_name, CACHE_FILE_EXT));
 
     Ok(path)
 }
 
+// load a compiled component from cache, the cache file is the SHA-256 digest
+// of the compiled component followed by the component itself, invalid cache
+// file is removed
+fn load_from_cache(engine: &Engine, path: &Path) -> Option<Component> {
+    let bytes = fs::read(path).ok()?;
+    let component = (bytes.len() > DIGEST_LEN)
+        .then(|| bytes.split_at(DIGEST_LEN))
+        .filter(|(digest, compiled)| Sha256::digest(compiled).as_slice() == *digest)
+        // safety: the compiled component is created by this engine's
+        // configuration and its integrity is verified above, the engine also
+        // rejects the components compiled by incompatible version
+        .and_then(|(_, compiled)| unsafe { Component::deserialize(engine, compiled) }.ok());
+    if component.is_none() {
+        let _ = fs::remove_file(path);
+    }
+    component
+}
+
+// send the download request, with the authorization header for the private
+// packages
+fn send_download(
+    rt: &Runtime,
+    url: reqwest::Url,
+    auth: Option<&str>,
+) -> WasmFdwResult<reqwest::Result<reqwest::Response>> {
+    let mut req = reqwest::Client::new().get(url);
+    if let Some(auth) = auth {
+        let mut value = HeaderValue::from_str(auth)
+            .map_err(|_| "invalid package authorization header".to_string())?;
+        value.set_sensitive(true);
+        req = req.header(AUTHORIZATION, value);
+    }
+    Ok(rt.block_on(req.send()))
+}
+
 fn download_and_verify(
     rt: &Runtime,
     url: reqwest::Url,
     expected_checksum: &str,
+    auth: Option<&str>,
 ) -> WasmFdwResult<Bytes> {
-    let resp = rt
-        .block_on(reqwest::get(url.clone()))
-        .map_err(|_| "failed to download component".to_string())?;
+    let resp =
+        send_download(rt, url, auth)?.map_err(|_| "failed to download component".to_string())?;
 
     if !resp.status().is_success() {
         return Err("component download failed - server error"
@@ -186,18 +392,147 @@ fn download_and_verify(
     Ok(bytes)
 }
 
-fn save_to_cache(path: &Path, bytes: &[u8]) -> WasmFdwResult<()> {
+// download the detached signature next to the package
+fn download_signature(
+    rt: &Runtime,
+    url: &reqwest::Url,
+    auth: Option<&str>,
+) -> WasmFdwResult<String> {
+    let mut sig_url = url.clone();
+    sig_url.set_path(&format!("{}.minisig", url.path()));
+
+    let resp = send_download(rt, sig_url, auth)?
+        .map_err(|_| "failed to download package signature".to_string())?;
+
+    if !resp.status().is_success() {
+        return Err("package signature download failed - server error"
+            .to_string()
+            .into());
+    }
+
+    rt.block_on(resp.text())
+        .map_err(|_| "failed to read package signature".to_string().into())
+}
+
+fn save_to_cache(path: &Path, compiled: &[u8]) -> WasmFdwResult<()> {
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent).map_err(|_| "cache access error".to_string())?;
     }
 
-    fs::write(path, bytes).map_err(|_| "cache write error".to_string())?;
+    // write to a temporary file first, so other backends won't read a
+    // partially written cache file
+    let tmp_path = path.with_extension(format!("{}.{}", CACHE_FILE_EXT, std::process::id()));
+    let mut content = Sha256::digest(compiled).to_vec();
+    content.extend_from_slice(compiled);
+    fs::write(&tmp_path, content)
+        .and_then(|_| fs::rename(&tmp_path, path))
+        .map_err(|_| {
+            let _ = fs::remove_file(&tmp_path);
+            "cache write error".to_string()
+        })?;
 
     Ok(())
 }
 
+// download, verify and compile the package specified by the server options
+fn load_package(
+    rt: &Runtime,
+    options: &HashMap<String, String>,
+) -> WasmFdwResult<(Engine, Component)> {
+    let pkg_url = require_option("fdw_package_url", options)?;
+    let pkg_name = require_option("fdw_package_name", options)?;
+    let pkg_version = require_option("fdw_package_version", options)?;
+    let pkg_checksum = options.get("fdw_package_checksum").map(|t| t.as_str());
+    let verifier = PackageVerifier::from_options(options)?;
+
+    // authorization header of the private packages, e.g. 'Bearer xxx'
+    let auth = match options.get("fdw_package_auth_id") {
+        Some(auth_id) => Some(
+            get_secret(options, auth_id)
+                .ok_or("secret of option fdw_package_auth_id is not found".to_string())?,
+        ),
+        None => options.get("fdw_package_auth_header").cloned(),
+    };
+
+    let mut config = Config::new();
+    config.wasm_component_model(true);
+    let engine = Engine::new(&config)?;
+
+    let component = download_component(
+        rt,
+        &engine,
+        pkg_url,
+        pkg_name,
+        pkg_version,
+        pkg_checksum,
+        verifier.as_ref(),
+        auth.as_deref(),
+    )?;
+
+    Ok((engine, component))
+}
+
+/// Download, verify and compile the Wasm package of a foreign server ahead of
+/// time, so the first query on the server doesn't wait for it
+///
+/// The compiled component is saved in the on-disk cache, which is shared by
+/// all backends, so it can be run in init scripts or after failovers.
+#[pg_extern]
+fn wasm_fdw_preload(server_name: &str) {
+    let options = Spi::connect(|client| {
+        let row = client
+            .select(
+                "select coalesce(srvoptions, '{}'), has_server_privilege(oid, 'USAGE')
+                 from pg_catalog.pg_foreign_server
+                 where srvname = $1",
+                None,
+                Some(vec![(
+                    PgBuiltInOids::TEXTOID.oid(),
+                    server_name.into_datum(),
+                )]),
+            )?
+            .first();
+        if row.is_empty() {
+            error!("foreign server \"{}\" does not exist", server_name);
+        }
+        if row.get::<bool>(2)? != Some(true) {
+            error!("permission denied for foreign server {}", server_name);
+        }
+        Ok::<_, pgrx::spi::Error>(row.get::<Vec<Option<String>>>(1)?.unwrap_or_default())
+    })
+    .unwrap_or_else(|err| error!("query foreign server failed: {}", err));
+
+    let preload = || -> WasmFdwResult<()> {
+        let rt = create_async_runtime()?;
+        load_package(&rt, &options_from_list(&options))?;
+        Ok(())
+    };
+    if let Err(err) = preload() {
+        error!("preload Wasm package failed: {}", err);
+    }
+}
+
+/// Remove the compiled Wasm components cached on disk, return the number of
+/// removed components
+#[pg_extern]
+fn wasm_fdw_cache_clear() -> i64 {
+    if !unsafe { pg_sys::superuser() } {
+        error!("must be superuser to clear the Wasm FDW cache");
+    }
+
+    let Ok(entries) = fs::read_dir(cache_dir()) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == CACHE_FILE_EXT))
+        .filter(|path| fs::remove_file(path).is_ok())
+        .count() as i64
+}
+
 #[wrappers_fdw(
-    version = "0.1.4",
+    version = "0.1.8",
     author = "Supabase",
     website = "https://github.com/supabase/wrappers/tree/main/wrappers/src/fdw/wasm_fdw",
     error_type = "WasmFdwError"
@@ -215,28 +550,22 @@ impl WasmFdw {
 
 impl ForeignDataWrapper<WasmFdwError> for WasmFdw {
     fn new(server: ForeignServer) -> WasmFdwResult<Self> {
-        let pkg_url = require_option("fdw_package_url", &server.options)?;
-        let pkg_name = require_option("fdw_package_name", &server.options)?;
-        let pkg_version = require_option("fdw_package_version", &server.options)?;
-        let pkg_checksum = server
-            .options
-            .get("fdw_package_checksum")
-            .map(|t| t.as_str());
-
         let rt = create_async_runtime()?;
-
-        let mut config = Config::new();
-        config.wasm_component_model(true);
-        let engine = Engine::new(&config)?;
-
-        let component =
-            download_component(&rt, &engine, pkg_url, pkg_name, pkg_version, pkg_checksum)?;
+        let (engine, component) = load_package(&rt, &server.options)?;
 
         let mut linker = Linker::new(&engine);
         Wrappers::add_to_linker(&mut linker, |host: &mut FdwHost| host)?;
 
         let mut fdw_host = FdwHost::new(rt);
+        fdw_host.server_oid = server.server_oid;
         fdw_host.svr_opts.clone_from(&server.options);
+        // the wasm fdw reads its credentials from server options, so the user
+        // mapping options override them for the current user
+        fdw_host
+            .svr_opts
+            .extend(server.user_mapping_options.clone());
+        fdw_host.rate_limiter = RateLimiter::from_server(&server)?;
+        fdw_host.circuit_breaker = CircuitBreaker::from_server(&server)?;
 
         let mut store = Store::new(&engine, fdw_host);
         let bindings = Wrappers::instantiate(&mut store, &component, &linker)?;
@@ -260,6 +589,16 @@ impl ForeignDataWrapper<WasmFdwError> for WasmFdw {
         Ok(wasm_fdw)
     }
 
+    fn capabilities() -> FdwCapabilities {
+        FdwCapabilities {
+            write: true,
+            qual_pushdown: true,
+            sort_pushdown: true,
+            limit_pushdown: true,
+            ..Default::default()
+        }
+    }
+
     fn begin_scan(
         &mut self,
         quals: &[Qual],
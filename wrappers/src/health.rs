@@ -0,0 +1,83 @@
+use pgrx::prelude::*;
+use pgrx::spi::quote_identifier;
+
+/// Check the connectivity and credentials of a foreign server, by calling the
+/// health check function of its foreign data wrapper
+#[pg_extern]
+fn wrappers_check_server(
+    server_name: &str,
+) -> TableIterator<
+    'static,
+    (
+        name!(status, String),
+        name!(latency_ms, f64),
+        name!(version, Option<String>),
+        name!(message, Option<String>),
+    ),
+> {
+    // the health check function is named after the handler function, e.g.
+    // stripe_fdw_handler -> stripe_fdw_health_check
+    let (server_oid, schema, handler) = Spi::connect(|client| {
+        let row = client
+            .select(
+                "select s.oid, n.nspname::text, p.proname::text,
+                        has_server_privilege(s.oid, 'USAGE')
+                 from pg_catalog.pg_foreign_server s
+                 join pg_catalog.pg_foreign_data_wrapper w on w.oid = s.srvfdw
+                 join pg_catalog.pg_proc p on p.oid = w.fdwhandler
+                 join pg_catalog.pg_namespace n on n.oid = p.pronamespace
+                 where s.srvname = $1",
+                None,
+                Some(vec![(
+                    PgBuiltInOids::TEXTOID.oid(),
+                    server_name.into_datum(),
+                )]),
+            )?
+            .first();
+        if row.is_empty() {
+            error!(
+                "foreign server \"{}\" does not exist or has no handler",
+                server_name
+            );
+        }
+        if row.get::<bool>(4)? != Some(true) {
+            error!("permission denied for foreign server {}", server_name);
+        }
+        Ok::<_, pgrx::spi::Error>((
+            row.get::<pg_sys::Oid>(1)?.unwrap_or(pg_sys::InvalidOid),
+            row.get::<String>(2)?.unwrap_or_default(),
+            row.get::<String>(3)?.unwrap_or_default(),
+        ))
+    })
+    .unwrap_or_else(|err| error!("query foreign server failed: {}", err));
+
+    let Some(prefix) = handler.strip_suffix("_handler") else {
+        error!(
+            "foreign data wrapper handler {} doesn't support health check",
+            handler
+        );
+    };
+    let sql = format!(
+        "select status, latency_ms, version, message from {}.{}($1)",
+        quote_identifier(&schema),
+        quote_identifier(format!("{}_health_check", prefix)),
+    );
+    let check = Spi::connect(|client| {
+        let row = client
+            .select(
+                &sql,
+                None,
+                Some(vec![(PgBuiltInOids::OIDOID.oid(), server_oid.into_datum())]),
+            )?
+            .first();
+        Ok::<_, pgrx::spi::Error>((
+            row.get::<String>(1)?.unwrap_or_default(),
+            row.get::<f64>(2)?.unwrap_or_default(),
+            row.get::<String>(3)?,
+            row.get::<String>(4)?,
+        ))
+    })
+    .unwrap_or_else(|err| error!("health check of server {} failed: {}", server_name, err));
+
+    TableIterator::once(check)
+}
@@ -5,8 +5,26 @@ pg_module_magic!();
 extension_sql_file!("../sql/bootstrap.sql", bootstrap);
 extension_sql_file!("../sql/finalize.sql", finalize);
 
+mod cache;
+mod capabilities;
+mod circuit_breaker;
 mod fdw;
+mod health;
+mod metrics;
+mod pool;
 mod stats;
+mod sync;
+mod sync_worker;
+mod validate;
+mod vault;
+
+#[allow(non_snake_case)]
+#[pg_guard]
+pub extern "C" fn _PG_init() {
+    supabase_wrappers::prelude::init_shared_memory();
+    supabase_wrappers::prelude::init_gucs();
+    sync_worker::init_sync_worker();
+}
 
 #[cfg(test)]
 pub mod pg_test {
@@ -15,6 +33,6 @@ pub mod pg_test {
     }
 
     pub fn postgresql_conf_options() -> Vec<&'static str> {
-        vec![]
+        vec!["wrappers.wasm_package_dirs = '../../../wasm-wrappers/fdw'"]
     }
 }
@@ -0,0 +1,64 @@
+use pgrx::prelude::*;
+use std::ffi::CStr;
+use supabase_wrappers::prelude::{reset_server_metrics, server_metrics};
+
+/// Runtime metrics of each foreign server in current database, the counters
+/// cover all backends if the extension is loaded by `shared_preload_libraries`
+#[pg_extern]
+fn wrappers_stats() -> TableIterator<
+    'static,
+    (
+        name!(server_name, String),
+        name!(fdw_name, String),
+        name!(scans, i64),
+        name!(rows_fetched, i64),
+        name!(bytes_received, i64),
+        name!(remote_requests, i64),
+        name!(errors, i64),
+        name!(retries, i64),
+        name!(total_wait_time_ms, f64),
+    ),
+> {
+    let rows = server_metrics()
+        .into_iter()
+        .filter_map(|(server_oid, metrics)| {
+            // skip the foreign servers which are dropped
+            let (server_name, fdw_name) = unsafe {
+                let fserver = pg_sys::GetForeignServerExtended(
+                    server_oid,
+                    pg_sys::FSV_MISSING_OK as pg_sys::bits16,
+                );
+                if fserver.is_null() {
+                    return None;
+                }
+                let fdw = pg_sys::GetForeignDataWrapper((*fserver).fdwid);
+                (
+                    CStr::from_ptr((*fserver).servername)
+                        .to_string_lossy()
+                        .into_owned(),
+                    CStr::from_ptr((*fdw).fdwname)
+                        .to_string_lossy()
+                        .into_owned(),
+                )
+            };
+            Some((
+                server_name,
+                fdw_name,
+                metrics.scans,
+                metrics.rows_fetched,
+                metrics.bytes_received,
+                metrics.remote_requests,
+                metrics.errors,
+                metrics.retries,
+                metrics.wait_time_us as f64 / 1000.0,
+            ))
+        })
+        .collect::<Vec<_>>();
+    TableIterator::new(rows)
+}
+
+/// Reset the runtime metrics of all foreign servers in current database
+#[pg_extern]
+fn wrappers_stats_reset() {
+    reset_server_metrics();
+}
@@ -0,0 +1,9 @@
+use pgrx::prelude::*;
+use supabase_wrappers::prelude::close_connections;
+
+/// Close the idle connections of the foreign servers in current backend, return
+/// the number of closed connections
+#[pg_extern]
+fn wrappers_close_connections() -> i64 {
+    close_connections() as i64
+}
@@ -0,0 +1,307 @@
+use pgrx::prelude::*;
+use pgrx::spi::{quote_identifier, quote_literal, SpiClient};
+use std::collections::HashMap;
+
+const DEFAULT_BATCH_SIZE: i32 = 1000;
+
+// a column in both the foreign table and the target table
+struct SyncColumn {
+    name: String,
+    type_name: String,
+    is_key: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SyncMode {
+    // replace all rows in the target table
+    Full,
+    // upsert the rows newer than the max watermark in the target table
+    Incremental,
+    // insert the rows, skip the rows already in the target table
+    Append,
+}
+
+impl SyncMode {
+    fn parse(mode: &str) -> Option<Self> {
+        match mode {
+            "full" => Some(Self::Full),
+            "incremental" => Some(Self::Incremental),
+            "append" => Some(Self::Append),
+            _ => None,
+        }
+    }
+}
+
+fn relation_name(client: &SpiClient, relid: pg_sys::Oid) -> spi::Result<(String, String)> {
+    let row = client
+        .select(
+            "select $1::regclass::text, relkind::text from pg_catalog.pg_class where oid = $1",
+            None,
+            Some(vec![(PgBuiltInOids::OIDOID.oid(), relid.into_datum())]),
+        )?
+        .first();
+    if row.is_empty() {
+        error!("relation with oid {} does not exist", relid.as_u32());
+    }
+    Ok((
+        row.get::<String>(1)?.unwrap_or_default(),
+        row.get::<String>(2)?.unwrap_or_default(),
+    ))
+}
+
+// get the columns of the target table which are also in the foreign table, the
+// generated columns are excluded as they cannot be inserted
+fn sync_columns(
+    client: &SpiClient,
+    foreign_table: pg_sys::Oid,
+    target: pg_sys::Oid,
+) -> spi::Result<Vec<SyncColumn>> {
+    let rows = client.select(
+        "select a.attname::text, pg_catalog.format_type(a.atttypid, a.atttypmod),
+                coalesce(a.attnum = any(i.indkey), false)
+         from pg_catalog.pg_attribute a
+         join pg_catalog.pg_attribute f
+           on f.attrelid = $1 and f.attname = a.attname
+          and f.attnum > 0 and not f.attisdropped
+         left join pg_catalog.pg_index i
+           on i.indrelid = a.attrelid and i.indisprimary
+         where a.attrelid = $2 and a.attnum > 0 and not a.attisdropped
+           and a.attgenerated = ''
+         order by a.attnum",
+        None,
+        Some(vec![
+            (PgBuiltInOids::OIDOID.oid(), foreign_table.into_datum()),
+            (PgBuiltInOids::OIDOID.oid(), target.into_datum()),
+        ]),
+    )?;
+    rows.map(|row| {
+        Ok(SyncColumn {
+            name: row.get::<String>(1)?.unwrap_or_default(),
+            type_name: row.get::<String>(2)?.unwrap_or_default(),
+            is_key: row.get::<bool>(3)?.unwrap_or_default(),
+        })
+    })
+    .collect()
+}
+
+// format a value fetched as text to a literal of the column type
+fn value_literal(value: &Option<String>, col: &SyncColumn) -> String {
+    match value {
+        Some(v) => format!("{}::{}", quote_literal(v), col.type_name),
+        None => "null".to_string(),
+    }
+}
+
+/// Copy the rows of a foreign table into a local table
+///
+/// The columns of the same name in both tables are copied, and the rows are
+/// inserted in batches of `batch_size` rows. The modes below are supported:
+///
+/// - `full`: replace all rows in the target table
+/// - `incremental`: only fetch the rows whose `watermark` column is greater
+///   than its max value in the target table, and upsert them
+/// - `append`: insert the rows, the rows already in the target table are
+///   skipped
+///
+/// The `full` and `incremental` modes require a primary key on the target
+/// table, the rows with the same key are updated. Returns the number of rows
+/// inserted or updated.
+#[pg_extern]
+fn wrappers_sync(
+    foreign_table: pg_sys::Oid,
+    target: pg_sys::Oid,
+    mode: default!(&str, "'full'"),
+    watermark: default!(Option<&str>, "NULL"),
+    batch_size: default!(i32, 1000),
+) -> i64 {
+    sync_table(foreign_table, target, mode, watermark, batch_size)
+}
+
+// copy the rows of a foreign table into a local table, see wrappers_sync()
+pub(crate) fn sync_table(
+    foreign_table: pg_sys::Oid,
+    target: pg_sys::Oid,
+    mode: &str,
+    watermark: Option<&str>,
+    batch_size: i32,
+) -> i64 {
+    let Some(mode) = SyncMode::parse(mode) else {
+        error!(
+            "invalid sync mode \"{}\", must be full, incremental or append",
+            mode
+        );
+    };
+    let batch_size = if batch_size > 0 {
+        batch_size
+    } else {
+        DEFAULT_BATCH_SIZE
+    };
+
+    Spi::connect(|mut client| {
+        let (src_name, src_kind) = relation_name(&client, foreign_table)?;
+        if src_kind != "f" {
+            error!("{} is not a foreign table", src_name);
+        }
+        let (tgt_name, tgt_kind) = relation_name(&client, target)?;
+        if tgt_kind != "r" && tgt_kind != "p" {
+            error!("{} is not a table", tgt_name);
+        }
+
+        let cols = sync_columns(&client, foreign_table, target)?;
+        if cols.is_empty() {
+            error!("{} and {} have no common columns", src_name, tgt_name);
+        }
+        let keys: Vec<usize> = (0..cols.len()).filter(|i| cols[*i].is_key).collect();
+        if keys.is_empty() && mode != SyncMode::Append {
+            error!(
+                "{} must have a primary key to sync in {:?} mode",
+                tgt_name, mode
+            );
+        }
+        let watermark = match watermark {
+            Some(name) => match cols.iter().position(|col| col.name == name) {
+                Some(idx) => Some(idx),
+                None => error!("watermark column \"{}\" is not in both tables", name),
+            },
+            None if mode == SyncMode::Incremental => {
+                error!("watermark column is required to sync in incremental mode")
+            }
+            None => None,
+        };
+
+        // the source query, the rows are fetched as text so they can be
+        // inserted into the target columns of any type
+        let col_list = cols
+            .iter()
+            .map(|col| quote_identifier(&col.name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut src_sql = format!(
+            "select {} from {}",
+            cols.iter()
+                .map(|col| format!("{}::text", quote_identifier(&col.name)))
+                .collect::<Vec<_>>()
+                .join(", "),
+            src_name
+        );
+        if let Some(idx) = watermark {
+            let wm_col = &cols[idx];
+            let wm_name = quote_identifier(&wm_col.name);
+            if mode == SyncMode::Incremental {
+                let last = client
+                    .select(
+                        &format!("select max({})::text from {}", wm_name, tgt_name),
+                        None,
+                        None,
+                    )?
+                    .first()
+                    .get::<String>(1)?;
+                if let Some(last) = last {
+                    src_sql.push_str(&format!(
+                        " where {} > {}",
+                        wm_name,
+                        value_literal(&Some(last), wm_col)
+                    ));
+                }
+            }
+            // the rows are fetched in watermark order, so the newest row wins
+            // if there are rows with the same key
+            src_sql.push_str(&format!(" order by {}", wm_name));
+        }
+
+        // how the rows already in the target table are resolved
+        let conflict = if keys.is_empty() {
+            String::new()
+        } else {
+            let key_list = keys
+                .iter()
+                .map(|i| quote_identifier(&cols[*i].name))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let sets = cols
+                .iter()
+                .filter(|col| !col.is_key)
+                .map(|col| {
+                    let name = quote_identifier(&col.name);
+                    format!("{} = excluded.{}", name, name)
+                })
+                .collect::<Vec<_>>();
+            if mode == SyncMode::Append || sets.is_empty() {
+                format!(" on conflict ({}) do nothing", key_list)
+            } else {
+                let mut conflict = format!(
+                    " on conflict ({}) do update set {}",
+                    key_list,
+                    sets.join(", ")
+                );
+                // don't overwrite the rows newer than the fetched rows
+                if let Some(idx) = watermark {
+                    let wm_name = quote_identifier(&cols[idx].name);
+                    conflict.push_str(&format!(
+                        " where target.{} <= excluded.{}",
+                        wm_name, wm_name
+                    ));
+                }
+                conflict
+            }
+        };
+
+        if mode == SyncMode::Full {
+            client.update(&format!("delete from {}", tgt_name), None, None)?;
+        }
+
+        let mut synced = 0i64;
+        let mut cursor = client.open_cursor(&src_sql, None);
+        loop {
+            let batch = cursor.fetch(batch_size as _)?;
+            if batch.is_empty() {
+                break;
+            }
+
+            // a statement cannot upsert the same row twice, so only the last
+            // row of each key in the batch is kept
+            let mut rows: Vec<Vec<Option<String>>> = Vec::with_capacity(batch.len());
+            let mut key_idx: HashMap<Vec<Option<String>>, usize> = HashMap::new();
+            for row in batch {
+                let values = (1..=cols.len())
+                    .map(|i| row.get::<String>(i))
+                    .collect::<spi::Result<Vec<_>>>()?;
+                if keys.is_empty() {
+                    rows.push(values);
+                    continue;
+                }
+                let key = keys.iter().map(|i| values[*i].clone()).collect();
+                match key_idx.get(&key) {
+                    Some(idx) => rows[*idx] = values,
+                    None => {
+                        key_idx.insert(key, rows.len());
+                        rows.push(values);
+                    }
+                }
+            }
+
+            let values = rows
+                .iter()
+                .map(|row| {
+                    let literals = row
+                        .iter()
+                        .zip(cols.iter())
+                        .map(|(value, col)| value_literal(value, col))
+                        .collect::<Vec<_>>();
+                    format!("({})", literals.join(", "))
+                })
+                .collect::<Vec<_>>();
+            let sql = format!(
+                "insert into {} as target ({}) values {}{}",
+                tgt_name,
+                col_list,
+                values.join(", "),
+                conflict
+            );
+            synced += client.update(&sql, None, None)?.len() as i64;
+        }
+
+        Ok::<_, spi::Error>(synced)
+    })
+    .unwrap_or_else(|err| error!("sync foreign table failed: {}", err))
+}
@@ -0,0 +1,181 @@
+use pgrx::bgworkers::{BackgroundWorker, BackgroundWorkerBuilder, SignalWakeFlags};
+use pgrx::pg_sys::panic::CaughtError;
+use pgrx::prelude::*;
+use pgrx::spi::{quote_identifier, quote_literal};
+use std::panic::AssertUnwindSafe;
+use std::time::{Duration, Instant};
+use supabase_wrappers::prelude::{redact, sync_database, sync_naptime};
+
+use crate::sync::sync_table;
+
+const WORKER_NAME: &str = "wrappers sync worker";
+
+// a sync job due to run
+struct SyncJob {
+    id: i64,
+    foreign_table: pg_sys::Oid,
+    target: pg_sys::Oid,
+    mode: String,
+    watermark: Option<String>,
+    owner: String,
+}
+
+/// Register the sync background worker if `wrappers.sync_database` is set, it
+/// must be called in `_PG_init()` after the settings are registered
+pub(crate) fn init_sync_worker() {
+    if unsafe { !pg_sys::process_shared_preload_libraries_in_progress } {
+        return;
+    }
+    if sync_database().is_none() {
+        return;
+    }
+    BackgroundWorkerBuilder::new(WORKER_NAME)
+        .set_library("wrappers")
+        .set_function("wrappers_sync_worker_main")
+        .enable_spi_access()
+        .set_restart_time(Some(Duration::from_secs(60)))
+        .load();
+}
+
+#[pg_guard]
+#[no_mangle]
+pub extern "C" fn wrappers_sync_worker_main(_arg: pg_sys::Datum) {
+    BackgroundWorker::attach_signal_handlers(SignalWakeFlags::SIGHUP | SignalWakeFlags::SIGTERM);
+    let Some(db) = sync_database() else {
+        return;
+    };
+    BackgroundWorker::connect_worker_to_spi(Some(&db), None);
+    log!("{} started in database {}", WORKER_NAME, db);
+
+    while BackgroundWorker::wait_latch(Some(sync_naptime())) {
+        let Some(schema) = BackgroundWorker::transaction(extension_schema) else {
+            continue;
+        };
+        let jobs = BackgroundWorker::transaction(AssertUnwindSafe(|| due_jobs(&schema)));
+        for job in jobs {
+            if BackgroundWorker::sigterm_received() {
+                break;
+            }
+            BackgroundWorker::transaction(AssertUnwindSafe(|| run_job(&schema, &job)));
+        }
+    }
+}
+
+// get the schema of the extension, or None if it is not installed
+fn extension_schema() -> Option<String> {
+    Spi::get_one::<String>(
+        "select n.nspname::text from pg_catalog.pg_extension e
+         join pg_catalog.pg_namespace n on n.oid = e.extnamespace
+         where e.extname = 'wrappers'",
+    )
+    .unwrap_or_default()
+}
+
+fn due_jobs(schema: &str) -> Vec<SyncJob> {
+    let sql = format!(
+        "select id, foreign_table::oid, target::oid, mode, watermark, owner::text
+         from {}.wrappers_sync_jobs
+         where enabled and (last_run_at is null or last_run_at + sync_interval <= now())
+         order by id",
+        quote_identifier(schema)
+    );
+    Spi::connect(|client| {
+        client
+            .select(&sql, None, None)?
+            .map(|row| {
+                Ok(SyncJob {
+                    id: row.get::<i64>(1)?.unwrap_or_default(),
+                    foreign_table: row.get::<pg_sys::Oid>(2)?.unwrap_or(pg_sys::InvalidOid),
+                    target: row.get::<pg_sys::Oid>(3)?.unwrap_or(pg_sys::InvalidOid),
+                    mode: row.get::<String>(4)?.unwrap_or_default(),
+                    watermark: row.get::<String>(5)?,
+                    owner: row.get::<String>(6)?.unwrap_or_default(),
+                })
+            })
+            .collect::<spi::Result<Vec<_>>>()
+    })
+    .unwrap_or_else(|err| {
+        warning!("{} cannot get the sync jobs: {}", WORKER_NAME, err);
+        Vec::new()
+    })
+}
+
+// run a function in a subtransaction, the subtransaction is rolled back if the
+// function raises an error, and the error message is returned
+pub(crate) fn in_subtransaction<R>(f: impl FnOnce() -> R) -> Result<R, String> {
+    unsafe {
+        let old_ctx = pg_sys::CurrentMemoryContext;
+        let old_owner = pg_sys::CurrentResourceOwner;
+        pg_sys::BeginInternalSubTransaction(std::ptr::null());
+        pg_sys::MemoryContextSwitchTo(old_ctx);
+
+        PgTryBuilder::new(AssertUnwindSafe(|| {
+            let result = f();
+            pg_sys::ReleaseCurrentSubTransaction();
+            pg_sys::MemoryContextSwitchTo(old_ctx);
+            pg_sys::CurrentResourceOwner = old_owner;
+            Ok(result)
+        }))
+        .catch_others(|e| {
+            pg_sys::RollbackAndReleaseCurrentSubTransaction();
+            pg_sys::MemoryContextSwitchTo(old_ctx);
+            pg_sys::CurrentResourceOwner = old_owner;
+            let (CaughtError::PostgresError(ereport)
+            | CaughtError::ErrorReport(ereport)
+            | CaughtError::RustPanic { ereport, .. }) = e;
+            Err(redact(ereport.message()))
+        })
+        .execute()
+    }
+}
+
+// run a sync job as its owner, and save the result to the job
+fn run_job(schema: &str, job: &SyncJob) {
+    let started_at = Instant::now();
+    let result = in_subtransaction(|| {
+        Spi::run(&format!(
+            "select pg_catalog.set_config('role', {}, true)",
+            quote_literal(&job.owner)
+        ))
+        .unwrap_or_else(|err| error!("cannot set role {}: {}", job.owner, err));
+        let rows = sync_table(
+            job.foreign_table,
+            job.target,
+            &job.mode,
+            job.watermark.as_deref(),
+            1000,
+        );
+        Spi::run("select pg_catalog.set_config('role', 'none', true)")
+            .unwrap_or_else(|err| error!("cannot reset role: {}", err));
+        rows
+    });
+    let (status, rows, error) = match result {
+        Ok(rows) => ("succeeded", Some(rows), None),
+        Err(msg) => {
+            warning!("{} job {} failed: {}", WORKER_NAME, job.id, msg);
+            ("failed", None, Some(msg))
+        }
+    };
+
+    let sql = format!(
+        "update {}.wrappers_sync_jobs
+         set last_run_at = now(), last_status = $2, last_rows = $3,
+             last_duration = make_interval(secs => $4), last_error = $5
+         where id = $1",
+        quote_identifier(schema)
+    );
+    Spi::run_with_args(
+        &sql,
+        Some(vec![
+            (PgBuiltInOids::INT8OID.oid(), job.id.into_datum()),
+            (PgBuiltInOids::TEXTOID.oid(), status.into_datum()),
+            (PgBuiltInOids::INT8OID.oid(), rows.into_datum()),
+            (
+                PgBuiltInOids::FLOAT8OID.oid(),
+                started_at.elapsed().as_secs_f64().into_datum(),
+            ),
+            (PgBuiltInOids::TEXTOID.oid(), error.into_datum()),
+        ]),
+    )
+    .unwrap_or_else(|err| warning!("{} cannot save job {}: {}", WORKER_NAME, job.id, err));
+}
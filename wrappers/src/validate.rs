@@ -0,0 +1,184 @@
+use pgrx::prelude::*;
+
+use crate::sync_worker::in_subtransaction;
+
+// options keeping credentials in plain text, and the options referencing the
+// credentials in Vault which replace them
+const DEPRECATED_OPTIONS: &[(&str, &str)] = &[
+    ("api_key", "api_key_id"),
+    ("conn_string", "conn_string_id"),
+    ("conn_url", "conn_url_id"),
+    ("password", "password_id"),
+    ("sa_key", "sa_key_id"),
+];
+
+// an object with options on the foreign server
+struct OptionsObject {
+    kind: String,
+    name: String,
+    options: Vec<Option<String>>,
+    catalog: pg_sys::Oid,
+}
+
+type ValidationRow = (
+    String,
+    String,
+    Option<String>,
+    String,
+    String,
+    Option<String>,
+    Option<String>,
+);
+
+/// Re-run the validator of the foreign data wrapper against a foreign server,
+/// and its user mappings, foreign tables and columns
+///
+/// Each row is a problem found in an object's options:
+///
+/// - `error`: the options are rejected by the validator, e.g. a required
+///   option is missing after upgrading the extension
+/// - `deprecated`: the option is still accepted but should be replaced, e.g.
+///   `api_key` in plain text should be replaced by `api_key_id` in Vault
+///
+/// The `action` and `replacement` columns are the suggested fix, like
+/// `replace` the option by the `replacement` option.
+#[pg_extern]
+fn wrappers_validate_server(
+    server_name: &str,
+) -> TableIterator<
+    'static,
+    (
+        name!(object_type, String),
+        name!(object_name, String),
+        name!(option, Option<String>),
+        name!(severity, String),
+        name!(message, String),
+        name!(action, Option<String>),
+        name!(replacement, Option<String>),
+    ),
+> {
+    let (validator, objects) = Spi::connect(|client| {
+        let row = client
+            .select(
+                "select s.oid, w.fdwvalidator::regproc::text, w.fdwvalidator <> 0,
+                        has_server_privilege(s.oid, 'USAGE')
+                 from pg_catalog.pg_foreign_server s
+                 join pg_catalog.pg_foreign_data_wrapper w on w.oid = s.srvfdw
+                 where s.srvname = $1",
+                None,
+                Some(vec![(
+                    PgBuiltInOids::TEXTOID.oid(),
+                    server_name.into_datum(),
+                )]),
+            )?
+            .first();
+        if row.is_empty() {
+            error!("foreign server \"{}\" does not exist", server_name);
+        }
+        if row.get::<bool>(4)? != Some(true) {
+            error!("permission denied for foreign server {}", server_name);
+        }
+        let server_oid = row.get::<pg_sys::Oid>(1)?.unwrap_or(pg_sys::InvalidOid);
+        let validator = if row.get::<bool>(3)? == Some(true) {
+            row.get::<String>(2)?
+        } else {
+            None
+        };
+
+        let objects = client
+            .select(
+                "select 'server', s.srvname::text, coalesce(s.srvoptions, '{}'),
+                        'pg_catalog.pg_foreign_server'::regclass::oid
+                 from pg_catalog.pg_foreign_server s
+                 where s.oid = $1
+                 union all
+                 select 'user mapping', u.usename::text, coalesce(u.umoptions, '{}'),
+                        'pg_catalog.pg_user_mapping'::regclass::oid
+                 from pg_catalog.pg_user_mappings u
+                 where u.srvid = $1
+                 union all
+                 select 'foreign table', t.ftrelid::regclass::text, coalesce(t.ftoptions, '{}'),
+                        'pg_catalog.pg_foreign_table'::regclass::oid
+                 from pg_catalog.pg_foreign_table t
+                 where t.ftserver = $1
+                 union all
+                 select 'column', format('%s.%I', a.attrelid::regclass, a.attname),
+                        coalesce(a.attfdwoptions, '{}'),
+                        'pg_catalog.pg_attribute'::regclass::oid
+                 from pg_catalog.pg_foreign_table t
+                 join pg_catalog.pg_attribute a
+                   on a.attrelid = t.ftrelid and a.attnum > 0 and not a.attisdropped
+                 where t.ftserver = $1",
+                None,
+                Some(vec![(PgBuiltInOids::OIDOID.oid(), server_oid.into_datum())]),
+            )?
+            .map(|row| {
+                Ok(OptionsObject {
+                    kind: row.get::<String>(1)?.unwrap_or_default(),
+                    name: row.get::<String>(2)?.unwrap_or_default(),
+                    options: row.get::<Vec<Option<String>>>(3)?.unwrap_or_default(),
+                    catalog: row.get::<pg_sys::Oid>(4)?.unwrap_or(pg_sys::InvalidOid),
+                })
+            })
+            .collect::<pgrx::spi::Result<Vec<_>>>()?;
+        Ok::<_, pgrx::spi::Error>((validator, objects))
+    })
+    .unwrap_or_else(|err| error!("query foreign server failed: {}", err));
+
+    let mut rows: Vec<ValidationRow> = Vec::new();
+    for obj in objects {
+        if let Some(validator) = &validator {
+            if let Err(msg) = in_subtransaction(|| validate_options(validator, &obj)) {
+                rows.push((
+                    obj.kind.clone(),
+                    obj.name.clone(),
+                    None,
+                    "error".to_string(),
+                    msg,
+                    Some("fix".to_string()),
+                    None,
+                ));
+            }
+        }
+
+        // credentials are only in the server and user mapping options
+        if obj.kind != "server" && obj.kind != "user mapping" {
+            continue;
+        }
+        for opt in obj.options.iter().flatten() {
+            let name = opt.split_once('=').map_or(opt.as_str(), |(name, _)| name);
+            if let Some((_, replacement)) = DEPRECATED_OPTIONS.iter().find(|(o, _)| *o == name) {
+                rows.push((
+                    obj.kind.clone(),
+                    obj.name.clone(),
+                    Some(name.to_string()),
+                    "deprecated".to_string(),
+                    format!(
+                        "option {} keeps the credential in plain text, store it in Vault and use option {} instead",
+                        name, replacement
+                    ),
+                    Some("replace".to_string()),
+                    Some(replacement.to_string()),
+                ));
+            }
+        }
+    }
+
+    TableIterator::new(rows)
+}
+
+// call the validator function with the object options, it raises an error if
+// the options are invalid, the function name is already quoted by regproc
+fn validate_options(validator: &str, obj: &OptionsObject) {
+    Spi::run_with_args(
+        &format!("select {}($1, $2)", validator),
+        Some(vec![
+            (
+                PgBuiltInOids::TEXTARRAYOID.oid(),
+                obj.options.clone().into_datum(),
+            ),
+            (PgBuiltInOids::OIDOID.oid(), obj.catalog.into_datum()),
+        ]),
+    )
+    .unwrap_or_else(|err| error!("{}", err));
+}
@@ -0,0 +1,9 @@
+use pgrx::prelude::*;
+use supabase_wrappers::prelude::flush_vault_secrets;
+
+/// Remove the cached Vault secrets in current backend, so the rotated secrets
+/// are read from Vault in the next query
+#[pg_extern]
+fn wrappers_flush_secrets() {
+    flush_vault_secrets();
+}